@@ -0,0 +1,19 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use netlink_rs::socket::parse;
+
+// Attribute streams, descending into every attribute as if it were nested
+fn walk(bytes: &[u8], depth: usize) {
+    for attr in parse::attrs(bytes) {
+        match attr {
+            Ok(a) if depth < 8 => walk(a.payload(), depth + 1),
+            Ok(_) => {},
+            Err(_) => break,
+        }
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    walk(data, 0);
+});
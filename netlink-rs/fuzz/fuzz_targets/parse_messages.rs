@@ -0,0 +1,23 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use netlink_rs::socket::parse;
+
+// Every message of a datagram, decoding error payloads and attribute tails
+fuzz_target!(|data: &[u8]| {
+    for msg in parse::messages(data) {
+        let (hdr, payload) = match msg {
+            Ok(m) => m,
+            Err(_) => break,
+        };
+        assert!(payload.len() + 16 <= hdr.msg_length() as usize);
+        if u16::from(hdr.msg_type()) == 2 {
+            let _ = parse::error(payload);
+        }
+        for attr in parse::attrs(payload) {
+            if attr.is_err() {
+                break;
+            }
+        }
+    }
+});
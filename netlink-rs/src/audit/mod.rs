@@ -0,0 +1,66 @@
+//! Linux audit
+//!
+//! The audit subsystem (`Protocol::Audit`) is programmed with rules telling
+//! which system calls and file accesses to log, and emits the resulting
+//! records to a single registered daemon. Requests carry fixed C structures
+//! rather than attributes, records `key=value` text. Most requests need
+//! CAP_AUDIT_CONTROL in the initial user namespace.
+
+mod rule;
+pub use self::rule::*;
+mod record;
+pub use self::record::*;
+
+use socket::{Socket, Msg, NlMsgHeader, NetlinkAddr, Payload, MsgType, parse};
+
+use std::borrow::Cow;
+use std::io;
+
+const AUDIT_ADD_RULE: u16 = 1011;
+const AUDIT_DEL_RULE: u16 = 1012;
+const AUDIT_LIST_RULES: u16 = 1013;
+
+/// Replies expected to an audit request
+#[derive(Clone, Copy, PartialEq)]
+enum Reply {
+    None,
+    List,
+}
+
+/// Sends an audit request and returns the payloads of its replies of the
+/// same type.
+///
+/// The kernel acks audit requests before replying, and sends lists from
+/// another thread, so unlike `Socket::talk` a list is read up to the
+/// NLMSG_DONE that follows the ack.
+fn exchange(socket: &mut Socket, msg_type: u16, payload: &[u8], reply: Reply) -> io::Result<Vec<Vec<u8>>> {
+    let mut hdr = NlMsgHeader::user_defined(msg_type);
+    hdr.data_length(payload.len() as u32).ack().seq(1);
+    socket.send(Msg::new(hdr, Payload::Data(Cow::Borrowed(payload))), &NetlinkAddr::new(0, 0))?;
+
+    let mut replies = vec![];
+    let mut acked = false;
+    let mut done = false;
+    while !acked || (reply == Reply::List && !done) {
+        let (_, datagram) = socket.recv_bytes()?;
+        for m in parse::messages(datagram) {
+            let (h, payload) = m?;
+            if h.sequence() != 1 {
+                continue;
+            }
+            match h.msg_type() {
+                MsgType::Error => {
+                    let (e, _) = parse::error(payload)?;
+                    if e != 0 {
+                        return Err(io::Error::from_raw_os_error(-e));
+                    }
+                    acked = true;
+                },
+                MsgType::Done => done = true,
+                MsgType::UserDefined(t) if t == msg_type => replies.push(payload.to_vec()),
+                _ => {},
+            }
+        }
+    }
+    Ok(replies)
+}
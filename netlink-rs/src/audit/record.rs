@@ -0,0 +1,353 @@
+use std::collections::BTreeMap;
+use std::io::{self, ErrorKind};
+use std::time::Duration;
+
+/// Type of an audit record, as carried in the netlink message type
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum RecordType {
+    /// System call, with its arguments and the task's credentials
+    Syscall,
+    /// File name used by a system call
+    Path,
+    /// Socket address used by a system call
+    Sockaddr,
+    /// Working directory of a system call
+    Cwd,
+    /// Arguments of an execve
+    Execve,
+    /// End of a multi-record event
+    Eoe,
+    /// Command line of the task
+    Proctitle,
+    /// Rule or configuration change
+    ConfigChange,
+    Other(u16),
+}
+
+impl From<RecordType> for u16 {
+    fn from(t: RecordType) -> u16 {
+        match t {
+            RecordType::Syscall => 1300,
+            RecordType::Path => 1302,
+            RecordType::Sockaddr => 1306,
+            RecordType::Cwd => 1307,
+            RecordType::Execve => 1309,
+            RecordType::Eoe => 1320,
+            RecordType::Proctitle => 1327,
+            RecordType::ConfigChange => 1305,
+            RecordType::Other(i) => i,
+        }
+    }
+}
+
+impl From<u16> for RecordType {
+    fn from(t: u16) -> RecordType {
+        match t {
+            1300 => RecordType::Syscall,
+            1302 => RecordType::Path,
+            1306 => RecordType::Sockaddr,
+            1307 => RecordType::Cwd,
+            1309 => RecordType::Execve,
+            1320 => RecordType::Eoe,
+            1327 => RecordType::Proctitle,
+            1305 => RecordType::ConfigChange,
+            i => RecordType::Other(i),
+        }
+    }
+}
+
+impl RecordType {
+    /// Whether the record belongs to a system call event, which the kernel
+    /// ends with an EOE record. Other records are events on their own.
+    fn is_multi(self) -> bool {
+        let t = u16::from(self);
+        (1300..1400).contains(&t) && self != RecordType::ConfigChange
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, msg.to_string())
+}
+
+fn hex_decode(s: &str) -> Option<Vec<u8>> {
+    if s.is_empty() || !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len()).step_by(2)
+        .map(|i| s.get(i..i + 2).and_then(|b| u8::from_str_radix(b, 16).ok()))
+        .collect()
+}
+
+// RECORD FORMAT
+// audit(<seconds>.<milliseconds>:<serial>): key=value key="string" ...
+/// An audit record, as emitted by the kernel: a type, the event stamp and
+/// `key=value` fields.
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct AuditRecord {
+    record_type: RecordType,
+    time: Duration,
+    serial: u64,
+    fields: Vec<(String, String)>,
+}
+
+impl AuditRecord {
+    /// Decodes the text payload of a record of netlink type `record_type`.
+    pub fn from_bytes(record_type: u16, bytes: &[u8]) -> io::Result<AuditRecord> {
+        let text = String::from_utf8_lossy(bytes);
+        let text = text.trim_end_matches(['\0', '\n']);
+        if !text.starts_with("audit(") {
+            return Err(invalid("audit record without stamp"));
+        }
+        let end = text.find("):").ok_or_else(|| invalid("audit record without stamp"))?;
+        let stamp = &text[6..end];
+        let (time, serial) = {
+            let mut parts = stamp.splitn(2, ':');
+            let time = parts.next().unwrap_or("");
+            let serial = parts.next().and_then(|s| s.parse().ok())
+                .ok_or_else(|| invalid("bad audit serial"))?;
+            let mut time = time.splitn(2, '.');
+            let secs = time.next().and_then(|s| s.parse().ok());
+            let millis = time.next().and_then(|s| s.parse().ok());
+            match (secs, millis) {
+                (Some(s), Some(m)) => (Duration::from_secs(s) + Duration::from_millis(m), serial),
+                _ => return Err(invalid("bad audit timestamp")),
+            }
+        };
+
+        Ok(AuditRecord {
+            record_type: record_type.into(),
+            time,
+            serial,
+            fields: split_fields(&text[end + 2..]),
+        })
+    }
+
+    pub fn record_type(&self) -> RecordType {
+        self.record_type
+    }
+
+    /// Time of the event since the epoch
+    pub fn time(&self) -> Duration {
+        self.time
+    }
+
+    /// Serial number of the event, shared by its records
+    pub fn serial(&self) -> u64 {
+        self.serial
+    }
+
+    /// Fields in order, with values as printed
+    pub fn fields(&self) -> &[(String, String)] {
+        &self.fields
+    }
+
+    /// Value of `key` without its quotes
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.raw(key).map(|v| v.trim_matches(['"', '\'']))
+    }
+
+    /// Value of a string field: quoted values as is, hex encoded ones (used
+    /// for strings with spaces or control characters) decoded. `None` for
+    /// missing and `(null)` values.
+    pub fn decoded(&self, key: &str) -> Option<Vec<u8>> {
+        let v = self.raw(key)?;
+        if v.len() >= 2 && v.starts_with('"') && v.ends_with('"') {
+            Some(v.as_bytes()[1..v.len() - 1].to_vec())
+        } else {
+            hex_decode(v)
+        }
+    }
+
+    /// Value of a numeric field, decimal or hexadecimal (system call
+    /// arguments and `arch`)
+    pub fn number(&self, key: &str) -> Option<u64> {
+        let v = self.get(key)?;
+        let hex = key == "arch" || (key.starts_with('a') && key[1..].parse::<u8>().is_ok());
+        if hex {
+            u64::from_str_radix(v, 16).ok()
+        } else {
+            v.parse().ok()
+        }
+    }
+
+    fn raw(&self, key: &str) -> Option<&str> {
+        self.fields.iter().find(|f| f.0 == key).map(|f| f.1.as_str())
+    }
+}
+
+/// Splits `key=value` fields at spaces outside quotes.
+fn split_fields(text: &str) -> Vec<(String, String)> {
+    let mut fields = vec![];
+    let mut rest = text.trim_start();
+    while !rest.is_empty() {
+        let eq = match rest.find('=') {
+            Some(i) if !rest[..i].contains(' ') => i,
+            _ => {
+                // A word without value
+                let end = rest.find(' ').unwrap_or(rest.len());
+                rest = rest[end..].trim_start();
+                continue;
+            },
+        };
+        let key = &rest[..eq];
+        let value = &rest[eq + 1..];
+        let len = match value.chars().next() {
+            Some(q) if q == '"' || q == '\'' => value[1..].find(q).map(|i| i + 2).unwrap_or_else(|| value.len()),
+            _ => value.find(' ').unwrap_or(value.len()),
+        };
+        fields.push((key.to_string(), value[..len].to_string()));
+        rest = value[len..].trim_start();
+    }
+    fields
+}
+
+/// The records of one event, sharing a serial
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct AuditEvent {
+    serial: u64,
+    time: Duration,
+    records: Vec<AuditRecord>,
+}
+
+impl AuditEvent {
+    pub fn serial(&self) -> u64 {
+        self.serial
+    }
+
+    pub fn time(&self) -> Duration {
+        self.time
+    }
+
+    /// Records in the order received, without the EOE record
+    pub fn records(&self) -> &[AuditRecord] {
+        &self.records
+    }
+
+    /// First record of type `record_type`
+    pub fn record(&self, record_type: RecordType) -> Option<&AuditRecord> {
+        self.records.iter().find(|r| r.record_type == record_type)
+    }
+
+    /// PATH records, in the kernel's `item` order
+    pub fn paths(&self) -> Vec<&AuditRecord> {
+        let mut paths: Vec<_> = self.records.iter().filter(|r| r.record_type == RecordType::Path).collect();
+        paths.sort_by_key(|r| r.number("item"));
+        paths
+    }
+
+    /// Arguments of the EXECVE record
+    pub fn execve_args(&self) -> Option<Vec<Vec<u8>>> {
+        let execve = self.record(RecordType::Execve)?;
+        let argc = execve.number("argc")?;
+        (0..argc).map(|i| execve.decoded(&format!("a{}", i))).collect()
+    }
+}
+
+/// Groups records into events by serial.
+///
+/// System call events end with an EOE record; records of other types are
+/// events on their own.
+#[derive(Default)]
+pub struct EventCollector {
+    pending: BTreeMap<u64, AuditEvent>,
+}
+
+impl EventCollector {
+    pub fn new() -> EventCollector {
+        EventCollector::default()
+    }
+
+    /// Adds a record, returning its event if it is complete.
+    pub fn push(&mut self, record: AuditRecord) -> Option<AuditEvent> {
+        if record.record_type == RecordType::Eoe {
+            return self.pending.remove(&record.serial);
+        }
+        if !record.record_type.is_multi() {
+            return Some(AuditEvent { serial: record.serial, time: record.time, records: vec![record] });
+        }
+        let event = self.pending.entry(record.serial).or_insert_with(|| AuditEvent {
+            serial: record.serial,
+            time: record.time,
+            records: vec![],
+        });
+        event.records.push(record);
+        None
+    }
+
+    /// Number of incomplete events
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Returns the incomplete events, oldest first, e.g. when the stream
+    /// ends.
+    pub fn flush(&mut self) -> Vec<AuditEvent> {
+        let pending = ::std::mem::take(&mut self.pending);
+        pending.into_values().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(record_type: RecordType, text: &str) -> AuditRecord {
+        AuditRecord::from_bytes(record_type.into(), text.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_record() {
+        let r = record(RecordType::Syscall, "audit(1700000000.250:42): arch=c000003e syscall=59 \
+                       success=yes exit=0 a0=55d0 items=2 pid=7 comm=\"ls\" exe=\"/usr/bin/ls\" key=(null)");
+        assert_eq!(r.serial(), 42);
+        assert_eq!(r.time(), Duration::from_millis(1_700_000_000_250));
+        assert_eq!(r.number("arch"), Some(0xc000_003e));
+        assert_eq!(r.number("a0"), Some(0x55d0));
+        assert_eq!(r.number("syscall"), Some(59));
+        assert_eq!(r.get("comm"), Some("ls"));
+        assert_eq!(r.decoded("exe"), Some(b"/usr/bin/ls".to_vec()));
+        assert_eq!(r.decoded("key"), None);
+        assert_eq!(r.fields().len(), 10);
+
+        assert!(AuditRecord::from_bytes(1300, b"arch=c000003e").is_err());
+        assert!(AuditRecord::from_bytes(1300, b"audit(1.2): a=b").is_err());
+
+        let user = record(RecordType::Other(1100), "audit(1.000:3): pid=1 msg='op=login acct=\"root\" res=success'");
+        assert_eq!(user.get("msg"), Some("op=login acct=\"root\" res=success"));
+    }
+
+    #[test]
+    fn test_events() {
+        let mut collector = EventCollector::new();
+        let records = [
+            (RecordType::Syscall, "audit(5.000:9): syscall=59 success=yes"),
+            (RecordType::Execve, "audit(5.000:9): argc=2 a0=\"echo\" a1=6869207468657265"),
+            (RecordType::Path, "audit(5.000:9): item=1 name=\"/lib/ld.so\""),
+            (RecordType::Path, "audit(5.000:9): item=0 name=\"/bin/echo\""),
+            (RecordType::Cwd, "audit(5.000:10): cwd=\"/\""),
+        ];
+        for &(t, text) in &records {
+            assert!(collector.push(record(t, text)).is_none());
+        }
+        assert_eq!(collector.pending(), 2);
+
+        let config = collector.push(record(RecordType::ConfigChange, "audit(5.000:11): op=add_rule res=1"));
+        assert_eq!(config.unwrap().records().len(), 1);
+
+        let event = collector.push(record(RecordType::Eoe, "audit(5.000:9): ")).unwrap();
+        assert_eq!(event.serial(), 9);
+        assert_eq!(event.records().len(), 4);
+        assert_eq!(event.execve_args(), Some(vec![b"echo".to_vec(), b"hi there".to_vec()]));
+        let paths = event.paths();
+        assert_eq!(paths[0].get("name"), Some("/bin/echo"));
+        assert_eq!(event.record(RecordType::Syscall).unwrap().number("syscall"), Some(59));
+
+        let rest = collector.flush();
+        assert_eq!(rest.len(), 1);
+        assert_eq!(rest[0].serial(), 10);
+        assert_eq!(collector.pending(), 0);
+    }
+}
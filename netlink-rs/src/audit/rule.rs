@@ -0,0 +1,454 @@
+use super::{exchange, Reply, AUDIT_ADD_RULE, AUDIT_DEL_RULE, AUDIT_LIST_RULES};
+use socket::Socket;
+
+use std::io::{self, ErrorKind};
+
+const AUDIT_BITMASK_SIZE: usize = 64;
+const AUDIT_MAX_FIELDS: usize = 64;
+const AUDIT_MAX_KEY_LEN: usize = 256;
+const RULE_DATA_LEN: usize = 16 + 4 * (AUDIT_BITMASK_SIZE + 3 * AUDIT_MAX_FIELDS);
+
+/// Filter list a rule is attached to
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum RuleList {
+    /// Messages sent from userspace
+    User,
+    /// Task creation
+    Task,
+    /// System call exit
+    Exit,
+    /// Records to drop
+    Exclude,
+    /// Inode marks of file systems
+    Filesystem,
+}
+
+impl From<RuleList> for u32 {
+    fn from(l: RuleList) -> u32 {
+        match l {
+            RuleList::User => 0,
+            RuleList::Task => 1,
+            RuleList::Exit => 4,
+            RuleList::Exclude => 5,
+            RuleList::Filesystem => 6,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum RuleAction {
+    /// Do not log
+    Never,
+    /// Log
+    Always,
+}
+
+impl From<RuleAction> for u32 {
+    fn from(a: RuleAction) -> u32 {
+        match a {
+            RuleAction::Never => 0,
+            RuleAction::Always => 2,
+        }
+    }
+}
+
+/// Property of the event compared by a rule (AUDIT_* field)
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum RuleField {
+    Pid,
+    Uid,
+    Euid,
+    Gid,
+    Egid,
+    /// Login uid
+    Auid,
+    /// AUDIT_ARCH_* of the system call
+    Arch,
+    /// Record type, for the user and exclude lists
+    MsgType,
+    Ppid,
+    /// Return value of the system call
+    Exit,
+    /// Whether the system call succeeded
+    Success,
+    /// Path of a watched file
+    Watch,
+    /// AUDIT_PERM_* access to a watch
+    Perm,
+    /// Path of a watched directory tree
+    Dir,
+    /// Path of the executable
+    Exe,
+    /// Key attached to the records, for searching
+    FilterKey,
+    Other(u32),
+}
+
+impl From<RuleField> for u32 {
+    fn from(f: RuleField) -> u32 {
+        use self::RuleField::*;
+        match f {
+            Pid => 0,
+            Uid => 1,
+            Euid => 2,
+            Gid => 5,
+            Egid => 6,
+            Auid => 9,
+            Arch => 11,
+            MsgType => 12,
+            Ppid => 18,
+            Exit => 103,
+            Success => 104,
+            Watch => 105,
+            Perm => 106,
+            Dir => 107,
+            Exe => 112,
+            FilterKey => 210,
+            Other(i) => i,
+        }
+    }
+}
+
+impl From<u32> for RuleField {
+    fn from(f: u32) -> RuleField {
+        use self::RuleField::*;
+        match f {
+            0 => Pid,
+            1 => Uid,
+            2 => Euid,
+            5 => Gid,
+            6 => Egid,
+            9 => Auid,
+            11 => Arch,
+            12 => MsgType,
+            18 => Ppid,
+            103 => Exit,
+            104 => Success,
+            105 => Watch,
+            106 => Perm,
+            107 => Dir,
+            112 => Exe,
+            210 => FilterKey,
+            i => Other(i),
+        }
+    }
+}
+
+impl RuleField {
+    /// Whether the kernel compares the field as a string, passed in the
+    /// rule's buffer: paths, the key and the SELinux contexts
+    fn is_string(self) -> bool {
+        matches!(u32::from(self), 13..=17 | 19..=23 | 105 | 107 | 112 | 210)
+    }
+}
+
+/// Comparison of a field with its value
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum RuleOp {
+    Equal,
+    NotEqual,
+    LessThan,
+    LessThanOrEqual,
+    GreaterThan,
+    GreaterThanOrEqual,
+    /// Any of the value's bits set
+    BitMask,
+    /// All of the value's bits set
+    BitTest,
+}
+
+impl From<RuleOp> for u32 {
+    fn from(op: RuleOp) -> u32 {
+        use self::RuleOp::*;
+        match op {
+            BitMask => 0x0800_0000,
+            LessThan => 0x1000_0000,
+            GreaterThan => 0x2000_0000,
+            NotEqual => 0x3000_0000,
+            Equal => 0x4000_0000,
+            BitTest => 0x4800_0000,
+            LessThanOrEqual => 0x5000_0000,
+            GreaterThanOrEqual => 0x6000_0000,
+        }
+    }
+}
+
+impl RuleOp {
+    fn from_u32(op: u32) -> io::Result<RuleOp> {
+        use self::RuleOp::*;
+        [BitMask, LessThan, GreaterThan, NotEqual, Equal, BitTest, LessThanOrEqual, GreaterThanOrEqual]
+            .iter()
+            .cloned()
+            .find(|&o| u32::from(o) == op)
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "unknown audit rule operator"))
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum RuleValue {
+    Num(u32),
+    Str(String),
+}
+
+// HEADER FORMAT
+// __u32 flags;       /* AUDIT_PER_{TASK,CALL}, AUDIT_PREPEND */
+// __u32 action;      /* AUDIT_NEVER, AUDIT_POSSIBLE, AUDIT_ALWAYS */
+// __u32 field_count;
+// __u32 mask[AUDIT_BITMASK_SIZE]; /* syscall(s) affected */
+// __u32 fields[AUDIT_MAX_FIELDS];
+// __u32 values[AUDIT_MAX_FIELDS];
+// __u32 fieldflags[AUDIT_MAX_FIELDS];
+// __u32 buflen;      /* total length of string fields */
+// char buf[0];       /* string fields buffer */
+/// An audit rule (`struct audit_rule_data`)
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct AuditRule {
+    list: RuleList,
+    action: RuleAction,
+    #[cfg_attr(feature = "serde", serde(with = "serde_mask"))]
+    mask: [u32; AUDIT_BITMASK_SIZE],
+    fields: Vec<(RuleField, RuleOp, RuleValue)>,
+}
+
+impl AuditRule {
+    /// A rule matching no system call and no field yet
+    pub fn new(list: RuleList, action: RuleAction) -> AuditRule {
+        AuditRule {
+            list,
+            action,
+            mask: [0; AUDIT_BITMASK_SIZE],
+            fields: vec![],
+        }
+    }
+
+    /// Matches system call number `nr` of the rule's architecture
+    pub fn syscall(&mut self, nr: u32) -> &mut AuditRule {
+        let i = nr as usize / 32;
+        if i < AUDIT_BITMASK_SIZE {
+            self.mask[i] |= 1 << (nr % 32);
+        }
+        self
+    }
+
+    /// Matches every system call
+    pub fn all_syscalls(&mut self) -> &mut AuditRule {
+        self.mask = [!0; AUDIT_BITMASK_SIZE];
+        self
+    }
+
+    /// Compares a numeric field
+    pub fn field(&mut self, field: RuleField, op: RuleOp, value: u32) -> &mut AuditRule {
+        self.fields.push((field, op, RuleValue::Num(value)));
+        self
+    }
+
+    /// Compares a string field such as `Watch` or `FilterKey`
+    pub fn string_field(&mut self, field: RuleField, op: RuleOp, value: &str) -> &mut AuditRule {
+        self.fields.push((field, op, RuleValue::Str(value.into())));
+        self
+    }
+
+    /// Tags the records of the rule with `key`
+    pub fn key(&mut self, key: &str) -> &mut AuditRule {
+        self.string_field(RuleField::FilterKey, RuleOp::Equal, key)
+    }
+
+    pub fn list(&self) -> RuleList {
+        self.list
+    }
+
+    pub fn action(&self) -> RuleAction {
+        self.action
+    }
+
+    pub fn has_syscall(&self, nr: u32) -> bool {
+        let i = nr as usize / 32;
+        i < AUDIT_BITMASK_SIZE && self.mask[i] & 1 << (nr % 32) != 0
+    }
+
+    pub fn fields(&self) -> &[(RuleField, RuleOp, RuleValue)] {
+        &self.fields
+    }
+
+    fn bytes(&self) -> io::Result<Vec<u8>> {
+        if self.fields.len() > AUDIT_MAX_FIELDS {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "too many audit rule fields"));
+        }
+        let mut fields = [0u32; AUDIT_MAX_FIELDS];
+        let mut values = [0u32; AUDIT_MAX_FIELDS];
+        let mut ops = [0u32; AUDIT_MAX_FIELDS];
+        let mut buf = vec![];
+        for (i, &(field, op, ref value)) in self.fields.iter().enumerate() {
+            fields[i] = field.into();
+            ops[i] = op.into();
+            values[i] = match *value {
+                RuleValue::Num(n) => n,
+                RuleValue::Str(ref s) => {
+                    if s.len() > AUDIT_MAX_KEY_LEN && field == RuleField::FilterKey {
+                        return Err(io::Error::new(ErrorKind::InvalidInput, "audit key too long"));
+                    }
+                    buf.extend_from_slice(s.as_bytes());
+                    s.len() as u32
+                },
+            };
+        }
+
+        let mut bytes = Vec::with_capacity(RULE_DATA_LEN + buf.len());
+        let header = [self.list.into(), self.action.into(), self.fields.len() as u32];
+        let words = header.iter().chain(self.mask.iter()).chain(fields.iter())
+            .chain(values.iter()).chain(ops.iter());
+        for w in words {
+            bytes.extend_from_slice(&w.to_ne_bytes());
+        }
+        bytes.extend_from_slice(&(buf.len() as u32).to_ne_bytes());
+        bytes.extend(buf);
+        Ok(bytes)
+    }
+
+    fn from_bytes(bytes: &[u8]) -> io::Result<AuditRule> {
+        if bytes.len() < RULE_DATA_LEN {
+            return Err(io::Error::new(ErrorKind::InvalidData, "audit rule too short"));
+        }
+        let word = |i: usize| {
+            let n = 4 * i;
+            u32::from_ne_bytes([bytes[n], bytes[n + 1], bytes[n + 2], bytes[n + 3]])
+        };
+        let list = match word(0) & 0x7 {
+            0 => RuleList::User,
+            1 => RuleList::Task,
+            4 => RuleList::Exit,
+            5 => RuleList::Exclude,
+            6 => RuleList::Filesystem,
+            _ => return Err(io::Error::new(ErrorKind::InvalidData, "unknown audit filter list")),
+        };
+        let action = match word(1) {
+            0 => RuleAction::Never,
+            _ => RuleAction::Always,
+        };
+        let count = (word(2) as usize).min(AUDIT_MAX_FIELDS);
+        let mut mask = [0; AUDIT_BITMASK_SIZE];
+        for (i, m) in mask.iter_mut().enumerate() {
+            *m = word(3 + i);
+        }
+
+        let fields_at = 3 + AUDIT_BITMASK_SIZE;
+        let values_at = fields_at + AUDIT_MAX_FIELDS;
+        let ops_at = values_at + AUDIT_MAX_FIELDS;
+        let buf = &bytes[RULE_DATA_LEN..];
+        let mut offset = 0;
+        let mut fields = Vec::with_capacity(count);
+        for i in 0..count {
+            let field = RuleField::from(word(fields_at + i));
+            let op = RuleOp::from_u32(word(ops_at + i))?;
+            let value = word(values_at + i);
+            let value = if field.is_string() {
+                let end = offset + value as usize;
+                if end > buf.len() {
+                    return Err(io::Error::new(ErrorKind::InvalidData, "audit rule string out of bounds"));
+                }
+                let s = String::from_utf8_lossy(&buf[offset..end]).into_owned();
+                offset = end;
+                RuleValue::Str(s)
+            } else {
+                RuleValue::Num(value)
+            };
+            fields.push((field, op, value));
+        }
+        Ok(AuditRule { list, action, mask, fields })
+    }
+}
+
+/// Adds `rule` at the end of its list.
+pub fn add_rule(socket: &mut Socket, rule: &AuditRule) -> io::Result<()> {
+    exchange(socket, AUDIT_ADD_RULE, &rule.bytes()?, Reply::None)?;
+    Ok(())
+}
+
+/// Deletes the rule equal to `rule`.
+pub fn delete_rule(socket: &mut Socket, rule: &AuditRule) -> io::Result<()> {
+    exchange(socket, AUDIT_DEL_RULE, &rule.bytes()?, Reply::None)?;
+    Ok(())
+}
+
+/// Returns the rules of all lists.
+pub fn rules(socket: &mut Socket) -> io::Result<Vec<AuditRule>> {
+    let replies = exchange(socket, AUDIT_LIST_RULES, &[], Reply::List)?;
+    replies.iter().map(|r| AuditRule::from_bytes(r)).collect()
+}
+
+// serde only derives arrays of up to 32 elements
+#[cfg(feature = "serde")]
+mod serde_mask {
+    use super::AUDIT_BITMASK_SIZE;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+    use serde::de::Error;
+
+    pub fn serialize<S: Serializer>(mask: &[u32; AUDIT_BITMASK_SIZE], s: S) -> Result<S::Ok, S::Error> {
+        s.collect_seq(mask.iter())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<[u32; AUDIT_BITMASK_SIZE], D::Error> {
+        let words = Vec::<u32>::deserialize(d)?;
+        let mut mask = [0; AUDIT_BITMASK_SIZE];
+        if words.len() != mask.len() {
+            return Err(D::Error::invalid_length(words.len(), &"64 mask words"));
+        }
+        mask.copy_from_slice(&words);
+        Ok(mask)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Protocol;
+
+    fn test_rule() -> AuditRule {
+        let mut rule = AuditRule::new(RuleList::Exit, RuleAction::Always);
+        // getpid on x86_64, by a pid that does not exist
+        rule.syscall(39)
+            .field(RuleField::Pid, RuleOp::Equal, 0x3fff_fff0)
+            .key("nlrs-test");
+        rule
+    }
+
+    #[test]
+    fn test_rule_encoding() {
+        let rule = test_rule();
+        let bytes = rule.bytes().unwrap();
+        assert_eq!(bytes.len(), RULE_DATA_LEN + 9);
+        assert_eq!(&bytes[..4], &4u32.to_ne_bytes());
+        assert_eq!(&bytes[8..12], &2u32.to_ne_bytes());
+        assert_eq!(&bytes[RULE_DATA_LEN..], b"nlrs-test");
+
+        let decoded = AuditRule::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded, rule);
+        assert!(decoded.has_syscall(39) && !decoded.has_syscall(40));
+        assert_eq!(decoded.fields()[1].2, RuleValue::Str("nlrs-test".into()));
+        assert!(AuditRule::from_bytes(&bytes[..RULE_DATA_LEN + 4]).is_err());
+    }
+
+    #[test]
+    fn test_rules() {
+        let mut socket = Socket::new(Protocol::Audit).unwrap();
+        let rule = test_rule();
+        match add_rule(&mut socket, &rule) {
+            Ok(()) => {},
+            Err(ref e) if e.raw_os_error() == Some(::libc::EPERM) => return,
+            Err(ref e) if e.raw_os_error() == Some(::libc::ECONNREFUSED) => return,
+            Err(e) => panic!("{}", e),
+        }
+        let listed = rules(&mut socket);
+        delete_rule(&mut socket, &rule).unwrap();
+        assert!(listed.unwrap().contains(&rule));
+        assert!(!rules(&mut socket).unwrap().contains(&rule));
+        assert_eq!(delete_rule(&mut socket, &rule).unwrap_err().raw_os_error(), Some(::libc::ENOENT));
+    }
+}
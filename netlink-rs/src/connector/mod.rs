@@ -0,0 +1,227 @@
+//! Kernel connector
+//!
+//! The connector carries small messages between userspace and kernel
+//! clients such as the proc connector, w1 or device mapper events, each
+//! addressed by a `CbId`. Every message is a `cn_msg` envelope inside an
+//! NLMSG_DONE netlink message; clients subscribe to the multicast group
+//! numbered after the `idx` of their id.
+
+use socket::{Socket, NetlinkTransport, Msg, NlMsgHeader, NetlinkAddr, Payload, MsgType, parse};
+use Protocol;
+
+use std::borrow::Cow;
+use std::io::{self, ErrorKind};
+
+/// Id of the proc connector (process events)
+pub const CN_IDX_PROC: u32 = 1;
+pub const CN_VAL_PROC: u32 = 1;
+
+const CN_MSG_LEN: usize = 20;
+
+// HEADER FORMAT
+// __u32 idx;
+// __u32 val;
+/// Address of a connector client (`struct cb_id`)
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct CbId {
+    idx: u32,
+    val: u32,
+}
+
+impl CbId {
+    pub fn new(idx: u32, val: u32) -> CbId {
+        CbId { idx, val }
+    }
+
+    pub fn idx(&self) -> u32 {
+        self.idx
+    }
+
+    pub fn val(&self) -> u32 {
+        self.val
+    }
+}
+
+// HEADER FORMAT
+// struct cb_id id;
+// __u32 seq;
+// __u32 ack;
+// __u16 len;  /* Length of the following data */
+// __u16 flags;
+// __u8 data[0];
+/// A connector message (`struct cn_msg`)
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct CnMsg<'a> {
+    id: CbId,
+    seq: u32,
+    ack: u32,
+    flags: u16,
+    data: &'a [u8],
+}
+
+impl<'a> CnMsg<'a> {
+    pub fn new(id: CbId, data: &'a [u8]) -> CnMsg<'a> {
+        CnMsg { id, seq: 0, ack: 0, flags: 0, data }
+    }
+
+    /// Set sequence number
+    pub fn seq(&mut self, seq: u32) -> &mut CnMsg<'a> {
+        self.seq = seq;
+        self
+    }
+
+    /// Set acknowledgement number, by convention the sequence number of the
+    /// message answered plus one
+    pub fn set_ack(&mut self, ack: u32) -> &mut CnMsg<'a> {
+        self.ack = ack;
+        self
+    }
+
+    pub fn set_flags(&mut self, flags: u16) -> &mut CnMsg<'a> {
+        self.flags = flags;
+        self
+    }
+
+    pub fn id(&self) -> CbId {
+        self.id
+    }
+
+    pub fn sequence(&self) -> u32 {
+        self.seq
+    }
+
+    pub fn ack(&self) -> u32 {
+        self.ack
+    }
+
+    pub fn flags(&self) -> u16 {
+        self.flags
+    }
+
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn from_bytes(bytes: &'a [u8]) -> io::Result<CnMsg<'a>> {
+        if bytes.len() < CN_MSG_LEN {
+            return Err(io::Error::new(ErrorKind::InvalidData, "cn_msg too short"));
+        }
+        let u32_at = |i: usize| u32::from_ne_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+        let len = u16::from_ne_bytes([bytes[16], bytes[17]]) as usize;
+        if CN_MSG_LEN + len > bytes.len() {
+            return Err(io::Error::new(ErrorKind::InvalidData, "cn_msg length too large"));
+        }
+        Ok(CnMsg {
+            id: CbId::new(u32_at(0), u32_at(4)),
+            seq: u32_at(8),
+            ack: u32_at(12),
+            flags: u16::from_ne_bytes([bytes[18], bytes[19]]),
+            data: &bytes[CN_MSG_LEN..CN_MSG_LEN + len],
+        })
+    }
+
+    pub fn bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(CN_MSG_LEN + self.data.len());
+        bytes.extend_from_slice(&self.id.idx.to_ne_bytes());
+        bytes.extend_from_slice(&self.id.val.to_ne_bytes());
+        bytes.extend_from_slice(&self.seq.to_ne_bytes());
+        bytes.extend_from_slice(&self.ack.to_ne_bytes());
+        bytes.extend_from_slice(&(self.data.len() as u16).to_ne_bytes());
+        bytes.extend_from_slice(&self.flags.to_ne_bytes());
+        bytes.extend_from_slice(self.data);
+        bytes
+    }
+}
+
+/// A connector socket
+pub struct Connector<T: NetlinkTransport = Socket> {
+    socket: T,
+}
+
+impl Connector {
+    /// Subscribes to the clients whose `idx` is in `groups`, a bit mask with
+    /// bit `idx - 1` per client; usually requires CAP_NET_ADMIN.
+    pub fn new(groups: u32) -> io::Result<Connector> {
+        let socket = Socket::new(Protocol::Connector)?;
+        socket.bind(NetlinkAddr::new(0, groups))?;
+        Ok(Connector::from_transport(socket))
+    }
+}
+
+impl<T: NetlinkTransport> Connector<T> {
+    /// Uses `transport`, e.g. a bound `Protocol::Connector` socket.
+    pub fn from_transport(transport: T) -> Connector<T> {
+        Connector { socket: transport }
+    }
+
+    /// Sends `msg` to the kernel.
+    pub fn send(&mut self, msg: &CnMsg) -> io::Result<()> {
+        self.send_to(msg, &NetlinkAddr::new(0, 0))
+    }
+
+    /// Sends `msg` to the port `addr`.
+    pub fn send_to(&mut self, msg: &CnMsg, addr: &NetlinkAddr) -> io::Result<()> {
+        let payload = msg.bytes();
+        let mut hdr = NlMsgHeader::done();
+        hdr.set_flags(0).data_length(payload.len() as u32).seq(msg.seq);
+        self.socket.send(Msg::new(hdr, Payload::Data(Cow::Borrowed(&payload))), addr)?;
+        Ok(())
+    }
+
+    /// Blocks until messages arrive and returns those read at once.
+    pub fn recv(&mut self) -> io::Result<Vec<CnMsg<'_>>> {
+        let (_, datagram) = self.socket.recv_bytes()?;
+        let mut msgs = vec![];
+        for m in parse::messages(datagram) {
+            let (hdr, payload) = m?;
+            match hdr.msg_type() {
+                MsgType::Done => msgs.push(CnMsg::from_bytes(payload)?),
+                MsgType::Error => {
+                    let (e, _) = parse::error(payload)?;
+                    if e != 0 {
+                        return Err(io::Error::from_raw_os_error(-e));
+                    }
+                },
+                _ => {},
+            }
+        }
+        Ok(msgs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cn_msg() {
+        let mut msg = CnMsg::new(CbId::new(CN_IDX_PROC, CN_VAL_PROC), &[1, 0, 0, 0]);
+        msg.seq(7).set_ack(3);
+        assert_eq!(msg.ack(), 3);
+        let bytes = msg.bytes();
+        assert_eq!(bytes.len(), 24);
+        assert_eq!(&bytes[16..18], &4u16.to_ne_bytes());
+        assert_eq!(CnMsg::from_bytes(&bytes).unwrap(), msg);
+
+        assert!(CnMsg::from_bytes(&bytes[..20 + 3]).is_err());
+        assert!(CnMsg::from_bytes(&bytes[..19]).is_err());
+    }
+
+    #[test]
+    fn test_connector_loopback() {
+        let recv = Socket::new(Protocol::Connector).unwrap();
+        recv.bind(NetlinkAddr::new(113, 0)).unwrap();
+        let mut recv = Connector::from_transport(recv);
+        let send = Socket::new(Protocol::Connector).unwrap();
+        send.bind(NetlinkAddr::new(114, 0)).unwrap();
+        let mut send = Connector::from_transport(send);
+
+        let mut msg = CnMsg::new(CbId::new(10, 2), b"dm event");
+        msg.seq(5);
+        send.send_to(&msg, &NetlinkAddr::new(113, 0)).unwrap();
+        let msgs = recv.recv().unwrap();
+        assert_eq!(msgs, vec![msg]);
+    }
+}
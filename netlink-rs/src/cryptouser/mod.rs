@@ -0,0 +1,404 @@
+//! Kernel crypto API (crypto_user)
+//!
+//! `Protocol::Crypto` reports the algorithm implementations registered with
+//! the kernel crypto API, as listed in /proc/crypto: each reply carries a
+//! `struct crypto_user_alg` followed by the priority and a report specific to
+//! the type of the algorithm. Requests need CAP_NET_ADMIN, and the socket
+//! exists only with CONFIG_CRYPTO_USER.
+
+use socket::{NetlinkTransport, NlMsgHeader, NlAttr, Msg, Payload, c_string};
+
+use std::borrow::Cow;
+use std::io::{self, ErrorKind};
+
+const CRYPTO_MSG_GETALG: u16 = 0x13;
+
+const CRYPTO_MAX_NAME: usize = 64;
+const CRYPTO_USER_ALG_LEN: usize = 3 * CRYPTO_MAX_NAME + 16;
+
+const CRYPTOCFGA_PRIORITY_VAL: u16 = 1;
+const CRYPTOCFGA_REPORT_LARVAL: u16 = 2;
+const CRYPTOCFGA_REPORT_HASH: u16 = 3;
+const CRYPTOCFGA_REPORT_BLKCIPHER: u16 = 4;
+const CRYPTOCFGA_REPORT_AEAD: u16 = 5;
+const CRYPTOCFGA_REPORT_COMPRESS: u16 = 6;
+const CRYPTOCFGA_REPORT_RNG: u16 = 7;
+const CRYPTOCFGA_REPORT_CIPHER: u16 = 8;
+const CRYPTOCFGA_REPORT_AKCIPHER: u16 = 9;
+const CRYPTOCFGA_REPORT_KPP: u16 = 10;
+const CRYPTOCFGA_REPORT_ACOMP: u16 = 11;
+const CRYPTOCFGA_STAT_HASH: u16 = 13;
+const CRYPTOCFGA_STAT_AEAD: u16 = 15;
+const CRYPTOCFGA_STAT_CIPHER: u16 = 18;
+
+/// The algorithm passed its self tests (CRYPTO_ALG_TESTED)
+pub const CRYPTO_ALG_TESTED: u32 = 0x400;
+/// Only usable through another algorithm (CRYPTO_ALG_INTERNAL)
+pub const CRYPTO_ALG_INTERNAL: u32 = 0x2000;
+
+/// Reads the `unsigned int` fields of a report after its type name(s).
+fn u32s(bytes: &[u8], names: usize, count: usize) -> io::Result<Vec<u32>> {
+    let start = names * CRYPTO_MAX_NAME;
+    if bytes.len() < start + 4 * count {
+        return Err(io::Error::new(ErrorKind::InvalidData, "crypto report too short"));
+    }
+    Ok(bytes[start..start + 4 * count].chunks(4)
+        .map(|c| u32::from_ne_bytes([c[0], c[1], c[2], c[3]]))
+        .collect())
+}
+
+fn u64s(bytes: &[u8], count: usize) -> io::Result<Vec<u64>> {
+    let start = CRYPTO_MAX_NAME;
+    if bytes.len() < start + 8 * count {
+        return Err(io::Error::new(ErrorKind::InvalidData, "crypto statistics too short"));
+    }
+    Ok(bytes[start..start + 8 * count].chunks(8)
+        .map(|c| u64::from_ne_bytes([c[0], c[1], c[2], c[3], c[4], c[5], c[6], c[7]]))
+        .collect())
+}
+
+/// Properties specific to the type of an algorithm (`struct
+/// crypto_report_*`)
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum AlgReport {
+    /// Placeholder of an algorithm being instantiated
+    Larval,
+    Hash {
+        block_size: u32,
+        digest_size: u32,
+    },
+    /// Single block cipher
+    Cipher {
+        block_size: u32,
+        min_key_size: u32,
+        max_key_size: u32,
+    },
+    /// Symmetric cipher over a mode of operation
+    Skcipher {
+        /// IV generator, "<none>" or "<default>" for most
+        geniv: String,
+        block_size: u32,
+        min_key_size: u32,
+        max_key_size: u32,
+        iv_size: u32,
+    },
+    /// Authenticated encryption
+    Aead {
+        geniv: String,
+        block_size: u32,
+        max_auth_size: u32,
+        iv_size: u32,
+    },
+    Rng {
+        seed_size: u32,
+    },
+    Compress,
+    Acomp,
+    Akcipher,
+    /// Key agreement
+    Kpp,
+}
+
+impl AlgReport {
+    fn from_attr(attr: &NlAttr) -> io::Result<Option<AlgReport>> {
+        let p = attr.payload();
+        let report = match attr.attr_type() {
+            CRYPTOCFGA_REPORT_LARVAL => AlgReport::Larval,
+            CRYPTOCFGA_REPORT_HASH => {
+                let v = u32s(p, 1, 2)?;
+                AlgReport::Hash { block_size: v[0], digest_size: v[1] }
+            },
+            CRYPTOCFGA_REPORT_CIPHER => {
+                let v = u32s(p, 1, 3)?;
+                AlgReport::Cipher { block_size: v[0], min_key_size: v[1], max_key_size: v[2] }
+            },
+            CRYPTOCFGA_REPORT_BLKCIPHER => {
+                let v = u32s(p, 2, 4)?;
+                AlgReport::Skcipher {
+                    geniv: c_string(&p[CRYPTO_MAX_NAME..2 * CRYPTO_MAX_NAME]),
+                    block_size: v[0],
+                    min_key_size: v[1],
+                    max_key_size: v[2],
+                    iv_size: v[3],
+                }
+            },
+            CRYPTOCFGA_REPORT_AEAD => {
+                let v = u32s(p, 2, 3)?;
+                AlgReport::Aead {
+                    geniv: c_string(&p[CRYPTO_MAX_NAME..2 * CRYPTO_MAX_NAME]),
+                    block_size: v[0],
+                    max_auth_size: v[1],
+                    iv_size: v[2],
+                }
+            },
+            CRYPTOCFGA_REPORT_RNG => AlgReport::Rng { seed_size: u32s(p, 1, 1)?[0] },
+            CRYPTOCFGA_REPORT_COMPRESS => AlgReport::Compress,
+            CRYPTOCFGA_REPORT_ACOMP => AlgReport::Acomp,
+            CRYPTOCFGA_REPORT_AKCIPHER => AlgReport::Akcipher,
+            CRYPTOCFGA_REPORT_KPP => AlgReport::Kpp,
+            _ => return Ok(None),
+        };
+        Ok(Some(report))
+    }
+}
+
+/// Usage counters of an algorithm (`struct crypto_stat_*`), reported by
+/// kernels before 6.7 built with CONFIG_CRYPTO_STATS
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum AlgStats {
+    Hash {
+        hashes: u64,
+        hashed_bytes: u64,
+        errors: u64,
+    },
+    /// Counters of ciphers and, with `aead`, of AEAD algorithms
+    Cipher {
+        aead: bool,
+        encryptions: u64,
+        encrypted_bytes: u64,
+        decryptions: u64,
+        decrypted_bytes: u64,
+        errors: u64,
+    },
+}
+
+impl AlgStats {
+    fn from_attr(attr: &NlAttr) -> io::Result<Option<AlgStats>> {
+        let p = attr.payload();
+        let stats = match attr.attr_type() {
+            CRYPTOCFGA_STAT_HASH => {
+                let v = u64s(p, 3)?;
+                AlgStats::Hash { hashes: v[0], hashed_bytes: v[1], errors: v[2] }
+            },
+            t @ CRYPTOCFGA_STAT_AEAD | t @ CRYPTOCFGA_STAT_CIPHER => {
+                let v = u64s(p, 5)?;
+                AlgStats::Cipher {
+                    aead: t == CRYPTOCFGA_STAT_AEAD,
+                    encryptions: v[0],
+                    encrypted_bytes: v[1],
+                    decryptions: v[2],
+                    decrypted_bytes: v[3],
+                    errors: v[4],
+                }
+            },
+            _ => return Ok(None),
+        };
+        Ok(Some(stats))
+    }
+}
+
+// HEADER FORMAT
+// char cru_name[CRYPTO_MAX_NAME];
+// char cru_driver_name[CRYPTO_MAX_NAME];
+// char cru_module_name[CRYPTO_MAX_NAME];
+// __u32 cru_type;
+// __u32 cru_mask;
+// __u32 cru_refcnt;
+// __u32 cru_flags;
+/// A registered algorithm implementation
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct CryptoAlg {
+    name: String,
+    driver: String,
+    module: String,
+    refcnt: u32,
+    flags: u32,
+    priority: Option<u32>,
+    report_type: Option<String>,
+    report: Option<AlgReport>,
+    stats: Option<AlgStats>,
+}
+
+impl CryptoAlg {
+    fn from_bytes(bytes: &[u8]) -> io::Result<CryptoAlg> {
+        if bytes.len() < CRYPTO_USER_ALG_LEN {
+            return Err(io::Error::new(ErrorKind::InvalidData, "crypto_user_alg too short"));
+        }
+        let u32_at = |i| u32::from_ne_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+        let names = 3 * CRYPTO_MAX_NAME;
+        let mut alg = CryptoAlg {
+            name: c_string(&bytes[..CRYPTO_MAX_NAME]),
+            driver: c_string(&bytes[CRYPTO_MAX_NAME..2 * CRYPTO_MAX_NAME]),
+            module: c_string(&bytes[2 * CRYPTO_MAX_NAME..names]),
+            refcnt: u32_at(names + 8),
+            flags: u32_at(names + 12),
+            priority: None,
+            report_type: None,
+            report: None,
+            stats: None,
+        };
+        for attr in NlAttr::parse(&bytes[CRYPTO_USER_ALG_LEN..])? {
+            let p = attr.payload();
+            if attr.attr_type() == CRYPTOCFGA_PRIORITY_VAL && p.len() >= 4 {
+                alg.priority = Some(u32::from_ne_bytes([p[0], p[1], p[2], p[3]]));
+            } else if let Some(report) = AlgReport::from_attr(&attr)? {
+                alg.report_type = Some(c_string(&p[..p.len().min(CRYPTO_MAX_NAME)]));
+                alg.report = Some(report);
+            } else if let Some(stats) = AlgStats::from_attr(&attr)? {
+                alg.stats = Some(stats);
+            }
+        }
+        Ok(alg)
+    }
+
+    /// Generic name, e.g. "sha256"
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Name of the implementation, e.g. "sha256-avx2"
+    pub fn driver(&self) -> &str {
+        &self.driver
+    }
+
+    /// Module providing the implementation, "kernel" for built-ins
+    pub fn module(&self) -> &str {
+        &self.module
+    }
+
+    pub fn refcnt(&self) -> u32 {
+        self.refcnt
+    }
+
+    /// CRYPTO_ALG_* flags
+    pub fn flags(&self) -> u32 {
+        self.flags
+    }
+
+    /// Implementations of a name with higher priority are preferred
+    pub fn priority(&self) -> Option<u32> {
+        self.priority
+    }
+
+    /// Type as named in /proc/crypto, e.g. "shash" or "skcipher"
+    pub fn report_type(&self) -> Option<&str> {
+        self.report_type.as_deref()
+    }
+
+    pub fn report(&self) -> Option<&AlgReport> {
+        self.report.as_ref()
+    }
+
+    pub fn stats(&self) -> Option<AlgStats> {
+        self.stats
+    }
+}
+
+/// Encodes a `struct crypto_user_alg` selecting `driver`.
+fn request(driver: &str) -> io::Result<Vec<u8>> {
+    if driver.len() >= CRYPTO_MAX_NAME {
+        return Err(io::Error::new(ErrorKind::InvalidInput, "crypto driver name too long"));
+    }
+    let mut bytes = vec![0; CRYPTO_USER_ALG_LEN];
+    bytes[CRYPTO_MAX_NAME..CRYPTO_MAX_NAME + driver.len()].copy_from_slice(driver.as_bytes());
+    Ok(bytes)
+}
+
+fn exchange(socket: &mut impl NetlinkTransport, mut hdr: NlMsgHeader, payload: &[u8]) -> io::Result<Vec<Vec<u8>>> {
+    hdr.data_length(payload.len() as u32);
+    socket.talk(Msg::new(hdr, Payload::Data(Cow::Borrowed(payload))))
+}
+
+/// Dumps the registered algorithms.
+pub fn algorithms(socket: &mut impl NetlinkTransport) -> io::Result<Vec<CryptoAlg>> {
+    let mut hdr = NlMsgHeader::user_defined(CRYPTO_MSG_GETALG);
+    hdr.dump();
+    let replies = exchange(socket, hdr, &request("")?)?;
+    replies.iter().map(|r| CryptoAlg::from_bytes(r)).collect()
+}
+
+/// Looks up the implementation named `driver`, failing with ENOENT if none
+/// is registered.
+pub fn algorithm(socket: &mut impl NetlinkTransport, driver: &str) -> io::Result<CryptoAlg> {
+    let hdr = NlMsgHeader::user_defined(CRYPTO_MSG_GETALG);
+    let replies = exchange(socket, hdr, &request(driver)?)?;
+    match replies.first() {
+        Some(r) => CryptoAlg::from_bytes(r),
+        None => Err(io::Error::new(ErrorKind::InvalidData, "no crypto_user_alg in reply")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket::{Socket, ReplayTransport};
+    use Protocol;
+
+    fn name(s: &str, len: usize) -> Vec<u8> {
+        let mut bytes = s.as_bytes().to_vec();
+        bytes.resize(len, 0);
+        bytes
+    }
+
+    fn reply(driver: &str, attrs: &[u8]) -> Vec<u8> {
+        let mut payload = name("sha256", 64);
+        payload.extend(name(driver, 64));
+        payload.extend(name("kernel", 64));
+        for &v in &[0u32, 0, 2, CRYPTO_ALG_TESTED] {
+            payload.extend_from_slice(&v.to_ne_bytes());
+        }
+        payload.extend_from_slice(attrs);
+
+        let mut hdr = NlMsgHeader::user_defined(CRYPTO_MSG_GETALG);
+        hdr.data_length(payload.len() as u32).seq(1);
+        let mut bytes = hdr.bytes().to_vec();
+        bytes.extend(payload);
+        bytes
+    }
+
+    #[test]
+    fn test_replay_algorithms() {
+        let mut hash = name("shash", 64);
+        hash.extend_from_slice(&64u32.to_ne_bytes());
+        hash.extend_from_slice(&32u32.to_ne_bytes());
+        let mut attrs = NlAttr::new(CRYPTOCFGA_PRIORITY_VAL, &100u32.to_ne_bytes()).bytes();
+        attrs.extend(NlAttr::new(CRYPTOCFGA_REPORT_HASH, &hash).bytes());
+        let mut stats = name("hash", 64);
+        for v in &[3u64, 96, 0] {
+            stats.extend_from_slice(&v.to_ne_bytes());
+        }
+        attrs.extend(NlAttr::new(CRYPTOCFGA_STAT_HASH, &stats).bytes());
+
+        let mut datagram = reply("sha256-generic", &attrs);
+        datagram.extend_from_slice(&NlMsgHeader::done().bytes());
+        let mut replay = ReplayTransport::new(vec![datagram]);
+        let algs = algorithms(&mut replay).unwrap();
+        assert_eq!(algs.len(), 1);
+
+        let alg = &algs[0];
+        assert_eq!(alg.name(), "sha256");
+        assert_eq!(alg.driver(), "sha256-generic");
+        assert_eq!(alg.module(), "kernel");
+        assert_eq!(alg.refcnt(), 2);
+        assert_eq!(alg.priority(), Some(100));
+        assert_eq!(alg.report_type(), Some("shash"));
+        assert_eq!(alg.report(), Some(&AlgReport::Hash { block_size: 64, digest_size: 32 }));
+        assert_eq!(alg.stats(), Some(AlgStats::Hash { hashes: 3, hashed_bytes: 96, errors: 0 }));
+
+        // A dump selects no driver
+        let sent = &replay.sent()[0];
+        assert_eq!(sent.len(), 16 + CRYPTO_USER_ALG_LEN);
+        assert_eq!(sent[16 + 64], 0);
+        assert!(request(&"x".repeat(64)).is_err());
+        assert!(CryptoAlg::from_bytes(&[0; 100]).is_err());
+    }
+
+    #[test]
+    fn test_algorithms() {
+        let mut socket = match Socket::new(Protocol::Crypto) {
+            Ok(s) => s,
+            Err(ref e) if e.raw_os_error() == Some(::libc::EPROTONOSUPPORT) => return,
+            Err(e) => panic!("{}", e),
+        };
+        let algs = match algorithms(&mut socket) {
+            Err(ref e) if e.raw_os_error() == Some(::libc::EPERM) => return,
+            r => r.unwrap(),
+        };
+        let sha = algs.iter().find(|a| a.name() == "sha256").unwrap();
+        assert_eq!(algorithm(&mut socket, sha.driver()).unwrap().name(), "sha256");
+        assert_eq!(algorithm(&mut socket, "nlrs-none").unwrap_err().raw_os_error(), Some(::libc::ENOENT));
+    }
+}
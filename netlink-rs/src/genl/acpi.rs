@@ -0,0 +1,172 @@
+//! ACPI event generic netlink family
+//!
+//! Lid, button, AC adapter and battery notifications, as read by acpid
+//! (`acpi_listen`) now that /proc/acpi/event is gone.
+
+use super::{GenlFamily, GenlMsgHeader};
+use socket::{Socket, NetlinkTransport, NlAttr, MsgType, Payload, c_string};
+use Protocol;
+
+use std::io::{self, ErrorKind};
+
+const ACPI_GENL_FAMILY_NAME: &str = "acpi_event";
+const ACPI_GENL_MCAST_GROUP_NAME: &str = "acpi_mc_group";
+
+const ACPI_GENL_CMD_EVENT: u8 = 1;
+const ACPI_GENL_ATTR_EVENT: u16 = 1;
+
+// HEADER FORMAT
+// struct acpi_genl_event {
+//     acpi_device_class device_class;   /* char[20] */
+//     char bus_id[15];
+//     u32 type;
+//     u32 data;
+// };
+const DEVICE_CLASS_LEN: usize = 20;
+const BUS_ID_LEN: usize = 15;
+const ACPI_GENL_EVENT_LEN: usize = 44;
+
+/// One ACPI notification, e.g. `button/lid LID0 80 1`
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct AcpiEvent {
+    device_class: String,
+    bus_id: String,
+    event_type: u32,
+    data: u32,
+}
+
+impl AcpiEvent {
+    fn from_bytes(bytes: &[u8]) -> io::Result<AcpiEvent> {
+        if bytes.len() < ACPI_GENL_EVENT_LEN {
+            return Err(io::Error::new(ErrorKind::InvalidData, "short acpi_genl_event"));
+        }
+        let u32_at = |i: usize| u32::from_ne_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+        Ok(AcpiEvent {
+            device_class: c_string(&bytes[..DEVICE_CLASS_LEN]),
+            bus_id: c_string(&bytes[DEVICE_CLASS_LEN..DEVICE_CLASS_LEN + BUS_ID_LEN]),
+            event_type: u32_at(36),
+            data: u32_at(40),
+        })
+    }
+
+    /// Decodes an ACPI_GENL_CMD_EVENT message, genl header included.
+    fn from_genl(bytes: &[u8]) -> io::Result<Option<AcpiEvent>> {
+        let (hdr, n) = GenlMsgHeader::from_bytes(bytes)?;
+        if hdr.cmd() != ACPI_GENL_CMD_EVENT {
+            return Ok(None);
+        }
+        match NlAttr::parse(&bytes[n..])?.iter().find(|a| a.attr_type() == ACPI_GENL_ATTR_EVENT) {
+            Some(a) => AcpiEvent::from_bytes(a.payload()).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Class of the device, e.g. `button/power` or `battery`
+    pub fn device_class(&self) -> &str {
+        &self.device_class
+    }
+
+    /// ACPI bus id of the device, e.g. `PNP0C0A:00`
+    pub fn bus_id(&self) -> &str {
+        &self.bus_id
+    }
+
+    /// Notification value, e.g. 0x80 for a status change
+    pub fn event_type(&self) -> u32 {
+        self.event_type
+    }
+
+    /// Driver specific data, e.g. the lid state or the press count
+    pub fn data(&self) -> u32 {
+        self.data
+    }
+}
+
+/// A socket subscribed to the acpi_event multicast group
+pub struct AcpiMonitor<T: NetlinkTransport = Socket> {
+    socket: T,
+    family: GenlFamily,
+}
+
+impl AcpiMonitor {
+    pub fn new() -> io::Result<AcpiMonitor> {
+        let mut socket = Socket::new(Protocol::Generic)?;
+        let family = GenlFamily::resolve(&mut socket, ACPI_GENL_FAMILY_NAME)?;
+        let group = family.group(ACPI_GENL_MCAST_GROUP_NAME)
+            .ok_or_else(|| io::Error::new(ErrorKind::NotFound, "acpi_event has no multicast group"))?;
+        socket.add_membership(group.id())?;
+        Ok(AcpiMonitor {
+            socket,
+            family,
+        })
+    }
+}
+
+impl<T: NetlinkTransport> AcpiMonitor<T> {
+    /// Reads events from `transport`, which is already subscribed to the
+    /// acpi_event group; the family is resolved through it.
+    pub fn from_transport(mut transport: T) -> io::Result<AcpiMonitor<T>> {
+        let family = GenlFamily::resolve(&mut transport, ACPI_GENL_FAMILY_NAME)?;
+        Ok(AcpiMonitor {
+            socket: transport,
+            family,
+        })
+    }
+
+    /// Blocks until events arrive. Fails with an `Overrun` error if some
+    /// were dropped, which cannot be recovered.
+    pub fn recv(&mut self) -> io::Result<Vec<AcpiEvent>> {
+        loop {
+            let mut events = vec![];
+            let messages = self.socket.recv_msgs()?;
+            for msg in messages {
+                match msg.header().msg_type() {
+                    MsgType::UserDefined(t) if t == self.family.id() => {},
+                    _ => continue,
+                }
+                if let Payload::Data(ref b) = *msg.payload() {
+                    events.extend(AcpiEvent::from_genl(b)?);
+                }
+            }
+            if !events.is_empty() {
+                return Ok(events);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_decode() {
+        let mut event = [0u8; ACPI_GENL_EVENT_LEN];
+        event[..10].copy_from_slice(b"button/lid");
+        event[20..24].copy_from_slice(b"LID0");
+        event[36..40].copy_from_slice(&0x80u32.to_ne_bytes());
+        event[40..].copy_from_slice(&1u32.to_ne_bytes());
+        let mut bytes = GenlMsgHeader::new(ACPI_GENL_CMD_EVENT, 1).bytes().to_vec();
+        bytes.extend(NlAttr::new(ACPI_GENL_ATTR_EVENT, &event).bytes());
+
+        let event = AcpiEvent::from_genl(&bytes).unwrap().unwrap();
+        assert_eq!(event.device_class(), "button/lid");
+        assert_eq!(event.bus_id(), "LID0");
+        assert_eq!((event.event_type(), event.data()), (0x80, 1));
+
+        bytes[0] = 2;
+        assert_eq!(AcpiEvent::from_genl(&bytes).unwrap(), None);
+        assert!(AcpiEvent::from_bytes(&[0; 40]).is_err());
+    }
+
+    #[test]
+    fn test_monitor() {
+        match AcpiMonitor::new() {
+            Ok(_) => {},
+            // Kernel without ACPI
+            Err(ref e) if e.raw_os_error() == Some(::libc::ENOENT) => {},
+            Err(e) => panic!("{}", e),
+        }
+    }
+}
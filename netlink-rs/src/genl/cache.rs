@@ -0,0 +1,118 @@
+use super::{GenlFamily, GenlMsgHeader, GENL_ID_CTRL, CTRL_ATTR_FAMILY_NAME};
+use socket::{Socket, NetlinkTransport, NlAttr, Payload, MsgType, Overrun};
+use Protocol;
+
+use std::collections::HashMap;
+use std::io;
+
+const CTRL_CMD_NEWFAMILY: u8 = 1;
+const CTRL_CMD_DELFAMILY: u8 = 2;
+const CTRL_CMD_NEWMCAST_GRP: u8 = 7;
+const CTRL_CMD_DELMCAST_GRP: u8 = 8;
+
+/// Resolved families by name, kept up to date by listening to the
+/// controller's `notify` group. Entries are dropped when their family is
+/// registered, unregistered or changes its multicast groups, and resolved
+/// again on the next lookup.
+pub struct FamilyCache {
+    notify: Socket,
+    families: HashMap<String, GenlFamily>,
+}
+
+impl FamilyCache {
+    pub fn new() -> io::Result<FamilyCache> {
+        let mut notify = Socket::new(Protocol::Generic)?;
+        let ctrl = GenlFamily::resolve(&mut notify, "nlctrl")?;
+        let group = ctrl.group("notify")
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "nlctrl has no notify group"))?;
+        notify.add_membership(group.id())?;
+        Ok(FamilyCache {
+            notify,
+            families: HashMap::new(),
+        })
+    }
+
+    /// Returns the family registered under `name`, resolving it through
+    /// `socket` unless it is cached and no change was announced since.
+    pub fn family(&mut self, socket: &mut impl NetlinkTransport, name: &str) -> io::Result<&GenlFamily> {
+        self.process_notifications()?;
+        if !self.families.contains_key(name) {
+            let family = GenlFamily::resolve(socket, name)?;
+            self.families.insert(name.into(), family);
+        }
+        Ok(&self.families[name])
+    }
+
+    /// Forgets `name`, forcing the next lookup to ask the kernel.
+    pub fn invalidate(&mut self, name: &str) {
+        self.families.remove(name);
+    }
+
+    fn process_notifications(&mut self) -> io::Result<()> {
+        loop {
+            let mut changed = vec![];
+            match self.notify.try_recv() {
+                Ok(Some((_, messages))) => {
+                    for msg in messages {
+                        match msg.header().msg_type() {
+                            MsgType::UserDefined(GENL_ID_CTRL) => {},
+                            _ => continue,
+                        }
+                        if let Payload::Data(ref b) = *msg.payload() {
+                            if let Some(name) = changed_family(b)? {
+                                changed.push(name);
+                            }
+                        }
+                    }
+                },
+                Ok(None) => return Ok(()),
+                // Notifications were lost, nothing cached can be trusted
+                Err(ref e) if Overrun::matches(e) => self.families.clear(),
+                Err(e) => return Err(e),
+            }
+            for name in changed {
+                self.invalidate(&name);
+            }
+        }
+    }
+}
+
+/// Name of the family a controller notification is about, if it affects
+/// resolved families.
+fn changed_family(bytes: &[u8]) -> io::Result<Option<String>> {
+    let (hdr, n) = GenlMsgHeader::from_bytes(bytes)?;
+    match hdr.cmd() {
+        CTRL_CMD_NEWFAMILY | CTRL_CMD_DELFAMILY | CTRL_CMD_NEWMCAST_GRP | CTRL_CMD_DELMCAST_GRP => {},
+        _ => return Ok(None),
+    }
+    match NlAttr::parse(&bytes[n..])?.iter().find(|a| a.attr_type() == CTRL_ATTR_FAMILY_NAME) {
+        Some(a) => Ok(Some(a.get_str()?.to_string())),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changed_family() {
+        let mut bytes = GenlMsgHeader::new(CTRL_CMD_DELFAMILY, 2).bytes().to_vec();
+        bytes.extend(NlAttr::new(CTRL_ATTR_FAMILY_NAME, b"test\0").bytes());
+        assert_eq!(changed_family(&bytes).unwrap(), Some("test".into()));
+
+        bytes[0] = 3;
+        assert_eq!(changed_family(&bytes).unwrap(), None);
+    }
+
+    #[test]
+    fn test_cache_resolve() {
+        let mut socket = Socket::new(Protocol::Generic).unwrap();
+        let mut cache = FamilyCache::new().unwrap();
+        assert_eq!(cache.family(&mut socket, "nlctrl").unwrap().id(), GENL_ID_CTRL);
+        assert!(cache.families.contains_key("nlctrl"));
+        cache.invalidate("nlctrl");
+        assert!(cache.families.is_empty());
+        assert!(cache.family(&mut socket, "no-such-family").is_err());
+    }
+}
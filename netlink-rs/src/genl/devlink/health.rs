@@ -0,0 +1,429 @@
+use super::{Devlink, DevlinkDevice, DEVLINK_ATTR_PORT_INDEX};
+use socket::{NetlinkTransport, NlAttr, string_attr};
+
+use std::io::{self, ErrorKind};
+
+const DEVLINK_CMD_HEALTH_REPORTER_GET: u8 = 52;
+const DEVLINK_CMD_HEALTH_REPORTER_RECOVER: u8 = 54;
+const DEVLINK_CMD_HEALTH_REPORTER_DIAGNOSE: u8 = 55;
+const DEVLINK_CMD_HEALTH_REPORTER_DUMP_GET: u8 = 56;
+const DEVLINK_CMD_HEALTH_REPORTER_DUMP_CLEAR: u8 = 57;
+
+const DEVLINK_ATTR_FMSG: u16 = 106;
+const DEVLINK_ATTR_FMSG_OBJ_NEST_START: u16 = 107;
+const DEVLINK_ATTR_FMSG_PAIR_NEST_START: u16 = 108;
+const DEVLINK_ATTR_FMSG_ARR_NEST_START: u16 = 109;
+const DEVLINK_ATTR_FMSG_NEST_END: u16 = 110;
+const DEVLINK_ATTR_FMSG_OBJ_NAME: u16 = 111;
+const DEVLINK_ATTR_FMSG_OBJ_VALUE_TYPE: u16 = 112;
+const DEVLINK_ATTR_FMSG_OBJ_VALUE_DATA: u16 = 113;
+const DEVLINK_ATTR_HEALTH_REPORTER: u16 = 114;
+const DEVLINK_ATTR_HEALTH_REPORTER_NAME: u16 = 115;
+const DEVLINK_ATTR_HEALTH_REPORTER_STATE: u16 = 116;
+const DEVLINK_ATTR_HEALTH_REPORTER_ERR_COUNT: u16 = 117;
+const DEVLINK_ATTR_HEALTH_REPORTER_RECOVER_COUNT: u16 = 118;
+const DEVLINK_ATTR_HEALTH_REPORTER_GRACEFUL_PERIOD: u16 = 120;
+const DEVLINK_ATTR_HEALTH_REPORTER_AUTO_RECOVER: u16 = 121;
+const DEVLINK_ATTR_HEALTH_REPORTER_DUMP_TS_NS: u16 = 137;
+const DEVLINK_ATTR_HEALTH_REPORTER_AUTO_DUMP: u16 = 141;
+
+// Kernel attribute policy types (NLA_*) used in FMSG_OBJ_VALUE_TYPE
+const NLA_U8: u8 = 1;
+const NLA_U32: u8 = 3;
+const NLA_U64: u8 = 4;
+const NLA_FLAG: u8 = 6;
+const NLA_NUL_STRING: u8 = 10;
+const NLA_BINARY: u8 = 11;
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum HealthState {
+    Healthy,
+    Error,
+    Other(u8),
+}
+
+impl From<u8> for HealthState {
+    fn from(t: u8) -> HealthState {
+        match t {
+            0 => HealthState::Healthy,
+            1 => HealthState::Error,
+            i => HealthState::Other(i),
+        }
+    }
+}
+
+/// A health reporter of a device or port, e.g. `tx` or `fw_fatal`
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct HealthReporter {
+    device: DevlinkDevice,
+    port: Option<u32>,
+    name: String,
+    state: HealthState,
+    error_count: u64,
+    recover_count: u64,
+    graceful_period: Option<u64>,
+    auto_recover: Option<bool>,
+    auto_dump: Option<bool>,
+    dump_ts: Option<u64>,
+}
+
+impl HealthReporter {
+    fn from_attrs(bytes: &[u8]) -> io::Result<HealthReporter> {
+        let mut reporter = HealthReporter {
+            device: DevlinkDevice::from_attrs(bytes)?,
+            port: None,
+            name: String::new(),
+            state: HealthState::Healthy,
+            error_count: 0,
+            recover_count: 0,
+            graceful_period: None,
+            auto_recover: None,
+            auto_dump: None,
+            dump_ts: None,
+        };
+        for attr in NlAttr::parse(bytes)? {
+            match attr.attr_type() {
+                DEVLINK_ATTR_PORT_INDEX => {
+                    reporter.port = Some(attr.get_u32()?)
+                },
+                DEVLINK_ATTR_HEALTH_REPORTER => reporter.parse_nested(&attr)?,
+                _ => {},
+            }
+        }
+        Ok(reporter)
+    }
+
+    fn parse_nested(&mut self, attr: &NlAttr) -> io::Result<()> {
+        for a in attr.nested()? {
+            match a.attr_type() {
+                DEVLINK_ATTR_HEALTH_REPORTER_NAME => self.name = a.get_str()?.to_string(),
+                DEVLINK_ATTR_HEALTH_REPORTER_STATE => self.state = a.get_u8()?.into(),
+                DEVLINK_ATTR_HEALTH_REPORTER_ERR_COUNT => self.error_count = a.get_u64()?,
+                DEVLINK_ATTR_HEALTH_REPORTER_RECOVER_COUNT => self.recover_count = a.get_u64()?,
+                DEVLINK_ATTR_HEALTH_REPORTER_GRACEFUL_PERIOD => self.graceful_period = Some(a.get_u64()?),
+                DEVLINK_ATTR_HEALTH_REPORTER_AUTO_RECOVER => self.auto_recover = Some(a.get_u8()? != 0),
+                DEVLINK_ATTR_HEALTH_REPORTER_AUTO_DUMP => self.auto_dump = Some(a.get_u8()? != 0),
+                DEVLINK_ATTR_HEALTH_REPORTER_DUMP_TS_NS => self.dump_ts = Some(a.get_u64()?),
+                _ => {},
+            }
+        }
+        Ok(())
+    }
+
+    pub fn device(&self) -> &DevlinkDevice {
+        &self.device
+    }
+
+    /// Port index, for reporters of a port rather than of the device
+    pub fn port(&self) -> Option<u32> {
+        self.port
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn state(&self) -> HealthState {
+        self.state
+    }
+
+    /// Errors reported since the device was probed
+    pub fn error_count(&self) -> u64 {
+        self.error_count
+    }
+
+    /// Successful recoveries
+    pub fn recover_count(&self) -> u64 {
+        self.recover_count
+    }
+
+    /// Minimum time between automatic recoveries, in milliseconds
+    pub fn graceful_period(&self) -> Option<u64> {
+        self.graceful_period
+    }
+
+    pub fn auto_recover(&self) -> Option<bool> {
+        self.auto_recover
+    }
+
+    pub fn auto_dump(&self) -> Option<bool> {
+        self.auto_dump
+    }
+
+    /// Time of the stored dump, in nanoseconds of CLOCK_REALTIME
+    pub fn dump_ts(&self) -> Option<u64> {
+        self.dump_ts
+    }
+}
+
+/// A node of a formatted message (fmsg), the JSON-like tree drivers answer
+/// diagnose and dump requests with
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum FmsgValue {
+    Bool(bool),
+    U8(u8),
+    U32(u32),
+    U64(u64),
+    String(String),
+    Binary(Vec<u8>),
+    /// Named members, in the order the driver put them
+    Object(Vec<(String, FmsgValue)>),
+    Array(Vec<FmsgValue>),
+}
+
+impl FmsgValue {
+    /// Member `name` of an object
+    pub fn get(&self, name: &str) -> Option<&FmsgValue> {
+        match *self {
+            FmsgValue::Object(ref members) => members.iter().find(|m| m.0 == name).map(|m| &m.1),
+            _ => None,
+        }
+    }
+
+    fn from_data(nla_type: u8, attr: &NlAttr) -> io::Result<FmsgValue> {
+        let payload = attr.payload();
+        Ok(match nla_type {
+            NLA_FLAG => FmsgValue::Bool(payload.first().is_none_or(|&b| b != 0)),
+            NLA_U8 => FmsgValue::U8(attr.get_u8()?),
+            NLA_U32 => FmsgValue::U32(attr.get_u32()?),
+            NLA_U64 => FmsgValue::U64(attr.get_u64()?),
+            NLA_NUL_STRING => FmsgValue::String(attr.get_str()?.to_string()),
+            NLA_BINARY => FmsgValue::Binary(payload.to_vec()),
+            _ => return Err(io::Error::new(ErrorKind::InvalidData, "unknown fmsg value type")),
+        })
+    }
+}
+
+/// Open nest of an fmsg stream
+enum Frame {
+    Object(Vec<(String, FmsgValue)>),
+    Pair(String, Option<FmsgValue>),
+    Array(Vec<FmsgValue>),
+}
+
+fn bad_fmsg() -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, "malformed fmsg")
+}
+
+/// Rebuilds the tree from the flat stream of DEVLINK_ATTR_FMSG items, which
+/// may be split across several messages.
+fn parse_fmsg(items: &[NlAttr]) -> io::Result<FmsgValue> {
+    let mut stack: Vec<Frame> = vec![];
+    let mut value_type = None;
+    let mut root = None;
+    for item in items {
+        let value = match item.attr_type() {
+            DEVLINK_ATTR_FMSG_OBJ_NEST_START => {
+                stack.push(Frame::Object(vec![]));
+                continue;
+            },
+            DEVLINK_ATTR_FMSG_PAIR_NEST_START => {
+                stack.push(Frame::Pair(String::new(), None));
+                continue;
+            },
+            DEVLINK_ATTR_FMSG_ARR_NEST_START => {
+                stack.push(Frame::Array(vec![]));
+                continue;
+            },
+            DEVLINK_ATTR_FMSG_OBJ_NAME => {
+                match stack.last_mut() {
+                    Some(&mut Frame::Pair(ref mut name, _)) => *name = item.get_str()?.to_string(),
+                    _ => return Err(bad_fmsg()),
+                }
+                continue;
+            },
+            DEVLINK_ATTR_FMSG_OBJ_VALUE_TYPE => {
+                value_type = item.payload().first().cloned();
+                continue;
+            },
+            DEVLINK_ATTR_FMSG_OBJ_VALUE_DATA => {
+                FmsgValue::from_data(value_type.ok_or_else(bad_fmsg)?, item)?
+            },
+            DEVLINK_ATTR_FMSG_NEST_END => match stack.pop().ok_or_else(bad_fmsg)? {
+                Frame::Object(members) => FmsgValue::Object(members),
+                Frame::Array(values) => FmsgValue::Array(values),
+                Frame::Pair(name, value) => {
+                    match (stack.last_mut(), value) {
+                        (Some(&mut Frame::Object(ref mut members)), Some(value)) => members.push((name, value)),
+                        (Some(&mut Frame::Object(_)), None) => {},
+                        _ => return Err(bad_fmsg()),
+                    }
+                    continue;
+                },
+            },
+            _ => continue,
+        };
+        match stack.last_mut() {
+            Some(&mut Frame::Pair(_, ref mut v)) => *v = Some(value),
+            Some(&mut Frame::Array(ref mut values)) => values.push(value),
+            Some(&mut Frame::Object(_)) => return Err(bad_fmsg()),
+            None => root = Some(value),
+        }
+    }
+    if !stack.is_empty() {
+        return Err(bad_fmsg());
+    }
+    root.ok_or_else(bad_fmsg)
+}
+
+fn fmsg_from_replies(replies: &[Vec<u8>]) -> io::Result<FmsgValue> {
+    let mut items = vec![];
+    for reply in replies {
+        for attr in NlAttr::parse(reply)? {
+            if attr.attr_type() == DEVLINK_ATTR_FMSG {
+                items.extend(attr.nested()?);
+            }
+        }
+    }
+    parse_fmsg(&items)
+}
+
+fn reporter_attrs(device: &DevlinkDevice, name: &str) -> Vec<u8> {
+    let mut attrs = device.attrs();
+    attrs.extend(string_attr(DEVLINK_ATTR_HEALTH_REPORTER_NAME, name));
+    attrs
+}
+
+impl<T: NetlinkTransport> Devlink<T> {
+    /// Lists the health reporters of every device and port
+    /// (DEVLINK_CMD_HEALTH_REPORTER_GET dump).
+    pub fn health_reporters(&mut self) -> io::Result<Vec<HealthReporter>> {
+        let replies = self.family.dump(&mut self.socket, DEVLINK_CMD_HEALTH_REPORTER_GET, &[])?;
+        replies.iter().map(|r| HealthReporter::from_attrs(r)).collect()
+    }
+
+    pub fn health_reporter(&mut self, device: &DevlinkDevice, name: &str) -> io::Result<HealthReporter> {
+        let attrs = reporter_attrs(device, name);
+        let replies = self.family.request(&mut self.socket, DEVLINK_CMD_HEALTH_REPORTER_GET, &attrs)?;
+        match replies.first() {
+            Some(reply) => HealthReporter::from_attrs(reply),
+            None => Err(io::Error::new(ErrorKind::NotFound, "no health reporter reply")),
+        }
+    }
+
+    /// Asks the driver to recover from the last reported error.
+    pub fn health_recover(&mut self, device: &DevlinkDevice, name: &str) -> io::Result<()> {
+        let attrs = reporter_attrs(device, name);
+        self.family.request(&mut self.socket, DEVLINK_CMD_HEALTH_REPORTER_RECOVER, &attrs)?;
+        Ok(())
+    }
+
+    /// Current state of the reported component, as rendered by the driver.
+    pub fn health_diagnose(&mut self, device: &DevlinkDevice, name: &str) -> io::Result<FmsgValue> {
+        let attrs = reporter_attrs(device, name);
+        let replies = self.request_multipart(DEVLINK_CMD_HEALTH_REPORTER_DIAGNOSE, &attrs)?;
+        fmsg_from_replies(&replies)
+    }
+
+    /// Dump stored at the last error, taking one now if there is none.
+    pub fn health_dump(&mut self, device: &DevlinkDevice, name: &str) -> io::Result<FmsgValue> {
+        let attrs = reporter_attrs(device, name);
+        let replies = self.family.dump(&mut self.socket, DEVLINK_CMD_HEALTH_REPORTER_DUMP_GET, &attrs)?;
+        fmsg_from_replies(&replies)
+    }
+
+    /// Discards the stored dump, so that the next error stores a new one.
+    pub fn health_dump_clear(&mut self, device: &DevlinkDevice, name: &str) -> io::Result<()> {
+        let attrs = reporter_attrs(device, name);
+        self.family.request(&mut self.socket, DEVLINK_CMD_HEALTH_REPORTER_DUMP_CLEAR, &attrs)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket::NLA_F_NESTED;
+
+    fn flag(attr_type: u16) -> Vec<u8> {
+        NlAttr::new(attr_type, &[]).bytes()
+    }
+
+    fn value(nla_type: u8, data: &[u8]) -> Vec<u8> {
+        let mut bytes = NlAttr::new(DEVLINK_ATTR_FMSG_OBJ_VALUE_TYPE, &[nla_type]).bytes();
+        bytes.extend(NlAttr::new(DEVLINK_ATTR_FMSG_OBJ_VALUE_DATA, data).bytes());
+        bytes
+    }
+
+    fn pair(name: &[u8]) -> Vec<u8> {
+        let mut bytes = flag(DEVLINK_ATTR_FMSG_PAIR_NEST_START);
+        bytes.extend(NlAttr::new(DEVLINK_ATTR_FMSG_OBJ_NAME, name).bytes());
+        bytes
+    }
+
+    #[test]
+    fn test_fmsg_tree() {
+        // {"Status": "ok", "SQs": [{"sqn": 4, "stopped": false}]}
+        let mut stream = flag(DEVLINK_ATTR_FMSG_OBJ_NEST_START);
+        stream.extend(pair(b"Status\0"));
+        stream.extend(value(NLA_NUL_STRING, b"ok\0"));
+        stream.extend(flag(DEVLINK_ATTR_FMSG_NEST_END));
+        stream.extend(pair(b"SQs\0"));
+        stream.extend(flag(DEVLINK_ATTR_FMSG_ARR_NEST_START));
+        stream.extend(flag(DEVLINK_ATTR_FMSG_OBJ_NEST_START));
+        stream.extend(pair(b"sqn\0"));
+        stream.extend(value(NLA_U32, &4u32.to_ne_bytes()));
+        stream.extend(flag(DEVLINK_ATTR_FMSG_NEST_END));
+        // Split across two replies, as in dumps
+        let split = stream.len();
+        let mut rest = pair(b"stopped\0");
+        rest.extend(value(NLA_FLAG, &[0]));
+        rest.extend(flag(DEVLINK_ATTR_FMSG_NEST_END));
+        for _ in 0..4 {
+            rest.extend(flag(DEVLINK_ATTR_FMSG_NEST_END));
+        }
+        stream.extend(rest);
+
+        let first = NlAttr::new(DEVLINK_ATTR_FMSG | NLA_F_NESTED, &stream[..split]).bytes();
+        let second = NlAttr::new(DEVLINK_ATTR_FMSG | NLA_F_NESTED, &stream[split..]).bytes();
+        let tree = fmsg_from_replies(&[first, second]).unwrap();
+
+        assert_eq!(tree.get("Status"), Some(&FmsgValue::String("ok".into())));
+        let sq = match tree.get("SQs") {
+            Some(FmsgValue::Array(sqs)) => sqs[0].clone(),
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(sq.get("sqn"), Some(&FmsgValue::U32(4)));
+        assert_eq!(sq.get("stopped"), Some(&FmsgValue::Bool(false)));
+
+        // Unbalanced
+        let items = NlAttr::parse(&stream[..split]).unwrap();
+        assert!(parse_fmsg(&items).is_err());
+    }
+
+    #[test]
+    fn test_reporter_decode() {
+        let dev = DevlinkDevice::new("pci", "0000:03:00.0");
+        let mut nested = NlAttr::new(DEVLINK_ATTR_HEALTH_REPORTER_NAME, b"tx\0").bytes();
+        nested.extend(NlAttr::new(DEVLINK_ATTR_HEALTH_REPORTER_STATE, &[1]).bytes());
+        nested.extend(NlAttr::new(DEVLINK_ATTR_HEALTH_REPORTER_ERR_COUNT, &3u64.to_ne_bytes()).bytes());
+        nested.extend(NlAttr::new(DEVLINK_ATTR_HEALTH_REPORTER_RECOVER_COUNT, &2u64.to_ne_bytes()).bytes());
+        nested.extend(NlAttr::new(DEVLINK_ATTR_HEALTH_REPORTER_AUTO_RECOVER, &[1]).bytes());
+        let mut bytes = dev.attrs();
+        bytes.extend(NlAttr::new(DEVLINK_ATTR_PORT_INDEX, &1u32.to_ne_bytes()).bytes());
+        bytes.extend(NlAttr::new(DEVLINK_ATTR_HEALTH_REPORTER | NLA_F_NESTED, &nested).bytes());
+
+        let reporter = HealthReporter::from_attrs(&bytes).unwrap();
+        assert_eq!(reporter.device(), &dev);
+        assert_eq!(reporter.port(), Some(1));
+        assert_eq!(reporter.name(), "tx");
+        assert_eq!(reporter.state(), HealthState::Error);
+        assert_eq!((reporter.error_count(), reporter.recover_count()), (3, 2));
+        assert_eq!(reporter.auto_recover(), Some(true));
+        assert_eq!(reporter.graceful_period(), None);
+    }
+
+    #[test]
+    fn test_health_reporters() {
+        let mut devlink = match Devlink::new() {
+            Ok(devlink) => devlink,
+            // Kernel without devlink
+            Err(ref e) if e.raw_os_error() == Some(::libc::ENOENT) => return,
+            Err(e) => panic!("{}", e),
+        };
+        devlink.health_reporters().unwrap();
+    }
+}
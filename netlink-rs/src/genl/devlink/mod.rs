@@ -0,0 +1,333 @@
+//! devlink generic netlink family
+//!
+//! Device level view of NICs and switch ASICs, independent of the netdevs
+//! they expose.
+//!
+//! Health reporters (`devlink health`) report errors and recoveries of a
+//! device and render diagnostics and dumps as fmsg trees. Parameters
+//! (`devlink dev param`) and resources (`devlink resource`) are the device
+//! wide tunables; most resource and `driverinit` parameter changes only take
+//! effect after a reload (`devlink dev reload`).
+
+mod health;
+mod param;
+mod resource;
+pub use self::health::*;
+pub use self::param::*;
+pub use self::resource::*;
+
+use super::GenlFamily;
+use socket::{Socket, NetlinkTransport, NlAttr, string_attr};
+use Protocol;
+
+use std::fmt;
+use std::io;
+
+const DEVLINK_GENL_NAME: &str = "devlink";
+
+const DEVLINK_CMD_GET: u8 = 1;
+const DEVLINK_CMD_PORT_GET: u8 = 5;
+
+const DEVLINK_ATTR_BUS_NAME: u16 = 1;
+const DEVLINK_ATTR_DEV_NAME: u16 = 2;
+const DEVLINK_ATTR_PORT_INDEX: u16 = 3;
+const DEVLINK_ATTR_PORT_TYPE: u16 = 4;
+const DEVLINK_ATTR_PORT_NETDEV_IFINDEX: u16 = 6;
+const DEVLINK_ATTR_PORT_NETDEV_NAME: u16 = 7;
+const DEVLINK_ATTR_PORT_IBDEV_NAME: u16 = 8;
+const DEVLINK_ATTR_PORT_SPLIT_COUNT: u16 = 9;
+const DEVLINK_ATTR_PORT_FLAVOUR: u16 = 77;
+const DEVLINK_ATTR_PORT_NUMBER: u16 = 78;
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum PortType {
+    NotSet,
+    Auto,
+    Eth,
+    Ib,
+    Other(u16),
+}
+
+impl From<u16> for PortType {
+    fn from(t: u16) -> PortType {
+        use self::PortType::*;
+        match t {
+            0 => NotSet,
+            1 => Auto,
+            2 => Eth,
+            3 => Ib,
+            i => Other(i),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum PortFlavour {
+    /// Port physically facing the user, e.g. a front panel port
+    Physical,
+    /// CPU port
+    Cpu,
+    /// Distributed switch architecture interconnect port
+    Dsa,
+    /// eswitch port for a PCI physical function
+    PciPf,
+    /// eswitch port for a PCI virtual function
+    PciVf,
+    /// Any virtual port facing the user
+    Virtual,
+    /// Port which exists in the switch, but is not used
+    Unused,
+    /// eswitch port for a PCI subfunction
+    PciSf,
+    Other(u16),
+}
+
+impl From<u16> for PortFlavour {
+    fn from(t: u16) -> PortFlavour {
+        use self::PortFlavour::*;
+        match t {
+            0 => Physical,
+            1 => Cpu,
+            2 => Dsa,
+            3 => PciPf,
+            4 => PciVf,
+            5 => Virtual,
+            6 => Unused,
+            7 => PciSf,
+            i => Other(i),
+        }
+    }
+}
+
+/// A devlink instance, identified by bus and device name
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct DevlinkDevice {
+    bus_name: String,
+    dev_name: String,
+}
+
+impl DevlinkDevice {
+    pub fn new(bus_name: &str, dev_name: &str) -> DevlinkDevice {
+        DevlinkDevice {
+            bus_name: bus_name.into(),
+            dev_name: dev_name.into(),
+        }
+    }
+
+    fn from_attrs(bytes: &[u8]) -> io::Result<DevlinkDevice> {
+        let mut dev = DevlinkDevice::new("", "");
+        for attr in NlAttr::parse(bytes)? {
+            match attr.attr_type() {
+                DEVLINK_ATTR_BUS_NAME => dev.bus_name = attr.get_str()?.to_string(),
+                DEVLINK_ATTR_DEV_NAME => dev.dev_name = attr.get_str()?.to_string(),
+                _ => {},
+            }
+        }
+        Ok(dev)
+    }
+
+    /// Bus the device sits on, e.g. `pci` or `netdevsim`
+    pub fn bus_name(&self) -> &str {
+        &self.bus_name
+    }
+
+    /// Device name on the bus, e.g. `0000:01:00.0`
+    pub fn dev_name(&self) -> &str {
+        &self.dev_name
+    }
+
+    /// Handle attributes selecting this device in a request
+    fn attrs(&self) -> Vec<u8> {
+        let mut bytes = string_attr(DEVLINK_ATTR_BUS_NAME, &self.bus_name);
+        bytes.extend(string_attr(DEVLINK_ATTR_DEV_NAME, &self.dev_name));
+        bytes
+    }
+}
+
+impl fmt::Display for DevlinkDevice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.bus_name, self.dev_name)
+    }
+}
+
+/// A port of a devlink instance
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct DevlinkPort {
+    device: DevlinkDevice,
+    index: u32,
+    port_type: PortType,
+    flavour: Option<PortFlavour>,
+    number: Option<u32>,
+    split_count: Option<u32>,
+    netdev_ifindex: Option<u32>,
+    netdev_name: Option<String>,
+    ibdev_name: Option<String>,
+}
+
+impl DevlinkPort {
+    fn from_attrs(bytes: &[u8]) -> io::Result<DevlinkPort> {
+        let mut port = DevlinkPort {
+            device: DevlinkDevice::from_attrs(bytes)?,
+            index: 0,
+            port_type: PortType::NotSet,
+            flavour: None,
+            number: None,
+            split_count: None,
+            netdev_ifindex: None,
+            netdev_name: None,
+            ibdev_name: None,
+        };
+
+        for attr in NlAttr::parse(bytes)? {
+            match attr.attr_type() {
+                DEVLINK_ATTR_PORT_INDEX => port.index = attr.get_u32()?,
+                DEVLINK_ATTR_PORT_TYPE => port.port_type = attr.get_u16()?.into(),
+                DEVLINK_ATTR_PORT_FLAVOUR => port.flavour = Some(attr.get_u16()?.into()),
+                DEVLINK_ATTR_PORT_NUMBER => port.number = Some(attr.get_u32()?),
+                DEVLINK_ATTR_PORT_SPLIT_COUNT => port.split_count = Some(attr.get_u32()?),
+                DEVLINK_ATTR_PORT_NETDEV_IFINDEX => port.netdev_ifindex = Some(attr.get_u32()?),
+                DEVLINK_ATTR_PORT_NETDEV_NAME => port.netdev_name = Some(attr.get_str()?.to_string()),
+                DEVLINK_ATTR_PORT_IBDEV_NAME => port.ibdev_name = Some(attr.get_str()?.to_string()),
+                _ => {},
+            }
+        }
+
+        Ok(port)
+    }
+
+    pub fn device(&self) -> &DevlinkDevice {
+        &self.device
+    }
+
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    pub fn port_type(&self) -> PortType {
+        self.port_type
+    }
+
+    pub fn flavour(&self) -> Option<PortFlavour> {
+        self.flavour
+    }
+
+    /// Physical port number, as printed on the front panel
+    pub fn number(&self) -> Option<u32> {
+        self.number
+    }
+
+    /// Number of subports this port has been split into
+    pub fn split_count(&self) -> Option<u32> {
+        self.split_count
+    }
+
+    pub fn netdev_ifindex(&self) -> Option<u32> {
+        self.netdev_ifindex
+    }
+
+    pub fn netdev_name(&self) -> Option<&str> {
+        self.netdev_name.as_deref()
+    }
+
+    pub fn ibdev_name(&self) -> Option<&str> {
+        self.ibdev_name.as_deref()
+    }
+}
+
+/// Handle to the devlink family
+pub struct Devlink<T: NetlinkTransport = Socket> {
+    socket: T,
+    family: GenlFamily,
+}
+
+impl Devlink {
+    pub fn new() -> io::Result<Devlink> {
+        Devlink::from_transport(Socket::new(Protocol::Generic)?)
+    }
+}
+
+impl<T: NetlinkTransport> Devlink<T> {
+    /// Like `new`, but talks to the kernel through `transport`.
+    pub fn from_transport(mut transport: T) -> io::Result<Devlink<T>> {
+        let family = GenlFamily::resolve(&mut transport, DEVLINK_GENL_NAME)?;
+        Ok(Devlink {
+            socket: transport,
+            family,
+        })
+    }
+
+    /// Sends a command answered with a multipart reply. The kernel ends it
+    /// with NLMSG_DONE and only then acks the request; the ack is drained so
+    /// that it is not taken for the reply to the next request.
+    fn request_multipart(&mut self, cmd: u8, attrs: &[u8]) -> io::Result<Vec<Vec<u8>>> {
+        let replies = self.family.request(&mut self.socket, cmd, attrs)?;
+        self.socket.recv_bytes()?;
+        Ok(replies)
+    }
+
+    /// Lists all devlink instances (DEVLINK_CMD_GET dump).
+    pub fn devices(&mut self) -> io::Result<Vec<DevlinkDevice>> {
+        let replies = self.family.dump(&mut self.socket, DEVLINK_CMD_GET, &[])?;
+        replies.iter().map(|r| DevlinkDevice::from_attrs(r)).collect()
+    }
+
+    /// Lists the ports of every devlink instance (DEVLINK_CMD_PORT_GET dump).
+    pub fn ports(&mut self) -> io::Result<Vec<DevlinkPort>> {
+        let replies = self.family.dump(&mut self.socket, DEVLINK_CMD_PORT_GET, &[])?;
+        replies.iter().map(|r| DevlinkPort::from_attrs(r)).collect()
+    }
+
+    /// Fetches a single port of `device`.
+    pub fn port(&mut self, device: &DevlinkDevice, index: u32) -> io::Result<DevlinkPort> {
+        let mut attrs = device.attrs();
+        attrs.extend(NlAttr::new(DEVLINK_ATTR_PORT_INDEX, &index.to_ne_bytes()).bytes());
+
+        let replies = self.family.request(&mut self.socket, DEVLINK_CMD_PORT_GET, &attrs)?;
+        match replies.first() {
+            Some(reply) => DevlinkPort::from_attrs(reply),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, "no port reply")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket::NlAttr;
+
+    #[test]
+    fn test_device_attrs() {
+        let dev = DevlinkDevice::new("pci", "0000:01:00.0");
+        assert_eq!(dev.to_string(), "pci/0000:01:00.0");
+
+        let decoded = DevlinkDevice::from_attrs(&dev.attrs()).unwrap();
+        assert_eq!(decoded, dev);
+    }
+
+    #[test]
+    fn test_port_decode() {
+        let dev = DevlinkDevice::new("netdevsim", "netdevsim10");
+        let mut bytes = dev.attrs();
+        bytes.extend(NlAttr::new(DEVLINK_ATTR_PORT_INDEX, &2u32.to_ne_bytes()).bytes());
+        bytes.extend(NlAttr::new(DEVLINK_ATTR_PORT_TYPE, &2u16.to_ne_bytes()).bytes());
+        bytes.extend(NlAttr::new(DEVLINK_ATTR_PORT_NETDEV_IFINDEX, &7u32.to_ne_bytes()).bytes());
+        bytes.extend(NlAttr::new(DEVLINK_ATTR_PORT_NETDEV_NAME, b"eni10np2\0").bytes());
+        bytes.extend(NlAttr::new(DEVLINK_ATTR_PORT_FLAVOUR, &0u16.to_ne_bytes()).bytes());
+        bytes.extend(NlAttr::new(DEVLINK_ATTR_PORT_NUMBER, &2u32.to_ne_bytes()).bytes());
+
+        let port = DevlinkPort::from_attrs(&bytes).unwrap();
+        assert_eq!(port.device(), &dev);
+        assert_eq!(port.index(), 2);
+        assert_eq!(port.port_type(), PortType::Eth);
+        assert_eq!(port.flavour(), Some(PortFlavour::Physical));
+        assert_eq!(port.number(), Some(2));
+        assert_eq!(port.netdev_ifindex(), Some(7));
+        assert_eq!(port.netdev_name(), Some("eni10np2"));
+        assert_eq!(port.ibdev_name(), None);
+        assert_eq!(port.split_count(), None);
+    }
+}
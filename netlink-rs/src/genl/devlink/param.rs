@@ -0,0 +1,265 @@
+use super::{Devlink, DevlinkDevice};
+use socket::{NetlinkTransport, NlAttr, string_attr};
+
+use std::io::{self, ErrorKind};
+
+const DEVLINK_CMD_PARAM_GET: u8 = 38;
+const DEVLINK_CMD_PARAM_SET: u8 = 39;
+
+const DEVLINK_ATTR_PARAM: u16 = 80;
+const DEVLINK_ATTR_PARAM_NAME: u16 = 81;
+const DEVLINK_ATTR_PARAM_GENERIC: u16 = 82;
+const DEVLINK_ATTR_PARAM_TYPE: u16 = 83;
+const DEVLINK_ATTR_PARAM_VALUES_LIST: u16 = 84;
+const DEVLINK_ATTR_PARAM_VALUE: u16 = 85;
+const DEVLINK_ATTR_PARAM_VALUE_DATA: u16 = 86;
+const DEVLINK_ATTR_PARAM_VALUE_CMODE: u16 = 87;
+
+// Kernel attribute policy types (NLA_*) used in PARAM_TYPE
+const NLA_U8: u8 = 1;
+const NLA_U16: u8 = 2;
+const NLA_U32: u8 = 3;
+const NLA_STRING: u8 = 5;
+const NLA_FLAG: u8 = 6;
+
+/// When a parameter value applies
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum ParamCmode {
+    /// Immediately
+    Runtime,
+    /// On the next driver reload
+    DriverInit,
+    /// Stored in the device, on the next reset
+    Permanent,
+    Other(u8),
+}
+
+impl From<ParamCmode> for u8 {
+    fn from(c: ParamCmode) -> u8 {
+        match c {
+            ParamCmode::Runtime => 0,
+            ParamCmode::DriverInit => 1,
+            ParamCmode::Permanent => 2,
+            ParamCmode::Other(i) => i,
+        }
+    }
+}
+
+impl From<u8> for ParamCmode {
+    fn from(c: u8) -> ParamCmode {
+        match c {
+            0 => ParamCmode::Runtime,
+            1 => ParamCmode::DriverInit,
+            2 => ParamCmode::Permanent,
+            i => ParamCmode::Other(i),
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum ParamValue {
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    String(String),
+    Bool(bool),
+}
+
+impl ParamValue {
+    fn nla_type(&self) -> u8 {
+        match *self {
+            ParamValue::U8(_) => NLA_U8,
+            ParamValue::U16(_) => NLA_U16,
+            ParamValue::U32(_) => NLA_U32,
+            ParamValue::String(_) => NLA_STRING,
+            ParamValue::Bool(_) => NLA_FLAG,
+        }
+    }
+
+    /// Decodes PARAM_VALUE_DATA, absent for a false flag.
+    fn from_data(nla_type: u8, data: Option<NlAttr>) -> io::Result<ParamValue> {
+        let value = data.unwrap_or(NlAttr::new(DEVLINK_ATTR_PARAM_VALUE_DATA, &[]));
+        Ok(match nla_type {
+            NLA_U8 => ParamValue::U8(value.get_u8()?),
+            NLA_U16 => ParamValue::U16(value.get_u16()?),
+            NLA_U32 => ParamValue::U32(value.get_u32()?),
+            NLA_STRING => ParamValue::String(value.get_str()?.to_string()),
+            NLA_FLAG => ParamValue::Bool(data.is_some()),
+            _ => return Err(io::Error::new(ErrorKind::InvalidData, "unknown param type")),
+        })
+    }
+
+    fn data_attr(&self) -> Vec<u8> {
+        match *self {
+            ParamValue::U8(v) => NlAttr::new(DEVLINK_ATTR_PARAM_VALUE_DATA, &[v]).bytes(),
+            ParamValue::U16(v) => NlAttr::new(DEVLINK_ATTR_PARAM_VALUE_DATA, &v.to_ne_bytes()).bytes(),
+            ParamValue::U32(v) => NlAttr::new(DEVLINK_ATTR_PARAM_VALUE_DATA, &v.to_ne_bytes()).bytes(),
+            ParamValue::String(ref s) => string_attr(DEVLINK_ATTR_PARAM_VALUE_DATA, s),
+            ParamValue::Bool(true) => NlAttr::new(DEVLINK_ATTR_PARAM_VALUE_DATA, &[]).bytes(),
+            ParamValue::Bool(false) => vec![],
+        }
+    }
+}
+
+/// A device parameter, e.g. the generic `enable_sriov` or a driver
+/// specific one
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct DevlinkParam {
+    device: DevlinkDevice,
+    name: String,
+    generic: bool,
+    values: Vec<(ParamCmode, ParamValue)>,
+}
+
+impl DevlinkParam {
+    fn from_attrs(bytes: &[u8]) -> io::Result<DevlinkParam> {
+        let mut param = DevlinkParam {
+            device: DevlinkDevice::from_attrs(bytes)?,
+            name: String::new(),
+            generic: false,
+            values: vec![],
+        };
+        let nested = match NlAttr::parse(bytes)?.into_iter().find(|a| a.attr_type() == DEVLINK_ATTR_PARAM) {
+            Some(attr) => attr.nested()?,
+            None => return Err(io::Error::new(ErrorKind::InvalidData, "no param attribute")),
+        };
+        let mut nla_type = None;
+        let mut list = None;
+        for a in nested {
+            match a.attr_type() {
+                DEVLINK_ATTR_PARAM_NAME => param.name = a.get_str()?.to_string(),
+                DEVLINK_ATTR_PARAM_GENERIC => param.generic = true,
+                DEVLINK_ATTR_PARAM_TYPE => nla_type = Some(a.get_u8()?),
+                DEVLINK_ATTR_PARAM_VALUES_LIST => list = Some(a),
+                _ => {},
+            }
+        }
+        if let (Some(nla_type), Some(list)) = (nla_type, list) {
+            for value in list.array() {
+                let (value_type, value) = value?;
+                if value_type != DEVLINK_ATTR_PARAM_VALUE {
+                    continue;
+                }
+                let mut cmode = None;
+                let mut data = None;
+                for a in value.nested()? {
+                    match a.attr_type() {
+                        DEVLINK_ATTR_PARAM_VALUE_CMODE => cmode = Some(a.get_u8()?),
+                        DEVLINK_ATTR_PARAM_VALUE_DATA => data = Some(a),
+                        _ => {},
+                    }
+                }
+                if let Some(cmode) = cmode {
+                    param.values.push((cmode.into(), ParamValue::from_data(nla_type, data)?));
+                }
+            }
+        }
+        Ok(param)
+    }
+
+    pub fn device(&self) -> &DevlinkDevice {
+        &self.device
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether the parameter is defined by devlink rather than the driver
+    pub fn is_generic(&self) -> bool {
+        self.generic
+    }
+
+    /// Values, one per supported configuration mode
+    pub fn values(&self) -> &[(ParamCmode, ParamValue)] {
+        &self.values
+    }
+
+    pub fn value(&self, cmode: ParamCmode) -> Option<&ParamValue> {
+        self.values.iter().find(|v| v.0 == cmode).map(|v| &v.1)
+    }
+}
+
+fn param_attrs(device: &DevlinkDevice, name: &str) -> Vec<u8> {
+    let mut attrs = device.attrs();
+    attrs.extend(string_attr(DEVLINK_ATTR_PARAM_NAME, name));
+    attrs
+}
+
+impl<T: NetlinkTransport> Devlink<T> {
+    /// Lists the parameters of every device (DEVLINK_CMD_PARAM_GET dump).
+    pub fn params(&mut self) -> io::Result<Vec<DevlinkParam>> {
+        let replies = self.family.dump(&mut self.socket, DEVLINK_CMD_PARAM_GET, &[])?;
+        replies.iter().map(|r| DevlinkParam::from_attrs(r)).collect()
+    }
+
+    pub fn param(&mut self, device: &DevlinkDevice, name: &str) -> io::Result<DevlinkParam> {
+        let attrs = param_attrs(device, name);
+        let replies = self.family.request(&mut self.socket, DEVLINK_CMD_PARAM_GET, &attrs)?;
+        match replies.first() {
+            Some(reply) => DevlinkParam::from_attrs(reply),
+            None => Err(io::Error::new(ErrorKind::NotFound, "no param reply")),
+        }
+    }
+
+    /// Sets the `cmode` value of a parameter. The value must have the type
+    /// of the parameter.
+    pub fn set_param(&mut self, device: &DevlinkDevice, name: &str, cmode: ParamCmode, value: &ParamValue)
+        -> io::Result<()> {
+            let mut attrs = param_attrs(device, name);
+            attrs.extend(NlAttr::new(DEVLINK_ATTR_PARAM_TYPE, &[value.nla_type()]).bytes());
+            attrs.extend(value.data_attr());
+            attrs.extend(NlAttr::new(DEVLINK_ATTR_PARAM_VALUE_CMODE, &[u8::from(cmode)]).bytes());
+            self.family.request(&mut self.socket, DEVLINK_CMD_PARAM_SET, &attrs)?;
+            Ok(())
+        }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket::NLA_F_NESTED;
+
+    fn param_value(cmode: ParamCmode, value: &ParamValue) -> Vec<u8> {
+        let mut bytes = NlAttr::new(DEVLINK_ATTR_PARAM_VALUE_CMODE, &[u8::from(cmode)]).bytes();
+        bytes.extend(value.data_attr());
+        NlAttr::new(DEVLINK_ATTR_PARAM_VALUE | NLA_F_NESTED, &bytes).bytes()
+    }
+
+    #[test]
+    fn test_param_decode() {
+        let dev = DevlinkDevice::new("pci", "0000:03:00.0");
+        let mut list = param_value(ParamCmode::Runtime, &ParamValue::Bool(false));
+        list.extend(param_value(ParamCmode::DriverInit, &ParamValue::Bool(true)));
+        let mut nested = NlAttr::new(DEVLINK_ATTR_PARAM_NAME, b"enable_sriov\0").bytes();
+        nested.extend(NlAttr::new(DEVLINK_ATTR_PARAM_GENERIC, &[]).bytes());
+        nested.extend(NlAttr::new(DEVLINK_ATTR_PARAM_TYPE, &[NLA_FLAG]).bytes());
+        nested.extend(NlAttr::new(DEVLINK_ATTR_PARAM_VALUES_LIST | NLA_F_NESTED, &list).bytes());
+        let mut bytes = dev.attrs();
+        bytes.extend(NlAttr::new(DEVLINK_ATTR_PARAM | NLA_F_NESTED, &nested).bytes());
+
+        let param = DevlinkParam::from_attrs(&bytes).unwrap();
+        assert_eq!(param.device(), &dev);
+        assert_eq!(param.name(), "enable_sriov");
+        assert!(param.is_generic());
+        assert_eq!(param.values().len(), 2);
+        assert_eq!(param.value(ParamCmode::Runtime), Some(&ParamValue::Bool(false)));
+        assert_eq!(param.value(ParamCmode::DriverInit), Some(&ParamValue::Bool(true)));
+        assert_eq!(param.value(ParamCmode::Permanent), None);
+    }
+
+    #[test]
+    fn test_param_value_data() {
+        for value in &[ParamValue::U8(7), ParamValue::U16(300), ParamValue::U32(1 << 20),
+                       ParamValue::String("flow".into())] {
+            let bytes = value.data_attr();
+            let (attr, _) = NlAttr::from_bytes(&bytes).unwrap();
+            assert_eq!(&ParamValue::from_data(value.nla_type(), Some(attr)).unwrap(), value);
+        }
+        assert!(ParamValue::Bool(false).data_attr().is_empty());
+        assert!(ParamValue::from_data(NLA_U32, None).is_err());
+    }
+}
@@ -0,0 +1,195 @@
+use super::{Devlink, DevlinkDevice};
+use socket::{NetlinkTransport, NlAttr};
+
+use std::io;
+
+const DEVLINK_CMD_RESOURCE_SET: u8 = 35;
+const DEVLINK_CMD_RESOURCE_DUMP: u8 = 36;
+
+const DEVLINK_ATTR_RESOURCE_LIST: u16 = 63;
+const DEVLINK_ATTR_RESOURCE: u16 = 64;
+const DEVLINK_ATTR_RESOURCE_NAME: u16 = 65;
+const DEVLINK_ATTR_RESOURCE_ID: u16 = 66;
+const DEVLINK_ATTR_RESOURCE_SIZE: u16 = 67;
+const DEVLINK_ATTR_RESOURCE_SIZE_NEW: u16 = 68;
+const DEVLINK_ATTR_RESOURCE_SIZE_VALID: u16 = 69;
+const DEVLINK_ATTR_RESOURCE_SIZE_MIN: u16 = 70;
+const DEVLINK_ATTR_RESOURCE_SIZE_MAX: u16 = 71;
+const DEVLINK_ATTR_RESOURCE_SIZE_GRAN: u16 = 72;
+const DEVLINK_ATTR_RESOURCE_OCC: u16 = 74;
+
+/// A partition of a device resource, e.g. the KVD memory of a switch ASIC,
+/// with the partitions it is split into
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct DevlinkResource {
+    name: String,
+    id: u64,
+    size: u64,
+    size_new: Option<u64>,
+    size_valid: bool,
+    size_min: u64,
+    size_max: u64,
+    size_gran: u64,
+    occupancy: Option<u64>,
+    children: Vec<DevlinkResource>,
+}
+
+impl DevlinkResource {
+    fn from_attr(attr: &NlAttr) -> io::Result<DevlinkResource> {
+        let mut resource = DevlinkResource {
+            name: String::new(),
+            id: 0,
+            size: 0,
+            size_new: None,
+            size_valid: true,
+            size_min: 0,
+            size_max: 0,
+            size_gran: 1,
+            occupancy: None,
+            children: vec![],
+        };
+        for a in attr.nested()? {
+            match a.attr_type() {
+                DEVLINK_ATTR_RESOURCE_NAME => resource.name = a.get_str()?.to_string(),
+                DEVLINK_ATTR_RESOURCE_ID => resource.id = a.get_u64()?,
+                DEVLINK_ATTR_RESOURCE_SIZE => resource.size = a.get_u64()?,
+                DEVLINK_ATTR_RESOURCE_SIZE_NEW => resource.size_new = Some(a.get_u64()?),
+                DEVLINK_ATTR_RESOURCE_SIZE_VALID => resource.size_valid = a.get_u8()? != 0,
+                DEVLINK_ATTR_RESOURCE_SIZE_MIN => resource.size_min = a.get_u64()?,
+                DEVLINK_ATTR_RESOURCE_SIZE_MAX => resource.size_max = a.get_u64()?,
+                DEVLINK_ATTR_RESOURCE_SIZE_GRAN => resource.size_gran = a.get_u64()?,
+                DEVLINK_ATTR_RESOURCE_OCC => resource.occupancy = Some(a.get_u64()?),
+                DEVLINK_ATTR_RESOURCE_LIST => resource.children = resources_from_list(&a)?,
+                _ => {},
+            }
+        }
+        Ok(resource)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Id to resize the resource with
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Size in effect
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Size set but waiting for a reload
+    pub fn size_new(&self) -> Option<u64> {
+        self.size_new
+    }
+
+    /// Whether the sizes of the children add up within this resource
+    pub fn is_size_valid(&self) -> bool {
+        self.size_valid
+    }
+
+    pub fn size_min(&self) -> u64 {
+        self.size_min
+    }
+
+    pub fn size_max(&self) -> u64 {
+        self.size_max
+    }
+
+    /// Granularity sizes must be a multiple of
+    pub fn size_gran(&self) -> u64 {
+        self.size_gran
+    }
+
+    /// Amount in use, if the driver tracks it
+    pub fn occupancy(&self) -> Option<u64> {
+        self.occupancy
+    }
+
+    pub fn children(&self) -> &[DevlinkResource] {
+        &self.children
+    }
+
+    /// Looks up a descendant by path, e.g. `kvd/linear`.
+    pub fn find(&self, path: &str) -> Option<&DevlinkResource> {
+        let mut parts = path.splitn(2, '/');
+        let first = parts.next()?;
+        let child = self.children.iter().find(|c| c.name == first)?;
+        match parts.next() {
+            Some(rest) => child.find(rest),
+            None => Some(child),
+        }
+    }
+}
+
+fn resources_from_list(list: &NlAttr) -> io::Result<Vec<DevlinkResource>> {
+    let mut resources = vec![];
+    for entry in list.array() {
+        let (entry_type, entry) = entry?;
+        if entry_type == DEVLINK_ATTR_RESOURCE {
+            resources.push(DevlinkResource::from_attr(&entry)?);
+        }
+    }
+    Ok(resources)
+}
+
+impl<T: NetlinkTransport> Devlink<T> {
+    /// Returns the top level resources of `device` (DEVLINK_CMD_RESOURCE_DUMP).
+    pub fn resources(&mut self, device: &DevlinkDevice) -> io::Result<Vec<DevlinkResource>> {
+        let mut resources = vec![];
+        for reply in self.request_multipart(DEVLINK_CMD_RESOURCE_DUMP, &device.attrs())? {
+            for attr in NlAttr::parse(&reply)? {
+                if attr.attr_type() == DEVLINK_ATTR_RESOURCE_LIST {
+                    resources.extend(resources_from_list(&attr)?);
+                }
+            }
+        }
+        Ok(resources)
+    }
+
+    /// Resizes resource `id`; the size applies after the device is reloaded.
+    pub fn set_resource_size(&mut self, device: &DevlinkDevice, id: u64, size: u64) -> io::Result<()> {
+        let mut attrs = device.attrs();
+        attrs.extend(NlAttr::new(DEVLINK_ATTR_RESOURCE_ID, &id.to_ne_bytes()).bytes());
+        attrs.extend(NlAttr::new(DEVLINK_ATTR_RESOURCE_SIZE, &size.to_ne_bytes()).bytes());
+        self.family.request(&mut self.socket, DEVLINK_CMD_RESOURCE_SET, &attrs)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket::NLA_F_NESTED;
+
+    fn resource(name: &[u8], id: u64, size: u64, children: &[u8]) -> Vec<u8> {
+        let mut bytes = NlAttr::new(DEVLINK_ATTR_RESOURCE_NAME, name).bytes();
+        bytes.extend(NlAttr::new(DEVLINK_ATTR_RESOURCE_ID, &id.to_ne_bytes()).bytes());
+        bytes.extend(NlAttr::new(DEVLINK_ATTR_RESOURCE_SIZE, &size.to_ne_bytes()).bytes());
+        if !children.is_empty() {
+            bytes.extend(NlAttr::new(DEVLINK_ATTR_RESOURCE_LIST | NLA_F_NESTED, children).bytes());
+        }
+        NlAttr::new(DEVLINK_ATTR_RESOURCE | NLA_F_NESTED, &bytes).bytes()
+    }
+
+    #[test]
+    fn test_resource_tree() {
+        let mut children = resource(b"linear\0", 2, 1024, &[]);
+        children.extend(resource(b"hash_single\0", 3, 2048, &[]));
+        let kvd = resource(b"kvd\0", 1, 3072, &children);
+        let bytes = NlAttr::new(DEVLINK_ATTR_RESOURCE_LIST | NLA_F_NESTED, &kvd).bytes();
+        let (list, _) = NlAttr::from_bytes(&bytes).unwrap();
+
+        let resources = resources_from_list(&list).unwrap();
+        assert_eq!(resources.len(), 1);
+        assert_eq!((resources[0].name(), resources[0].size()), ("kvd", 3072));
+        assert_eq!(resources[0].children().len(), 2);
+        let linear = resources[0].find("linear").unwrap();
+        assert_eq!((linear.id(), linear.size()), (2, 1024));
+        assert!(resources[0].find("linear/none").is_none());
+        assert_eq!(linear.size_new(), None);
+    }
+}
@@ -0,0 +1,626 @@
+//! ethtool generic netlink family
+//!
+//! Netlink replacement for the `SIOCETHTOOL` ioctl interface, available
+//! since Linux 5.6.
+
+use super::GenlFamily;
+use socket::{Socket, NetlinkTransport, NlAttr, NLA_F_NESTED, string_attr};
+use Protocol;
+
+use std::io::{self, ErrorKind};
+
+const ETHTOOL_GENL_NAME: &str = "ethtool";
+
+const ETHTOOL_MSG_LINKMODES_GET: u8 = 4;
+const ETHTOOL_MSG_FEATURES_GET: u8 = 11;
+const ETHTOOL_MSG_FEATURES_SET: u8 = 12;
+const ETHTOOL_MSG_COALESCE_GET: u8 = 19;
+const ETHTOOL_MSG_COALESCE_SET: u8 = 20;
+
+const ETHTOOL_A_HEADER_DEV_NAME: u16 = 2;
+
+const ETHTOOL_A_BITSET_NOMASK: u16 = 1;
+const ETHTOOL_A_BITSET_SIZE: u16 = 2;
+const ETHTOOL_A_BITSET_BITS: u16 = 3;
+
+const ETHTOOL_A_BITSET_BITS_BIT: u16 = 1;
+
+const ETHTOOL_A_BIT_INDEX: u16 = 1;
+const ETHTOOL_A_BIT_NAME: u16 = 2;
+const ETHTOOL_A_BIT_VALUE: u16 = 3;
+
+const ETHTOOL_A_LINKMODES_HEADER: u16 = 1;
+const ETHTOOL_A_LINKMODES_AUTONEG: u16 = 2;
+const ETHTOOL_A_LINKMODES_OURS: u16 = 3;
+const ETHTOOL_A_LINKMODES_PEER: u16 = 4;
+const ETHTOOL_A_LINKMODES_SPEED: u16 = 5;
+const ETHTOOL_A_LINKMODES_DUPLEX: u16 = 6;
+
+const ETHTOOL_A_FEATURES_HW: u16 = 2;
+const ETHTOOL_A_FEATURES_WANTED: u16 = 3;
+const ETHTOOL_A_FEATURES_ACTIVE: u16 = 4;
+const ETHTOOL_A_FEATURES_NOCHANGE: u16 = 5;
+
+const ETHTOOL_A_COALESCE_RX_USECS: u16 = 2;
+const ETHTOOL_A_COALESCE_RX_MAX_FRAMES: u16 = 3;
+const ETHTOOL_A_COALESCE_TX_USECS: u16 = 6;
+const ETHTOOL_A_COALESCE_TX_MAX_FRAMES: u16 = 7;
+const ETHTOOL_A_COALESCE_USE_ADAPTIVE_RX: u16 = 11;
+const ETHTOOL_A_COALESCE_USE_ADAPTIVE_TX: u16 = 12;
+
+// #define SPEED_UNKNOWN       -1
+const SPEED_UNKNOWN: u32 = !0;
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum Duplex {
+    Half,
+    Full,
+    Unknown,
+}
+
+impl From<u8> for Duplex {
+    fn from(d: u8) -> Duplex {
+        match d {
+            0 => Duplex::Half,
+            1 => Duplex::Full,
+            _ => Duplex::Unknown,
+        }
+    }
+}
+
+/// One bit of a verbose ethtool bitset
+#[derive(Clone, Eq, PartialEq, Debug)]
+struct Bit {
+    index: u32,
+    name: String,
+    set: bool,
+}
+
+/// Verbose (non-compact) ethtool bitset
+///
+/// Without NOMASK the listed bits are the mask and `set` marks the value;
+/// with NOMASK only the bits set in the value are listed.
+#[derive(Clone, Eq, PartialEq, Debug)]
+struct Bitset {
+    size: u32,
+    bits: Vec<Bit>,
+}
+
+impl Bitset {
+    fn from_attr(attr: &NlAttr) -> io::Result<Bitset> {
+        let mut nomask = false;
+        let mut bitset = Bitset { size: 0, bits: vec![] };
+
+        for a in attr.nested()? {
+            match a.attr_type() {
+                ETHTOOL_A_BITSET_NOMASK => nomask = true,
+                ETHTOOL_A_BITSET_SIZE => {
+                    bitset.size = a.get_u32()?
+                },
+                ETHTOOL_A_BITSET_BITS => {
+                    for b in a.nested()? {
+                        if b.attr_type() != ETHTOOL_A_BITSET_BITS_BIT {
+                            continue
+                        }
+                        let mut bit = Bit { index: 0, name: String::new(), set: false };
+                        for f in b.nested()? {
+                            match f.attr_type() {
+                                ETHTOOL_A_BIT_INDEX => {
+                                    bit.index = f.get_u32()?
+                                },
+                                ETHTOOL_A_BIT_NAME => bit.name = f.get_str()?.to_string(),
+                                ETHTOOL_A_BIT_VALUE => bit.set = true,
+                                _ => {},
+                            }
+                        }
+                        bitset.bits.push(bit);
+                    }
+                },
+                _ => {},
+            }
+        }
+
+        if nomask {
+            for bit in &mut bitset.bits {
+                bit.set = true;
+            }
+        }
+        Ok(bitset)
+    }
+
+    /// Names of every listed bit
+    fn mask_names(&self) -> Vec<String> {
+        self.bits.iter().map(|b| b.name.clone()).collect()
+    }
+
+    /// Names of the bits set in the value
+    fn value_names(&self) -> Vec<String> {
+        self.bits.iter().filter(|b| b.set).map(|b| b.name.clone()).collect()
+    }
+
+    /// Encodes a verbose bitset changing the named `bits` to their value,
+    /// leaving the unlisted ones alone.
+    fn named_attr(attr_type: u16, bits: &[(&str, bool)]) -> Vec<u8> {
+        let mut list = vec![];
+        for &(name, set) in bits {
+            let mut bit = string_attr(ETHTOOL_A_BIT_NAME, name);
+            if set {
+                bit.extend(NlAttr::new(ETHTOOL_A_BIT_VALUE, &[]).bytes());
+            }
+            list.extend(NlAttr::new(ETHTOOL_A_BITSET_BITS_BIT | NLA_F_NESTED, &bit).bytes());
+        }
+        let bitset = NlAttr::new(ETHTOOL_A_BITSET_BITS | NLA_F_NESTED, &list).bytes();
+        NlAttr::new(attr_type | NLA_F_NESTED, &bitset).bytes()
+    }
+}
+
+/// Link modes and settings of a device (`ethtool <dev>`)
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct LinkModes {
+    autoneg: bool,
+    speed: Option<u32>,
+    duplex: Duplex,
+    supported: Vec<String>,
+    advertised: Vec<String>,
+    peer: Vec<String>,
+}
+
+impl LinkModes {
+    fn from_attrs(bytes: &[u8]) -> io::Result<LinkModes> {
+        let mut modes = LinkModes {
+            autoneg: false,
+            speed: None,
+            duplex: Duplex::Unknown,
+            supported: vec![],
+            advertised: vec![],
+            peer: vec![],
+        };
+
+        for attr in NlAttr::parse(bytes)? {
+            match attr.attr_type() {
+                ETHTOOL_A_LINKMODES_AUTONEG => modes.autoneg = attr.get_u8()? != 0,
+                ETHTOOL_A_LINKMODES_SPEED => {
+                    let speed = attr.get_u32()?;
+                    if speed != SPEED_UNKNOWN {
+                        modes.speed = Some(speed);
+                    }
+                },
+                ETHTOOL_A_LINKMODES_DUPLEX => modes.duplex = attr.get_u8()?.into(),
+                ETHTOOL_A_LINKMODES_OURS => {
+                    let ours = Bitset::from_attr(&attr)?;
+                    modes.supported = ours.mask_names();
+                    modes.advertised = ours.value_names();
+                },
+                ETHTOOL_A_LINKMODES_PEER => {
+                    modes.peer = Bitset::from_attr(&attr)?.value_names();
+                },
+                _ => {},
+            }
+        }
+
+        Ok(modes)
+    }
+
+    pub fn autoneg(&self) -> bool {
+        self.autoneg
+    }
+
+    /// Speed in Mb/s, `None` if unknown (e.g. no carrier)
+    pub fn speed(&self) -> Option<u32> {
+        self.speed
+    }
+
+    pub fn duplex(&self) -> Duplex {
+        self.duplex
+    }
+
+    /// Link modes supported by the device, e.g. `1000baseT/Full`
+    pub fn supported(&self) -> &[String] {
+        &self.supported
+    }
+
+    /// Link modes advertised by the device
+    pub fn advertised(&self) -> &[String] {
+        &self.advertised
+    }
+
+    /// Link modes advertised by the link partner
+    pub fn peer(&self) -> &[String] {
+        &self.peer
+    }
+}
+
+/// Offload features of a device (`ethtool -k`), by their ethtool names,
+/// e.g. `rx-gro` or `tx-tcp-segmentation`
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Features {
+    all: Vec<String>,
+    changeable: Vec<String>,
+    wanted: Vec<String>,
+    active: Vec<String>,
+    fixed: Vec<String>,
+}
+
+impl Features {
+    fn from_attrs(bytes: &[u8]) -> io::Result<Features> {
+        let mut features = Features {
+            all: vec![],
+            changeable: vec![],
+            wanted: vec![],
+            active: vec![],
+            fixed: vec![],
+        };
+
+        for attr in NlAttr::parse(bytes)? {
+            match attr.attr_type() {
+                ETHTOOL_A_FEATURES_HW => {
+                    let hw = Bitset::from_attr(&attr)?;
+                    features.all = hw.mask_names();
+                    features.changeable = hw.value_names();
+                },
+                ETHTOOL_A_FEATURES_WANTED => features.wanted = Bitset::from_attr(&attr)?.value_names(),
+                ETHTOOL_A_FEATURES_ACTIVE => features.active = Bitset::from_attr(&attr)?.value_names(),
+                ETHTOOL_A_FEATURES_NOCHANGE => features.fixed = Bitset::from_attr(&attr)?.value_names(),
+                _ => {},
+            }
+        }
+
+        Ok(features)
+    }
+
+    /// Every feature known to the kernel
+    pub fn all(&self) -> &[String] {
+        &self.all
+    }
+
+    /// Features the device lets the user toggle
+    pub fn changeable(&self) -> &[String] {
+        &self.changeable
+    }
+
+    /// Features requested by the user, whether or not they are in effect
+    pub fn wanted(&self) -> &[String] {
+        &self.wanted
+    }
+
+    /// Features in effect
+    pub fn active(&self) -> &[String] {
+        &self.active
+    }
+
+    /// Features that are never changed, such as `netns-local`
+    pub fn fixed(&self) -> &[String] {
+        &self.fixed
+    }
+
+    pub fn is_active(&self, name: &str) -> bool {
+        self.active.iter().any(|f| f == name)
+    }
+
+    pub fn is_changeable(&self, name: &str) -> bool {
+        self.changeable.iter().any(|f| f == name)
+    }
+}
+
+/// Interrupt coalescing parameters (`ethtool -c`)
+///
+/// Read back with only the parameters the driver supports set; to change
+/// them, set the ones to change and leave the others `None`.
+#[derive(Clone, Copy, Default, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Coalesce {
+    rx_usecs: Option<u32>,
+    rx_max_frames: Option<u32>,
+    tx_usecs: Option<u32>,
+    tx_max_frames: Option<u32>,
+    adaptive_rx: Option<bool>,
+    adaptive_tx: Option<bool>,
+}
+
+impl Coalesce {
+    pub fn new() -> Coalesce {
+        Coalesce::default()
+    }
+
+    fn from_attrs(bytes: &[u8]) -> io::Result<Coalesce> {
+        let mut coalesce = Coalesce::new();
+        for attr in NlAttr::parse(bytes)? {
+            match attr.attr_type() {
+                ETHTOOL_A_COALESCE_RX_USECS => coalesce.rx_usecs = Some(attr.get_u32()?),
+                ETHTOOL_A_COALESCE_RX_MAX_FRAMES => coalesce.rx_max_frames = Some(attr.get_u32()?),
+                ETHTOOL_A_COALESCE_TX_USECS => coalesce.tx_usecs = Some(attr.get_u32()?),
+                ETHTOOL_A_COALESCE_TX_MAX_FRAMES => coalesce.tx_max_frames = Some(attr.get_u32()?),
+                ETHTOOL_A_COALESCE_USE_ADAPTIVE_RX => coalesce.adaptive_rx = Some(attr.get_u8()? != 0),
+                ETHTOOL_A_COALESCE_USE_ADAPTIVE_TX => coalesce.adaptive_tx = Some(attr.get_u8()? != 0),
+                _ => {},
+            }
+        }
+        Ok(coalesce)
+    }
+
+    fn attrs(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        let words = [
+            (ETHTOOL_A_COALESCE_RX_USECS, self.rx_usecs),
+            (ETHTOOL_A_COALESCE_RX_MAX_FRAMES, self.rx_max_frames),
+            (ETHTOOL_A_COALESCE_TX_USECS, self.tx_usecs),
+            (ETHTOOL_A_COALESCE_TX_MAX_FRAMES, self.tx_max_frames),
+        ];
+        for &(attr_type, value) in &words {
+            if let Some(v) = value {
+                bytes.extend(NlAttr::new(attr_type, &v.to_ne_bytes()).bytes());
+            }
+        }
+        if let Some(on) = self.adaptive_rx {
+            bytes.extend(NlAttr::new(ETHTOOL_A_COALESCE_USE_ADAPTIVE_RX, &[on as u8]).bytes());
+        }
+        if let Some(on) = self.adaptive_tx {
+            bytes.extend(NlAttr::new(ETHTOOL_A_COALESCE_USE_ADAPTIVE_TX, &[on as u8]).bytes());
+        }
+        bytes
+    }
+
+    /// Set microseconds to delay an RX interrupt after a packet arrives
+    pub fn set_rx_usecs(&mut self, usecs: u32) -> &mut Coalesce {
+        self.rx_usecs = Some(usecs);
+        self
+    }
+
+    /// Set packets to receive before an RX interrupt
+    pub fn set_rx_max_frames(&mut self, frames: u32) -> &mut Coalesce {
+        self.rx_max_frames = Some(frames);
+        self
+    }
+
+    /// Set microseconds to delay a TX interrupt after a packet is sent
+    pub fn set_tx_usecs(&mut self, usecs: u32) -> &mut Coalesce {
+        self.tx_usecs = Some(usecs);
+        self
+    }
+
+    /// Set packets to send before a TX interrupt
+    pub fn set_tx_max_frames(&mut self, frames: u32) -> &mut Coalesce {
+        self.tx_max_frames = Some(frames);
+        self
+    }
+
+    /// Set whether the driver adapts RX coalescing to the load
+    pub fn set_adaptive_rx(&mut self, on: bool) -> &mut Coalesce {
+        self.adaptive_rx = Some(on);
+        self
+    }
+
+    /// Set whether the driver adapts TX coalescing to the load
+    pub fn set_adaptive_tx(&mut self, on: bool) -> &mut Coalesce {
+        self.adaptive_tx = Some(on);
+        self
+    }
+
+    pub fn rx_usecs(&self) -> Option<u32> {
+        self.rx_usecs
+    }
+
+    pub fn rx_max_frames(&self) -> Option<u32> {
+        self.rx_max_frames
+    }
+
+    pub fn tx_usecs(&self) -> Option<u32> {
+        self.tx_usecs
+    }
+
+    pub fn tx_max_frames(&self) -> Option<u32> {
+        self.tx_max_frames
+    }
+
+    pub fn adaptive_rx(&self) -> Option<bool> {
+        self.adaptive_rx
+    }
+
+    pub fn adaptive_tx(&self) -> Option<bool> {
+        self.adaptive_tx
+    }
+}
+
+/// Request header identifying the target device by name
+fn header(ifname: &str) -> Vec<u8> {
+    let dev = string_attr(ETHTOOL_A_HEADER_DEV_NAME, ifname);
+    NlAttr::new(ETHTOOL_A_LINKMODES_HEADER | NLA_F_NESTED, &dev).bytes()
+}
+
+/// Handle to the ethtool family
+pub struct Ethtool<T: NetlinkTransport = Socket> {
+    socket: T,
+    family: GenlFamily,
+}
+
+impl Ethtool {
+    pub fn new() -> io::Result<Ethtool> {
+        Ethtool::from_transport(Socket::new(Protocol::Generic)?)
+    }
+}
+
+impl<T: NetlinkTransport> Ethtool<T> {
+    /// Like `new`, but talks to the kernel through `transport`.
+    pub fn from_transport(mut transport: T) -> io::Result<Ethtool<T>> {
+        let family = GenlFamily::resolve(&mut transport, ETHTOOL_GENL_NAME)?;
+        Ok(Ethtool {
+            socket: transport,
+            family,
+        })
+    }
+
+    /// Fetches speed, duplex and link modes of `ifname` (ETHTOOL_MSG_LINKMODES_GET).
+    pub fn link_modes(&mut self, ifname: &str) -> io::Result<LinkModes> {
+        let replies = self.family.request(&mut self.socket, ETHTOOL_MSG_LINKMODES_GET,
+                                          &header(ifname))?;
+        match replies.first() {
+            Some(reply) => LinkModes::from_attrs(reply),
+            None => Err(io::Error::new(ErrorKind::NotFound, "no link modes reply")),
+        }
+    }
+
+    /// Fetches the offload features of `ifname` (ETHTOOL_MSG_FEATURES_GET).
+    pub fn features(&mut self, ifname: &str) -> io::Result<Features> {
+        let replies = self.family.request(&mut self.socket, ETHTOOL_MSG_FEATURES_GET,
+                                          &header(ifname))?;
+        match replies.first() {
+            Some(reply) => Features::from_attrs(reply),
+            None => Err(io::Error::new(ErrorKind::NotFound, "no features reply")),
+        }
+    }
+
+    /// Turns the named features of `ifname` on or off; the others are left
+    /// as they are. The kernel may not be able to honour every request, or
+    /// may change dependent features too: read back `features` to check.
+    pub fn set_features(&mut self, ifname: &str, features: &[(&str, bool)]) -> io::Result<()> {
+        let mut attrs = header(ifname);
+        attrs.extend(Bitset::named_attr(ETHTOOL_A_FEATURES_WANTED, features));
+        self.family.request(&mut self.socket, ETHTOOL_MSG_FEATURES_SET, &attrs)?;
+        Ok(())
+    }
+
+    /// Fetches the interrupt coalescing of `ifname` (ETHTOOL_MSG_COALESCE_GET).
+    /// Fails with EOPNOTSUPP for devices without coalescing support.
+    pub fn coalesce(&mut self, ifname: &str) -> io::Result<Coalesce> {
+        let replies = self.family.request(&mut self.socket, ETHTOOL_MSG_COALESCE_GET,
+                                          &header(ifname))?;
+        match replies.first() {
+            Some(reply) => Coalesce::from_attrs(reply),
+            None => Err(io::Error::new(ErrorKind::NotFound, "no coalesce reply")),
+        }
+    }
+
+    /// Changes the parameters set in `coalesce`; the driver rejects those it
+    /// does not support.
+    pub fn set_coalesce(&mut self, ifname: &str, coalesce: &Coalesce) -> io::Result<()> {
+        let mut attrs = header(ifname);
+        attrs.extend(coalesce.attrs());
+        self.family.request(&mut self.socket, ETHTOOL_MSG_COALESCE_SET, &attrs)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket::{NlAttr, NLA_F_NESTED};
+
+    fn bit(index: u32, name: &str, set: bool) -> Vec<u8> {
+        let mut b = NlAttr::new(ETHTOOL_A_BIT_INDEX, &index.to_ne_bytes()).bytes();
+        b.extend(string_attr(ETHTOOL_A_BIT_NAME, name));
+        if set {
+            b.extend(NlAttr::new(ETHTOOL_A_BIT_VALUE, &[]).bytes());
+        }
+        NlAttr::new(ETHTOOL_A_BITSET_BITS_BIT | NLA_F_NESTED, &b).bytes()
+    }
+
+    #[test]
+    fn test_header() {
+        let bytes = header("lo");
+        let attrs = NlAttr::parse(&bytes).unwrap();
+        assert_eq!(attrs.len(), 1);
+        assert!(attrs[0].is_nested());
+
+        let dev = attrs[0].nested().unwrap();
+        assert_eq!(dev[0].attr_type(), ETHTOOL_A_HEADER_DEV_NAME);
+        assert_eq!(dev[0].payload(), b"lo\0");
+    }
+
+    #[test]
+    fn test_link_modes_decode() {
+        let mut ours_bits = bit(5, "1000baseT/Full", true);
+        ours_bits.extend(bit(6, "Autoneg", false));
+        let mut ours = NlAttr::new(ETHTOOL_A_BITSET_SIZE, &92u32.to_ne_bytes()).bytes();
+        ours.extend(NlAttr::new(ETHTOOL_A_BITSET_BITS | NLA_F_NESTED, &ours_bits).bytes());
+
+        let peer_bits = bit(3, "100baseT/Full", false);
+        let mut peer = NlAttr::new(ETHTOOL_A_BITSET_NOMASK, &[]).bytes();
+        peer.extend(NlAttr::new(ETHTOOL_A_BITSET_BITS | NLA_F_NESTED, &peer_bits).bytes());
+
+        let mut bytes = header("eth0");
+        bytes.extend(NlAttr::new(ETHTOOL_A_LINKMODES_AUTONEG, &[1]).bytes());
+        bytes.extend(NlAttr::new(ETHTOOL_A_LINKMODES_OURS | NLA_F_NESTED, &ours).bytes());
+        bytes.extend(NlAttr::new(ETHTOOL_A_LINKMODES_PEER | NLA_F_NESTED, &peer).bytes());
+        bytes.extend(NlAttr::new(ETHTOOL_A_LINKMODES_SPEED, &1000u32.to_ne_bytes()).bytes());
+        bytes.extend(NlAttr::new(ETHTOOL_A_LINKMODES_DUPLEX, &[1]).bytes());
+
+        let modes = LinkModes::from_attrs(&bytes).unwrap();
+        assert!(modes.autoneg());
+        assert_eq!(modes.speed(), Some(1000));
+        assert_eq!(modes.duplex(), Duplex::Full);
+        assert_eq!(modes.supported(), &["1000baseT/Full", "Autoneg"]);
+        assert_eq!(modes.advertised(), &["1000baseT/Full"]);
+        assert_eq!(modes.peer(), &["100baseT/Full"]);
+    }
+
+    #[test]
+    fn test_features_decode() {
+        let mut hw_bits = bit(0, "tx-scatter-gather", true);
+        hw_bits.extend(bit(1, "netns-local", false));
+        hw_bits.extend(bit(2, "rx-gro", true));
+        let hw = NlAttr::new(ETHTOOL_A_BITSET_BITS | NLA_F_NESTED, &hw_bits).bytes();
+        let mut active = NlAttr::new(ETHTOOL_A_BITSET_NOMASK, &[]).bytes();
+        let mut active_bits = bit(1, "netns-local", false);
+        active_bits.extend(bit(2, "rx-gro", false));
+        active.extend(NlAttr::new(ETHTOOL_A_BITSET_BITS | NLA_F_NESTED, &active_bits).bytes());
+
+        let mut bytes = header("eth0");
+        bytes.extend(NlAttr::new(ETHTOOL_A_FEATURES_HW | NLA_F_NESTED, &hw).bytes());
+        bytes.extend(NlAttr::new(ETHTOOL_A_FEATURES_ACTIVE | NLA_F_NESTED, &active).bytes());
+
+        let features = Features::from_attrs(&bytes).unwrap();
+        assert_eq!(features.all().len(), 3);
+        assert_eq!(features.changeable(), &["tx-scatter-gather", "rx-gro"]);
+        assert!(features.is_active("rx-gro"));
+        assert!(!features.is_active("tx-scatter-gather"));
+        assert!(!features.is_changeable("netns-local"));
+        assert!(features.wanted().is_empty());
+    }
+
+    #[test]
+    fn test_named_bitset() {
+        let bits = [("rx-gro", false), ("tx-tcp-segmentation", true)];
+        let bytes = Bitset::named_attr(ETHTOOL_A_FEATURES_WANTED, &bits);
+        let (attr, _) = NlAttr::from_bytes(&bytes).unwrap();
+        assert_eq!(attr.attr_type() & !NLA_F_NESTED, ETHTOOL_A_FEATURES_WANTED);
+
+        // Listed bits are the mask of the change
+        let bitset = Bitset::from_attr(&attr).unwrap();
+        assert_eq!(bitset.mask_names(), &["rx-gro", "tx-tcp-segmentation"]);
+        assert_eq!(bitset.value_names(), &["tx-tcp-segmentation"]);
+    }
+
+    #[test]
+    fn test_loopback_features() {
+        let mut ethtool = Ethtool::new().unwrap();
+        let features = ethtool.features("lo").unwrap();
+        assert!(!features.all().is_empty());
+        assert!(features.is_active("loopback") || features.fixed().iter().any(|f| f == "loopback"));
+    }
+
+    #[test]
+    fn test_coalesce_roundtrip() {
+        let mut coalesce = Coalesce::new();
+        coalesce.set_rx_usecs(50).set_tx_max_frames(64).set_adaptive_rx(true);
+
+        let mut bytes = header("eth0");
+        bytes.extend(coalesce.attrs());
+        let decoded = Coalesce::from_attrs(&bytes).unwrap();
+        assert_eq!(decoded, coalesce);
+        assert_eq!(decoded.rx_usecs(), Some(50));
+        assert_eq!(decoded.tx_usecs(), None);
+        assert_eq!(decoded.adaptive_rx(), Some(true));
+        assert!(Coalesce::new().attrs().is_empty());
+    }
+
+    #[test]
+    fn test_unknown_speed() {
+        let bytes = NlAttr::new(ETHTOOL_A_LINKMODES_SPEED, &SPEED_UNKNOWN.to_ne_bytes()).bytes();
+        let modes = LinkModes::from_attrs(&bytes).unwrap();
+        assert_eq!(modes.speed(), None);
+        assert_eq!(modes.duplex(), Duplex::Unknown);
+    }
+}
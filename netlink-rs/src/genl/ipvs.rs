@@ -0,0 +1,757 @@
+//! IPVS generic netlink family
+//!
+//! Virtual services of the in-kernel layer 4 load balancer and the real
+//! servers (destinations) behind them (`ipvsadm`).
+
+use super::GenlFamily;
+use socket::{Socket, NetlinkTransport, NlAttr, AttrWriter};
+use Protocol;
+
+use std::io::{self, ErrorKind};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use libc::{AF_INET, AF_INET6, IPPROTO_TCP, IPPROTO_UDP, IPPROTO_SCTP};
+
+const IPVS_GENL_NAME: &str = "IPVS";
+
+const IPVS_CMD_NEW_SERVICE: u8 = 1;
+const IPVS_CMD_SET_SERVICE: u8 = 2;
+const IPVS_CMD_DEL_SERVICE: u8 = 3;
+const IPVS_CMD_GET_SERVICE: u8 = 4;
+const IPVS_CMD_NEW_DEST: u8 = 5;
+const IPVS_CMD_SET_DEST: u8 = 6;
+const IPVS_CMD_DEL_DEST: u8 = 7;
+const IPVS_CMD_GET_DEST: u8 = 8;
+const IPVS_CMD_FLUSH: u8 = 17;
+
+const IPVS_CMD_ATTR_SERVICE: u16 = 1;
+const IPVS_CMD_ATTR_DEST: u16 = 2;
+
+const IPVS_SVC_ATTR_AF: u16 = 1;
+const IPVS_SVC_ATTR_PROTOCOL: u16 = 2;
+const IPVS_SVC_ATTR_ADDR: u16 = 3;
+const IPVS_SVC_ATTR_PORT: u16 = 4;
+const IPVS_SVC_ATTR_FWMARK: u16 = 5;
+const IPVS_SVC_ATTR_SCHED_NAME: u16 = 6;
+const IPVS_SVC_ATTR_FLAGS: u16 = 7;
+const IPVS_SVC_ATTR_TIMEOUT: u16 = 8;
+const IPVS_SVC_ATTR_NETMASK: u16 = 9;
+const IPVS_SVC_ATTR_STATS: u16 = 10;
+const IPVS_SVC_ATTR_STATS64: u16 = 12;
+
+const IPVS_DEST_ATTR_ADDR: u16 = 1;
+const IPVS_DEST_ATTR_PORT: u16 = 2;
+const IPVS_DEST_ATTR_FWD_METHOD: u16 = 3;
+const IPVS_DEST_ATTR_WEIGHT: u16 = 4;
+const IPVS_DEST_ATTR_U_THRESH: u16 = 5;
+const IPVS_DEST_ATTR_L_THRESH: u16 = 6;
+const IPVS_DEST_ATTR_ACTIVE_CONNS: u16 = 7;
+const IPVS_DEST_ATTR_INACT_CONNS: u16 = 8;
+const IPVS_DEST_ATTR_PERSIST_CONNS: u16 = 9;
+const IPVS_DEST_ATTR_STATS: u16 = 10;
+const IPVS_DEST_ATTR_ADDR_FAMILY: u16 = 11;
+const IPVS_DEST_ATTR_STATS64: u16 = 12;
+
+const IPVS_STATS_ATTR_CONNS: u16 = 1;
+const IPVS_STATS_ATTR_INPKTS: u16 = 2;
+const IPVS_STATS_ATTR_OUTPKTS: u16 = 3;
+const IPVS_STATS_ATTR_INBYTES: u16 = 4;
+const IPVS_STATS_ATTR_OUTBYTES: u16 = 5;
+const IPVS_STATS_ATTR_CPS: u16 = 6;
+
+const IP_VS_SVC_F_PERSISTENT: u32 = 1;
+
+/// Connection scheduler of a service
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum Scheduler {
+    RoundRobin,
+    WeightedRoundRobin,
+    LeastConnection,
+    WeightedLeastConnection,
+    SourceHashing,
+    DestinationHashing,
+    /// Maglev hashing
+    Maglev,
+    ShortestExpectedDelay,
+    NeverQueue,
+    Other(String),
+}
+
+impl Scheduler {
+    /// Name of the kernel module, without the `ip_vs_` prefix
+    pub fn name(&self) -> &str {
+        use self::Scheduler::*;
+        match *self {
+            RoundRobin => "rr",
+            WeightedRoundRobin => "wrr",
+            LeastConnection => "lc",
+            WeightedLeastConnection => "wlc",
+            SourceHashing => "sh",
+            DestinationHashing => "dh",
+            Maglev => "mh",
+            ShortestExpectedDelay => "sed",
+            NeverQueue => "nq",
+            Other(ref s) => s,
+        }
+    }
+}
+
+impl From<&str> for Scheduler {
+    fn from(s: &str) -> Scheduler {
+        use self::Scheduler::*;
+        match s {
+            "rr" => RoundRobin,
+            "wrr" => WeightedRoundRobin,
+            "lc" => LeastConnection,
+            "wlc" => WeightedLeastConnection,
+            "sh" => SourceHashing,
+            "dh" => DestinationHashing,
+            "mh" => Maglev,
+            "sed" => ShortestExpectedDelay,
+            "nq" => NeverQueue,
+            s => Other(s.into()),
+        }
+    }
+}
+
+/// Transport protocol of a service
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum ServiceProtocol {
+    Tcp,
+    Udp,
+    Sctp,
+    Other(u16),
+}
+
+impl From<ServiceProtocol> for u16 {
+    fn from(p: ServiceProtocol) -> u16 {
+        match p {
+            ServiceProtocol::Tcp => IPPROTO_TCP as u16,
+            ServiceProtocol::Udp => IPPROTO_UDP as u16,
+            ServiceProtocol::Sctp => IPPROTO_SCTP as u16,
+            ServiceProtocol::Other(i) => i,
+        }
+    }
+}
+
+impl From<u16> for ServiceProtocol {
+    fn from(p: u16) -> ServiceProtocol {
+        match p as i32 {
+            IPPROTO_TCP => ServiceProtocol::Tcp,
+            IPPROTO_UDP => ServiceProtocol::Udp,
+            IPPROTO_SCTP => ServiceProtocol::Sctp,
+            _ => ServiceProtocol::Other(p),
+        }
+    }
+}
+
+/// How packets are forwarded to a destination
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum ForwardMethod {
+    /// NAT
+    Masquerade,
+    Local,
+    /// IP-in-IP encapsulation
+    Tunnel,
+    /// Direct routing, the destination shares the virtual address
+    DirectRoute,
+    Bypass,
+    Other(u32),
+}
+
+impl From<ForwardMethod> for u32 {
+    fn from(m: ForwardMethod) -> u32 {
+        use self::ForwardMethod::*;
+        match m {
+            Masquerade  => 0,
+            Local       => 1,
+            Tunnel      => 2,
+            DirectRoute => 3,
+            Bypass      => 4,
+            Other(i)    => i,
+        }
+    }
+}
+
+impl From<u32> for ForwardMethod {
+    fn from(m: u32) -> ForwardMethod {
+        use self::ForwardMethod::*;
+        match m {
+            0 => Masquerade,
+            1 => Local,
+            2 => Tunnel,
+            3 => DirectRoute,
+            4 => Bypass,
+            i => Other(i),
+        }
+    }
+}
+
+/// Traffic counters of a service or destination
+#[derive(Clone, Copy, Default, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct IpvsStats {
+    conns: u64,
+    inpkts: u64,
+    outpkts: u64,
+    inbytes: u64,
+    outbytes: u64,
+    cps: u64,
+}
+
+impl IpvsStats {
+    /// Decodes IPVS_*_ATTR_STATS64, or with `wide` unset the older
+    /// IPVS_*_ATTR_STATS with 32 bit packet counters and rates.
+    fn from_attr(attr: &NlAttr, wide: bool) -> io::Result<IpvsStats> {
+        let mut stats = IpvsStats::default();
+        for a in attr.nested()? {
+            let value = match a.attr_type() {
+                IPVS_STATS_ATTR_INBYTES | IPVS_STATS_ATTR_OUTBYTES => a.get_u64()?,
+                _ if wide => a.get_u64()?,
+                _ => u64::from(a.get_u32()?),
+            };
+            match a.attr_type() {
+                IPVS_STATS_ATTR_CONNS => stats.conns = value,
+                IPVS_STATS_ATTR_INPKTS => stats.inpkts = value,
+                IPVS_STATS_ATTR_OUTPKTS => stats.outpkts = value,
+                IPVS_STATS_ATTR_INBYTES => stats.inbytes = value,
+                IPVS_STATS_ATTR_OUTBYTES => stats.outbytes = value,
+                IPVS_STATS_ATTR_CPS => stats.cps = value,
+                _ => {},
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Connections scheduled
+    pub fn conns(&self) -> u64 {
+        self.conns
+    }
+
+    pub fn inpkts(&self) -> u64 {
+        self.inpkts
+    }
+
+    pub fn outpkts(&self) -> u64 {
+        self.outpkts
+    }
+
+    pub fn inbytes(&self) -> u64 {
+        self.inbytes
+    }
+
+    pub fn outbytes(&self) -> u64 {
+        self.outbytes
+    }
+
+    /// Connections per second, as estimated by the kernel
+    pub fn cps(&self) -> u64 {
+        self.cps
+    }
+}
+
+fn af(ipv6: bool) -> u16 {
+    if ipv6 { AF_INET6 as u16 } else { AF_INET as u16 }
+}
+
+fn parse_addr(payload: &[u8], af: u16) -> io::Result<IpAddr> {
+    // union nf_inet_addr, only the first 4 bytes are used by IPv4
+    if af == AF_INET6 as u16 && payload.len() >= 16 {
+        let mut octets = [0u8; 16];
+        octets.copy_from_slice(&payload[..16]);
+        Ok(IpAddr::V6(Ipv6Addr::from(octets)))
+    } else if af != AF_INET6 as u16 && payload.len() >= 4 {
+        Ok(IpAddr::V4(Ipv4Addr::new(payload[0], payload[1], payload[2], payload[3])))
+    } else {
+        Err(io::Error::new(ErrorKind::InvalidData, "bad address length"))
+    }
+}
+
+fn addr_bytes(addr: IpAddr) -> Vec<u8> {
+    match addr {
+        IpAddr::V4(a) => a.octets().to_vec(),
+        IpAddr::V6(a) => a.octets().to_vec(),
+    }
+}
+
+fn svc_flags(payload: &[u8]) -> io::Result<u32> {
+    // struct ip_vs_flags { flags, mask }
+    match payload.get(..4) {
+        Some(b) => Ok(u32::from_ne_bytes([b[0], b[1], b[2], b[3]])),
+        None => Err(io::Error::new(ErrorKind::InvalidData, "bad flags length")),
+    }
+}
+
+/// A virtual service, identified by protocol, address and port, or by
+/// firewall mark
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Service {
+    ipv6: bool,
+    protocol: ServiceProtocol,
+    addr: Option<SocketAddr>,
+    fwmark: Option<u32>,
+    scheduler: Scheduler,
+    flags: u32,
+    timeout: u32,
+    netmask: u32,
+    stats: Option<IpvsStats>,
+}
+
+impl Service {
+    pub fn new(protocol: ServiceProtocol, addr: SocketAddr) -> Service {
+        let mut service = Service::with_fwmark(0, addr.is_ipv6());
+        service.protocol = protocol;
+        service.addr = Some(addr);
+        service.fwmark = None;
+        service
+    }
+
+    /// Service matching the packets marked with `mark`, e.g. by iptables
+    pub fn with_fwmark(mark: u32, ipv6: bool) -> Service {
+        Service {
+            ipv6,
+            protocol: ServiceProtocol::Other(0),
+            addr: None,
+            fwmark: Some(mark),
+            scheduler: Scheduler::WeightedLeastConnection,
+            flags: 0,
+            timeout: 0,
+            netmask: if ipv6 { 128 } else { 0xffff_ffff },
+            stats: None,
+        }
+    }
+
+    fn from_attr(attr: &NlAttr) -> io::Result<Service> {
+        let attrs = attr.nested()?;
+        let family = match attrs.iter().find(|a| a.attr_type() == IPVS_SVC_ATTR_AF) {
+            Some(a) => a.get_u16()?,
+            None => AF_INET as u16,
+        };
+        let mut service = Service::with_fwmark(0, family == AF_INET6 as u16);
+        service.fwmark = None;
+        let mut addr = None;
+        let mut port = 0;
+        for a in &attrs {
+            match a.attr_type() {
+                IPVS_SVC_ATTR_PROTOCOL => service.protocol = ServiceProtocol::from(a.get_u16()?),
+                IPVS_SVC_ATTR_ADDR => addr = Some(parse_addr(a.payload(), family)?),
+                // be16 without NLA_F_NET_BYTEORDER
+                IPVS_SVC_ATTR_PORT => port = u16::from_be(a.get_u16()?),
+                IPVS_SVC_ATTR_FWMARK => {
+                    let mark = a.get_u32()?;
+                    if mark != 0 {
+                        service.fwmark = Some(mark);
+                    }
+                },
+                IPVS_SVC_ATTR_SCHED_NAME => service.scheduler = Scheduler::from(a.get_str()?),
+                IPVS_SVC_ATTR_FLAGS => service.flags = svc_flags(a.payload())?,
+                IPVS_SVC_ATTR_TIMEOUT => service.timeout = a.get_u32()?,
+                IPVS_SVC_ATTR_NETMASK => service.netmask = a.get_u32()?,
+                IPVS_SVC_ATTR_STATS if service.stats.is_none() => {
+                    service.stats = Some(IpvsStats::from_attr(a, false)?)
+                },
+                IPVS_SVC_ATTR_STATS64 => service.stats = Some(IpvsStats::from_attr(a, true)?),
+                _ => {},
+            }
+        }
+        if service.fwmark.is_none() {
+            service.addr = addr.map(|a| SocketAddr::new(a, port));
+        }
+        Ok(service)
+    }
+
+    /// Writes the attributes identifying the service, plus its settings
+    /// with `full`, as a nested IPVS_CMD_ATTR_SERVICE attribute.
+    fn put_attr(&self, w: &mut AttrWriter, full: bool) -> io::Result<()> {
+        w.begin_nested(IPVS_CMD_ATTR_SERVICE)?.put_u16(IPVS_SVC_ATTR_AF, af(self.ipv6))?;
+        match (self.fwmark, self.addr) {
+            (Some(mark), _) => {
+                w.put_u32(IPVS_SVC_ATTR_FWMARK, mark)?;
+            },
+            (None, addr) => {
+                let addr = addr.unwrap_or_else(|| SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0));
+                w.put_u16(IPVS_SVC_ATTR_PROTOCOL, u16::from(self.protocol))?
+                    .put(IPVS_SVC_ATTR_ADDR, &addr_bytes(addr.ip()))?
+                    .put_u16(IPVS_SVC_ATTR_PORT, addr.port().to_be())?;
+            },
+        }
+        if full {
+            // struct ip_vs_flags { flags, mask }
+            let mut flags = self.flags.to_ne_bytes().to_vec();
+            flags.extend_from_slice(&(!0u32).to_ne_bytes());
+            w.put_str(IPVS_SVC_ATTR_SCHED_NAME, self.scheduler.name())?
+                .put(IPVS_SVC_ATTR_FLAGS, &flags)?
+                .put_u32(IPVS_SVC_ATTR_TIMEOUT, self.timeout)?
+                .put_u32(IPVS_SVC_ATTR_NETMASK, self.netmask)?;
+        }
+        w.end_nested()?;
+        Ok(())
+    }
+
+    pub fn set_scheduler(&mut self, scheduler: Scheduler) -> &mut Service {
+        self.scheduler = scheduler;
+        self
+    }
+
+    /// Set persistence, sending a client to the same destination for
+    /// `timeout` seconds
+    pub fn set_persistent(&mut self, timeout: u32) -> &mut Service {
+        self.flags |= IP_VS_SVC_F_PERSISTENT;
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set mask grouping client addresses for persistence, a prefix
+    /// length for IPv6
+    pub fn set_netmask(&mut self, netmask: u32) -> &mut Service {
+        self.netmask = netmask;
+        self
+    }
+
+    pub fn is_ipv6(&self) -> bool {
+        self.ipv6
+    }
+
+    pub fn protocol(&self) -> ServiceProtocol {
+        self.protocol
+    }
+
+    /// Virtual address, unless the service is matched by mark
+    pub fn addr(&self) -> Option<SocketAddr> {
+        self.addr
+    }
+
+    pub fn fwmark(&self) -> Option<u32> {
+        self.fwmark
+    }
+
+    pub fn scheduler(&self) -> &Scheduler {
+        &self.scheduler
+    }
+
+    pub fn is_persistent(&self) -> bool {
+        self.flags & IP_VS_SVC_F_PERSISTENT != 0
+    }
+
+    /// Persistence timeout in seconds
+    pub fn timeout(&self) -> u32 {
+        self.timeout
+    }
+
+    pub fn netmask(&self) -> u32 {
+        self.netmask
+    }
+
+    /// Counters, as reported by the kernel
+    pub fn stats(&self) -> Option<&IpvsStats> {
+        self.stats.as_ref()
+    }
+}
+
+/// A real server behind a service
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Destination {
+    addr: SocketAddr,
+    forward: ForwardMethod,
+    weight: u32,
+    upper_threshold: u32,
+    lower_threshold: u32,
+    active_conns: u32,
+    inactive_conns: u32,
+    persist_conns: u32,
+    stats: Option<IpvsStats>,
+}
+
+impl Destination {
+    pub fn new(addr: SocketAddr) -> Destination {
+        Destination {
+            addr,
+            forward: ForwardMethod::Masquerade,
+            weight: 1,
+            upper_threshold: 0,
+            lower_threshold: 0,
+            active_conns: 0,
+            inactive_conns: 0,
+            persist_conns: 0,
+            stats: None,
+        }
+    }
+
+    fn from_attr(attr: &NlAttr, service_af: u16) -> io::Result<Destination> {
+        let attrs = attr.nested()?;
+        // Destinations of another family than the service need a recent kernel
+        let family = match attrs.iter().find(|a| a.attr_type() == IPVS_DEST_ATTR_ADDR_FAMILY) {
+            Some(a) => a.get_u16()?,
+            None => service_af,
+        };
+        let mut dest = Destination::new(SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0));
+        let mut port = 0;
+        for a in &attrs {
+            match a.attr_type() {
+                IPVS_DEST_ATTR_ADDR => dest.addr.set_ip(parse_addr(a.payload(), family)?),
+                IPVS_DEST_ATTR_PORT => port = u16::from_be(a.get_u16()?),
+                IPVS_DEST_ATTR_FWD_METHOD => dest.forward = ForwardMethod::from(a.get_u32()?),
+                IPVS_DEST_ATTR_WEIGHT => dest.weight = a.get_u32()?,
+                IPVS_DEST_ATTR_U_THRESH => dest.upper_threshold = a.get_u32()?,
+                IPVS_DEST_ATTR_L_THRESH => dest.lower_threshold = a.get_u32()?,
+                IPVS_DEST_ATTR_ACTIVE_CONNS => dest.active_conns = a.get_u32()?,
+                IPVS_DEST_ATTR_INACT_CONNS => dest.inactive_conns = a.get_u32()?,
+                IPVS_DEST_ATTR_PERSIST_CONNS => dest.persist_conns = a.get_u32()?,
+                IPVS_DEST_ATTR_STATS if dest.stats.is_none() => dest.stats = Some(IpvsStats::from_attr(a, false)?),
+                IPVS_DEST_ATTR_STATS64 => dest.stats = Some(IpvsStats::from_attr(a, true)?),
+                _ => {},
+            }
+        }
+        dest.addr.set_port(port);
+        Ok(dest)
+    }
+
+    /// Writes the destination, with its settings if `full`, as a nested
+    /// IPVS_CMD_ATTR_DEST attribute.
+    fn put_attr(&self, w: &mut AttrWriter, full: bool) -> io::Result<()> {
+        w.begin_nested(IPVS_CMD_ATTR_DEST)?;
+        self.put_fields(w, full)?;
+        w.end_nested()?;
+        Ok(())
+    }
+
+    fn put_fields(&self, w: &mut AttrWriter, full: bool) -> io::Result<()> {
+        w.put(IPVS_DEST_ATTR_ADDR, &addr_bytes(self.addr.ip()))?
+            .put_u16(IPVS_DEST_ATTR_PORT, self.addr.port().to_be())?
+            .put_u16(IPVS_DEST_ATTR_ADDR_FAMILY, af(self.addr.is_ipv6()))?;
+        if full {
+            w.put_u32(IPVS_DEST_ATTR_FWD_METHOD, u32::from(self.forward))?
+                .put_u32(IPVS_DEST_ATTR_WEIGHT, self.weight)?
+                .put_u32(IPVS_DEST_ATTR_U_THRESH, self.upper_threshold)?
+                .put_u32(IPVS_DEST_ATTR_L_THRESH, self.lower_threshold)?;
+        }
+        Ok(())
+    }
+
+    pub fn set_forward(&mut self, forward: ForwardMethod) -> &mut Destination {
+        self.forward = forward;
+        self
+    }
+
+    /// Set weight, 0 to stop scheduling new connections
+    pub fn set_weight(&mut self, weight: u32) -> &mut Destination {
+        self.weight = weight;
+        self
+    }
+
+    /// Set connection thresholds to stop and resume scheduling at, 0 for
+    /// none
+    pub fn set_thresholds(&mut self, upper: u32, lower: u32) -> &mut Destination {
+        self.upper_threshold = upper;
+        self.lower_threshold = lower;
+        self
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn forward(&self) -> ForwardMethod {
+        self.forward
+    }
+
+    pub fn weight(&self) -> u32 {
+        self.weight
+    }
+
+    pub fn upper_threshold(&self) -> u32 {
+        self.upper_threshold
+    }
+
+    pub fn lower_threshold(&self) -> u32 {
+        self.lower_threshold
+    }
+
+    pub fn active_conns(&self) -> u32 {
+        self.active_conns
+    }
+
+    pub fn inactive_conns(&self) -> u32 {
+        self.inactive_conns
+    }
+
+    pub fn persist_conns(&self) -> u32 {
+        self.persist_conns
+    }
+
+    /// Counters, as reported by the kernel
+    pub fn stats(&self) -> Option<&IpvsStats> {
+        self.stats.as_ref()
+    }
+}
+
+/// Handle to the IPVS family
+pub struct Ipvs<T: NetlinkTransport = Socket> {
+    socket: T,
+    family: GenlFamily,
+}
+
+impl Ipvs {
+    pub fn new() -> io::Result<Ipvs> {
+        Ipvs::from_transport(Socket::new(Protocol::Generic)?)
+    }
+}
+
+impl<T: NetlinkTransport> Ipvs<T> {
+    /// Like `new`, but talks to the kernel through `transport`.
+    pub fn from_transport(mut transport: T) -> io::Result<Ipvs<T>> {
+        let family = GenlFamily::resolve(&mut transport, IPVS_GENL_NAME)?;
+        Ok(Ipvs {
+            socket: transport,
+            family,
+        })
+    }
+
+    /// Lists the virtual services (IPVS_CMD_GET_SERVICE dump).
+    pub fn services(&mut self) -> io::Result<Vec<Service>> {
+        let replies = self.family.dump(&mut self.socket, IPVS_CMD_GET_SERVICE, &[])?;
+        let mut services = vec![];
+        for reply in replies {
+            for attr in NlAttr::parse(&reply)? {
+                if attr.attr_type() == IPVS_CMD_ATTR_SERVICE {
+                    services.push(Service::from_attr(&attr)?);
+                }
+            }
+        }
+        Ok(services)
+    }
+
+    pub fn add_service(&mut self, service: &Service) -> io::Result<()> {
+        let mut w = AttrWriter::new();
+        service.put_attr(&mut w, true)?;
+        self.family.request(&mut self.socket, IPVS_CMD_NEW_SERVICE, w.bytes())?;
+        Ok(())
+    }
+
+    /// Updates the scheduler and persistence of an existing service.
+    pub fn set_service(&mut self, service: &Service) -> io::Result<()> {
+        let mut w = AttrWriter::new();
+        service.put_attr(&mut w, true)?;
+        self.family.request(&mut self.socket, IPVS_CMD_SET_SERVICE, w.bytes())?;
+        Ok(())
+    }
+
+    /// Deletes a service with its destinations.
+    pub fn del_service(&mut self, service: &Service) -> io::Result<()> {
+        let mut w = AttrWriter::new();
+        service.put_attr(&mut w, false)?;
+        self.family.request(&mut self.socket, IPVS_CMD_DEL_SERVICE, w.bytes())?;
+        Ok(())
+    }
+
+    /// Lists the destinations of `service` (IPVS_CMD_GET_DEST dump).
+    pub fn destinations(&mut self, service: &Service) -> io::Result<Vec<Destination>> {
+        let mut w = AttrWriter::new();
+        service.put_attr(&mut w, false)?;
+        let replies = self.family.dump(&mut self.socket, IPVS_CMD_GET_DEST, w.bytes())?;
+        let mut dests = vec![];
+        for reply in replies {
+            for attr in NlAttr::parse(&reply)? {
+                if attr.attr_type() == IPVS_CMD_ATTR_DEST {
+                    dests.push(Destination::from_attr(&attr, af(service.is_ipv6()))?);
+                }
+            }
+        }
+        Ok(dests)
+    }
+
+    fn dest_request(&mut self, cmd: u8, service: &Service, dest: &Destination, full: bool) -> io::Result<()> {
+        let mut w = AttrWriter::new();
+        service.put_attr(&mut w, false)?;
+        dest.put_attr(&mut w, full)?;
+        self.family.request(&mut self.socket, cmd, w.bytes())?;
+        Ok(())
+    }
+
+    pub fn add_destination(&mut self, service: &Service, dest: &Destination) -> io::Result<()> {
+        self.dest_request(IPVS_CMD_NEW_DEST, service, dest, true)
+    }
+
+    /// Updates the weight, thresholds and forwarding of a destination.
+    pub fn set_destination(&mut self, service: &Service, dest: &Destination) -> io::Result<()> {
+        self.dest_request(IPVS_CMD_SET_DEST, service, dest, true)
+    }
+
+    pub fn del_destination(&mut self, service: &Service, dest: &Destination) -> io::Result<()> {
+        self.dest_request(IPVS_CMD_DEL_DEST, service, dest, false)
+    }
+
+    /// Deletes every service.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.family.request(&mut self.socket, IPVS_CMD_FLUSH, &[])?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use libc::ENOENT;
+
+    #[test]
+    fn test_service_roundtrip() {
+        let mut service = Service::new(ServiceProtocol::Tcp, "192.0.2.10:80".parse().unwrap());
+        service.set_scheduler(Scheduler::RoundRobin).set_persistent(300);
+
+        let mut w = AttrWriter::new();
+        service.put_attr(&mut w, true).unwrap();
+        let (attr, _) = NlAttr::from_bytes(w.bytes()).unwrap();
+        let decoded = Service::from_attr(&attr).unwrap();
+        assert_eq!(decoded, service);
+        assert!(decoded.is_persistent());
+        assert_eq!(decoded.scheduler().name(), "rr");
+
+        let service = Service::with_fwmark(7, true);
+        let mut w = AttrWriter::new();
+        service.put_attr(&mut w, false).unwrap();
+        let (attr, _) = NlAttr::from_bytes(w.bytes()).unwrap();
+        let decoded = Service::from_attr(&attr).unwrap();
+        assert_eq!((decoded.fwmark(), decoded.addr(), decoded.is_ipv6()), (Some(7), None, true));
+        assert_eq!(Scheduler::from("fo"), Scheduler::Other("fo".into()));
+    }
+
+    #[test]
+    fn test_destination_roundtrip() {
+        let mut dest = Destination::new("[2001:db8::5]:8080".parse().unwrap());
+        dest.set_forward(ForwardMethod::DirectRoute).set_weight(5).set_thresholds(100, 50);
+
+        let mut w = AttrWriter::new();
+        w.begin_nested(IPVS_CMD_ATTR_DEST).unwrap();
+        dest.put_fields(&mut w, true).unwrap();
+        w.begin_nested(IPVS_DEST_ATTR_STATS64).unwrap()
+            .put_u64(IPVS_STATS_ATTR_CONNS, 9).unwrap()
+            .put_u64(IPVS_STATS_ATTR_INBYTES, 4000).unwrap()
+            .end_nested().unwrap()
+            .end_nested().unwrap();
+
+        let (attr, _) = NlAttr::from_bytes(w.bytes()).unwrap();
+        let decoded = Destination::from_attr(&attr, AF_INET as u16).unwrap();
+        assert_eq!(decoded.addr(), dest.addr());
+        assert_eq!(decoded.forward(), ForwardMethod::DirectRoute);
+        assert_eq!((decoded.weight(), decoded.upper_threshold(), decoded.lower_threshold()), (5, 100, 50));
+        let stats = decoded.stats().unwrap();
+        assert_eq!((stats.conns(), stats.inbytes()), (9, 4000));
+    }
+
+    #[test]
+    fn test_ipvs_services() {
+        let mut ipvs = match Ipvs::new() {
+            Ok(ipvs) => ipvs,
+            // ip_vs not loaded
+            Err(ref e) if e.raw_os_error() == Some(ENOENT) => return,
+            Err(e) => panic!("{}", e),
+        };
+        ipvs.services().unwrap();
+    }
+}
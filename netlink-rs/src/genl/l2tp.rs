@@ -0,0 +1,672 @@
+//! L2TP generic netlink family
+//!
+//! Creates the kernel data path of L2TP tunnels and sessions (`ip l2tp`);
+//! the control protocol itself is left to the daemon.
+
+use super::GenlFamily;
+use socket::{Socket, NetlinkTransport, NlAttr, AttrWriter};
+use Protocol;
+
+use std::io::{self, ErrorKind};
+use std::net::IpAddr;
+
+const L2TP_GENL_NAME: &str = "l2tp";
+
+const L2TP_CMD_TUNNEL_CREATE: u8 = 1;
+const L2TP_CMD_TUNNEL_DELETE: u8 = 2;
+const L2TP_CMD_TUNNEL_GET: u8 = 4;
+const L2TP_CMD_SESSION_CREATE: u8 = 5;
+const L2TP_CMD_SESSION_DELETE: u8 = 6;
+const L2TP_CMD_SESSION_GET: u8 = 8;
+
+const L2TP_ATTR_PW_TYPE: u16 = 1;
+const L2TP_ATTR_ENCAP_TYPE: u16 = 2;
+const L2TP_ATTR_PROTO_VERSION: u16 = 7;
+const L2TP_ATTR_IFNAME: u16 = 8;
+const L2TP_ATTR_CONN_ID: u16 = 9;
+const L2TP_ATTR_PEER_CONN_ID: u16 = 10;
+const L2TP_ATTR_SESSION_ID: u16 = 11;
+const L2TP_ATTR_PEER_SESSION_ID: u16 = 12;
+const L2TP_ATTR_UDP_CSUM: u16 = 13;
+const L2TP_ATTR_COOKIE: u16 = 15;
+const L2TP_ATTR_PEER_COOKIE: u16 = 16;
+const L2TP_ATTR_RECV_SEQ: u16 = 18;
+const L2TP_ATTR_SEND_SEQ: u16 = 19;
+const L2TP_ATTR_LNS_MODE: u16 = 20;
+const L2TP_ATTR_FD: u16 = 23;
+const L2TP_ATTR_IP_SADDR: u16 = 24;
+const L2TP_ATTR_IP_DADDR: u16 = 25;
+const L2TP_ATTR_UDP_SPORT: u16 = 26;
+const L2TP_ATTR_UDP_DPORT: u16 = 27;
+const L2TP_ATTR_MTU: u16 = 28;
+const L2TP_ATTR_STATS: u16 = 30;
+const L2TP_ATTR_IP6_SADDR: u16 = 31;
+const L2TP_ATTR_IP6_DADDR: u16 = 32;
+
+const L2TP_ATTR_TX_PACKETS: u16 = 1;
+const L2TP_ATTR_TX_BYTES: u16 = 2;
+const L2TP_ATTR_TX_ERRORS: u16 = 3;
+const L2TP_ATTR_RX_PACKETS: u16 = 4;
+const L2TP_ATTR_RX_BYTES: u16 = 5;
+const L2TP_ATTR_RX_SEQ_DISCARDS: u16 = 6;
+const L2TP_ATTR_RX_OOS_PACKETS: u16 = 7;
+const L2TP_ATTR_RX_ERRORS: u16 = 8;
+
+/// Transport of the tunnel
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum EncapType {
+    Udp,
+    /// L2TPv3 directly over IP, protocol 115
+    Ip,
+    Other(u16),
+}
+
+impl From<EncapType> for u16 {
+    fn from(t: EncapType) -> u16 {
+        match t {
+            EncapType::Udp => 0,
+            EncapType::Ip => 1,
+            EncapType::Other(i) => i,
+        }
+    }
+}
+
+impl From<u16> for EncapType {
+    fn from(t: u16) -> EncapType {
+        match t {
+            0 => EncapType::Udp,
+            1 => EncapType::Ip,
+            i => EncapType::Other(i),
+        }
+    }
+}
+
+/// Pseudowire carried by a session
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum PwType {
+    None,
+    EthVlan,
+    /// Ethernet, exposed as an l2tpethN netdev
+    Eth,
+    /// PPP, attached through a PPPoL2TP socket
+    Ppp,
+    PppAc,
+    Ip,
+    Other(u16),
+}
+
+impl From<PwType> for u16 {
+    fn from(t: PwType) -> u16 {
+        use self::PwType::*;
+        match t {
+            None    =>  0,
+            EthVlan =>  4,
+            Eth     =>  5,
+            Ppp     =>  7,
+            PppAc   =>  8,
+            Ip      => 11,
+            Other(i) => i,
+        }
+    }
+}
+
+impl From<u16> for PwType {
+    fn from(t: u16) -> PwType {
+        use self::PwType::*;
+        match t {
+            0 => None,
+            4 => EthVlan,
+            5 => Eth,
+            7 => Ppp,
+            8 => PppAc,
+            11 => Ip,
+            i => Other(i),
+        }
+    }
+}
+
+/// Data path counters of a tunnel or session
+#[derive(Clone, Copy, Default, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct L2tpStats {
+    tx_packets: u64,
+    tx_bytes: u64,
+    tx_errors: u64,
+    rx_packets: u64,
+    rx_bytes: u64,
+    rx_seq_discards: u64,
+    rx_oos_packets: u64,
+    rx_errors: u64,
+}
+
+impl L2tpStats {
+    fn from_attr(attr: &NlAttr) -> io::Result<L2tpStats> {
+        let mut stats = L2tpStats::default();
+        for a in attr.nested()? {
+            let value = a.get_u64();
+            match a.attr_type() {
+                L2TP_ATTR_TX_PACKETS => stats.tx_packets = value?,
+                L2TP_ATTR_TX_BYTES => stats.tx_bytes = value?,
+                L2TP_ATTR_TX_ERRORS => stats.tx_errors = value?,
+                L2TP_ATTR_RX_PACKETS => stats.rx_packets = value?,
+                L2TP_ATTR_RX_BYTES => stats.rx_bytes = value?,
+                L2TP_ATTR_RX_SEQ_DISCARDS => stats.rx_seq_discards = value?,
+                L2TP_ATTR_RX_OOS_PACKETS => stats.rx_oos_packets = value?,
+                L2TP_ATTR_RX_ERRORS => stats.rx_errors = value?,
+                _ => {},
+            }
+        }
+        Ok(stats)
+    }
+
+    pub fn tx_packets(&self) -> u64 {
+        self.tx_packets
+    }
+
+    pub fn tx_bytes(&self) -> u64 {
+        self.tx_bytes
+    }
+
+    pub fn tx_errors(&self) -> u64 {
+        self.tx_errors
+    }
+
+    pub fn rx_packets(&self) -> u64 {
+        self.rx_packets
+    }
+
+    pub fn rx_bytes(&self) -> u64 {
+        self.rx_bytes
+    }
+
+    /// Packets dropped for arriving out of sequence
+    pub fn rx_seq_discards(&self) -> u64 {
+        self.rx_seq_discards
+    }
+
+    /// Packets received out of sequence and reordered
+    pub fn rx_oos_packets(&self) -> u64 {
+        self.rx_oos_packets
+    }
+
+    pub fn rx_errors(&self) -> u64 {
+        self.rx_errors
+    }
+}
+
+fn put_addr(w: &mut AttrWriter, v4: u16, v6: u16, addr: IpAddr) -> io::Result<()> {
+    match addr {
+        IpAddr::V4(a) => w.put(v4, &a.octets())?,
+        IpAddr::V6(a) => w.put(v6, &a.octets())?,
+    };
+    Ok(())
+}
+
+/// An L2TP tunnel, identified by its local connection id
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Tunnel {
+    conn_id: u32,
+    peer_conn_id: u32,
+    version: u8,
+    encap: EncapType,
+    local: Option<IpAddr>,
+    remote: Option<IpAddr>,
+    local_port: Option<u16>,
+    remote_port: Option<u16>,
+    fd: Option<i32>,
+    udp_csum: Option<bool>,
+    stats: Option<L2tpStats>,
+}
+
+impl Tunnel {
+    /// Tunnel of protocol `version` (2 or 3) between `conn_id` and
+    /// `peer_conn_id`.
+    pub fn new(conn_id: u32, peer_conn_id: u32, version: u8, encap: EncapType) -> Tunnel {
+        Tunnel {
+            conn_id,
+            peer_conn_id,
+            version,
+            encap,
+            local: None,
+            remote: None,
+            local_port: None,
+            remote_port: None,
+            fd: None,
+            udp_csum: None,
+            stats: None,
+        }
+    }
+
+    fn from_attrs(bytes: &[u8]) -> io::Result<Tunnel> {
+        let mut tunnel = Tunnel::new(0, 0, 0, EncapType::Udp);
+        for attr in NlAttr::parse(bytes)? {
+            match attr.attr_type() {
+                L2TP_ATTR_CONN_ID => tunnel.conn_id = attr.get_u32()?,
+                L2TP_ATTR_PEER_CONN_ID => tunnel.peer_conn_id = attr.get_u32()?,
+                L2TP_ATTR_PROTO_VERSION => tunnel.version = attr.get_u8()?,
+                L2TP_ATTR_ENCAP_TYPE => tunnel.encap = EncapType::from(attr.get_u16()?),
+                L2TP_ATTR_IP_SADDR | L2TP_ATTR_IP6_SADDR => tunnel.local = Some(attr.get_ip()?),
+                L2TP_ATTR_IP_DADDR | L2TP_ATTR_IP6_DADDR => tunnel.remote = Some(attr.get_ip()?),
+                L2TP_ATTR_UDP_SPORT => tunnel.local_port = Some(attr.get_u16()?),
+                L2TP_ATTR_UDP_DPORT => tunnel.remote_port = Some(attr.get_u16()?),
+                L2TP_ATTR_UDP_CSUM => tunnel.udp_csum = Some(attr.get_u8()? != 0),
+                L2TP_ATTR_STATS => tunnel.stats = Some(L2tpStats::from_attr(&attr)?),
+                _ => {},
+            }
+        }
+        Ok(tunnel)
+    }
+
+    fn attrs(&self) -> io::Result<Vec<u8>> {
+        let mut w = AttrWriter::new();
+        w.put_u32(L2TP_ATTR_CONN_ID, self.conn_id)?
+            .put_u32(L2TP_ATTR_PEER_CONN_ID, self.peer_conn_id)?
+            .put_u8(L2TP_ATTR_PROTO_VERSION, self.version)?
+            .put_u16(L2TP_ATTR_ENCAP_TYPE, u16::from(self.encap))?;
+        if let Some(fd) = self.fd {
+            w.put_u32(L2TP_ATTR_FD, fd as u32)?;
+        }
+        if let Some(local) = self.local {
+            put_addr(&mut w, L2TP_ATTR_IP_SADDR, L2TP_ATTR_IP6_SADDR, local)?;
+        }
+        if let Some(remote) = self.remote {
+            put_addr(&mut w, L2TP_ATTR_IP_DADDR, L2TP_ATTR_IP6_DADDR, remote)?;
+        }
+        if let Some(port) = self.local_port {
+            w.put_u16(L2TP_ATTR_UDP_SPORT, port)?;
+        }
+        if let Some(port) = self.remote_port {
+            w.put_u16(L2TP_ATTR_UDP_DPORT, port)?;
+        }
+        if let Some(csum) = self.udp_csum {
+            w.put_u8(L2TP_ATTR_UDP_CSUM, csum as u8)?;
+        }
+        w.into_vec()
+    }
+
+    /// Set addresses of a tunnel whose socket the kernel creates
+    pub fn set_addrs(&mut self, local: IpAddr, remote: IpAddr) -> &mut Tunnel {
+        self.local = Some(local);
+        self.remote = Some(remote);
+        self
+    }
+
+    /// Set UDP ports of a tunnel whose socket the kernel creates
+    pub fn set_ports(&mut self, local: u16, remote: u16) -> &mut Tunnel {
+        self.local_port = Some(local);
+        self.remote_port = Some(remote);
+        self
+    }
+
+    /// Set connected socket of the daemon to carry the tunnel, instead of
+    /// letting the kernel create one
+    pub fn set_fd(&mut self, fd: i32) -> &mut Tunnel {
+        self.fd = Some(fd);
+        self
+    }
+
+    /// Set whether UDP checksums are computed on transmit
+    pub fn set_udp_csum(&mut self, csum: bool) -> &mut Tunnel {
+        self.udp_csum = Some(csum);
+        self
+    }
+
+    pub fn conn_id(&self) -> u32 {
+        self.conn_id
+    }
+
+    pub fn peer_conn_id(&self) -> u32 {
+        self.peer_conn_id
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    pub fn encap(&self) -> EncapType {
+        self.encap
+    }
+
+    pub fn local(&self) -> Option<IpAddr> {
+        self.local
+    }
+
+    pub fn remote(&self) -> Option<IpAddr> {
+        self.remote
+    }
+
+    pub fn local_port(&self) -> Option<u16> {
+        self.local_port
+    }
+
+    pub fn remote_port(&self) -> Option<u16> {
+        self.remote_port
+    }
+
+    pub fn udp_csum(&self) -> Option<bool> {
+        self.udp_csum
+    }
+
+    /// Counters, as reported by the kernel
+    pub fn stats(&self) -> Option<&L2tpStats> {
+        self.stats.as_ref()
+    }
+}
+
+/// A session within a tunnel, identified by its local session id
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Session {
+    conn_id: u32,
+    session_id: u32,
+    peer_session_id: u32,
+    pw_type: PwType,
+    ifname: Option<String>,
+    cookie: Option<Vec<u8>>,
+    peer_cookie: Option<Vec<u8>>,
+    recv_seq: Option<bool>,
+    send_seq: Option<bool>,
+    lns_mode: Option<bool>,
+    mtu: Option<u16>,
+    stats: Option<L2tpStats>,
+}
+
+impl Session {
+    /// Session of the tunnel `conn_id` between `session_id` and
+    /// `peer_session_id`.
+    pub fn new(conn_id: u32, session_id: u32, peer_session_id: u32, pw_type: PwType) -> Session {
+        Session {
+            conn_id,
+            session_id,
+            peer_session_id,
+            pw_type,
+            ifname: None,
+            cookie: None,
+            peer_cookie: None,
+            recv_seq: None,
+            send_seq: None,
+            lns_mode: None,
+            mtu: None,
+            stats: None,
+        }
+    }
+
+    fn from_attrs(bytes: &[u8]) -> io::Result<Session> {
+        let mut session = Session::new(0, 0, 0, PwType::None);
+        for attr in NlAttr::parse(bytes)? {
+            match attr.attr_type() {
+                L2TP_ATTR_CONN_ID => session.conn_id = attr.get_u32()?,
+                L2TP_ATTR_SESSION_ID => session.session_id = attr.get_u32()?,
+                L2TP_ATTR_PEER_SESSION_ID => session.peer_session_id = attr.get_u32()?,
+                L2TP_ATTR_PW_TYPE => session.pw_type = PwType::from(attr.get_u16()?),
+                L2TP_ATTR_IFNAME => session.ifname = Some(attr.get_str()?.into()),
+                L2TP_ATTR_COOKIE => session.cookie = Some(attr.get_bytes().to_vec()),
+                L2TP_ATTR_PEER_COOKIE => session.peer_cookie = Some(attr.get_bytes().to_vec()),
+                L2TP_ATTR_RECV_SEQ => session.recv_seq = Some(attr.get_u8()? != 0),
+                L2TP_ATTR_SEND_SEQ => session.send_seq = Some(attr.get_u8()? != 0),
+                L2TP_ATTR_LNS_MODE => session.lns_mode = Some(attr.get_u8()? != 0),
+                L2TP_ATTR_MTU => session.mtu = Some(attr.get_u16()?),
+                L2TP_ATTR_STATS => session.stats = Some(L2tpStats::from_attr(&attr)?),
+                _ => {},
+            }
+        }
+        Ok(session)
+    }
+
+    fn attrs(&self) -> io::Result<Vec<u8>> {
+        let mut w = AttrWriter::new();
+        w.put_u32(L2TP_ATTR_CONN_ID, self.conn_id)?
+            .put_u32(L2TP_ATTR_SESSION_ID, self.session_id)?
+            .put_u32(L2TP_ATTR_PEER_SESSION_ID, self.peer_session_id)?
+            .put_u16(L2TP_ATTR_PW_TYPE, u16::from(self.pw_type))?;
+        if let Some(ref ifname) = self.ifname {
+            w.put_str(L2TP_ATTR_IFNAME, ifname)?;
+        }
+        if let Some(ref cookie) = self.cookie {
+            w.put(L2TP_ATTR_COOKIE, cookie)?;
+        }
+        if let Some(ref cookie) = self.peer_cookie {
+            w.put(L2TP_ATTR_PEER_COOKIE, cookie)?;
+        }
+        if let Some(seq) = self.recv_seq {
+            w.put_u8(L2TP_ATTR_RECV_SEQ, seq as u8)?;
+        }
+        if let Some(seq) = self.send_seq {
+            w.put_u8(L2TP_ATTR_SEND_SEQ, seq as u8)?;
+        }
+        if let Some(lns) = self.lns_mode {
+            w.put_u8(L2TP_ATTR_LNS_MODE, lns as u8)?;
+        }
+        if let Some(mtu) = self.mtu {
+            w.put_u16(L2TP_ATTR_MTU, mtu)?;
+        }
+        w.into_vec()
+    }
+
+    /// Set name of the netdev of an Ethernet pseudowire
+    pub fn set_ifname(&mut self, ifname: &str) -> &mut Session {
+        self.ifname = Some(ifname.into());
+        self
+    }
+
+    /// Set L2TPv3 cookie (4 or 8 bytes) expected on received packets
+    pub fn set_cookie(&mut self, cookie: &[u8]) -> &mut Session {
+        self.cookie = Some(cookie.to_vec());
+        self
+    }
+
+    /// Set L2TPv3 cookie sent to the peer
+    pub fn set_peer_cookie(&mut self, cookie: &[u8]) -> &mut Session {
+        self.peer_cookie = Some(cookie.to_vec());
+        self
+    }
+
+    /// Set whether sequence numbers are required on receive and added on
+    /// transmit
+    pub fn set_seq(&mut self, recv: bool, send: bool) -> &mut Session {
+        self.recv_seq = Some(recv);
+        self.send_seq = Some(send);
+        self
+    }
+
+    /// Set whether this end is the L2TP network server
+    pub fn set_lns_mode(&mut self, lns: bool) -> &mut Session {
+        self.lns_mode = Some(lns);
+        self
+    }
+
+    pub fn set_mtu(&mut self, mtu: u16) -> &mut Session {
+        self.mtu = Some(mtu);
+        self
+    }
+
+    pub fn conn_id(&self) -> u32 {
+        self.conn_id
+    }
+
+    pub fn session_id(&self) -> u32 {
+        self.session_id
+    }
+
+    pub fn peer_session_id(&self) -> u32 {
+        self.peer_session_id
+    }
+
+    pub fn pw_type(&self) -> PwType {
+        self.pw_type
+    }
+
+    pub fn ifname(&self) -> Option<&str> {
+        self.ifname.as_deref()
+    }
+
+    pub fn cookie(&self) -> Option<&[u8]> {
+        self.cookie.as_deref()
+    }
+
+    pub fn peer_cookie(&self) -> Option<&[u8]> {
+        self.peer_cookie.as_deref()
+    }
+
+    pub fn recv_seq(&self) -> Option<bool> {
+        self.recv_seq
+    }
+
+    pub fn send_seq(&self) -> Option<bool> {
+        self.send_seq
+    }
+
+    pub fn lns_mode(&self) -> Option<bool> {
+        self.lns_mode
+    }
+
+    pub fn mtu(&self) -> Option<u16> {
+        self.mtu
+    }
+
+    /// Counters, as reported by the kernel
+    pub fn stats(&self) -> Option<&L2tpStats> {
+        self.stats.as_ref()
+    }
+}
+
+/// Handle to the l2tp family
+pub struct L2tp<T: NetlinkTransport = Socket> {
+    socket: T,
+    family: GenlFamily,
+}
+
+impl L2tp {
+    pub fn new() -> io::Result<L2tp> {
+        L2tp::from_transport(Socket::new(Protocol::Generic)?)
+    }
+}
+
+impl<T: NetlinkTransport> L2tp<T> {
+    /// Like `new`, but talks to the kernel through `transport`.
+    pub fn from_transport(mut transport: T) -> io::Result<L2tp<T>> {
+        let family = GenlFamily::resolve(&mut transport, L2TP_GENL_NAME)?;
+        Ok(L2tp {
+            socket: transport,
+            family,
+        })
+    }
+
+    pub fn create_tunnel(&mut self, tunnel: &Tunnel) -> io::Result<()> {
+        self.family.request(&mut self.socket, L2TP_CMD_TUNNEL_CREATE, &tunnel.attrs()?)?;
+        Ok(())
+    }
+
+    /// Deletes the tunnel `conn_id` with its sessions.
+    pub fn delete_tunnel(&mut self, conn_id: u32) -> io::Result<()> {
+        let mut w = AttrWriter::new();
+        w.put_u32(L2TP_ATTR_CONN_ID, conn_id)?;
+        self.family.request(&mut self.socket, L2TP_CMD_TUNNEL_DELETE, w.bytes())?;
+        Ok(())
+    }
+
+    pub fn tunnel(&mut self, conn_id: u32) -> io::Result<Tunnel> {
+        let mut w = AttrWriter::new();
+        w.put_u32(L2TP_ATTR_CONN_ID, conn_id)?;
+        let replies = self.family.request(&mut self.socket, L2TP_CMD_TUNNEL_GET, w.bytes())?;
+        match replies.first() {
+            Some(reply) => Tunnel::from_attrs(reply),
+            None => Err(io::Error::new(ErrorKind::NotFound, "no tunnel reply")),
+        }
+    }
+
+    /// Lists the tunnels (L2TP_CMD_TUNNEL_GET dump).
+    pub fn tunnels(&mut self) -> io::Result<Vec<Tunnel>> {
+        let replies = self.family.dump(&mut self.socket, L2TP_CMD_TUNNEL_GET, &[])?;
+        replies.iter().map(|r| Tunnel::from_attrs(r)).collect()
+    }
+
+    pub fn create_session(&mut self, session: &Session) -> io::Result<()> {
+        self.family.request(&mut self.socket, L2TP_CMD_SESSION_CREATE, &session.attrs()?)?;
+        Ok(())
+    }
+
+    pub fn delete_session(&mut self, conn_id: u32, session_id: u32) -> io::Result<()> {
+        let mut w = AttrWriter::new();
+        w.put_u32(L2TP_ATTR_CONN_ID, conn_id)?.put_u32(L2TP_ATTR_SESSION_ID, session_id)?;
+        self.family.request(&mut self.socket, L2TP_CMD_SESSION_DELETE, w.bytes())?;
+        Ok(())
+    }
+
+    pub fn session(&mut self, conn_id: u32, session_id: u32) -> io::Result<Session> {
+        let mut w = AttrWriter::new();
+        w.put_u32(L2TP_ATTR_CONN_ID, conn_id)?.put_u32(L2TP_ATTR_SESSION_ID, session_id)?;
+        let replies = self.family.request(&mut self.socket, L2TP_CMD_SESSION_GET, w.bytes())?;
+        match replies.first() {
+            Some(reply) => Session::from_attrs(reply),
+            None => Err(io::Error::new(ErrorKind::NotFound, "no session reply")),
+        }
+    }
+
+    /// Lists the sessions of all tunnels (L2TP_CMD_SESSION_GET dump).
+    pub fn sessions(&mut self) -> io::Result<Vec<Session>> {
+        let replies = self.family.dump(&mut self.socket, L2TP_CMD_SESSION_GET, &[])?;
+        replies.iter().map(|r| Session::from_attrs(r)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use libc::ENOENT;
+
+    #[test]
+    fn test_tunnel_roundtrip() {
+        let mut tunnel = Tunnel::new(10, 20, 3, EncapType::Udp);
+        tunnel.set_addrs("192.0.2.1".parse().unwrap(), "192.0.2.2".parse().unwrap())
+            .set_ports(1701, 1702)
+            .set_udp_csum(true);
+
+        let mut bytes = tunnel.attrs().unwrap();
+        let mut stats = AttrWriter::new();
+        stats.begin_nested(L2TP_ATTR_STATS).unwrap()
+            .put_u64(L2TP_ATTR_RX_PACKETS, 7).unwrap()
+            .put_u64(L2TP_ATTR_TX_BYTES, 300).unwrap()
+            .end_nested().unwrap();
+        bytes.extend_from_slice(stats.bytes());
+
+        let decoded = Tunnel::from_attrs(&bytes).unwrap();
+        assert_eq!(decoded.conn_id(), 10);
+        assert_eq!(decoded.peer_conn_id(), 20);
+        assert_eq!(decoded.version(), 3);
+        assert_eq!(decoded.remote(), Some("192.0.2.2".parse().unwrap()));
+        assert_eq!(decoded.local_port(), Some(1701));
+        assert_eq!(decoded.udp_csum(), Some(true));
+        let stats = decoded.stats().unwrap();
+        assert_eq!(stats.rx_packets(), 7);
+        assert_eq!(stats.tx_bytes(), 300);
+    }
+
+    #[test]
+    fn test_session_roundtrip() {
+        let mut session = Session::new(10, 1, 2, PwType::Eth);
+        session.set_ifname("l2tpeth9").set_cookie(&[1, 2, 3, 4]).set_seq(true, false).set_mtu(1400);
+
+        let decoded = Session::from_attrs(&session.attrs().unwrap()).unwrap();
+        assert_eq!(decoded, session);
+        assert_eq!(decoded.pw_type(), PwType::Eth);
+        assert_eq!(decoded.ifname(), Some("l2tpeth9"));
+        assert_eq!(u16::from(PwType::Ppp), 7);
+        assert_eq!(EncapType::from(1), EncapType::Ip);
+    }
+
+    #[test]
+    fn test_l2tp_tunnels() {
+        let mut l2tp = match L2tp::new() {
+            Ok(l2tp) => l2tp,
+            // l2tp_netlink not loaded
+            Err(ref e) if e.raw_os_error() == Some(ENOENT) => return,
+            Err(e) => panic!("{}", e),
+        };
+        l2tp.tunnels().unwrap();
+    }
+}
@@ -0,0 +1,371 @@
+//! Generic netlink
+//!
+//! Generic netlink multiplexes many kernel subsystems ("families") over the
+//! single `Protocol::Generic` socket type. Families are registered at runtime
+//! and have to be resolved by name through the `nlctrl` controller before
+//! they can be used.
+
+pub mod acpi;
+pub mod devlink;
+pub mod ethtool;
+pub mod ipvs;
+pub mod l2tp;
+pub mod mptcp;
+pub mod nbd;
+pub mod nl80211;
+pub mod ovs;
+pub mod taskstats;
+pub mod team;
+pub mod tcp_metrics;
+
+mod cache;
+mod policy;
+pub use self::cache::*;
+pub use self::policy::*;
+
+use socket::{NetlinkTransport, Msg, NlMsgHeader, NlAttr, Payload, string_attr};
+
+use std::borrow::Cow;
+use std::io::{self, ErrorKind, Cursor};
+
+use byteorder::{NativeEndian, ReadBytesExt};
+
+// #define GENL_ID_CTRL        NLMSG_MIN_TYPE
+const GENL_ID_CTRL: u16 = 0x10;
+const GENL_CTRL_VERSION: u8 = 2;
+
+// enum { CTRL_CMD_UNSPEC, CTRL_CMD_NEWFAMILY, CTRL_CMD_DELFAMILY, CTRL_CMD_GETFAMILY, ... }
+const CTRL_CMD_GETFAMILY: u8 = 3;
+
+const CTRL_ATTR_FAMILY_ID: u16 = 1;
+const CTRL_ATTR_FAMILY_NAME: u16 = 2;
+const CTRL_ATTR_VERSION: u16 = 3;
+const CTRL_ATTR_HDRSIZE: u16 = 4;
+const CTRL_ATTR_MAXATTR: u16 = 5;
+const CTRL_ATTR_OPS: u16 = 6;
+const CTRL_ATTR_MCAST_GROUPS: u16 = 7;
+
+const CTRL_ATTR_OP_ID: u16 = 1;
+const CTRL_ATTR_OP_FLAGS: u16 = 2;
+
+const CTRL_ATTR_MCAST_GRP_NAME: u16 = 1;
+const CTRL_ATTR_MCAST_GRP_ID: u16 = 2;
+
+// HEADER FORMAT
+// __u8    cmd;
+// __u8    version;
+// __u16   reserved;
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct GenlMsgHeader {
+    cmd: u8,
+    version: u8,
+}
+
+impl GenlMsgHeader {
+    pub fn new(cmd: u8, version: u8) -> GenlMsgHeader {
+        GenlMsgHeader {
+            cmd,
+            version,
+        }
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<(GenlMsgHeader, usize)> {
+        let mut cursor = Cursor::new(bytes);
+        let cmd = cursor.read_u8()?;
+        let version = cursor.read_u8()?;
+        let _reserved = cursor.read_u16::<NativeEndian>()?;
+        Ok((GenlMsgHeader::new(cmd, version), cursor.position() as usize))
+    }
+
+    pub fn bytes(&self) -> [u8; 4] {
+        [self.cmd, self.version, 0, 0]
+    }
+
+    pub fn cmd(&self) -> u8 {
+        self.cmd
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+}
+
+/// Multicast group registered by a family
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct McastGroup {
+    name: String,
+    id: u32,
+}
+
+impl McastGroup {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Group number to pass to `NetlinkAddr` or `NETLINK_ADD_MEMBERSHIP`
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+const GENL_ADMIN_PERM: u32 = 0x01;
+const GENL_CMD_CAP_DO: u32 = 0x02;
+const GENL_CMD_CAP_DUMP: u32 = 0x04;
+
+/// Command supported by a family
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct GenlOp {
+    cmd: u8,
+    flags: u32,
+}
+
+impl GenlOp {
+    pub fn cmd(&self) -> u8 {
+        self.cmd
+    }
+
+    /// GENL_* flags, e.g. GENL_ADMIN_PERM
+    pub fn flags(&self) -> u32 {
+        self.flags
+    }
+
+    /// Requires CAP_NET_ADMIN
+    pub fn needs_admin(&self) -> bool {
+        self.flags & GENL_ADMIN_PERM != 0
+    }
+
+    /// Accepts requests without NLM_F_DUMP
+    pub fn supports_do(&self) -> bool {
+        self.flags & GENL_CMD_CAP_DO != 0
+    }
+
+    pub fn supports_dump(&self) -> bool {
+        self.flags & GENL_CMD_CAP_DUMP != 0
+    }
+}
+
+/// A resolved generic netlink family
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct GenlFamily {
+    name: String,
+    id: u16,
+    version: u8,
+    hdr_size: u32,
+    max_attr: u32,
+    ops: Vec<GenlOp>,
+    groups: Vec<McastGroup>,
+}
+
+impl GenlFamily {
+    fn ctrl() -> GenlFamily {
+        GenlFamily {
+            name: "nlctrl".into(),
+            id: GENL_ID_CTRL,
+            version: GENL_CTRL_VERSION,
+            hdr_size: 0,
+            max_attr: 0,
+            ops: vec![],
+            groups: vec![],
+        }
+    }
+
+    /// Asks the controller for the family registered under `name`.
+    pub fn resolve(socket: &mut impl NetlinkTransport, name: &str) -> io::Result<GenlFamily> {
+        let attrs = string_attr(CTRL_ATTR_FAMILY_NAME, name);
+
+        let replies = GenlFamily::ctrl().request(socket, CTRL_CMD_GETFAMILY, &attrs)?;
+        match replies.first() {
+            Some(reply) => GenlFamily::from_attrs(reply),
+            None => Err(io::Error::new(ErrorKind::NotFound, "no reply from generic netlink controller")),
+        }
+    }
+
+    fn from_attrs(bytes: &[u8]) -> io::Result<GenlFamily> {
+        let mut family = GenlFamily {
+            name: String::new(),
+            id: 0,
+            version: 0,
+            hdr_size: 0,
+            max_attr: 0,
+            ops: vec![],
+            groups: vec![],
+        };
+
+        for attr in NlAttr::parse(bytes)? {
+            match attr.attr_type() {
+                CTRL_ATTR_FAMILY_ID => family.id = attr.get_u16()?,
+                CTRL_ATTR_FAMILY_NAME => family.name = attr.get_str()?.to_string(),
+                CTRL_ATTR_VERSION => family.version = attr.get_u32()? as u8,
+                CTRL_ATTR_HDRSIZE => family.hdr_size = attr.get_u32()?,
+                CTRL_ATTR_MAXATTR => family.max_attr = attr.get_u32()?,
+                CTRL_ATTR_OPS => {
+                    for entry in attr.array() {
+                        let (_, entry) = entry?;
+                        let mut op = GenlOp { cmd: 0, flags: 0 };
+                        for a in entry.nested()? {
+                            match a.attr_type() {
+                                CTRL_ATTR_OP_ID => op.cmd = a.get_u32()? as u8,
+                                CTRL_ATTR_OP_FLAGS => op.flags = a.get_u32()?,
+                                _ => {},
+                            }
+                        }
+                        family.ops.push(op);
+                    }
+                },
+                CTRL_ATTR_MCAST_GROUPS => {
+                    for entry in attr.array() {
+                        let (_, entry) = entry?;
+                        let mut group = McastGroup { name: String::new(), id: 0 };
+                        for a in entry.nested()? {
+                            match a.attr_type() {
+                                CTRL_ATTR_MCAST_GRP_NAME => group.name = a.get_str()?.to_string(),
+                                CTRL_ATTR_MCAST_GRP_ID => {
+                                    group.id = a.get_u32()?
+                                },
+                                _ => {},
+                            }
+                        }
+                        family.groups.push(group);
+                    }
+                },
+                _ => {},
+            }
+        }
+
+        if family.id == 0 {
+            Err(io::Error::new(ErrorKind::InvalidData, "family reply without CTRL_ATTR_FAMILY_ID"))
+        } else {
+            Ok(family)
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Message type to use in the netlink header of requests to this family
+    pub fn id(&self) -> u16 {
+        self.id
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// Length of the family specific header following the genl header
+    pub fn hdr_size(&self) -> u32 {
+        self.hdr_size
+    }
+
+    pub fn max_attr(&self) -> u32 {
+        self.max_attr
+    }
+
+    /// Commands of the family
+    pub fn ops(&self) -> &[GenlOp] {
+        &self.ops
+    }
+
+    pub fn op(&self, cmd: u8) -> Option<&GenlOp> {
+        self.ops.iter().find(|op| op.cmd == cmd)
+    }
+
+    pub fn groups(&self) -> &[McastGroup] {
+        &self.groups
+    }
+
+    pub fn group(&self, name: &str) -> Option<&McastGroup> {
+        self.groups.iter().find(|g| g.name == name)
+    }
+
+    /// Sends `cmd` with the encoded `attrs` and returns the attribute stream of
+    /// every reply, with the genl header stripped.
+    pub fn request(&self, socket: &mut impl NetlinkTransport, cmd: u8, attrs: &[u8])
+        -> io::Result<Vec<Vec<u8>>> {
+            let mut hdr = NlMsgHeader::user_defined(self.id);
+            self.exchange(socket, &mut hdr, cmd, attrs)
+        }
+
+    /// Like `request`, but asks for a dump of all matching objects.
+    pub fn dump(&self, socket: &mut impl NetlinkTransport, cmd: u8, attrs: &[u8])
+        -> io::Result<Vec<Vec<u8>>> {
+            let mut hdr = NlMsgHeader::user_defined(self.id);
+            hdr.dump();
+            self.exchange(socket, &mut hdr, cmd, attrs)
+        }
+
+    fn exchange(&self, socket: &mut impl NetlinkTransport, hdr: &mut NlMsgHeader, cmd: u8, attrs: &[u8])
+        -> io::Result<Vec<Vec<u8>>> {
+            let mut payload = GenlMsgHeader::new(cmd, self.version).bytes().to_vec();
+            payload.extend_from_slice(attrs);
+            hdr.data_length(payload.len() as u32);
+
+            let replies = socket.talk(Msg::new(*hdr, Payload::Data(Cow::Borrowed(&payload))))?;
+            replies.iter().map(|r| {
+                let (_, n) = GenlMsgHeader::from_bytes(r)?;
+                Ok(r[n..].to_vec())
+            }).collect()
+        }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket::{Socket, NlAttr, array_attr};
+    use Protocol;
+
+    #[test]
+    fn test_genl_header() {
+        let hdr = GenlMsgHeader::new(3, 2);
+        let (decoded, n) = GenlMsgHeader::from_bytes(&hdr.bytes()).unwrap();
+        assert_eq!(n, 4);
+        assert_eq!(decoded, hdr);
+    }
+
+    #[test]
+    fn test_family_from_attrs() {
+        let mut grp = NlAttr::new(CTRL_ATTR_MCAST_GRP_ID, &7u32.to_ne_bytes()).bytes();
+        grp.extend(NlAttr::new(CTRL_ATTR_MCAST_GRP_NAME, b"monitor\0").bytes());
+
+        let mut bytes = NlAttr::new(CTRL_ATTR_FAMILY_NAME, b"test\0").bytes();
+        bytes.extend(NlAttr::new(CTRL_ATTR_FAMILY_ID, &0x1au16.to_ne_bytes()).bytes());
+        bytes.extend(NlAttr::new(CTRL_ATTR_VERSION, &1u32.to_ne_bytes()).bytes());
+        bytes.extend(array_attr(CTRL_ATTR_MCAST_GROUPS, &[grp]));
+        let op = |cmd: u32, flags: u32| {
+            let mut op = NlAttr::new(CTRL_ATTR_OP_ID, &cmd.to_ne_bytes()).bytes();
+            op.extend(NlAttr::new(CTRL_ATTR_OP_FLAGS, &flags.to_ne_bytes()).bytes());
+            op
+        };
+        bytes.extend(array_attr(CTRL_ATTR_OPS, &[op(1, 0), op(2, 1)]));
+
+        let family = GenlFamily::from_attrs(&bytes).unwrap();
+        assert_eq!(family.name(), "test");
+        assert_eq!(family.id(), 0x1a);
+        assert_eq!(family.version(), 1);
+        assert_eq!(family.group("monitor").unwrap().id(), 7);
+        assert!(family.group("other").is_none());
+        assert_eq!(family.op(2).unwrap().flags(), 1);
+        assert!(family.op(3).is_none());
+    }
+
+    #[test]
+    fn test_resolve_nlctrl() {
+        let mut socket = Socket::new(Protocol::Generic).unwrap();
+        let family = GenlFamily::resolve(&mut socket, "nlctrl").unwrap();
+        assert_eq!(family.id(), GENL_ID_CTRL);
+        assert!(family.group("notify").is_some());
+        let op = family.op(CTRL_CMD_GETFAMILY).unwrap();
+        assert!(op.supports_do() && op.supports_dump() && !op.needs_admin());
+    }
+
+    #[test]
+    fn test_resolve_unknown_family() {
+        let mut socket = Socket::new(Protocol::Generic).unwrap();
+        let err = GenlFamily::resolve(&mut socket, "no-such-family").unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(::libc::ENOENT));
+    }
+}
@@ -0,0 +1,385 @@
+//! MPTCP in-kernel path manager generic netlink family
+//!
+//! Configures the endpoints (local addresses) the path manager uses for
+//! additional subflows, and the per-connection limits (`ip mptcp`).
+
+use super::GenlFamily;
+use socket::{Socket, NetlinkTransport, NlAttr, NLA_F_NESTED};
+use Protocol;
+
+use std::io::{self, ErrorKind};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use libc::{AF_INET, AF_INET6};
+
+const MPTCP_PM_NAME: &str = "mptcp_pm";
+
+const MPTCP_PM_CMD_ADD_ADDR: u8 = 1;
+const MPTCP_PM_CMD_DEL_ADDR: u8 = 2;
+const MPTCP_PM_CMD_GET_ADDR: u8 = 3;
+const MPTCP_PM_CMD_SET_LIMITS: u8 = 5;
+const MPTCP_PM_CMD_GET_LIMITS: u8 = 6;
+
+const MPTCP_PM_ATTR_ADDR: u16 = 1;
+const MPTCP_PM_ATTR_RCV_ADD_ADDRS: u16 = 2;
+const MPTCP_PM_ATTR_SUBFLOWS: u16 = 3;
+
+const MPTCP_PM_ADDR_ATTR_FAMILY: u16 = 1;
+const MPTCP_PM_ADDR_ATTR_ID: u16 = 2;
+const MPTCP_PM_ADDR_ATTR_ADDR4: u16 = 3;
+const MPTCP_PM_ADDR_ATTR_ADDR6: u16 = 4;
+const MPTCP_PM_ADDR_ATTR_PORT: u16 = 5;
+const MPTCP_PM_ADDR_ATTR_FLAGS: u16 = 6;
+const MPTCP_PM_ADDR_ATTR_IF_IDX: u16 = 7;
+
+/// Endpoint flags
+#[derive(Clone, Copy)]
+enum Flags {
+    /// Announce the address to the peer (ADD_ADDR)
+    Signal,
+    /// Create subflows from this address
+    Subflow,
+    /// Subflows over this address are backup paths
+    Backup,
+    /// Create subflows to every announced peer address
+    Fullmesh,
+    /// Created by the kernel, not by the user
+    Implicit,
+}
+
+impl From<Flags> for u32 {
+    fn from(t: Flags) -> u32 {
+        use self::Flags::*;
+        match t {
+            Signal   =>  1,
+            Subflow  =>  2,
+            Backup   =>  4,
+            Fullmesh =>  8,
+            Implicit =>  16,
+        }
+    }
+}
+
+/// A path manager endpoint (`ip mptcp endpoint`)
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Endpoint {
+    id: u8,
+    addr: IpAddr,
+    port: Option<u16>,
+    flags: u32,
+    ifindex: Option<i32>,
+}
+
+impl Endpoint {
+    pub fn new(addr: IpAddr) -> Endpoint {
+        Endpoint {
+            id: 0,
+            addr,
+            port: None,
+            flags: 0,
+            ifindex: None,
+        }
+    }
+
+    fn from_attr(attr: &NlAttr) -> io::Result<Endpoint> {
+        let mut ep = Endpoint::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+        for a in attr.nested()? {
+            match a.attr_type() {
+                MPTCP_PM_ADDR_ATTR_ID => ep.id = a.get_u8()?,
+                MPTCP_PM_ADDR_ATTR_ADDR4 => {
+                    let p = a.payload();
+                    if p.len() != 4 {
+                        return Err(io::Error::new(ErrorKind::InvalidData, "bad IPv4 address length"));
+                    }
+                    ep.addr = IpAddr::V4(Ipv4Addr::new(p[0], p[1], p[2], p[3]));
+                },
+                MPTCP_PM_ADDR_ATTR_ADDR6 => {
+                    let mut octets = [0u8; 16];
+                    if a.payload().len() != octets.len() {
+                        return Err(io::Error::new(ErrorKind::InvalidData, "bad IPv6 address length"));
+                    }
+                    octets.copy_from_slice(a.payload());
+                    ep.addr = IpAddr::V6(Ipv6Addr::from(octets));
+                },
+                MPTCP_PM_ADDR_ATTR_PORT => {
+                    let port = a.get_u16()?;
+                    if port != 0 {
+                        ep.port = Some(port);
+                    }
+                },
+                MPTCP_PM_ADDR_ATTR_FLAGS => ep.flags = a.get_u32()?,
+                MPTCP_PM_ADDR_ATTR_IF_IDX => {
+                    let ifindex = a.get_u32()? as i32;
+                    if ifindex != 0 {
+                        ep.ifindex = Some(ifindex);
+                    }
+                },
+                _ => {},
+            }
+        }
+        Ok(ep)
+    }
+
+    /// Encodes the endpoint as a nested MPTCP_PM_ATTR_ADDR attribute.
+    fn attr(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        match self.addr {
+            IpAddr::V4(a) => {
+                bytes.extend(NlAttr::new(MPTCP_PM_ADDR_ATTR_FAMILY, &(AF_INET as u16).to_ne_bytes()).bytes());
+                bytes.extend(NlAttr::new(MPTCP_PM_ADDR_ATTR_ADDR4, &a.octets()).bytes());
+            },
+            IpAddr::V6(a) => {
+                bytes.extend(NlAttr::new(MPTCP_PM_ADDR_ATTR_FAMILY, &(AF_INET6 as u16).to_ne_bytes()).bytes());
+                bytes.extend(NlAttr::new(MPTCP_PM_ADDR_ATTR_ADDR6, &a.octets()).bytes());
+            },
+        }
+        if self.id != 0 {
+            bytes.extend(NlAttr::new(MPTCP_PM_ADDR_ATTR_ID, &[self.id]).bytes());
+        }
+        if let Some(port) = self.port {
+            bytes.extend(NlAttr::new(MPTCP_PM_ADDR_ATTR_PORT, &port.to_ne_bytes()).bytes());
+        }
+        if self.flags != 0 {
+            bytes.extend(NlAttr::new(MPTCP_PM_ADDR_ATTR_FLAGS, &self.flags.to_ne_bytes()).bytes());
+        }
+        if let Some(ifindex) = self.ifindex {
+            bytes.extend(NlAttr::new(MPTCP_PM_ADDR_ATTR_IF_IDX, &ifindex.to_ne_bytes()).bytes());
+        }
+        NlAttr::new(MPTCP_PM_ATTR_ADDR | NLA_F_NESTED, &bytes).bytes()
+    }
+
+    /// Set endpoint id, 0 lets the kernel pick one
+    pub fn set_id(&mut self, id: u8) -> &mut Endpoint {
+        self.id = id;
+        self
+    }
+
+    /// Set port to listen on for MP_JOIN requests
+    pub fn set_port(&mut self, port: u16) -> &mut Endpoint {
+        self.port = Some(port);
+        self
+    }
+
+    /// Set interface subflows from this endpoint are bound to
+    pub fn set_ifindex(&mut self, ifindex: i32) -> &mut Endpoint {
+        self.ifindex = Some(ifindex);
+        self
+    }
+
+    /// Announce the address to the peer
+    pub fn signal(&mut self) -> &mut Endpoint {
+        self.flags |= u32::from(Flags::Signal);
+        self
+    }
+
+    /// Create subflows from this address
+    pub fn subflow(&mut self) -> &mut Endpoint {
+        self.flags |= u32::from(Flags::Subflow);
+        self
+    }
+
+    /// Use subflows over this address as backup paths
+    pub fn backup(&mut self) -> &mut Endpoint {
+        self.flags |= u32::from(Flags::Backup);
+        self
+    }
+
+    /// Create subflows to every announced peer address
+    pub fn fullmesh(&mut self) -> &mut Endpoint {
+        self.flags |= u32::from(Flags::Fullmesh);
+        self
+    }
+
+    pub fn id(&self) -> u8 {
+        self.id
+    }
+
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    pub fn port(&self) -> Option<u16> {
+        self.port
+    }
+
+    pub fn ifindex(&self) -> Option<i32> {
+        self.ifindex
+    }
+
+    pub fn is_signal(&self) -> bool {
+        self.flags & u32::from(Flags::Signal) != 0
+    }
+
+    pub fn is_subflow(&self) -> bool {
+        self.flags & u32::from(Flags::Subflow) != 0
+    }
+
+    pub fn is_backup(&self) -> bool {
+        self.flags & u32::from(Flags::Backup) != 0
+    }
+
+    pub fn is_fullmesh(&self) -> bool {
+        self.flags & u32::from(Flags::Fullmesh) != 0
+    }
+
+    pub fn is_implicit(&self) -> bool {
+        self.flags & u32::from(Flags::Implicit) != 0
+    }
+}
+
+/// Per-connection path manager limits (`ip mptcp limits`)
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Limits {
+    subflows: u32,
+    add_addr_accepted: u32,
+}
+
+impl Limits {
+    pub fn new(subflows: u32, add_addr_accepted: u32) -> Limits {
+        Limits {
+            subflows,
+            add_addr_accepted,
+        }
+    }
+
+    fn from_attrs(bytes: &[u8]) -> io::Result<Limits> {
+        let mut limits = Limits::new(0, 0);
+        for attr in NlAttr::parse(bytes)? {
+            match attr.attr_type() {
+                MPTCP_PM_ATTR_SUBFLOWS => limits.subflows = attr.get_u32()?,
+                MPTCP_PM_ATTR_RCV_ADD_ADDRS => limits.add_addr_accepted = attr.get_u32()?,
+                _ => {},
+            }
+        }
+        Ok(limits)
+    }
+
+    fn attrs(&self) -> Vec<u8> {
+        let mut bytes = NlAttr::new(MPTCP_PM_ATTR_RCV_ADD_ADDRS, &self.add_addr_accepted.to_ne_bytes()).bytes();
+        bytes.extend(NlAttr::new(MPTCP_PM_ATTR_SUBFLOWS, &self.subflows.to_ne_bytes()).bytes());
+        bytes
+    }
+
+    /// Maximum number of additional subflows per connection
+    pub fn subflows(&self) -> u32 {
+        self.subflows
+    }
+
+    /// Maximum number of ADD_ADDR announcements accepted per connection
+    pub fn add_addr_accepted(&self) -> u32 {
+        self.add_addr_accepted
+    }
+}
+
+/// Handle to the mptcp_pm family
+pub struct Mptcp<T: NetlinkTransport = Socket> {
+    socket: T,
+    family: GenlFamily,
+}
+
+impl Mptcp {
+    pub fn new() -> io::Result<Mptcp> {
+        Mptcp::from_transport(Socket::new(Protocol::Generic)?)
+    }
+}
+
+impl<T: NetlinkTransport> Mptcp<T> {
+    /// Like `new`, but talks to the kernel through `transport`.
+    pub fn from_transport(mut transport: T) -> io::Result<Mptcp<T>> {
+        let family = GenlFamily::resolve(&mut transport, MPTCP_PM_NAME)?;
+        Ok(Mptcp {
+            socket: transport,
+            family,
+        })
+    }
+
+    /// Lists the configured endpoints (MPTCP_PM_CMD_GET_ADDR dump).
+    pub fn endpoints(&mut self) -> io::Result<Vec<Endpoint>> {
+        let replies = self.family.dump(&mut self.socket, MPTCP_PM_CMD_GET_ADDR, &[])?;
+        let mut endpoints = vec![];
+        for reply in replies {
+            for attr in NlAttr::parse(&reply)? {
+                if attr.attr_type() == MPTCP_PM_ATTR_ADDR {
+                    endpoints.push(Endpoint::from_attr(&attr)?);
+                }
+            }
+        }
+        Ok(endpoints)
+    }
+
+    pub fn add_endpoint(&mut self, endpoint: &Endpoint) -> io::Result<()> {
+        self.family.request(&mut self.socket, MPTCP_PM_CMD_ADD_ADDR, &endpoint.attr())?;
+        Ok(())
+    }
+
+    pub fn del_endpoint(&mut self, id: u8) -> io::Result<()> {
+        let mut endpoint = Endpoint::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+        endpoint.set_id(id);
+        self.family.request(&mut self.socket, MPTCP_PM_CMD_DEL_ADDR, &endpoint.attr())?;
+        Ok(())
+    }
+
+    pub fn limits(&mut self) -> io::Result<Limits> {
+        let replies = self.family.request(&mut self.socket, MPTCP_PM_CMD_GET_LIMITS, &[])?;
+        match replies.first() {
+            Some(reply) => Limits::from_attrs(reply),
+            None => Err(io::Error::new(ErrorKind::NotFound, "no limits reply")),
+        }
+    }
+
+    pub fn set_limits(&mut self, limits: &Limits) -> io::Result<()> {
+        self.family.request(&mut self.socket, MPTCP_PM_CMD_SET_LIMITS, &limits.attrs())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket::NlAttr;
+    use std::net::IpAddr;
+
+    #[test]
+    fn test_endpoint_roundtrip() {
+        let mut ep = Endpoint::new("10.0.0.2".parse().unwrap());
+        ep.set_id(3).set_port(8080).set_ifindex(2).signal().backup();
+
+        let bytes = ep.attr();
+        let (attr, _) = NlAttr::from_bytes(&bytes).unwrap();
+        let decoded = Endpoint::from_attr(&attr).unwrap();
+        assert_eq!(decoded, ep);
+        assert!(decoded.is_signal());
+        assert!(decoded.is_backup());
+        assert!(!decoded.is_subflow());
+        assert_eq!(decoded.port(), Some(8080));
+    }
+
+    #[test]
+    fn test_endpoint_v6() {
+        let addr: IpAddr = "2001:db8::1".parse().unwrap();
+        let mut ep = Endpoint::new(addr);
+        ep.subflow().fullmesh();
+
+        let bytes = ep.attr();
+        let (attr, _) = NlAttr::from_bytes(&bytes).unwrap();
+        let decoded = Endpoint::from_attr(&attr).unwrap();
+        assert_eq!(decoded.addr(), addr);
+        assert_eq!(decoded.id(), 0);
+        assert!(decoded.is_fullmesh());
+    }
+
+    #[test]
+    fn test_limits_roundtrip() {
+        let limits = Limits::new(4, 2);
+        assert_eq!(Limits::from_attrs(&limits.attrs()).unwrap(), limits);
+    }
+
+    #[test]
+    fn test_get_limits() {
+        let mut mptcp = Mptcp::new().unwrap();
+        let limits = mptcp.limits().unwrap();
+        assert!(limits.subflows() <= 8);
+    }
+}
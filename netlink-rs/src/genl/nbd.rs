@@ -0,0 +1,348 @@
+//! nbd generic netlink family
+//!
+//! Hands connected sockets to the network block device driver and manages
+//! the /dev/nbdN devices using them, as `nbd-client -N` does.
+
+use super::GenlFamily;
+use socket::{Socket, NetlinkTransport, NlAttr, AttrWriter};
+use Protocol;
+
+use std::io::{self, ErrorKind};
+use std::os::unix::io::RawFd;
+
+const NBD_GENL_FAMILY_NAME: &str = "nbd";
+
+const NBD_CMD_CONNECT: u8 = 1;
+const NBD_CMD_DISCONNECT: u8 = 2;
+const NBD_CMD_RECONFIGURE: u8 = 3;
+const NBD_CMD_STATUS: u8 = 5;
+
+const NBD_ATTR_INDEX: u16 = 1;
+const NBD_ATTR_SIZE_BYTES: u16 = 2;
+const NBD_ATTR_BLOCK_SIZE_BYTES: u16 = 3;
+const NBD_ATTR_TIMEOUT: u16 = 4;
+const NBD_ATTR_SERVER_FLAGS: u16 = 5;
+const NBD_ATTR_CLIENT_FLAGS: u16 = 6;
+const NBD_ATTR_SOCKETS: u16 = 7;
+const NBD_ATTR_DEAD_CONN_TIMEOUT: u16 = 8;
+const NBD_ATTR_DEVICE_LIST: u16 = 9;
+const NBD_ATTR_BACKEND_IDENTIFIER: u16 = 10;
+
+const NBD_SOCK_ITEM: u16 = 1;
+const NBD_SOCK_FD: u16 = 1;
+
+const NBD_DEVICE_ITEM: u16 = 1;
+const NBD_DEVICE_INDEX: u16 = 1;
+const NBD_DEVICE_CONNECTED: u16 = 2;
+
+/// Transmission flags negotiated with the server, for `set_server_flags`
+pub const NBD_FLAG_HAS_FLAGS: u64 = 1;
+pub const NBD_FLAG_READ_ONLY: u64 = 1 << 1;
+pub const NBD_FLAG_SEND_FLUSH: u64 = 1 << 2;
+pub const NBD_FLAG_SEND_FUA: u64 = 1 << 3;
+pub const NBD_FLAG_SEND_TRIM: u64 = 1 << 5;
+pub const NBD_FLAG_SEND_WRITE_ZEROES: u64 = 1 << 6;
+pub const NBD_FLAG_CAN_MULTI_CONN: u64 = 1 << 8;
+
+/// Client flags
+#[derive(Clone, Copy)]
+enum ClientFlags {
+    /// Free the device when it is disconnected
+    DestroyOnDisconnect,
+    /// Disconnect when the last opener closes the device
+    DisconnectOnClose,
+}
+
+impl From<ClientFlags> for u64 {
+    fn from(t: ClientFlags) -> u64 {
+        match t {
+            ClientFlags::DestroyOnDisconnect => 1,
+            ClientFlags::DisconnectOnClose   => 2,
+        }
+    }
+}
+
+/// Settings of a device, for `Nbd::connect` and `Nbd::reconfigure`
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct NbdConfig {
+    index: Option<u32>,
+    size: Option<u64>,
+    block_size: Option<u64>,
+    timeout: Option<u64>,
+    dead_conn_timeout: Option<u64>,
+    server_flags: Option<u64>,
+    client_flags: Option<u64>,
+    sockets: Vec<RawFd>,
+    backend: Option<String>,
+}
+
+impl NbdConfig {
+    pub fn new() -> NbdConfig {
+        NbdConfig::default()
+    }
+
+    fn attrs(&self) -> io::Result<Vec<u8>> {
+        let mut w = AttrWriter::new();
+        if let Some(index) = self.index {
+            w.put_u32(NBD_ATTR_INDEX, index)?;
+        }
+        if let Some(size) = self.size {
+            w.put_u64(NBD_ATTR_SIZE_BYTES, size)?;
+        }
+        if let Some(size) = self.block_size {
+            w.put_u64(NBD_ATTR_BLOCK_SIZE_BYTES, size)?;
+        }
+        if let Some(timeout) = self.timeout {
+            w.put_u64(NBD_ATTR_TIMEOUT, timeout)?;
+        }
+        if let Some(timeout) = self.dead_conn_timeout {
+            w.put_u64(NBD_ATTR_DEAD_CONN_TIMEOUT, timeout)?;
+        }
+        if let Some(flags) = self.server_flags {
+            w.put_u64(NBD_ATTR_SERVER_FLAGS, flags)?;
+        }
+        if let Some(flags) = self.client_flags {
+            w.put_u64(NBD_ATTR_CLIENT_FLAGS, flags)?;
+        }
+        if !self.sockets.is_empty() {
+            w.begin_nested(NBD_ATTR_SOCKETS)?;
+            for &fd in &self.sockets {
+                w.begin_nested(NBD_SOCK_ITEM)?.put_u32(NBD_SOCK_FD, fd as u32)?.end_nested()?;
+            }
+            w.end_nested()?;
+        }
+        if let Some(ref backend) = self.backend {
+            w.put_str(NBD_ATTR_BACKEND_IDENTIFIER, backend)?;
+        }
+        w.into_vec()
+    }
+
+    /// Set device to use, /dev/nbd`index`; otherwise the kernel picks a
+    /// free one on connect
+    pub fn set_index(&mut self, index: u32) -> &mut NbdConfig {
+        self.index = Some(index);
+        self
+    }
+
+    /// Set size of the export in bytes
+    pub fn set_size(&mut self, size: u64) -> &mut NbdConfig {
+        self.size = Some(size);
+        self
+    }
+
+    pub fn set_block_size(&mut self, block_size: u64) -> &mut NbdConfig {
+        self.block_size = Some(block_size);
+        self
+    }
+
+    /// Set request timeout in seconds
+    pub fn set_timeout(&mut self, timeout: u64) -> &mut NbdConfig {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Set seconds to wait for a dead connection to be replaced before
+    /// failing requests
+    pub fn set_dead_conn_timeout(&mut self, timeout: u64) -> &mut NbdConfig {
+        self.dead_conn_timeout = Some(timeout);
+        self
+    }
+
+    /// Set transmission flags from the handshake, see NBD_FLAG_*
+    pub fn set_server_flags(&mut self, flags: u64) -> &mut NbdConfig {
+        self.server_flags = Some(flags);
+        self
+    }
+
+    /// Free the device when it is disconnected
+    pub fn destroy_on_disconnect(&mut self) -> &mut NbdConfig {
+        *self.client_flags.get_or_insert(0) |= u64::from(ClientFlags::DestroyOnDisconnect);
+        self
+    }
+
+    /// Disconnect when the last opener closes the device
+    pub fn disconnect_on_close(&mut self) -> &mut NbdConfig {
+        *self.client_flags.get_or_insert(0) |= u64::from(ClientFlags::DisconnectOnClose);
+        self
+    }
+
+    /// Add socket connected to the server after the handshake, one per
+    /// connection
+    pub fn add_socket(&mut self, fd: RawFd) -> &mut NbdConfig {
+        self.sockets.push(fd);
+        self
+    }
+
+    /// Set identifier of the export, reported in sysfs
+    pub fn set_backend(&mut self, backend: &str) -> &mut NbdConfig {
+        self.backend = Some(backend.into());
+        self
+    }
+}
+
+/// State of a device, as reported by NBD_CMD_STATUS
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct NbdDevice {
+    index: u32,
+    connected: bool,
+}
+
+impl NbdDevice {
+    fn from_attr(attr: &NlAttr) -> io::Result<NbdDevice> {
+        let mut dev = NbdDevice {
+            index: 0,
+            connected: false,
+        };
+        for a in attr.nested()? {
+            match a.attr_type() {
+                NBD_DEVICE_INDEX => dev.index = a.get_u32()?,
+                NBD_DEVICE_CONNECTED => dev.connected = a.get_u8()? != 0,
+                _ => {},
+            }
+        }
+        Ok(dev)
+    }
+
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+}
+
+fn devices_from_attrs(bytes: &[u8]) -> io::Result<Vec<NbdDevice>> {
+    let mut devices = vec![];
+    for attr in NlAttr::parse(bytes)? {
+        if attr.attr_type() == NBD_ATTR_DEVICE_LIST {
+            for item in attr.nested()? {
+                if item.attr_type() == NBD_DEVICE_ITEM {
+                    devices.push(NbdDevice::from_attr(&item)?);
+                }
+            }
+        }
+    }
+    Ok(devices)
+}
+
+/// Handle to the nbd family
+pub struct Nbd<T: NetlinkTransport = Socket> {
+    socket: T,
+    family: GenlFamily,
+}
+
+impl Nbd {
+    pub fn new() -> io::Result<Nbd> {
+        Nbd::from_transport(Socket::new(Protocol::Generic)?)
+    }
+}
+
+impl<T: NetlinkTransport> Nbd<T> {
+    /// Like `new`, but talks to the kernel through `transport`.
+    pub fn from_transport(mut transport: T) -> io::Result<Nbd<T>> {
+        let family = GenlFamily::resolve(&mut transport, NBD_GENL_FAMILY_NAME)?;
+        Ok(Nbd {
+            socket: transport,
+            family,
+        })
+    }
+
+    /// Sets up a device with the sockets of `config` and returns its index.
+    pub fn connect(&mut self, config: &NbdConfig) -> io::Result<u32> {
+        let replies = self.family.request(&mut self.socket, NBD_CMD_CONNECT, &config.attrs()?)?;
+        for reply in replies {
+            for attr in NlAttr::parse(&reply)? {
+                if attr.attr_type() == NBD_ATTR_INDEX {
+                    return Ok(attr.get_u32()?);
+                }
+            }
+        }
+        Err(io::Error::new(ErrorKind::NotFound, "no connect reply"))
+    }
+
+    pub fn disconnect(&mut self, index: u32) -> io::Result<()> {
+        let mut w = AttrWriter::new();
+        w.put_u32(NBD_ATTR_INDEX, index)?;
+        self.family.request(&mut self.socket, NBD_CMD_DISCONNECT, w.bytes())?;
+        Ok(())
+    }
+
+    /// Replaces dead sockets of a connected device, or changes its timeouts
+    /// and client flags. The index of `config` must be set.
+    pub fn reconfigure(&mut self, config: &NbdConfig) -> io::Result<()> {
+        if config.index.is_none() {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "reconfigure needs a device index"));
+        }
+        self.family.request(&mut self.socket, NBD_CMD_RECONFIGURE, &config.attrs()?)?;
+        Ok(())
+    }
+
+    /// Reports the device `index`, or all devices when `None`.
+    pub fn status(&mut self, index: Option<u32>) -> io::Result<Vec<NbdDevice>> {
+        let mut w = AttrWriter::new();
+        if let Some(i) = index {
+            w.put_u32(NBD_ATTR_INDEX, i)?;
+        }
+        let mut devices = vec![];
+        for reply in self.family.request(&mut self.socket, NBD_CMD_STATUS, w.bytes())? {
+            devices.extend(devices_from_attrs(&reply)?);
+        }
+        Ok(devices)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use libc::ENOENT;
+
+    #[test]
+    fn test_config_attrs() {
+        let mut config = NbdConfig::new();
+        config.set_size(1 << 30).set_server_flags(NBD_FLAG_HAS_FLAGS | NBD_FLAG_SEND_FLUSH)
+            .destroy_on_disconnect().disconnect_on_close()
+            .add_socket(5).add_socket(6);
+
+        let bytes = config.attrs().unwrap();
+        let attrs = NlAttr::parse(&bytes).unwrap();
+        assert_eq!(attrs[0].attr_type(), NBD_ATTR_SIZE_BYTES);
+        assert_eq!(attrs[0].payload(), &(1u64 << 30).to_ne_bytes());
+        assert_eq!(attrs[2].payload(), &3u64.to_ne_bytes());
+        let sockets = attrs[3].nested().unwrap();
+        assert_eq!(sockets.len(), 2);
+        assert_eq!(sockets[1].nested().unwrap()[0].payload(), &6u32.to_ne_bytes());
+    }
+
+    #[test]
+    fn test_devices_decode() {
+        let mut w = AttrWriter::new();
+        w.begin_nested(NBD_ATTR_DEVICE_LIST).unwrap()
+            .begin_nested(NBD_DEVICE_ITEM).unwrap()
+            .put_u32(NBD_DEVICE_INDEX, 2).unwrap()
+            .put_u8(NBD_DEVICE_CONNECTED, 1).unwrap()
+            .end_nested().unwrap()
+            .end_nested().unwrap();
+        let bytes = w.into_vec().unwrap();
+
+        let devices = devices_from_attrs(&bytes).unwrap();
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].index(), 2);
+        assert!(devices[0].is_connected());
+    }
+
+    #[test]
+    fn test_nbd_status() {
+        let mut nbd = match Nbd::new() {
+            Ok(nbd) => nbd,
+            // nbd not loaded
+            Err(ref e) if e.raw_os_error() == Some(ENOENT) => return,
+            Err(e) => panic!("{}", e),
+        };
+        nbd.status(None).unwrap();
+        assert_eq!(nbd.reconfigure(&NbdConfig::new()).unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+}
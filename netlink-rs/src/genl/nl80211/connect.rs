@@ -0,0 +1,328 @@
+use super::{Nl80211, NL80211_GENL_NAME, NL80211_ATTR_IFINDEX, NL80211_ATTR_MAC};
+use genl::{GenlFamily, GenlMsgHeader};
+use socket::{Socket, NetlinkTransport, NlAttr, MacAddr, MsgType, Payload};
+use Protocol;
+
+use std::io::{self, ErrorKind};
+
+const NL80211_MLME_GROUP: &str = "mlme";
+
+const NL80211_CMD_AUTHENTICATE: u8 = 37;
+const NL80211_CMD_ASSOCIATE: u8 = 38;
+const NL80211_CMD_DEAUTHENTICATE: u8 = 39;
+const NL80211_CMD_DISASSOCIATE: u8 = 40;
+const NL80211_CMD_CONNECT: u8 = 46;
+const NL80211_CMD_ROAM: u8 = 47;
+const NL80211_CMD_DISCONNECT: u8 = 48;
+
+const NL80211_ATTR_WIPHY_FREQ: u16 = 38;
+const NL80211_ATTR_IE: u16 = 42;
+const NL80211_ATTR_SSID: u16 = 52;
+const NL80211_ATTR_AUTH_TYPE: u16 = 53;
+const NL80211_ATTR_REASON_CODE: u16 = 54;
+const NL80211_ATTR_TIMED_OUT: u16 = 65;
+const NL80211_ATTR_PRIVACY: u16 = 70;
+const NL80211_ATTR_DISCONNECTED_BY_AP: u16 = 71;
+const NL80211_ATTR_STATUS_CODE: u16 = 72;
+
+/// IEEE 802.11 reason code 3, the station is leaving
+pub const WLAN_REASON_DEAUTH_LEAVING: u16 = 3;
+
+/// Authentication algorithm of a connection
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum AuthType {
+    OpenSystem,
+    SharedKey,
+    /// Fast BSS transition (802.11r)
+    Ft,
+    NetworkEap,
+    Sae,
+}
+
+impl From<AuthType> for u32 {
+    fn from(t: AuthType) -> u32 {
+        match t {
+            AuthType::OpenSystem => 0,
+            AuthType::SharedKey => 1,
+            AuthType::Ft => 2,
+            AuthType::NetworkEap => 3,
+            AuthType::Sae => 4,
+        }
+    }
+}
+
+/// Parameters of NL80211_CMD_CONNECT, where the kernel or the device
+/// handles authentication and association
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct ConnectParams {
+    ssid: Vec<u8>,
+    bssid: Option<MacAddr>,
+    freq: Option<u32>,
+    auth_type: Option<AuthType>,
+    privacy: bool,
+    ie: Vec<u8>,
+}
+
+impl ConnectParams {
+    pub fn new(ssid: &[u8]) -> ConnectParams {
+        ConnectParams {
+            ssid: ssid.to_vec(),
+            bssid: None,
+            freq: None,
+            auth_type: None,
+            privacy: false,
+            ie: vec![],
+        }
+    }
+
+    fn attrs(&self, ifindex: u32) -> Vec<u8> {
+        let mut bytes = NlAttr::new(NL80211_ATTR_IFINDEX, &ifindex.to_ne_bytes()).bytes();
+        bytes.extend(NlAttr::new(NL80211_ATTR_SSID, &self.ssid).bytes());
+        if let Some(bssid) = self.bssid {
+            bytes.extend(bssid.attr(NL80211_ATTR_MAC));
+        }
+        if let Some(freq) = self.freq {
+            bytes.extend(NlAttr::new(NL80211_ATTR_WIPHY_FREQ, &freq.to_ne_bytes()).bytes());
+        }
+        if let Some(auth_type) = self.auth_type {
+            bytes.extend(NlAttr::new(NL80211_ATTR_AUTH_TYPE, &u32::from(auth_type).to_ne_bytes()).bytes());
+        }
+        if self.privacy {
+            bytes.extend(NlAttr::new(NL80211_ATTR_PRIVACY, &[]).bytes());
+        }
+        if !self.ie.is_empty() {
+            bytes.extend(NlAttr::new(NL80211_ATTR_IE, &self.ie).bytes());
+        }
+        bytes
+    }
+
+    /// Set access point to connect to, otherwise any with the SSID
+    pub fn set_bssid(&mut self, bssid: MacAddr) -> &mut ConnectParams {
+        self.bssid = Some(bssid);
+        self
+    }
+
+    /// Set channel by frequency in MHz
+    pub fn set_freq(&mut self, freq: u32) -> &mut ConnectParams {
+        self.freq = Some(freq);
+        self
+    }
+
+    /// Set authentication algorithm, otherwise the kernel tries those that
+    /// fit
+    pub fn set_auth_type(&mut self, auth_type: AuthType) -> &mut ConnectParams {
+        self.auth_type = Some(auth_type);
+        self
+    }
+
+    /// Require an access point with privacy (WEP or better)
+    pub fn privacy(&mut self) -> &mut ConnectParams {
+        self.privacy = true;
+        self
+    }
+
+    /// Set information elements to add to the association request, such as
+    /// the RSN element of a WPA2 network
+    pub fn set_ie(&mut self, ie: &[u8]) -> &mut ConnectParams {
+        self.ie = ie.to_vec();
+        self
+    }
+}
+
+/// Connection state change reported on the mlme group
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum MlmeEvent {
+    /// Result of a connection attempt; `status` is the IEEE 802.11 status
+    /// code, 0 on success
+    Connect { ifindex: u32, bssid: Option<MacAddr>, status: u16, timed_out: bool },
+    /// Moved to another access point of the same network
+    Roam { ifindex: u32, bssid: Option<MacAddr> },
+    /// Connection lost; `reason` is the IEEE 802.11 reason code
+    Disconnect { ifindex: u32, reason: u16, by_ap: bool },
+    Authenticate { ifindex: u32, timed_out: bool },
+    Associate { ifindex: u32, timed_out: bool },
+    Deauthenticate { ifindex: u32 },
+    Disassociate { ifindex: u32 },
+}
+
+impl MlmeEvent {
+    /// Decodes an nl80211 message, genl header included.
+    fn from_genl(bytes: &[u8]) -> io::Result<Option<MlmeEvent>> {
+        let (hdr, n) = GenlMsgHeader::from_bytes(bytes)?;
+        let mut ifindex = 0;
+        let mut bssid = None;
+        let mut status = 0;
+        let mut reason = 0;
+        let mut timed_out = false;
+        let mut by_ap = false;
+        for attr in NlAttr::parse(&bytes[n..])? {
+            match attr.attr_type() {
+                NL80211_ATTR_IFINDEX => ifindex = attr.get_u32()?,
+                NL80211_ATTR_MAC => bssid = Some(attr.get_mac()?),
+                NL80211_ATTR_STATUS_CODE => status = attr.get_u16()?,
+                NL80211_ATTR_REASON_CODE => reason = attr.get_u16()?,
+                NL80211_ATTR_TIMED_OUT => timed_out = true,
+                NL80211_ATTR_DISCONNECTED_BY_AP => by_ap = true,
+                _ => {},
+            }
+        }
+        Ok(Some(match hdr.cmd() {
+            NL80211_CMD_CONNECT => MlmeEvent::Connect { ifindex, bssid, status, timed_out },
+            NL80211_CMD_ROAM => MlmeEvent::Roam { ifindex, bssid },
+            NL80211_CMD_DISCONNECT => MlmeEvent::Disconnect { ifindex, reason, by_ap },
+            NL80211_CMD_AUTHENTICATE => MlmeEvent::Authenticate { ifindex, timed_out },
+            NL80211_CMD_ASSOCIATE => MlmeEvent::Associate { ifindex, timed_out },
+            NL80211_CMD_DEAUTHENTICATE => MlmeEvent::Deauthenticate { ifindex },
+            NL80211_CMD_DISASSOCIATE => MlmeEvent::Disassociate { ifindex },
+            _ => return Ok(None),
+        }))
+    }
+
+    /// Interface the event is about
+    pub fn ifindex(&self) -> u32 {
+        match *self {
+            MlmeEvent::Connect { ifindex, .. } |
+            MlmeEvent::Roam { ifindex, .. } |
+            MlmeEvent::Disconnect { ifindex, .. } |
+            MlmeEvent::Authenticate { ifindex, .. } |
+            MlmeEvent::Associate { ifindex, .. } |
+            MlmeEvent::Deauthenticate { ifindex } |
+            MlmeEvent::Disassociate { ifindex } => ifindex,
+        }
+    }
+}
+
+impl<T: NetlinkTransport> Nl80211<T> {
+    /// Starts connecting `ifindex`; the outcome is reported as a Connect
+    /// event on the mlme group.
+    pub fn connect(&mut self, ifindex: u32, params: &ConnectParams) -> io::Result<()> {
+        self.family.request(&mut self.socket, NL80211_CMD_CONNECT, &params.attrs(ifindex))?;
+        Ok(())
+    }
+
+    /// Disconnects `ifindex`, telling the access point `reason`.
+    pub fn disconnect(&mut self, ifindex: u32, reason: u16) -> io::Result<()> {
+        let mut attrs = NlAttr::new(NL80211_ATTR_IFINDEX, &ifindex.to_ne_bytes()).bytes();
+        attrs.extend(NlAttr::new(NL80211_ATTR_REASON_CODE, &reason.to_ne_bytes()).bytes());
+        self.family.request(&mut self.socket, NL80211_CMD_DISCONNECT, &attrs)?;
+        Ok(())
+    }
+}
+
+/// A socket subscribed to the nl80211 mlme multicast group
+pub struct MlmeMonitor<T: NetlinkTransport = Socket> {
+    socket: T,
+    family: GenlFamily,
+}
+
+impl MlmeMonitor {
+    pub fn new() -> io::Result<MlmeMonitor> {
+        let mut socket = Socket::new(Protocol::Generic)?;
+        let family = GenlFamily::resolve(&mut socket, NL80211_GENL_NAME)?;
+        let group = family.group(NL80211_MLME_GROUP)
+            .ok_or_else(|| io::Error::new(ErrorKind::NotFound, "nl80211 has no mlme group"))?;
+        socket.add_membership(group.id())?;
+        Ok(MlmeMonitor {
+            socket,
+            family,
+        })
+    }
+}
+
+impl<T: NetlinkTransport> MlmeMonitor<T> {
+    /// Reads events from `transport`, which is already subscribed to the
+    /// mlme group; the family is resolved through it.
+    pub fn from_transport(mut transport: T) -> io::Result<MlmeMonitor<T>> {
+        let family = GenlFamily::resolve(&mut transport, NL80211_GENL_NAME)?;
+        Ok(MlmeMonitor {
+            socket: transport,
+            family,
+        })
+    }
+
+    /// Blocks until events arrive. Fails with an `Overrun` error if some
+    /// were dropped, which cannot be recovered.
+    pub fn recv(&mut self) -> io::Result<Vec<MlmeEvent>> {
+        loop {
+            let mut events = vec![];
+            let messages = self.socket.recv_msgs()?;
+            for msg in messages {
+                match msg.header().msg_type() {
+                    MsgType::UserDefined(t) if t == self.family.id() => {},
+                    _ => continue,
+                }
+                if let Payload::Data(ref b) = *msg.payload() {
+                    events.extend(MlmeEvent::from_genl(b)?);
+                }
+            }
+            if !events.is_empty() {
+                return Ok(events);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use libc::ENOENT;
+
+    fn event(cmd: u8, attrs: &[Vec<u8>]) -> Vec<u8> {
+        let mut bytes = GenlMsgHeader::new(cmd, 1).bytes().to_vec();
+        bytes.extend(NlAttr::new(NL80211_ATTR_IFINDEX, &3u32.to_ne_bytes()).bytes());
+        for a in attrs {
+            bytes.extend_from_slice(a);
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_mlme_events() {
+        let bssid = MacAddr::new([0x02, 0, 0, 0, 0, 1]);
+        let connect = event(NL80211_CMD_CONNECT, &[
+            bssid.attr(NL80211_ATTR_MAC),
+            NlAttr::new(NL80211_ATTR_STATUS_CODE, &0u16.to_ne_bytes()).bytes(),
+        ]);
+        assert_eq!(MlmeEvent::from_genl(&connect).unwrap(),
+                   Some(MlmeEvent::Connect { ifindex: 3, bssid: Some(bssid), status: 0, timed_out: false }));
+
+        let disconnect = event(NL80211_CMD_DISCONNECT, &[
+            NlAttr::new(NL80211_ATTR_REASON_CODE, &WLAN_REASON_DEAUTH_LEAVING.to_ne_bytes()).bytes(),
+            NlAttr::new(NL80211_ATTR_DISCONNECTED_BY_AP, &[]).bytes(),
+        ]);
+        let disconnect = MlmeEvent::from_genl(&disconnect).unwrap().unwrap();
+        assert_eq!(disconnect, MlmeEvent::Disconnect { ifindex: 3, reason: 3, by_ap: true });
+        assert_eq!(disconnect.ifindex(), 3);
+
+        let auth = event(NL80211_CMD_AUTHENTICATE, &[NlAttr::new(NL80211_ATTR_TIMED_OUT, &[]).bytes()]);
+        assert_eq!(MlmeEvent::from_genl(&auth).unwrap(),
+                   Some(MlmeEvent::Authenticate { ifindex: 3, timed_out: true }));
+        assert_eq!(MlmeEvent::from_genl(&event(NL80211_CMD_ASSOCIATE + 100, &[])).unwrap(), None);
+    }
+
+    #[test]
+    fn test_connect_attrs() {
+        let mut params = ConnectParams::new(b"lab");
+        params.set_freq(2412).set_auth_type(AuthType::OpenSystem).privacy();
+        let bytes = params.attrs(3);
+        let attrs = NlAttr::parse(&bytes).unwrap();
+        let types: Vec<u16> = attrs.iter().map(|a| a.attr_type()).collect();
+        assert_eq!(types, [NL80211_ATTR_IFINDEX, NL80211_ATTR_SSID, NL80211_ATTR_WIPHY_FREQ,
+                           NL80211_ATTR_AUTH_TYPE, NL80211_ATTR_PRIVACY]);
+        assert_eq!(attrs[1].payload(), b"lab");
+    }
+
+    #[test]
+    fn test_mlme_monitor() {
+        match MlmeMonitor::new() {
+            Ok(_) => {},
+            // cfg80211 not loaded
+            Err(ref e) if e.raw_os_error() == Some(ENOENT) => {},
+            Err(e) => panic!("{}", e),
+        }
+    }
+}
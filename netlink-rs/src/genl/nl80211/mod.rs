@@ -0,0 +1,46 @@
+//! nl80211 generic netlink family
+//!
+//! Configuration of Wi-Fi devices through cfg80211. Devices are named by
+//! the ifindex of their network interface; `Nl80211` sends the commands and
+//! `MlmeMonitor` follows the connection state the kernel reports on the
+//! `mlme` multicast group. Channel surveys report noise and busy time for
+//! channel selection.
+
+mod connect;
+mod survey;
+pub use self::connect::*;
+pub use self::survey::*;
+
+use super::GenlFamily;
+use socket::{Socket, NetlinkTransport};
+use Protocol;
+
+use std::io;
+
+const NL80211_GENL_NAME: &str = "nl80211";
+
+const NL80211_ATTR_IFINDEX: u16 = 3;
+const NL80211_ATTR_MAC: u16 = 6;
+
+/// Handle to the nl80211 family
+pub struct Nl80211<T: NetlinkTransport = Socket> {
+    socket: T,
+    family: GenlFamily,
+}
+
+impl Nl80211 {
+    pub fn new() -> io::Result<Nl80211> {
+        Nl80211::from_transport(Socket::new(Protocol::Generic)?)
+    }
+}
+
+impl<T: NetlinkTransport> Nl80211<T> {
+    /// Like `new`, but talks to the kernel through `transport`.
+    pub fn from_transport(mut transport: T) -> io::Result<Nl80211<T>> {
+        let family = GenlFamily::resolve(&mut transport, NL80211_GENL_NAME)?;
+        Ok(Nl80211 {
+            socket: transport,
+            family,
+        })
+    }
+}
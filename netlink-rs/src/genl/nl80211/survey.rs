@@ -0,0 +1,147 @@
+use super::{Nl80211, NL80211_ATTR_IFINDEX};
+use socket::{NetlinkTransport, NlAttr};
+
+use std::io::{self, ErrorKind};
+
+const NL80211_CMD_GET_SURVEY: u8 = 50;
+
+const NL80211_ATTR_SURVEY_INFO: u16 = 84;
+
+const NL80211_SURVEY_INFO_FREQUENCY: u16 = 1;
+const NL80211_SURVEY_INFO_NOISE: u16 = 2;
+const NL80211_SURVEY_INFO_IN_USE: u16 = 3;
+const NL80211_SURVEY_INFO_TIME: u16 = 4;
+const NL80211_SURVEY_INFO_TIME_BUSY: u16 = 5;
+const NL80211_SURVEY_INFO_TIME_EXT_BUSY: u16 = 6;
+const NL80211_SURVEY_INFO_TIME_RX: u16 = 7;
+const NL80211_SURVEY_INFO_TIME_TX: u16 = 8;
+const NL80211_SURVEY_INFO_TIME_SCAN: u16 = 9;
+
+/// Survey of one channel. Times are in milliseconds since the driver
+/// started counting; drivers report only what they measure.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Survey {
+    freq: u32,
+    noise: Option<i8>,
+    in_use: bool,
+    time: Option<u64>,
+    busy: Option<u64>,
+    ext_busy: Option<u64>,
+    rx: Option<u64>,
+    tx: Option<u64>,
+    scan: Option<u64>,
+}
+
+impl Survey {
+    fn from_attrs(bytes: &[u8]) -> io::Result<Survey> {
+        let attrs = NlAttr::parse(bytes)?;
+        let info = attrs.iter().find(|a| a.attr_type() == NL80211_ATTR_SURVEY_INFO)
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "survey without info"))?;
+        let mut survey = Survey::default();
+        for attr in NlAttr::parse(info.payload())? {
+            match attr.attr_type() {
+                NL80211_SURVEY_INFO_FREQUENCY => survey.freq = attr.get_u32()?,
+                NL80211_SURVEY_INFO_NOISE => survey.noise = Some(attr.get_u8()? as i8),
+                NL80211_SURVEY_INFO_IN_USE => survey.in_use = true,
+                NL80211_SURVEY_INFO_TIME => survey.time = Some(attr.get_u64()?),
+                NL80211_SURVEY_INFO_TIME_BUSY => survey.busy = Some(attr.get_u64()?),
+                NL80211_SURVEY_INFO_TIME_EXT_BUSY => survey.ext_busy = Some(attr.get_u64()?),
+                NL80211_SURVEY_INFO_TIME_RX => survey.rx = Some(attr.get_u64()?),
+                NL80211_SURVEY_INFO_TIME_TX => survey.tx = Some(attr.get_u64()?),
+                NL80211_SURVEY_INFO_TIME_SCAN => survey.scan = Some(attr.get_u64()?),
+                _ => {},
+            }
+        }
+        Ok(survey)
+    }
+
+    /// Channel frequency in MHz
+    pub fn freq(&self) -> u32 {
+        self.freq
+    }
+
+    /// Noise level in dBm
+    pub fn noise(&self) -> Option<i8> {
+        self.noise
+    }
+
+    /// Whether the interface currently operates on this channel
+    pub fn in_use(&self) -> bool {
+        self.in_use
+    }
+
+    /// Time the radio was on the channel
+    pub fn time(&self) -> Option<u64> {
+        self.time
+    }
+
+    /// Time the channel was sensed busy
+    pub fn busy(&self) -> Option<u64> {
+        self.busy
+    }
+
+    /// Time the extension channel was sensed busy
+    pub fn ext_busy(&self) -> Option<u64> {
+        self.ext_busy
+    }
+
+    /// Time spent receiving
+    pub fn rx(&self) -> Option<u64> {
+        self.rx
+    }
+
+    /// Time spent transmitting
+    pub fn tx(&self) -> Option<u64> {
+        self.tx
+    }
+
+    /// Time spent on the channel for scans
+    pub fn scan(&self) -> Option<u64> {
+        self.scan
+    }
+
+    /// Fraction of the time the channel was busy, from 0 to 1
+    pub fn utilization(&self) -> Option<f64> {
+        match (self.time, self.busy) {
+            (Some(time), Some(busy)) if time > 0 => Some(busy as f64 / time as f64),
+            _ => None,
+        }
+    }
+}
+
+impl<T: NetlinkTransport> Nl80211<T> {
+    /// Fetches the surveys of the channels `ifindex` has visited.
+    pub fn surveys(&mut self, ifindex: u32) -> io::Result<Vec<Survey>> {
+        let attrs = NlAttr::new(NL80211_ATTR_IFINDEX, &ifindex.to_ne_bytes()).bytes();
+        let replies = self.family.dump(&mut self.socket, NL80211_CMD_GET_SURVEY, &attrs)?;
+        replies.iter().map(|r| Survey::from_attrs(r)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket::NLA_F_NESTED;
+
+    #[test]
+    fn test_survey() {
+        let mut info = NlAttr::new(NL80211_SURVEY_INFO_FREQUENCY, &2412u32.to_ne_bytes()).bytes();
+        info.extend(NlAttr::new(NL80211_SURVEY_INFO_NOISE, &[-95i8 as u8]).bytes());
+        info.extend(NlAttr::new(NL80211_SURVEY_INFO_IN_USE, &[]).bytes());
+        info.extend(NlAttr::new(NL80211_SURVEY_INFO_TIME, &400u64.to_ne_bytes()).bytes());
+        info.extend(NlAttr::new(NL80211_SURVEY_INFO_TIME_BUSY, &100u64.to_ne_bytes()).bytes());
+        let mut bytes = NlAttr::new(NL80211_ATTR_IFINDEX, &3u32.to_ne_bytes()).bytes();
+        bytes.extend(NlAttr::new(NL80211_ATTR_SURVEY_INFO | NLA_F_NESTED, &info).bytes());
+
+        let survey = Survey::from_attrs(&bytes).unwrap();
+        assert_eq!(survey.freq(), 2412);
+        assert_eq!(survey.noise(), Some(-95));
+        assert!(survey.in_use());
+        assert_eq!(survey.rx(), None);
+        assert_eq!(survey.utilization(), Some(0.25));
+
+        let bare = NlAttr::new(NL80211_ATTR_IFINDEX, &3u32.to_ne_bytes()).bytes();
+        assert_eq!(Survey::from_attrs(&bare).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,179 @@
+use super::{Ovs, exchange, OVS_CMD_NEW, OVS_CMD_DEL, OVS_CMD_GET, first_reply};
+use socket::{NetlinkTransport, NlAttr, string_attr};
+
+use std::io::{self, Cursor};
+
+use byteorder::{NativeEndian, ReadBytesExt};
+
+const OVS_DP_ATTR_NAME: u16 = 1;
+const OVS_DP_ATTR_UPCALL_PID: u16 = 2;
+const OVS_DP_ATTR_STATS: u16 = 3;
+const OVS_DP_ATTR_USER_FEATURES: u16 = 5;
+
+/// Allow last Netlink attribute to be unaligned
+const OVS_DP_F_UNALIGNED: u32 = 1 << 0;
+/// Allow datapath to associate multiple Netlink PIDs to each vport
+const OVS_DP_F_VPORT_PIDS: u32 = 1 << 1;
+
+/// Datapath lookup statistics
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct DpStats {
+    hit: u64,
+    missed: u64,
+    lost: u64,
+    flows: u64,
+}
+
+impl DpStats {
+    // struct ovs_dp_stats {
+    //     __u64 n_hit;
+    //     __u64 n_missed;
+    //     __u64 n_lost;
+    //     __u64 n_flows;
+    // };
+    fn from_bytes(bytes: &[u8]) -> io::Result<DpStats> {
+        let mut cursor = Cursor::new(bytes);
+        Ok(DpStats {
+            hit: cursor.read_u64::<NativeEndian>()?,
+            missed: cursor.read_u64::<NativeEndian>()?,
+            lost: cursor.read_u64::<NativeEndian>()?,
+            flows: cursor.read_u64::<NativeEndian>()?,
+        })
+    }
+
+    /// Packets that matched a flow
+    pub fn hit(&self) -> u64 {
+        self.hit
+    }
+
+    /// Packets that missed the flow table and were sent to userspace
+    pub fn missed(&self) -> u64 {
+        self.missed
+    }
+
+    /// Misses that could not be sent to userspace
+    pub fn lost(&self) -> u64 {
+        self.lost
+    }
+
+    /// Flows currently installed
+    pub fn flows(&self) -> u64 {
+        self.flows
+    }
+}
+
+/// A kernel datapath (`ovs-dpctl show`)
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Datapath {
+    ifindex: i32,
+    name: String,
+    stats: DpStats,
+    user_features: u32,
+}
+
+impl Datapath {
+    fn from_attrs(ifindex: i32, bytes: &[u8]) -> io::Result<Datapath> {
+        let mut dp = Datapath {
+            ifindex,
+            name: String::new(),
+            stats: DpStats::default(),
+            user_features: 0,
+        };
+
+        for attr in NlAttr::parse(bytes)? {
+            match attr.attr_type() {
+                OVS_DP_ATTR_NAME => dp.name = attr.get_str()?.to_string(),
+                OVS_DP_ATTR_STATS => dp.stats = DpStats::from_bytes(attr.payload())?,
+                OVS_DP_ATTR_USER_FEATURES => {
+                    dp.user_features = attr.get_u32()?
+                },
+                _ => {},
+            }
+        }
+        Ok(dp)
+    }
+
+    /// Ifindex of the datapath's local port, used to address it in requests
+    pub fn ifindex(&self) -> i32 {
+        self.ifindex
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn stats(&self) -> DpStats {
+        self.stats
+    }
+
+    /// OVS_DP_F_* features enabled on the datapath
+    pub fn user_features(&self) -> u32 {
+        self.user_features
+    }
+}
+
+fn name_attr(name: &str) -> Vec<u8> {
+    string_attr(OVS_DP_ATTR_NAME, name)
+}
+
+impl<T: NetlinkTransport> Ovs<T> {
+    /// Lists all datapaths (OVS_DP_CMD_GET dump).
+    pub fn datapaths(&mut self) -> io::Result<Vec<Datapath>> {
+        let replies = exchange(&mut self.socket, &self.datapath, OVS_CMD_GET, true, 0, &[])?;
+        replies.iter().map(|&(ifindex, ref attrs)| Datapath::from_attrs(ifindex, attrs)).collect()
+    }
+
+    /// Looks up a datapath by name.
+    pub fn datapath(&mut self, name: &str) -> io::Result<Datapath> {
+        let replies = exchange(&mut self.socket, &self.datapath, OVS_CMD_GET, false, 0,
+                               &name_attr(name))?;
+        let (ifindex, attrs) = first_reply(replies)?;
+        Datapath::from_attrs(ifindex, &attrs)
+    }
+
+    /// Creates a datapath. `upcall_pid` is the netlink port that receives
+    /// packets missing the flow table, 0 to disable upcalls.
+    pub fn new_datapath(&mut self, name: &str, upcall_pid: u32) -> io::Result<Datapath> {
+        let mut attrs = name_attr(name);
+        attrs.extend(NlAttr::new(OVS_DP_ATTR_UPCALL_PID, &upcall_pid.to_ne_bytes()).bytes());
+        let features = OVS_DP_F_UNALIGNED | OVS_DP_F_VPORT_PIDS;
+        attrs.extend(NlAttr::new(OVS_DP_ATTR_USER_FEATURES, &features.to_ne_bytes()).bytes());
+
+        let replies = exchange(&mut self.socket, &self.datapath, OVS_CMD_NEW, false, 0, &attrs)?;
+        let (ifindex, attrs) = first_reply(replies)?;
+        Datapath::from_attrs(ifindex, &attrs)
+    }
+
+    pub fn del_datapath(&mut self, name: &str) -> io::Result<()> {
+        exchange(&mut self.socket, &self.datapath, OVS_CMD_DEL, false, 0, &name_attr(name))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket::NlAttr;
+
+    #[test]
+    fn test_datapath_decode() {
+        let mut stats = vec![];
+        for v in &[10u64, 2, 1, 3] {
+            stats.extend_from_slice(&v.to_ne_bytes());
+        }
+        let mut bytes = name_attr("ovs-system");
+        bytes.extend(NlAttr::new(OVS_DP_ATTR_STATS, &stats).bytes());
+        bytes.extend(NlAttr::new(OVS_DP_ATTR_USER_FEATURES, &3u32.to_ne_bytes()).bytes());
+
+        let dp = Datapath::from_attrs(5, &bytes).unwrap();
+        assert_eq!(dp.ifindex(), 5);
+        assert_eq!(dp.name(), "ovs-system");
+        assert_eq!(dp.stats().hit(), 10);
+        assert_eq!(dp.stats().missed(), 2);
+        assert_eq!(dp.stats().lost(), 1);
+        assert_eq!(dp.stats().flows(), 3);
+        assert_eq!(dp.user_features(), OVS_DP_F_UNALIGNED | OVS_DP_F_VPORT_PIDS);
+    }
+}
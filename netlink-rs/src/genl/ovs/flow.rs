@@ -0,0 +1,456 @@
+use super::{Ovs, exchange, OVS_CMD_NEW, OVS_CMD_DEL, OVS_CMD_GET};
+use socket::{NetlinkTransport, NlAttr};
+
+use std::io::{self, ErrorKind, Cursor};
+use std::net::Ipv4Addr;
+
+use byteorder::{BigEndian, NativeEndian, ReadBytesExt};
+
+const OVS_FLOW_ATTR_KEY: u16 = 1;
+const OVS_FLOW_ATTR_ACTIONS: u16 = 2;
+const OVS_FLOW_ATTR_STATS: u16 = 3;
+const OVS_FLOW_ATTR_TCP_FLAGS: u16 = 4;
+const OVS_FLOW_ATTR_USED: u16 = 5;
+const OVS_FLOW_ATTR_MASK: u16 = 7;
+
+const OVS_KEY_ATTR_ENCAP: u16 = 1;
+const OVS_KEY_ATTR_PRIORITY: u16 = 2;
+const OVS_KEY_ATTR_IN_PORT: u16 = 3;
+const OVS_KEY_ATTR_ETHERNET: u16 = 4;
+const OVS_KEY_ATTR_VLAN: u16 = 5;
+const OVS_KEY_ATTR_ETHERTYPE: u16 = 6;
+const OVS_KEY_ATTR_IPV4: u16 = 7;
+const OVS_KEY_ATTR_TCP: u16 = 9;
+const OVS_KEY_ATTR_UDP: u16 = 10;
+const OVS_KEY_ATTR_SKB_MARK: u16 = 15;
+const OVS_KEY_ATTR_RECIRC_ID: u16 = 20;
+
+const OVS_ACTION_ATTR_OUTPUT: u16 = 1;
+const OVS_ACTION_ATTR_PUSH_VLAN: u16 = 4;
+const OVS_ACTION_ATTR_POP_VLAN: u16 = 5;
+const OVS_ACTION_ATTR_RECIRC: u16 = 7;
+
+/// One field of a flow key or mask
+///
+/// Keys and masks use the same encoding: a mask attribute carries the bits
+/// of the matching key attribute that are significant, and a field missing
+/// from the mask is fully wildcarded. Multi-byte packet fields are kept in
+/// host order here and converted to network order on the wire.
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum FlowKeyAttr {
+    /// Fields of the packet inside a VLAN tag
+    Encap(Vec<FlowKeyAttr>),
+    /// skb->priority
+    Priority(u32),
+    /// Datapath port the packet arrived on
+    InPort(u32),
+    Ethernet {
+        src: [u8; 6],
+        dst: [u8; 6],
+    },
+    /// VLAN TCI
+    Vlan(u16),
+    EthType(u16),
+    Ipv4 {
+        src: Ipv4Addr,
+        dst: Ipv4Addr,
+        proto: u8,
+        tos: u8,
+        ttl: u8,
+        /// One of OVS_FRAG_TYPE_*
+        frag: u8,
+    },
+    Tcp {
+        src: u16,
+        dst: u16,
+    },
+    Udp {
+        src: u16,
+        dst: u16,
+    },
+    /// skb mark
+    SkbMark(u32),
+    RecircId(u32),
+    /// Any key attribute without a typed representation
+    Other(u16, Vec<u8>),
+}
+
+impl FlowKeyAttr {
+    fn parse_all(bytes: &[u8]) -> io::Result<Vec<FlowKeyAttr>> {
+        NlAttr::parse(bytes)?.iter().map(FlowKeyAttr::from_attr).collect()
+    }
+
+    fn from_attr(attr: &NlAttr) -> io::Result<FlowKeyAttr> {
+        let p = attr.payload();
+        let mut cursor = Cursor::new(p);
+        let key = match attr.attr_type() {
+            OVS_KEY_ATTR_ENCAP => FlowKeyAttr::Encap(FlowKeyAttr::parse_all(p)?),
+            OVS_KEY_ATTR_PRIORITY => FlowKeyAttr::Priority(attr.get_u32()?),
+            OVS_KEY_ATTR_IN_PORT => FlowKeyAttr::InPort(attr.get_u32()?),
+            OVS_KEY_ATTR_ETHERNET => {
+                if p.len() != 12 {
+                    return Err(io::Error::new(ErrorKind::InvalidData, "bad ovs_key_ethernet length"));
+                }
+                let mut src = [0u8; 6];
+                let mut dst = [0u8; 6];
+                src.copy_from_slice(&p[..6]);
+                dst.copy_from_slice(&p[6..12]);
+                FlowKeyAttr::Ethernet { src, dst }
+            },
+            OVS_KEY_ATTR_VLAN => FlowKeyAttr::Vlan(cursor.read_u16::<BigEndian>()?),
+            OVS_KEY_ATTR_ETHERTYPE => FlowKeyAttr::EthType(cursor.read_u16::<BigEndian>()?),
+            OVS_KEY_ATTR_IPV4 => {
+                FlowKeyAttr::Ipv4 {
+                    src: Ipv4Addr::from(cursor.read_u32::<BigEndian>()?),
+                    dst: Ipv4Addr::from(cursor.read_u32::<BigEndian>()?),
+                    proto: cursor.read_u8()?,
+                    tos: cursor.read_u8()?,
+                    ttl: cursor.read_u8()?,
+                    frag: cursor.read_u8()?,
+                }
+            },
+            OVS_KEY_ATTR_TCP => {
+                FlowKeyAttr::Tcp {
+                    src: cursor.read_u16::<BigEndian>()?,
+                    dst: cursor.read_u16::<BigEndian>()?,
+                }
+            },
+            OVS_KEY_ATTR_UDP => {
+                FlowKeyAttr::Udp {
+                    src: cursor.read_u16::<BigEndian>()?,
+                    dst: cursor.read_u16::<BigEndian>()?,
+                }
+            },
+            OVS_KEY_ATTR_SKB_MARK => FlowKeyAttr::SkbMark(attr.get_u32()?),
+            OVS_KEY_ATTR_RECIRC_ID => FlowKeyAttr::RecircId(attr.get_u32()?),
+            t => FlowKeyAttr::Other(t, p.to_vec()),
+        };
+        Ok(key)
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        let (t, payload) = match *self {
+            FlowKeyAttr::Encap(ref keys) => (OVS_KEY_ATTR_ENCAP, encode_keys(keys)),
+            FlowKeyAttr::Priority(p) => (OVS_KEY_ATTR_PRIORITY, p.to_ne_bytes().to_vec()),
+            FlowKeyAttr::InPort(p) => (OVS_KEY_ATTR_IN_PORT, p.to_ne_bytes().to_vec()),
+            FlowKeyAttr::Ethernet { src, dst } => {
+                let mut b = src.to_vec();
+                b.extend_from_slice(&dst);
+                (OVS_KEY_ATTR_ETHERNET, b)
+            },
+            FlowKeyAttr::Vlan(tci) => (OVS_KEY_ATTR_VLAN, tci.to_be_bytes().to_vec()),
+            FlowKeyAttr::EthType(t) => (OVS_KEY_ATTR_ETHERTYPE, t.to_be_bytes().to_vec()),
+            FlowKeyAttr::Ipv4 { src, dst, proto, tos, ttl, frag } => {
+                let mut b = src.octets().to_vec();
+                b.extend_from_slice(&dst.octets());
+                b.extend_from_slice(&[proto, tos, ttl, frag]);
+                (OVS_KEY_ATTR_IPV4, b)
+            },
+            FlowKeyAttr::Tcp { src, dst } => {
+                let mut b = src.to_be_bytes().to_vec();
+                b.extend_from_slice(&dst.to_be_bytes());
+                (OVS_KEY_ATTR_TCP, b)
+            },
+            FlowKeyAttr::Udp { src, dst } => {
+                let mut b = src.to_be_bytes().to_vec();
+                b.extend_from_slice(&dst.to_be_bytes());
+                (OVS_KEY_ATTR_UDP, b)
+            },
+            FlowKeyAttr::SkbMark(m) => (OVS_KEY_ATTR_SKB_MARK, m.to_ne_bytes().to_vec()),
+            FlowKeyAttr::RecircId(r) => (OVS_KEY_ATTR_RECIRC_ID, r.to_ne_bytes().to_vec()),
+            FlowKeyAttr::Other(t, ref b) => (t, b.clone()),
+        };
+        NlAttr::new(t, &payload).bytes()
+    }
+}
+
+fn encode_keys(keys: &[FlowKeyAttr]) -> Vec<u8> {
+    let mut bytes = vec![];
+    for k in keys {
+        bytes.extend(k.bytes());
+    }
+    bytes
+}
+
+/// Flow action, executed in order
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum Action {
+    /// Send the packet out of a datapath port
+    Output(u32),
+    PushVlan {
+        tpid: u16,
+        tci: u16,
+    },
+    PopVlan,
+    /// Re-run the packet through the flow table with this recirc id
+    Recirc(u32),
+    /// Any action without a typed representation
+    Other(u16, Vec<u8>),
+}
+
+impl Action {
+    fn from_attr(attr: &NlAttr) -> io::Result<Action> {
+        let p = attr.payload();
+        let mut cursor = Cursor::new(p);
+        let action = match attr.attr_type() {
+            OVS_ACTION_ATTR_OUTPUT => Action::Output(attr.get_u32()?),
+            OVS_ACTION_ATTR_PUSH_VLAN => {
+                Action::PushVlan {
+                    tpid: cursor.read_u16::<BigEndian>()?,
+                    tci: cursor.read_u16::<BigEndian>()?,
+                }
+            },
+            OVS_ACTION_ATTR_POP_VLAN => Action::PopVlan,
+            OVS_ACTION_ATTR_RECIRC => Action::Recirc(attr.get_u32()?),
+            t => Action::Other(t, p.to_vec()),
+        };
+        Ok(action)
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        let (t, payload) = match *self {
+            Action::Output(port) => (OVS_ACTION_ATTR_OUTPUT, port.to_ne_bytes().to_vec()),
+            Action::PushVlan { tpid, tci } => {
+                let mut b = tpid.to_be_bytes().to_vec();
+                b.extend_from_slice(&tci.to_be_bytes());
+                (OVS_ACTION_ATTR_PUSH_VLAN, b)
+            },
+            Action::PopVlan => (OVS_ACTION_ATTR_POP_VLAN, vec![]),
+            Action::Recirc(id) => (OVS_ACTION_ATTR_RECIRC, id.to_ne_bytes().to_vec()),
+            Action::Other(t, ref b) => (t, b.clone()),
+        };
+        NlAttr::new(t, &payload).bytes()
+    }
+}
+
+/// Packet and byte counters of a flow
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct FlowStats {
+    packets: u64,
+    bytes: u64,
+}
+
+impl FlowStats {
+    pub fn packets(&self) -> u64 {
+        self.packets
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+}
+
+/// A datapath flow (`ovs-dpctl dump-flows`)
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Flow {
+    dp_ifindex: i32,
+    key: Vec<FlowKeyAttr>,
+    mask: Vec<FlowKeyAttr>,
+    actions: Vec<Action>,
+    stats: Option<FlowStats>,
+    tcp_flags: Option<u8>,
+    used: Option<u64>,
+}
+
+impl Flow {
+    /// Describes a flow to be installed with `Ovs::add_flow`.
+    pub fn new() -> Flow {
+        Flow {
+            dp_ifindex: 0,
+            key: vec![],
+            mask: vec![],
+            actions: vec![],
+            stats: None,
+            tcp_flags: None,
+            used: None,
+        }
+    }
+
+    fn from_attrs(dp_ifindex: i32, bytes: &[u8]) -> io::Result<Flow> {
+        let mut flow = Flow::new();
+        flow.dp_ifindex = dp_ifindex;
+
+        for attr in NlAttr::parse(bytes)? {
+            let mut cursor = Cursor::new(attr.payload());
+            match attr.attr_type() {
+                OVS_FLOW_ATTR_KEY => flow.key = FlowKeyAttr::parse_all(attr.payload())?,
+                OVS_FLOW_ATTR_MASK => flow.mask = FlowKeyAttr::parse_all(attr.payload())?,
+                OVS_FLOW_ATTR_ACTIONS => {
+                    flow.actions = attr.nested()?.iter().map(Action::from_attr)
+                        .collect::<io::Result<_>>()?;
+                },
+                OVS_FLOW_ATTR_STATS => {
+                    flow.stats = Some(FlowStats {
+                        packets: cursor.read_u64::<NativeEndian>()?,
+                        bytes: cursor.read_u64::<NativeEndian>()?,
+                    });
+                },
+                OVS_FLOW_ATTR_TCP_FLAGS => flow.tcp_flags = Some(attr.get_u8()?),
+                OVS_FLOW_ATTR_USED => flow.used = Some(attr.get_u64()?),
+                _ => {},
+            }
+        }
+        Ok(flow)
+    }
+
+    fn attrs(&self) -> Vec<u8> {
+        let mut bytes = NlAttr::new(OVS_FLOW_ATTR_KEY, &encode_keys(&self.key)).bytes();
+        if !self.mask.is_empty() {
+            bytes.extend(NlAttr::new(OVS_FLOW_ATTR_MASK, &encode_keys(&self.mask)).bytes());
+        }
+        let mut actions = vec![];
+        for a in &self.actions {
+            actions.extend(a.bytes());
+        }
+        // An empty action list is valid and drops matching packets
+        bytes.extend(NlAttr::new(OVS_FLOW_ATTR_ACTIONS, &actions).bytes());
+        bytes
+    }
+
+    /// Add a field to match on
+    pub fn add_key(&mut self, key: FlowKeyAttr) -> &mut Flow {
+        self.key.push(key);
+        self
+    }
+
+    /// Add a mask for a key field, making the flow a megaflow
+    pub fn add_mask(&mut self, mask: FlowKeyAttr) -> &mut Flow {
+        self.mask.push(mask);
+        self
+    }
+
+    pub fn add_action(&mut self, action: Action) -> &mut Flow {
+        self.actions.push(action);
+        self
+    }
+
+    pub fn dp_ifindex(&self) -> i32 {
+        self.dp_ifindex
+    }
+
+    pub fn key(&self) -> &[FlowKeyAttr] {
+        &self.key
+    }
+
+    pub fn mask(&self) -> &[FlowKeyAttr] {
+        &self.mask
+    }
+
+    pub fn actions(&self) -> &[Action] {
+        &self.actions
+    }
+
+    pub fn stats(&self) -> Option<FlowStats> {
+        self.stats
+    }
+
+    /// Union of TCP flags seen on packets matching the flow
+    pub fn tcp_flags(&self) -> Option<u8> {
+        self.tcp_flags
+    }
+
+    /// Time the flow was last used, in milliseconds of system uptime
+    pub fn used(&self) -> Option<u64> {
+        self.used
+    }
+}
+
+impl Default for Flow {
+    fn default() -> Flow {
+        Flow::new()
+    }
+}
+
+impl<T: NetlinkTransport> Ovs<T> {
+    /// Lists the flows installed in the datapath with local port `dp_ifindex`.
+    pub fn flows(&mut self, dp_ifindex: i32) -> io::Result<Vec<Flow>> {
+        let replies = exchange(&mut self.socket, &self.flow, OVS_CMD_GET, true, dp_ifindex, &[])?;
+        replies.iter().map(|&(ifindex, ref attrs)| Flow::from_attrs(ifindex, attrs)).collect()
+    }
+
+    pub fn add_flow(&mut self, dp_ifindex: i32, flow: &Flow) -> io::Result<()> {
+        exchange(&mut self.socket, &self.flow, OVS_CMD_NEW, false, dp_ifindex, &flow.attrs())?;
+        Ok(())
+    }
+
+    /// Deletes the flow with exactly this key.
+    pub fn del_flow(&mut self, dp_ifindex: i32, key: &[FlowKeyAttr]) -> io::Result<()> {
+        let attrs = NlAttr::new(OVS_FLOW_ATTR_KEY, &encode_keys(key)).bytes();
+        exchange(&mut self.socket, &self.flow, OVS_CMD_DEL, false, dp_ifindex, &attrs)?;
+        Ok(())
+    }
+
+    /// Deletes every flow of the datapath.
+    pub fn flush_flows(&mut self, dp_ifindex: i32) -> io::Result<()> {
+        exchange(&mut self.socket, &self.flow, OVS_CMD_DEL, false, dp_ifindex, &[])?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_key_encoding() {
+        // ethertype and ports are big endian on the wire
+        let key = FlowKeyAttr::EthType(0x0800);
+        assert_eq!(key.bytes(), [6, 0, 6, 0, 0x08, 0x00, 0, 0]);
+
+        let key = FlowKeyAttr::Udp { src: 53, dst: 1024 };
+        assert_eq!(key.bytes(), [8, 0, 10, 0, 0, 53, 4, 0]);
+    }
+
+    #[test]
+    fn test_flow_roundtrip() {
+        let mut flow = Flow::new();
+        flow.add_key(FlowKeyAttr::InPort(1))
+            .add_key(FlowKeyAttr::Ethernet { src: [0, 1, 2, 3, 4, 5], dst: [0xff; 6] })
+            .add_key(FlowKeyAttr::EthType(0x8100))
+            .add_key(FlowKeyAttr::Vlan(0x1064))
+            .add_key(FlowKeyAttr::Encap(vec![
+                FlowKeyAttr::EthType(0x0800),
+                FlowKeyAttr::Ipv4 {
+                    src: Ipv4Addr::new(10, 0, 0, 1),
+                    dst: Ipv4Addr::new(10, 0, 0, 2),
+                    proto: 6,
+                    tos: 0,
+                    ttl: 64,
+                    frag: 0,
+                },
+                FlowKeyAttr::Tcp { src: 40000, dst: 80 },
+            ]))
+            .add_key(FlowKeyAttr::Other(19, vec![0, 0, 0, 0]))
+            .add_mask(FlowKeyAttr::InPort(!0))
+            .add_action(Action::PopVlan)
+            .add_action(Action::Output(2));
+
+        let decoded = Flow::from_attrs(3, &flow.attrs()).unwrap();
+        assert_eq!(decoded.dp_ifindex(), 3);
+        assert_eq!(decoded.key(), flow.key());
+        assert_eq!(decoded.mask(), flow.mask());
+        assert_eq!(decoded.actions(), flow.actions());
+        assert_eq!(decoded.stats(), None);
+    }
+
+    #[test]
+    fn test_flow_stats_decode() {
+        let mut stats = 5u64.to_ne_bytes().to_vec();
+        stats.extend_from_slice(&420u64.to_ne_bytes());
+        let mut bytes = NlAttr::new(OVS_FLOW_ATTR_STATS, &stats).bytes();
+        bytes.extend(NlAttr::new(OVS_FLOW_ATTR_USED, &1234u64.to_ne_bytes()).bytes());
+        bytes.extend(NlAttr::new(OVS_FLOW_ATTR_TCP_FLAGS, &[0x12]).bytes());
+
+        let flow = Flow::from_attrs(0, &bytes).unwrap();
+        let stats = flow.stats().unwrap();
+        assert_eq!(stats.packets(), 5);
+        assert_eq!(stats.bytes(), 420);
+        assert_eq!(flow.used(), Some(1234));
+        assert_eq!(flow.tcp_flags(), Some(0x12));
+    }
+}
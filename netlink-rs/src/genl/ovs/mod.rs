@@ -0,0 +1,119 @@
+//! Open vSwitch datapath generic netlink families
+//!
+//! The kernel datapath is driven through three families: `ovs_datapath`,
+//! `ovs_vport` and `ovs_flow`. Every message of these families carries a
+//! `struct ovs_header` between the genl header and the attributes, naming the
+//! datapath by the ifindex of its local port.
+
+mod datapath;
+pub use self::datapath::*;
+
+mod vport;
+pub use self::vport::*;
+
+mod flow;
+pub use self::flow::*;
+
+use super::GenlFamily;
+use socket::{Socket, NetlinkTransport};
+use Protocol;
+
+use std::io::{self, ErrorKind, Cursor};
+
+use byteorder::{NativeEndian, ReadBytesExt};
+
+const OVS_DATAPATH_FAMILY: &str = "ovs_datapath";
+const OVS_VPORT_FAMILY: &str = "ovs_vport";
+const OVS_FLOW_FAMILY: &str = "ovs_flow";
+
+// enum ovs_*_cmd are shared by all three families
+const OVS_CMD_NEW: u8 = 1;
+const OVS_CMD_DEL: u8 = 2;
+const OVS_CMD_GET: u8 = 3;
+
+// HEADER FORMAT
+// int dp_ifindex;     /* ifindex of local port for datapath, 0 for any */
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+struct OvsHeader {
+    dp_ifindex: i32,
+}
+
+impl OvsHeader {
+    fn from_bytes(bytes: &[u8]) -> io::Result<(OvsHeader, usize)> {
+        let mut cursor = Cursor::new(bytes);
+        let dp_ifindex = cursor.read_i32::<NativeEndian>()?;
+        Ok((OvsHeader { dp_ifindex }, cursor.position() as usize))
+    }
+
+    fn bytes(&self) -> [u8; 4] {
+        self.dp_ifindex.to_ne_bytes()
+    }
+}
+
+/// Handle to the Open vSwitch datapath families
+pub struct Ovs<T: NetlinkTransport = Socket> {
+    socket: T,
+    datapath: GenlFamily,
+    vport: GenlFamily,
+    flow: GenlFamily,
+}
+
+impl Ovs {
+    /// Resolves the OVS families; fails with `ENOENT` if the `openvswitch`
+    /// module is not loaded.
+    pub fn new() -> io::Result<Ovs> {
+        Ovs::from_transport(Socket::new(Protocol::Generic)?)
+    }
+}
+
+impl<T: NetlinkTransport> Ovs<T> {
+    /// Like `new`, but talks to the kernel through `transport`.
+    pub fn from_transport(mut transport: T) -> io::Result<Ovs<T>> {
+        let datapath = GenlFamily::resolve(&mut transport, OVS_DATAPATH_FAMILY)?;
+        let vport = GenlFamily::resolve(&mut transport, OVS_VPORT_FAMILY)?;
+        let flow = GenlFamily::resolve(&mut transport, OVS_FLOW_FAMILY)?;
+        Ok(Ovs {
+            socket: transport,
+            datapath,
+            vport,
+            flow,
+        })
+    }
+}
+
+/// Exchanges an OVS request and splits each reply into the datapath
+/// ifindex and its attribute stream.
+fn exchange(socket: &mut impl NetlinkTransport, family: &GenlFamily, cmd: u8, dump: bool,
+            dp_ifindex: i32, attrs: &[u8]) -> io::Result<Vec<(i32, Vec<u8>)>> {
+    let mut payload = OvsHeader { dp_ifindex }.bytes().to_vec();
+    payload.extend_from_slice(attrs);
+
+    let replies = if dump {
+        family.dump(socket, cmd, &payload)?
+    } else {
+        family.request(socket, cmd, &payload)?
+    };
+    replies.iter().map(|r| {
+        let (hdr, n) = OvsHeader::from_bytes(r)?;
+        Ok((hdr.dp_ifindex, r[n..].to_vec()))
+    }).collect()
+}
+
+/// First reply of a request, or an error if the kernel sent none
+fn first_reply(replies: Vec<(i32, Vec<u8>)>) -> io::Result<(i32, Vec<u8>)> {
+    replies.into_iter().next()
+        .ok_or_else(|| io::Error::new(ErrorKind::NotFound, "no reply from datapath"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ovs_header() {
+        let hdr = OvsHeader { dp_ifindex: 12 };
+        let (decoded, n) = OvsHeader::from_bytes(&hdr.bytes()).unwrap();
+        assert_eq!(n, 4);
+        assert_eq!(decoded, hdr);
+    }
+}
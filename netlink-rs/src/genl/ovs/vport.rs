@@ -0,0 +1,305 @@
+use super::{Ovs, exchange, OVS_CMD_NEW, OVS_CMD_DEL, OVS_CMD_GET, first_reply};
+use socket::{NetlinkTransport, NlAttr, string_attr};
+
+use std::io::{self, Cursor};
+
+use byteorder::{NativeEndian, ReadBytesExt};
+
+const OVS_VPORT_ATTR_PORT_NO: u16 = 1;
+const OVS_VPORT_ATTR_TYPE: u16 = 2;
+const OVS_VPORT_ATTR_NAME: u16 = 3;
+const OVS_VPORT_ATTR_OPTIONS: u16 = 4;
+const OVS_VPORT_ATTR_UPCALL_PID: u16 = 5;
+const OVS_VPORT_ATTR_STATS: u16 = 6;
+const OVS_VPORT_ATTR_IFINDEX: u16 = 8;
+
+const OVS_TUNNEL_ATTR_DST_PORT: u16 = 1;
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum VportType {
+    /// Network device
+    Netdev,
+    /// Network device implemented by datapath
+    Internal,
+    Gre,
+    Vxlan,
+    Geneve,
+    Other(u32),
+}
+
+impl From<VportType> for u32 {
+    fn from(t: VportType) -> u32 {
+        use self::VportType::*;
+        match t {
+            Netdev => 1,
+            Internal => 2,
+            Gre => 3,
+            Vxlan => 4,
+            Geneve => 5,
+            Other(i) => i,
+        }
+    }
+}
+
+impl From<u32> for VportType {
+    fn from(t: u32) -> VportType {
+        use self::VportType::*;
+        match t {
+            1 => Netdev,
+            2 => Internal,
+            3 => Gre,
+            4 => Vxlan,
+            5 => Geneve,
+            i => Other(i),
+        }
+    }
+}
+
+/// Traffic counters of a vport
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct VportStats {
+    rx_packets: u64,
+    tx_packets: u64,
+    rx_bytes: u64,
+    tx_bytes: u64,
+    rx_errors: u64,
+    tx_errors: u64,
+    rx_dropped: u64,
+    tx_dropped: u64,
+}
+
+impl VportStats {
+    fn from_bytes(bytes: &[u8]) -> io::Result<VportStats> {
+        let mut cursor = Cursor::new(bytes);
+        Ok(VportStats {
+            rx_packets: cursor.read_u64::<NativeEndian>()?,
+            tx_packets: cursor.read_u64::<NativeEndian>()?,
+            rx_bytes: cursor.read_u64::<NativeEndian>()?,
+            tx_bytes: cursor.read_u64::<NativeEndian>()?,
+            rx_errors: cursor.read_u64::<NativeEndian>()?,
+            tx_errors: cursor.read_u64::<NativeEndian>()?,
+            rx_dropped: cursor.read_u64::<NativeEndian>()?,
+            tx_dropped: cursor.read_u64::<NativeEndian>()?,
+        })
+    }
+
+    pub fn rx_packets(&self) -> u64 {
+        self.rx_packets
+    }
+
+    pub fn tx_packets(&self) -> u64 {
+        self.tx_packets
+    }
+
+    pub fn rx_bytes(&self) -> u64 {
+        self.rx_bytes
+    }
+
+    pub fn tx_bytes(&self) -> u64 {
+        self.tx_bytes
+    }
+
+    pub fn rx_errors(&self) -> u64 {
+        self.rx_errors
+    }
+
+    pub fn tx_errors(&self) -> u64 {
+        self.tx_errors
+    }
+
+    pub fn rx_dropped(&self) -> u64 {
+        self.rx_dropped
+    }
+
+    pub fn tx_dropped(&self) -> u64 {
+        self.tx_dropped
+    }
+}
+
+/// A datapath port
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Vport {
+    dp_ifindex: i32,
+    port_no: Option<u32>,
+    vport_type: VportType,
+    name: String,
+    upcall_pids: Vec<u32>,
+    dst_port: Option<u16>,
+    stats: VportStats,
+    ifindex: Option<i32>,
+}
+
+impl Vport {
+    /// Describes a vport to be added with `Ovs::add_vport`.
+    pub fn new(name: &str, vport_type: VportType) -> Vport {
+        Vport {
+            dp_ifindex: 0,
+            port_no: None,
+            vport_type,
+            name: name.into(),
+            upcall_pids: vec![],
+            dst_port: None,
+            stats: VportStats::default(),
+            ifindex: None,
+        }
+    }
+
+    fn from_attrs(dp_ifindex: i32, bytes: &[u8]) -> io::Result<Vport> {
+        let mut vport = Vport::new("", VportType::Other(0));
+        vport.dp_ifindex = dp_ifindex;
+
+        for attr in NlAttr::parse(bytes)? {
+            let mut cursor = Cursor::new(attr.payload());
+            match attr.attr_type() {
+                OVS_VPORT_ATTR_PORT_NO => vport.port_no = Some(attr.get_u32()?),
+                OVS_VPORT_ATTR_TYPE => vport.vport_type = attr.get_u32()?.into(),
+                OVS_VPORT_ATTR_NAME => vport.name = attr.get_str()?.to_string(),
+                OVS_VPORT_ATTR_UPCALL_PID => {
+                    // Unlike most attributes this is a bare array of u32
+                    while (cursor.position() as usize) < attr.payload().len() {
+                        vport.upcall_pids.push(cursor.read_u32::<NativeEndian>()?);
+                    }
+                },
+                OVS_VPORT_ATTR_OPTIONS => {
+                    for a in attr.nested()? {
+                        if a.attr_type() == OVS_TUNNEL_ATTR_DST_PORT {
+                            vport.dst_port = Some(a.get_u16()?);
+                        }
+                    }
+                },
+                OVS_VPORT_ATTR_STATS => vport.stats = VportStats::from_bytes(attr.payload())?,
+                OVS_VPORT_ATTR_IFINDEX => vport.ifindex = Some(attr.get_u32()? as i32),
+                _ => {},
+            }
+        }
+        Ok(vport)
+    }
+
+    fn attrs(&self) -> Vec<u8> {
+        let mut bytes = string_attr(OVS_VPORT_ATTR_NAME, &self.name);
+        bytes.extend(NlAttr::new(OVS_VPORT_ATTR_TYPE, &u32::from(self.vport_type).to_ne_bytes()).bytes());
+        if let Some(port_no) = self.port_no {
+            bytes.extend(NlAttr::new(OVS_VPORT_ATTR_PORT_NO, &port_no.to_ne_bytes()).bytes());
+        }
+
+        // The kernel requires an upcall pid, with 0 meaning no upcalls
+        let mut pids = vec![];
+        for pid in &self.upcall_pids {
+            pids.extend_from_slice(&pid.to_ne_bytes());
+        }
+        if pids.is_empty() {
+            pids.extend_from_slice(&0u32.to_ne_bytes());
+        }
+        bytes.extend(NlAttr::new(OVS_VPORT_ATTR_UPCALL_PID, &pids).bytes());
+
+        if let Some(dst_port) = self.dst_port {
+            let options = NlAttr::new(OVS_TUNNEL_ATTR_DST_PORT, &dst_port.to_ne_bytes()).bytes();
+            bytes.extend(NlAttr::new(OVS_VPORT_ATTR_OPTIONS, &options).bytes());
+        }
+        bytes
+    }
+
+    /// Request a specific port number instead of the first free one
+    pub fn set_port_no(&mut self, port_no: u32) -> &mut Vport {
+        self.port_no = Some(port_no);
+        self
+    }
+
+    /// Add a netlink port that receives upcalls for packets from this vport
+    pub fn add_upcall_pid(&mut self, pid: u32) -> &mut Vport {
+        self.upcall_pids.push(pid);
+        self
+    }
+
+    /// Set UDP destination port of a vxlan or geneve tunnel vport
+    pub fn set_dst_port(&mut self, port: u16) -> &mut Vport {
+        self.dst_port = Some(port);
+        self
+    }
+
+    /// Ifindex of the local port of the datapath this vport belongs to
+    pub fn dp_ifindex(&self) -> i32 {
+        self.dp_ifindex
+    }
+
+    pub fn port_no(&self) -> Option<u32> {
+        self.port_no
+    }
+
+    pub fn vport_type(&self) -> VportType {
+        self.vport_type
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn upcall_pids(&self) -> &[u32] {
+        &self.upcall_pids
+    }
+
+    pub fn dst_port(&self) -> Option<u16> {
+        self.dst_port
+    }
+
+    pub fn stats(&self) -> VportStats {
+        self.stats
+    }
+
+    /// Ifindex of the netdev backing the vport
+    pub fn ifindex(&self) -> Option<i32> {
+        self.ifindex
+    }
+}
+
+impl<T: NetlinkTransport> Ovs<T> {
+    /// Lists the ports of the datapath with local port `dp_ifindex`.
+    pub fn vports(&mut self, dp_ifindex: i32) -> io::Result<Vec<Vport>> {
+        let replies = exchange(&mut self.socket, &self.vport, OVS_CMD_GET, true, dp_ifindex, &[])?;
+        replies.iter().map(|&(ifindex, ref attrs)| Vport::from_attrs(ifindex, attrs)).collect()
+    }
+
+    /// Adds `vport` to the datapath and returns it as created by the kernel.
+    pub fn add_vport(&mut self, dp_ifindex: i32, vport: &Vport) -> io::Result<Vport> {
+        let replies = exchange(&mut self.socket, &self.vport, OVS_CMD_NEW, false, dp_ifindex,
+                               &vport.attrs())?;
+        let (ifindex, attrs) = first_reply(replies)?;
+        Vport::from_attrs(ifindex, &attrs)
+    }
+
+    pub fn del_vport(&mut self, dp_ifindex: i32, port_no: u32) -> io::Result<()> {
+        let attrs = NlAttr::new(OVS_VPORT_ATTR_PORT_NO, &port_no.to_ne_bytes()).bytes();
+        exchange(&mut self.socket, &self.vport, OVS_CMD_DEL, false, dp_ifindex, &attrs)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vport_roundtrip() {
+        let mut vport = Vport::new("vxlan_sys_4789", VportType::Vxlan);
+        vport.set_port_no(2).add_upcall_pid(100).add_upcall_pid(101).set_dst_port(4789);
+
+        let decoded = Vport::from_attrs(7, &vport.attrs()).unwrap();
+        assert_eq!(decoded.dp_ifindex(), 7);
+        assert_eq!(decoded.name(), "vxlan_sys_4789");
+        assert_eq!(decoded.vport_type(), VportType::Vxlan);
+        assert_eq!(decoded.port_no(), Some(2));
+        assert_eq!(decoded.upcall_pids(), &[100, 101]);
+        assert_eq!(decoded.dst_port(), Some(4789));
+    }
+
+    #[test]
+    fn test_vport_default_upcall_pid() {
+        let vport = Vport::new("br0", VportType::Internal);
+        let decoded = Vport::from_attrs(0, &vport.attrs()).unwrap();
+        assert_eq!(decoded.upcall_pids(), &[0]);
+        assert_eq!(decoded.port_no(), None);
+    }
+}
@@ -0,0 +1,368 @@
+use super::{GenlFamily, CTRL_ATTR_FAMILY_ID};
+use socket::{NetlinkTransport, NlAttr};
+
+use std::io;
+
+const CTRL_CMD_GETPOLICY: u8 = 10;
+
+const CTRL_ATTR_POLICY: u16 = 8;
+const CTRL_ATTR_OP_POLICY: u16 = 9;
+const CTRL_ATTR_OP: u16 = 10;
+
+const CTRL_ATTR_POLICY_DO: u16 = 1;
+const CTRL_ATTR_POLICY_DUMP: u16 = 2;
+
+const NL_POLICY_TYPE_ATTR_TYPE: u16 = 1;
+const NL_POLICY_TYPE_ATTR_MIN_VALUE_S: u16 = 2;
+const NL_POLICY_TYPE_ATTR_MAX_VALUE_S: u16 = 3;
+const NL_POLICY_TYPE_ATTR_MIN_VALUE_U: u16 = 4;
+const NL_POLICY_TYPE_ATTR_MAX_VALUE_U: u16 = 5;
+const NL_POLICY_TYPE_ATTR_MIN_LENGTH: u16 = 6;
+const NL_POLICY_TYPE_ATTR_MAX_LENGTH: u16 = 7;
+const NL_POLICY_TYPE_ATTR_POLICY_IDX: u16 = 8;
+const NL_POLICY_TYPE_ATTR_POLICY_MAXTYPE: u16 = 9;
+const NL_POLICY_TYPE_ATTR_BITFIELD32_MASK: u16 = 10;
+const NL_POLICY_TYPE_ATTR_MASK: u16 = 12;
+
+/// Type of an attribute as validated by the kernel (NL_ATTR_TYPE_*)
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum PolicyAttrType {
+    Invalid,
+    Flag,
+    U8,
+    U16,
+    U32,
+    U64,
+    S8,
+    S16,
+    S32,
+    S64,
+    Binary,
+    String,
+    NulString,
+    Nested,
+    NestedArray,
+    Bitfield32,
+    /// Signed integer of 4 or 8 bytes
+    Sint,
+    /// Unsigned integer of 4 or 8 bytes
+    Uint,
+    /// A type newer than this crate
+    Other(u32),
+}
+
+impl From<u32> for PolicyAttrType {
+    fn from(t: u32) -> PolicyAttrType {
+        use self::PolicyAttrType::*;
+        match t {
+            0 => Invalid,
+            1 => Flag,
+            2 => U8,
+            3 => U16,
+            4 => U32,
+            5 => U64,
+            6 => S8,
+            7 => S16,
+            8 => S32,
+            9 => S64,
+            10 => Binary,
+            11 => String,
+            12 => NulString,
+            13 => Nested,
+            14 => NestedArray,
+            15 => Bitfield32,
+            16 => Sint,
+            17 => Uint,
+            t => Other(t),
+        }
+    }
+}
+
+/// Validation rule of one attribute
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct AttrPolicy {
+    attr: u16,
+    attr_type: PolicyAttrType,
+    min_signed: Option<i64>,
+    max_signed: Option<i64>,
+    min_unsigned: Option<u64>,
+    max_unsigned: Option<u64>,
+    min_length: Option<u32>,
+    max_length: Option<u32>,
+    nested_policy: Option<u32>,
+    nested_max_attr: Option<u32>,
+    mask: Option<u64>,
+}
+
+impl AttrPolicy {
+    fn from_attr(attr: &NlAttr) -> io::Result<AttrPolicy> {
+        let mut policy = AttrPolicy {
+            attr: attr.attr_type(),
+            attr_type: PolicyAttrType::Invalid,
+            min_signed: None,
+            max_signed: None,
+            min_unsigned: None,
+            max_unsigned: None,
+            min_length: None,
+            max_length: None,
+            nested_policy: None,
+            nested_max_attr: None,
+            mask: None,
+        };
+        for a in attr.nested()? {
+            match a.attr_type() {
+                NL_POLICY_TYPE_ATTR_TYPE => policy.attr_type = a.get_u32()?.into(),
+                NL_POLICY_TYPE_ATTR_MIN_VALUE_S => policy.min_signed = Some(a.get_u64()? as i64),
+                NL_POLICY_TYPE_ATTR_MAX_VALUE_S => policy.max_signed = Some(a.get_u64()? as i64),
+                NL_POLICY_TYPE_ATTR_MIN_VALUE_U => policy.min_unsigned = Some(a.get_u64()?),
+                NL_POLICY_TYPE_ATTR_MAX_VALUE_U => policy.max_unsigned = Some(a.get_u64()?),
+                NL_POLICY_TYPE_ATTR_MIN_LENGTH => policy.min_length = Some(a.get_u32()?),
+                NL_POLICY_TYPE_ATTR_MAX_LENGTH => policy.max_length = Some(a.get_u32()?),
+                NL_POLICY_TYPE_ATTR_POLICY_IDX => policy.nested_policy = Some(a.get_u32()?),
+                NL_POLICY_TYPE_ATTR_POLICY_MAXTYPE => policy.nested_max_attr = Some(a.get_u32()?),
+                NL_POLICY_TYPE_ATTR_BITFIELD32_MASK => policy.mask = Some(a.get_u32()? as u64),
+                NL_POLICY_TYPE_ATTR_MASK => policy.mask = Some(a.get_u64()?),
+                _ => {},
+            }
+        }
+        Ok(policy)
+    }
+
+    /// Attribute type number
+    pub fn attr(&self) -> u16 {
+        self.attr
+    }
+
+    pub fn attr_type(&self) -> PolicyAttrType {
+        self.attr_type
+    }
+
+    /// Range of signed integer attributes
+    pub fn signed_range(&self) -> Option<(i64, i64)> {
+        match (self.min_signed, self.max_signed) {
+            (Some(min), Some(max)) => Some((min, max)),
+            _ => None,
+        }
+    }
+
+    /// Range of unsigned integer attributes
+    pub fn unsigned_range(&self) -> Option<(u64, u64)> {
+        match (self.min_unsigned, self.max_unsigned) {
+            (Some(min), Some(max)) => Some((min, max)),
+            _ => None,
+        }
+    }
+
+    /// Minimum payload length of binary and string attributes
+    pub fn min_length(&self) -> Option<u32> {
+        self.min_length
+    }
+
+    /// Maximum payload length of binary and string attributes
+    pub fn max_length(&self) -> Option<u32> {
+        self.max_length
+    }
+
+    /// Index of the policy of the attributes nested in this one
+    pub fn nested_policy(&self) -> Option<u32> {
+        self.nested_policy
+    }
+
+    /// Highest attribute type of the nested policy
+    pub fn nested_max_attr(&self) -> Option<u32> {
+        self.nested_max_attr
+    }
+
+    /// Bits that may be set, for bitfield32 and masked integer attributes
+    pub fn mask(&self) -> Option<u64> {
+        self.mask
+    }
+}
+
+/// One attribute policy (attribute set) of a family
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Policy {
+    index: u32,
+    attrs: Vec<AttrPolicy>,
+}
+
+impl Policy {
+    /// Index referenced by `OpPolicy` and `AttrPolicy::nested_policy`
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    pub fn attrs(&self) -> &[AttrPolicy] {
+        &self.attrs
+    }
+
+    pub fn attr(&self, attr: u16) -> Option<&AttrPolicy> {
+        self.attrs.iter().find(|a| a.attr == attr)
+    }
+}
+
+/// Policies used to validate the requests of one command
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct OpPolicy {
+    cmd: u8,
+    do_policy: Option<u32>,
+    dump_policy: Option<u32>,
+}
+
+impl OpPolicy {
+    pub fn cmd(&self) -> u8 {
+        self.cmd
+    }
+
+    /// Policy index for requests without NLM_F_DUMP
+    pub fn do_policy(&self) -> Option<u32> {
+        self.do_policy
+    }
+
+    /// Policy index for dump requests
+    pub fn dump_policy(&self) -> Option<u32> {
+        self.dump_policy
+    }
+}
+
+/// The attribute policies of a family, as dumped by CTRL_CMD_GETPOLICY
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct FamilyPolicy {
+    ops: Vec<OpPolicy>,
+    policies: Vec<Policy>,
+}
+
+impl FamilyPolicy {
+    fn add_reply(&mut self, bytes: &[u8]) -> io::Result<()> {
+        for attr in NlAttr::parse(bytes)? {
+            match attr.attr_type() {
+                // CTRL_ATTR_POLICY
+                //     policy index
+                //         attribute type
+                //             NL_POLICY_TYPE_ATTR_*
+                CTRL_ATTR_POLICY => {
+                    for p in attr.nested()? {
+                        let index = p.attr_type() as u32;
+                        let mut attrs = p.nested()?.iter()
+                            .map(AttrPolicy::from_attr)
+                            .collect::<io::Result<Vec<_>>>()?;
+                        match self.policies.iter_mut().find(|policy| policy.index == index) {
+                            Some(policy) => policy.attrs.append(&mut attrs),
+                            None => self.policies.push(Policy { index, attrs }),
+                        }
+                    }
+                },
+                // CTRL_ATTR_OP_POLICY
+                //     command
+                //         CTRL_ATTR_POLICY_DO / CTRL_ATTR_POLICY_DUMP
+                CTRL_ATTR_OP_POLICY => {
+                    for op in attr.nested()? {
+                        let mut policy = OpPolicy { cmd: op.attr_type() as u8, do_policy: None, dump_policy: None };
+                        for a in op.nested()? {
+                            let index = Some(a.get_u32()?);
+                            match a.attr_type() {
+                                CTRL_ATTR_POLICY_DO => policy.do_policy = index,
+                                CTRL_ATTR_POLICY_DUMP => policy.dump_policy = index,
+                                _ => {},
+                            }
+                        }
+                        self.ops.push(policy);
+                    }
+                },
+                _ => {},
+            }
+        }
+        Ok(())
+    }
+
+    pub fn ops(&self) -> &[OpPolicy] {
+        &self.ops
+    }
+
+    pub fn op(&self, cmd: u8) -> Option<&OpPolicy> {
+        self.ops.iter().find(|op| op.cmd == cmd)
+    }
+
+    pub fn policies(&self) -> &[Policy] {
+        &self.policies
+    }
+
+    pub fn policy(&self, index: u32) -> Option<&Policy> {
+        self.policies.iter().find(|p| p.index == index)
+    }
+}
+
+impl GenlFamily {
+    /// Dumps the attribute policies of all commands of the family.
+    pub fn policy(&self, socket: &mut impl NetlinkTransport) -> io::Result<FamilyPolicy> {
+        self.get_policy(socket, None)
+    }
+
+    /// Dumps the attribute policies used by command `cmd`.
+    pub fn op_policy(&self, socket: &mut impl NetlinkTransport, cmd: u8) -> io::Result<FamilyPolicy> {
+        self.get_policy(socket, Some(cmd))
+    }
+
+    fn get_policy(&self, socket: &mut impl NetlinkTransport, cmd: Option<u8>) -> io::Result<FamilyPolicy> {
+        let mut attrs = NlAttr::new(CTRL_ATTR_FAMILY_ID, &self.id.to_ne_bytes()).bytes();
+        if let Some(cmd) = cmd {
+            attrs.extend(NlAttr::new(CTRL_ATTR_OP, &(cmd as u32).to_ne_bytes()).bytes());
+        }
+        let mut policy = FamilyPolicy::default();
+        for reply in GenlFamily::ctrl().dump(socket, CTRL_CMD_GETPOLICY, &attrs)? {
+            policy.add_reply(&reply)?;
+        }
+        Ok(policy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use genl::{CTRL_CMD_GETFAMILY, CTRL_ATTR_FAMILY_NAME};
+    use socket::{Socket, NlAttr, NLA_F_NESTED};
+    use Protocol;
+
+    #[test]
+    fn test_policy_decoding() {
+        let mut rules = NlAttr::new(NL_POLICY_TYPE_ATTR_TYPE, &4u32.to_ne_bytes()).bytes();
+        rules.extend(NlAttr::new(NL_POLICY_TYPE_ATTR_MIN_VALUE_U, &1u64.to_ne_bytes()).bytes());
+        rules.extend(NlAttr::new(NL_POLICY_TYPE_ATTR_MAX_VALUE_U, &10u64.to_ne_bytes()).bytes());
+        let attr = NlAttr::new(3 | NLA_F_NESTED, &rules).bytes();
+        let policy = NlAttr::new(2 | NLA_F_NESTED, &attr).bytes();
+        let mut bytes = NlAttr::new(CTRL_ATTR_POLICY | NLA_F_NESTED, &policy).bytes();
+
+        let op = NlAttr::new(CTRL_ATTR_POLICY_DO, &2u32.to_ne_bytes()).bytes();
+        let ops = NlAttr::new(5 | NLA_F_NESTED, &op).bytes();
+        bytes.extend(NlAttr::new(CTRL_ATTR_OP_POLICY | NLA_F_NESTED, &ops).bytes());
+
+        let mut family_policy = FamilyPolicy::default();
+        family_policy.add_reply(&bytes).unwrap();
+        let op = family_policy.op(5).unwrap();
+        assert_eq!((op.do_policy(), op.dump_policy()), (Some(2), None));
+        let attr = family_policy.policy(2).unwrap().attr(3).unwrap();
+        assert_eq!(attr.attr_type(), PolicyAttrType::U32);
+        assert_eq!(attr.unsigned_range(), Some((1, 10)));
+        assert_eq!(attr.signed_range(), None);
+    }
+
+    #[test]
+    fn test_nlctrl_policy() {
+        let mut socket = Socket::new(Protocol::Generic).unwrap();
+        let family = GenlFamily::resolve(&mut socket, "nlctrl").unwrap();
+
+        let policy = family.op_policy(&mut socket, CTRL_CMD_GETFAMILY).unwrap();
+        let index = policy.op(CTRL_CMD_GETFAMILY).unwrap().do_policy().unwrap();
+        let name = policy.policy(index).unwrap().attr(CTRL_ATTR_FAMILY_NAME).unwrap();
+        assert_eq!(name.attr_type(), PolicyAttrType::NulString);
+
+        let all = family.policy(&mut socket).unwrap();
+        assert!(all.op(CTRL_CMD_GETFAMILY).is_some());
+        assert!(all.policies().len() >= policy.policies().len());
+    }
+}
@@ -0,0 +1,384 @@
+//! Per-task accounting generic netlink family
+//!
+//! Queries the kernel's delay accounting and IO accounting counters for a
+//! single thread (pid) or a whole thread group (tgid).
+
+use super::GenlFamily;
+use socket::{Socket, NetlinkTransport, NlAttr, c_string};
+use Protocol;
+
+use std::io::{self, ErrorKind, Cursor};
+
+use byteorder::{NativeEndian, ReadBytesExt};
+
+const TASKSTATS_GENL_NAME: &str = "TASKSTATS";
+
+const TASKSTATS_CMD_GET: u8 = 1;
+
+const TASKSTATS_TYPE_STATS: u16 = 3;
+const TASKSTATS_TYPE_AGGR_PID: u16 = 4;
+const TASKSTATS_TYPE_AGGR_TGID: u16 = 5;
+
+const TASKSTATS_CMD_ATTR_PID: u16 = 1;
+const TASKSTATS_CMD_ATTR_TGID: u16 = 2;
+
+const TS_COMM_LEN: usize = 32;
+
+// Offsets into struct taskstats of the fields that are not simply the next
+// u64, due to the __attribute__((aligned(8))) markers.
+const CPU_COUNT_OFFSET: u64 = 16;
+const AC_COMM_OFFSET: u64 = 80;
+const AC_UID_OFFSET: u64 = 120;
+const AC_ETIME_OFFSET: u64 = 144;
+// First field added after TASKSTATS_HAS_IO_ACCOUNTING
+const NVCSW_OFFSET: usize = 272;
+
+/// Accounting data of a task or thread group (`struct taskstats`)
+///
+/// Delays are in nanoseconds, CPU times in microseconds. Counters that the
+/// running kernel's `version` does not report are 0.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct TaskStats {
+    version: u16,
+    exitcode: u32,
+    comm: String,
+    uid: u32,
+    gid: u32,
+    pid: u32,
+    ppid: u32,
+    btime: u32,
+    etime: u64,
+    utime: u64,
+    stime: u64,
+    minflt: u64,
+    majflt: u64,
+    cpu_count: u64,
+    cpu_delay_total: u64,
+    blkio_count: u64,
+    blkio_delay_total: u64,
+    swapin_count: u64,
+    swapin_delay_total: u64,
+    cpu_run_real_total: u64,
+    cpu_run_virtual_total: u64,
+    read_char: u64,
+    write_char: u64,
+    read_syscalls: u64,
+    write_syscalls: u64,
+    read_bytes: u64,
+    write_bytes: u64,
+    cancelled_write_bytes: u64,
+    nvcsw: u64,
+    nivcsw: u64,
+}
+
+impl TaskStats {
+    fn from_bytes(bytes: &[u8]) -> io::Result<TaskStats> {
+        let mut stats = TaskStats::default();
+        let mut cursor = Cursor::new(bytes);
+
+        stats.version = cursor.read_u16::<NativeEndian>()?;
+        cursor.set_position(4);
+        stats.exitcode = cursor.read_u32::<NativeEndian>()?;
+
+        cursor.set_position(CPU_COUNT_OFFSET);
+        stats.cpu_count = cursor.read_u64::<NativeEndian>()?;
+        stats.cpu_delay_total = cursor.read_u64::<NativeEndian>()?;
+        stats.blkio_count = cursor.read_u64::<NativeEndian>()?;
+        stats.blkio_delay_total = cursor.read_u64::<NativeEndian>()?;
+        stats.swapin_count = cursor.read_u64::<NativeEndian>()?;
+        stats.swapin_delay_total = cursor.read_u64::<NativeEndian>()?;
+        stats.cpu_run_real_total = cursor.read_u64::<NativeEndian>()?;
+        stats.cpu_run_virtual_total = cursor.read_u64::<NativeEndian>()?;
+
+        let comm = AC_COMM_OFFSET as usize;
+        if bytes.len() < comm + TS_COMM_LEN {
+            return Err(io::Error::new(ErrorKind::InvalidData, "short taskstats"));
+        }
+        stats.comm = c_string(&bytes[comm..comm + TS_COMM_LEN]);
+
+        cursor.set_position(AC_UID_OFFSET);
+        stats.uid = cursor.read_u32::<NativeEndian>()?;
+        stats.gid = cursor.read_u32::<NativeEndian>()?;
+        stats.pid = cursor.read_u32::<NativeEndian>()?;
+        stats.ppid = cursor.read_u32::<NativeEndian>()?;
+        stats.btime = cursor.read_u32::<NativeEndian>()?;
+
+        cursor.set_position(AC_ETIME_OFFSET);
+        stats.etime = cursor.read_u64::<NativeEndian>()?;
+        stats.utime = cursor.read_u64::<NativeEndian>()?;
+        stats.stime = cursor.read_u64::<NativeEndian>()?;
+        stats.minflt = cursor.read_u64::<NativeEndian>()?;
+        stats.majflt = cursor.read_u64::<NativeEndian>()?;
+        // coremem, virtmem, hiwater_rss, hiwater_vm
+        cursor.set_position(cursor.position() + 4 * 8);
+        stats.read_char = cursor.read_u64::<NativeEndian>()?;
+        stats.write_char = cursor.read_u64::<NativeEndian>()?;
+        stats.read_syscalls = cursor.read_u64::<NativeEndian>()?;
+        stats.write_syscalls = cursor.read_u64::<NativeEndian>()?;
+        stats.read_bytes = cursor.read_u64::<NativeEndian>()?;
+        stats.write_bytes = cursor.read_u64::<NativeEndian>()?;
+        stats.cancelled_write_bytes = cursor.read_u64::<NativeEndian>()?;
+
+        if bytes.len() >= NVCSW_OFFSET + 16 {
+            stats.nvcsw = cursor.read_u64::<NativeEndian>()?;
+            stats.nivcsw = cursor.read_u64::<NativeEndian>()?;
+        }
+        Ok(stats)
+    }
+
+    /// TASKSTATS_VERSION of the reporting kernel
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+
+    /// Exit status, only meaningful for exited tasks
+    pub fn exitcode(&self) -> u32 {
+        self.exitcode
+    }
+
+    /// Command name
+    pub fn comm(&self) -> &str {
+        &self.comm
+    }
+
+    pub fn uid(&self) -> u32 {
+        self.uid
+    }
+
+    pub fn gid(&self) -> u32 {
+        self.gid
+    }
+
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+
+    pub fn ppid(&self) -> u32 {
+        self.ppid
+    }
+
+    /// Begin time, in seconds since the epoch
+    pub fn btime(&self) -> u32 {
+        self.btime
+    }
+
+    /// Elapsed time since the task started, in microseconds
+    pub fn etime(&self) -> u64 {
+        self.etime
+    }
+
+    /// User CPU time, in microseconds
+    pub fn utime(&self) -> u64 {
+        self.utime
+    }
+
+    /// System CPU time, in microseconds
+    pub fn stime(&self) -> u64 {
+        self.stime
+    }
+
+    pub fn minflt(&self) -> u64 {
+        self.minflt
+    }
+
+    pub fn majflt(&self) -> u64 {
+        self.majflt
+    }
+
+    /// Number of delay values recorded waiting for a CPU
+    pub fn cpu_count(&self) -> u64 {
+        self.cpu_count
+    }
+
+    /// Total time spent runnable but waiting for a CPU
+    pub fn cpu_delay_total(&self) -> u64 {
+        self.cpu_delay_total
+    }
+
+    pub fn blkio_count(&self) -> u64 {
+        self.blkio_count
+    }
+
+    /// Total time spent waiting for synchronous block IO
+    pub fn blkio_delay_total(&self) -> u64 {
+        self.blkio_delay_total
+    }
+
+    pub fn swapin_count(&self) -> u64 {
+        self.swapin_count
+    }
+
+    /// Total time spent waiting for pages to be swapped in
+    pub fn swapin_delay_total(&self) -> u64 {
+        self.swapin_delay_total
+    }
+
+    /// Wall-clock running time, in nanoseconds
+    pub fn cpu_run_real_total(&self) -> u64 {
+        self.cpu_run_real_total
+    }
+
+    /// Virtual running time as seen by the hypervisor, in nanoseconds
+    pub fn cpu_run_virtual_total(&self) -> u64 {
+        self.cpu_run_virtual_total
+    }
+
+    /// Bytes passed to read syscalls
+    pub fn read_char(&self) -> u64 {
+        self.read_char
+    }
+
+    /// Bytes passed to write syscalls
+    pub fn write_char(&self) -> u64 {
+        self.write_char
+    }
+
+    pub fn read_syscalls(&self) -> u64 {
+        self.read_syscalls
+    }
+
+    pub fn write_syscalls(&self) -> u64 {
+        self.write_syscalls
+    }
+
+    /// Bytes actually read from storage
+    pub fn read_bytes(&self) -> u64 {
+        self.read_bytes
+    }
+
+    /// Bytes actually written to storage
+    pub fn write_bytes(&self) -> u64 {
+        self.write_bytes
+    }
+
+    /// Bytes of dirtied page cache that were truncated before writeback
+    pub fn cancelled_write_bytes(&self) -> u64 {
+        self.cancelled_write_bytes
+    }
+
+    /// Voluntary context switches
+    pub fn nvcsw(&self) -> u64 {
+        self.nvcsw
+    }
+
+    /// Involuntary context switches
+    pub fn nivcsw(&self) -> u64 {
+        self.nivcsw
+    }
+}
+
+/// Handle to the TASKSTATS family
+pub struct Taskstats<T: NetlinkTransport = Socket> {
+    socket: T,
+    family: GenlFamily,
+}
+
+impl Taskstats {
+    pub fn new() -> io::Result<Taskstats> {
+        Taskstats::from_transport(Socket::new(Protocol::Generic)?)
+    }
+}
+
+impl<T: NetlinkTransport> Taskstats<T> {
+    /// Like `new`, but talks to the kernel through `transport`.
+    pub fn from_transport(mut transport: T) -> io::Result<Taskstats<T>> {
+        let family = GenlFamily::resolve(&mut transport, TASKSTATS_GENL_NAME)?;
+        Ok(Taskstats {
+            socket: transport,
+            family,
+        })
+    }
+
+    /// Accounting data of a single thread.
+    pub fn pid(&mut self, pid: u32) -> io::Result<TaskStats> {
+        self.get(TASKSTATS_CMD_ATTR_PID, pid)
+    }
+
+    /// Accounting data summed over all threads of a thread group.
+    pub fn tgid(&mut self, tgid: u32) -> io::Result<TaskStats> {
+        self.get(TASKSTATS_CMD_ATTR_TGID, tgid)
+    }
+
+    fn get(&mut self, attr: u16, id: u32) -> io::Result<TaskStats> {
+        let attrs = NlAttr::new(attr, &id.to_ne_bytes()).bytes();
+        let replies = self.family.request(&mut self.socket, TASKSTATS_CMD_GET, &attrs)?;
+        for reply in replies {
+            if let Some(stats) = stats_from_reply(&reply)? {
+                return Ok(stats);
+            }
+        }
+        Err(io::Error::new(ErrorKind::NotFound, "no taskstats in reply"))
+    }
+}
+
+// The reply nests the id and the stats struct:
+// TASKSTATS_TYPE_AGGR_PID/TGID
+//     TASKSTATS_TYPE_PID/TGID  (u32)
+//     TASKSTATS_TYPE_STATS     (struct taskstats)
+fn stats_from_reply(bytes: &[u8]) -> io::Result<Option<TaskStats>> {
+    for attr in NlAttr::parse(bytes)? {
+        let t = attr.attr_type();
+        if t != TASKSTATS_TYPE_AGGR_PID && t != TASKSTATS_TYPE_AGGR_TGID {
+            continue;
+        }
+        for a in attr.nested()? {
+            if a.attr_type() == TASKSTATS_TYPE_STATS {
+                return TaskStats::from_bytes(a.payload()).map(Some);
+            }
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket::{NlAttr, NLA_F_NESTED};
+
+    fn put_u64(buf: &mut [u8], off: usize, v: u64) {
+        buf[off..off + 8].copy_from_slice(&v.to_ne_bytes());
+    }
+
+    #[test]
+    fn test_taskstats_decode() {
+        let mut raw = vec![0u8; 400];
+        raw[0..2].copy_from_slice(&13u16.to_ne_bytes());
+        put_u64(&mut raw, 16, 7);
+        put_u64(&mut raw, 24, 1500);
+        raw[80..84].copy_from_slice(b"init");
+        raw[128..132].copy_from_slice(&1u32.to_ne_bytes());
+        put_u64(&mut raw, 152, 42);
+        put_u64(&mut raw, 248, 4096);
+        put_u64(&mut raw, 272, 3);
+
+        // TASKSTATS_TYPE_PID
+        let mut nested = NlAttr::new(1, &1u32.to_ne_bytes()).bytes();
+        nested.extend(NlAttr::new(TASKSTATS_TYPE_STATS, &raw).bytes());
+        let reply = NlAttr::new(TASKSTATS_TYPE_AGGR_PID | NLA_F_NESTED, &nested).bytes();
+
+        let stats = stats_from_reply(&reply).unwrap().unwrap();
+        assert_eq!(stats.version(), 13);
+        assert_eq!(stats.cpu_count(), 7);
+        assert_eq!(stats.cpu_delay_total(), 1500);
+        assert_eq!(stats.comm(), "init");
+        assert_eq!(stats.pid(), 1);
+        assert_eq!(stats.utime(), 42);
+        assert_eq!(stats.read_bytes(), 4096);
+        assert_eq!(stats.nvcsw(), 3);
+    }
+
+    #[test]
+    fn test_taskstats_short() {
+        assert!(TaskStats::from_bytes(&[0u8; 64]).is_err());
+    }
+
+    #[test]
+    fn test_own_pid() {
+        let pid = unsafe { ::libc::getpid() } as u32;
+        let mut ts = Taskstats::new().unwrap();
+        let stats = ts.pid(pid).unwrap();
+        assert_eq!(stats.pid(), pid);
+        assert!(stats.version() > 0);
+    }
+}
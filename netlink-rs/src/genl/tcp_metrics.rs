@@ -0,0 +1,281 @@
+//! TCP metrics cache generic netlink family
+//!
+//! The kernel caches the RTT, congestion window and ssthresh of closed
+//! connections per destination, and seeds new connections to the same
+//! destination with them (`ip tcp_metrics`).
+
+use super::GenlFamily;
+use socket::{Socket, NetlinkTransport, NlAttr};
+use Protocol;
+
+use std::io::{self, ErrorKind};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+const TCP_METRICS_GENL_NAME: &str = "tcp_metrics";
+
+const TCP_METRICS_CMD_GET: u8 = 1;
+const TCP_METRICS_CMD_DEL: u8 = 2;
+
+const TCP_METRICS_ATTR_ADDR_IPV4: u16 = 1;
+const TCP_METRICS_ATTR_ADDR_IPV6: u16 = 2;
+const TCP_METRICS_ATTR_AGE: u16 = 3;
+const TCP_METRICS_ATTR_VALS: u16 = 6;
+const TCP_METRICS_ATTR_FOPEN_MSS: u16 = 7;
+const TCP_METRICS_ATTR_FOPEN_SYN_DROPS: u16 = 8;
+const TCP_METRICS_ATTR_FOPEN_COOKIE: u16 = 10;
+const TCP_METRICS_ATTR_SADDR_IPV4: u16 = 11;
+const TCP_METRICS_ATTR_SADDR_IPV6: u16 = 12;
+
+// enum tcp_metric_index, plus one in TCP_METRICS_ATTR_VALS
+const TCP_METRIC_RTT: u16 = 1;
+const TCP_METRIC_RTTVAR: u16 = 2;
+const TCP_METRIC_SSTHRESH: u16 = 3;
+const TCP_METRIC_CWND: u16 = 4;
+const TCP_METRIC_REORDERING: u16 = 5;
+const TCP_METRIC_RTT_US: u16 = 6;
+const TCP_METRIC_RTTVAR_US: u16 = 7;
+
+fn addr_attr(v4: u16, v6: u16, addr: IpAddr) -> Vec<u8> {
+    match addr {
+        IpAddr::V4(a) => NlAttr::new(v4, &a.octets()).bytes(),
+        IpAddr::V6(a) => NlAttr::new(v6, &a.octets()).bytes(),
+    }
+}
+
+fn parse_addr(payload: &[u8]) -> io::Result<IpAddr> {
+    match payload.len() {
+        4 => Ok(IpAddr::V4(Ipv4Addr::new(payload[0], payload[1], payload[2], payload[3]))),
+        16 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(payload);
+            Ok(IpAddr::V6(Ipv6Addr::from(octets)))
+        },
+        _ => Err(io::Error::new(ErrorKind::InvalidData, "bad address length")),
+    }
+}
+
+/// Metrics cached for a destination. Values the kernel has not learned
+/// are `None`.
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct TcpMetricsEntry {
+    addr: IpAddr,
+    saddr: Option<IpAddr>,
+    age: Option<u64>,
+    rtt: Option<u32>,
+    rttvar: Option<u32>,
+    ssthresh: Option<u32>,
+    cwnd: Option<u32>,
+    reordering: Option<u32>,
+    fopen_mss: Option<u16>,
+    fopen_syn_drops: Option<u16>,
+    fopen_cookie: Option<Vec<u8>>,
+}
+
+impl TcpMetricsEntry {
+    fn from_attrs(bytes: &[u8]) -> io::Result<TcpMetricsEntry> {
+        let mut entry = TcpMetricsEntry {
+            addr: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            saddr: None,
+            age: None,
+            rtt: None,
+            rttvar: None,
+            ssthresh: None,
+            cwnd: None,
+            reordering: None,
+            fopen_mss: None,
+            fopen_syn_drops: None,
+            fopen_cookie: None,
+        };
+        // Microsecond RTTs are preferred over the millisecond ones
+        let mut rtt_ms = None;
+        let mut rttvar_ms = None;
+        for attr in NlAttr::parse(bytes)? {
+            match attr.attr_type() {
+                TCP_METRICS_ATTR_ADDR_IPV4 | TCP_METRICS_ATTR_ADDR_IPV6 => entry.addr = parse_addr(attr.payload())?,
+                TCP_METRICS_ATTR_SADDR_IPV4 | TCP_METRICS_ATTR_SADDR_IPV6 => {
+                    entry.saddr = Some(parse_addr(attr.payload())?)
+                },
+                TCP_METRICS_ATTR_AGE => entry.age = Some(attr.get_u64()?),
+                TCP_METRICS_ATTR_FOPEN_MSS => entry.fopen_mss = Some(attr.get_u16()?),
+                TCP_METRICS_ATTR_FOPEN_SYN_DROPS => entry.fopen_syn_drops = Some(attr.get_u16()?),
+                TCP_METRICS_ATTR_FOPEN_COOKIE => entry.fopen_cookie = Some(attr.payload().to_vec()),
+                TCP_METRICS_ATTR_VALS => {
+                    for val in attr.nested()? {
+                        let v = Some(val.get_u32()?);
+                        match val.attr_type() {
+                            TCP_METRIC_RTT => rtt_ms = v,
+                            TCP_METRIC_RTTVAR => rttvar_ms = v,
+                            TCP_METRIC_RTT_US => entry.rtt = v,
+                            TCP_METRIC_RTTVAR_US => entry.rttvar = v,
+                            TCP_METRIC_SSTHRESH => entry.ssthresh = v,
+                            TCP_METRIC_CWND => entry.cwnd = v,
+                            TCP_METRIC_REORDERING => entry.reordering = v,
+                            _ => {},
+                        }
+                    }
+                },
+                _ => {},
+            }
+        }
+        entry.rtt = entry.rtt.or_else(|| rtt_ms.map(|v| v * 1000));
+        entry.rttvar = entry.rttvar.or_else(|| rttvar_ms.map(|v| v * 1000));
+        Ok(entry)
+    }
+
+    /// Destination
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    /// Source address, for kernels that key the cache by both ends
+    pub fn saddr(&self) -> Option<IpAddr> {
+        self.saddr
+    }
+
+    /// Time since the entry was updated, in milliseconds
+    pub fn age(&self) -> Option<u64> {
+        self.age
+    }
+
+    /// Smoothed round trip time, in microseconds
+    pub fn rtt(&self) -> Option<u32> {
+        self.rtt
+    }
+
+    /// Round trip time variance, in microseconds
+    pub fn rttvar(&self) -> Option<u32> {
+        self.rttvar
+    }
+
+    /// Slow start threshold, in segments
+    pub fn ssthresh(&self) -> Option<u32> {
+        self.ssthresh
+    }
+
+    /// Congestion window, in segments
+    pub fn cwnd(&self) -> Option<u32> {
+        self.cwnd
+    }
+
+    pub fn reordering(&self) -> Option<u32> {
+        self.reordering
+    }
+
+    /// MSS learned for TCP Fast Open
+    pub fn fopen_mss(&self) -> Option<u16> {
+        self.fopen_mss
+    }
+
+    /// Fast Open SYNs that went unanswered
+    pub fn fopen_syn_drops(&self) -> Option<u16> {
+        self.fopen_syn_drops
+    }
+
+    /// Fast Open cookie received from the destination
+    pub fn fopen_cookie(&self) -> Option<&[u8]> {
+        self.fopen_cookie.as_deref()
+    }
+}
+
+/// Handle to the tcp_metrics family
+pub struct TcpMetrics<T: NetlinkTransport = Socket> {
+    socket: T,
+    family: GenlFamily,
+}
+
+impl TcpMetrics {
+    pub fn new() -> io::Result<TcpMetrics> {
+        TcpMetrics::from_transport(Socket::new(Protocol::Generic)?)
+    }
+}
+
+impl<T: NetlinkTransport> TcpMetrics<T> {
+    /// Like `new`, but talks to the kernel through `transport`.
+    pub fn from_transport(mut transport: T) -> io::Result<TcpMetrics<T>> {
+        let family = GenlFamily::resolve(&mut transport, TCP_METRICS_GENL_NAME)?;
+        Ok(TcpMetrics {
+            socket: transport,
+            family,
+        })
+    }
+
+    /// Lists the cached entries (TCP_METRICS_CMD_GET dump).
+    pub fn entries(&mut self) -> io::Result<Vec<TcpMetricsEntry>> {
+        let replies = self.family.dump(&mut self.socket, TCP_METRICS_CMD_GET, &[])?;
+        replies.iter().map(|r| TcpMetricsEntry::from_attrs(r)).collect()
+    }
+
+    /// Fetches the entry of `addr`, failing with ESRCH if there is none.
+    pub fn entry(&mut self, addr: IpAddr) -> io::Result<TcpMetricsEntry> {
+        let attrs = addr_attr(TCP_METRICS_ATTR_ADDR_IPV4, TCP_METRICS_ATTR_ADDR_IPV6, addr);
+        let replies = self.family.request(&mut self.socket, TCP_METRICS_CMD_GET, &attrs)?;
+        match replies.first() {
+            Some(reply) => TcpMetricsEntry::from_attrs(reply),
+            None => Err(io::Error::new(ErrorKind::NotFound, "no tcp_metrics reply")),
+        }
+    }
+
+    /// Deletes the entries of `addr`, of all sources or of `saddr` only,
+    /// failing with ESRCH if there are none.
+    pub fn delete(&mut self, addr: IpAddr, saddr: Option<IpAddr>) -> io::Result<()> {
+        let mut attrs = addr_attr(TCP_METRICS_ATTR_ADDR_IPV4, TCP_METRICS_ATTR_ADDR_IPV6, addr);
+        if let Some(saddr) = saddr {
+            attrs.extend(addr_attr(TCP_METRICS_ATTR_SADDR_IPV4, TCP_METRICS_ATTR_SADDR_IPV6, saddr));
+        }
+        self.family.request(&mut self.socket, TCP_METRICS_CMD_DEL, &attrs)?;
+        Ok(())
+    }
+
+    /// Deletes every entry of the namespace.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.family.request(&mut self.socket, TCP_METRICS_CMD_DEL, &[])?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket::NLA_F_NESTED;
+
+    use libc::{EPERM, ESRCH};
+
+    #[test]
+    fn test_entry_decode() {
+        let addr = IpAddr::V6("2001:db8::1".parse().unwrap());
+        let mut bytes = addr_attr(TCP_METRICS_ATTR_ADDR_IPV4, TCP_METRICS_ATTR_ADDR_IPV6, addr);
+        bytes.extend(NlAttr::new(TCP_METRICS_ATTR_AGE, &1500u64.to_ne_bytes()).bytes());
+        let mut vals = NlAttr::new(TCP_METRIC_RTT, &3u32.to_ne_bytes()).bytes();
+        vals.extend(NlAttr::new(TCP_METRIC_RTT_US, &2500u32.to_ne_bytes()).bytes());
+        vals.extend(NlAttr::new(TCP_METRIC_RTTVAR, &1u32.to_ne_bytes()).bytes());
+        vals.extend(NlAttr::new(TCP_METRIC_SSTHRESH, &20u32.to_ne_bytes()).bytes());
+        bytes.extend(NlAttr::new(TCP_METRICS_ATTR_VALS | NLA_F_NESTED, &vals).bytes());
+        bytes.extend(NlAttr::new(TCP_METRICS_ATTR_FOPEN_MSS, &1460u16.to_ne_bytes()).bytes());
+
+        let entry = TcpMetricsEntry::from_attrs(&bytes).unwrap();
+        assert_eq!(entry.addr(), addr);
+        assert_eq!(entry.saddr(), None);
+        assert_eq!(entry.age(), Some(1500));
+        assert_eq!(entry.rtt(), Some(2500));
+        // Only in milliseconds
+        assert_eq!(entry.rttvar(), Some(1000));
+        assert_eq!(entry.ssthresh(), Some(20));
+        assert_eq!(entry.cwnd(), None);
+        assert_eq!(entry.fopen_mss(), Some(1460));
+
+        assert!(parse_addr(&[1, 2, 3]).is_err());
+    }
+
+    #[test]
+    fn test_tcp_metrics() {
+        let mut metrics = TcpMetrics::new().unwrap();
+        metrics.entries().unwrap();
+        let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 199));
+        assert_eq!(metrics.entry(addr).unwrap_err().raw_os_error(), Some(ESRCH));
+        match metrics.delete(addr, None) {
+            Err(ref e) if e.raw_os_error() == Some(EPERM) => {},
+            r => assert_eq!(r.unwrap_err().raw_os_error(), Some(ESRCH)),
+        }
+    }
+}
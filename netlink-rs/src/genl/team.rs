@@ -0,0 +1,392 @@
+//! team driver generic netlink family
+//!
+//! Options and port state of team devices, as driven by teamd
+//! (`teamdctl`, `teamnl`).
+
+use super::GenlFamily;
+use socket::{Socket, NetlinkTransport, NlAttr, NLA_F_NESTED, string_attr};
+use Protocol;
+
+use std::io::{self, ErrorKind};
+
+const TEAM_GENL_NAME: &str = "team";
+
+const TEAM_CMD_OPTIONS_SET: u8 = 1;
+const TEAM_CMD_OPTIONS_GET: u8 = 2;
+const TEAM_CMD_PORT_LIST_GET: u8 = 3;
+
+const TEAM_ATTR_TEAM_IFINDEX: u16 = 1;
+const TEAM_ATTR_LIST_OPTION: u16 = 2;
+const TEAM_ATTR_LIST_PORT: u16 = 3;
+
+const TEAM_ATTR_ITEM_OPTION: u16 = 1;
+const TEAM_ATTR_ITEM_PORT: u16 = 1;
+
+const TEAM_ATTR_OPTION_NAME: u16 = 1;
+const TEAM_ATTR_OPTION_CHANGED: u16 = 2;
+const TEAM_ATTR_OPTION_TYPE: u16 = 3;
+const TEAM_ATTR_OPTION_DATA: u16 = 4;
+const TEAM_ATTR_OPTION_REMOVED: u16 = 5;
+const TEAM_ATTR_OPTION_PORT_IFINDEX: u16 = 6;
+const TEAM_ATTR_OPTION_ARRAY_INDEX: u16 = 7;
+
+const TEAM_ATTR_PORT_IFINDEX: u16 = 1;
+const TEAM_ATTR_PORT_CHANGED: u16 = 2;
+const TEAM_ATTR_PORT_LINKUP: u16 = 3;
+const TEAM_ATTR_PORT_SPEED: u16 = 4;
+const TEAM_ATTR_PORT_DUPLEX: u16 = 5;
+const TEAM_ATTR_PORT_REMOVED: u16 = 6;
+
+// Kernel attribute policy types (NLA_*) used in TEAM_ATTR_OPTION_TYPE
+const NLA_U32: u8 = 3;
+const NLA_STRING: u8 = 5;
+const NLA_FLAG: u8 = 6;
+const NLA_BINARY: u8 = 11;
+const NLA_S32: u8 = 14;
+
+/// Value of a team option
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum OptionValue {
+    U32(u32),
+    S32(i32),
+    String(String),
+    Binary(Vec<u8>),
+    Bool(bool),
+}
+
+impl OptionValue {
+    fn nla_type(&self) -> u8 {
+        match *self {
+            OptionValue::U32(_) => NLA_U32,
+            OptionValue::S32(_) => NLA_S32,
+            OptionValue::String(_) => NLA_STRING,
+            OptionValue::Binary(_) => NLA_BINARY,
+            OptionValue::Bool(_) => NLA_FLAG,
+        }
+    }
+}
+
+/// A team option, e.g. `mode` or the per-port `enabled`
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct TeamOption {
+    name: String,
+    value: OptionValue,
+    port_ifindex: Option<u32>,
+    array_index: Option<u32>,
+    changed: bool,
+    removed: bool,
+}
+
+impl TeamOption {
+    pub fn new(name: &str, value: OptionValue) -> TeamOption {
+        TeamOption {
+            name: name.into(),
+            value,
+            port_ifindex: None,
+            array_index: None,
+            changed: false,
+            removed: false,
+        }
+    }
+
+    fn from_attr(attr: &NlAttr) -> io::Result<TeamOption> {
+        let mut option = TeamOption::new("", OptionValue::Bool(false));
+        let mut nla_type = None;
+        let mut data = None;
+        for a in attr.nested()? {
+            match a.attr_type() {
+                TEAM_ATTR_OPTION_NAME => option.name = a.get_str()?.to_string(),
+                TEAM_ATTR_OPTION_CHANGED => option.changed = true,
+                TEAM_ATTR_OPTION_REMOVED => option.removed = true,
+                TEAM_ATTR_OPTION_TYPE => nla_type = Some(a.get_u8()?),
+                TEAM_ATTR_OPTION_DATA => data = Some(a),
+                TEAM_ATTR_OPTION_PORT_IFINDEX => option.port_ifindex = Some(a.get_u32()?),
+                TEAM_ATTR_OPTION_ARRAY_INDEX => option.array_index = Some(a.get_u32()?),
+                _ => {},
+            }
+        }
+        let value = data.unwrap_or(NlAttr::new(TEAM_ATTR_OPTION_DATA, &[]));
+        option.value = match nla_type {
+            Some(NLA_U32) => OptionValue::U32(value.get_u32()?),
+            Some(NLA_S32) => OptionValue::S32(value.get_u32()? as i32),
+            Some(NLA_STRING) => OptionValue::String(value.get_str()?.to_string()),
+            Some(NLA_BINARY) => OptionValue::Binary(value.payload().to_vec()),
+            // A flag is true when the data attribute is present
+            Some(NLA_FLAG) => OptionValue::Bool(data.is_some()),
+            _ => return Err(io::Error::new(ErrorKind::InvalidData, "unknown team option type")),
+        };
+        Ok(option)
+    }
+
+    /// Encodes the option as a nested TEAM_ATTR_ITEM_OPTION attribute.
+    fn attr(&self) -> Vec<u8> {
+        let mut bytes = string_attr(TEAM_ATTR_OPTION_NAME, &self.name);
+        bytes.extend(NlAttr::new(TEAM_ATTR_OPTION_TYPE, &[self.value.nla_type()]).bytes());
+        match self.value {
+            OptionValue::U32(v) => bytes.extend(NlAttr::new(TEAM_ATTR_OPTION_DATA, &v.to_ne_bytes()).bytes()),
+            OptionValue::S32(v) => bytes.extend(NlAttr::new(TEAM_ATTR_OPTION_DATA, &v.to_ne_bytes()).bytes()),
+            OptionValue::String(ref s) => bytes.extend(string_attr(TEAM_ATTR_OPTION_DATA, s)),
+            OptionValue::Binary(ref b) => bytes.extend(NlAttr::new(TEAM_ATTR_OPTION_DATA, b).bytes()),
+            OptionValue::Bool(true) => bytes.extend(NlAttr::new(TEAM_ATTR_OPTION_DATA, &[]).bytes()),
+            OptionValue::Bool(false) => {},
+        }
+        if let Some(ifindex) = self.port_ifindex {
+            bytes.extend(NlAttr::new(TEAM_ATTR_OPTION_PORT_IFINDEX, &ifindex.to_ne_bytes()).bytes());
+        }
+        if let Some(index) = self.array_index {
+            bytes.extend(NlAttr::new(TEAM_ATTR_OPTION_ARRAY_INDEX, &index.to_ne_bytes()).bytes());
+        }
+        NlAttr::new(TEAM_ATTR_ITEM_OPTION | NLA_F_NESTED, &bytes).bytes()
+    }
+
+    /// Set port a per-port option applies to
+    pub fn set_port_ifindex(&mut self, ifindex: u32) -> &mut TeamOption {
+        self.port_ifindex = Some(ifindex);
+        self
+    }
+
+    /// Set element of an array option
+    pub fn set_array_index(&mut self, index: u32) -> &mut TeamOption {
+        self.array_index = Some(index);
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn value(&self) -> &OptionValue {
+        &self.value
+    }
+
+    pub fn port_ifindex(&self) -> Option<u32> {
+        self.port_ifindex
+    }
+
+    pub fn array_index(&self) -> Option<u32> {
+        self.array_index
+    }
+
+    /// Whether the option changed since it was last reported
+    pub fn is_changed(&self) -> bool {
+        self.changed
+    }
+
+    /// Whether the option went away, e.g. with its port
+    pub fn is_removed(&self) -> bool {
+        self.removed
+    }
+}
+
+/// A port of a team device
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct TeamPort {
+    ifindex: u32,
+    linkup: bool,
+    speed: u32,
+    duplex: u8,
+    changed: bool,
+    removed: bool,
+}
+
+impl TeamPort {
+    fn from_attr(attr: &NlAttr) -> io::Result<TeamPort> {
+        let mut port = TeamPort {
+            ifindex: 0,
+            linkup: false,
+            speed: 0,
+            duplex: 0,
+            changed: false,
+            removed: false,
+        };
+        for a in attr.nested()? {
+            match a.attr_type() {
+                TEAM_ATTR_PORT_IFINDEX => port.ifindex = a.get_u32()?,
+                TEAM_ATTR_PORT_CHANGED => port.changed = true,
+                TEAM_ATTR_PORT_LINKUP => port.linkup = true,
+                TEAM_ATTR_PORT_SPEED => port.speed = a.get_u32()?,
+                TEAM_ATTR_PORT_DUPLEX => port.duplex = a.get_u8()?,
+                TEAM_ATTR_PORT_REMOVED => port.removed = true,
+                _ => {},
+            }
+        }
+        Ok(port)
+    }
+
+    pub fn ifindex(&self) -> u32 {
+        self.ifindex
+    }
+
+    pub fn is_linkup(&self) -> bool {
+        self.linkup
+    }
+
+    /// Link speed in Mb/s
+    pub fn speed(&self) -> u32 {
+        self.speed
+    }
+
+    /// Whether the link is full duplex
+    pub fn is_full_duplex(&self) -> bool {
+        self.duplex == 1
+    }
+
+    pub fn is_changed(&self) -> bool {
+        self.changed
+    }
+
+    pub fn is_removed(&self) -> bool {
+        self.removed
+    }
+}
+
+fn options_from_attrs(bytes: &[u8]) -> io::Result<Vec<TeamOption>> {
+    let mut options = vec![];
+    for attr in NlAttr::parse(bytes)? {
+        if attr.attr_type() == TEAM_ATTR_LIST_OPTION {
+            for item in attr.nested()? {
+                options.push(TeamOption::from_attr(&item)?);
+            }
+        }
+    }
+    Ok(options)
+}
+
+fn ports_from_attrs(bytes: &[u8]) -> io::Result<Vec<TeamPort>> {
+    let mut ports = vec![];
+    for attr in NlAttr::parse(bytes)? {
+        if attr.attr_type() == TEAM_ATTR_LIST_PORT {
+            for item in attr.nested()? {
+                if item.attr_type() == TEAM_ATTR_ITEM_PORT {
+                    ports.push(TeamPort::from_attr(&item)?);
+                }
+            }
+        }
+    }
+    Ok(ports)
+}
+
+/// Handle to the team family
+pub struct Team<T: NetlinkTransport = Socket> {
+    socket: T,
+    family: GenlFamily,
+}
+
+impl Team {
+    pub fn new() -> io::Result<Team> {
+        Team::from_transport(Socket::new(Protocol::Generic)?)
+    }
+}
+
+impl<T: NetlinkTransport> Team<T> {
+    /// Like `new`, but talks to the kernel through `transport`.
+    pub fn from_transport(mut transport: T) -> io::Result<Team<T>> {
+        let family = GenlFamily::resolve(&mut transport, TEAM_GENL_NAME)?;
+        Ok(Team {
+            socket: transport,
+            family,
+        })
+    }
+
+    fn get(&mut self, cmd: u8, team_ifindex: u32) -> io::Result<Vec<Vec<u8>>> {
+        let attrs = NlAttr::new(TEAM_ATTR_TEAM_IFINDEX, &team_ifindex.to_ne_bytes()).bytes();
+        let replies = self.family.request(&mut self.socket, cmd, &attrs)?;
+        // The reply is multipart; the ack comes after NLMSG_DONE, drain it
+        self.socket.recv_bytes()?;
+        Ok(replies)
+    }
+
+    /// Lists the options of the team device `team_ifindex`, including those
+    /// of its ports.
+    pub fn options(&mut self, team_ifindex: u32) -> io::Result<Vec<TeamOption>> {
+        let mut options = vec![];
+        for reply in self.get(TEAM_CMD_OPTIONS_GET, team_ifindex)? {
+            options.extend(options_from_attrs(&reply)?);
+        }
+        Ok(options)
+    }
+
+    /// Looks up the team-wide option `name`.
+    pub fn option(&mut self, team_ifindex: u32, name: &str) -> io::Result<TeamOption> {
+        self.options(team_ifindex)?.into_iter()
+            .find(|o| o.name() == name && o.port_ifindex().is_none())
+            .ok_or_else(|| io::Error::new(ErrorKind::NotFound, "no such team option"))
+    }
+
+    pub fn set_options(&mut self, team_ifindex: u32, options: &[TeamOption]) -> io::Result<()> {
+        let mut list = vec![];
+        for option in options {
+            list.extend(option.attr());
+        }
+        let mut attrs = NlAttr::new(TEAM_ATTR_TEAM_IFINDEX, &team_ifindex.to_ne_bytes()).bytes();
+        attrs.extend(NlAttr::new(TEAM_ATTR_LIST_OPTION | NLA_F_NESTED, &list).bytes());
+        self.family.request(&mut self.socket, TEAM_CMD_OPTIONS_SET, &attrs)?;
+        Ok(())
+    }
+
+    /// Lists the ports of the team device `team_ifindex`.
+    pub fn ports(&mut self, team_ifindex: u32) -> io::Result<Vec<TeamPort>> {
+        let mut ports = vec![];
+        for reply in self.get(TEAM_CMD_PORT_LIST_GET, team_ifindex)? {
+            ports.extend(ports_from_attrs(&reply)?);
+        }
+        Ok(ports)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use libc::ENOENT;
+
+    #[test]
+    fn test_option_roundtrip() {
+        let mut enabled = TeamOption::new("enabled", OptionValue::Bool(true));
+        enabled.set_port_ifindex(4);
+        let options = [
+            TeamOption::new("mode", OptionValue::String("activebackup".into())),
+            TeamOption::new("mcast_rejoin_count", OptionValue::U32(3)),
+            TeamOption::new("priority", OptionValue::S32(-2)),
+            TeamOption::new("user_linkup", OptionValue::Bool(false)),
+            enabled,
+        ];
+        let mut list = vec![];
+        for option in &options {
+            list.extend(option.attr());
+        }
+        let bytes = NlAttr::new(TEAM_ATTR_LIST_OPTION | NLA_F_NESTED, &list).bytes();
+        assert_eq!(options_from_attrs(&bytes).unwrap(), options);
+    }
+
+    #[test]
+    fn test_ports_decode() {
+        let mut port = NlAttr::new(TEAM_ATTR_PORT_IFINDEX, &5u32.to_ne_bytes()).bytes();
+        port.extend(NlAttr::new(TEAM_ATTR_PORT_LINKUP, &[]).bytes());
+        port.extend(NlAttr::new(TEAM_ATTR_PORT_SPEED, &1000u32.to_ne_bytes()).bytes());
+        port.extend(NlAttr::new(TEAM_ATTR_PORT_DUPLEX, &[1]).bytes());
+        let item = NlAttr::new(TEAM_ATTR_ITEM_PORT | NLA_F_NESTED, &port).bytes();
+        let bytes = NlAttr::new(TEAM_ATTR_LIST_PORT | NLA_F_NESTED, &item).bytes();
+
+        let ports = ports_from_attrs(&bytes).unwrap();
+        assert_eq!(ports.len(), 1);
+        assert_eq!(ports[0].ifindex(), 5);
+        assert!(ports[0].is_linkup());
+        assert_eq!(ports[0].speed(), 1000);
+        assert!(ports[0].is_full_duplex());
+        assert!(!ports[0].is_removed());
+    }
+
+    #[test]
+    fn test_team_family() {
+        match Team::new() {
+            Ok(_) => {},
+            // team module not loaded
+            Err(ref e) if e.raw_os_error() == Some(ENOENT) => {},
+            Err(e) => panic!("{}", e),
+        }
+    }
+}
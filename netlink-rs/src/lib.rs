@@ -1,12 +1,31 @@
-#![cfg_attr(feature="clippy", feature(plugin))]
-#![cfg_attr(feature="clippy", plugin(clippy))]
-
 extern crate libc;
 extern crate byteorder;
+#[cfg(feature = "serde")]
+extern crate serde;
 
 pub mod socket;
-
-use std::convert::Into;
+#[cfg(feature = "genl")]
+pub mod genl;
+#[cfg(feature = "rtnl")]
+pub mod rtnetlink;
+#[cfg(feature = "nf")]
+pub mod netfilter;
+#[cfg(feature = "cn")]
+pub mod connector;
+#[cfg(feature = "uevent")]
+pub mod uevent;
+#[cfg(feature = "audit")]
+pub mod audit;
+#[cfg(feature = "selinux")]
+pub mod selinux;
+#[cfg(feature = "crypto")]
+pub mod cryptouser;
+#[cfg(feature = "diag")]
+pub mod sock_diag;
+#[cfg(feature = "rdma")]
+pub mod rdma;
+#[cfg(all(test, any(feature = "rtnl", feature = "nf")))]
+mod netns;
 
 pub enum Protocol {
     Route,           /* 0    Routing/device hook              */
@@ -28,12 +47,14 @@ pub enum Protocol {
     Generic,         // 16
     SCSITransport,   // 18  /* SCSI Transports */
     Ecryptfs,        // 19
+    Rdma,            // 20
+    Crypto,          // 21
 }
 
-impl Into<i32> for Protocol {
-    fn into(self) -> i32 {
+impl From<Protocol> for i32 {
+    fn from(t: Protocol) -> i32 {
         use Protocol::*;
-        match self {
+        match t {
             Route => 0,
             Unused => 1,
             Usersock => 2,
@@ -53,6 +74,8 @@ impl Into<i32> for Protocol {
             Generic => 16,
             SCSITransport => 18,
             Ecryptfs => 19,
+            Rdma => 20,
+            Crypto => 21,
         }
     }
 }
@@ -0,0 +1,191 @@
+//! Named accounting objects (`nfacct`)
+//!
+//! Rules reference an object by name, e.g. `nfacct-name` in iptables or
+//! `counter name` in nftables, and it counts the packets and bytes they
+//! match.
+
+use super::{exchange, nfnl_header, NfGenMsg, Subsystem};
+use socket::{NetlinkTransport, NlAttr, string_attr};
+
+use std::io::{self, Cursor, ErrorKind};
+
+use byteorder::{BigEndian, ReadBytesExt};
+use libc::AF_UNSPEC;
+
+const NFNL_MSG_ACCT_NEW: u8 = 0;
+const NFNL_MSG_ACCT_GET: u8 = 1;
+const NFNL_MSG_ACCT_GET_CTRZERO: u8 = 2;
+const NFNL_MSG_ACCT_DEL: u8 = 3;
+
+const NFACCT_NAME: u16 = 1;
+const NFACCT_PKTS: u16 = 2;
+const NFACCT_BYTES: u16 = 3;
+const NFACCT_USE: u16 = 4;
+
+/// An accounting object and its counters
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Counter {
+    name: String,
+    packets: u64,
+    bytes: u64,
+    use_count: u32,
+}
+
+impl Counter {
+    fn from_bytes(bytes: &[u8]) -> io::Result<Counter> {
+        let (_, n) = NfGenMsg::from_bytes(bytes)?;
+        let mut counter = Counter {
+            name: String::new(),
+            packets: 0,
+            bytes: 0,
+            use_count: 0,
+        };
+        for attr in NlAttr::parse(&bytes[n..])? {
+            let mut cursor = Cursor::new(attr.payload());
+            match attr.attr_type() {
+                NFACCT_NAME => counter.name = attr.get_str()?.to_string(),
+                NFACCT_PKTS => counter.packets = cursor.read_u64::<BigEndian>()?,
+                NFACCT_BYTES => counter.bytes = cursor.read_u64::<BigEndian>()?,
+                // The kernel counts the reference held by the object itself
+                NFACCT_USE => counter.use_count = cursor.read_u32::<BigEndian>()?.saturating_sub(1),
+                _ => {},
+            }
+        }
+        Ok(counter)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn packets(&self) -> u64 {
+        self.packets
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Number of rules referencing the object
+    pub fn use_count(&self) -> u32 {
+        self.use_count
+    }
+}
+
+fn name_attr(name: &str) -> Vec<u8> {
+    string_attr(NFACCT_NAME, name)
+}
+
+fn get(socket: &mut impl NetlinkTransport, msg: u8, name: Option<&str>) -> io::Result<Vec<Counter>> {
+    let mut hdr = nfnl_header(Subsystem::Acct, msg);
+    let payload = match name {
+        Some(name) => name_attr(name),
+        None => {
+            hdr.dump();
+            vec![]
+        },
+    };
+    let replies = exchange(socket, hdr, AF_UNSPEC as u8, &payload)?;
+    replies.iter().map(|r| Counter::from_bytes(r)).collect()
+}
+
+fn single(mut counters: Vec<Counter>) -> io::Result<Counter> {
+    counters.pop().ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "no nfacct reply"))
+}
+
+/// Creates object `name` with zeroed counters, failing with `EEXIST` if it
+/// already exists.
+pub fn create_counter(socket: &mut impl NetlinkTransport, name: &str) -> io::Result<()> {
+    let mut hdr = nfnl_header(Subsystem::Acct, NFNL_MSG_ACCT_NEW);
+    hdr.create().excl();
+    exchange(socket, hdr, AF_UNSPEC as u8, &name_attr(name))?;
+    Ok(())
+}
+
+/// Deletes object `name`, which must not be referenced by any rule.
+pub fn del_counter(socket: &mut impl NetlinkTransport, name: &str) -> io::Result<()> {
+    let hdr = nfnl_header(Subsystem::Acct, NFNL_MSG_ACCT_DEL);
+    exchange(socket, hdr, AF_UNSPEC as u8, &name_attr(name))?;
+    Ok(())
+}
+
+/// Lists all objects.
+pub fn counters(socket: &mut impl NetlinkTransport) -> io::Result<Vec<Counter>> {
+    get(socket, NFNL_MSG_ACCT_GET, None)
+}
+
+pub fn counter(socket: &mut impl NetlinkTransport, name: &str) -> io::Result<Counter> {
+    single(get(socket, NFNL_MSG_ACCT_GET, Some(name))?)
+}
+
+/// Lists all objects and zeroes their counters, returning the values from
+/// before the reset.
+pub fn reset_counters(socket: &mut impl NetlinkTransport) -> io::Result<Vec<Counter>> {
+    get(socket, NFNL_MSG_ACCT_GET_CTRZERO, None)
+}
+
+/// Zeroes the counters of object `name`, returning the values from before the
+/// reset.
+pub fn reset_counter(socket: &mut impl NetlinkTransport, name: &str) -> io::Result<Counter> {
+    single(get(socket, NFNL_MSG_ACCT_GET_CTRZERO, Some(name))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket::{Socket, NlAttr};
+    use Protocol;
+    use netns;
+
+    use libc::{ENOENT, EOPNOTSUPP, EPERM};
+
+    #[test]
+    fn test_counter_decoding() {
+        let mut bytes = vec![0, 0, 0, 0];
+        bytes.extend(name_attr("web"));
+        bytes.extend(NlAttr::new(NFACCT_PKTS, &3u64.to_be_bytes()).bytes());
+        bytes.extend(NlAttr::new(NFACCT_BYTES, &(1u64 << 40).to_be_bytes()).bytes());
+        bytes.extend(NlAttr::new(NFACCT_USE, &2u32.to_be_bytes()).bytes());
+
+        let counter = Counter::from_bytes(&bytes).unwrap();
+        assert_eq!(counter.name(), "web");
+        assert_eq!(counter.packets(), 3);
+        assert_eq!(counter.bytes(), 1 << 40);
+        assert_eq!(counter.use_count(), 1);
+    }
+
+    /// Deletes counter `name` when dropped
+    struct CounterGuard(&'static str);
+
+    impl Drop for CounterGuard {
+        fn drop(&mut self) {
+            if let Ok(mut socket) = Socket::new(Protocol::Netfilter) {
+                let _ = del_counter(&mut socket, self.0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_counters() {
+        netns::run(|| {
+            let mut socket = Socket::new(Protocol::Netfilter).unwrap();
+            match create_counter(&mut socket, "nlrs-acct") {
+                // No nfnetlink_acct in this kernel
+                Err(ref e) if e.raw_os_error() == Some(EPERM) => return,
+                Err(ref e) if e.raw_os_error() == Some(EOPNOTSUPP) => return,
+                r => r.unwrap(),
+            }
+            let _guard = CounterGuard("nlrs-acct");
+
+            let c = counter(&mut socket, "nlrs-acct").unwrap();
+            assert_eq!((c.packets(), c.bytes(), c.use_count()), (0, 0, 0));
+            assert!(counters(&mut socket).unwrap().iter().any(|c| c.name() == "nlrs-acct"));
+            assert_eq!(reset_counter(&mut socket, "nlrs-acct").unwrap().name(), "nlrs-acct");
+
+            del_counter(&mut socket, "nlrs-acct").unwrap();
+            let err = counter(&mut socket, "nlrs-acct").unwrap_err();
+            assert_eq!(err.raw_os_error(), Some(ENOENT));
+        });
+    }
+}
@@ -0,0 +1,546 @@
+//! Connection tracking (`conntrack`)
+//!
+//! Connections and expectations are separate subsystems. Both are dumped
+//! across all families and can be monitored through `ConntrackEvents`.
+
+use super::{exchange, nfnl_header, NfGenMsg, Subsystem};
+use socket::{Socket, NetlinkTransport, NetlinkAddr, NlAttr, Payload, MsgType, Overrun, NLA_F_NESTED, ip_family, ip_from_bytes};
+
+use std::io::{self, Cursor};
+use std::net::{IpAddr, Ipv4Addr};
+
+use byteorder::{BigEndian, ReadBytesExt};
+use libc::AF_UNSPEC;
+
+const IPCTNL_MSG_CT_NEW: u8 = 0;
+const IPCTNL_MSG_CT_GET: u8 = 1;
+const IPCTNL_MSG_CT_DELETE: u8 = 2;
+
+const IPCTNL_MSG_EXP_NEW: u8 = 0;
+const IPCTNL_MSG_EXP_GET: u8 = 1;
+const IPCTNL_MSG_EXP_DELETE: u8 = 2;
+
+const CTA_TUPLE_ORIG: u16 = 1;
+const CTA_TUPLE_REPLY: u16 = 2;
+const CTA_STATUS: u16 = 3;
+const CTA_TIMEOUT: u16 = 7;
+const CTA_MARK: u16 = 8;
+const CTA_ID: u16 = 12;
+
+const CTA_TUPLE_IP: u16 = 1;
+const CTA_TUPLE_PROTO: u16 = 2;
+
+const CTA_IP_V4_SRC: u16 = 1;
+const CTA_IP_V4_DST: u16 = 2;
+const CTA_IP_V6_SRC: u16 = 3;
+const CTA_IP_V6_DST: u16 = 4;
+
+const CTA_PROTO_NUM: u16 = 1;
+const CTA_PROTO_SRC_PORT: u16 = 2;
+const CTA_PROTO_DST_PORT: u16 = 3;
+
+const CTA_EXPECT_MASTER: u16 = 1;
+const CTA_EXPECT_TUPLE: u16 = 2;
+const CTA_EXPECT_TIMEOUT: u16 = 4;
+const CTA_EXPECT_ID: u16 = 5;
+const CTA_EXPECT_HELP_NAME: u16 = 6;
+
+const NLM_F_CREATE: u16 = 0x400;
+
+/// ctnetlink multicast groups (NFNLGRP_CONNTRACK_*)
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum ConntrackGroup {
+    New,
+    Update,
+    Destroy,
+    ExpNew,
+    ExpUpdate,
+    ExpDestroy,
+}
+
+impl From<ConntrackGroup> for u32 {
+    fn from(g: ConntrackGroup) -> u32 {
+        use self::ConntrackGroup::*;
+        match g {
+            New => 1,
+            Update => 2,
+            Destroy => 3,
+            ExpNew => 4,
+            ExpUpdate => 5,
+            ExpDestroy => 6,
+        }
+    }
+}
+
+/// One direction of a connection: addresses, protocol and ports
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Tuple {
+    src: IpAddr,
+    dst: IpAddr,
+    protocol: u8,
+    src_port: Option<u16>,
+    dst_port: Option<u16>,
+}
+
+impl Tuple {
+    /// Describes a tuple without ports, e.g. for ICMP
+    pub fn new(src: IpAddr, dst: IpAddr, protocol: u8) -> Tuple {
+        Tuple {
+            src,
+            dst,
+            protocol,
+            src_port: None,
+            dst_port: None,
+        }
+    }
+
+    pub fn set_ports(&mut self, src: u16, dst: u16) -> &mut Tuple {
+        self.src_port = Some(src);
+        self.dst_port = Some(dst);
+        self
+    }
+
+    fn from_attr(attr: &NlAttr) -> io::Result<Tuple> {
+        let unspecified = IpAddr::V4(Ipv4Addr::UNSPECIFIED);
+        let mut tuple = Tuple::new(unspecified, unspecified, 0);
+        for a in attr.nested()? {
+            match a.attr_type() {
+                CTA_TUPLE_IP => {
+                    for ip in a.nested()? {
+                        match ip.attr_type() {
+                            CTA_IP_V4_SRC | CTA_IP_V6_SRC => tuple.src = addr(ip.payload())?,
+                            CTA_IP_V4_DST | CTA_IP_V6_DST => tuple.dst = addr(ip.payload())?,
+                            _ => {},
+                        }
+                    }
+                },
+                CTA_TUPLE_PROTO => {
+                    for p in a.nested()? {
+                        let mut cursor = Cursor::new(p.payload());
+                        match p.attr_type() {
+                            CTA_PROTO_NUM => tuple.protocol = p.get_u8()?,
+                            CTA_PROTO_SRC_PORT => tuple.src_port = Some(cursor.read_u16::<BigEndian>()?),
+                            CTA_PROTO_DST_PORT => tuple.dst_port = Some(cursor.read_u16::<BigEndian>()?),
+                            _ => {},
+                        }
+                    }
+                },
+                _ => {},
+            }
+        }
+        Ok(tuple)
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        let mut ip = vec![];
+        match (self.src, self.dst) {
+            (IpAddr::V6(s), IpAddr::V6(d)) => {
+                ip.extend(NlAttr::new(CTA_IP_V6_SRC, &s.octets()).bytes());
+                ip.extend(NlAttr::new(CTA_IP_V6_DST, &d.octets()).bytes());
+            },
+            (s, d) => {
+                ip.extend(NlAttr::new(CTA_IP_V4_SRC, &v4(s).octets()).bytes());
+                ip.extend(NlAttr::new(CTA_IP_V4_DST, &v4(d).octets()).bytes());
+            },
+        }
+        let mut proto = NlAttr::new(CTA_PROTO_NUM, &[self.protocol]).bytes();
+        if let Some(port) = self.src_port {
+            proto.extend(NlAttr::new(CTA_PROTO_SRC_PORT, &port.to_be_bytes()).bytes());
+        }
+        if let Some(port) = self.dst_port {
+            proto.extend(NlAttr::new(CTA_PROTO_DST_PORT, &port.to_be_bytes()).bytes());
+        }
+
+        let mut bytes = NlAttr::new(CTA_TUPLE_IP | NLA_F_NESTED, &ip).bytes();
+        bytes.extend(NlAttr::new(CTA_TUPLE_PROTO | NLA_F_NESTED, &proto).bytes());
+        bytes
+    }
+
+    fn family(&self) -> u8 {
+        ip_family(self.src)
+    }
+
+    pub fn src(&self) -> IpAddr {
+        self.src
+    }
+
+    pub fn dst(&self) -> IpAddr {
+        self.dst
+    }
+
+    /// IP protocol number, e.g. IPPROTO_TCP
+    pub fn protocol(&self) -> u8 {
+        self.protocol
+    }
+
+    pub fn src_port(&self) -> Option<u16> {
+        self.src_port
+    }
+
+    pub fn dst_port(&self) -> Option<u16> {
+        self.dst_port
+    }
+}
+
+fn addr(payload: &[u8]) -> io::Result<IpAddr> {
+    Ok(ip_from_bytes(payload)?)
+}
+
+fn v4(addr: IpAddr) -> Ipv4Addr {
+    match addr {
+        IpAddr::V4(a) => a,
+        IpAddr::V6(a) => a.to_ipv4().unwrap_or(Ipv4Addr::UNSPECIFIED),
+    }
+}
+
+/// A tracked connection
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Connection {
+    orig: Tuple,
+    reply: Tuple,
+    status: u32,
+    timeout: u32,
+    mark: u32,
+    id: u32,
+}
+
+impl Connection {
+    fn from_bytes(bytes: &[u8]) -> io::Result<Connection> {
+        let (_, n) = NfGenMsg::from_bytes(bytes)?;
+        let unspecified = IpAddr::V4(Ipv4Addr::UNSPECIFIED);
+        let mut conn = Connection {
+            orig: Tuple::new(unspecified, unspecified, 0),
+            reply: Tuple::new(unspecified, unspecified, 0),
+            status: 0,
+            timeout: 0,
+            mark: 0,
+            id: 0,
+        };
+        for attr in NlAttr::parse(&bytes[n..])? {
+            let mut cursor = Cursor::new(attr.payload());
+            match attr.attr_type() {
+                CTA_TUPLE_ORIG => conn.orig = Tuple::from_attr(&attr)?,
+                CTA_TUPLE_REPLY => conn.reply = Tuple::from_attr(&attr)?,
+                CTA_STATUS => conn.status = cursor.read_u32::<BigEndian>()?,
+                CTA_TIMEOUT => conn.timeout = cursor.read_u32::<BigEndian>()?,
+                CTA_MARK => conn.mark = cursor.read_u32::<BigEndian>()?,
+                CTA_ID => conn.id = cursor.read_u32::<BigEndian>()?,
+                _ => {},
+            }
+        }
+        Ok(conn)
+    }
+
+    /// Tuple of the packet that created the connection
+    pub fn orig(&self) -> Tuple {
+        self.orig
+    }
+
+    /// Tuple expected for replies, differing from `orig` under NAT
+    pub fn reply(&self) -> Tuple {
+        self.reply
+    }
+
+    /// IPS_* status bits, e.g. IPS_ASSURED
+    pub fn status(&self) -> u32 {
+        self.status
+    }
+
+    /// Seconds until the connection expires
+    pub fn timeout(&self) -> u32 {
+        self.timeout
+    }
+
+    pub fn mark(&self) -> u32 {
+        self.mark
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+/// A connection a helper expects, e.g. the data connection of FTP
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Expectation {
+    master: Tuple,
+    tuple: Tuple,
+    timeout: u32,
+    id: u32,
+    helper: String,
+}
+
+impl Expectation {
+    fn from_bytes(bytes: &[u8]) -> io::Result<Expectation> {
+        let (_, n) = NfGenMsg::from_bytes(bytes)?;
+        let unspecified = IpAddr::V4(Ipv4Addr::UNSPECIFIED);
+        let mut exp = Expectation {
+            master: Tuple::new(unspecified, unspecified, 0),
+            tuple: Tuple::new(unspecified, unspecified, 0),
+            timeout: 0,
+            id: 0,
+            helper: String::new(),
+        };
+        for attr in NlAttr::parse(&bytes[n..])? {
+            let mut cursor = Cursor::new(attr.payload());
+            match attr.attr_type() {
+                CTA_EXPECT_MASTER => exp.master = Tuple::from_attr(&attr)?,
+                CTA_EXPECT_TUPLE => exp.tuple = Tuple::from_attr(&attr)?,
+                CTA_EXPECT_TIMEOUT => exp.timeout = cursor.read_u32::<BigEndian>()?,
+                CTA_EXPECT_ID => exp.id = cursor.read_u32::<BigEndian>()?,
+                CTA_EXPECT_HELP_NAME => exp.helper = attr.get_str()?.to_string(),
+                _ => {},
+            }
+        }
+        Ok(exp)
+    }
+
+    /// Original tuple of the connection that created the expectation
+    pub fn master(&self) -> Tuple {
+        self.master
+    }
+
+    /// Tuple of the expected connection
+    pub fn tuple(&self) -> Tuple {
+        self.tuple
+    }
+
+    /// Seconds until the expectation expires
+    pub fn timeout(&self) -> u32 {
+        self.timeout
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Name of the helper, e.g. "ftp"
+    pub fn helper(&self) -> &str {
+        &self.helper
+    }
+}
+
+/// Lists the connections of all families.
+pub fn connections(socket: &mut impl NetlinkTransport) -> io::Result<Vec<Connection>> {
+    let mut hdr = nfnl_header(Subsystem::Ctnetlink, IPCTNL_MSG_CT_GET);
+    hdr.dump();
+    let replies = exchange(socket, hdr, AF_UNSPEC as u8, &[])?;
+    replies.iter().map(|r| Connection::from_bytes(r)).collect()
+}
+
+/// Deletes the connection whose original direction is `orig`.
+pub fn del_connection(socket: &mut impl NetlinkTransport, orig: &Tuple) -> io::Result<()> {
+    let hdr = nfnl_header(Subsystem::Ctnetlink, IPCTNL_MSG_CT_DELETE);
+    let payload = NlAttr::new(CTA_TUPLE_ORIG | NLA_F_NESTED, &orig.bytes()).bytes();
+    exchange(socket, hdr, orig.family(), &payload)?;
+    Ok(())
+}
+
+/// Lists the expectations of all families.
+pub fn expectations(socket: &mut impl NetlinkTransport) -> io::Result<Vec<Expectation>> {
+    let mut hdr = nfnl_header(Subsystem::CtnetlinkExp, IPCTNL_MSG_EXP_GET);
+    hdr.dump();
+    let replies = exchange(socket, hdr, AF_UNSPEC as u8, &[])?;
+    replies.iter().map(|r| Expectation::from_bytes(r)).collect()
+}
+
+/// A change of the connection tracking table
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum ConntrackEvent {
+    New(Connection),
+    Update(Connection),
+    Destroy(Connection),
+    ExpNew(Expectation),
+    ExpDestroy(Expectation),
+}
+
+/// A socket subscribed to connection tracking events
+pub struct ConntrackEvents<T: NetlinkTransport = Socket> {
+    socket: T,
+    resync: Option<Resync>,
+}
+
+struct Resync {
+    transport: Box<dyn NetlinkTransport>,
+    f: Box<dyn FnMut(Vec<Connection>)>,
+}
+
+impl ConntrackEvents {
+    /// Subscribes to `groups`; usually requires CAP_NET_ADMIN.
+    pub fn new(groups: &[ConntrackGroup]) -> io::Result<ConntrackEvents> {
+        let socket = Socket::new(::Protocol::Netfilter)?;
+        let mask = groups.iter().fold(0, |mask, &g| mask | 1 << (u32::from(g) - 1));
+        socket.bind(NetlinkAddr::new(0, mask))?;
+        Ok(ConntrackEvents::from_transport(socket))
+    }
+}
+
+impl<T: NetlinkTransport> ConntrackEvents<T> {
+    /// Reads events from `transport`, which is already subscribed.
+    pub fn from_transport(transport: T) -> ConntrackEvents<T> {
+        ConntrackEvents { socket: transport, resync: None }
+    }
+
+    /// Handles overruns by passing a fresh dump of the connections to
+    /// `resync` instead of failing `recv` with an `Overrun` error. The dump
+    /// goes through `transport`, e.g. a netfilter `Socket` of its own, as
+    /// the events would mix with its replies on the subscribed one.
+    pub fn set_resync<D, F>(&mut self, transport: D, resync: F) -> &mut ConntrackEvents<T>
+        where D: NetlinkTransport + 'static,
+              F: FnMut(Vec<Connection>) + 'static
+    {
+        self.resync = Some(Resync { transport: Box::new(transport), f: Box::new(resync) });
+        self
+    }
+
+    /// Blocks until events arrive and returns those read at once.
+    pub fn recv(&mut self) -> io::Result<Vec<ConntrackEvent>> {
+        let messages = match self.socket.recv_msgs() {
+            Ok(messages) => messages,
+            Err(ref e) if Overrun::matches(e) && self.resync.is_some() => {
+                if let Some(ref mut resync) = self.resync {
+                    let snapshot = connections(&mut resync.transport)?;
+                    (resync.f)(snapshot);
+                }
+                return Ok(vec![]);
+            },
+            Err(e) => return Err(e),
+        };
+        let mut events = vec![];
+        for msg in messages {
+            let hdr = msg.header();
+            let bytes = match *msg.payload() {
+                Payload::Data(ref b) => &b[..],
+                Payload::Err(e, _) => return Err(io::Error::from_raw_os_error(-e)),
+                _ => continue,
+            };
+            let t = match hdr.msg_type() {
+                MsgType::UserDefined(t) => t,
+                _ => continue,
+            };
+            let create = hdr.flags() & NLM_F_CREATE != 0;
+            let ct = u8::from(Subsystem::Ctnetlink) as u16;
+            let exp = u8::from(Subsystem::CtnetlinkExp) as u16;
+            let event = match (t >> 8, t as u8) {
+                (s, IPCTNL_MSG_CT_NEW) if s == ct && create => {
+                    ConntrackEvent::New(Connection::from_bytes(bytes)?)
+                },
+                (s, IPCTNL_MSG_CT_NEW) if s == ct => ConntrackEvent::Update(Connection::from_bytes(bytes)?),
+                (s, IPCTNL_MSG_CT_DELETE) if s == ct => ConntrackEvent::Destroy(Connection::from_bytes(bytes)?),
+                (s, IPCTNL_MSG_EXP_NEW) if s == exp => ConntrackEvent::ExpNew(Expectation::from_bytes(bytes)?),
+                (s, IPCTNL_MSG_EXP_DELETE) if s == exp => {
+                    ConntrackEvent::ExpDestroy(Expectation::from_bytes(bytes)?)
+                },
+                _ => continue,
+            };
+            events.push(event);
+        }
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{exchange, nfnl_header, Subsystem};
+    use socket::{Socket, Msg, NlAttr, NlMsgHeader, ReplayTransport, NLA_F_NESTED};
+    use Protocol;
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use libc::{AF_INET, EPERM, IPPROTO_UDP};
+
+    fn udp_tuple(src: [u8; 4], dst: [u8; 4], sport: u16, dport: u16) -> Tuple {
+        let mut t = Tuple::new(IpAddr::V4(Ipv4Addr::from(src)), IpAddr::V4(Ipv4Addr::from(dst)),
+                               IPPROTO_UDP as u8);
+        t.set_ports(sport, dport);
+        t
+    }
+
+    #[test]
+    fn test_tuple_roundtrip() {
+        let tuple = udp_tuple([192, 0, 2, 1], [198, 51, 100, 2], 5353, 53);
+        let attr = NlAttr::new(CTA_TUPLE_ORIG | NLA_F_NESTED, &tuple.bytes()).bytes();
+        let (parsed, _) = NlAttr::from_bytes(&attr).unwrap();
+        assert_eq!(Tuple::from_attr(&parsed).unwrap(), tuple);
+    }
+
+    #[test]
+    fn test_conntrack_events() {
+        let mut events = match ConntrackEvents::new(&[ConntrackGroup::New, ConntrackGroup::Destroy]) {
+            Err(ref e) if e.raw_os_error() == Some(EPERM) => return,
+            r => r.unwrap(),
+        };
+        let mut socket = Socket::new(Protocol::Netfilter).unwrap();
+        assert!(expectations(&mut socket).is_ok());
+
+        // Insert a connection as `conntrack -I` does
+        let orig = udp_tuple([192, 0, 2, 10], [192, 0, 2, 20], 40000, 7);
+        let reply = udp_tuple([192, 0, 2, 20], [192, 0, 2, 10], 7, 40000);
+        let mut payload = NlAttr::new(CTA_TUPLE_ORIG | NLA_F_NESTED, &orig.bytes()).bytes();
+        payload.extend(NlAttr::new(CTA_TUPLE_REPLY | NLA_F_NESTED, &reply.bytes()).bytes());
+        payload.extend(NlAttr::new(CTA_TIMEOUT, &30u32.to_be_bytes()).bytes());
+        let mut hdr = nfnl_header(Subsystem::Ctnetlink, IPCTNL_MSG_CT_NEW);
+        hdr.create().excl();
+        exchange(&mut socket, hdr, AF_INET as u8, &payload).unwrap();
+
+        let conn = connections(&mut socket).unwrap().into_iter().find(|c| c.orig() == orig).unwrap();
+        assert_eq!(conn.reply(), reply);
+        assert!(conn.timeout() <= 30);
+        del_connection(&mut socket, &orig).unwrap();
+
+        let mut seen = vec![];
+        while seen.len() < 2 {
+            for event in events.recv().unwrap() {
+                match event {
+                    ConntrackEvent::New(c) if c.orig() == orig => seen.push("new"),
+                    ConntrackEvent::Destroy(c) if c.orig() == orig => seen.push("destroy"),
+                    _ => {},
+                }
+            }
+        }
+        assert_eq!(seen, ["new", "destroy"]);
+    }
+
+    /// Reports that events were dropped
+    struct Overrunning;
+
+    impl NetlinkTransport for Overrunning {
+        fn talk(&mut self, _: Msg) -> io::Result<Vec<Vec<u8>>> {
+            unreachable!("the dump goes through the resync transport")
+        }
+
+        fn talk_multi(&mut self, _: Vec<Msg>) -> io::Result<Vec<Vec<u8>>> {
+            unreachable!("the dump goes through the resync transport")
+        }
+
+        fn recv_msgs(&mut self) -> io::Result<Vec<Msg<'_>>> {
+            Err(io::Error::other(Overrun))
+        }
+    }
+
+    #[test]
+    fn test_conntrack_resync() {
+        let mut done = NlMsgHeader::done();
+        done.data_length(4);
+        let mut datagram = done.bytes().to_vec();
+        datagram.extend_from_slice(&[0; 4]);
+
+        let mut events = ConntrackEvents::from_transport(Overrunning);
+        assert!(Overrun::matches(&events.recv().unwrap_err()));
+
+        let snapshots = Rc::new(RefCell::new(vec![]));
+        let seen = snapshots.clone();
+        events.set_resync(ReplayTransport::new(vec![datagram]), move |conns| seen.borrow_mut().push(conns));
+        assert_eq!(events.recv().unwrap(), []);
+        assert_eq!(*snapshots.borrow(), [vec![]]);
+    }
+}
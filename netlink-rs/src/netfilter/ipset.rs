@@ -0,0 +1,311 @@
+//! IP sets (`ipset`)
+//!
+//! Every ipset message carries the protocol version; values in set data are
+//! in network byte order and flagged NLA_F_NET_BYTEORDER.
+
+use super::{exchange, nfnl_header, NfGenMsg, Subsystem};
+use socket::{NetlinkTransport, NlAttr, NLA_F_NESTED, NLA_F_NET_BYTEORDER, string_attr};
+
+use std::io::{self, ErrorKind};
+use std::net::IpAddr;
+
+use libc::AF_INET;
+
+const IPSET_PROTOCOL: u8 = 7;
+
+const IPSET_CMD_CREATE: u8 = 2;
+const IPSET_CMD_DESTROY: u8 = 3;
+const IPSET_CMD_ADD: u8 = 9;
+const IPSET_CMD_DEL: u8 = 10;
+const IPSET_CMD_TEST: u8 = 11;
+const IPSET_CMD_TYPE: u8 = 13;
+
+const IPSET_ATTR_PROTOCOL: u16 = 1;
+const IPSET_ATTR_SETNAME: u16 = 2;
+const IPSET_ATTR_TYPENAME: u16 = 3;
+const IPSET_ATTR_REVISION: u16 = 4;
+const IPSET_ATTR_FAMILY: u16 = 5;
+const IPSET_ATTR_DATA: u16 = 7;
+
+// In IPSET_ATTR_DATA
+const IPSET_ATTR_IP: u16 = 1;
+const IPSET_ATTR_CIDR: u16 = 3;
+const IPSET_ATTR_PORT: u16 = 4;
+const IPSET_ATTR_TIMEOUT: u16 = 6;
+const IPSET_ATTR_PROTO: u16 = 7;
+const IPSET_ATTR_HASHSIZE: u16 = 18;
+const IPSET_ATTR_MAXELEM: u16 = 19;
+
+// In IPSET_ATTR_IP
+const IPSET_ATTR_IPADDR_IPV4: u16 = 1;
+const IPSET_ATTR_IPADDR_IPV6: u16 = 2;
+
+/// Returned by the kernel when testing for an entry that is not in the set
+const IPSET_ERR_EXIST: i32 = 4103;
+
+/// Address family of a set
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum SetFamily {
+    Inet,
+    Inet6,
+}
+
+impl From<SetFamily> for u8 {
+    fn from(f: SetFamily) -> u8 {
+        match f {
+            SetFamily::Inet => 2,
+            SetFamily::Inet6 => 10,
+        }
+    }
+}
+
+/// Parameters of a new set (`ipset create NAME TYPE`)
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct SetConfig {
+    name: String,
+    type_name: String,
+    family: SetFamily,
+    timeout: Option<u32>,
+    hashsize: Option<u32>,
+    maxelem: Option<u32>,
+}
+
+impl SetConfig {
+    /// `type_name` is the set type, e.g. "hash:ip" or "hash:net,port".
+    pub fn new(name: &str, type_name: &str, family: SetFamily) -> SetConfig {
+        SetConfig {
+            name: name.to_owned(),
+            type_name: type_name.to_owned(),
+            family,
+            timeout: None,
+            hashsize: None,
+            maxelem: None,
+        }
+    }
+
+    /// Default lifetime of entries in seconds, enabling per entry timeouts
+    pub fn set_timeout(&mut self, secs: u32) -> &mut SetConfig {
+        self.timeout = Some(secs);
+        self
+    }
+
+    /// Initial hash table size of hash types
+    pub fn set_hashsize(&mut self, hashsize: u32) -> &mut SetConfig {
+        self.hashsize = Some(hashsize);
+        self
+    }
+
+    /// Maximum number of entries of hash types
+    pub fn set_maxelem(&mut self, maxelem: u32) -> &mut SetConfig {
+        self.maxelem = Some(maxelem);
+        self
+    }
+
+    fn bytes(&self, revision: u8) -> Vec<u8> {
+        let mut bytes = header_attrs(&self.name);
+        bytes.extend(string_attr(IPSET_ATTR_TYPENAME, &self.type_name));
+        bytes.extend(NlAttr::new(IPSET_ATTR_REVISION, &[revision]).bytes());
+        bytes.extend(NlAttr::new(IPSET_ATTR_FAMILY, &[u8::from(self.family)]).bytes());
+
+        let mut data = vec![];
+        for &(attr_type, value) in &[(IPSET_ATTR_TIMEOUT, self.timeout),
+                                     (IPSET_ATTR_HASHSIZE, self.hashsize),
+                                     (IPSET_ATTR_MAXELEM, self.maxelem)] {
+            if let Some(v) = value {
+                data.extend(NlAttr::new(attr_type | NLA_F_NET_BYTEORDER, &v.to_be_bytes()).bytes());
+            }
+        }
+        bytes.extend(NlAttr::new(IPSET_ATTR_DATA | NLA_F_NESTED, &data).bytes());
+        bytes
+    }
+}
+
+/// An entry of a set (`ipset add NAME ENTRY`)
+///
+/// Which parts are required depends on the set type, e.g. hash:ip,port sets
+/// need a port.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct SetEntry {
+    addr: IpAddr,
+    cidr: Option<u8>,
+    port: Option<(u8, u16)>,
+    timeout: Option<u32>,
+}
+
+impl SetEntry {
+    pub fn new(addr: IpAddr) -> SetEntry {
+        SetEntry {
+            addr,
+            cidr: None,
+            port: None,
+            timeout: None,
+        }
+    }
+
+    /// Prefix length of net types
+    pub fn set_cidr(&mut self, cidr: u8) -> &mut SetEntry {
+        self.cidr = Some(cidr);
+        self
+    }
+
+    /// Port of `protocol`, e.g. `libc::IPPROTO_TCP as u8`
+    pub fn set_port(&mut self, protocol: u8, port: u16) -> &mut SetEntry {
+        self.port = Some((protocol, port));
+        self
+    }
+
+    /// Lifetime in seconds, overriding the default of the set
+    pub fn set_timeout(&mut self, secs: u32) -> &mut SetEntry {
+        self.timeout = Some(secs);
+        self
+    }
+
+    fn bytes(&self, set: &str) -> Vec<u8> {
+        let ip = match self.addr {
+            IpAddr::V4(a) => NlAttr::new(IPSET_ATTR_IPADDR_IPV4 | NLA_F_NET_BYTEORDER, &a.octets()).bytes(),
+            IpAddr::V6(a) => NlAttr::new(IPSET_ATTR_IPADDR_IPV6 | NLA_F_NET_BYTEORDER, &a.octets()).bytes(),
+        };
+        let mut data = NlAttr::new(IPSET_ATTR_IP | NLA_F_NESTED, &ip).bytes();
+        if let Some(cidr) = self.cidr {
+            data.extend(NlAttr::new(IPSET_ATTR_CIDR, &[cidr]).bytes());
+        }
+        if let Some((protocol, port)) = self.port {
+            data.extend(NlAttr::new(IPSET_ATTR_PORT | NLA_F_NET_BYTEORDER, &port.to_be_bytes()).bytes());
+            data.extend(NlAttr::new(IPSET_ATTR_PROTO, &[protocol]).bytes());
+        }
+        if let Some(timeout) = self.timeout {
+            data.extend(NlAttr::new(IPSET_ATTR_TIMEOUT | NLA_F_NET_BYTEORDER, &timeout.to_be_bytes()).bytes());
+        }
+
+        let mut bytes = header_attrs(set);
+        bytes.extend(NlAttr::new(IPSET_ATTR_DATA | NLA_F_NESTED, &data).bytes());
+        bytes
+    }
+}
+
+// Protocol version and set name, leading every command
+fn header_attrs(set: &str) -> Vec<u8> {
+    let mut bytes = NlAttr::new(IPSET_ATTR_PROTOCOL, &[IPSET_PROTOCOL]).bytes();
+    bytes.extend(string_attr(IPSET_ATTR_SETNAME, set));
+    bytes
+}
+
+fn command(socket: &mut impl NetlinkTransport, cmd: u8, payload: &[u8]) -> io::Result<Vec<Vec<u8>>> {
+    exchange(socket, nfnl_header(Subsystem::Ipset, cmd), AF_INET as u8, payload)
+}
+
+// Newest revision of the set type the kernel supports
+fn type_revision(socket: &mut impl NetlinkTransport, type_name: &str, family: SetFamily) -> io::Result<u8> {
+    let mut payload = NlAttr::new(IPSET_ATTR_PROTOCOL, &[IPSET_PROTOCOL]).bytes();
+    payload.extend(string_attr(IPSET_ATTR_TYPENAME, type_name));
+    payload.extend(NlAttr::new(IPSET_ATTR_FAMILY, &[u8::from(family)]).bytes());
+
+    for reply in command(socket, IPSET_CMD_TYPE, &payload)? {
+        let (_, n) = NfGenMsg::from_bytes(&reply)?;
+        for attr in NlAttr::parse(&reply[n..])? {
+            if attr.attr_type() == IPSET_ATTR_REVISION {
+                if let Some(&revision) = attr.payload().first() {
+                    return Ok(revision);
+                }
+            }
+        }
+    }
+    Err(io::Error::new(ErrorKind::InvalidData, "no revision in ipset type reply"))
+}
+
+/// Creates a set, using the newest revision of its type the kernel supports.
+pub fn create_set(socket: &mut impl NetlinkTransport, config: &SetConfig) -> io::Result<()> {
+    let revision = type_revision(socket, &config.type_name, config.family)?;
+    let mut hdr = nfnl_header(Subsystem::Ipset, IPSET_CMD_CREATE);
+    hdr.create().excl();
+    exchange(socket, hdr, AF_INET as u8, &config.bytes(revision))?;
+    Ok(())
+}
+
+/// Destroys set `name`, which must not be referenced by any rule.
+pub fn destroy_set(socket: &mut impl NetlinkTransport, name: &str) -> io::Result<()> {
+    command(socket, IPSET_CMD_DESTROY, &header_attrs(name))?;
+    Ok(())
+}
+
+pub fn add_entry(socket: &mut impl NetlinkTransport, set: &str, entry: &SetEntry) -> io::Result<()> {
+    command(socket, IPSET_CMD_ADD, &entry.bytes(set))?;
+    Ok(())
+}
+
+pub fn del_entry(socket: &mut impl NetlinkTransport, set: &str, entry: &SetEntry) -> io::Result<()> {
+    command(socket, IPSET_CMD_DEL, &entry.bytes(set))?;
+    Ok(())
+}
+
+/// Checks whether `entry` is in `set`.
+pub fn test_entry(socket: &mut impl NetlinkTransport, set: &str, entry: &SetEntry) -> io::Result<bool> {
+    match command(socket, IPSET_CMD_TEST, &entry.bytes(set)) {
+        Ok(_) => Ok(true),
+        Err(ref e) if e.raw_os_error() == Some(IPSET_ERR_EXIST) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket::{Socket, NlAttr, NLA_TYPE_MASK};
+    use Protocol;
+    use netns;
+
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use libc::{EPERM, IPPROTO_TCP};
+
+    #[test]
+    fn test_entry_bytes() {
+        let mut entry = SetEntry::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)));
+        entry.set_port(IPPROTO_TCP as u8, 443);
+
+        let bytes = entry.bytes("s");
+        let attrs = NlAttr::parse(&bytes).unwrap();
+        assert_eq!(attrs[0].payload(), &[IPSET_PROTOCOL]);
+        assert_eq!(attrs[1].payload(), b"s\0");
+
+        let data = attrs[2].nested().unwrap();
+        let types: Vec<u16> = data.iter().map(|a| a.attr_type() & NLA_TYPE_MASK).collect();
+        assert_eq!(types, [IPSET_ATTR_IP, IPSET_ATTR_PORT, IPSET_ATTR_PROTO]);
+        assert_eq!(data[0].nested().unwrap()[0].payload(), &[192, 0, 2, 1]);
+        assert_eq!(data[1].payload(), &[1, 187]);
+    }
+
+    /// Destroys set `name` when dropped
+    struct SetGuard(&'static str);
+
+    impl Drop for SetGuard {
+        fn drop(&mut self) {
+            if let Ok(mut socket) = Socket::new(Protocol::Netfilter) {
+                let _ = destroy_set(&mut socket, self.0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_set_entries() {
+        netns::run(|| {
+            let mut socket = Socket::new(Protocol::Netfilter).unwrap();
+            let config = SetConfig::new("nlrs-set", "hash:ip", SetFamily::Inet);
+            match create_set(&mut socket, &config) {
+                Err(ref e) if e.raw_os_error() == Some(EPERM) => return,
+                r => r.unwrap(),
+            }
+            let _guard = SetGuard("nlrs-set");
+
+            let entry = SetEntry::new(IpAddr::V4(Ipv4Addr::new(198, 51, 100, 7)));
+            assert!(!test_entry(&mut socket, "nlrs-set", &entry).unwrap());
+            add_entry(&mut socket, "nlrs-set", &entry).unwrap();
+            assert!(test_entry(&mut socket, "nlrs-set", &entry).unwrap());
+            del_entry(&mut socket, "nlrs-set", &entry).unwrap();
+            assert!(!test_entry(&mut socket, "nlrs-set", &entry).unwrap());
+        });
+    }
+}
@@ -0,0 +1,253 @@
+//! Netfilter netlink (nfnetlink)
+//!
+//! nfnetlink (`Protocol::Netfilter`) multiplexes several subsystems, such as
+//! conntrack, ipset and nftables, over one protocol. The subsystem is the
+//! high byte of the message type and every message starts with a
+//! `struct nfgenmsg`.
+
+pub mod acct;
+pub mod conntrack;
+pub mod ipset;
+pub mod nftables;
+
+use socket::{NetlinkTransport, Msg, NlMsgHeader, Payload};
+
+use std::borrow::Cow;
+use std::io::{self, Cursor};
+
+use byteorder::{BigEndian, ReadBytesExt};
+use libc::AF_UNSPEC;
+
+const NFNL_MSG_BATCH_BEGIN: u16 = 0x10;
+const NFNL_MSG_BATCH_END: u16 = 0x11;
+
+const NFNETLINK_V0: u8 = 0;
+
+/// nfnetlink subsystems (NFNL_SUBSYS_*)
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum Subsystem {
+    Ctnetlink,
+    CtnetlinkExp,
+    Queue,
+    Ulog,
+    Osf,
+    Ipset,
+    Acct,
+    CtnetlinkTimeout,
+    CtHelper,
+    Nftables,
+    NftCompat,
+}
+
+impl From<Subsystem> for u8 {
+    fn from(s: Subsystem) -> u8 {
+        use self::Subsystem::*;
+        match s {
+            Ctnetlink => 1,
+            CtnetlinkExp => 2,
+            Queue => 3,
+            Ulog => 4,
+            Osf => 5,
+            Ipset => 6,
+            Acct => 7,
+            CtnetlinkTimeout => 8,
+            CtHelper => 9,
+            Nftables => 10,
+            NftCompat => 11,
+        }
+    }
+}
+
+/// Builds the header of message `msg` of `subsystem`.
+pub fn nfnl_header(subsystem: Subsystem, msg: u8) -> NlMsgHeader {
+    NlMsgHeader::user_defined((u8::from(subsystem) as u16) << 8 | msg as u16)
+}
+
+// HEADER FORMAT
+// __u8    nfgen_family;   /* AF_xxx */
+// __u8    version;        /* nfnetlink version */
+// __be16  res_id;         /* resource id */
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+struct NfGenMsg {
+    family: u8,
+    version: u8,
+    res_id: u16,
+}
+
+impl NfGenMsg {
+    fn new(family: u8) -> NfGenMsg {
+        NfGenMsg {
+            family,
+            version: NFNETLINK_V0,
+            res_id: 0,
+        }
+    }
+
+    fn from_bytes(bytes: &[u8]) -> io::Result<(NfGenMsg, usize)> {
+        let mut cursor = Cursor::new(bytes);
+        let family = cursor.read_u8()?;
+        let version = cursor.read_u8()?;
+        let res_id = cursor.read_u16::<BigEndian>()?;
+        Ok((NfGenMsg {
+            family,
+            version,
+            res_id,
+        }, cursor.position() as usize))
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![self.family, self.version];
+        bytes.extend_from_slice(&self.res_id.to_be_bytes());
+        bytes
+    }
+}
+
+/// Sends a request made of `hdr` and `payload`, the attributes following the
+/// nfgenmsg header for `family`, and returns the payloads of the replies.
+fn exchange(socket: &mut impl NetlinkTransport, mut hdr: NlMsgHeader, family: u8, payload: &[u8])
+            -> io::Result<Vec<Vec<u8>>> {
+    let mut bytes = NfGenMsg::new(family).bytes();
+    bytes.extend_from_slice(payload);
+    hdr.data_length(bytes.len() as u32);
+    socket.talk(Msg::new(hdr, Payload::Data(Cow::Borrowed(&bytes))))
+}
+
+/// A transaction of nfnetlink messages, framed by batch begin and end
+/// messages
+///
+/// Subsystems such as nftables only accept changes as batches, which the
+/// kernel applies atomically: if one message fails, none take effect.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Batch {
+    subsystem: Subsystem,
+    messages: Vec<(NlMsgHeader, Vec<u8>)>,
+}
+
+impl Batch {
+    pub fn new(subsystem: Subsystem) -> Batch {
+        Batch {
+            subsystem,
+            messages: vec![],
+        }
+    }
+
+    /// Appends a message made of `hdr`, e.g. from `nfnl_header`, and
+    /// `payload`, the attributes following the nfgenmsg header for `family`.
+    pub fn add(&mut self, hdr: NlMsgHeader, family: u8, payload: &[u8]) -> &mut Batch {
+        let mut bytes = NfGenMsg::new(family).bytes();
+        bytes.extend_from_slice(payload);
+        self.messages.push((hdr, bytes));
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    // Frames the messages with sequence numbers 1..=len, each requesting an
+    // acknowledgement. The begin and end messages carry the subsystem as
+    // their resource id.
+    fn frame(&self) -> Vec<(NlMsgHeader, Vec<u8>)> {
+        let control = NfGenMsg {
+            res_id: u8::from(self.subsystem) as u16,
+            ..NfGenMsg::new(AF_UNSPEC as u8)
+        };
+        let mut framed = vec![(NlMsgHeader::user_defined(NFNL_MSG_BATCH_BEGIN), control.bytes())];
+        for (i, &(mut hdr, ref payload)) in self.messages.iter().enumerate() {
+            hdr.seq(i as u32 + 1).ack();
+            framed.push((hdr, payload.clone()));
+        }
+        let mut end = NlMsgHeader::user_defined(NFNL_MSG_BATCH_END);
+        end.seq(self.messages.len() as u32 + 1);
+        framed.push((end, control.bytes()));
+
+        for &mut (ref mut hdr, ref payload) in &mut framed {
+            hdr.data_length(payload.len() as u32);
+        }
+        framed
+    }
+
+    /// Sends the batch in one datagram and waits until the kernel has
+    /// processed every message, returning the first error.
+    pub fn send(&self, socket: &mut impl NetlinkTransport) -> io::Result<()> {
+        let framed = self.frame();
+        let messages = framed.iter()
+            .map(|&(hdr, ref payload)| Msg::new(hdr, Payload::Data(Cow::Borrowed(payload))))
+            .collect();
+        socket.talk_multi(messages)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket::{Socket, string_attr};
+    use Protocol;
+    use netns;
+
+    use libc::{AF_INET, ENOENT, EPERM};
+
+    const NFT_MSG_NEWTABLE: u8 = 0;
+    const NFT_MSG_DELTABLE: u8 = 2;
+    const NFTA_TABLE_NAME: u16 = 1;
+
+    fn table_msg(msg: u8, name: &str) -> (NlMsgHeader, Vec<u8>) {
+        (nfnl_header(Subsystem::Nftables, msg), string_attr(NFTA_TABLE_NAME, name))
+    }
+
+    #[test]
+    fn test_nfgenmsg_roundtrip() {
+        let msg = NfGenMsg { family: 2, version: 0, res_id: 0x0a01 };
+        let bytes = msg.bytes();
+        assert_eq!(bytes, [2, 0, 0x0a, 0x01]);
+        assert_eq!(NfGenMsg::from_bytes(&bytes).unwrap(), (msg, 4));
+    }
+
+    #[test]
+    fn test_batch_framing() {
+        let (hdr, payload) = table_msg(NFT_MSG_NEWTABLE, "t");
+        let mut batch = Batch::new(Subsystem::Nftables);
+        batch.add(hdr, AF_INET as u8, &payload);
+
+        let framed = batch.frame();
+        assert_eq!(framed.len(), 3);
+        assert_eq!(u16::from(framed[0].0.msg_type()), NFNL_MSG_BATCH_BEGIN);
+        assert_eq!(framed[0].1, [AF_UNSPEC as u8, 0, 0, 10]);
+        assert_eq!(u16::from(framed[1].0.msg_type()), 10 << 8);
+        assert_eq!(framed[1].0.sequence(), 1);
+        assert!(framed[1].0.wants_ack());
+        assert_eq!(framed[1].1[..4], [AF_INET as u8, 0, 0, 0]);
+        assert_eq!(u16::from(framed[2].0.msg_type()), NFNL_MSG_BATCH_END);
+        assert_eq!(framed[2].1, framed[0].1);
+    }
+
+    #[test]
+    fn test_batch_send() {
+        netns::run(|| {
+            let mut socket = Socket::new(Protocol::Netfilter).unwrap();
+            let mut batch = Batch::new(Subsystem::Nftables);
+            let (hdr, payload) = table_msg(NFT_MSG_NEWTABLE, "nlrs_batch");
+            batch.add(hdr, AF_INET as u8, &payload);
+            let (hdr, payload) = table_msg(NFT_MSG_DELTABLE, "nlrs_batch");
+            batch.add(hdr, AF_INET as u8, &payload);
+            match batch.send(&mut socket) {
+                Err(ref e) if e.raw_os_error() == Some(EPERM) => return,
+                r => r.unwrap(),
+            }
+
+            // The whole batch is rejected by the failing deletion
+            let mut batch = Batch::new(Subsystem::Nftables);
+            let (hdr, payload) = table_msg(NFT_MSG_DELTABLE, "nlrs_missing");
+            batch.add(hdr, AF_INET as u8, &payload);
+            let err = batch.send(&mut socket).unwrap_err();
+            assert_eq!(err.raw_os_error(), Some(ENOENT));
+        });
+    }
+}
@@ -0,0 +1,411 @@
+//! nftables tables, chains and rules
+//!
+//! Changes have to be sent as a `Batch`; this module only decodes the
+//! ruleset. Integers in nftables attributes are big endian.
+
+use super::{exchange, nfnl_header, NfGenMsg, Subsystem};
+use socket::{NetlinkTransport, NlAttr};
+
+use std::io::{self, Cursor};
+
+use byteorder::{BigEndian, ReadBytesExt};
+use libc::AF_UNSPEC;
+
+const NFT_MSG_GETTABLE: u8 = 1;
+const NFT_MSG_GETCHAIN: u8 = 4;
+const NFT_MSG_GETRULE: u8 = 7;
+
+const NFTA_TABLE_NAME: u16 = 1;
+const NFTA_TABLE_FLAGS: u16 = 2;
+const NFTA_TABLE_USE: u16 = 3;
+const NFTA_TABLE_HANDLE: u16 = 4;
+
+const NFTA_CHAIN_TABLE: u16 = 1;
+const NFTA_CHAIN_HANDLE: u16 = 2;
+const NFTA_CHAIN_NAME: u16 = 3;
+const NFTA_CHAIN_HOOK: u16 = 4;
+const NFTA_CHAIN_POLICY: u16 = 5;
+const NFTA_CHAIN_USE: u16 = 6;
+const NFTA_CHAIN_TYPE: u16 = 7;
+
+const NFTA_HOOK_HOOKNUM: u16 = 1;
+const NFTA_HOOK_PRIORITY: u16 = 2;
+
+const NFTA_RULE_TABLE: u16 = 1;
+const NFTA_RULE_CHAIN: u16 = 2;
+const NFTA_RULE_HANDLE: u16 = 3;
+const NFTA_RULE_EXPRESSIONS: u16 = 4;
+
+const NFTA_LIST_ELEM: u16 = 1;
+
+const NFTA_EXPR_NAME: u16 = 1;
+const NFTA_EXPR_DATA: u16 = 2;
+
+/// An nftables table
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Table {
+    family: u8,
+    name: String,
+    flags: u32,
+    use_count: u32,
+    handle: u64,
+}
+
+impl Table {
+    fn from_bytes(bytes: &[u8]) -> io::Result<Table> {
+        let (nfgen, n) = NfGenMsg::from_bytes(bytes)?;
+        let mut table = Table {
+            family: nfgen.family,
+            name: String::new(),
+            flags: 0,
+            use_count: 0,
+            handle: 0,
+        };
+        for attr in NlAttr::parse(&bytes[n..])? {
+            let mut cursor = Cursor::new(attr.payload());
+            match attr.attr_type() {
+                NFTA_TABLE_NAME => table.name = attr.get_str()?.to_string(),
+                NFTA_TABLE_FLAGS => table.flags = cursor.read_u32::<BigEndian>()?,
+                NFTA_TABLE_USE => table.use_count = cursor.read_u32::<BigEndian>()?,
+                NFTA_TABLE_HANDLE => table.handle = cursor.read_u64::<BigEndian>()?,
+                _ => {},
+            }
+        }
+        Ok(table)
+    }
+
+    /// Protocol family (NFPROTO_*), e.g. 1 for inet or 2 for ip
+    pub fn family(&self) -> u8 {
+        self.family
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// NFT_TABLE_F_* flags, e.g. dormant
+    pub fn flags(&self) -> u32 {
+        self.flags
+    }
+
+    /// Number of chains, sets and other objects in the table
+    pub fn use_count(&self) -> u32 {
+        self.use_count
+    }
+
+    pub fn handle(&self) -> u64 {
+        self.handle
+    }
+}
+
+/// An nftables chain
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Chain {
+    family: u8,
+    table: String,
+    name: String,
+    handle: u64,
+    hook: Option<(u32, i32)>,
+    policy: Option<u32>,
+    chain_type: Option<String>,
+    use_count: u32,
+}
+
+impl Chain {
+    fn from_bytes(bytes: &[u8]) -> io::Result<Chain> {
+        let (nfgen, n) = NfGenMsg::from_bytes(bytes)?;
+        let mut chain = Chain {
+            family: nfgen.family,
+            table: String::new(),
+            name: String::new(),
+            handle: 0,
+            hook: None,
+            policy: None,
+            chain_type: None,
+            use_count: 0,
+        };
+        for attr in NlAttr::parse(&bytes[n..])? {
+            let mut cursor = Cursor::new(attr.payload());
+            match attr.attr_type() {
+                NFTA_CHAIN_TABLE => chain.table = attr.get_str()?.to_string(),
+                NFTA_CHAIN_NAME => chain.name = attr.get_str()?.to_string(),
+                NFTA_CHAIN_HANDLE => chain.handle = cursor.read_u64::<BigEndian>()?,
+                NFTA_CHAIN_POLICY => chain.policy = Some(cursor.read_u32::<BigEndian>()?),
+                NFTA_CHAIN_TYPE => chain.chain_type = Some(attr.get_str()?.to_string()),
+                NFTA_CHAIN_USE => chain.use_count = cursor.read_u32::<BigEndian>()?,
+                NFTA_CHAIN_HOOK => {
+                    let (mut hooknum, mut priority) = (0, 0);
+                    for hook in attr.nested()? {
+                        let mut cursor = Cursor::new(hook.payload());
+                        match hook.attr_type() {
+                            NFTA_HOOK_HOOKNUM => hooknum = cursor.read_u32::<BigEndian>()?,
+                            NFTA_HOOK_PRIORITY => priority = cursor.read_i32::<BigEndian>()?,
+                            _ => {},
+                        }
+                    }
+                    chain.hook = Some((hooknum, priority));
+                },
+                _ => {},
+            }
+        }
+        Ok(chain)
+    }
+
+    pub fn family(&self) -> u8 {
+        self.family
+    }
+
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn handle(&self) -> u64 {
+        self.handle
+    }
+
+    /// Hook number (NF_INET_*) and priority of a base chain, `None` for a
+    /// regular chain that is only reached by jumps
+    pub fn hook(&self) -> Option<(u32, i32)> {
+        self.hook
+    }
+
+    /// Verdict for packets reaching the end of a base chain, e.g. NF_ACCEPT
+    pub fn policy(&self) -> Option<u32> {
+        self.policy
+    }
+
+    /// Type of a base chain, e.g. "filter" or "nat"
+    pub fn chain_type(&self) -> Option<&str> {
+        self.chain_type.as_deref()
+    }
+
+    /// Number of rules in the chain and jumps to it
+    pub fn use_count(&self) -> u32 {
+        self.use_count
+    }
+}
+
+/// An expression of a rule, such as "payload", "cmp" or "counter"
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Expr {
+    name: String,
+    data: Vec<u8>,
+}
+
+impl Expr {
+    fn from_attr(attr: &NlAttr) -> io::Result<Expr> {
+        let mut expr = Expr {
+            name: String::new(),
+            data: vec![],
+        };
+        for a in attr.nested()? {
+            match a.attr_type() {
+                NFTA_EXPR_NAME => expr.name = a.get_str()?.to_string(),
+                NFTA_EXPR_DATA => expr.data = a.payload().to_vec(),
+                _ => {},
+            }
+        }
+        Ok(expr)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Raw NFTA_EXPR_DATA payload, specific to the expression
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Attributes of the expression, e.g. NFTA_CMP_OP for "cmp"
+    pub fn attrs(&self) -> io::Result<Vec<NlAttr<'_>>> {
+        NlAttr::parse(&self.data)
+    }
+}
+
+/// An nftables rule
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Rule {
+    family: u8,
+    table: String,
+    chain: String,
+    handle: u64,
+    exprs: Vec<Expr>,
+}
+
+impl Rule {
+    fn from_bytes(bytes: &[u8]) -> io::Result<Rule> {
+        let (nfgen, n) = NfGenMsg::from_bytes(bytes)?;
+        let mut rule = Rule {
+            family: nfgen.family,
+            table: String::new(),
+            chain: String::new(),
+            handle: 0,
+            exprs: vec![],
+        };
+        for attr in NlAttr::parse(&bytes[n..])? {
+            match attr.attr_type() {
+                NFTA_RULE_TABLE => rule.table = attr.get_str()?.to_string(),
+                NFTA_RULE_CHAIN => rule.chain = attr.get_str()?.to_string(),
+                NFTA_RULE_HANDLE => {
+                    rule.handle = Cursor::new(attr.payload()).read_u64::<BigEndian>()?
+                },
+                NFTA_RULE_EXPRESSIONS => {
+                    for elem in attr.nested()? {
+                        if elem.attr_type() == NFTA_LIST_ELEM {
+                            rule.exprs.push(Expr::from_attr(&elem)?);
+                        }
+                    }
+                },
+                _ => {},
+            }
+        }
+        Ok(rule)
+    }
+
+    pub fn family(&self) -> u8 {
+        self.family
+    }
+
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+
+    pub fn chain(&self) -> &str {
+        &self.chain
+    }
+
+    pub fn handle(&self) -> u64 {
+        self.handle
+    }
+
+    /// Expressions in evaluation order
+    pub fn exprs(&self) -> &[Expr] {
+        &self.exprs
+    }
+}
+
+// Dumps `msg` across all families
+fn dump(socket: &mut impl NetlinkTransport, msg: u8) -> io::Result<Vec<Vec<u8>>> {
+    let mut hdr = nfnl_header(Subsystem::Nftables, msg);
+    hdr.dump();
+    exchange(socket, hdr, AF_UNSPEC as u8, &[])
+}
+
+/// Lists the tables of all families.
+pub fn tables(socket: &mut impl NetlinkTransport) -> io::Result<Vec<Table>> {
+    dump(socket, NFT_MSG_GETTABLE)?.iter().map(|r| Table::from_bytes(r)).collect()
+}
+
+/// Lists the chains of all tables.
+pub fn chains(socket: &mut impl NetlinkTransport) -> io::Result<Vec<Chain>> {
+    dump(socket, NFT_MSG_GETCHAIN)?.iter().map(|r| Chain::from_bytes(r)).collect()
+}
+
+/// Lists the rules of all chains.
+pub fn rules(socket: &mut impl NetlinkTransport) -> io::Result<Vec<Rule>> {
+    dump(socket, NFT_MSG_GETRULE)?.iter().map(|r| Rule::from_bytes(r)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{Batch, nfnl_header, Subsystem};
+    use socket::{Socket, NlAttr, NLA_F_NESTED, string_attr};
+    use Protocol;
+    use netns;
+
+    use libc::EPERM;
+
+    const NFT_MSG_NEWTABLE: u8 = 0;
+    const NFT_MSG_DELTABLE: u8 = 2;
+    const NFT_MSG_NEWCHAIN: u8 = 3;
+    const NFT_MSG_NEWRULE: u8 = 6;
+    const NFPROTO_INET: u8 = 1;
+
+    #[test]
+    fn test_rule_exprs() {
+        let mut expr = string_attr(NFTA_EXPR_NAME, "cmp");
+        expr.extend(NlAttr::new(NFTA_EXPR_DATA | NLA_F_NESTED,
+                                &NlAttr::new(1, &[0, 0, 0, 1]).bytes()).bytes());
+        let list = NlAttr::new(NFTA_LIST_ELEM | NLA_F_NESTED, &expr).bytes();
+
+        let mut bytes = vec![NFPROTO_INET, 0, 0, 0];
+        bytes.extend(string_attr(NFTA_RULE_TABLE, "t"));
+        bytes.extend(string_attr(NFTA_RULE_CHAIN, "c"));
+        bytes.extend(NlAttr::new(NFTA_RULE_HANDLE, &4u64.to_be_bytes()).bytes());
+        bytes.extend(NlAttr::new(NFTA_RULE_EXPRESSIONS | NLA_F_NESTED, &list).bytes());
+
+        let rule = Rule::from_bytes(&bytes).unwrap();
+        assert_eq!(rule.family(), NFPROTO_INET);
+        assert_eq!((rule.table(), rule.chain(), rule.handle()), ("t", "c", 4));
+        assert_eq!(rule.exprs().len(), 1);
+        assert_eq!(rule.exprs()[0].name(), "cmp");
+        let attrs = rule.exprs()[0].attrs().unwrap();
+        assert_eq!(attrs[0].payload(), &[0, 0, 0, 1]);
+    }
+
+    /// Deletes inet table `name` when dropped
+    struct TableGuard(&'static str);
+
+    impl Drop for TableGuard {
+        fn drop(&mut self) {
+            if let Ok(mut socket) = Socket::new(Protocol::Netfilter) {
+                let mut batch = Batch::new(Subsystem::Nftables);
+                let table = string_attr(NFTA_TABLE_NAME, self.0);
+                batch.add(nfnl_header(Subsystem::Nftables, NFT_MSG_DELTABLE), NFPROTO_INET, &table);
+                let _ = batch.send(&mut socket);
+            }
+        }
+    }
+
+    #[test]
+    fn test_dump_ruleset() {
+        netns::run(|| {
+            let table = string_attr(NFTA_TABLE_NAME, "nlrs_dump");
+            let mut chain = string_attr(NFTA_CHAIN_TABLE, "nlrs_dump");
+            chain.extend(string_attr(NFTA_CHAIN_NAME, "c"));
+            let counter = NlAttr::new(NFTA_LIST_ELEM | NLA_F_NESTED,
+                                      &string_attr(NFTA_EXPR_NAME, "counter")).bytes();
+            let mut rule = string_attr(NFTA_RULE_TABLE, "nlrs_dump");
+            rule.extend(string_attr(NFTA_RULE_CHAIN, "c"));
+            rule.extend(NlAttr::new(NFTA_RULE_EXPRESSIONS | NLA_F_NESTED, &counter).bytes());
+
+            // Rules without a handle must be created explicitly
+            let mut new_rule = nfnl_header(Subsystem::Nftables, NFT_MSG_NEWRULE);
+            new_rule.create().append();
+
+            let mut socket = Socket::new(Protocol::Netfilter).unwrap();
+            let mut batch = Batch::new(Subsystem::Nftables);
+            batch.add(nfnl_header(Subsystem::Nftables, NFT_MSG_NEWTABLE), NFPROTO_INET, &table)
+                .add(nfnl_header(Subsystem::Nftables, NFT_MSG_NEWCHAIN), NFPROTO_INET, &chain)
+                .add(new_rule, NFPROTO_INET, &rule);
+            match batch.send(&mut socket) {
+                Err(ref e) if e.raw_os_error() == Some(EPERM) => return,
+                r => r.unwrap(),
+            }
+            let _guard = TableGuard("nlrs_dump");
+
+            let t = tables(&mut socket).unwrap().into_iter().find(|t| t.name() == "nlrs_dump").unwrap();
+            assert_eq!(t.family(), NFPROTO_INET);
+            assert_eq!(t.use_count(), 1);
+
+            let c = chains(&mut socket).unwrap().into_iter().find(|c| c.table() == "nlrs_dump").unwrap();
+            assert_eq!(c.name(), "c");
+            assert_eq!(c.hook(), None);
+
+            let r = rules(&mut socket).unwrap().into_iter().find(|r| r.table() == "nlrs_dump").unwrap();
+            assert_eq!(r.chain(), "c");
+            assert_eq!(r.exprs().iter().map(|e| e.name()).collect::<Vec<_>>(), ["counter"]);
+            assert!(!r.exprs()[0].attrs().unwrap().is_empty());
+        });
+    }
+}
@@ -0,0 +1,29 @@
+//! Private network namespaces for tests that create links, routes, qdiscs
+//! or firewall rules
+
+use std::io;
+use std::panic;
+use std::thread;
+
+use libc::{unshare, CLONE_NEWNET, EPERM};
+
+/// Runs `test` on a thread of its own after moving that thread into a new
+/// network namespace, so nothing it creates is visible on the host and all
+/// of it disappears when the thread exits. Without CAP_SYS_ADMIN the test is
+/// skipped.
+pub fn run<F: FnOnce() + Send + 'static>(test: F) {
+    let result = thread::spawn(move || {
+        // CLONE_NEWNET only moves the calling thread
+        if unsafe { unshare(CLONE_NEWNET) } != 0 {
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() == Some(EPERM) {
+                return;
+            }
+            panic!("unshare(CLONE_NEWNET): {}", err);
+        }
+        test()
+    }).join();
+    if let Err(payload) = result {
+        panic::resume_unwind(payload);
+    }
+}
@@ -0,0 +1,391 @@
+//! RDMA netlink (nldev)
+//!
+//! `Protocol::Rdma` multiplexes clients in the message type, the client in
+//! the bits above the operation. The nldev client reports RDMA devices,
+//! their ports and the resources allocated on them, as `rdma dev`, `rdma
+//! link` and `rdma resource` show, with attributes only.
+
+use socket::{NetlinkTransport, NlMsgHeader, NlAttr, Msg, Payload};
+
+use std::borrow::Cow;
+use std::io;
+
+const RDMA_NL_NLDEV: u16 = 5;
+
+const RDMA_NLDEV_CMD_GET: u16 = 1;
+const RDMA_NLDEV_CMD_PORT_GET: u16 = 5;
+const RDMA_NLDEV_CMD_RES_GET: u16 = 9;
+
+const RDMA_NLDEV_ATTR_DEV_INDEX: u16 = 1;
+const RDMA_NLDEV_ATTR_DEV_NAME: u16 = 2;
+const RDMA_NLDEV_ATTR_PORT_INDEX: u16 = 3;
+const RDMA_NLDEV_ATTR_CAP_FLAGS: u16 = 4;
+const RDMA_NLDEV_ATTR_FW_VERSION: u16 = 5;
+const RDMA_NLDEV_ATTR_NODE_GUID: u16 = 6;
+const RDMA_NLDEV_ATTR_SYS_IMAGE_GUID: u16 = 7;
+const RDMA_NLDEV_ATTR_SUBNET_PREFIX: u16 = 8;
+const RDMA_NLDEV_ATTR_LID: u16 = 9;
+const RDMA_NLDEV_ATTR_SM_LID: u16 = 10;
+const RDMA_NLDEV_ATTR_LMC: u16 = 11;
+const RDMA_NLDEV_ATTR_PORT_STATE: u16 = 12;
+const RDMA_NLDEV_ATTR_PORT_PHYS_STATE: u16 = 13;
+const RDMA_NLDEV_ATTR_DEV_NODE_TYPE: u16 = 14;
+const RDMA_NLDEV_ATTR_RES_SUMMARY: u16 = 15;
+const RDMA_NLDEV_ATTR_RES_SUMMARY_ENTRY: u16 = 16;
+const RDMA_NLDEV_ATTR_RES_SUMMARY_ENTRY_NAME: u16 = 17;
+const RDMA_NLDEV_ATTR_RES_SUMMARY_ENTRY_CURR: u16 = 18;
+
+/// Type of message `op` of the nldev client (RDMA_NL_GET_TYPE)
+fn nldev_type(op: u16) -> u16 {
+    RDMA_NL_NLDEV << 10 | op
+}
+
+/// Logical state of a port (IB_PORT_*)
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum PortState {
+    Nop,
+    Down,
+    Init,
+    Armed,
+    Active,
+    ActiveDefer,
+    Other(u8),
+}
+
+impl From<u8> for PortState {
+    fn from(s: u8) -> PortState {
+        use self::PortState::*;
+        match s {
+            0 => Nop,
+            1 => Down,
+            2 => Init,
+            3 => Armed,
+            4 => Active,
+            5 => ActiveDefer,
+            i => Other(i),
+        }
+    }
+}
+
+/// An RDMA device
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct RdmaDevice {
+    index: u32,
+    name: String,
+    fw_version: Option<String>,
+    node_guid: Option<u64>,
+    sys_image_guid: Option<u64>,
+    node_type: Option<u8>,
+    cap_flags: u64,
+    ports: Option<u32>,
+}
+
+impl RdmaDevice {
+    fn from_attrs(bytes: &[u8]) -> io::Result<RdmaDevice> {
+        let mut dev = RdmaDevice::default();
+        for attr in NlAttr::parse(bytes)? {
+            match attr.attr_type() {
+                RDMA_NLDEV_ATTR_DEV_INDEX => dev.index = attr.get_u32()?,
+                RDMA_NLDEV_ATTR_DEV_NAME => dev.name = attr.get_str()?.to_string(),
+                RDMA_NLDEV_ATTR_PORT_INDEX => dev.ports = Some(attr.get_u32()?),
+                RDMA_NLDEV_ATTR_CAP_FLAGS => dev.cap_flags = attr.get_u64()?,
+                RDMA_NLDEV_ATTR_FW_VERSION => dev.fw_version = Some(attr.get_str()?.to_string()),
+                RDMA_NLDEV_ATTR_NODE_GUID => dev.node_guid = Some(attr.get_u64()?),
+                RDMA_NLDEV_ATTR_SYS_IMAGE_GUID => dev.sys_image_guid = Some(attr.get_u64()?),
+                RDMA_NLDEV_ATTR_DEV_NODE_TYPE => dev.node_type = Some(attr.get_u8()?),
+                _ => {},
+            }
+        }
+        Ok(dev)
+    }
+
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// Device name, e.g. "mlx5_0" or "rxe0"
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn fw_version(&self) -> Option<&str> {
+        self.fw_version.as_deref()
+    }
+
+    pub fn node_guid(&self) -> Option<u64> {
+        self.node_guid
+    }
+
+    pub fn sys_image_guid(&self) -> Option<u64> {
+        self.sys_image_guid
+    }
+
+    /// RDMA_NODE_* type, 1 for an InfiniBand channel adapter
+    pub fn node_type(&self) -> Option<u8> {
+        self.node_type
+    }
+
+    /// IB_DEVICE_* capabilities
+    pub fn cap_flags(&self) -> u64 {
+        self.cap_flags
+    }
+
+    /// Number of ports, numbered from 1
+    pub fn ports(&self) -> Option<u32> {
+        self.ports
+    }
+}
+
+/// A port of an RDMA device
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct RdmaPort {
+    dev_index: u32,
+    dev_name: String,
+    index: u32,
+    state: PortState,
+    phys_state: Option<u8>,
+    lid: Option<u32>,
+    sm_lid: Option<u32>,
+    lmc: Option<u8>,
+    subnet_prefix: Option<u64>,
+    cap_flags: u64,
+}
+
+impl RdmaPort {
+    fn from_attrs(bytes: &[u8]) -> io::Result<RdmaPort> {
+        let mut port = RdmaPort {
+            dev_index: 0,
+            dev_name: String::new(),
+            index: 0,
+            state: PortState::Nop,
+            phys_state: None,
+            lid: None,
+            sm_lid: None,
+            lmc: None,
+            subnet_prefix: None,
+            cap_flags: 0,
+        };
+        for attr in NlAttr::parse(bytes)? {
+            match attr.attr_type() {
+                RDMA_NLDEV_ATTR_DEV_INDEX => port.dev_index = attr.get_u32()?,
+                RDMA_NLDEV_ATTR_DEV_NAME => port.dev_name = attr.get_str()?.to_string(),
+                RDMA_NLDEV_ATTR_PORT_INDEX => port.index = attr.get_u32()?,
+                RDMA_NLDEV_ATTR_PORT_STATE => port.state = attr.get_u8()?.into(),
+                RDMA_NLDEV_ATTR_PORT_PHYS_STATE => port.phys_state = Some(attr.get_u8()?),
+                RDMA_NLDEV_ATTR_LID => port.lid = Some(attr.get_u32()?),
+                RDMA_NLDEV_ATTR_SM_LID => port.sm_lid = Some(attr.get_u32()?),
+                RDMA_NLDEV_ATTR_LMC => port.lmc = Some(attr.get_u8()?),
+                RDMA_NLDEV_ATTR_SUBNET_PREFIX => port.subnet_prefix = Some(attr.get_u64()?),
+                RDMA_NLDEV_ATTR_CAP_FLAGS => port.cap_flags = attr.get_u64()?,
+                _ => {},
+            }
+        }
+        Ok(port)
+    }
+
+    pub fn dev_index(&self) -> u32 {
+        self.dev_index
+    }
+
+    pub fn dev_name(&self) -> &str {
+        &self.dev_name
+    }
+
+    /// Port number, from 1
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    pub fn state(&self) -> PortState {
+        self.state
+    }
+
+    /// IB_PORT_PHYS_STATE_* state of the link, 5 when up
+    pub fn phys_state(&self) -> Option<u8> {
+        self.phys_state
+    }
+
+    /// Local identifier, InfiniBand only
+    pub fn lid(&self) -> Option<u32> {
+        self.lid
+    }
+
+    /// LID of the subnet manager, InfiniBand only
+    pub fn sm_lid(&self) -> Option<u32> {
+        self.sm_lid
+    }
+
+    /// LID mask control, InfiniBand only
+    pub fn lmc(&self) -> Option<u8> {
+        self.lmc
+    }
+
+    pub fn subnet_prefix(&self) -> Option<u64> {
+        self.subnet_prefix
+    }
+
+    /// IB_PORT_* capabilities
+    pub fn cap_flags(&self) -> u64 {
+        self.cap_flags
+    }
+}
+
+/// Resources allocated on a device, by type
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct ResourceSummary {
+    dev_index: u32,
+    dev_name: String,
+    counts: Vec<(String, u64)>,
+}
+
+impl ResourceSummary {
+    fn from_attrs(bytes: &[u8]) -> io::Result<ResourceSummary> {
+        let mut summary = ResourceSummary { dev_index: 0, dev_name: String::new(), counts: vec![] };
+        for attr in NlAttr::parse(bytes)? {
+            match attr.attr_type() {
+                RDMA_NLDEV_ATTR_DEV_INDEX => {
+                    summary.dev_index = attr.get_u32()?
+                },
+                RDMA_NLDEV_ATTR_DEV_NAME => summary.dev_name = attr.get_str()?.to_string(),
+                RDMA_NLDEV_ATTR_RES_SUMMARY => {
+                    for entry in attr.nested()? {
+                        if entry.attr_type() != RDMA_NLDEV_ATTR_RES_SUMMARY_ENTRY {
+                            continue;
+                        }
+                        let mut name = None;
+                        let mut curr = None;
+                        for a in entry.nested()? {
+                            match a.attr_type() {
+                                RDMA_NLDEV_ATTR_RES_SUMMARY_ENTRY_NAME => name = Some(a.get_str()?.to_string()),
+                                RDMA_NLDEV_ATTR_RES_SUMMARY_ENTRY_CURR => {
+                                    curr = Some(a.get_u64()?)
+                                },
+                                _ => {},
+                            }
+                        }
+                        if let (Some(name), Some(curr)) = (name, curr) {
+                            summary.counts.push((name, curr));
+                        }
+                    }
+                },
+                _ => {},
+            }
+        }
+        Ok(summary)
+    }
+
+    pub fn dev_index(&self) -> u32 {
+        self.dev_index
+    }
+
+    pub fn dev_name(&self) -> &str {
+        &self.dev_name
+    }
+
+    /// Number of resources per type, as named by the kernel: "pd", "cq",
+    /// "qp", "cm_id", "mr", "ctx", "srq"
+    pub fn counts(&self) -> &[(String, u64)] {
+        &self.counts
+    }
+
+    /// Number of resources of type `name`
+    pub fn get(&self, name: &str) -> Option<u64> {
+        self.counts.iter().find(|c| c.0 == name).map(|c| c.1)
+    }
+}
+
+fn dump(socket: &mut impl NetlinkTransport, op: u16) -> io::Result<Vec<Vec<u8>>> {
+    let mut hdr = NlMsgHeader::user_defined(nldev_type(op));
+    hdr.dump();
+    socket.talk(Msg::new(hdr, Payload::Data(Cow::Borrowed(&[]))))
+}
+
+/// Lists the RDMA devices (RDMA_NLDEV_CMD_GET dump).
+pub fn devices(socket: &mut impl NetlinkTransport) -> io::Result<Vec<RdmaDevice>> {
+    let replies = dump(socket, RDMA_NLDEV_CMD_GET)?;
+    replies.iter().map(|r| RdmaDevice::from_attrs(r)).collect()
+}
+
+/// Lists the ports of every device (RDMA_NLDEV_CMD_PORT_GET dump).
+pub fn ports(socket: &mut impl NetlinkTransport) -> io::Result<Vec<RdmaPort>> {
+    let replies = dump(socket, RDMA_NLDEV_CMD_PORT_GET)?;
+    replies.iter().map(|r| RdmaPort::from_attrs(r)).collect()
+}
+
+/// Counts the resources of every device (RDMA_NLDEV_CMD_RES_GET dump).
+pub fn resources(socket: &mut impl NetlinkTransport) -> io::Result<Vec<ResourceSummary>> {
+    let replies = dump(socket, RDMA_NLDEV_CMD_RES_GET)?;
+    replies.iter().map(|r| ResourceSummary::from_attrs(r)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket::{Socket, ReplayTransport, NLA_F_NESTED};
+    use Protocol;
+
+    fn entry(name: &[u8], curr: u64) -> Vec<u8> {
+        let mut bytes = NlAttr::new(RDMA_NLDEV_ATTR_RES_SUMMARY_ENTRY_NAME, name).bytes();
+        bytes.extend(NlAttr::new(RDMA_NLDEV_ATTR_RES_SUMMARY_ENTRY_CURR, &curr.to_ne_bytes()).bytes());
+        NlAttr::new(RDMA_NLDEV_ATTR_RES_SUMMARY_ENTRY, &bytes).bytes()
+    }
+
+    #[test]
+    fn test_decode() {
+        let mut bytes = NlAttr::new(RDMA_NLDEV_ATTR_DEV_INDEX, &1u32.to_ne_bytes()).bytes();
+        bytes.extend(NlAttr::new(RDMA_NLDEV_ATTR_DEV_NAME, b"rxe0\0").bytes());
+        bytes.extend(NlAttr::new(RDMA_NLDEV_ATTR_PORT_INDEX, &1u32.to_ne_bytes()).bytes());
+        bytes.extend(NlAttr::new(RDMA_NLDEV_ATTR_NODE_GUID, &0x5054_00ffu64.to_ne_bytes()).bytes());
+        bytes.extend(NlAttr::new(RDMA_NLDEV_ATTR_DEV_NODE_TYPE, &[1]).bytes());
+        let dev = RdmaDevice::from_attrs(&bytes).unwrap();
+        assert_eq!(dev.index(), 1);
+        assert_eq!(dev.name(), "rxe0");
+        assert_eq!(dev.ports(), Some(1));
+        assert_eq!(dev.node_guid(), Some(0x5054_00ff));
+        assert_eq!(dev.node_type(), Some(1));
+        assert_eq!(dev.fw_version(), None);
+
+        let mut port = bytes.clone();
+        port.extend(NlAttr::new(RDMA_NLDEV_ATTR_PORT_STATE, &[4]).bytes());
+        port.extend(NlAttr::new(RDMA_NLDEV_ATTR_PORT_PHYS_STATE, &[5]).bytes());
+        let port = RdmaPort::from_attrs(&port).unwrap();
+        assert_eq!((port.dev_name(), port.index()), ("rxe0", 1));
+        assert_eq!(port.state(), PortState::Active);
+        assert_eq!(port.phys_state(), Some(5));
+        assert_eq!(port.lid(), None);
+
+        let mut entries = entry(b"pd\0", 2);
+        entries.extend(entry(b"qp\0", 7));
+        bytes.extend(NlAttr::new(RDMA_NLDEV_ATTR_RES_SUMMARY | NLA_F_NESTED, &entries).bytes());
+        let summary = ResourceSummary::from_attrs(&bytes).unwrap();
+        assert_eq!(summary.counts().len(), 2);
+        assert_eq!(summary.get("qp"), Some(7));
+        assert_eq!(summary.get("mr"), None);
+    }
+
+    #[test]
+    fn test_replay_devices() {
+        let done = NlMsgHeader::done().bytes().to_vec();
+        let mut replay = ReplayTransport::new(vec![done]);
+        assert!(devices(&mut replay).unwrap().is_empty());
+        let sent = &replay.sent()[0];
+        assert_eq!(&sent[4..6], &(5u16 << 10 | 1).to_ne_bytes());
+    }
+
+    #[test]
+    fn test_devices() {
+        let mut socket = match Socket::new(Protocol::Rdma) {
+            Ok(s) => s,
+            Err(ref e) if e.raw_os_error() == Some(::libc::EPROTONOSUPPORT) => return,
+            Err(e) => panic!("{}", e),
+        };
+        let devs = devices(&mut socket).unwrap();
+        let ports = ports(&mut socket).unwrap();
+        assert!(ports.iter().all(|p| devs.iter().any(|d| d.index() == p.dev_index())));
+        assert_eq!(resources(&mut socket).unwrap().len(), devs.len());
+    }
+}
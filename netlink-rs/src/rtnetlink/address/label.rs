@@ -0,0 +1,194 @@
+use super::super::{exchange, RTM_NEWADDRLABEL, RTM_DELADDRLABEL, RTM_GETADDRLABEL};
+use socket::{NetlinkTransport, NlMsgHeader, NlAttr};
+
+use std::io::{self, Cursor, ErrorKind};
+use std::net::Ipv6Addr;
+
+use byteorder::{NativeEndian, ReadBytesExt};
+use libc::AF_INET6;
+
+const IFAL_ADDRESS: u16 = 1;
+const IFAL_LABEL: u16 = 2;
+
+// HEADER FORMAT
+// __u8    ifal_family;
+// __u8    __ifal_reserved;
+// __u8    ifal_prefixlen;     /* Prefix length */
+// __u8    ifal_flags;         /* Flags */
+// __u32   ifal_index;         /* Link index */
+// __u32   ifal_seq;           /* sequence number */
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+struct IfAddrLblMsg {
+    prefix_len: u8,
+    index: u32,
+    seq: u32,
+}
+
+impl IfAddrLblMsg {
+    fn from_bytes(bytes: &[u8]) -> io::Result<(IfAddrLblMsg, usize)> {
+        let mut cursor = Cursor::new(bytes);
+        let _family = cursor.read_u8()?;
+        let _reserved = cursor.read_u8()?;
+        let prefix_len = cursor.read_u8()?;
+        let _flags = cursor.read_u8()?;
+        let index = cursor.read_u32::<NativeEndian>()?;
+        let seq = cursor.read_u32::<NativeEndian>()?;
+        Ok((IfAddrLblMsg {
+            prefix_len,
+            index,
+            seq,
+        }, cursor.position() as usize))
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![AF_INET6 as u8, 0, self.prefix_len, 0];
+        bytes.extend_from_slice(&self.index.to_ne_bytes());
+        bytes.extend_from_slice(&self.seq.to_ne_bytes());
+        bytes
+    }
+}
+
+/// An entry of the IPv6 address selection policy table (RFC 6724,
+/// `ip addrlabel`)
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct AddrLabel {
+    prefix: Ipv6Addr,
+    prefix_len: u8,
+    label: u32,
+    ifindex: u32,
+}
+
+impl AddrLabel {
+    /// Assigns `label` to addresses in `prefix/prefix_len`.
+    pub fn new(prefix: Ipv6Addr, prefix_len: u8, label: u32) -> AddrLabel {
+        AddrLabel {
+            prefix,
+            prefix_len,
+            label,
+            ifindex: 0,
+        }
+    }
+
+    fn from_bytes(bytes: &[u8]) -> io::Result<AddrLabel> {
+        let (ifal, n) = IfAddrLblMsg::from_bytes(bytes)?;
+        let mut prefix = None;
+        let mut label = 0;
+        for attr in NlAttr::parse(&bytes[n..])? {
+            let p = attr.payload();
+            match attr.attr_type() {
+                IFAL_ADDRESS if p.len() == 16 => {
+                    let mut octets = [0u8; 16];
+                    octets.copy_from_slice(p);
+                    prefix = Some(Ipv6Addr::from(octets));
+                },
+                IFAL_LABEL => label = attr.get_u32()?,
+                _ => {},
+            }
+        }
+        let prefix = prefix.ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "no IFAL_ADDRESS"))?;
+        Ok(AddrLabel {
+            prefix,
+            prefix_len: ifal.prefix_len,
+            label,
+            ifindex: ifal.index,
+        })
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        let ifal = IfAddrLblMsg { prefix_len: self.prefix_len, index: self.ifindex, seq: 0 };
+        let mut bytes = ifal.bytes();
+        bytes.extend(NlAttr::new(IFAL_ADDRESS, &self.prefix.octets()).bytes());
+        bytes.extend(NlAttr::new(IFAL_LABEL, &self.label.to_ne_bytes()).bytes());
+        bytes
+    }
+
+    /// Restricts the entry to link `ifindex`; entries apply to all links by
+    /// default.
+    pub fn set_ifindex(&mut self, ifindex: i32) -> &mut AddrLabel {
+        self.ifindex = ifindex as u32;
+        self
+    }
+
+    pub fn prefix(&self) -> Ipv6Addr {
+        self.prefix
+    }
+
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+
+    pub fn label(&self) -> u32 {
+        self.label
+    }
+
+    /// Link the entry is restricted to, 0 for all links
+    pub fn ifindex(&self) -> i32 {
+        self.ifindex as i32
+    }
+}
+
+/// Dumps the policy table, including the kernel's default entries.
+pub fn addr_labels(socket: &mut impl NetlinkTransport) -> io::Result<Vec<AddrLabel>> {
+    let mut hdr = NlMsgHeader::user_defined(RTM_GETADDRLABEL);
+    hdr.dump();
+    let replies = exchange(socket, hdr, &IfAddrLblMsg::default().bytes())?;
+    replies.iter().map(|r| AddrLabel::from_bytes(r)).collect()
+}
+
+/// Adds `label`, failing with `EEXIST` if the table has an entry for the
+/// same prefix and link.
+pub fn add_addr_label(socket: &mut impl NetlinkTransport, label: &AddrLabel) -> io::Result<()> {
+    let mut hdr = NlMsgHeader::user_defined(RTM_NEWADDRLABEL);
+    hdr.create().excl();
+    exchange(socket, hdr, &label.bytes())?;
+    Ok(())
+}
+
+/// Adds `label`, replacing the label of an existing entry for the same
+/// prefix and link.
+pub fn replace_addr_label(socket: &mut impl NetlinkTransport, label: &AddrLabel) -> io::Result<()> {
+    let mut hdr = NlMsgHeader::user_defined(RTM_NEWADDRLABEL);
+    hdr.create().replace();
+    exchange(socket, hdr, &label.bytes())?;
+    Ok(())
+}
+
+pub fn del_addr_label(socket: &mut impl NetlinkTransport, label: &AddrLabel) -> io::Result<()> {
+    let hdr = NlMsgHeader::user_defined(RTM_DELADDRLABEL);
+    exchange(socket, hdr, &label.bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket::Socket;
+    use Protocol;
+
+    use libc::EPERM;
+
+    #[test]
+    fn test_addr_labels() {
+        let mut socket = Socket::new(Protocol::Route).unwrap();
+        let prefix = Ipv6Addr::new(0x2001, 0xdb8, 0x4c, 0, 0, 0, 0, 0);
+        let mut label = AddrLabel::new(prefix, 48, 1000);
+        match add_addr_label(&mut socket, &label) {
+            Err(ref e) if e.raw_os_error() == Some(EPERM) => return,
+            r => r.unwrap(),
+        }
+
+        let find = |socket: &mut Socket| addr_labels(socket).unwrap().into_iter()
+            .find(|l| l.prefix() == prefix && l.prefix_len() == 48);
+        assert_eq!(find(&mut socket).unwrap().label(), 1000);
+        // The kernel always has a default entry for ::/0
+        assert!(addr_labels(&mut socket).unwrap().iter().any(|l| l.prefix_len() == 0));
+
+        label = AddrLabel::new(prefix, 48, 1001);
+        replace_addr_label(&mut socket, &label).unwrap();
+        assert_eq!(find(&mut socket).unwrap().label(), 1001);
+
+        del_addr_label(&mut socket, &label).unwrap();
+        assert!(find(&mut socket).is_none());
+    }
+}
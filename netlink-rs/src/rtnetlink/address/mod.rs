@@ -0,0 +1,386 @@
+//! Interface addresses (`ip address`)
+//!
+//! An address is a `struct ifaddrmsg` followed by attributes. The 8-bit
+//! `ifa_flags` field only holds the original flags; IFA_FLAGS carries all
+//! 32 bits, and IFA_CACHEINFO the lifetimes of dynamic (mostly IPv6)
+//! addresses.
+
+mod label;
+pub use self::label::*;
+
+use super::{exchange, addr_attr, RTM_NEWADDR, RTM_DELADDR, RTM_GETADDR};
+use super::route::RtScope;
+use socket::{NetlinkTransport, NlMsgHeader, NlAttr, string_attr, ip_family};
+
+use std::io::{self, Cursor};
+use std::net::IpAddr;
+
+use byteorder::{NativeEndian, ReadBytesExt};
+use libc::AF_UNSPEC;
+
+const IFA_ADDRESS: u16 = 1;
+const IFA_LOCAL: u16 = 2;
+const IFA_LABEL: u16 = 3;
+const IFA_CACHEINFO: u16 = 6;
+const IFA_FLAGS: u16 = 8;
+
+/// Skip duplicate address detection
+pub const IFA_F_NODAD: u32 = 0x02;
+/// Duplicate address detection failed
+pub const IFA_F_DADFAILED: u32 = 0x08;
+/// Preferred lifetime expired
+pub const IFA_F_DEPRECATED: u32 = 0x20;
+/// Duplicate address detection is in progress
+pub const IFA_F_TENTATIVE: u32 = 0x40;
+/// Static address without lifetimes
+pub const IFA_F_PERMANENT: u32 = 0x80;
+/// Create temporary (privacy) addresses from this prefix
+pub const IFA_F_MANAGETEMPADDR: u32 = 0x100;
+/// Don't add a prefix route for the address
+pub const IFA_F_NOPREFIXROUTE: u32 = 0x200;
+
+/// Lifetime of an address that never expires
+pub const INFINITY_LIFE_TIME: u32 = 0xFFFF_FFFF;
+
+// HEADER FORMAT
+// __u8    ifa_family;
+// __u8    ifa_prefixlen;  /* The prefix length          */
+// __u8    ifa_flags;      /* Flags                      */
+// __u8    ifa_scope;      /* Address scope              */
+// __u32   ifa_index;      /* Link index                 */
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+struct IfAddrMsg {
+    family: u8,
+    prefix_len: u8,
+    flags: u8,
+    scope: u8,
+    index: u32,
+}
+
+impl IfAddrMsg {
+    fn from_bytes(bytes: &[u8]) -> io::Result<(IfAddrMsg, usize)> {
+        let mut cursor = Cursor::new(bytes);
+        let family = cursor.read_u8()?;
+        let prefix_len = cursor.read_u8()?;
+        let flags = cursor.read_u8()?;
+        let scope = cursor.read_u8()?;
+        let index = cursor.read_u32::<NativeEndian>()?;
+        Ok((IfAddrMsg {
+            family,
+            prefix_len,
+            flags,
+            scope,
+            index,
+        }, cursor.position() as usize))
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![self.family, self.prefix_len, self.flags, self.scope];
+        bytes.extend_from_slice(&self.index.to_ne_bytes());
+        bytes
+    }
+}
+
+// HEADER FORMAT
+// __u32   ifa_prefered;
+// __u32   ifa_valid;
+// __u32   cstamp; /* created timestamp, hundredths of seconds */
+// __u32   tstamp; /* updated timestamp, hundredths of seconds */
+/// Lifetimes and timestamps of an address
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct CacheInfo {
+    preferred: u32,
+    valid: u32,
+    created: u32,
+    updated: u32,
+}
+
+impl CacheInfo {
+    fn from_bytes(bytes: &[u8]) -> io::Result<CacheInfo> {
+        let mut cursor = Cursor::new(bytes);
+        Ok(CacheInfo {
+            preferred: cursor.read_u32::<NativeEndian>()?,
+            valid: cursor.read_u32::<NativeEndian>()?,
+            created: cursor.read_u32::<NativeEndian>()?,
+            updated: cursor.read_u32::<NativeEndian>()?,
+        })
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        for v in &[self.preferred, self.valid, self.created, self.updated] {
+            bytes.extend_from_slice(&v.to_ne_bytes());
+        }
+        bytes
+    }
+
+    /// Remaining preferred lifetime in seconds, `INFINITY_LIFE_TIME` if it
+    /// never expires
+    pub fn preferred(&self) -> u32 {
+        self.preferred
+    }
+
+    /// Remaining valid lifetime in seconds, `INFINITY_LIFE_TIME` if it never
+    /// expires
+    pub fn valid(&self) -> u32 {
+        self.valid
+    }
+
+    /// Creation time in hundredths of seconds since boot
+    pub fn created(&self) -> u32 {
+        self.created
+    }
+
+    /// Last update time in hundredths of seconds since boot
+    pub fn updated(&self) -> u32 {
+        self.updated
+    }
+}
+
+/// An address, as dumped or to be added (`ip address add ADDR/LEN dev ...`)
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Address {
+    family: u8,
+    ifindex: u32,
+    address: Option<IpAddr>,
+    local: Option<IpAddr>,
+    prefix_len: u8,
+    scope: RtScope,
+    label: Option<String>,
+    flags: u32,
+    cache_info: Option<CacheInfo>,
+}
+
+impl Address {
+    /// Describes address `addr/prefix_len` on link `ifindex`.
+    pub fn new(ifindex: i32, addr: IpAddr, prefix_len: u8) -> Address {
+        Address {
+            family: ip_family(addr),
+            ifindex: ifindex as u32,
+            address: Some(addr),
+            local: Some(addr),
+            prefix_len,
+            scope: RtScope::Universe,
+            label: None,
+            flags: 0,
+            cache_info: None,
+        }
+    }
+
+    fn from_bytes(bytes: &[u8]) -> io::Result<Address> {
+        let (ifa, n) = IfAddrMsg::from_bytes(bytes)?;
+        let mut address = Address {
+            family: ifa.family,
+            ifindex: ifa.index,
+            address: None,
+            local: None,
+            prefix_len: ifa.prefix_len,
+            scope: ifa.scope.into(),
+            label: None,
+            flags: ifa.flags as u32,
+            cache_info: None,
+        };
+        for attr in NlAttr::parse(&bytes[n..])? {
+            let p = attr.payload();
+            match attr.attr_type() {
+                IFA_ADDRESS => address.address = Some(attr.get_ip_in(address.family)?),
+                IFA_LOCAL => address.local = Some(attr.get_ip_in(address.family)?),
+                IFA_LABEL => address.label = Some(attr.get_str()?.to_string()),
+                IFA_FLAGS => address.flags = attr.get_u32()?,
+                IFA_CACHEINFO => address.cache_info = Some(CacheInfo::from_bytes(p)?),
+                _ => {},
+            }
+        }
+        Ok(address)
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        let ifa = IfAddrMsg {
+            family: self.family,
+            prefix_len: self.prefix_len,
+            flags: self.flags as u8,
+            scope: self.scope.into(),
+            index: self.ifindex,
+        };
+
+        let mut bytes = ifa.bytes();
+        if let Some(local) = self.local {
+            bytes.extend(addr_attr(IFA_LOCAL, local));
+        }
+        if let Some(address) = self.address {
+            bytes.extend(addr_attr(IFA_ADDRESS, address));
+        }
+        if let Some(ref label) = self.label {
+            bytes.extend(string_attr(IFA_LABEL, label));
+        }
+        bytes.extend(NlAttr::new(IFA_FLAGS, &self.flags.to_ne_bytes()).bytes());
+        if let Some(cache_info) = self.cache_info {
+            bytes.extend(NlAttr::new(IFA_CACHEINFO, &cache_info.bytes()).bytes());
+        }
+        bytes
+    }
+
+    /// Remote end of a point-to-point link, which replaces the address as
+    /// IFA_ADDRESS
+    pub fn set_peer(&mut self, peer: IpAddr) -> &mut Address {
+        self.address = Some(peer);
+        self
+    }
+
+    /// Universe scope by default
+    pub fn set_scope(&mut self, scope: RtScope) -> &mut Address {
+        self.scope = scope;
+        self
+    }
+
+    /// IPv4 label, e.g. `eth0:1`
+    pub fn set_label(&mut self, label: &str) -> &mut Address {
+        self.label = Some(label.to_owned());
+        self
+    }
+
+    /// IFA_F_* flags, e.g. `IFA_F_NODAD | IFA_F_NOPREFIXROUTE`
+    pub fn set_flags(&mut self, flags: u32) -> &mut Address {
+        self.flags = flags;
+        self
+    }
+
+    /// Lifetimes in seconds; the address becomes deprecated after `preferred`
+    /// and is removed after `valid`. Addresses are permanent by default.
+    pub fn set_lifetimes(&mut self, preferred: u32, valid: u32) -> &mut Address {
+        self.cache_info = Some(CacheInfo { preferred, valid, created: 0, updated: 0 });
+        self
+    }
+
+    /// Address family, AF_INET or AF_INET6
+    pub fn family(&self) -> u8 {
+        self.family
+    }
+
+    pub fn ifindex(&self) -> i32 {
+        self.ifindex as i32
+    }
+
+    /// IFA_ADDRESS: the address, or the peer on point-to-point links
+    pub fn address(&self) -> Option<IpAddr> {
+        self.address
+    }
+
+    /// IFA_LOCAL: the local address, not sent for IPv6 without a peer
+    pub fn local(&self) -> Option<IpAddr> {
+        self.local
+    }
+
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+
+    pub fn scope(&self) -> RtScope {
+        self.scope
+    }
+
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    /// IFA_F_* flags
+    pub fn flags(&self) -> u32 {
+        self.flags
+    }
+
+    /// Lifetimes, always present for dumped IPv6 addresses
+    pub fn cache_info(&self) -> Option<&CacheInfo> {
+        self.cache_info.as_ref()
+    }
+}
+
+/// Dumps the addresses of all links and families.
+pub fn addresses(socket: &mut impl NetlinkTransport) -> io::Result<Vec<Address>> {
+    let mut hdr = NlMsgHeader::user_defined(RTM_GETADDR);
+    hdr.dump();
+    let ifa = IfAddrMsg { family: AF_UNSPEC as u8, ..Default::default() };
+    let replies = exchange(socket, hdr, &ifa.bytes())?;
+    replies.iter().map(|r| Address::from_bytes(r)).collect()
+}
+
+/// Adds `address`, failing with `EEXIST` if the link already has it.
+pub fn add_address(socket: &mut impl NetlinkTransport, address: &Address) -> io::Result<()> {
+    let mut hdr = NlMsgHeader::user_defined(RTM_NEWADDR);
+    hdr.create().excl();
+    exchange(socket, hdr, &address.bytes())?;
+    Ok(())
+}
+
+/// Updates the flags and lifetimes of an existing address.
+pub fn change_address(socket: &mut impl NetlinkTransport, address: &Address) -> io::Result<()> {
+    let mut hdr = NlMsgHeader::user_defined(RTM_NEWADDR);
+    hdr.replace();
+    exchange(socket, hdr, &address.bytes())?;
+    Ok(())
+}
+
+pub fn del_address(socket: &mut impl NetlinkTransport, address: &Address) -> io::Result<()> {
+    let hdr = NlMsgHeader::user_defined(RTM_DELADDR);
+    exchange(socket, hdr, &address.bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rtnetlink::link::{create_veth, link_by_name, set_link, LinkSet, LinkGuard};
+    use socket::Socket;
+    use Protocol;
+    use netns;
+
+    use std::net::{IpAddr, Ipv6Addr};
+
+    use libc::EPERM;
+
+    #[test]
+    fn test_address_roundtrip() {
+        let mut address = Address::new(3, IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)), 64);
+        address.set_flags(IFA_F_NODAD | IFA_F_MANAGETEMPADDR).set_lifetimes(600, 1200);
+        let decoded = Address::from_bytes(&address.bytes()).unwrap();
+        assert_eq!(decoded, address);
+        assert_eq!(decoded.cache_info().unwrap().valid(), 1200);
+    }
+
+    #[test]
+    fn test_add_address() {
+        netns::run(|| {
+            let mut socket = Socket::new(Protocol::Route).unwrap();
+            match create_veth(&mut socket, "nlrs-ad0", "nlrs-ad1") {
+                Err(ref e) if e.raw_os_error() == Some(EPERM) => return,
+                r => r.unwrap(),
+            }
+            let link = link_by_name(&mut socket, "nlrs-ad0").unwrap();
+            let _guard = LinkGuard(link.index());
+            set_link(&mut socket, LinkSet::new(link.index()).up()).unwrap();
+
+            let ip = IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1));
+            let mut address = Address::new(link.index(), ip, 64);
+            address.set_flags(IFA_F_NODAD | IFA_F_NOPREFIXROUTE).set_lifetimes(600, 1200);
+            add_address(&mut socket, &address).unwrap();
+
+            let find = |socket: &mut Socket| addresses(socket).unwrap().into_iter()
+                .find(|a| a.ifindex() == link.index() && a.address() == Some(ip)).unwrap();
+            let found = find(&mut socket);
+            assert_eq!(found.flags() & (IFA_F_NODAD | IFA_F_NOPREFIXROUTE), IFA_F_NODAD | IFA_F_NOPREFIXROUTE);
+            assert_eq!(found.flags() & IFA_F_TENTATIVE, 0);
+            let info = *found.cache_info().unwrap();
+            assert!(info.preferred() <= 600 && info.valid() <= 1200 && info.valid() > 600);
+
+            address.set_lifetimes(INFINITY_LIFE_TIME, INFINITY_LIFE_TIME);
+            change_address(&mut socket, &address).unwrap();
+            let found = find(&mut socket);
+            assert_ne!(found.flags() & IFA_F_PERMANENT, 0);
+            assert_eq!(found.cache_info().unwrap().valid(), INFINITY_LIFE_TIME);
+
+            del_address(&mut socket, &address).unwrap();
+            assert!(!addresses(&mut socket).unwrap().iter().any(|a| a.address() == Some(ip)));
+        });
+    }
+}
@@ -0,0 +1,163 @@
+use super::{create_link, set_master, set_nomaster};
+use socket::{NetlinkTransport, NlAttr};
+
+use std::io;
+
+const IFLA_BOND_MODE: u16 = 1;
+const IFLA_BOND_MIIMON: u16 = 3;
+const IFLA_BOND_UPDELAY: u16 = 4;
+const IFLA_BOND_DOWNDELAY: u16 = 5;
+
+/// Bonding policy
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum BondMode {
+    /// Round robin over all slaves
+    BalanceRr,
+    /// One active slave, the others take over on failure
+    ActiveBackup,
+    /// Transmit slave chosen by a hash of the packet
+    BalanceXor,
+    /// Transmit everything on all slaves
+    Broadcast,
+    /// IEEE 802.3ad dynamic link aggregation (LACP)
+    Lacp,
+    /// Adaptive transmit load balancing
+    BalanceTlb,
+    /// Adaptive transmit and receive load balancing
+    BalanceAlb,
+}
+
+impl From<BondMode> for u8 {
+    fn from(t: BondMode) -> u8 {
+        use self::BondMode::*;
+        match t {
+            BalanceRr    => 0,
+            ActiveBackup => 1,
+            BalanceXor   => 2,
+            Broadcast    => 3,
+            Lacp         => 4,
+            BalanceTlb   => 5,
+            BalanceAlb   => 6,
+        }
+    }
+}
+
+/// Options of a bond link (`ip link add NAME type bond mode MODE ...`)
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct BondConfig {
+    name: String,
+    mode: BondMode,
+    miimon: Option<u32>,
+    updelay: Option<u32>,
+    downdelay: Option<u32>,
+}
+
+impl BondConfig {
+    pub fn new(name: &str, mode: BondMode) -> BondConfig {
+        BondConfig {
+            name: name.into(),
+            mode,
+            miimon: None,
+            updelay: None,
+            downdelay: None,
+        }
+    }
+
+    /// MII link monitoring interval in milliseconds, 0 disables it
+    pub fn set_miimon(&mut self, ms: u32) -> &mut BondConfig {
+        self.miimon = Some(ms);
+        self
+    }
+
+    /// Delay before enabling a slave after its link came up, in milliseconds
+    pub fn set_updelay(&mut self, ms: u32) -> &mut BondConfig {
+        self.updelay = Some(ms);
+        self
+    }
+
+    /// Delay before disabling a slave after its link went down, in milliseconds
+    pub fn set_downdelay(&mut self, ms: u32) -> &mut BondConfig {
+        self.downdelay = Some(ms);
+        self
+    }
+
+    fn data(&self) -> Vec<u8> {
+        let mut bytes = NlAttr::new(IFLA_BOND_MODE, &[u8::from(self.mode)]).bytes();
+        if let Some(ms) = self.miimon {
+            bytes.extend(NlAttr::new(IFLA_BOND_MIIMON, &ms.to_ne_bytes()).bytes());
+        }
+        if let Some(ms) = self.updelay {
+            bytes.extend(NlAttr::new(IFLA_BOND_UPDELAY, &ms.to_ne_bytes()).bytes());
+        }
+        if let Some(ms) = self.downdelay {
+            bytes.extend(NlAttr::new(IFLA_BOND_DOWNDELAY, &ms.to_ne_bytes()).bytes());
+        }
+        bytes
+    }
+}
+
+/// Creates a bond link as described by `config`.
+pub fn create_bond(socket: &mut impl NetlinkTransport, config: &BondConfig) -> io::Result<()> {
+    create_link(socket, &config.name, "bond", &config.data())
+}
+
+/// Adds link `slave` to `bond`. The kernel requires the slave to be down.
+pub fn add_slave(socket: &mut impl NetlinkTransport, bond: i32, slave: i32) -> io::Result<()> {
+    set_master(socket, slave, bond)
+}
+
+/// Removes link `slave` from its bond.
+pub fn del_slave(socket: &mut impl NetlinkTransport, slave: i32) -> io::Result<()> {
+    set_nomaster(socket, slave)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{create_veth, link_by_name, LinkGuard};
+    use socket::{Socket, NlAttr};
+    use Protocol;
+    use netns;
+
+    use libc::{EPERM, EOPNOTSUPP};
+
+    #[test]
+    fn test_bond_data() {
+        let mut config = BondConfig::new("bond0", BondMode::Lacp);
+        config.set_miimon(100);
+
+        let data = config.data();
+        let attrs = NlAttr::parse(&data).unwrap();
+        assert_eq!(attrs.len(), 2);
+        assert_eq!(attrs[0].attr_type(), IFLA_BOND_MODE);
+        assert_eq!(attrs[0].payload(), &[4]);
+        assert_eq!(attrs[1].attr_type(), IFLA_BOND_MIIMON);
+        assert_eq!(attrs[1].payload(), &100u32.to_ne_bytes());
+    }
+
+    #[test]
+    fn test_bond_slaves() {
+        netns::run(|| {
+            let mut socket = Socket::new(Protocol::Route).unwrap();
+            let config = BondConfig::new("nlrs-bond0", BondMode::ActiveBackup);
+            match create_bond(&mut socket, &config) {
+                // No bonding driver in this kernel
+                Err(ref e) if e.raw_os_error() == Some(EOPNOTSUPP) => return,
+                Err(ref e) if e.raw_os_error() == Some(EPERM) => return,
+                r => r.unwrap(),
+            }
+            let bond = link_by_name(&mut socket, "nlrs-bond0").unwrap();
+            let _bond_guard = LinkGuard(bond.index());
+            create_veth(&mut socket, "nlrs-bs0", "nlrs-bs1").unwrap();
+            let slave = link_by_name(&mut socket, "nlrs-bs0").unwrap();
+            let _slave_guard = LinkGuard(slave.index());
+
+            add_slave(&mut socket, bond.index(), slave.index()).unwrap();
+            assert_eq!(link_by_name(&mut socket, "nlrs-bs0").unwrap().master(), Some(bond.index()));
+            del_slave(&mut socket, slave.index()).unwrap();
+            assert_eq!(link_by_name(&mut socket, "nlrs-bs0").unwrap().master(), None);
+        });
+    }
+}
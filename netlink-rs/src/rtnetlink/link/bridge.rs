@@ -0,0 +1,290 @@
+use super::{create_link, IfInfoMsg, IFLA_LINKINFO, IFLA_INFO_KIND, IFLA_INFO_DATA, IFLA_AF_SPEC};
+use super::super::{exchange, RTM_NEWLINK, RTM_SETLINK, RTM_DELLINK, RTM_GETVLAN};
+use socket::{NetlinkTransport, NlMsgHeader, NlAttr, NLA_F_NESTED};
+
+use std::io::{self, Cursor};
+
+use byteorder::{NativeEndian, ReadBytesExt};
+use libc::AF_BRIDGE;
+
+const IFLA_BR_VLAN_FILTERING: u16 = 7;
+
+const IFLA_BRIDGE_FLAGS: u16 = 0;
+const IFLA_BRIDGE_VLAN_INFO: u16 = 2;
+
+const BRIDGE_FLAGS_SELF: u16 = 2;
+
+const BRIDGE_VLAN_INFO_PVID: u16 = 1 << 1;
+const BRIDGE_VLAN_INFO_UNTAGGED: u16 = 1 << 2;
+
+const BRIDGE_VLANDB_ENTRY: u16 = 1;
+const BRIDGE_VLANDB_ENTRY_INFO: u16 = 1;
+const BRIDGE_VLANDB_ENTRY_RANGE: u16 = 2;
+
+/// Creates a bridge with default options (`ip link add NAME type bridge`).
+///
+/// Ports are added with `set_master` and removed with `set_nomaster`.
+pub fn create_bridge(socket: &mut impl NetlinkTransport, name: &str) -> io::Result<()> {
+    create_link(socket, name, "bridge", &[])
+}
+
+/// Turns VLAN filtering of bridge `index` on or off
+/// (`ip link set BRIDGE type bridge vlan_filtering 1`).
+pub fn set_vlan_filtering(socket: &mut impl NetlinkTransport, index: i32, enabled: bool) -> io::Result<()> {
+    let data = NlAttr::new(IFLA_BR_VLAN_FILTERING, &[enabled as u8]).bytes();
+    let mut info = NlAttr::new(IFLA_INFO_KIND, b"bridge").bytes();
+    info.extend(NlAttr::new(IFLA_INFO_DATA | NLA_F_NESTED, &data).bytes());
+
+    let hdr = NlMsgHeader::user_defined(RTM_NEWLINK);
+    let mut payload = IfInfoMsg { index, ..Default::default() }.bytes();
+    payload.extend(NlAttr::new(IFLA_LINKINFO | NLA_F_NESTED, &info).bytes());
+    exchange(socket, hdr, &payload)?;
+    Ok(())
+}
+
+/// VLAN membership of a bridge port (`bridge vlan`)
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct BridgeVlan {
+    ifindex: i32,
+    vid: u16,
+    flags: u16,
+    on_self: bool,
+}
+
+impl BridgeVlan {
+    /// Describes membership of port `ifindex` in VLAN `vid`.
+    pub fn new(ifindex: i32, vid: u16) -> BridgeVlan {
+        BridgeVlan {
+            ifindex,
+            vid,
+            flags: 0,
+            on_self: false,
+        }
+    }
+
+    /// Untagged ingress frames are assigned to this VLAN
+    pub fn pvid(&mut self) -> &mut BridgeVlan {
+        self.flags |= BRIDGE_VLAN_INFO_PVID;
+        self
+    }
+
+    /// Frames of this VLAN egress without a tag
+    pub fn untagged(&mut self) -> &mut BridgeVlan {
+        self.flags |= BRIDGE_VLAN_INFO_UNTAGGED;
+        self
+    }
+
+    /// `ifindex` is the bridge itself rather than one of its ports
+    pub fn set_self(&mut self) -> &mut BridgeVlan {
+        self.on_self = true;
+        self
+    }
+
+    pub fn ifindex(&self) -> i32 {
+        self.ifindex
+    }
+
+    pub fn vid(&self) -> u16 {
+        self.vid
+    }
+
+    pub fn is_pvid(&self) -> bool {
+        self.flags & BRIDGE_VLAN_INFO_PVID != 0
+    }
+
+    pub fn is_untagged(&self) -> bool {
+        self.flags & BRIDGE_VLAN_INFO_UNTAGGED != 0
+    }
+
+    // struct bridge_vlan_info {
+    //     __u16 flags;
+    //     __u16 vid;
+    // };
+    fn info(&self) -> Vec<u8> {
+        let mut info = self.flags.to_ne_bytes().to_vec();
+        info.extend_from_slice(&self.vid.to_ne_bytes());
+        info
+    }
+
+    // struct ifinfomsg (AF_BRIDGE)
+    // IFLA_AF_SPEC
+    //     IFLA_BRIDGE_FLAGS      BRIDGE_FLAGS_SELF, for the bridge device
+    //     IFLA_BRIDGE_VLAN_INFO  struct bridge_vlan_info
+    fn bytes(&self) -> Vec<u8> {
+        let mut spec = vec![];
+        if self.on_self {
+            spec.extend(NlAttr::new(IFLA_BRIDGE_FLAGS, &BRIDGE_FLAGS_SELF.to_ne_bytes()).bytes());
+        }
+        spec.extend(NlAttr::new(IFLA_BRIDGE_VLAN_INFO, &self.info()).bytes());
+
+        let mut bytes = IfInfoMsg { family: AF_BRIDGE as u8, index: self.ifindex, ..Default::default() }.bytes();
+        bytes.extend(NlAttr::new(IFLA_AF_SPEC | NLA_F_NESTED, &spec).bytes());
+        bytes
+    }
+}
+
+/// Adds a port to a VLAN, or updates its pvid/untagged flags.
+pub fn add_bridge_vlan(socket: &mut impl NetlinkTransport, vlan: &BridgeVlan) -> io::Result<()> {
+    let hdr = NlMsgHeader::user_defined(RTM_SETLINK);
+    exchange(socket, hdr, &vlan.bytes())?;
+    Ok(())
+}
+
+/// Removes a port from a VLAN.
+pub fn del_bridge_vlan(socket: &mut impl NetlinkTransport, vlan: &BridgeVlan) -> io::Result<()> {
+    // With AF_BRIDGE and IFLA_AF_SPEC this deletes the VLAN, not the link
+    let hdr = NlMsgHeader::user_defined(RTM_DELLINK);
+    exchange(socket, hdr, &vlan.bytes())?;
+    Ok(())
+}
+
+// HEADER FORMAT
+// __u8    family;
+// __u8    reserved1;
+// __u16   reserved2;
+// __u32   ifindex;
+fn br_vlan_msg(ifindex: i32) -> Vec<u8> {
+    let mut bytes = vec![AF_BRIDGE as u8, 0, 0, 0];
+    bytes.extend_from_slice(&ifindex.to_ne_bytes());
+    bytes
+}
+
+// BRIDGE_VLANDB_ENTRY
+//     BRIDGE_VLANDB_ENTRY_INFO   struct bridge_vlan_info
+//     BRIDGE_VLANDB_ENTRY_RANGE  last vid, if the entry covers a range
+fn vlans_from_bytes(bytes: &[u8]) -> io::Result<Vec<BridgeVlan>> {
+    let mut cursor = Cursor::new(bytes);
+    let _family = cursor.read_u32::<NativeEndian>()?;
+    let ifindex = cursor.read_i32::<NativeEndian>()?;
+
+    let mut vlans = vec![];
+    for entry in NlAttr::parse(&bytes[cursor.position() as usize..])? {
+        if entry.attr_type() != BRIDGE_VLANDB_ENTRY {
+            continue;
+        }
+        let mut first = None;
+        let mut last = None;
+        for a in entry.nested()? {
+            let mut cursor = Cursor::new(a.payload());
+            match a.attr_type() {
+                BRIDGE_VLANDB_ENTRY_INFO => {
+                    let flags = cursor.read_u16::<NativeEndian>()?;
+                    let vid = cursor.read_u16::<NativeEndian>()?;
+                    first = Some((flags, vid));
+                },
+                BRIDGE_VLANDB_ENTRY_RANGE => last = Some(a.get_u16()?),
+                _ => {},
+            }
+        }
+        if let Some((flags, vid)) = first {
+            for v in vid..=last.unwrap_or(vid) {
+                vlans.push(BridgeVlan { ifindex, vid: v, flags, on_self: false });
+            }
+        }
+    }
+    Ok(vlans)
+}
+
+/// Dumps the VLAN membership of all bridges and bridge ports (RTM_GETVLAN).
+pub fn bridge_vlans(socket: &mut impl NetlinkTransport) -> io::Result<Vec<BridgeVlan>> {
+    let mut hdr = NlMsgHeader::user_defined(RTM_GETVLAN);
+    hdr.dump();
+    let replies = exchange(socket, hdr, &br_vlan_msg(0))?;
+    let mut vlans = vec![];
+    for reply in replies {
+        vlans.extend(vlans_from_bytes(&reply)?);
+    }
+    Ok(vlans)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{create_veth, link_by_name, set_master, LinkGuard};
+    use socket::{Socket, NlAttr, NLA_F_NESTED};
+    use Protocol;
+    use netns;
+
+    use libc::{EPERM, EOPNOTSUPP};
+
+    #[test]
+    fn test_vlan_range_decode() {
+        let mut info = BRIDGE_VLAN_INFO_UNTAGGED.to_ne_bytes().to_vec();
+        info.extend_from_slice(&10u16.to_ne_bytes());
+        let mut entry = NlAttr::new(BRIDGE_VLANDB_ENTRY_INFO, &info).bytes();
+        entry.extend(NlAttr::new(BRIDGE_VLANDB_ENTRY_RANGE, &12u16.to_ne_bytes()).bytes());
+
+        let mut bytes = br_vlan_msg(4);
+        bytes.extend(NlAttr::new(BRIDGE_VLANDB_ENTRY | NLA_F_NESTED, &entry).bytes());
+
+        let vlans = vlans_from_bytes(&bytes).unwrap();
+        let vids: Vec<u16> = vlans.iter().map(|v| v.vid()).collect();
+        assert_eq!(vids, [10, 11, 12]);
+        assert!(vlans.iter().all(|v| v.ifindex() == 4 && v.is_untagged() && !v.is_pvid()));
+    }
+
+    #[test]
+    fn test_bridge_ports() {
+        netns::run(|| {
+            let mut socket = Socket::new(Protocol::Route).unwrap();
+            match create_bridge(&mut socket, "nlrs-br0") {
+                Err(ref e) if e.raw_os_error() == Some(EPERM) => return,
+                r => r.unwrap(),
+            }
+            let br = link_by_name(&mut socket, "nlrs-br0").unwrap();
+            let _br_guard = LinkGuard(br.index());
+            assert_eq!(br.kind(), Some("bridge"));
+
+            create_veth(&mut socket, "nlrs-brp0", "nlrs-brp1").unwrap();
+            let port = link_by_name(&mut socket, "nlrs-brp0").unwrap();
+            let _port_guard = LinkGuard(port.index());
+            assert_eq!(port.master(), None);
+
+            set_master(&mut socket, port.index(), br.index()).unwrap();
+            let port = link_by_name(&mut socket, "nlrs-brp0").unwrap();
+            assert_eq!(port.master(), Some(br.index()));
+
+            super::super::set_nomaster(&mut socket, port.index()).unwrap();
+            let port = link_by_name(&mut socket, "nlrs-brp0").unwrap();
+            assert_eq!(port.master(), None);
+        });
+    }
+
+    #[test]
+    fn test_bridge_vlans() {
+        netns::run(|| {
+            let mut socket = Socket::new(Protocol::Route).unwrap();
+            match create_bridge(&mut socket, "nlrs-vbr0") {
+                Err(ref e) if e.raw_os_error() == Some(EPERM) => return,
+                r => r.unwrap(),
+            }
+            let br = link_by_name(&mut socket, "nlrs-vbr0").unwrap();
+            let _br_guard = LinkGuard(br.index());
+            match set_vlan_filtering(&mut socket, br.index(), true) {
+                // Kernel built without CONFIG_BRIDGE_VLAN_FILTERING
+                Err(ref e) if e.raw_os_error() == Some(EOPNOTSUPP) => return,
+                r => r.unwrap(),
+            }
+            create_veth(&mut socket, "nlrs-vbp0", "nlrs-vbp1").unwrap();
+            let port = link_by_name(&mut socket, "nlrs-vbp0").unwrap();
+            let _port_guard = LinkGuard(port.index());
+            set_master(&mut socket, port.index(), br.index()).unwrap();
+
+            let mut vlan = BridgeVlan::new(port.index(), 20);
+            vlan.pvid().untagged();
+            add_bridge_vlan(&mut socket, &vlan).unwrap();
+
+            let vlans = bridge_vlans(&mut socket).unwrap();
+            let found = vlans.iter().find(|v| v.ifindex() == port.index() && v.vid() == 20).unwrap();
+            assert!(found.is_pvid());
+            assert!(found.is_untagged());
+
+            del_bridge_vlan(&mut socket, &vlan).unwrap();
+            let vlans = bridge_vlans(&mut socket).unwrap();
+            assert!(!vlans.iter().any(|v| v.ifindex() == port.index() && v.vid() == 20));
+            // The port itself is still there
+            link_by_name(&mut socket, "nlrs-vbp0").unwrap();
+        });
+    }
+}
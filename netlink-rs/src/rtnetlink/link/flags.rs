@@ -0,0 +1,189 @@
+use std::fmt;
+use std::ops::{BitAnd, BitOr, BitOrAssign, Not, Sub};
+
+/// IFF_* device flags, as in `ifi_flags` and `ifi_change`
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct IfFlags(u32);
+
+const NAMES: [(IfFlags, &str); 19] = [
+    (IfFlags::UP, "UP"),
+    (IfFlags::BROADCAST, "BROADCAST"),
+    (IfFlags::DEBUG, "DEBUG"),
+    (IfFlags::LOOPBACK, "LOOPBACK"),
+    (IfFlags::POINTOPOINT, "POINTOPOINT"),
+    (IfFlags::NOTRAILERS, "NOTRAILERS"),
+    (IfFlags::RUNNING, "RUNNING"),
+    (IfFlags::NOARP, "NOARP"),
+    (IfFlags::PROMISC, "PROMISC"),
+    (IfFlags::ALLMULTI, "ALLMULTI"),
+    (IfFlags::MASTER, "MASTER"),
+    (IfFlags::SLAVE, "SLAVE"),
+    (IfFlags::MULTICAST, "MULTICAST"),
+    (IfFlags::PORTSEL, "PORTSEL"),
+    (IfFlags::AUTOMEDIA, "AUTOMEDIA"),
+    (IfFlags::DYNAMIC, "DYNAMIC"),
+    (IfFlags::LOWER_UP, "LOWER_UP"),
+    (IfFlags::DORMANT, "DORMANT"),
+    (IfFlags::ECHO, "ECHO"),
+];
+
+impl IfFlags {
+    /// Administratively up
+    pub const UP: IfFlags = IfFlags(1);
+    pub const BROADCAST: IfFlags = IfFlags(1 << 1);
+    pub const DEBUG: IfFlags = IfFlags(1 << 2);
+    pub const LOOPBACK: IfFlags = IfFlags(1 << 3);
+    pub const POINTOPOINT: IfFlags = IfFlags(1 << 4);
+    pub const NOTRAILERS: IfFlags = IfFlags(1 << 5);
+    /// Operationally up, RFC 2863 state up or unknown
+    pub const RUNNING: IfFlags = IfFlags(1 << 6);
+    pub const NOARP: IfFlags = IfFlags(1 << 7);
+    pub const PROMISC: IfFlags = IfFlags(1 << 8);
+    pub const ALLMULTI: IfFlags = IfFlags(1 << 9);
+    pub const MASTER: IfFlags = IfFlags(1 << 10);
+    pub const SLAVE: IfFlags = IfFlags(1 << 11);
+    pub const MULTICAST: IfFlags = IfFlags(1 << 12);
+    pub const PORTSEL: IfFlags = IfFlags(1 << 13);
+    pub const AUTOMEDIA: IfFlags = IfFlags(1 << 14);
+    pub const DYNAMIC: IfFlags = IfFlags(1 << 15);
+    /// Carrier present
+    pub const LOWER_UP: IfFlags = IfFlags(1 << 16);
+    pub const DORMANT: IfFlags = IfFlags(1 << 17);
+    pub const ECHO: IfFlags = IfFlags(1 << 18);
+
+    pub fn empty() -> IfFlags {
+        IfFlags(0)
+    }
+
+    pub fn from_bits(bits: u32) -> IfFlags {
+        IfFlags(bits)
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Whether all of `flags` are set
+    pub fn contains(&self, flags: IfFlags) -> bool {
+        self.0 & flags.0 == flags.0
+    }
+
+    pub fn insert(&mut self, flags: IfFlags) {
+        self.0 |= flags.0;
+    }
+
+    pub fn remove(&mut self, flags: IfFlags) {
+        self.0 &= !flags.0;
+    }
+
+    pub fn is_up(&self) -> bool {
+        self.contains(IfFlags::UP)
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.contains(IfFlags::RUNNING)
+    }
+
+    pub fn is_loopback(&self) -> bool {
+        self.contains(IfFlags::LOOPBACK)
+    }
+}
+
+impl From<u32> for IfFlags {
+    fn from(bits: u32) -> IfFlags {
+        IfFlags(bits)
+    }
+}
+
+impl From<IfFlags> for u32 {
+    fn from(flags: IfFlags) -> u32 {
+        flags.0
+    }
+}
+
+impl BitOr for IfFlags {
+    type Output = IfFlags;
+
+    fn bitor(self, other: IfFlags) -> IfFlags {
+        IfFlags(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for IfFlags {
+    fn bitor_assign(&mut self, other: IfFlags) {
+        self.0 |= other.0;
+    }
+}
+
+impl BitAnd for IfFlags {
+    type Output = IfFlags;
+
+    fn bitand(self, other: IfFlags) -> IfFlags {
+        IfFlags(self.0 & other.0)
+    }
+}
+
+impl Sub for IfFlags {
+    type Output = IfFlags;
+
+    fn sub(self, other: IfFlags) -> IfFlags {
+        IfFlags(self.0 & !other.0)
+    }
+}
+
+impl Not for IfFlags {
+    type Output = IfFlags;
+
+    fn not(self) -> IfFlags {
+        IfFlags(!self.0)
+    }
+}
+
+impl fmt::Debug for IfFlags {
+    /// Lists the flags by name, as `ip link` does, e.g.
+    /// `IfFlags(UP | LOOPBACK | RUNNING)`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "IfFlags(")?;
+        let mut rest = *self;
+        let mut first = true;
+        for &(flag, name) in NAMES.iter() {
+            if self.contains(flag) {
+                write!(f, "{}{}", if first { "" } else { " | " }, name)?;
+                rest.remove(flag);
+                first = false;
+            }
+        }
+        if !rest.is_empty() {
+            write!(f, "{}{:#x}", if first { "" } else { " | " }, rest.0)?;
+        }
+        write!(f, ")")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use libc::{IFF_LOOPBACK, IFF_RUNNING, IFF_UP};
+
+    #[test]
+    fn test_if_flags() {
+        let flags = IfFlags::from((IFF_UP | IFF_LOOPBACK | IFF_RUNNING) as u32 | 1 << 16);
+        assert!(flags.is_up() && flags.is_running() && flags.is_loopback());
+        assert!(flags.contains(IfFlags::UP | IfFlags::LOWER_UP));
+        assert!(!flags.contains(IfFlags::UP | IfFlags::PROMISC));
+        assert_eq!(format!("{:?}", flags), "IfFlags(UP | LOOPBACK | RUNNING | LOWER_UP)");
+        assert_eq!(format!("{:?}", IfFlags::from(1 << 30)), "IfFlags(0x40000000)");
+
+        let mut down = flags - IfFlags::UP;
+        assert!(!down.is_up());
+        down |= IfFlags::PROMISC;
+        assert_eq!(down & IfFlags::PROMISC, IfFlags::PROMISC);
+        assert!(IfFlags::empty().is_empty());
+    }
+}
@@ -0,0 +1,90 @@
+use super::create_link_on;
+use socket::{NetlinkTransport, NlAttr};
+
+use std::io;
+
+const IFLA_MACVLAN_MODE: u16 = 1;
+
+/// How macvlans on the same lower device reach each other
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum MacvlanMode {
+    /// No traffic between macvlans of the same parent
+    Private,
+    /// Traffic between macvlans goes through the external switch
+    Vepa,
+    /// Traffic between macvlans is switched locally
+    Bridge,
+    /// A single macvlan takes over the lower device
+    Passthru,
+}
+
+impl From<MacvlanMode> for u32 {
+    fn from(t: MacvlanMode) -> u32 {
+        use self::MacvlanMode::*;
+        match t {
+            Private  => 1,
+            Vepa     => 2,
+            Bridge   => 4,
+            Passthru => 8,
+        }
+    }
+}
+
+fn macvlan_data(mode: MacvlanMode) -> Vec<u8> {
+    NlAttr::new(IFLA_MACVLAN_MODE, &u32::from(mode).to_ne_bytes()).bytes()
+}
+
+/// Creates a macvlan `name` on link `parent`
+/// (`ip link add NAME link PARENT type macvlan mode MODE`).
+pub fn create_macvlan(socket: &mut impl NetlinkTransport, parent: i32, name: &str, mode: MacvlanMode)
+    -> io::Result<()> {
+        create_link_on(socket, parent, name, "macvlan", &macvlan_data(mode))
+    }
+
+/// Like `create_macvlan`, but the device is also exposed as a `/dev/tapN`
+/// character device.
+pub fn create_macvtap(socket: &mut impl NetlinkTransport, parent: i32, name: &str, mode: MacvlanMode)
+    -> io::Result<()> {
+        create_link_on(socket, parent, name, "macvtap", &macvlan_data(mode))
+    }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{create_veth, link_by_name, del_link, LinkGuard};
+    use socket::Socket;
+    use Protocol;
+    use netns;
+
+    use libc::EPERM;
+
+    #[test]
+    fn test_macvlan_data() {
+        let data = macvlan_data(MacvlanMode::Bridge);
+        assert_eq!(data.len(), 8);
+        assert_eq!(&data[4..], &4u32.to_ne_bytes());
+    }
+
+    #[test]
+    fn test_create_macvlan() {
+        netns::run(|| {
+            let mut socket = Socket::new(Protocol::Route).unwrap();
+            match create_veth(&mut socket, "nlrs-mvp0", "nlrs-mvp1") {
+                Err(ref e) if e.raw_os_error() == Some(EPERM) => return,
+                r => r.unwrap(),
+            }
+            let parent = link_by_name(&mut socket, "nlrs-mvp0").unwrap();
+            let _guard = LinkGuard(parent.index());
+
+            create_macvlan(&mut socket, parent.index(), "nlrs-mv0", MacvlanMode::Bridge).unwrap();
+            create_macvtap(&mut socket, parent.index(), "nlrs-mvt0", MacvlanMode::Vepa).unwrap();
+            assert_eq!(link_by_name(&mut socket, "nlrs-mv0").unwrap().kind(), Some("macvlan"));
+            assert_eq!(link_by_name(&mut socket, "nlrs-mvt0").unwrap().kind(), Some("macvtap"));
+
+            // Removing the lower device removes the macvlans on top of it
+            del_link(&mut socket, parent.index()).unwrap();
+            assert!(link_by_name(&mut socket, "nlrs-mv0").is_err());
+        });
+    }
+}
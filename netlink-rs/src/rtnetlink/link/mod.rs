@@ -0,0 +1,481 @@
+//! Network interfaces (`ip link`)
+
+mod bond;
+pub use self::bond::*;
+
+mod bridge;
+pub use self::bridge::*;
+
+mod flags;
+pub use self::flags::*;
+
+mod macvlan;
+pub use self::macvlan::*;
+
+mod set;
+pub use self::set::*;
+
+mod stats;
+pub use self::stats::*;
+
+mod tunnel;
+pub use self::tunnel::*;
+
+mod veth;
+pub use self::veth::*;
+
+mod vf;
+pub use self::vf::*;
+
+mod vxlan;
+pub use self::vxlan::*;
+
+use super::{exchange, RTM_NEWLINK, RTM_DELLINK, RTM_GETLINK};
+use socket::{NetlinkTransport, NlMsgHeader, NlAttr, NLA_F_NESTED, string_attr, MacAddr};
+
+use std::io::{self, ErrorKind, Cursor};
+
+use byteorder::{NativeEndian, ReadBytesExt};
+use libc::AF_UNSPEC;
+
+const IFLA_ADDRESS: u16 = 1;
+const IFLA_IFNAME: u16 = 3;
+const IFLA_MTU: u16 = 4;
+const IFLA_LINK: u16 = 5;
+const IFLA_MASTER: u16 = 10;
+const IFLA_OPERSTATE: u16 = 16;
+const IFLA_LINKINFO: u16 = 18;
+const IFLA_VFINFO_LIST: u16 = 22;
+const IFLA_STATS64: u16 = 23;
+const IFLA_AF_SPEC: u16 = 26;
+const IFLA_EXT_MASK: u16 = 29;
+
+const IFLA_INFO_KIND: u16 = 1;
+const IFLA_INFO_DATA: u16 = 2;
+
+// Asks for IFLA_VFINFO_LIST in IFLA_EXT_MASK
+const RTEXT_FILTER_VF: u32 = 1;
+
+// HEADER FORMAT
+// unsigned char   ifi_family;
+// unsigned char   __ifi_pad;
+// unsigned short  ifi_type;       /* ARPHRD_* */
+// int             ifi_index;      /* Link index   */
+// unsigned        ifi_flags;      /* IFF_* flags  */
+// unsigned        ifi_change;     /* IFF_* change mask */
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+struct IfInfoMsg {
+    family: u8,
+    link_type: u16,
+    index: i32,
+    flags: u32,
+    change: u32,
+}
+
+impl IfInfoMsg {
+    fn from_bytes(bytes: &[u8]) -> io::Result<(IfInfoMsg, usize)> {
+        let mut cursor = Cursor::new(bytes);
+        let family = cursor.read_u8()?;
+        let _pad = cursor.read_u8()?;
+        let link_type = cursor.read_u16::<NativeEndian>()?;
+        let index = cursor.read_i32::<NativeEndian>()?;
+        let flags = cursor.read_u32::<NativeEndian>()?;
+        let change = cursor.read_u32::<NativeEndian>()?;
+        Ok((IfInfoMsg {
+            family,
+            link_type,
+            index,
+            flags,
+            change,
+        }, cursor.position() as usize))
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![self.family, 0];
+        bytes.extend_from_slice(&self.link_type.to_ne_bytes());
+        bytes.extend_from_slice(&self.index.to_ne_bytes());
+        bytes.extend_from_slice(&self.flags.to_ne_bytes());
+        bytes.extend_from_slice(&self.change.to_ne_bytes());
+        bytes
+    }
+}
+
+/// Interface counters (`struct rtnl_link_stats64`)
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct LinkStats64 {
+    rx_packets: u64,
+    tx_packets: u64,
+    rx_bytes: u64,
+    tx_bytes: u64,
+    rx_errors: u64,
+    tx_errors: u64,
+    rx_dropped: u64,
+    tx_dropped: u64,
+    multicast: u64,
+    collisions: u64,
+}
+
+impl LinkStats64 {
+    fn from_bytes(bytes: &[u8]) -> io::Result<LinkStats64> {
+        let mut cursor = Cursor::new(bytes);
+        Ok(LinkStats64 {
+            rx_packets: cursor.read_u64::<NativeEndian>()?,
+            tx_packets: cursor.read_u64::<NativeEndian>()?,
+            rx_bytes: cursor.read_u64::<NativeEndian>()?,
+            tx_bytes: cursor.read_u64::<NativeEndian>()?,
+            rx_errors: cursor.read_u64::<NativeEndian>()?,
+            tx_errors: cursor.read_u64::<NativeEndian>()?,
+            rx_dropped: cursor.read_u64::<NativeEndian>()?,
+            tx_dropped: cursor.read_u64::<NativeEndian>()?,
+            multicast: cursor.read_u64::<NativeEndian>()?,
+            collisions: cursor.read_u64::<NativeEndian>()?,
+        })
+    }
+
+    pub fn rx_packets(&self) -> u64 {
+        self.rx_packets
+    }
+
+    pub fn tx_packets(&self) -> u64 {
+        self.tx_packets
+    }
+
+    pub fn rx_bytes(&self) -> u64 {
+        self.rx_bytes
+    }
+
+    pub fn tx_bytes(&self) -> u64 {
+        self.tx_bytes
+    }
+
+    pub fn rx_errors(&self) -> u64 {
+        self.rx_errors
+    }
+
+    pub fn tx_errors(&self) -> u64 {
+        self.tx_errors
+    }
+
+    /// Packets received but dropped, e.g. for lack of buffer space
+    pub fn rx_dropped(&self) -> u64 {
+        self.rx_dropped
+    }
+
+    pub fn tx_dropped(&self) -> u64 {
+        self.tx_dropped
+    }
+
+    /// Multicast packets received
+    pub fn multicast(&self) -> u64 {
+        self.multicast
+    }
+
+    pub fn collisions(&self) -> u64 {
+        self.collisions
+    }
+}
+
+/// A network interface, as reported by RTM_GETLINK
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Link {
+    index: i32,
+    link_type: u16,
+    flags: IfFlags,
+    name: String,
+    address: Vec<u8>,
+    mtu: Option<u32>,
+    master: Option<i32>,
+    operstate: Option<u8>,
+    kind: Option<String>,
+    stats: Option<LinkStats64>,
+    vfs: Vec<VfInfo>,
+}
+
+impl Link {
+    fn from_bytes(bytes: &[u8]) -> io::Result<Link> {
+        let (ifi, n) = IfInfoMsg::from_bytes(bytes)?;
+        let mut link = Link {
+            index: ifi.index,
+            link_type: ifi.link_type,
+            flags: ifi.flags.into(),
+            name: String::new(),
+            address: vec![],
+            mtu: None,
+            master: None,
+            operstate: None,
+            kind: None,
+            stats: None,
+            vfs: vec![],
+        };
+
+        for attr in NlAttr::parse(&bytes[n..])? {
+            match attr.attr_type() {
+                IFLA_IFNAME => link.name = attr.get_str()?.to_owned(),
+                IFLA_ADDRESS => link.address = attr.payload().to_vec(),
+                IFLA_MTU => link.mtu = Some(attr.get_u32()?),
+                IFLA_MASTER => link.master = Some(attr.get_u32()? as i32),
+                IFLA_OPERSTATE => link.operstate = Some(attr.get_u8()?),
+                IFLA_LINKINFO => {
+                    for a in attr.nested()? {
+                        if a.attr_type() == IFLA_INFO_KIND {
+                            link.kind = Some(a.get_str()?.to_string());
+                        }
+                    }
+                },
+                IFLA_STATS64 => link.stats = Some(LinkStats64::from_bytes(attr.payload())?),
+                IFLA_VFINFO_LIST => link.vfs = vfs_from_attr(&attr)?,
+                _ => {},
+            }
+        }
+        Ok(link)
+    }
+
+    pub fn index(&self) -> i32 {
+        self.index
+    }
+
+    /// ARPHRD_* hardware type
+    pub fn link_type(&self) -> u16 {
+        self.link_type
+    }
+
+    /// Device flags, e.g. whether the link is up
+    pub fn flags(&self) -> IfFlags {
+        self.flags
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Hardware address, empty if the device has none
+    pub fn address(&self) -> &[u8] {
+        &self.address
+    }
+
+    /// Hardware address if it is an Ethernet address
+    pub fn mac(&self) -> Option<MacAddr> {
+        MacAddr::from_bytes(&self.address).ok()
+    }
+
+    pub fn mtu(&self) -> Option<u32> {
+        self.mtu
+    }
+
+    /// Index of the bridge or bond this link is enslaved to
+    pub fn master(&self) -> Option<i32> {
+        self.master
+    }
+
+    /// RFC 2863 operational state (IF_OPER_*)
+    pub fn operstate(&self) -> Option<u8> {
+        self.operstate
+    }
+
+    /// Driver kind of virtual links, e.g. "veth" or "bridge"
+    pub fn kind(&self) -> Option<&str> {
+        self.kind.as_deref()
+    }
+
+    /// 64-bit interface counters
+    pub fn stats(&self) -> Option<LinkStats64> {
+        self.stats
+    }
+
+    /// SR-IOV virtual functions, empty unless this is a physical function
+    pub fn vfs(&self) -> &[VfInfo] {
+        &self.vfs
+    }
+}
+
+/// Lists all network interfaces.
+pub fn links(socket: &mut impl NetlinkTransport) -> io::Result<Vec<Link>> {
+    let mut hdr = NlMsgHeader::user_defined(RTM_GETLINK);
+    hdr.dump();
+    let mut payload = IfInfoMsg { family: AF_UNSPEC as u8, ..Default::default() }.bytes();
+    payload.extend(ext_mask_attr());
+    let replies = exchange(socket, hdr, &payload)?;
+    replies.iter().map(|r| Link::from_bytes(r)).collect()
+}
+
+/// Looks up a network interface by index.
+pub fn link(socket: &mut impl NetlinkTransport, index: i32) -> io::Result<Link> {
+    get_link(socket, index, &[])
+}
+
+/// Looks up a network interface by name.
+pub fn link_by_name(socket: &mut impl NetlinkTransport, name: &str) -> io::Result<Link> {
+    get_link(socket, 0, &name_attr(name))
+}
+
+fn get_link(socket: &mut impl NetlinkTransport, index: i32, attrs: &[u8]) -> io::Result<Link> {
+    let hdr = NlMsgHeader::user_defined(RTM_GETLINK);
+    let mut payload = IfInfoMsg { family: AF_UNSPEC as u8, index, ..Default::default() }.bytes();
+    payload.extend_from_slice(attrs);
+    payload.extend(ext_mask_attr());
+    let replies = exchange(socket, hdr, &payload)?;
+    match replies.first() {
+        Some(reply) => Link::from_bytes(reply),
+        None => Err(io::Error::new(ErrorKind::NotFound, "no link reply")),
+    }
+}
+
+/// Deletes a network interface. Deleting one end of a veth pair deletes both.
+pub fn del_link(socket: &mut impl NetlinkTransport, index: i32) -> io::Result<()> {
+    let hdr = NlMsgHeader::user_defined(RTM_DELLINK);
+    let ifi = IfInfoMsg { family: AF_UNSPEC as u8, index, ..Default::default() };
+    exchange(socket, hdr, &ifi.bytes())?;
+    Ok(())
+}
+
+fn ext_mask_attr() -> Vec<u8> {
+    NlAttr::new(IFLA_EXT_MASK, &RTEXT_FILTER_VF.to_ne_bytes()).bytes()
+}
+
+fn name_attr(name: &str) -> Vec<u8> {
+    string_attr(IFLA_IFNAME, name)
+}
+
+// IFLA_IFNAME
+// IFLA_LINKINFO
+//     IFLA_INFO_KIND  "veth", "bridge", ...
+//     IFLA_INFO_DATA  kind specific attributes
+fn new_link_attrs(name: &str, kind: &str, data: &[u8]) -> Vec<u8> {
+    let mut info = NlAttr::new(IFLA_INFO_KIND, kind.as_bytes()).bytes();
+    if !data.is_empty() {
+        info.extend(NlAttr::new(IFLA_INFO_DATA | NLA_F_NESTED, data).bytes());
+    }
+    let mut bytes = name_attr(name);
+    bytes.extend(NlAttr::new(IFLA_LINKINFO | NLA_F_NESTED, &info).bytes());
+    bytes
+}
+
+/// Creates a link of driver `kind`, failing with `EEXIST` if `name` is taken.
+/// `data` holds the kind specific IFLA_INFO_DATA attributes.
+fn create_link(socket: &mut impl NetlinkTransport, name: &str, kind: &str, data: &[u8]) -> io::Result<()> {
+    send_new_link(socket, &new_link_attrs(name, kind, data))
+}
+
+/// Like `create_link`, for links stacked on the lower device `parent`.
+fn create_link_on(socket: &mut impl NetlinkTransport, parent: i32, name: &str, kind: &str, data: &[u8])
+    -> io::Result<()> {
+        let mut attrs = new_link_attrs(name, kind, data);
+        attrs.extend(NlAttr::new(IFLA_LINK, &parent.to_ne_bytes()).bytes());
+        send_new_link(socket, &attrs)
+    }
+
+fn send_new_link(socket: &mut impl NetlinkTransport, attrs: &[u8]) -> io::Result<()> {
+    let mut hdr = NlMsgHeader::user_defined(RTM_NEWLINK);
+    hdr.create().excl();
+    let mut payload = IfInfoMsg { family: AF_UNSPEC as u8, ..Default::default() }.bytes();
+    payload.extend_from_slice(attrs);
+    exchange(socket, hdr, &payload)?;
+    Ok(())
+}
+
+/// Deletes link `index` when dropped, so a test that fails half way does not
+/// leave it behind.
+#[cfg(test)]
+pub(crate) struct LinkGuard(pub(crate) i32);
+
+#[cfg(test)]
+impl Drop for LinkGuard {
+    fn drop(&mut self) {
+        if let Ok(mut socket) = ::socket::Socket::new(::Protocol::Route) {
+            let _ = del_link(&mut socket, self.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket::{Socket, NlAttr, NLA_F_NESTED};
+    use Protocol;
+
+    #[test]
+    fn test_ifinfomsg_roundtrip() {
+        let ifi = IfInfoMsg { family: 0, link_type: 1, index: 3, flags: 0x1003, change: !0 };
+        let bytes = ifi.bytes();
+        assert_eq!(bytes.len(), 16);
+        let (decoded, n) = IfInfoMsg::from_bytes(&bytes).unwrap();
+        assert_eq!(n, 16);
+        assert_eq!(decoded, ifi);
+    }
+
+    #[test]
+    fn test_link_decode() {
+        let mut bytes = IfInfoMsg { index: 2, ..Default::default() }.bytes();
+        bytes.extend(NlAttr::new(IFLA_IFNAME, b"veth0\0").bytes());
+        bytes.extend(NlAttr::new(IFLA_MTU, &1500u32.to_ne_bytes()).bytes());
+        bytes.extend(NlAttr::new(IFLA_ADDRESS, &[2, 0, 0, 0, 0, 1]).bytes());
+        let kind = NlAttr::new(IFLA_INFO_KIND, b"veth").bytes();
+        bytes.extend(NlAttr::new(IFLA_LINKINFO | NLA_F_NESTED, &kind).bytes());
+        let mut stats = vec![];
+        for v in 1..26u64 {
+            stats.extend_from_slice(&v.to_ne_bytes());
+        }
+        bytes.extend(NlAttr::new(IFLA_STATS64, &stats).bytes());
+
+        let link = Link::from_bytes(&bytes).unwrap();
+        assert_eq!(link.index(), 2);
+        assert_eq!(link.name(), "veth0");
+        assert_eq!(link.mtu(), Some(1500));
+        assert_eq!(link.mac(), Some(MacAddr::new([2, 0, 0, 0, 0, 1])));
+        assert_eq!(link.kind(), Some("veth"));
+        let stats = link.stats().unwrap();
+        assert_eq!(stats.rx_packets(), 1);
+        assert_eq!(stats.tx_bytes(), 4);
+        assert_eq!(stats.tx_dropped(), 8);
+        assert_eq!(stats.collisions(), 10);
+    }
+
+    #[test]
+    fn test_link_vfs() {
+        // struct ifla_vf_mac and struct ifla_vf_vlan_info of VF 1
+        let mut mac = 1u32.to_ne_bytes().to_vec();
+        mac.extend_from_slice(&[2, 0, 0, 0, 0, 9]);
+        mac.resize(36, 0);
+        let mut vlan = vec![];
+        for v in &[1u32, 100, 3] {
+            vlan.extend_from_slice(&v.to_ne_bytes());
+        }
+        vlan.extend_from_slice(&[0x88, 0xa8, 0, 0]);
+        let mut vf = NlAttr::new(1, &mac).bytes();
+        vf.extend(NlAttr::new(12 | NLA_F_NESTED, &NlAttr::new(1, &vlan).bytes()).bytes());
+        let mut trust = 1u32.to_ne_bytes().to_vec();
+        trust.extend_from_slice(&1u32.to_ne_bytes());
+        vf.extend(NlAttr::new(9, &trust).bytes());
+        let untagged = NlAttr::new(2, &[0; 12]).bytes();
+        // Each element is an IFLA_VF_INFO rather than an ordinal
+        let mut list = NlAttr::new(1 | NLA_F_NESTED, &untagged).bytes();
+        list.extend(NlAttr::new(1 | NLA_F_NESTED, &vf).bytes());
+
+        let mut bytes = IfInfoMsg { index: 4, ..Default::default() }.bytes();
+        bytes.extend(NlAttr::new(IFLA_VFINFO_LIST | NLA_F_NESTED, &list).bytes());
+        let link = Link::from_bytes(&bytes).unwrap();
+        let vfs = link.vfs();
+        assert_eq!(vfs.len(), 2);
+        assert!(vfs[0].vlans().is_empty());
+        assert_eq!(vfs[1].vf(), 1);
+        assert_eq!(vfs[1].mac(), Some(MacAddr::new([2, 0, 0, 0, 0, 9])));
+        assert_eq!(vfs[1].trust(), Some(true));
+        assert_eq!(vfs[1].spoofchk(), None);
+        let vlan = vfs[1].vlans()[0];
+        assert_eq!((vlan.vid(), vlan.qos(), vlan.proto()), (100, 3, 0x88a8));
+    }
+
+    #[test]
+    fn test_dump_links() {
+        let mut socket = Socket::new(Protocol::Route).unwrap();
+        let links = links(&mut socket).unwrap();
+        let lo = links.iter().find(|l| l.name() == "lo").unwrap();
+        assert!(lo.stats().is_some());
+        assert!(lo.flags().is_loopback());
+
+        let by_index = link(&mut socket, lo.index()).unwrap();
+        assert_eq!(by_index.name(), "lo");
+    }
+}
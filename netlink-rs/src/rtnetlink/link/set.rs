@@ -0,0 +1,181 @@
+use super::{name_attr, IfInfoMsg, IfFlags, IFLA_MTU, IFLA_MASTER};
+use super::super::{exchange, RTM_SETLINK};
+use socket::{NetlinkTransport, NlMsgHeader, NlAttr};
+
+use std::io;
+
+use libc::AF_UNSPEC;
+
+/// Changes to an existing link (`ip link set DEV ...`)
+///
+/// Only the properties that were set are sent; all of them are applied
+/// atomically by one RTM_SETLINK request.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct LinkSet {
+    index: i32,
+    flags: IfFlags,
+    change: IfFlags,
+    mtu: Option<u32>,
+    name: Option<String>,
+    master: Option<i32>,
+}
+
+impl LinkSet {
+    pub fn new(index: i32) -> LinkSet {
+        LinkSet {
+            index,
+            flags: IfFlags::empty(),
+            change: IfFlags::empty(),
+            mtu: None,
+            name: None,
+            master: None,
+        }
+    }
+
+    /// Set administrative state up
+    pub fn up(&mut self) -> &mut LinkSet {
+        self.set_flags(IfFlags::UP, true)
+    }
+
+    /// Set administrative state down
+    pub fn down(&mut self) -> &mut LinkSet {
+        self.set_flags(IfFlags::UP, false)
+    }
+
+    /// Set or clear `flags`, e.g. `IfFlags::PROMISC`; other flags are left
+    /// as they are
+    pub fn set_flags(&mut self, flags: IfFlags, on: bool) -> &mut LinkSet {
+        self.change |= flags;
+        if on {
+            self.flags |= flags;
+        } else {
+            self.flags.remove(flags);
+        }
+        self
+    }
+
+    pub fn set_mtu(&mut self, mtu: u32) -> &mut LinkSet {
+        self.mtu = Some(mtu);
+        self
+    }
+
+    /// Rename the link; the kernel refuses this while the link is up
+    pub fn set_name(&mut self, name: &str) -> &mut LinkSet {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Enslave the link to the bridge or bond `master`
+    pub fn set_master(&mut self, master: i32) -> &mut LinkSet {
+        self.master = Some(master);
+        self
+    }
+
+    /// Release the link from its master
+    pub fn nomaster(&mut self) -> &mut LinkSet {
+        self.master = Some(0);
+        self
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        // ifi_change selects the flags that ifi_flags overwrites
+        let ifi = IfInfoMsg {
+            family: AF_UNSPEC as u8,
+            index: self.index,
+            flags: self.flags.bits(),
+            change: self.change.bits(),
+            ..Default::default()
+        };
+
+        let mut bytes = ifi.bytes();
+        if let Some(mtu) = self.mtu {
+            bytes.extend(NlAttr::new(IFLA_MTU, &mtu.to_ne_bytes()).bytes());
+        }
+        if let Some(ref name) = self.name {
+            bytes.extend(name_attr(name));
+        }
+        if let Some(master) = self.master {
+            bytes.extend(NlAttr::new(IFLA_MASTER, &master.to_ne_bytes()).bytes());
+        }
+        bytes
+    }
+}
+
+/// Applies `change` to its link.
+pub fn set_link(socket: &mut impl NetlinkTransport, change: &LinkSet) -> io::Result<()> {
+    let hdr = NlMsgHeader::user_defined(RTM_SETLINK);
+    exchange(socket, hdr, &change.bytes())?;
+    Ok(())
+}
+
+/// Enslaves link `index` to the bridge or bond `master`.
+pub fn set_master(socket: &mut impl NetlinkTransport, index: i32, master: i32) -> io::Result<()> {
+    set_link(socket, LinkSet::new(index).set_master(master))
+}
+
+/// Releases link `index` from its master (`ip link set DEV nomaster`).
+pub fn set_nomaster(socket: &mut impl NetlinkTransport, index: i32) -> io::Result<()> {
+    set_link(socket, LinkSet::new(index).nomaster())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{create_veth, link_by_name, IfInfoMsg, LinkGuard};
+    use socket::{Socket, NlAttr};
+    use Protocol;
+    use netns;
+
+    use libc::{EPERM, IFF_UP};
+
+    #[test]
+    fn test_linkset_flags() {
+        let (ifi, n) = IfInfoMsg::from_bytes(&LinkSet::new(4).up().bytes()).unwrap();
+        assert_eq!(n, 16);
+        assert_eq!(ifi.index, 4);
+        assert_eq!(ifi.flags, IFF_UP as u32);
+        assert_eq!(ifi.change, IFF_UP as u32);
+
+        let bytes = LinkSet::new(4).down().set_mtu(9000).bytes();
+        let (ifi, n) = IfInfoMsg::from_bytes(&bytes).unwrap();
+        assert_eq!(ifi.flags, 0);
+        assert_eq!(ifi.change, IFF_UP as u32);
+        let attrs = NlAttr::parse(&bytes[n..]).unwrap();
+        assert_eq!(attrs.len(), 1);
+        assert_eq!(attrs[0].payload(), &9000u32.to_ne_bytes());
+
+        // Without up/down no flag is touched
+        let (ifi, _) = IfInfoMsg::from_bytes(&LinkSet::new(4).set_name("x").bytes()).unwrap();
+        assert_eq!(ifi.change, 0);
+
+        let bytes = LinkSet::new(4).up().set_flags(IfFlags::PROMISC | IfFlags::NOARP, true)
+            .set_flags(IfFlags::NOARP, false).bytes();
+        let (ifi, _) = IfInfoMsg::from_bytes(&bytes).unwrap();
+        assert_eq!(IfFlags::from(ifi.flags), IfFlags::UP | IfFlags::PROMISC);
+        assert_eq!(IfFlags::from(ifi.change), IfFlags::UP | IfFlags::PROMISC | IfFlags::NOARP);
+    }
+
+    #[test]
+    fn test_set_link() {
+        netns::run(|| {
+            let mut socket = Socket::new(Protocol::Route).unwrap();
+            match create_veth(&mut socket, "nlrs-set0", "nlrs-set1") {
+                Err(ref e) if e.raw_os_error() == Some(EPERM) => return,
+                r => r.unwrap(),
+            }
+            let link = link_by_name(&mut socket, "nlrs-set0").unwrap();
+            let _guard = LinkGuard(link.index());
+
+            set_link(&mut socket, LinkSet::new(link.index()).set_mtu(1400).set_name("nlrs-set2")).unwrap();
+            let renamed = link_by_name(&mut socket, "nlrs-set2").unwrap();
+            assert_eq!(renamed.index(), link.index());
+            assert_eq!(renamed.mtu(), Some(1400));
+
+            set_link(&mut socket, LinkSet::new(link.index()).up()).unwrap();
+            assert!(link_by_name(&mut socket, "nlrs-set2").unwrap().flags().is_up());
+            set_link(&mut socket, LinkSet::new(link.index()).down()).unwrap();
+            assert!(!link_by_name(&mut socket, "nlrs-set2").unwrap().flags().is_up());
+        });
+    }
+}
@@ -0,0 +1,174 @@
+use super::LinkStats64;
+use super::super::{exchange, RTM_GETSTATS};
+use socket::{NetlinkTransport, NlMsgHeader, NlAttr};
+
+use std::io::{self, Cursor, ErrorKind};
+
+use byteorder::{NativeEndian, ReadBytesExt};
+use libc::AF_UNSPEC;
+
+const IFLA_STATS_LINK_64: u16 = 1;
+const IFLA_STATS_LINK_OFFLOAD_XSTATS: u16 = 4;
+
+const IFLA_OFFLOAD_XSTATS_CPU_HIT: u16 = 1;
+
+/// Attribute groups of RTM_GETSTATS; only the requested groups are
+/// collected and sent by the kernel
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum StatsGroup {
+    /// Software counters, as IFLA_STATS64 of RTM_GETLINK
+    Link64,
+    /// Counters of hardware offloaded devices, e.g. packets that hit the CPU
+    /// instead of being forwarded by the switch ASIC
+    OffloadXstats,
+}
+
+impl From<StatsGroup> for u16 {
+    fn from(g: StatsGroup) -> u16 {
+        match g {
+            StatsGroup::Link64 => IFLA_STATS_LINK_64,
+            StatsGroup::OffloadXstats => IFLA_STATS_LINK_OFFLOAD_XSTATS,
+        }
+    }
+}
+
+// Bit `attr - 1` of the filter mask selects attribute `attr`
+fn filter_mask(groups: &[StatsGroup]) -> u32 {
+    groups.iter().fold(0, |mask, &g| mask | 1 << (u16::from(g) - 1))
+}
+
+// HEADER FORMAT
+// __u8  family;
+// __u8  pad1;
+// __u16 pad2;
+// __u32 ifindex;
+// __u32 filter_mask;
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+struct IfStatsMsg {
+    ifindex: i32,
+    filter_mask: u32,
+}
+
+impl IfStatsMsg {
+    fn from_bytes(bytes: &[u8]) -> io::Result<(IfStatsMsg, usize)> {
+        let mut cursor = Cursor::new(bytes);
+        let _family = cursor.read_u8()?;
+        let _pad1 = cursor.read_u8()?;
+        let _pad2 = cursor.read_u16::<NativeEndian>()?;
+        let ifindex = cursor.read_i32::<NativeEndian>()?;
+        let filter_mask = cursor.read_u32::<NativeEndian>()?;
+        Ok((IfStatsMsg {
+            ifindex,
+            filter_mask,
+        }, cursor.position() as usize))
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![AF_UNSPEC as u8, 0, 0, 0];
+        bytes.extend_from_slice(&self.ifindex.to_ne_bytes());
+        bytes.extend_from_slice(&self.filter_mask.to_ne_bytes());
+        bytes
+    }
+}
+
+/// Statistics of one interface, as reported by RTM_GETSTATS
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct InterfaceStats {
+    ifindex: i32,
+    link64: Option<LinkStats64>,
+    cpu_hit: Option<LinkStats64>,
+}
+
+impl InterfaceStats {
+    fn from_bytes(bytes: &[u8]) -> io::Result<InterfaceStats> {
+        let (ifsm, n) = IfStatsMsg::from_bytes(bytes)?;
+        let mut stats = InterfaceStats {
+            ifindex: ifsm.ifindex,
+            link64: None,
+            cpu_hit: None,
+        };
+        for attr in NlAttr::parse(&bytes[n..])? {
+            match attr.attr_type() {
+                IFLA_STATS_LINK_64 => stats.link64 = Some(LinkStats64::from_bytes(attr.payload())?),
+                IFLA_STATS_LINK_OFFLOAD_XSTATS => {
+                    for a in attr.nested()? {
+                        if a.attr_type() == IFLA_OFFLOAD_XSTATS_CPU_HIT {
+                            stats.cpu_hit = Some(LinkStats64::from_bytes(a.payload())?);
+                        }
+                    }
+                },
+                _ => {},
+            }
+        }
+        Ok(stats)
+    }
+
+    pub fn ifindex(&self) -> i32 {
+        self.ifindex
+    }
+
+    /// Software counters, if `StatsGroup::Link64` was requested
+    pub fn link64(&self) -> Option<LinkStats64> {
+        self.link64
+    }
+
+    /// Packets handled by the CPU rather than the offload device, if
+    /// `StatsGroup::OffloadXstats` was requested and the device reports them
+    pub fn cpu_hit(&self) -> Option<LinkStats64> {
+        self.cpu_hit
+    }
+}
+
+/// Collects `groups` of interface `ifindex`.
+pub fn interface_stats(socket: &mut impl NetlinkTransport, ifindex: i32, groups: &[StatsGroup]) -> io::Result<InterfaceStats> {
+    let hdr = NlMsgHeader::user_defined(RTM_GETSTATS);
+    let ifsm = IfStatsMsg { ifindex, filter_mask: filter_mask(groups) };
+    let replies = exchange(socket, hdr, &ifsm.bytes())?;
+    match replies.first() {
+        Some(reply) => InterfaceStats::from_bytes(reply),
+        None => Err(io::Error::new(ErrorKind::NotFound, "no stats reply")),
+    }
+}
+
+/// Collects `groups` of all interfaces.
+pub fn all_interface_stats(socket: &mut impl NetlinkTransport, groups: &[StatsGroup]) -> io::Result<Vec<InterfaceStats>> {
+    let mut hdr = NlMsgHeader::user_defined(RTM_GETSTATS);
+    hdr.dump();
+    let ifsm = IfStatsMsg { ifindex: 0, filter_mask: filter_mask(groups) };
+    let replies = exchange(socket, hdr, &ifsm.bytes())?;
+    replies.iter().map(|r| InterfaceStats::from_bytes(r)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rtnetlink::link::link_by_name;
+    use socket::Socket;
+    use Protocol;
+
+    #[test]
+    fn test_filter_mask() {
+        assert_eq!(filter_mask(&[]), 0);
+        assert_eq!(filter_mask(&[StatsGroup::Link64]), 0x1);
+        assert_eq!(filter_mask(&[StatsGroup::Link64, StatsGroup::OffloadXstats]), 0x9);
+    }
+
+    #[test]
+    fn test_interface_stats() {
+        let mut socket = Socket::new(Protocol::Route).unwrap();
+        let lo = link_by_name(&mut socket, "lo").unwrap();
+
+        let stats = interface_stats(&mut socket, lo.index(), &[StatsGroup::Link64]).unwrap();
+        assert_eq!(stats.ifindex(), lo.index());
+        assert!(stats.link64().is_some());
+        assert_eq!(stats.cpu_hit(), None);
+
+        let all = all_interface_stats(&mut socket, &[StatsGroup::OffloadXstats]).unwrap();
+        let lo_stats = all.iter().find(|s| s.ifindex() == lo.index()).unwrap();
+        // Only the requested group is sent, and lo is not offloaded
+        assert_eq!(lo_stats.link64(), None);
+        assert_eq!(lo_stats.cpu_hit(), None);
+    }
+}
@@ -0,0 +1,206 @@
+use super::create_link;
+use socket::{NetlinkTransport, NlAttr};
+
+use std::io::{self, ErrorKind};
+use std::net::Ipv4Addr;
+
+const IFLA_GRE_LINK: u16 = 1;
+const IFLA_GRE_IFLAGS: u16 = 2;
+const IFLA_GRE_OFLAGS: u16 = 3;
+const IFLA_GRE_IKEY: u16 = 4;
+const IFLA_GRE_OKEY: u16 = 5;
+const IFLA_GRE_LOCAL: u16 = 6;
+const IFLA_GRE_REMOTE: u16 = 7;
+const IFLA_GRE_TTL: u16 = 8;
+
+const IFLA_IPTUN_LINK: u16 = 1;
+const IFLA_IPTUN_LOCAL: u16 = 2;
+const IFLA_IPTUN_REMOTE: u16 = 3;
+const IFLA_IPTUN_TTL: u16 = 4;
+
+// #define GRE_KEY     __cpu_to_be16(0x2000)
+const GRE_KEY: u16 = 0x2000;
+
+/// IPv4 point-to-point tunnel drivers
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum TunnelKind {
+    /// Generic routing encapsulation
+    Gre,
+    /// IPv4 in IPv4
+    Ipip,
+    /// IPv6 in IPv4
+    Sit,
+}
+
+impl TunnelKind {
+    fn name(&self) -> &'static str {
+        match *self {
+            TunnelKind::Gre => "gre",
+            TunnelKind::Ipip => "ipip",
+            TunnelKind::Sit => "sit",
+        }
+    }
+}
+
+/// Options of a tunnel link (`ip link add NAME type gre remote ADDR ...`)
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct TunnelConfig {
+    name: String,
+    kind: TunnelKind,
+    local: Option<Ipv4Addr>,
+    remote: Option<Ipv4Addr>,
+    ttl: Option<u8>,
+    key: Option<u32>,
+    link: Option<i32>,
+}
+
+impl TunnelConfig {
+    pub fn new(name: &str, kind: TunnelKind) -> TunnelConfig {
+        TunnelConfig {
+            name: name.into(),
+            kind,
+            local: None,
+            remote: None,
+            ttl: None,
+            key: None,
+            link: None,
+        }
+    }
+
+    pub fn set_local(&mut self, addr: Ipv4Addr) -> &mut TunnelConfig {
+        self.local = Some(addr);
+        self
+    }
+
+    pub fn set_remote(&mut self, addr: Ipv4Addr) -> &mut TunnelConfig {
+        self.remote = Some(addr);
+        self
+    }
+
+    /// TTL of the outer header, 0 inherits it from the inner packet
+    pub fn set_ttl(&mut self, ttl: u8) -> &mut TunnelConfig {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// GRE key used in both directions, only valid for `TunnelKind::Gre`
+    pub fn set_key(&mut self, key: u32) -> &mut TunnelConfig {
+        self.key = Some(key);
+        self
+    }
+
+    /// Bind the tunnel to an underlay device
+    pub fn set_link(&mut self, index: i32) -> &mut TunnelConfig {
+        self.link = Some(index);
+        self
+    }
+
+    fn data(&self) -> io::Result<Vec<u8>> {
+        let mut bytes = vec![];
+        if self.kind == TunnelKind::Gre {
+            if let Some(link) = self.link {
+                bytes.extend(NlAttr::new(IFLA_GRE_LINK, &link.to_ne_bytes()).bytes());
+            }
+            if let Some(addr) = self.local {
+                bytes.extend(NlAttr::new(IFLA_GRE_LOCAL, &addr.octets()).bytes());
+            }
+            if let Some(addr) = self.remote {
+                bytes.extend(NlAttr::new(IFLA_GRE_REMOTE, &addr.octets()).bytes());
+            }
+            if let Some(ttl) = self.ttl {
+                bytes.extend(NlAttr::new(IFLA_GRE_TTL, &[ttl]).bytes());
+            }
+            if let Some(key) = self.key {
+                // Keys and flags are in network byte order
+                for &t in &[IFLA_GRE_IFLAGS, IFLA_GRE_OFLAGS] {
+                    bytes.extend(NlAttr::new(t, &GRE_KEY.to_be_bytes()).bytes());
+                }
+                for &t in &[IFLA_GRE_IKEY, IFLA_GRE_OKEY] {
+                    bytes.extend(NlAttr::new(t, &key.to_be_bytes()).bytes());
+                }
+            }
+        } else {
+            if self.key.is_some() {
+                return Err(io::Error::new(ErrorKind::InvalidInput, "only gre tunnels have a key"));
+            }
+            if let Some(link) = self.link {
+                bytes.extend(NlAttr::new(IFLA_IPTUN_LINK, &link.to_ne_bytes()).bytes());
+            }
+            if let Some(addr) = self.local {
+                bytes.extend(NlAttr::new(IFLA_IPTUN_LOCAL, &addr.octets()).bytes());
+            }
+            if let Some(addr) = self.remote {
+                bytes.extend(NlAttr::new(IFLA_IPTUN_REMOTE, &addr.octets()).bytes());
+            }
+            if let Some(ttl) = self.ttl {
+                bytes.extend(NlAttr::new(IFLA_IPTUN_TTL, &[ttl]).bytes());
+            }
+        }
+        Ok(bytes)
+    }
+}
+
+/// Creates a tunnel link as described by `config`.
+pub fn create_tunnel(socket: &mut impl NetlinkTransport, config: &TunnelConfig) -> io::Result<()> {
+    create_link(socket, &config.name, config.kind.name(), &config.data()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{link_by_name, LinkGuard};
+    use socket::{Socket, NlAttr};
+    use Protocol;
+    use netns;
+
+    use std::net::Ipv4Addr;
+
+    use libc::{EPERM, EOPNOTSUPP};
+
+    #[test]
+    fn test_gre_data() {
+        let mut config = TunnelConfig::new("gre1", TunnelKind::Gre);
+        config.set_remote(Ipv4Addr::new(192, 0, 2, 1)).set_ttl(64).set_key(7);
+
+        let data = config.data().unwrap();
+        let attrs = NlAttr::parse(&data).unwrap();
+        let types: Vec<u16> = attrs.iter().map(|a| a.attr_type()).collect();
+        assert_eq!(types, [IFLA_GRE_REMOTE, IFLA_GRE_TTL, IFLA_GRE_IFLAGS, IFLA_GRE_OFLAGS,
+                           IFLA_GRE_IKEY, IFLA_GRE_OKEY]);
+        assert_eq!(attrs[0].payload(), &[192, 0, 2, 1]);
+        assert_eq!(attrs[2].payload(), &[0x20, 0]);
+        assert_eq!(attrs[4].payload(), &[0, 0, 0, 7]);
+    }
+
+    #[test]
+    fn test_iptun_data() {
+        let mut config = TunnelConfig::new("sit1", TunnelKind::Sit);
+        config.set_local(Ipv4Addr::new(192, 0, 2, 2));
+        let data = config.data().unwrap();
+        let attrs = NlAttr::parse(&data).unwrap();
+        assert_eq!(attrs[0].attr_type(), IFLA_IPTUN_LOCAL);
+
+        config.set_key(1);
+        assert!(config.data().is_err());
+    }
+
+    #[test]
+    fn test_create_ipip() {
+        netns::run(|| {
+            let mut socket = Socket::new(Protocol::Route).unwrap();
+            let mut config = TunnelConfig::new("nlrs-ipip0", TunnelKind::Ipip);
+            config.set_remote(Ipv4Addr::new(192, 0, 2, 1));
+            match create_tunnel(&mut socket, &config) {
+                // No ipip driver in this kernel
+                Err(ref e) if e.raw_os_error() == Some(EOPNOTSUPP) => return,
+                Err(ref e) if e.raw_os_error() == Some(EPERM) => return,
+                r => r.unwrap(),
+            }
+            let link = link_by_name(&mut socket, "nlrs-ipip0").unwrap();
+            let _guard = LinkGuard(link.index());
+            assert_eq!(link.kind(), Some("ipip"));
+        });
+    }
+}
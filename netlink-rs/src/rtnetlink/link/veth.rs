@@ -0,0 +1,66 @@
+use super::{create_link, name_attr, IfInfoMsg};
+use socket::{NetlinkTransport, NlAttr};
+
+use std::io;
+
+use libc::AF_UNSPEC;
+
+const VETH_INFO_PEER: u16 = 1;
+
+// IFLA_INFO_DATA
+//     VETH_INFO_PEER
+//         struct ifinfomsg
+//         IFLA_IFNAME
+fn veth_data(peer: &str) -> Vec<u8> {
+    let mut peer_info = IfInfoMsg { family: AF_UNSPEC as u8, ..Default::default() }.bytes();
+    peer_info.extend(name_attr(peer));
+    NlAttr::new(VETH_INFO_PEER, &peer_info).bytes()
+}
+
+/// Creates a veth pair `name_a` <-> `name_b` (`ip link add A type veth peer name B`).
+pub fn create_veth(socket: &mut impl NetlinkTransport, name_a: &str, name_b: &str) -> io::Result<()> {
+    create_link(socket, name_a, "veth", &veth_data(name_b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{link_by_name, del_link, LinkGuard};
+    use socket::{Socket, NlAttr};
+    use Protocol;
+    use netns;
+
+    use libc::EPERM;
+
+    #[test]
+    fn test_veth_data() {
+        let data = veth_data("peer0");
+        let attrs = NlAttr::parse(&data).unwrap();
+        assert_eq!(attrs.len(), 1);
+        assert_eq!(attrs[0].attr_type(), VETH_INFO_PEER);
+
+        let payload = attrs[0].payload();
+        let (ifi, n) = IfInfoMsg::from_bytes(payload).unwrap();
+        assert_eq!(ifi.index, 0);
+        assert_eq!(&payload[n..], &name_attr("peer0")[..]);
+    }
+
+    #[test]
+    fn test_create_veth() {
+        netns::run(|| {
+            let mut socket = Socket::new(Protocol::Route).unwrap();
+            match create_veth(&mut socket, "nlrs-veth0", "nlrs-veth1") {
+                Err(ref e) if e.raw_os_error() == Some(EPERM) => return,
+                r => r.unwrap(),
+            }
+            let a = link_by_name(&mut socket, "nlrs-veth0").unwrap();
+            let _guard = LinkGuard(a.index());
+            let b = link_by_name(&mut socket, "nlrs-veth1").unwrap();
+            assert_eq!(a.kind(), Some("veth"));
+            assert_eq!(b.kind(), Some("veth"));
+
+            del_link(&mut socket, a.index()).unwrap();
+            assert!(link_by_name(&mut socket, "nlrs-veth1").is_err());
+        });
+    }
+}
@@ -0,0 +1,189 @@
+use socket::{NlAttr, MacAddr};
+
+use std::io::{self, Cursor};
+
+use byteorder::{BigEndian, NativeEndian, ReadBytesExt};
+
+const IFLA_VF_INFO: u16 = 1;
+
+const IFLA_VF_MAC: u16 = 1;
+const IFLA_VF_VLAN: u16 = 2;
+const IFLA_VF_SPOOFCHK: u16 = 4;
+const IFLA_VF_LINK_STATE: u16 = 5;
+const IFLA_VF_RATE: u16 = 6;
+const IFLA_VF_TRUST: u16 = 9;
+const IFLA_VF_VLAN_LIST: u16 = 12;
+
+const IFLA_VF_VLAN_INFO: u16 = 1;
+
+const ETH_P_8021Q: u16 = 0x8100;
+
+/// A VLAN an SR-IOV virtual function tags its traffic with
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct VfVlan {
+    vid: u32,
+    qos: u32,
+    proto: u16,
+}
+
+impl VfVlan {
+    // struct ifla_vf_vlan_info {
+    //     __u32  vf;
+    //     __u32  vlan;
+    //     __u32  qos;
+    //     __be16 vlan_proto;
+    // };
+    fn from_bytes(bytes: &[u8]) -> io::Result<VfVlan> {
+        let mut cursor = Cursor::new(bytes);
+        let _vf = cursor.read_u32::<NativeEndian>()?;
+        Ok(VfVlan {
+            vid: cursor.read_u32::<NativeEndian>()?,
+            qos: cursor.read_u32::<NativeEndian>()?,
+            proto: cursor.read_u16::<BigEndian>()?,
+        })
+    }
+
+    pub fn vid(&self) -> u32 {
+        self.vid
+    }
+
+    /// 802.1p priority
+    pub fn qos(&self) -> u32 {
+        self.qos
+    }
+
+    /// Tag protocol, ETH_P_8021Q or ETH_P_8021AD
+    pub fn proto(&self) -> u16 {
+        self.proto
+    }
+}
+
+/// Configuration of an SR-IOV virtual function of a physical link
+/// (`ip link show` "vf" lines)
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct VfInfo {
+    vf: u32,
+    mac: Option<MacAddr>,
+    vlans: Vec<VfVlan>,
+    min_tx_rate: Option<u32>,
+    max_tx_rate: Option<u32>,
+    spoofchk: Option<bool>,
+    trust: Option<bool>,
+    link_state: Option<u32>,
+}
+
+impl VfInfo {
+    // IFLA_VF_INFO
+    //     IFLA_VF_MAC         struct ifla_vf_mac { __u32 vf; __u8 mac[32]; }
+    //     IFLA_VF_VLAN        struct ifla_vf_vlan { __u32 vf, vlan, qos; }
+    //     IFLA_VF_VLAN_LIST
+    //         IFLA_VF_VLAN_INFO  struct ifla_vf_vlan_info, repeated
+    //     IFLA_VF_RATE        struct ifla_vf_rate { __u32 vf, min_tx_rate, max_tx_rate; }
+    //     IFLA_VF_SPOOFCHK, IFLA_VF_TRUST, IFLA_VF_LINK_STATE  { __u32 vf, setting; }
+    fn from_attr(attr: &NlAttr) -> io::Result<VfInfo> {
+        let mut info = VfInfo {
+            vf: 0,
+            mac: None,
+            vlans: vec![],
+            min_tx_rate: None,
+            max_tx_rate: None,
+            spoofchk: None,
+            trust: None,
+            link_state: None,
+        };
+        let mut vlan = None;
+        for a in attr.nested()? {
+            let mut cursor = Cursor::new(a.payload());
+            // Every member starts with the VF number
+            info.vf = cursor.read_u32::<NativeEndian>()?;
+            match a.attr_type() {
+                IFLA_VF_MAC => {
+                    let p = a.payload();
+                    info.mac = MacAddr::from_bytes(&p[4..p.len().min(10)]).ok();
+                },
+                IFLA_VF_VLAN => {
+                    let vid = cursor.read_u32::<NativeEndian>()?;
+                    let qos = cursor.read_u32::<NativeEndian>()?;
+                    vlan = Some(VfVlan { vid, qos, proto: ETH_P_8021Q });
+                },
+                IFLA_VF_VLAN_LIST => {
+                    for entry in a.array() {
+                        let (vlan_type, entry) = entry?;
+                        if vlan_type == IFLA_VF_VLAN_INFO {
+                            info.vlans.push(VfVlan::from_bytes(entry.payload())?);
+                        }
+                    }
+                },
+                IFLA_VF_RATE => {
+                    info.min_tx_rate = Some(cursor.read_u32::<NativeEndian>()?);
+                    info.max_tx_rate = Some(cursor.read_u32::<NativeEndian>()?);
+                },
+                IFLA_VF_SPOOFCHK => info.spoofchk = Some(cursor.read_u32::<NativeEndian>()? != 0),
+                IFLA_VF_TRUST => info.trust = Some(cursor.read_u32::<NativeEndian>()? != 0),
+                IFLA_VF_LINK_STATE => info.link_state = Some(cursor.read_u32::<NativeEndian>()?),
+                _ => {},
+            }
+        }
+        // Kernels without IFLA_VF_VLAN_LIST only report the 802.1Q tag, and
+        // an untagged VF is reported as VLAN 0
+        if info.vlans.is_empty() {
+            info.vlans.extend(vlan);
+        }
+        info.vlans.retain(|v| v.vid != 0);
+        Ok(info)
+    }
+
+    /// Index of the virtual function on its physical link
+    pub fn vf(&self) -> u32 {
+        self.vf
+    }
+
+    /// Administratively set MAC address, all zero if unset
+    pub fn mac(&self) -> Option<MacAddr> {
+        self.mac
+    }
+
+    /// VLANs the VF traffic is tagged with, empty if it is untagged
+    pub fn vlans(&self) -> &[VfVlan] {
+        &self.vlans
+    }
+
+    /// Minimum transmit rate in Mbit/s, 0 if unlimited
+    pub fn min_tx_rate(&self) -> Option<u32> {
+        self.min_tx_rate
+    }
+
+    /// Maximum transmit rate in Mbit/s, 0 if unlimited
+    pub fn max_tx_rate(&self) -> Option<u32> {
+        self.max_tx_rate
+    }
+
+    /// Whether frames with a spoofed source MAC are dropped
+    pub fn spoofchk(&self) -> Option<bool> {
+        self.spoofchk
+    }
+
+    /// Whether the VF may change its MAC address and enter promiscuous mode
+    pub fn trust(&self) -> Option<bool> {
+        self.trust
+    }
+
+    /// IFLA_VF_LINK_STATE_* (auto, enable or disable)
+    pub fn link_state(&self) -> Option<u32> {
+        self.link_state
+    }
+}
+
+/// Decodes IFLA_VFINFO_LIST, an array of IFLA_VF_INFO.
+pub(super) fn vfs_from_attr(attr: &NlAttr) -> io::Result<Vec<VfInfo>> {
+    let mut vfs = vec![];
+    for entry in attr.array() {
+        let (vf_type, entry) = entry?;
+        if vf_type == IFLA_VF_INFO {
+            vfs.push(VfInfo::from_attr(&entry)?);
+        }
+    }
+    Ok(vfs)
+}
@@ -0,0 +1,161 @@
+use super::create_link;
+use socket::{NetlinkTransport, NlAttr};
+
+use std::io;
+use std::net::IpAddr;
+
+const IFLA_VXLAN_ID: u16 = 1;
+const IFLA_VXLAN_GROUP: u16 = 2;
+const IFLA_VXLAN_LINK: u16 = 3;
+const IFLA_VXLAN_LOCAL: u16 = 4;
+const IFLA_VXLAN_TTL: u16 = 5;
+const IFLA_VXLAN_LEARNING: u16 = 7;
+const IFLA_VXLAN_PORT: u16 = 15;
+const IFLA_VXLAN_GROUP6: u16 = 16;
+const IFLA_VXLAN_LOCAL6: u16 = 17;
+
+/// IANA assigned VXLAN port; the kernel defaults to the legacy 8472
+pub const VXLAN_PORT: u16 = 4789;
+
+/// Options of a vxlan link (`ip link add NAME type vxlan id VNI ...`)
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct VxlanConfig {
+    name: String,
+    vni: u32,
+    remote: Option<IpAddr>,
+    local: Option<IpAddr>,
+    link: Option<i32>,
+    port: Option<u16>,
+    ttl: Option<u8>,
+    learning: Option<bool>,
+}
+
+impl VxlanConfig {
+    pub fn new(name: &str, vni: u32) -> VxlanConfig {
+        VxlanConfig {
+            name: name.into(),
+            vni,
+            remote: None,
+            local: None,
+            link: None,
+            port: None,
+            ttl: None,
+            learning: None,
+        }
+    }
+
+    /// Unicast remote VTEP, or multicast group to join
+    pub fn set_remote(&mut self, addr: IpAddr) -> &mut VxlanConfig {
+        self.remote = Some(addr);
+        self
+    }
+
+    /// Source address of encapsulated packets
+    pub fn set_local(&mut self, addr: IpAddr) -> &mut VxlanConfig {
+        self.local = Some(addr);
+        self
+    }
+
+    /// Underlay device, required for multicast groups
+    pub fn set_link(&mut self, index: i32) -> &mut VxlanConfig {
+        self.link = Some(index);
+        self
+    }
+
+    /// UDP destination port
+    pub fn set_port(&mut self, port: u16) -> &mut VxlanConfig {
+        self.port = Some(port);
+        self
+    }
+
+    pub fn set_ttl(&mut self, ttl: u8) -> &mut VxlanConfig {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Learn remote MAC addresses into the FDB, on by default
+    pub fn set_learning(&mut self, learning: bool) -> &mut VxlanConfig {
+        self.learning = Some(learning);
+        self
+    }
+
+    fn data(&self) -> Vec<u8> {
+        let mut bytes = NlAttr::new(IFLA_VXLAN_ID, &self.vni.to_ne_bytes()).bytes();
+        match self.remote {
+            Some(IpAddr::V4(a)) => bytes.extend(NlAttr::new(IFLA_VXLAN_GROUP, &a.octets()).bytes()),
+            Some(IpAddr::V6(a)) => bytes.extend(NlAttr::new(IFLA_VXLAN_GROUP6, &a.octets()).bytes()),
+            None => {},
+        }
+        match self.local {
+            Some(IpAddr::V4(a)) => bytes.extend(NlAttr::new(IFLA_VXLAN_LOCAL, &a.octets()).bytes()),
+            Some(IpAddr::V6(a)) => bytes.extend(NlAttr::new(IFLA_VXLAN_LOCAL6, &a.octets()).bytes()),
+            None => {},
+        }
+        if let Some(link) = self.link {
+            bytes.extend(NlAttr::new(IFLA_VXLAN_LINK, &link.to_ne_bytes()).bytes());
+        }
+        if let Some(port) = self.port {
+            // The only big endian attribute of the family
+            bytes.extend(NlAttr::new(IFLA_VXLAN_PORT, &port.to_be_bytes()).bytes());
+        }
+        if let Some(ttl) = self.ttl {
+            bytes.extend(NlAttr::new(IFLA_VXLAN_TTL, &[ttl]).bytes());
+        }
+        if let Some(learning) = self.learning {
+            bytes.extend(NlAttr::new(IFLA_VXLAN_LEARNING, &[learning as u8]).bytes());
+        }
+        bytes
+    }
+}
+
+/// Creates a vxlan link as described by `config`.
+pub fn create_vxlan(socket: &mut impl NetlinkTransport, config: &VxlanConfig) -> io::Result<()> {
+    create_link(socket, &config.name, "vxlan", &config.data())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{link_by_name, LinkGuard};
+    use socket::{Socket, NlAttr};
+    use Protocol;
+    use netns;
+
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use libc::EPERM;
+
+    #[test]
+    fn test_vxlan_data() {
+        let mut config = VxlanConfig::new("vx0", 42);
+        config.set_remote(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)))
+            .set_port(VXLAN_PORT)
+            .set_learning(false);
+
+        let data = config.data();
+        let attrs = NlAttr::parse(&data).unwrap();
+        let types: Vec<u16> = attrs.iter().map(|a| a.attr_type()).collect();
+        assert_eq!(types, [IFLA_VXLAN_ID, IFLA_VXLAN_GROUP, IFLA_VXLAN_PORT, IFLA_VXLAN_LEARNING]);
+        assert_eq!(attrs[0].payload(), &42u32.to_ne_bytes());
+        assert_eq!(attrs[1].payload(), &[192, 0, 2, 1]);
+        assert_eq!(attrs[2].payload(), &[0x12, 0xb5]);
+        assert_eq!(attrs[3].payload(), &[0]);
+    }
+
+    #[test]
+    fn test_create_vxlan() {
+        netns::run(|| {
+            let mut socket = Socket::new(Protocol::Route).unwrap();
+            let mut config = VxlanConfig::new("nlrs-vx0", 100);
+            config.set_remote(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))).set_port(VXLAN_PORT);
+            match create_vxlan(&mut socket, &config) {
+                Err(ref e) if e.raw_os_error() == Some(EPERM) => return,
+                r => r.unwrap(),
+            }
+            let link = link_by_name(&mut socket, "nlrs-vx0").unwrap();
+            let _guard = LinkGuard(link.index());
+            assert_eq!(link.kind(), Some("vxlan"));
+        });
+    }
+}
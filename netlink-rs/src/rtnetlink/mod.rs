@@ -0,0 +1,72 @@
+//! Routing netlink
+//!
+//! rtnetlink (`Protocol::Route`) manages the kernel's networking objects:
+//! links, addresses, routes, neighbours and traffic control. Every message
+//! starts with a fixed, object specific header (e.g. `struct ifinfomsg`)
+//! followed by attributes.
+
+pub mod address;
+pub mod link;
+pub mod neighbor;
+pub mod route;
+pub mod tc;
+
+use socket::{NetlinkTransport, Msg, NlMsgHeader, Payload, ip_from_bytes, ip_attr};
+
+use std::borrow::Cow;
+use std::io;
+use std::net::IpAddr;
+
+const RTM_NEWLINK: u16 = 16;
+const RTM_DELLINK: u16 = 17;
+const RTM_GETLINK: u16 = 18;
+const RTM_SETLINK: u16 = 19;
+
+const RTM_NEWADDR: u16 = 20;
+const RTM_DELADDR: u16 = 21;
+const RTM_GETADDR: u16 = 22;
+
+const RTM_NEWROUTE: u16 = 24;
+const RTM_DELROUTE: u16 = 25;
+const RTM_GETROUTE: u16 = 26;
+
+const RTM_NEWNEIGH: u16 = 28;
+const RTM_DELNEIGH: u16 = 29;
+const RTM_GETNEIGH: u16 = 30;
+
+const RTM_NEWQDISC: u16 = 36;
+const RTM_DELQDISC: u16 = 37;
+const RTM_GETQDISC: u16 = 38;
+
+const RTM_NEWTCLASS: u16 = 40;
+const RTM_DELTCLASS: u16 = 41;
+const RTM_GETTCLASS: u16 = 42;
+
+const RTM_NEWTFILTER: u16 = 44;
+const RTM_DELTFILTER: u16 = 45;
+const RTM_GETTFILTER: u16 = 46;
+
+const RTM_NEWADDRLABEL: u16 = 72;
+const RTM_DELADDRLABEL: u16 = 73;
+const RTM_GETADDRLABEL: u16 = 74;
+
+const RTM_GETSTATS: u16 = 94;
+
+const RTM_GETVLAN: u16 = 114;
+
+/// Sends an rtnetlink request made of `hdr` and `payload` and returns the
+/// payloads of the kernel's replies.
+fn exchange(socket: &mut impl NetlinkTransport, mut hdr: NlMsgHeader, payload: &[u8]) -> io::Result<Vec<Vec<u8>>> {
+    hdr.data_length(payload.len() as u32);
+    socket.talk(Msg::new(hdr, Payload::Data(Cow::Borrowed(payload))))
+}
+
+/// Decodes an IPv4 or IPv6 address attribute payload.
+fn addr(payload: &[u8]) -> io::Result<IpAddr> {
+    Ok(ip_from_bytes(payload)?)
+}
+
+/// Encodes `addr` as attribute `attr_type`.
+fn addr_attr(attr_type: u16, addr: IpAddr) -> Vec<u8> {
+    ip_attr(attr_type, addr)
+}
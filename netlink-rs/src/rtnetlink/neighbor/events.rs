@@ -0,0 +1,143 @@
+use super::{Neighbor, FdbEntry, NudState, neighbors, fdb};
+use super::super::{RTM_NEWNEIGH, RTM_DELNEIGH};
+use socket::{Socket, NetlinkTransport, NetlinkAddr, Payload, MsgType, Overrun};
+
+use std::io;
+
+use libc::AF_BRIDGE;
+
+const RTNLGRP_NEIGH: u32 = 3;
+
+/// A change of a neighbour table, with the entry's state after it
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum NeighborEvent {
+    /// An IP neighbour was added or changed state (RTM_NEWNEIGH)
+    New(Neighbor),
+    /// An IP neighbour was removed (RTM_DELNEIGH)
+    Delete(Neighbor),
+    NewFdb(FdbEntry),
+    DeleteFdb(FdbEntry),
+}
+
+impl NeighborEvent {
+    /// NUD_* state of the entry, e.g. `NudState::STALE` once a reachable
+    /// neighbour was not confirmed in time
+    pub fn state(&self) -> NudState {
+        match *self {
+            NeighborEvent::New(ref n) | NeighborEvent::Delete(ref n) => n.state(),
+            NeighborEvent::NewFdb(ref e) | NeighborEvent::DeleteFdb(ref e) => e.state(),
+        }
+    }
+}
+
+/// A socket subscribed to neighbour table changes (`ip monitor neigh`)
+pub struct NeighborEvents<T: NetlinkTransport = Socket> {
+    socket: T,
+    resync: Option<Resync>,
+}
+
+struct Resync {
+    transport: Box<dyn NetlinkTransport>,
+    f: Box<dyn FnMut(Vec<Neighbor>, Vec<FdbEntry>)>,
+}
+
+impl NeighborEvents {
+    /// Subscribes to the IP neighbour and bridge FDB changes.
+    pub fn new() -> io::Result<NeighborEvents> {
+        let socket = Socket::new(::Protocol::Route)?;
+        socket.bind(NetlinkAddr::new(0, 1 << (RTNLGRP_NEIGH - 1)))?;
+        Ok(NeighborEvents::from_transport(socket))
+    }
+}
+
+impl<T: NetlinkTransport> NeighborEvents<T> {
+    /// Reads events from `transport`, which is already subscribed.
+    pub fn from_transport(transport: T) -> NeighborEvents<T> {
+        NeighborEvents { socket: transport, resync: None }
+    }
+
+    /// Handles overruns by passing fresh dumps of the neighbours and of the
+    /// FDB to `resync` instead of failing `recv` with an `Overrun` error.
+    /// The dumps go through `transport`, see `ConntrackEvents::set_resync`.
+    pub fn set_resync<D, F>(&mut self, transport: D, resync: F) -> &mut NeighborEvents<T>
+        where D: NetlinkTransport + 'static,
+              F: FnMut(Vec<Neighbor>, Vec<FdbEntry>) + 'static
+    {
+        self.resync = Some(Resync { transport: Box::new(transport), f: Box::new(resync) });
+        self
+    }
+
+    /// Blocks until events arrive and returns those read at once.
+    pub fn recv(&mut self) -> io::Result<Vec<NeighborEvent>> {
+        let messages = match self.socket.recv_msgs() {
+            Ok(messages) => messages,
+            Err(ref e) if Overrun::matches(e) && self.resync.is_some() => {
+                if let Some(ref mut resync) = self.resync {
+                    let neighbors = neighbors(&mut resync.transport)?;
+                    let entries = fdb(&mut resync.transport)?;
+                    (resync.f)(neighbors, entries);
+                }
+                return Ok(vec![]);
+            },
+            Err(e) => return Err(e),
+        };
+        let mut events = vec![];
+        for msg in messages {
+            let bytes = match *msg.payload() {
+                Payload::Data(ref b) => &b[..],
+                Payload::Err(e, _) => return Err(io::Error::from_raw_os_error(-e)),
+                _ => continue,
+            };
+            let new = match msg.header().msg_type() {
+                MsgType::UserDefined(RTM_NEWNEIGH) => true,
+                MsgType::UserDefined(RTM_DELNEIGH) => false,
+                _ => continue,
+            };
+            let bridge = bytes.first() == Some(&(AF_BRIDGE as u8));
+            events.push(match (bridge, new) {
+                (false, true) => NeighborEvent::New(Neighbor::from_bytes(bytes)?),
+                (false, false) => NeighborEvent::Delete(Neighbor::from_bytes(bytes)?),
+                (true, true) => NeighborEvent::NewFdb(FdbEntry::from_bytes(bytes)?),
+                (true, false) => NeighborEvent::DeleteFdb(FdbEntry::from_bytes(bytes)?),
+            });
+        }
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket::{NlMsgHeader, MacAddr, ReplayTransport};
+
+    use std::net::{IpAddr, Ipv4Addr};
+
+    fn message(msg_type: u16, payload: &[u8]) -> Vec<u8> {
+        let mut hdr = NlMsgHeader::user_defined(msg_type);
+        hdr.data_length(payload.len() as u32);
+        let mut bytes = hdr.bytes().to_vec();
+        bytes.extend_from_slice(payload);
+        bytes
+    }
+
+    #[test]
+    fn test_neighbor_events() {
+        let mut stale = Neighbor::new(2, IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)), &[2, 0, 0, 0, 0, 1]);
+        stale.set_state(NudState::STALE);
+        let mut failed = stale.clone();
+        failed.set_state(NudState::FAILED);
+        let mut entry = FdbEntry::new(MacAddr::from([2, 0, 0, 0, 0, 2]), 3);
+        entry.dynamic();
+
+        let mut datagram = message(RTM_NEWNEIGH, &stale.bytes());
+        datagram.extend(message(RTM_DELNEIGH, &failed.bytes()));
+        datagram.extend(message(RTM_NEWNEIGH, &entry.bytes()));
+        let mut events = NeighborEvents::from_transport(ReplayTransport::new(vec![datagram]));
+
+        let received = events.recv().unwrap();
+        assert_eq!(received, [NeighborEvent::New(stale), NeighborEvent::Delete(failed), NeighborEvent::NewFdb(entry)]);
+        let states: Vec<_> = received.iter().map(NeighborEvent::state).collect();
+        assert_eq!(states, [NudState::STALE, NudState::FAILED, NudState::REACHABLE]);
+    }
+}
@@ -0,0 +1,248 @@
+use super::{NdMsg, NudState, NDA_DST, NDA_LLADDR, NDA_VLAN, NDA_MASTER, NTF_SELF, NTF_MASTER};
+use super::super::{exchange, RTM_NEWNEIGH, RTM_DELNEIGH, RTM_GETNEIGH};
+use socket::{NetlinkTransport, NlMsgHeader, NlAttr, MacAddr};
+
+use std::io::{self, ErrorKind, Cursor};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use byteorder::{BigEndian, ReadBytesExt};
+use libc::AF_BRIDGE;
+
+/// A bridge forwarding database entry (`bridge fdb`)
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct FdbEntry {
+    mac: MacAddr,
+    ifindex: i32,
+    vlan: Option<u16>,
+    master: Option<i32>,
+    dst: Option<IpAddr>,
+    state: NudState,
+    flags: u8,
+}
+
+impl FdbEntry {
+    /// Describes a static entry forwarding `mac` to port `ifindex`.
+    pub fn new(mac: MacAddr, ifindex: i32) -> FdbEntry {
+        FdbEntry {
+            mac,
+            ifindex,
+            vlan: None,
+            master: None,
+            dst: None,
+            state: NudState::NOARP,
+            flags: 0,
+        }
+    }
+
+    pub(super) fn from_bytes(bytes: &[u8]) -> io::Result<FdbEntry> {
+        let (ndm, n) = NdMsg::from_bytes(bytes)?;
+        let mut entry = FdbEntry::new(MacAddr::default(), ndm.ifindex);
+        entry.state = ndm.state.into();
+        entry.flags = ndm.flags;
+
+        for attr in NlAttr::parse(&bytes[n..])? {
+            let p = attr.payload();
+            let mut cursor = Cursor::new(p);
+            match attr.attr_type() {
+                NDA_LLADDR => entry.mac = attr.get_mac()?,
+                NDA_VLAN => entry.vlan = Some(attr.get_u16()?),
+                NDA_MASTER => entry.master = Some(attr.get_u32()? as i32),
+                NDA_DST => {
+                    entry.dst = match p.len() {
+                        4 => Some(IpAddr::V4(Ipv4Addr::from(cursor.read_u32::<BigEndian>()?))),
+                        16 => {
+                            let mut octets = [0u8; 16];
+                            octets.copy_from_slice(p);
+                            Some(IpAddr::V6(Ipv6Addr::from(octets)))
+                        },
+                        _ => return Err(io::Error::new(ErrorKind::InvalidData, "bad fdb dst length")),
+                    }
+                },
+                _ => {},
+            }
+        }
+        Ok(entry)
+    }
+
+    pub(super) fn bytes(&self) -> Vec<u8> {
+        let ndm = NdMsg {
+            family: AF_BRIDGE as u8,
+            ifindex: self.ifindex,
+            state: self.state.into(),
+            flags: self.flags,
+            ndm_type: 0,
+        };
+        let mut bytes = ndm.bytes();
+        bytes.extend(self.mac.attr(NDA_LLADDR));
+        if let Some(vlan) = self.vlan {
+            bytes.extend(NlAttr::new(NDA_VLAN, &vlan.to_ne_bytes()).bytes());
+        }
+        match self.dst {
+            Some(IpAddr::V4(a)) => bytes.extend(NlAttr::new(NDA_DST, &a.octets()).bytes()),
+            Some(IpAddr::V6(a)) => bytes.extend(NlAttr::new(NDA_DST, &a.octets()).bytes()),
+            None => {},
+        }
+        bytes
+    }
+
+    /// Only match frames of this VLAN
+    pub fn set_vlan(&mut self, vlan: u16) -> &mut FdbEntry {
+        self.vlan = Some(vlan);
+        self
+    }
+
+    /// Remote VTEP for entries of a vxlan device
+    pub fn set_dst(&mut self, addr: IpAddr) -> &mut FdbEntry {
+        self.dst = Some(addr);
+        self
+    }
+
+    /// Local address of the port itself (`bridge fdb add ... permanent`)
+    pub fn permanent(&mut self) -> &mut FdbEntry {
+        self.state = NudState::PERMANENT;
+        self
+    }
+
+    /// Entry that ages out like a learned one (`bridge fdb add ... dynamic`)
+    pub fn dynamic(&mut self) -> &mut FdbEntry {
+        self.state = NudState::REACHABLE;
+        self
+    }
+
+    /// Program the device's own FDB (NTF_SELF), e.g. of a vxlan device,
+    /// instead of the FDB of the bridge it is a port of
+    pub fn set_self(&mut self) -> &mut FdbEntry {
+        self.flags |= NTF_SELF;
+        self
+    }
+
+    /// Program the bridge FDB explicitly, the default for bridge ports;
+    /// combined with `set_self` both FDBs are programmed
+    pub fn set_master(&mut self) -> &mut FdbEntry {
+        self.flags |= NTF_MASTER;
+        self
+    }
+
+    pub fn mac(&self) -> MacAddr {
+        self.mac
+    }
+
+    /// Port the MAC address is forwarded to
+    pub fn ifindex(&self) -> i32 {
+        self.ifindex
+    }
+
+    pub fn vlan(&self) -> Option<u16> {
+        self.vlan
+    }
+
+    /// Bridge owning the entry
+    pub fn master(&self) -> Option<i32> {
+        self.master
+    }
+
+    pub fn dst(&self) -> Option<IpAddr> {
+        self.dst
+    }
+
+    pub fn state(&self) -> NudState {
+        self.state
+    }
+
+    pub fn is_permanent(&self) -> bool {
+        self.state.contains(NudState::PERMANENT)
+    }
+
+    /// Configured rather than learned
+    pub fn is_static(&self) -> bool {
+        self.state.intersects(NudState::NOARP | NudState::PERMANENT)
+    }
+
+    /// Entry of the device's own FDB rather than its bridge's
+    pub fn is_self(&self) -> bool {
+        self.flags & NTF_SELF != 0
+    }
+}
+
+/// Dumps the forwarding databases of all bridges and bridge ports.
+pub fn fdb(socket: &mut impl NetlinkTransport) -> io::Result<Vec<FdbEntry>> {
+    let mut hdr = NlMsgHeader::user_defined(RTM_GETNEIGH);
+    hdr.dump();
+    let ndm = NdMsg { family: AF_BRIDGE as u8, ..Default::default() };
+    let replies = exchange(socket, hdr, &ndm.bytes())?;
+    replies.iter().map(|r| FdbEntry::from_bytes(r)).collect()
+}
+
+/// Adds `entry`, failing with `EEXIST` if it is already present.
+pub fn add_fdb(socket: &mut impl NetlinkTransport, entry: &FdbEntry) -> io::Result<()> {
+    let mut hdr = NlMsgHeader::user_defined(RTM_NEWNEIGH);
+    hdr.create().excl();
+    exchange(socket, hdr, &entry.bytes())?;
+    Ok(())
+}
+
+/// Deletes the entry matching the MAC address, port and VLAN of `entry`.
+pub fn del_fdb(socket: &mut impl NetlinkTransport, entry: &FdbEntry) -> io::Result<()> {
+    let hdr = NlMsgHeader::user_defined(RTM_DELNEIGH);
+    exchange(socket, hdr, &entry.bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rtnetlink::link::{create_bridge, create_veth, link_by_name, set_master, LinkGuard};
+    use socket::Socket;
+    use Protocol;
+    use netns;
+
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use libc::EPERM;
+
+    #[test]
+    fn test_fdb_roundtrip() {
+        let mut entry = FdbEntry::new(MacAddr::new([2, 0, 0, 0, 0, 1]), 5);
+        entry.set_vlan(10).set_dst(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))).set_self();
+
+        let decoded = FdbEntry::from_bytes(&entry.bytes()).unwrap();
+        assert_eq!(decoded, entry);
+        assert!(decoded.is_static());
+        assert!(!decoded.is_permanent());
+        assert!(decoded.is_self());
+    }
+
+    #[test]
+    fn test_bridge_fdb() {
+        netns::run(|| {
+            let mut socket = Socket::new(Protocol::Route).unwrap();
+            match create_bridge(&mut socket, "nlrs-fdbbr") {
+                Err(ref e) if e.raw_os_error() == Some(EPERM) => return,
+                r => r.unwrap(),
+            }
+            let br = link_by_name(&mut socket, "nlrs-fdbbr").unwrap();
+            let _br_guard = LinkGuard(br.index());
+            create_veth(&mut socket, "nlrs-fdb0", "nlrs-fdb1").unwrap();
+            let port = link_by_name(&mut socket, "nlrs-fdb0").unwrap();
+            let _port_guard = LinkGuard(port.index());
+            set_master(&mut socket, port.index(), br.index()).unwrap();
+
+            let mac = MacAddr::new([2, 0x6e, 0x6c, 0x72, 0x73, 1]);
+            // No VLAN: the bridge does not filter VLANs
+            let entry = FdbEntry::new(mac, port.index());
+            add_fdb(&mut socket, &entry).unwrap();
+
+            let entries = fdb(&mut socket).unwrap();
+            let found = entries.iter().find(|e| e.mac() == mac).unwrap();
+            assert_eq!(found.ifindex(), port.index());
+            assert_eq!(found.master(), Some(br.index()));
+            assert!(!found.is_self());
+            assert!(found.is_static());
+
+            del_fdb(&mut socket, &entry).unwrap();
+            let entries = fdb(&mut socket).unwrap();
+            assert!(!entries.iter().any(|e| e.mac() == mac));
+        });
+    }
+}
@@ -0,0 +1,235 @@
+use super::{NdMsg, NudState, NDA_DST, NDA_LLADDR, NTF_PROXY, NTF_ROUTER};
+use super::super::{exchange, addr_attr, RTM_NEWNEIGH, RTM_DELNEIGH, RTM_GETNEIGH};
+use socket::{NetlinkTransport, NlMsgHeader, NlAttr, MacAddr, ip_family};
+
+use std::io::{self, ErrorKind};
+use std::net::IpAddr;
+
+use libc::AF_UNSPEC;
+
+/// An ARP or NDP neighbour entry, or a proxy entry answering for an address
+/// on behalf of another host (`ip neigh`, `ip neigh ... proxy`)
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Neighbor {
+    family: u8,
+    ifindex: i32,
+    dst: IpAddr,
+    lladdr: Option<Vec<u8>>,
+    state: NudState,
+    flags: u8,
+}
+
+impl Neighbor {
+    /// Describes a permanent entry resolving `dst` to `lladdr` on link
+    /// `ifindex`.
+    pub fn new(ifindex: i32, dst: IpAddr, lladdr: &[u8]) -> Neighbor {
+        let mut neighbor = Neighbor::entry(ifindex, dst);
+        neighbor.lladdr = Some(lladdr.to_vec());
+        neighbor
+    }
+
+    /// Describes a proxy entry answering ARP or NDP requests for `dst`
+    /// received on link `ifindex`, or on any link if it is 0.
+    pub fn proxy(ifindex: i32, dst: IpAddr) -> Neighbor {
+        let mut neighbor = Neighbor::entry(ifindex, dst);
+        neighbor.flags = NTF_PROXY;
+        neighbor
+    }
+
+    fn entry(ifindex: i32, dst: IpAddr) -> Neighbor {
+        Neighbor {
+            family: ip_family(dst),
+            ifindex,
+            dst,
+            lladdr: None,
+            state: NudState::PERMANENT,
+            flags: 0,
+        }
+    }
+
+    pub(super) fn from_bytes(bytes: &[u8]) -> io::Result<Neighbor> {
+        let (ndm, n) = NdMsg::from_bytes(bytes)?;
+        let mut dst = None;
+        let mut lladdr = None;
+        for attr in NlAttr::parse(&bytes[n..])? {
+            match attr.attr_type() {
+                NDA_DST => dst = Some(attr.get_ip_in(ndm.family)?),
+                NDA_LLADDR => lladdr = Some(attr.payload().to_vec()),
+                _ => {},
+            }
+        }
+        let dst = dst.ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "no NDA_DST"))?;
+        Ok(Neighbor {
+            family: ndm.family,
+            ifindex: ndm.ifindex,
+            dst,
+            lladdr,
+            state: ndm.state.into(),
+            flags: ndm.flags,
+        })
+    }
+
+    pub(super) fn bytes(&self) -> Vec<u8> {
+        let ndm = NdMsg {
+            family: self.family,
+            ifindex: self.ifindex,
+            state: self.state.into(),
+            flags: self.flags,
+            ndm_type: 0,
+        };
+        let mut bytes = ndm.bytes();
+        bytes.extend(addr_attr(NDA_DST, self.dst));
+        if let Some(ref lladdr) = self.lladdr {
+            bytes.extend(NlAttr::new(NDA_LLADDR, lladdr).bytes());
+        }
+        bytes
+    }
+
+    /// Marks the neighbour as an IPv6 router (NTF_ROUTER); proxy entries
+    /// then answer with the router flag set
+    pub fn router(&mut self) -> &mut Neighbor {
+        self.flags |= NTF_ROUTER;
+        self
+    }
+
+    /// `NudState::PERMANENT` by default
+    pub fn set_state(&mut self, state: NudState) -> &mut Neighbor {
+        self.state = state;
+        self
+    }
+
+    /// Address family, AF_INET or AF_INET6
+    pub fn family(&self) -> u8 {
+        self.family
+    }
+
+    pub fn ifindex(&self) -> i32 {
+        self.ifindex
+    }
+
+    pub fn dst(&self) -> IpAddr {
+        self.dst
+    }
+
+    /// Link layer address, `None` for proxy and unresolved entries
+    pub fn lladdr(&self) -> Option<&[u8]> {
+        self.lladdr.as_deref()
+    }
+
+    /// Link layer address if it is an Ethernet address
+    pub fn mac(&self) -> Option<MacAddr> {
+        self.lladdr.as_ref().and_then(|a| MacAddr::from_bytes(a).ok())
+    }
+
+    pub fn state(&self) -> NudState {
+        self.state
+    }
+
+    /// NTF_* flags
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    pub fn is_proxy(&self) -> bool {
+        self.flags & NTF_PROXY != 0
+    }
+
+    pub fn is_router(&self) -> bool {
+        self.flags & NTF_ROUTER != 0
+    }
+}
+
+fn dump(socket: &mut impl NetlinkTransport, flags: u8) -> io::Result<Vec<Neighbor>> {
+    let mut hdr = NlMsgHeader::user_defined(RTM_GETNEIGH);
+    hdr.dump();
+    let ndm = NdMsg { family: AF_UNSPEC as u8, flags, ..Default::default() };
+    let replies = exchange(socket, hdr, &ndm.bytes())?;
+    replies.iter().map(|r| Neighbor::from_bytes(r)).collect()
+}
+
+/// Dumps the IPv4 and IPv6 neighbour caches, without proxy entries.
+pub fn neighbors(socket: &mut impl NetlinkTransport) -> io::Result<Vec<Neighbor>> {
+    dump(socket, 0)
+}
+
+/// Dumps the IPv4 and IPv6 proxy entries.
+pub fn proxy_neighbors(socket: &mut impl NetlinkTransport) -> io::Result<Vec<Neighbor>> {
+    dump(socket, NTF_PROXY)
+}
+
+/// Adds `neighbor`, failing with `EEXIST` if an entry for its address
+/// already exists.
+pub fn add_neighbor(socket: &mut impl NetlinkTransport, neighbor: &Neighbor) -> io::Result<()> {
+    let mut hdr = NlMsgHeader::user_defined(RTM_NEWNEIGH);
+    hdr.create().excl();
+    exchange(socket, hdr, &neighbor.bytes())?;
+    Ok(())
+}
+
+/// Deletes the entry, or proxy entry, for the address and link of `neighbor`.
+pub fn del_neighbor(socket: &mut impl NetlinkTransport, neighbor: &Neighbor) -> io::Result<()> {
+    let hdr = NlMsgHeader::user_defined(RTM_DELNEIGH);
+    exchange(socket, hdr, &neighbor.bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rtnetlink::link::{create_veth, link_by_name, LinkGuard};
+    use socket::Socket;
+    use Protocol;
+    use netns;
+
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    use libc::EPERM;
+
+    #[test]
+    fn test_neighbor_roundtrip() {
+        let mut proxy = Neighbor::proxy(4, IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 7)));
+        proxy.router();
+        let decoded = Neighbor::from_bytes(&proxy.bytes()).unwrap();
+        assert_eq!(decoded, proxy);
+        assert!(decoded.is_proxy() && decoded.is_router());
+        assert_eq!(decoded.lladdr(), None);
+    }
+
+    #[test]
+    fn test_neighbors() {
+        netns::run(|| {
+            let mut socket = Socket::new(Protocol::Route).unwrap();
+            match create_veth(&mut socket, "nlrs-nb0", "nlrs-nb1") {
+                Err(ref e) if e.raw_os_error() == Some(EPERM) => return,
+                r => r.unwrap(),
+            }
+            let link = link_by_name(&mut socket, "nlrs-nb0").unwrap();
+            let _guard = LinkGuard(link.index());
+
+            let ip = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 7));
+            let mac = MacAddr::new([2, 0x6e, 0x6c, 0x72, 0x73, 7]);
+            let neighbor = Neighbor::new(link.index(), ip, mac.as_ref());
+            add_neighbor(&mut socket, &neighbor).unwrap();
+            let ip6 = IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 7));
+            let mut proxy = Neighbor::proxy(link.index(), ip6);
+            proxy.router();
+            add_neighbor(&mut socket, &proxy).unwrap();
+
+            let found = neighbors(&mut socket).unwrap().into_iter().find(|n| n.dst() == ip).unwrap();
+            assert_eq!(found.lladdr(), Some(mac.as_ref()));
+            assert_eq!(found.mac(), Some(mac));
+            assert_eq!(found.state(), NudState::PERMANENT);
+            assert!(!found.is_proxy());
+            assert!(!neighbors(&mut socket).unwrap().iter().any(|n| n.dst() == ip6));
+
+            let found = proxy_neighbors(&mut socket).unwrap().into_iter().find(|n| n.dst() == ip6).unwrap();
+            assert_eq!(found.ifindex(), link.index());
+            assert!(found.is_proxy() && found.is_router());
+
+            del_neighbor(&mut socket, &proxy).unwrap();
+            assert!(!proxy_neighbors(&mut socket).unwrap().iter().any(|n| n.dst() == ip6));
+            del_neighbor(&mut socket, &neighbor).unwrap();
+        });
+    }
+}
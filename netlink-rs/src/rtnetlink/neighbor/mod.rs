@@ -0,0 +1,90 @@
+//! Neighbour tables (`ip neigh`, `bridge fdb`)
+//!
+//! IP neighbours (ARP/NDP entries) and the bridge forwarding database share
+//! the same messages; bridge FDB entries use the AF_BRIDGE family. Proxy
+//! entries live in a separate table, selected with NTF_PROXY. Changes of
+//! both are monitored through `NeighborEvents`.
+
+mod events;
+mod fdb;
+mod ip;
+mod state;
+pub use self::events::*;
+pub use self::fdb::*;
+pub use self::ip::*;
+pub use self::state::*;
+
+use std::io::{self, Cursor};
+
+use byteorder::{NativeEndian, ReadBytesExt};
+
+const NDA_DST: u16 = 1;
+const NDA_LLADDR: u16 = 2;
+const NDA_VLAN: u16 = 5;
+const NDA_MASTER: u16 = 9;
+
+const NTF_SELF: u8 = 1 << 1;
+const NTF_MASTER: u8 = 1 << 2;
+const NTF_PROXY: u8 = 1 << 3;
+const NTF_ROUTER: u8 = 1 << 7;
+
+// HEADER FORMAT
+// __u8    ndm_family;
+// __u8    ndm_pad1;
+// __u16   ndm_pad2;
+// __s32   ndm_ifindex;
+// __u16   ndm_state;      /* NUD_* */
+// __u8    ndm_flags;      /* NTF_* */
+// __u8    ndm_type;
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+struct NdMsg {
+    family: u8,
+    ifindex: i32,
+    state: u16,
+    flags: u8,
+    ndm_type: u8,
+}
+
+impl NdMsg {
+    fn from_bytes(bytes: &[u8]) -> io::Result<(NdMsg, usize)> {
+        let mut cursor = Cursor::new(bytes);
+        let family = cursor.read_u8()?;
+        let _pad1 = cursor.read_u8()?;
+        let _pad2 = cursor.read_u16::<NativeEndian>()?;
+        let ifindex = cursor.read_i32::<NativeEndian>()?;
+        let state = cursor.read_u16::<NativeEndian>()?;
+        let flags = cursor.read_u8()?;
+        let ndm_type = cursor.read_u8()?;
+        Ok((NdMsg {
+            family,
+            ifindex,
+            state,
+            flags,
+            ndm_type,
+        }, cursor.position() as usize))
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![self.family, 0, 0, 0];
+        bytes.extend_from_slice(&self.ifindex.to_ne_bytes());
+        bytes.extend_from_slice(&self.state.to_ne_bytes());
+        bytes.push(self.flags);
+        bytes.push(self.ndm_type);
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ndmsg_roundtrip() {
+        let ndm = NdMsg { family: 7, ifindex: 3, state: NudState::PERMANENT.bits(), flags: NTF_MASTER, ndm_type: 0 };
+        let bytes = ndm.bytes();
+        assert_eq!(bytes.len(), 12);
+        let (decoded, n) = NdMsg::from_bytes(&bytes).unwrap();
+        assert_eq!(n, 12);
+        assert_eq!(decoded, ndm);
+    }
+}
@@ -0,0 +1,175 @@
+use std::fmt;
+use std::ops::{BitAnd, BitOr, BitOrAssign, Not, Sub};
+
+/// NUD_* state of a neighbour entry, as in `ndm_state`
+///
+/// Dumped entries carry a single state; requests and filters may combine
+/// several.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct NudState(u16);
+
+const NAMES: [(NudState, &str); 8] = [
+    (NudState::INCOMPLETE, "INCOMPLETE"),
+    (NudState::REACHABLE, "REACHABLE"),
+    (NudState::STALE, "STALE"),
+    (NudState::DELAY, "DELAY"),
+    (NudState::PROBE, "PROBE"),
+    (NudState::FAILED, "FAILED"),
+    (NudState::NOARP, "NOARP"),
+    (NudState::PERMANENT, "PERMANENT"),
+];
+
+impl NudState {
+    /// No state, as in a request that leaves the state to the kernel
+    pub const NONE: NudState = NudState(0);
+    /// Resolution in progress
+    pub const INCOMPLETE: NudState = NudState(0x01);
+    /// Confirmed recently
+    pub const REACHABLE: NudState = NudState(0x02);
+    /// Usable but due for confirmation
+    pub const STALE: NudState = NudState(0x04);
+    /// Waiting for upper layer confirmation before probing
+    pub const DELAY: NudState = NudState(0x08);
+    pub const PROBE: NudState = NudState(0x10);
+    /// Resolution failed
+    pub const FAILED: NudState = NudState(0x20);
+    /// Needs no resolution, e.g. on point-to-point links
+    pub const NOARP: NudState = NudState(0x40);
+    /// Static, never ages out
+    pub const PERMANENT: NudState = NudState(0x80);
+
+    pub fn from_bits(bits: u16) -> NudState {
+        NudState(bits)
+    }
+
+    pub fn bits(&self) -> u16 {
+        self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Whether all of `state` is set
+    pub fn contains(&self, state: NudState) -> bool {
+        self.0 & state.0 == state.0
+    }
+
+    /// Whether any of `state` is set
+    pub fn intersects(&self, state: NudState) -> bool {
+        self.0 & state.0 != 0
+    }
+
+    pub fn insert(&mut self, state: NudState) {
+        self.0 |= state.0;
+    }
+
+    pub fn remove(&mut self, state: NudState) {
+        self.0 &= !state.0;
+    }
+
+    /// The link layer address can be used (NUD_VALID)
+    pub fn is_valid(&self) -> bool {
+        self.intersects(NudState::PERMANENT | NudState::NOARP | NudState::REACHABLE |
+                        NudState::PROBE | NudState::STALE | NudState::DELAY)
+    }
+
+    /// The address is known good without probing (NUD_CONNECTED)
+    pub fn is_connected(&self) -> bool {
+        self.intersects(NudState::PERMANENT | NudState::NOARP | NudState::REACHABLE)
+    }
+}
+
+impl From<u16> for NudState {
+    fn from(bits: u16) -> NudState {
+        NudState(bits)
+    }
+}
+
+impl From<NudState> for u16 {
+    fn from(state: NudState) -> u16 {
+        state.0
+    }
+}
+
+impl BitOr for NudState {
+    type Output = NudState;
+
+    fn bitor(self, other: NudState) -> NudState {
+        NudState(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for NudState {
+    fn bitor_assign(&mut self, other: NudState) {
+        self.0 |= other.0;
+    }
+}
+
+impl BitAnd for NudState {
+    type Output = NudState;
+
+    fn bitand(self, other: NudState) -> NudState {
+        NudState(self.0 & other.0)
+    }
+}
+
+impl Sub for NudState {
+    type Output = NudState;
+
+    fn sub(self, other: NudState) -> NudState {
+        NudState(self.0 & !other.0)
+    }
+}
+
+impl Not for NudState {
+    type Output = NudState;
+
+    fn not(self) -> NudState {
+        NudState(!self.0)
+    }
+}
+
+impl fmt::Debug for NudState {
+    /// Lists the states by name, e.g. `NudState(STALE)`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "NudState(")?;
+        let mut rest = *self;
+        let mut first = true;
+        for &(state, name) in NAMES.iter() {
+            if self.contains(state) {
+                write!(f, "{}{}", if first { "" } else { " | " }, name)?;
+                rest.remove(state);
+                first = false;
+            }
+        }
+        if !rest.is_empty() {
+            write!(f, "{}{:#x}", if first { "" } else { " | " }, rest.0)?;
+        } else if first {
+            write!(f, "NONE")?;
+        }
+        write!(f, ")")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nud_state() {
+        let stale = NudState::from(0x04);
+        assert_eq!(stale, NudState::STALE);
+        assert!(stale.is_valid() && !stale.is_connected());
+        assert!(NudState::PERMANENT.is_connected());
+        assert!(!NudState::FAILED.is_valid() && !NudState::INCOMPLETE.is_valid());
+        assert_eq!(format!("{:?}", stale), "NudState(STALE)");
+        assert_eq!(format!("{:?}", NudState::NONE), "NudState(NONE)");
+        assert_eq!(format!("{:?}", NudState::NOARP | NudState::from(0x100)), "NudState(NOARP | 0x100)");
+
+        let filter = NudState::REACHABLE | NudState::STALE;
+        assert!(filter.intersects(stale) && !filter.contains(NudState::DELAY));
+        assert_eq!(filter - NudState::STALE, NudState::REACHABLE);
+    }
+}
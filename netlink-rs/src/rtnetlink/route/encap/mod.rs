@@ -0,0 +1,70 @@
+mod ip;
+mod mpls;
+mod seg6;
+pub use self::ip::*;
+pub use self::mpls::*;
+pub use self::seg6::*;
+
+use std::io;
+
+const LWTUNNEL_ENCAP_MPLS: u16 = 1;
+const LWTUNNEL_ENCAP_IP: u16 = 2;
+const LWTUNNEL_ENCAP_IP6: u16 = 4;
+const LWTUNNEL_ENCAP_SEG6: u16 = 5;
+
+/// Lightweight tunnel encapsulation of a route (RTA_ENCAP_TYPE and
+/// RTA_ENCAP, `ip route add ... encap ...`)
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum RouteEncap {
+    Mpls(MplsEncap),
+    /// `encap ip` or `encap ip6`, depending on the tunnel destination
+    Ip(IpEncap),
+    Seg6(Seg6Encap),
+}
+
+impl RouteEncap {
+    pub(super) fn from_attr(encap_type: u16, bytes: &[u8]) -> io::Result<Option<RouteEncap>> {
+        match encap_type {
+            LWTUNNEL_ENCAP_MPLS => Ok(Some(RouteEncap::Mpls(MplsEncap::from_bytes(bytes)?))),
+            LWTUNNEL_ENCAP_IP | LWTUNNEL_ENCAP_IP6 => Ok(Some(RouteEncap::Ip(IpEncap::from_bytes(bytes)?))),
+            LWTUNNEL_ENCAP_SEG6 => Ok(Some(RouteEncap::Seg6(Seg6Encap::from_bytes(bytes)?))),
+            _ => Ok(None),
+        }
+    }
+
+    /// LWTUNNEL_ENCAP_* type, the payload of RTA_ENCAP_TYPE
+    pub(super) fn encap_type(&self) -> u16 {
+        match *self {
+            RouteEncap::Mpls(_) => LWTUNNEL_ENCAP_MPLS,
+            RouteEncap::Ip(ref ip) if ip.is_ipv6() => LWTUNNEL_ENCAP_IP6,
+            RouteEncap::Ip(_) => LWTUNNEL_ENCAP_IP,
+            RouteEncap::Seg6(_) => LWTUNNEL_ENCAP_SEG6,
+        }
+    }
+
+    /// The attributes nested in RTA_ENCAP
+    pub(super) fn bytes(&self) -> Vec<u8> {
+        match *self {
+            RouteEncap::Mpls(ref mpls) => mpls.bytes(),
+            RouteEncap::Ip(ref ip) => ip.bytes(),
+            RouteEncap::Seg6(ref seg6) => seg6.bytes(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::{IpAddr, Ipv6Addr};
+
+    #[test]
+    fn test_encap_type() {
+        let ip6 = IpEncap::new(1, IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)));
+        let encap = RouteEncap::Ip(ip6);
+        assert_eq!(encap.encap_type(), LWTUNNEL_ENCAP_IP6);
+        assert_eq!(RouteEncap::from_attr(encap.encap_type(), &encap.bytes()).unwrap(), Some(encap));
+        assert_eq!(RouteEncap::from_attr(8, &[]).unwrap(), None);
+    }
+}
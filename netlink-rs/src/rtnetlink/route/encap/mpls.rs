@@ -0,0 +1,73 @@
+use super::super::mpls::{label_stack, parse_label_stack};
+use socket::NlAttr;
+
+use std::io;
+
+const MPLS_IPTUNNEL_DST: u16 = 1;
+const MPLS_IPTUNNEL_TTL: u16 = 2;
+
+/// MPLS encapsulation, pushing a label stack (`encap mpls L1/L2/...`)
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct MplsEncap {
+    labels: Vec<u32>,
+    ttl: Option<u8>,
+}
+
+impl MplsEncap {
+    /// Pushes `labels`, outermost first. Labels are 20-bit values.
+    pub fn new(labels: &[u32]) -> MplsEncap {
+        MplsEncap {
+            labels: labels.to_vec(),
+            ttl: None,
+        }
+    }
+
+    pub(super) fn from_bytes(bytes: &[u8]) -> io::Result<MplsEncap> {
+        let mut encap = MplsEncap::new(&[]);
+        for attr in NlAttr::parse(bytes)? {
+            let p = attr.payload();
+            match attr.attr_type() {
+                MPLS_IPTUNNEL_DST => encap.labels = parse_label_stack(p)?,
+                MPLS_IPTUNNEL_TTL => encap.ttl = Some(attr.get_u8()?),
+                _ => {},
+            }
+        }
+        Ok(encap)
+    }
+
+    pub(super) fn bytes(&self) -> Vec<u8> {
+        let mut bytes = NlAttr::new(MPLS_IPTUNNEL_DST, &label_stack(&self.labels)).bytes();
+        if let Some(ttl) = self.ttl {
+            bytes.extend(NlAttr::new(MPLS_IPTUNNEL_TTL, &[ttl]).bytes());
+        }
+        bytes
+    }
+
+    /// TTL of the pushed labels; by default it is copied from the IP header
+    pub fn set_ttl(&mut self, ttl: u8) -> &mut MplsEncap {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Labels, outermost first
+    pub fn labels(&self) -> &[u32] {
+        &self.labels
+    }
+
+    pub fn ttl(&self) -> Option<u8> {
+        self.ttl
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mpls_roundtrip() {
+        let mut encap = MplsEncap::new(&[100, 200]);
+        encap.set_ttl(64);
+        assert_eq!(MplsEncap::from_bytes(&encap.bytes()).unwrap(), encap);
+    }
+}
@@ -0,0 +1,141 @@
+use socket::NlAttr;
+
+use std::io::{self, Cursor, ErrorKind};
+use std::net::Ipv6Addr;
+
+use byteorder::{BigEndian, NativeEndian, ReadBytesExt};
+
+const SEG6_IPTUNNEL_SRH: u16 = 1;
+
+const IPV6_SRCRT_TYPE_4: u8 = 4;
+
+/// How packets are steered through the segment list
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum Seg6Mode {
+    /// Insert a segment routing header into the packet
+    Inline,
+    /// Encapsulate the packet in an outer IPv6 header with a segment routing
+    /// header
+    Encap,
+    /// Encapsulate the whole layer 2 frame
+    L2Encap,
+}
+
+impl From<Seg6Mode> for i32 {
+    fn from(m: Seg6Mode) -> i32 {
+        match m {
+            Seg6Mode::Inline => 0,
+            Seg6Mode::Encap => 1,
+            Seg6Mode::L2Encap => 2,
+        }
+    }
+}
+
+/// SRv6 encapsulation (`encap seg6 mode MODE segs SEG1,SEG2,...`)
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Seg6Encap {
+    mode: Seg6Mode,
+    segments: Vec<Ipv6Addr>,
+}
+
+impl Seg6Encap {
+    /// Steers packets through `segments`, in the order they are visited.
+    pub fn new(mode: Seg6Mode, segments: &[Ipv6Addr]) -> Seg6Encap {
+        Seg6Encap {
+            mode,
+            segments: segments.to_vec(),
+        }
+    }
+
+    // SEG6_IPTUNNEL_SRH
+    //     int mode;
+    //     struct ipv6_sr_hdr {
+    //         __u8    nexthdr;        /* set by the kernel */
+    //         __u8    hdrlen;         /* 8-byte units, not counting the first 8 */
+    //         __u8    type;
+    //         __u8    segments_left;
+    //         __u8    first_segment;  /* index of the last entry of segments */
+    //         __u8    flags;
+    //         __u16   tag;
+    //         struct in6_addr segments[0]; /* in reverse order */
+    //     };
+    pub(super) fn from_bytes(bytes: &[u8]) -> io::Result<Seg6Encap> {
+        for attr in NlAttr::parse(bytes)? {
+            if attr.attr_type() != SEG6_IPTUNNEL_SRH {
+                continue;
+            }
+            let p = attr.payload();
+            let mut cursor = Cursor::new(p);
+            let mode = match cursor.read_i32::<NativeEndian>()? {
+                0 => Seg6Mode::Inline,
+                1 => Seg6Mode::Encap,
+                2 => Seg6Mode::L2Encap,
+                _ => return Err(io::Error::new(ErrorKind::InvalidData, "unknown seg6 mode")),
+            };
+            let _nexthdr = cursor.read_u8()?;
+            let _hdrlen = cursor.read_u8()?;
+            let _srh_type = cursor.read_u8()?;
+            let _segments_left = cursor.read_u8()?;
+            let first_segment = cursor.read_u8()? as usize;
+            let _flags = cursor.read_u8()?;
+            let _tag = cursor.read_u16::<BigEndian>()?;
+
+            // Optional TLVs follow the segments
+            let start = cursor.position() as usize;
+            let end = start + (first_segment + 1) * 16;
+            if p.len() < end {
+                return Err(io::Error::new(ErrorKind::InvalidData, "short seg6 srh"));
+            }
+            let mut segments: Vec<Ipv6Addr> = p[start..end].chunks(16)
+                .map(|c| {
+                    let mut octets = [0u8; 16];
+                    octets.copy_from_slice(c);
+                    Ipv6Addr::from(octets)
+                })
+                .collect();
+            segments.reverse();
+            return Ok(Seg6Encap { mode, segments });
+        }
+        Err(io::Error::new(ErrorKind::InvalidData, "no SEG6_IPTUNNEL_SRH"))
+    }
+
+    pub(super) fn bytes(&self) -> Vec<u8> {
+        let last = self.segments.len().saturating_sub(1) as u8;
+        let mut srh = i32::from(self.mode).to_ne_bytes().to_vec();
+        srh.extend_from_slice(&[0, (self.segments.len() * 2) as u8, IPV6_SRCRT_TYPE_4, last, last, 0, 0, 0]);
+        for segment in self.segments.iter().rev() {
+            srh.extend_from_slice(&segment.octets());
+        }
+        NlAttr::new(SEG6_IPTUNNEL_SRH, &srh).bytes()
+    }
+
+    pub fn mode(&self) -> Seg6Mode {
+        self.mode
+    }
+
+    /// Segments in the order they are visited
+    pub fn segments(&self) -> &[Ipv6Addr] {
+        &self.segments
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::Ipv6Addr;
+
+    #[test]
+    fn test_seg6_roundtrip() {
+        let segments = [Ipv6Addr::new(0xfc00, 0, 0, 0, 0, 0, 0, 1), Ipv6Addr::new(0xfc00, 0, 0, 0, 0, 0, 0, 2)];
+        let encap = Seg6Encap::new(Seg6Mode::Encap, &segments);
+        let bytes = encap.bytes();
+        // Header, mode, srh and two segments
+        assert_eq!(bytes.len(), 4 + 4 + 8 + 32);
+        // The last segment comes first
+        assert_eq!(bytes[16..32], segments[1].octets());
+        assert_eq!(Seg6Encap::from_bytes(&bytes).unwrap(), encap);
+    }
+}
@@ -0,0 +1,230 @@
+use super::{Route, RtMsg, RtScope, RtnType, RTA_DST};
+use super::super::{exchange, addr_attr, RTM_GETROUTE};
+use socket::{NetlinkTransport, Msg, NlMsgHeader, Payload, ip_family};
+
+use std::borrow::Cow;
+use std::io::{self, ErrorKind};
+use std::net::{IpAddr, Ipv4Addr};
+
+const FIB_RESULT_NL_LEN: usize = 20;
+const FIB_LOOKUP_SEQ: u32 = 1;
+
+const RTM_F_LOOKUP_TABLE: u32 = 0x1000;
+
+/// An IPv4 route lookup, answered by the kernel on `Protocol::FibLookup`
+/// without installing or dumping routes
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct FibLookup {
+    addr: Ipv4Addr,
+    mark: u32,
+    tos: u8,
+    scope: RtScope,
+    table: u8,
+}
+
+impl FibLookup {
+    /// Looks up `addr` in the main table.
+    pub fn new(addr: Ipv4Addr) -> FibLookup {
+        FibLookup {
+            addr,
+            mark: 0,
+            tos: 0,
+            scope: RtScope::Universe,
+            table: 0,
+        }
+    }
+
+    /// Firewall mark of the flow
+    pub fn set_mark(&mut self, mark: u32) -> &mut FibLookup {
+        self.mark = mark;
+        self
+    }
+
+    pub fn set_tos(&mut self, tos: u8) -> &mut FibLookup {
+        self.tos = tos;
+        self
+    }
+
+    /// Narrowest scope of the routes to consider
+    pub fn set_scope(&mut self, scope: RtScope) -> &mut FibLookup {
+        self.scope = scope;
+        self
+    }
+
+    /// Table to look in, the main table for 0. Policy rules are not
+    /// applied.
+    pub fn set_table(&mut self, table: u8) -> &mut FibLookup {
+        self.table = table;
+        self
+    }
+
+    // HEADER FORMAT
+    // __be32          fl_addr;   /* To be looked up*/
+    // __u32           fl_mark;
+    // unsigned char   fl_tos;
+    // unsigned char   fl_scope;
+    // unsigned char   tb_id_in;
+    //
+    // unsigned char   tb_id;      /* Results */
+    // unsigned char   prefixlen;
+    // unsigned char   nh_sel;
+    // unsigned char   type;
+    // unsigned char   scope;
+    // int             err;
+    fn bytes(&self) -> Vec<u8> {
+        let mut bytes = self.addr.octets().to_vec();
+        bytes.extend_from_slice(&self.mark.to_ne_bytes());
+        bytes.extend_from_slice(&[self.tos, self.scope.into(), self.table]);
+        bytes.resize(FIB_RESULT_NL_LEN, 0);
+        bytes
+    }
+}
+
+/// The route matched by a `FibLookup`
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct FibResult {
+    table: u8,
+    prefix_len: u8,
+    nh_sel: u8,
+    route_type: RtnType,
+    scope: RtScope,
+}
+
+impl FibResult {
+    fn from_bytes(bytes: &[u8]) -> io::Result<FibResult> {
+        if bytes.len() < FIB_RESULT_NL_LEN {
+            return Err(io::Error::new(ErrorKind::InvalidData, "fib_result_nl too short"));
+        }
+        let err = i32::from_ne_bytes([bytes[16], bytes[17], bytes[18], bytes[19]]);
+        if err != 0 {
+            return Err(io::Error::from_raw_os_error(-err));
+        }
+        Ok(FibResult {
+            table: bytes[11],
+            prefix_len: bytes[12],
+            nh_sel: bytes[13],
+            route_type: bytes[14].into(),
+            scope: bytes[15].into(),
+        })
+    }
+
+    /// Table of the route
+    pub fn table(&self) -> u8 {
+        self.table
+    }
+
+    /// Prefix length of the route
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+
+    /// Index of the next hop chosen among the paths of a multipath route
+    pub fn nh_sel(&self) -> u8 {
+        self.nh_sel
+    }
+
+    /// Type of the route
+    pub fn route_type(&self) -> RtnType {
+        self.route_type
+    }
+
+    /// Scope of the route
+    pub fn scope(&self) -> RtScope {
+        self.scope
+    }
+}
+
+/// Returns the route the kernel would use to reach `dst` (`ip route get
+/// DST`), with the output link in `oif` and the source address it would
+/// pick in `prefsrc`, and the table that matched in `table`. Policy rules
+/// are applied. Fails with ENETUNREACH if no route matches.
+pub fn route_get(socket: &mut impl NetlinkTransport, dst: IpAddr) -> io::Result<Route> {
+    let hdr = NlMsgHeader::user_defined(RTM_GETROUTE);
+    let family = ip_family(dst);
+    let dst_len = if dst.is_ipv4() { 32 } else { 128 };
+    let rtm = RtMsg { family, dst_len, flags: RTM_F_LOOKUP_TABLE, ..Default::default() };
+    let mut payload = rtm.bytes();
+    payload.extend(addr_attr(RTA_DST, dst));
+    let replies = exchange(socket, hdr, &payload)?;
+    match replies.first() {
+        Some(reply) => Route::from_bytes(reply),
+        None => Err(io::Error::new(ErrorKind::InvalidData, "no route in reply")),
+    }
+}
+
+/// Looks up the route the kernel would use for `lookup` on a
+/// `Protocol::FibLookup` socket, without applying policy rules. Fails with
+/// EAGAIN if no route matches and ENOENT for a missing table. The result
+/// names the route, not its output link; `route_get` has that.
+pub fn fib_lookup(socket: &mut impl NetlinkTransport, lookup: &FibLookup) -> io::Result<FibResult> {
+    let payload = lookup.bytes();
+    let mut hdr = NlMsgHeader::request();
+    hdr.data_length(payload.len() as u32).seq(FIB_LOOKUP_SEQ);
+    // The kernel answers with the request itself, filled in, and never acks
+    // it: sent without NLM_F_ACK, `talk_multi` does not wait for a reply
+    socket.talk_multi(vec![Msg::new(hdr, Payload::Data(Cow::Borrowed(&payload)))])?;
+    loop {
+        for msg in socket.recv_msgs()? {
+            if msg.header().sequence() != FIB_LOOKUP_SEQ {
+                continue;
+            }
+            match *msg.payload() {
+                Payload::Data(ref b) => return FibResult::from_bytes(b),
+                Payload::Err(e, _) => return Err(io::Error::from_raw_os_error(-e)),
+                _ => {},
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::RT_TABLE_LOCAL;
+    use socket::Socket;
+    use Protocol;
+
+    #[test]
+    fn test_result_decode() {
+        let mut lookup = FibLookup::new(Ipv4Addr::new(192, 0, 2, 1));
+        lookup.set_mark(7).set_table(254);
+        let mut bytes = lookup.bytes();
+        assert_eq!(&bytes[..4], &[192, 0, 2, 1]);
+        assert_eq!(bytes[10], 254);
+
+        bytes[11..16].copy_from_slice(&[254, 24, 0, 1, 253]);
+        let result = FibResult::from_bytes(&bytes).unwrap();
+        assert_eq!((result.table(), result.prefix_len(), result.route_type(), result.scope()),
+                   (254, 24, RtnType::Unicast, RtScope::Link));
+
+        bytes[16..].copy_from_slice(&(-::libc::EAGAIN).to_ne_bytes());
+        assert_eq!(FibResult::from_bytes(&bytes).unwrap_err().raw_os_error(), Some(::libc::EAGAIN));
+    }
+
+    #[test]
+    fn test_fib_lookup() {
+        let mut socket = match Socket::new(Protocol::FibLookup) {
+            Ok(s) => s,
+            Err(ref e) if e.raw_os_error() == Some(::libc::EPROTONOSUPPORT) => return,
+            Err(e) => panic!("{}", e),
+        };
+        let mut lookup = FibLookup::new(Ipv4Addr::LOCALHOST);
+        lookup.set_table(RT_TABLE_LOCAL as u8);
+        let result = fib_lookup(&mut socket, &lookup).unwrap();
+        assert_eq!(result.table(), RT_TABLE_LOCAL as u8);
+        assert_eq!(result.route_type(), RtnType::Local);
+        assert_eq!(result.prefix_len(), 32);
+    }
+
+    #[test]
+    fn test_route_get() {
+        let mut socket = Socket::new(Protocol::Route).unwrap();
+        let route = route_get(&mut socket, IpAddr::V4(Ipv4Addr::LOCALHOST)).unwrap();
+        assert_eq!(route.route_type(), RtnType::Local);
+        assert_eq!(route.dst(), Some(IpAddr::V4(Ipv4Addr::LOCALHOST)));
+        assert_eq!(route.prefsrc(), Some(IpAddr::V4(Ipv4Addr::LOCALHOST)));
+        assert_eq!(route.oif(), Some(1));
+    }
+}
@@ -0,0 +1,111 @@
+use socket::NlAttr;
+
+use std::io;
+
+const RTAX_MTU: u16 = 2;
+const RTAX_WINDOW: u16 = 3;
+const RTAX_RTT: u16 = 4;
+const RTAX_ADVMSS: u16 = 8;
+
+/// Per-route TCP and path properties, the RTAX_* attributes nested in
+/// RTA_METRICS (`ip route add ... mtu 1400 advmss 1360`)
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct RouteMetrics {
+    mtu: Option<u32>,
+    window: Option<u32>,
+    rtt: Option<u32>,
+    advmss: Option<u32>,
+}
+
+impl RouteMetrics {
+    pub fn new() -> RouteMetrics {
+        Default::default()
+    }
+
+    pub(super) fn from_bytes(bytes: &[u8]) -> io::Result<RouteMetrics> {
+        let mut metrics = RouteMetrics::new();
+        for attr in NlAttr::parse(bytes)? {
+            // Not every metric is a u32, e.g. RTAX_CC_ALGO is a string
+            match attr.attr_type() {
+                RTAX_MTU => metrics.mtu = Some(attr.get_u32()?),
+                RTAX_WINDOW => metrics.window = Some(attr.get_u32()?),
+                RTAX_RTT => metrics.rtt = Some(attr.get_u32()?),
+                RTAX_ADVMSS => metrics.advmss = Some(attr.get_u32()?),
+                _ => {},
+            }
+        }
+        Ok(metrics)
+    }
+
+    pub(super) fn bytes(&self) -> Vec<u8> {
+        [(RTAX_MTU, self.mtu), (RTAX_WINDOW, self.window), (RTAX_RTT, self.rtt),
+         (RTAX_ADVMSS, self.advmss)].iter()
+            .filter_map(|&(attr_type, value)| value.map(|v| NlAttr::new(attr_type, &v.to_ne_bytes()).bytes()))
+            .flatten()
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == RouteMetrics::new()
+    }
+
+    /// Path MTU in bytes
+    pub fn set_mtu(&mut self, mtu: u32) -> &mut RouteMetrics {
+        self.mtu = Some(mtu);
+        self
+    }
+
+    /// Largest TCP window advertised to peers on this route, in bytes
+    pub fn set_window(&mut self, window: u32) -> &mut RouteMetrics {
+        self.window = Some(window);
+        self
+    }
+
+    /// Initial TCP round trip time estimate, in units of 1/8 millisecond as
+    /// the kernel stores it
+    pub fn set_rtt(&mut self, rtt: u32) -> &mut RouteMetrics {
+        self.rtt = Some(rtt);
+        self
+    }
+
+    /// TCP MSS advertised to peers on this route
+    pub fn set_advmss(&mut self, advmss: u32) -> &mut RouteMetrics {
+        self.advmss = Some(advmss);
+        self
+    }
+
+    pub fn mtu(&self) -> Option<u32> {
+        self.mtu
+    }
+
+    pub fn window(&self) -> Option<u32> {
+        self.window
+    }
+
+    /// Round trip time in units of 1/8 millisecond
+    pub fn rtt(&self) -> Option<u32> {
+        self.rtt
+    }
+
+    pub fn advmss(&self) -> Option<u32> {
+        self.advmss
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metrics_roundtrip() {
+        let mut metrics = RouteMetrics::new();
+        assert!(metrics.is_empty());
+        assert!(metrics.bytes().is_empty());
+
+        metrics.set_mtu(1400).set_advmss(1360);
+        let bytes = metrics.bytes();
+        assert_eq!(bytes.len(), 16);
+        assert_eq!(RouteMetrics::from_bytes(&bytes).unwrap(), metrics);
+    }
+}
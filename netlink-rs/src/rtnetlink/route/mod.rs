@@ -0,0 +1,682 @@
+//! Routes (`ip route`)
+//!
+//! A route is a `struct rtmsg` followed by attributes. Tables are
+//! identified by 32-bit ids carried in RTA_TABLE; the 8-bit `rtm_table`
+//! field only holds ids below 256.
+//!
+//! MPLS (AF_MPLS) routes match an incoming label instead of a prefix and
+//! have a single table.
+//!
+//! `route_get` asks which route, output link and source address a
+//! destination would take; `fib_lookup` does so for IPv4 without policy
+//! rules.
+
+mod encap;
+mod lookup;
+mod metrics;
+mod mpls;
+mod multipath;
+mod types;
+pub use self::encap::*;
+pub use self::lookup::*;
+pub use self::metrics::*;
+pub use self::multipath::*;
+pub use self::types::*;
+
+use self::mpls::{label_stack, parse_label_stack, via_bytes, parse_via};
+use self::multipath::{parse_multipath, multipath_bytes};
+use super::{exchange, addr, addr_attr, RTM_NEWROUTE, RTM_DELROUTE, RTM_GETROUTE};
+use socket::{NetlinkTransport, NlMsgHeader, NlAttr, NLA_F_NESTED, ip_family};
+
+use std::io::{self, Cursor};
+use std::net::IpAddr;
+
+use byteorder::{NativeEndian, ReadBytesExt};
+use libc::{AF_MPLS, AF_UNSPEC};
+
+const RTA_DST: u16 = 1;
+const RTA_SRC: u16 = 2;
+const RTA_OIF: u16 = 4;
+const RTA_GATEWAY: u16 = 5;
+const RTA_PRIORITY: u16 = 6;
+const RTA_PREFSRC: u16 = 7;
+const RTA_METRICS: u16 = 8;
+const RTA_MULTIPATH: u16 = 9;
+const RTA_TABLE: u16 = 15;
+const RTA_VIA: u16 = 18;
+const RTA_NEWDST: u16 = 19;
+const RTA_ENCAP_TYPE: u16 = 21;
+const RTA_ENCAP: u16 = 22;
+
+/// The main routing table
+pub const RT_TABLE_MAIN: u32 = 254;
+/// The table of local and broadcast addresses
+pub const RT_TABLE_LOCAL: u32 = 255;
+
+// HEADER FORMAT
+// unsigned char   rtm_family;
+// unsigned char   rtm_dst_len;
+// unsigned char   rtm_src_len;
+// unsigned char   rtm_tos;
+// unsigned char   rtm_table;      /* Routing table id */
+// unsigned char   rtm_protocol;   /* Routing protocol; see below */
+// unsigned char   rtm_scope;      /* See below */
+// unsigned char   rtm_type;       /* See below */
+// unsigned        rtm_flags;
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+struct RtMsg {
+    family: u8,
+    dst_len: u8,
+    src_len: u8,
+    tos: u8,
+    table: u8,
+    protocol: u8,
+    scope: u8,
+    rtm_type: u8,
+    flags: u32,
+}
+
+impl RtMsg {
+    fn from_bytes(bytes: &[u8]) -> io::Result<(RtMsg, usize)> {
+        let mut cursor = Cursor::new(bytes);
+        let family = cursor.read_u8()?;
+        let dst_len = cursor.read_u8()?;
+        let src_len = cursor.read_u8()?;
+        let tos = cursor.read_u8()?;
+        let table = cursor.read_u8()?;
+        let protocol = cursor.read_u8()?;
+        let scope = cursor.read_u8()?;
+        let rtm_type = cursor.read_u8()?;
+        let flags = cursor.read_u32::<NativeEndian>()?;
+        Ok((RtMsg {
+            family,
+            dst_len,
+            src_len,
+            tos,
+            table,
+            protocol,
+            scope,
+            rtm_type,
+            flags,
+        }, cursor.position() as usize))
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![self.family, self.dst_len, self.src_len, self.tos, self.table,
+                             self.protocol, self.scope, self.rtm_type];
+        bytes.extend_from_slice(&self.flags.to_ne_bytes());
+        bytes
+    }
+}
+
+/// A route, as dumped or to be added (`ip route add DST/LEN ...`)
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Route {
+    family: u8,
+    dst: Option<IpAddr>,
+    dst_len: u8,
+    label: Option<u32>,
+    new_labels: Vec<u32>,
+    via: Option<IpAddr>,
+    src: Option<IpAddr>,
+    src_len: u8,
+    gateway: Option<IpAddr>,
+    oif: Option<i32>,
+    priority: Option<u32>,
+    prefsrc: Option<IpAddr>,
+    multipath: Vec<NextHop>,
+    metrics: RouteMetrics,
+    encap: Option<RouteEncap>,
+    table: u32,
+    protocol: RtProto,
+    scope: Option<RtScope>,
+    route_type: RtnType,
+}
+
+impl Route {
+    /// Describes a unicast route to `dst/dst_len` in the main table.
+    pub fn new(dst: IpAddr, dst_len: u8) -> Route {
+        let mut route = Route::empty(ip_family(dst), dst_len);
+        route.dst = Some(dst);
+        route
+    }
+
+    /// Describes an MPLS route for packets arriving with top label `label`;
+    /// it needs `set_via` and usually `set_new_labels`.
+    pub fn mpls(label: u32) -> Route {
+        let mut route = Route::empty(AF_MPLS as u8, 20);
+        route.label = Some(label);
+        route.scope = Some(RtScope::Universe);
+        route
+    }
+
+    fn empty(family: u8, dst_len: u8) -> Route {
+        Route {
+            family,
+            dst: None,
+            dst_len,
+            label: None,
+            new_labels: vec![],
+            via: None,
+            src: None,
+            src_len: 0,
+            gateway: None,
+            oif: None,
+            priority: None,
+            prefsrc: None,
+            multipath: vec![],
+            metrics: RouteMetrics::new(),
+            encap: None,
+            table: RT_TABLE_MAIN,
+            protocol: RtProto::Boot,
+            scope: None,
+            route_type: RtnType::Unicast,
+        }
+    }
+
+    fn from_bytes(bytes: &[u8]) -> io::Result<Route> {
+        let (rtm, n) = RtMsg::from_bytes(bytes)?;
+        let mut route = Route::empty(rtm.family, rtm.dst_len);
+        route.src_len = rtm.src_len;
+        route.table = rtm.table as u32;
+        route.protocol = rtm.protocol.into();
+        route.scope = Some(rtm.scope.into());
+        route.route_type = rtm.rtm_type.into();
+        let mut encap_type = None;
+        let mut encap = None;
+        for attr in NlAttr::parse(&bytes[n..])? {
+            let p = attr.payload();
+            match attr.attr_type() {
+                RTA_DST if route.family == AF_MPLS as u8 => {
+                    route.label = parse_label_stack(p)?.first().cloned();
+                },
+                RTA_DST => route.dst = Some(attr.get_ip_in(route.family)?),
+                RTA_NEWDST => route.new_labels = parse_label_stack(p)?,
+                RTA_VIA => route.via = parse_via(p)?,
+                RTA_SRC => route.src = Some(attr.get_ip_in(route.family)?),
+                RTA_GATEWAY => route.gateway = Some(attr.get_ip_in(route.family)?),
+                RTA_PREFSRC => route.prefsrc = Some(attr.get_ip_in(route.family)?),
+                RTA_OIF => route.oif = Some(attr.get_u32()? as i32),
+                RTA_PRIORITY => route.priority = Some(attr.get_u32()?),
+                RTA_TABLE => route.table = attr.get_u32()?,
+                RTA_MULTIPATH => route.multipath = parse_multipath(p)?,
+                RTA_METRICS => route.metrics = RouteMetrics::from_bytes(p)?,
+                RTA_ENCAP_TYPE => encap_type = Some(attr.get_u16()?),
+                RTA_ENCAP => encap = Some(p),
+                _ => {},
+            }
+        }
+        if let (Some(encap_type), Some(encap)) = (encap_type, encap) {
+            route.encap = RouteEncap::from_attr(encap_type, encap)?;
+        }
+        Ok(route)
+    }
+
+    // `delete` leaves the scope unspecified unless one was set, so that
+    // routes of any scope match
+    fn bytes(&self, delete: bool) -> Vec<u8> {
+        let scope = match self.scope {
+            Some(scope) => scope,
+            None if delete => RtScope::Nowhere,
+            None if self.gateway.is_none() && self.multipath.is_empty() => RtScope::Link,
+            None => RtScope::Universe,
+        };
+        let rtm = RtMsg {
+            family: self.family,
+            dst_len: self.dst_len,
+            src_len: self.src_len,
+            table: if self.table < 256 { self.table as u8 } else { 0 },
+            protocol: self.protocol.into(),
+            scope: scope.into(),
+            rtm_type: self.route_type.into(),
+            ..Default::default()
+        };
+
+        let mut bytes = rtm.bytes();
+        // MPLS rejects attributes it does not know, RTA_TABLE included
+        if self.family != AF_MPLS as u8 {
+            bytes.extend(NlAttr::new(RTA_TABLE, &self.table.to_ne_bytes()).bytes());
+        }
+        if let Some(label) = self.label {
+            bytes.extend(NlAttr::new(RTA_DST, &label_stack(&[label])).bytes());
+        }
+        if !self.new_labels.is_empty() {
+            bytes.extend(NlAttr::new(RTA_NEWDST, &label_stack(&self.new_labels)).bytes());
+        }
+        if let Some(via) = self.via {
+            bytes.extend(NlAttr::new(RTA_VIA, &via_bytes(via)).bytes());
+        }
+        if let Some(dst) = self.dst {
+            bytes.extend(addr_attr(RTA_DST, dst));
+        }
+        if let Some(src) = self.src {
+            bytes.extend(addr_attr(RTA_SRC, src));
+        }
+        if let Some(gateway) = self.gateway {
+            bytes.extend(addr_attr(RTA_GATEWAY, gateway));
+        }
+        if let Some(prefsrc) = self.prefsrc {
+            bytes.extend(addr_attr(RTA_PREFSRC, prefsrc));
+        }
+        if let Some(oif) = self.oif {
+            bytes.extend(NlAttr::new(RTA_OIF, &oif.to_ne_bytes()).bytes());
+        }
+        if let Some(priority) = self.priority {
+            bytes.extend(NlAttr::new(RTA_PRIORITY, &priority.to_ne_bytes()).bytes());
+        }
+        if !self.multipath.is_empty() {
+            bytes.extend(NlAttr::new(RTA_MULTIPATH, &multipath_bytes(&self.multipath)).bytes());
+        }
+        if !self.metrics.is_empty() {
+            bytes.extend(NlAttr::new(RTA_METRICS | NLA_F_NESTED, &self.metrics.bytes()).bytes());
+        }
+        if let Some(ref encap) = self.encap {
+            bytes.extend(NlAttr::new(RTA_ENCAP_TYPE, &encap.encap_type().to_ne_bytes()).bytes());
+            bytes.extend(NlAttr::new(RTA_ENCAP | NLA_F_NESTED, &encap.bytes()).bytes());
+        }
+        bytes
+    }
+
+    /// Source prefix for source routing, IPv6 only
+    pub fn set_src(&mut self, src: IpAddr, src_len: u8) -> &mut Route {
+        self.src = Some(src);
+        self.src_len = src_len;
+        self
+    }
+
+    /// Labels replacing the incoming label of an MPLS route, outermost first;
+    /// without them the label is popped
+    pub fn set_new_labels(&mut self, labels: &[u32]) -> &mut Route {
+        self.new_labels = labels.to_vec();
+        self
+    }
+
+    /// Next hop of an MPLS route, or an IPv6 next hop of an IPv4 route
+    pub fn set_via(&mut self, via: IpAddr) -> &mut Route {
+        self.via = Some(via);
+        self
+    }
+
+    pub fn set_gateway(&mut self, gateway: IpAddr) -> &mut Route {
+        self.gateway = Some(gateway);
+        self
+    }
+
+    /// Output link
+    pub fn set_oif(&mut self, oif: i32) -> &mut Route {
+        self.oif = Some(oif);
+        self
+    }
+
+    /// Adds a path of a multipath route, used instead of `set_gateway` and
+    /// `set_oif`
+    pub fn add_nexthop(&mut self, nexthop: NextHop) -> &mut Route {
+        self.multipath.push(nexthop);
+        self
+    }
+
+    pub fn set_metrics(&mut self, metrics: RouteMetrics) -> &mut Route {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Encapsulates packets matching the route
+    pub fn set_encap(&mut self, encap: RouteEncap) -> &mut Route {
+        self.encap = Some(encap);
+        self
+    }
+
+    /// Metric; routes with a lower value are preferred
+    pub fn set_priority(&mut self, priority: u32) -> &mut Route {
+        self.priority = Some(priority);
+        self
+    }
+
+    /// Preferred source address of locally generated packets
+    pub fn set_prefsrc(&mut self, prefsrc: IpAddr) -> &mut Route {
+        self.prefsrc = Some(prefsrc);
+        self
+    }
+
+    /// Table id, any 32-bit value except 0
+    pub fn set_table(&mut self, table: u32) -> &mut Route {
+        self.table = table;
+        self
+    }
+
+    /// Origin of the route, `RtProto::Boot` by default
+    pub fn set_protocol(&mut self, protocol: RtProto) -> &mut Route {
+        self.protocol = protocol;
+        self
+    }
+
+    /// By default link scope without a gateway and universe scope otherwise
+    pub fn set_scope(&mut self, scope: RtScope) -> &mut Route {
+        self.scope = Some(scope);
+        self
+    }
+
+    /// `RtnType::Unicast` by default; e.g. `Blackhole` or `Unreachable` for
+    /// routes that drop matching packets
+    pub fn set_route_type(&mut self, route_type: RtnType) -> &mut Route {
+        self.route_type = route_type;
+        self
+    }
+
+    /// Address family, AF_INET, AF_INET6 or AF_MPLS
+    pub fn family(&self) -> u8 {
+        self.family
+    }
+
+    /// Destination, `None` for default routes
+    pub fn dst(&self) -> Option<IpAddr> {
+        self.dst
+    }
+
+    pub fn dst_len(&self) -> u8 {
+        self.dst_len
+    }
+
+    /// Incoming label of an MPLS route
+    pub fn label(&self) -> Option<u32> {
+        self.label
+    }
+
+    /// Outgoing labels of an MPLS route, empty if the label is popped
+    pub fn new_labels(&self) -> &[u32] {
+        &self.new_labels
+    }
+
+    pub fn via(&self) -> Option<IpAddr> {
+        self.via
+    }
+
+    pub fn src(&self) -> Option<IpAddr> {
+        self.src
+    }
+
+    pub fn src_len(&self) -> u8 {
+        self.src_len
+    }
+
+    pub fn gateway(&self) -> Option<IpAddr> {
+        self.gateway
+    }
+
+    pub fn oif(&self) -> Option<i32> {
+        self.oif
+    }
+
+    pub fn priority(&self) -> Option<u32> {
+        self.priority
+    }
+
+    pub fn prefsrc(&self) -> Option<IpAddr> {
+        self.prefsrc
+    }
+
+    /// Paths of a multipath route, empty for single path routes
+    pub fn multipath(&self) -> &[NextHop] {
+        &self.multipath
+    }
+
+    pub fn metrics(&self) -> &RouteMetrics {
+        &self.metrics
+    }
+
+    /// Encapsulation, `None` for plain routes and unsupported encapsulation
+    /// types
+    pub fn encap(&self) -> Option<&RouteEncap> {
+        self.encap.as_ref()
+    }
+
+    pub fn table(&self) -> u32 {
+        self.table
+    }
+
+    pub fn protocol(&self) -> RtProto {
+        self.protocol
+    }
+
+    /// Scope, always known for dumped routes
+    pub fn scope(&self) -> Option<RtScope> {
+        self.scope
+    }
+
+    pub fn route_type(&self) -> RtnType {
+        self.route_type
+    }
+}
+
+/// Dumps the routes of all tables and families.
+pub fn routes(socket: &mut impl NetlinkTransport) -> io::Result<Vec<Route>> {
+    let mut hdr = NlMsgHeader::user_defined(RTM_GETROUTE);
+    hdr.dump();
+    let rtm = RtMsg { family: AF_UNSPEC as u8, ..Default::default() };
+    let replies = exchange(socket, hdr, &rtm.bytes())?;
+    replies.iter().map(|r| Route::from_bytes(r)).collect()
+}
+
+/// Adds `route`, failing with `EEXIST` if the table already has it.
+pub fn add_route(socket: &mut impl NetlinkTransport, route: &Route) -> io::Result<()> {
+    let mut hdr = NlMsgHeader::user_defined(RTM_NEWROUTE);
+    hdr.create().excl();
+    exchange(socket, hdr, &route.bytes(false))?;
+    Ok(())
+}
+
+/// Deletes the route matching `route`; unset properties match any value.
+pub fn del_route(socket: &mut impl NetlinkTransport, route: &Route) -> io::Result<()> {
+    let hdr = NlMsgHeader::user_defined(RTM_DELROUTE);
+    exchange(socket, hdr, &route.bytes(true))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rtnetlink::link::{create_veth, link_by_name, set_link, LinkSet, LinkGuard};
+    use socket::Socket;
+    use Protocol;
+    use netns;
+
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    use libc::{EINVAL, EOPNOTSUPP, EPERM};
+
+    #[test]
+    fn test_rtmsg_roundtrip() {
+        let rtm = RtMsg { family: 2, dst_len: 24, table: 254, scope: RtScope::Link.into(), rtm_type: RtnType::Unicast.into(), ..Default::default() };
+        let bytes = rtm.bytes();
+        assert_eq!(bytes.len(), 12);
+        assert_eq!(RtMsg::from_bytes(&bytes).unwrap(), (rtm, 12));
+    }
+
+    #[test]
+    fn test_mpls_roundtrip() {
+        let mut route = Route::mpls(100);
+        route.set_new_labels(&[200, 300]).set_via(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))).set_oif(3);
+        let bytes = route.bytes(false);
+        let mut decoded = Route::from_bytes(&bytes).unwrap();
+        assert!(!NlAttr::parse(&bytes[12..]).unwrap().iter().any(|a| a.attr_type() == RTA_TABLE));
+        // rtm_table of MPLS routes is the main table
+        assert_eq!(decoded.table(), RT_TABLE_MAIN);
+        decoded.scope = route.scope;
+        assert_eq!(decoded, route);
+        assert_eq!(decoded.label(), Some(100));
+    }
+
+    #[test]
+    fn test_large_table() {
+        let mut route = Route::new(IpAddr::V4(Ipv4Addr::new(198, 51, 100, 0)), 24);
+        route.set_table(1000).set_oif(3);
+
+        let bytes = route.bytes(false);
+        // rtm_table is RT_TABLE_UNSPEC, the id only travels in RTA_TABLE
+        assert_eq!(bytes[4], 0);
+        assert_eq!(RtScope::from(bytes[6]), RtScope::Link);
+
+        let decoded = Route::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.table(), 1000);
+        assert_eq!(decoded.dst(), route.dst());
+        assert_eq!(decoded.oif(), Some(3));
+    }
+
+    #[test]
+    fn test_add_route() {
+        netns::run(|| {
+            let mut socket = Socket::new(Protocol::Route).unwrap();
+            match create_veth(&mut socket, "nlrs-rt0", "nlrs-rt1") {
+                Err(ref e) if e.raw_os_error() == Some(EPERM) => return,
+                r => r.unwrap(),
+            }
+            let link = link_by_name(&mut socket, "nlrs-rt0").unwrap();
+            let _guard = LinkGuard(link.index());
+            set_link(&mut socket, LinkSet::new(link.index()).up()).unwrap();
+
+            let dst = IpAddr::V4(Ipv4Addr::new(198, 51, 100, 0));
+            let mut route = Route::new(dst, 24);
+            let mut metrics = RouteMetrics::new();
+            metrics.set_mtu(1400).set_advmss(1360);
+            route.set_oif(link.index()).set_table(1000).set_metrics(metrics);
+            add_route(&mut socket, &route).unwrap();
+
+            let found = routes(&mut socket).unwrap().into_iter()
+                .find(|r| r.dst() == Some(dst) && r.table() == 1000).unwrap();
+            assert_eq!(found.oif(), Some(link.index()));
+            assert_eq!(found.scope(), Some(RtScope::Link));
+            assert_eq!(found.metrics(), &metrics);
+
+            del_route(&mut socket, &route).unwrap();
+            assert!(!routes(&mut socket).unwrap().iter().any(|r| r.table() == 1000));
+        });
+    }
+
+    #[test]
+    fn test_add_multipath_route() {
+        netns::run(|| {
+            let mut socket = Socket::new(Protocol::Route).unwrap();
+            match create_veth(&mut socket, "nlrs-mp0", "nlrs-mp1") {
+                Err(ref e) if e.raw_os_error() == Some(EPERM) => return,
+                r => r.unwrap(),
+            }
+            create_veth(&mut socket, "nlrs-mp2", "nlrs-mp3").unwrap();
+            let a = link_by_name(&mut socket, "nlrs-mp0").unwrap();
+            let b = link_by_name(&mut socket, "nlrs-mp2").unwrap();
+            let _guards = (LinkGuard(a.index()), LinkGuard(b.index()));
+            set_link(&mut socket, LinkSet::new(a.index()).up()).unwrap();
+            set_link(&mut socket, LinkSet::new(b.index()).up()).unwrap();
+
+            let dst = IpAddr::V4(Ipv4Addr::new(203, 0, 113, 0));
+            let nh_a = NextHop::new(a.index());
+            let mut nh_b = NextHop::new(b.index());
+            nh_b.set_weight(3);
+            let mut route = Route::new(dst, 24);
+            route.set_table(1001).add_nexthop(nh_a).add_nexthop(nh_b);
+            add_route(&mut socket, &route).unwrap();
+
+            let found = routes(&mut socket).unwrap().into_iter()
+                .find(|r| r.dst() == Some(dst) && r.table() == 1001).unwrap();
+            let paths: Vec<(i32, u16)> = found.multipath().iter().map(|nh| (nh.ifindex(), nh.weight())).collect();
+            assert_eq!(paths, [(a.index(), 1), (b.index(), 3)]);
+
+            del_route(&mut socket, &route).unwrap();
+        });
+    }
+
+    #[test]
+    fn test_add_seg6_route() {
+        netns::run(|| {
+            let mut socket = Socket::new(Protocol::Route).unwrap();
+            match create_veth(&mut socket, "nlrs-sr0", "nlrs-sr1") {
+                Err(ref e) if e.raw_os_error() == Some(EPERM) => return,
+                r => r.unwrap(),
+            }
+            let link = link_by_name(&mut socket, "nlrs-sr0").unwrap();
+            let _guard = LinkGuard(link.index());
+            set_link(&mut socket, LinkSet::new(link.index()).up()).unwrap();
+
+            let dst = IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 5, 0, 0, 0, 0, 0));
+            let segments = [Ipv6Addr::new(0xfc00, 0, 0, 0, 0, 0, 0, 1), Ipv6Addr::new(0xfc00, 0, 0, 0, 0, 0, 0, 2)];
+            let encap = RouteEncap::Seg6(Seg6Encap::new(Seg6Mode::Encap, &segments));
+            let mut route = Route::new(dst, 64);
+            route.set_oif(link.index()).set_table(1002).set_encap(encap.clone());
+            match add_route(&mut socket, &route) {
+                // No CONFIG_IPV6_SEG6_LWTUNNEL
+                Err(ref e) if e.raw_os_error() == Some(EOPNOTSUPP) => return,
+                r => r.unwrap(),
+            }
+
+            let found = routes(&mut socket).unwrap().into_iter()
+                .find(|r| r.dst() == Some(dst) && r.table() == 1002).unwrap();
+            assert_eq!(found.encap(), Some(&encap));
+
+            del_route(&mut socket, &route).unwrap();
+        });
+    }
+
+    #[test]
+    fn test_add_encap_routes() {
+        netns::run(|| {
+            let mut socket = Socket::new(Protocol::Route).unwrap();
+            match create_veth(&mut socket, "nlrs-lwt0", "nlrs-lwt1") {
+                Err(ref e) if e.raw_os_error() == Some(EPERM) => return,
+                r => r.unwrap(),
+            }
+            let link = link_by_name(&mut socket, "nlrs-lwt0").unwrap();
+            let _guard = LinkGuard(link.index());
+            set_link(&mut socket, LinkSet::new(link.index()).up()).unwrap();
+
+            let mut ip = IpEncap::new(5, IpAddr::V4(Ipv4Addr::new(192, 0, 2, 9)));
+            ip.set_ttl(64);
+            let mut mpls = MplsEncap::new(&[100, 200]);
+            mpls.set_ttl(32);
+            let encaps = [(RouteEncap::Ip(ip), 25), (RouteEncap::Mpls(mpls), 26)];
+
+            let dst = IpAddr::V4(Ipv4Addr::new(198, 51, 100, 0));
+            for &(ref encap, dst_len) in &encaps {
+                let mut route = Route::new(dst, dst_len);
+                route.set_oif(link.index()).set_table(1003).set_encap(encap.clone());
+                match add_route(&mut socket, &route) {
+                    // No MPLS in this kernel
+                    Err(ref e) if e.raw_os_error() == Some(EOPNOTSUPP) => continue,
+                    r => r.unwrap(),
+                }
+                let found = routes(&mut socket).unwrap().into_iter()
+                    .find(|r| r.dst() == Some(dst) && r.dst_len() == dst_len && r.table() == 1003).unwrap();
+                assert_eq!(found.encap(), Some(encap));
+                del_route(&mut socket, &route).unwrap();
+            }
+        });
+    }
+
+    #[test]
+    fn test_add_mpls_route() {
+        netns::run(|| {
+            let mut socket = Socket::new(Protocol::Route).unwrap();
+            match create_veth(&mut socket, "nlrs-mpls0", "nlrs-mpls1") {
+                Err(ref e) if e.raw_os_error() == Some(EPERM) => return,
+                r => r.unwrap(),
+            }
+            let link = link_by_name(&mut socket, "nlrs-mpls0").unwrap();
+            let _guard = LinkGuard(link.index());
+            set_link(&mut socket, LinkSet::new(link.index()).up()).unwrap();
+
+            let mut route = Route::mpls(1000);
+            route.set_new_labels(&[2000]).set_via(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))).set_oif(link.index());
+            match add_route(&mut socket, &route) {
+                // No mpls_router in this kernel, or the label space is not
+                // configured (net.mpls.platform_labels)
+                Err(ref e) if e.raw_os_error() == Some(EOPNOTSUPP) || e.raw_os_error() == Some(EINVAL) => return,
+                r => r.unwrap(),
+            }
+
+            let found = routes(&mut socket).unwrap().into_iter().find(|r| r.label() == Some(1000)).unwrap();
+            assert_eq!(found.new_labels(), [2000]);
+            assert_eq!(found.via(), route.via());
+
+            del_route(&mut socket, &route).unwrap();
+        });
+    }
+}
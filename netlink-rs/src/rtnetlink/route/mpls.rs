@@ -0,0 +1,85 @@
+use super::addr;
+
+use std::io::{self, Cursor, ErrorKind};
+use std::net::IpAddr;
+
+use byteorder::{BigEndian, NativeEndian, ReadBytesExt};
+use libc::{AF_INET, AF_INET6};
+
+const MPLS_LS_LABEL_SHIFT: u32 = 12;
+const MPLS_LS_S: u32 = 1 << 8;
+
+/// Encodes `labels`, outermost first, as big endian label stack entries
+///     label:20 | traffic class:3 | bottom of stack:1 | ttl:8
+pub(super) fn label_stack(labels: &[u32]) -> Vec<u8> {
+    let mut bytes = vec![];
+    for (i, label) in labels.iter().enumerate() {
+        let mut entry = label << MPLS_LS_LABEL_SHIFT;
+        if i == labels.len() - 1 {
+            entry |= MPLS_LS_S;
+        }
+        bytes.extend_from_slice(&entry.to_be_bytes());
+    }
+    bytes
+}
+
+/// Decodes a label stack, returning the labels outermost first.
+pub(super) fn parse_label_stack(bytes: &[u8]) -> io::Result<Vec<u32>> {
+    if !bytes.len().is_multiple_of(4) {
+        return Err(io::Error::new(ErrorKind::InvalidData, "bad mpls label stack length"));
+    }
+    let mut cursor = Cursor::new(bytes);
+    let mut labels = vec![];
+    for _ in 0..bytes.len() / 4 {
+        labels.push(cursor.read_u32::<BigEndian>()? >> MPLS_LS_LABEL_SHIFT);
+    }
+    Ok(labels)
+}
+
+// struct rtvia {
+//     __kernel_sa_family_t    rtvia_family;
+//     __u8                    rtvia_addr[0];
+// };
+pub(super) fn via_bytes(via: IpAddr) -> Vec<u8> {
+    let (family, octets) = match via {
+        IpAddr::V4(a) => (AF_INET, a.octets().to_vec()),
+        IpAddr::V6(a) => (AF_INET6, a.octets().to_vec()),
+    };
+    let mut bytes = (family as u16).to_ne_bytes().to_vec();
+    bytes.extend(octets);
+    bytes
+}
+
+/// Decodes RTA_VIA; link layer (AF_PACKET) next hops are not supported.
+pub(super) fn parse_via(bytes: &[u8]) -> io::Result<Option<IpAddr>> {
+    let family = Cursor::new(bytes).read_u16::<NativeEndian>()? as i32;
+    if family == AF_INET || family == AF_INET6 {
+        Ok(Some(addr(&bytes[2..])?))
+    } else {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::{IpAddr, Ipv6Addr};
+
+    #[test]
+    fn test_label_stack() {
+        let bytes = label_stack(&[100, 200]);
+        // Bottom of stack is only set on the innermost label
+        assert_eq!(bytes, [0x00, 0x06, 0x40, 0x00, 0x00, 0x0c, 0x81, 0x00]);
+        assert_eq!(parse_label_stack(&bytes).unwrap(), [100, 200]);
+        assert!(parse_label_stack(&bytes[..6]).is_err());
+    }
+
+    #[test]
+    fn test_via_roundtrip() {
+        let via = IpAddr::V6(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1));
+        let bytes = via_bytes(via);
+        assert_eq!(bytes.len(), 18);
+        assert_eq!(parse_via(&bytes).unwrap(), Some(via));
+    }
+}
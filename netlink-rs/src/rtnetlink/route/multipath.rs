@@ -0,0 +1,145 @@
+use super::{addr, addr_attr, RTA_GATEWAY};
+use socket::NlAttr;
+
+use std::io::{self, Cursor, ErrorKind};
+use std::net::IpAddr;
+
+use byteorder::{NativeEndian, ReadBytesExt};
+
+const RTNH_F_ONLINK: u8 = 4;
+
+const RTNH_LEN: usize = 8;
+
+/// One path of a multipath (ECMP) route (`ip route add ... nexthop ...`)
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct NextHop {
+    ifindex: i32,
+    gateway: Option<IpAddr>,
+    hops: u8,
+    flags: u8,
+}
+
+impl NextHop {
+    /// Describes a path through link `ifindex` with weight 1.
+    pub fn new(ifindex: i32) -> NextHop {
+        NextHop {
+            ifindex,
+            gateway: None,
+            hops: 0,
+            flags: 0,
+        }
+    }
+
+    pub fn set_gateway(&mut self, gateway: IpAddr) -> &mut NextHop {
+        self.gateway = Some(gateway);
+        self
+    }
+
+    /// Share of the traffic relative to the other paths, from 1 to 256
+    pub fn set_weight(&mut self, weight: u16) -> &mut NextHop {
+        self.hops = (weight.clamp(1, 256) - 1) as u8;
+        self
+    }
+
+    /// Use the gateway even if no address of the link covers it
+    pub fn onlink(&mut self) -> &mut NextHop {
+        self.flags |= RTNH_F_ONLINK;
+        self
+    }
+
+    pub fn ifindex(&self) -> i32 {
+        self.ifindex
+    }
+
+    pub fn gateway(&self) -> Option<IpAddr> {
+        self.gateway
+    }
+
+    pub fn weight(&self) -> u16 {
+        self.hops as u16 + 1
+    }
+
+    /// RTNH_F_* flags, e.g. RTNH_F_DEAD for paths over a link that is down
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    // struct rtnexthop {
+    //     unsigned short  rtnh_len;
+    //     unsigned char   rtnh_flags;
+    //     unsigned char   rtnh_hops;
+    //     int             rtnh_ifindex;
+    // };
+    // followed by attributes of the path, padded to 4 bytes
+    fn bytes(&self) -> Vec<u8> {
+        let attrs = match self.gateway {
+            Some(gateway) => addr_attr(RTA_GATEWAY, gateway),
+            None => vec![],
+        };
+        let mut bytes = ((RTNH_LEN + attrs.len()) as u16).to_ne_bytes().to_vec();
+        bytes.push(self.flags);
+        bytes.push(self.hops);
+        bytes.extend_from_slice(&self.ifindex.to_ne_bytes());
+        bytes.extend(attrs);
+        bytes
+    }
+}
+
+/// Decodes the rtnexthop array of RTA_MULTIPATH.
+pub(super) fn parse_multipath(bytes: &[u8]) -> io::Result<Vec<NextHop>> {
+    let mut nexthops = vec![];
+    let mut n = 0;
+    while n + RTNH_LEN <= bytes.len() {
+        let mut cursor = Cursor::new(&bytes[n..]);
+        let len = cursor.read_u16::<NativeEndian>()? as usize;
+        if len < RTNH_LEN || n + len > bytes.len() {
+            return Err(io::Error::new(ErrorKind::InvalidData, "bad rtnexthop length"));
+        }
+        let flags = cursor.read_u8()?;
+        let hops = cursor.read_u8()?;
+        let ifindex = cursor.read_i32::<NativeEndian>()?;
+
+        let mut nexthop = NextHop { ifindex, gateway: None, hops, flags };
+        for attr in NlAttr::parse(&bytes[n + RTNH_LEN..n + len])? {
+            if attr.attr_type() == RTA_GATEWAY {
+                nexthop.gateway = Some(addr(attr.payload())?);
+            }
+        }
+        nexthops.push(nexthop);
+        n += (len + 3) & !3;
+    }
+    Ok(nexthops)
+}
+
+/// Encodes `nexthops` as the payload of RTA_MULTIPATH.
+pub(super) fn multipath_bytes(nexthops: &[NextHop]) -> Vec<u8> {
+    nexthops.iter().flat_map(|nh| nh.bytes()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::{IpAddr, Ipv4Addr};
+
+    #[test]
+    fn test_multipath_roundtrip() {
+        let mut a = NextHop::new(2);
+        a.set_gateway(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))).set_weight(3).onlink();
+        let b = NextHop::new(3);
+
+        let bytes = multipath_bytes(&[a, b]);
+        assert_eq!(bytes.len(), 16 + 8);
+        let decoded = parse_multipath(&bytes).unwrap();
+        assert_eq!(decoded, [a, b]);
+        assert_eq!(decoded[0].weight(), 3);
+    }
+
+    #[test]
+    fn test_weight_bounds() {
+        let mut nh = NextHop::new(1);
+        assert_eq!(nh.set_weight(0).weight(), 1);
+        assert_eq!(nh.set_weight(1000).weight(), 256);
+    }
+}
@@ -0,0 +1,200 @@
+/// Origin of a route (RTPROT_*)
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum RtProto {
+    Unspec,
+    /// ICMP redirect
+    Redirect,
+    /// Added by the kernel, e.g. for an address
+    Kernel,
+    /// Added at boot or by `ip route` without a protocol
+    Boot,
+    /// Added by the administrator
+    Static,
+    /// IPv6 router advertisement
+    Ra,
+    Zebra,
+    Bird,
+    Dhcp,
+    Keepalived,
+    Babel,
+    Bgp,
+    Isis,
+    Ospf,
+    Rip,
+    Eigrp,
+    Other(u8),
+}
+
+impl From<u8> for RtProto {
+    fn from(t: u8) -> RtProto {
+        match t {
+            0 => RtProto::Unspec,
+            1 => RtProto::Redirect,
+            2 => RtProto::Kernel,
+            3 => RtProto::Boot,
+            4 => RtProto::Static,
+            9 => RtProto::Ra,
+            11 => RtProto::Zebra,
+            12 => RtProto::Bird,
+            16 => RtProto::Dhcp,
+            18 => RtProto::Keepalived,
+            42 => RtProto::Babel,
+            186 => RtProto::Bgp,
+            187 => RtProto::Isis,
+            188 => RtProto::Ospf,
+            189 => RtProto::Rip,
+            192 => RtProto::Eigrp,
+            i => RtProto::Other(i),
+        }
+    }
+}
+
+impl From<RtProto> for u8 {
+    fn from(t: RtProto) -> u8 {
+        match t {
+            RtProto::Unspec => 0,
+            RtProto::Redirect => 1,
+            RtProto::Kernel => 2,
+            RtProto::Boot => 3,
+            RtProto::Static => 4,
+            RtProto::Ra => 9,
+            RtProto::Zebra => 11,
+            RtProto::Bird => 12,
+            RtProto::Dhcp => 16,
+            RtProto::Keepalived => 18,
+            RtProto::Babel => 42,
+            RtProto::Bgp => 186,
+            RtProto::Isis => 187,
+            RtProto::Ospf => 188,
+            RtProto::Rip => 189,
+            RtProto::Eigrp => 192,
+            RtProto::Other(i) => i,
+        }
+    }
+}
+
+/// Distance to the destination (RT_SCOPE_*)
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum RtScope {
+    /// Anywhere, through a gateway
+    Universe,
+    Site,
+    /// On an attached link
+    Link,
+    /// On this host
+    Host,
+    /// No destination
+    Nowhere,
+    Other(u8),
+}
+
+impl From<u8> for RtScope {
+    fn from(t: u8) -> RtScope {
+        match t {
+            0 => RtScope::Universe,
+            200 => RtScope::Site,
+            253 => RtScope::Link,
+            254 => RtScope::Host,
+            255 => RtScope::Nowhere,
+            i => RtScope::Other(i),
+        }
+    }
+}
+
+impl From<RtScope> for u8 {
+    fn from(t: RtScope) -> u8 {
+        match t {
+            RtScope::Universe => 0,
+            RtScope::Site => 200,
+            RtScope::Link => 253,
+            RtScope::Host => 254,
+            RtScope::Nowhere => 255,
+            RtScope::Other(i) => i,
+        }
+    }
+}
+
+/// Type of a route (RTN_*)
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum RtnType {
+    Unspec,
+    /// Gateway or direct route
+    Unicast,
+    /// Address of this host
+    Local,
+    /// Accepted as broadcast, sent as broadcast
+    Broadcast,
+    Anycast,
+    Multicast,
+    /// Silently dropped
+    Blackhole,
+    /// Rejected with host unreachable
+    Unreachable,
+    /// Rejected with administratively prohibited
+    Prohibit,
+    /// Lookup continues in the next table
+    Throw,
+    Nat,
+    Xresolve,
+    Other(u8),
+}
+
+impl From<u8> for RtnType {
+    fn from(t: u8) -> RtnType {
+        match t {
+            0 => RtnType::Unspec,
+            1 => RtnType::Unicast,
+            2 => RtnType::Local,
+            3 => RtnType::Broadcast,
+            4 => RtnType::Anycast,
+            5 => RtnType::Multicast,
+            6 => RtnType::Blackhole,
+            7 => RtnType::Unreachable,
+            8 => RtnType::Prohibit,
+            9 => RtnType::Throw,
+            10 => RtnType::Nat,
+            11 => RtnType::Xresolve,
+            i => RtnType::Other(i),
+        }
+    }
+}
+
+impl From<RtnType> for u8 {
+    fn from(t: RtnType) -> u8 {
+        match t {
+            RtnType::Unspec => 0,
+            RtnType::Unicast => 1,
+            RtnType::Local => 2,
+            RtnType::Broadcast => 3,
+            RtnType::Anycast => 4,
+            RtnType::Multicast => 5,
+            RtnType::Blackhole => 6,
+            RtnType::Unreachable => 7,
+            RtnType::Prohibit => 8,
+            RtnType::Throw => 9,
+            RtnType::Nat => 10,
+            RtnType::Xresolve => 11,
+            RtnType::Other(i) => i,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route_enums() {
+        for t in 0..=255u8 {
+            assert_eq!(u8::from(RtProto::from(t)), t);
+            assert_eq!(u8::from(RtScope::from(t)), t);
+            assert_eq!(u8::from(RtnType::from(t)), t);
+        }
+        assert_eq!(RtScope::from(253), RtScope::Link);
+        assert_eq!(RtProto::from(3), RtProto::Boot);
+        assert_eq!(RtnType::from(200), RtnType::Other(200));
+    }
+}
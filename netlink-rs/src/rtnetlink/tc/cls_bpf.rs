@@ -0,0 +1,167 @@
+use super::add_filter;
+use socket::{NetlinkTransport, NlAttr, string_attr};
+
+use std::io;
+use std::os::unix::io::RawFd;
+
+const TCA_BPF_CLASSID: u16 = 3;
+const TCA_BPF_FD: u16 = 6;
+const TCA_BPF_NAME: u16 = 7;
+const TCA_BPF_FLAGS: u16 = 8;
+
+const TCA_BPF_FLAG_ACT_DIRECT: u32 = 1 << 0;
+
+/// Options of the BPF classifier, running an already loaded program of type
+/// BPF_PROG_TYPE_SCHED_CLS (`tc filter add ... bpf fd FD`)
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct BpfFilter {
+    fd: RawFd,
+    name: String,
+    classid: Option<u32>,
+    direct_action: bool,
+}
+
+impl BpfFilter {
+    /// The kernel takes its own reference to the program, `fd` may be closed
+    /// once the filter is added. `name` is informational only.
+    pub fn new(fd: RawFd, name: &str) -> BpfFilter {
+        BpfFilter {
+            fd,
+            name: name.to_owned(),
+            classid: None,
+            direct_action: false,
+        }
+    }
+
+    /// Use the program's return value as the tc action (`da`), the usual mode
+    /// on clsact hooks
+    pub fn direct_action(&mut self) -> &mut BpfFilter {
+        self.direct_action = true;
+        self
+    }
+
+    /// Default class of matching packets when the program does not return one
+    pub fn set_classid(&mut self, classid: u32) -> &mut BpfFilter {
+        self.classid = Some(classid);
+        self
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        let mut bytes = NlAttr::new(TCA_BPF_FD, &(self.fd as u32).to_ne_bytes()).bytes();
+        bytes.extend(string_attr(TCA_BPF_NAME, &self.name));
+        if self.direct_action {
+            bytes.extend(NlAttr::new(TCA_BPF_FLAGS, &TCA_BPF_FLAG_ACT_DIRECT.to_ne_bytes()).bytes());
+        }
+        if let Some(classid) = self.classid {
+            bytes.extend(NlAttr::new(TCA_BPF_CLASSID, &classid.to_ne_bytes()).bytes());
+        }
+        bytes
+    }
+}
+
+/// Attaches a BPF filter to `parent` of link `ifindex`, typically a clsact
+/// hook. `protocol` is the Ethernet protocol to match, e.g.
+/// `libc::ETH_P_ALL as u16`.
+pub fn add_bpf_filter(socket: &mut impl NetlinkTransport, ifindex: i32, parent: u32, prio: u16, protocol: u16,
+                      filter: &BpfFilter) -> io::Result<()> {
+    add_filter(socket, ifindex, parent, prio, protocol, "bpf", &filter.bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{add_clsact, filters, tc_handle, TC_H_INGRESS, TC_H_MIN_EGRESS};
+    use rtnetlink::link::{create_veth, link_by_name, LinkGuard};
+    use socket::{Socket, NlAttr};
+    use Protocol;
+    use netns;
+
+    use std::io;
+    use std::mem;
+    use std::os::unix::io::RawFd;
+
+    use libc::{self, EPERM, ETH_P_ALL};
+
+    // The leading fields of union bpf_attr for BPF_PROG_LOAD
+    #[repr(C)]
+    struct ProgLoadAttr {
+        prog_type: u32,
+        insn_cnt: u32,
+        insns: u64,
+        license: u64,
+        log_level: u32,
+        log_size: u32,
+        log_buf: u64,
+        kern_version: u32,
+        prog_flags: u32,
+    }
+
+    // Loads `r0 = 0; exit`, TC_ACT_OK in direct action mode
+    fn load_prog() -> io::Result<RawFd> {
+        const BPF_PROG_LOAD: libc::c_long = 5;
+        const BPF_PROG_TYPE_SCHED_CLS: u32 = 3;
+        let insns: [u64; 2] = [0xb7, 0x95];
+        let license = b"GPL\0";
+        let attr = ProgLoadAttr {
+            prog_type: BPF_PROG_TYPE_SCHED_CLS,
+            insn_cnt: insns.len() as u32,
+            insns: insns.as_ptr() as u64,
+            license: license.as_ptr() as u64,
+            log_level: 0,
+            log_size: 0,
+            log_buf: 0,
+            kern_version: 0,
+            prog_flags: 0,
+        };
+        let fd = unsafe {
+            libc::syscall(libc::SYS_bpf, BPF_PROG_LOAD, &attr as *const ProgLoadAttr,
+                          mem::size_of::<ProgLoadAttr>())
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(fd as RawFd)
+    }
+
+    #[test]
+    fn test_bpf_bytes() {
+        let mut filter = BpfFilter::new(7, "prog");
+        filter.direct_action();
+
+        let bytes = filter.bytes();
+        let attrs = NlAttr::parse(&bytes).unwrap();
+        let types: Vec<u16> = attrs.iter().map(|a| a.attr_type()).collect();
+        assert_eq!(types, [TCA_BPF_FD, TCA_BPF_NAME, TCA_BPF_FLAGS]);
+        assert_eq!(attrs[0].payload(), &7u32.to_ne_bytes());
+        assert_eq!(attrs[1].payload(), b"prog\0");
+    }
+
+    #[test]
+    fn test_add_bpf_filter() {
+        netns::run(|| {
+            let fd = match load_prog() {
+                Ok(fd) => fd,
+                // No bpf() or not privileged enough to load programs
+                Err(_) => return,
+            };
+            let mut socket = Socket::new(Protocol::Route).unwrap();
+            match create_veth(&mut socket, "nlrs-bpf0", "nlrs-bpf1") {
+                Err(ref e) if e.raw_os_error() == Some(EPERM) => return,
+                r => r.unwrap(),
+            }
+            let link = link_by_name(&mut socket, "nlrs-bpf0").unwrap();
+            let _guard = LinkGuard(link.index());
+            add_clsact(&mut socket, link.index()).unwrap();
+
+            let parent = tc_handle((TC_H_INGRESS >> 16) as u16, TC_H_MIN_EGRESS);
+            let mut filter = BpfFilter::new(fd, "nlrs_pass");
+            filter.direct_action();
+            add_bpf_filter(&mut socket, link.index(), parent, 1, ETH_P_ALL as u16, &filter).unwrap();
+            unsafe { libc::close(fd) };
+
+            let found = filters(&mut socket, link.index(), parent).unwrap();
+            assert!(found.iter().any(|f| f.kind() == "bpf"));
+        });
+    }
+}
@@ -0,0 +1,181 @@
+use super::add_filter;
+use socket::{NetlinkTransport, NlAttr};
+
+use std::io;
+use std::net::Ipv4Addr;
+
+const TCA_U32_CLASSID: u16 = 1;
+const TCA_U32_SEL: u16 = 5;
+
+const TC_U32_TERMINAL: u8 = 1;
+
+// Offsets into the IPv4 header
+const IPV4_SRC: i32 = 12;
+const IPV4_DST: i32 = 16;
+
+// struct tc_u32_key {
+//     __be32  mask;
+//     __be32  val;
+//     int     off;
+//     int     offmask;
+// };
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+struct U32Key {
+    value: u32,
+    mask: u32,
+    offset: i32,
+}
+
+/// Options of the u32 classifier, matching 32-bit words of the packet
+/// (`tc filter add ... u32 match u32 VALUE MASK at OFFSET`)
+///
+/// All keys must match. A filter without keys matches every packet.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct U32Filter {
+    keys: Vec<U32Key>,
+    classid: Option<u32>,
+}
+
+impl U32Filter {
+    pub fn new() -> U32Filter {
+        U32Filter::default()
+    }
+
+    /// Matches the word at `offset` bytes into the network header, which must
+    /// be a multiple of 4. `value` and `mask` are in host byte order.
+    pub fn match_u32(&mut self, value: u32, mask: u32, offset: i32) -> &mut U32Filter {
+        self.keys.push(U32Key { value: value & mask, mask, offset });
+        self
+    }
+
+    /// Matches IPv4 packets from `addr/prefix_len`
+    pub fn match_ipv4_src(&mut self, addr: Ipv4Addr, prefix_len: u8) -> &mut U32Filter {
+        self.match_u32(u32::from(addr), prefix_mask(prefix_len), IPV4_SRC)
+    }
+
+    /// Matches IPv4 packets to `addr/prefix_len`
+    pub fn match_ipv4_dst(&mut self, addr: Ipv4Addr, prefix_len: u8) -> &mut U32Filter {
+        self.match_u32(u32::from(addr), prefix_mask(prefix_len), IPV4_DST)
+    }
+
+    /// Classifies matching packets into class `classid` (`flowid`)
+    pub fn set_classid(&mut self, classid: u32) -> &mut U32Filter {
+        self.classid = Some(classid);
+        self
+    }
+
+    // struct tc_u32_sel {
+    //     unsigned char   flags;
+    //     unsigned char   offshift;
+    //     unsigned char   nkeys;
+    //     __be16          offmask;
+    //     __u16           off;
+    //     short           offoff;
+    //     short           hoff;
+    //     __be32          hmask;
+    //     struct tc_u32_key keys[0];
+    // };
+    fn bytes(&self) -> io::Result<Vec<u8>> {
+        if self.keys.len() > u8::MAX as usize {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "too many u32 keys"));
+        }
+        let flags = if self.classid.is_some() { TC_U32_TERMINAL } else { 0 };
+        let mut sel = vec![flags, 0, self.keys.len() as u8, 0];
+        sel.extend_from_slice(&[0; 12]);
+        for key in &self.keys {
+            sel.extend_from_slice(&key.mask.to_be_bytes());
+            sel.extend_from_slice(&key.value.to_be_bytes());
+            sel.extend_from_slice(&key.offset.to_ne_bytes());
+            sel.extend_from_slice(&0i32.to_ne_bytes());
+        }
+
+        let mut bytes = vec![];
+        if let Some(classid) = self.classid {
+            bytes.extend(NlAttr::new(TCA_U32_CLASSID, &classid.to_ne_bytes()).bytes());
+        }
+        bytes.extend(NlAttr::new(TCA_U32_SEL, &sel).bytes());
+        Ok(bytes)
+    }
+}
+
+fn prefix_mask(prefix_len: u8) -> u32 {
+    match prefix_len {
+        0 => 0,
+        n if n >= 32 => u32::MAX,
+        n => !(u32::MAX >> n),
+    }
+}
+
+/// Attaches a u32 filter to `parent` of link `ifindex`. `protocol` is the
+/// Ethernet protocol to match, e.g. `libc::ETH_P_IP as u16`.
+pub fn add_u32_filter(socket: &mut impl NetlinkTransport, ifindex: i32, parent: u32, prio: u16, protocol: u16,
+                      filter: &U32Filter) -> io::Result<()> {
+    add_filter(socket, ifindex, parent, prio, protocol, "u32", &filter.bytes()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{add_clsact, filters, del_filter, tc_handle, TC_H_INGRESS, TC_H_MIN_INGRESS};
+    use rtnetlink::link::{create_veth, link_by_name, LinkGuard};
+    use socket::{Socket, NlAttr};
+    use Protocol;
+    use netns;
+
+    use std::net::Ipv4Addr;
+
+    use libc::{EPERM, ETH_P_IP};
+
+    #[test]
+    fn test_prefix_mask() {
+        assert_eq!(prefix_mask(0), 0);
+        assert_eq!(prefix_mask(24), 0xFFFF_FF00);
+        assert_eq!(prefix_mask(32), u32::MAX);
+    }
+
+    #[test]
+    fn test_u32_bytes() {
+        let mut filter = U32Filter::new();
+        filter.match_ipv4_dst(Ipv4Addr::new(10, 0, 0, 1), 24).set_classid(tc_handle(1, 10));
+
+        let bytes = filter.bytes().unwrap();
+        let attrs = NlAttr::parse(&bytes).unwrap();
+        assert_eq!(attrs[0].attr_type(), TCA_U32_CLASSID);
+        assert_eq!(attrs[0].payload(), &tc_handle(1, 10).to_ne_bytes());
+
+        let sel = attrs[1].payload();
+        assert_eq!(sel.len(), 32);
+        assert_eq!(sel[..3], [TC_U32_TERMINAL, 0, 1]);
+        assert_eq!(sel[16..24], [255, 255, 255, 0, 10, 0, 0, 0]);
+        assert_eq!(sel[24..28], IPV4_DST.to_ne_bytes());
+    }
+
+    #[test]
+    fn test_add_u32_filter() {
+        netns::run(|| {
+            let mut socket = Socket::new(Protocol::Route).unwrap();
+            match create_veth(&mut socket, "nlrs-u320", "nlrs-u321") {
+                Err(ref e) if e.raw_os_error() == Some(EPERM) => return,
+                r => r.unwrap(),
+            }
+            let link = link_by_name(&mut socket, "nlrs-u320").unwrap();
+            let _guard = LinkGuard(link.index());
+            add_clsact(&mut socket, link.index()).unwrap();
+
+            let parent = tc_handle((TC_H_INGRESS >> 16) as u16, TC_H_MIN_INGRESS);
+            let mut filter = U32Filter::new();
+            filter.match_ipv4_src(Ipv4Addr::new(192, 0, 2, 0), 24).set_classid(tc_handle(1, 1));
+            add_u32_filter(&mut socket, link.index(), parent, 1, ETH_P_IP as u16, &filter).unwrap();
+
+            let found = filters(&mut socket, link.index(), parent).unwrap();
+            assert!(!found.is_empty());
+            assert!(found.iter().all(|f| f.kind() == "u32" && f.prio() == 1));
+            assert!(found.iter().all(|f| f.protocol() == ETH_P_IP as u16));
+
+            del_filter(&mut socket, link.index(), parent, 1, ETH_P_IP as u16).unwrap();
+            assert!(filters(&mut socket, link.index(), parent).unwrap().is_empty());
+        });
+    }
+}
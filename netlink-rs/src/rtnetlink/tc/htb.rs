@@ -0,0 +1,259 @@
+use super::{add_qdisc, add_class, ticks};
+use socket::{NetlinkTransport, NlAttr};
+
+use std::cmp;
+use std::io;
+
+const TCA_HTB_PARMS: u16 = 1;
+const TCA_HTB_INIT: u16 = 2;
+const TCA_HTB_RATE64: u16 = 6;
+const TCA_HTB_CEIL64: u16 = 7;
+
+const TC_HTB_PROTOVER: u32 = 3;
+
+// Burst allowance when none is configured, as computed by tc: one timer
+// tick at HZ=1000 worth of bytes plus an MTU
+const DEFAULT_MTU: u32 = 1600;
+const DEFAULT_HZ: u64 = 1000;
+
+/// Options of an HTB qdisc (`tc qdisc add ... htb default 10`)
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct HtbOptions {
+    default_class: u32,
+}
+
+impl HtbOptions {
+    pub fn new() -> HtbOptions {
+        HtbOptions::default()
+    }
+
+    /// Minor of the class unclassified traffic goes to. Without one such
+    /// traffic bypasses shaping.
+    pub fn set_default_class(&mut self, minor: u16) -> &mut HtbOptions {
+        self.default_class = minor as u32;
+        self
+    }
+
+    // struct tc_htb_glob {
+    //     __u32 version;       /* to match HTB/TC */
+    //     __u32 rate2quantum;  /* bps->quantum divisor */
+    //     __u32 defcls;        /* default class number */
+    //     __u32 debug;         /* debug flags */
+    //     __u32 direct_pkts;   /* count of non shaped packets */
+    // };
+    fn bytes(&self) -> Vec<u8> {
+        let mut glob = vec![];
+        for v in &[TC_HTB_PROTOVER, 10, self.default_class, 0, 0] {
+            glob.extend_from_slice(&v.to_ne_bytes());
+        }
+        NlAttr::new(TCA_HTB_INIT, &glob).bytes()
+    }
+}
+
+/// Options of an HTB class (`tc class add ... htb rate RATE ceil CEIL`)
+///
+/// Rates are in bytes per second, bursts in bytes.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct HtbClass {
+    rate: u64,
+    ceil: u64,
+    burst: Option<u32>,
+    cburst: Option<u32>,
+    quantum: u32,
+    prio: u32,
+}
+
+impl HtbClass {
+    /// Guarantees `rate`, which is also the ceiling unless `set_ceil` is used.
+    pub fn new(rate: u64) -> HtbClass {
+        HtbClass {
+            rate,
+            ceil: rate,
+            burst: None,
+            cburst: None,
+            quantum: 0,
+            prio: 0,
+        }
+    }
+
+    /// Rate the class may borrow up to from its parent
+    pub fn set_ceil(&mut self, ceil: u64) -> &mut HtbClass {
+        self.ceil = ceil;
+        self
+    }
+
+    /// Bytes that may be sent at once above `rate`
+    pub fn set_burst(&mut self, burst: u32) -> &mut HtbClass {
+        self.burst = Some(burst);
+        self
+    }
+
+    /// Bytes that may be sent at once above `ceil`
+    pub fn set_cburst(&mut self, cburst: u32) -> &mut HtbClass {
+        self.cburst = Some(cburst);
+        self
+    }
+
+    /// Bytes dequeued per round when borrowing, derived from the rate if 0
+    pub fn set_quantum(&mut self, quantum: u32) -> &mut HtbClass {
+        self.quantum = quantum;
+        self
+    }
+
+    /// Classes with a lower priority are offered excess bandwidth first
+    pub fn set_prio(&mut self, prio: u32) -> &mut HtbClass {
+        self.prio = prio;
+        self
+    }
+
+    // struct tc_htb_opt {
+    //     struct tc_ratespec  rate;
+    //     struct tc_ratespec  ceil;
+    //     __u32   buffer;     /* psched ticks */
+    //     __u32   cbuffer;
+    //     __u32   quantum;
+    //     __u32   level;      /* out only */
+    //     __u32   prio;
+    // };
+    fn bytes(&self) -> Vec<u8> {
+        let burst = self.burst.unwrap_or_else(|| default_burst(self.rate));
+        let cburst = self.cburst.unwrap_or_else(|| default_burst(self.ceil));
+
+        let mut opt = ratespec(self.rate);
+        opt.extend(ratespec(self.ceil));
+        for v in &[xmit_ticks(self.rate, burst), xmit_ticks(self.ceil, cburst), self.quantum,
+                   0, self.prio] {
+            opt.extend_from_slice(&v.to_ne_bytes());
+        }
+
+        let mut bytes = vec![];
+        // Rates beyond 32 bits are only carried by the 64-bit attributes
+        if self.rate > u32::MAX as u64 {
+            bytes.extend(NlAttr::new(TCA_HTB_RATE64, &self.rate.to_ne_bytes()).bytes());
+        }
+        if self.ceil > u32::MAX as u64 {
+            bytes.extend(NlAttr::new(TCA_HTB_CEIL64, &self.ceil.to_ne_bytes()).bytes());
+        }
+        bytes.extend(NlAttr::new(TCA_HTB_PARMS, &opt).bytes());
+        bytes
+    }
+}
+
+// struct tc_ratespec {
+//     unsigned char   cell_log;
+//     __u8            linklayer;
+//     unsigned short  overhead;
+//     short           cell_align;
+//     unsigned short  mpu;
+//     __u32           rate;       /* bytes per second */
+// };
+fn ratespec(rate: u64) -> Vec<u8> {
+    let mut bytes = vec![0; 8];
+    bytes.extend_from_slice(&(cmp::min(rate, u32::MAX as u64) as u32).to_ne_bytes());
+    bytes
+}
+
+fn default_burst(rate: u64) -> u32 {
+    cmp::min(rate / DEFAULT_HZ + DEFAULT_MTU as u64, u32::MAX as u64) as u32
+}
+
+// Time to send `size` bytes at `rate`, in psched ticks
+fn xmit_ticks(rate: u64, size: u32) -> u32 {
+    if rate == 0 {
+        return u32::MAX;
+    }
+    let ns = size as u128 * 1_000_000_000 / rate as u128;
+    ticks(cmp::min(ns, i64::MAX as u128) as i64)
+}
+
+/// Attaches an HTB qdisc to link `ifindex` at `parent`, e.g. `TC_H_ROOT`.
+pub fn add_htb(socket: &mut impl NetlinkTransport, ifindex: i32, parent: u32, handle: u32,
+               options: &HtbOptions) -> io::Result<()> {
+    add_qdisc(socket, ifindex, parent, handle, "htb", &options.bytes())
+}
+
+/// Adds HTB class `classid` below `parent`, the HTB qdisc or another of its
+/// classes.
+pub fn add_htb_class(socket: &mut impl NetlinkTransport, ifindex: i32, parent: u32, classid: u32,
+                     class: &HtbClass) -> io::Result<()> {
+    add_class(socket, ifindex, parent, classid, "htb", &class.bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{classes, del_class, tc_handle, TC_H_ROOT};
+    use rtnetlink::link::{create_veth, link_by_name, LinkGuard};
+    use socket::{Socket, NlAttr};
+    use Protocol;
+    use netns;
+
+    use std::io::Cursor;
+
+    use byteorder::{NativeEndian, ReadBytesExt};
+    use libc::EPERM;
+
+    #[test]
+    fn test_xmit_ticks() {
+        // 1500 bytes at 1.5 MB/s take a millisecond
+        assert_eq!(xmit_ticks(1_500_000, 1500), 1_000_000 >> 6);
+        assert_eq!(xmit_ticks(0, 1500), u32::MAX);
+    }
+
+    #[test]
+    fn test_htb_class_bytes() {
+        let mut class = HtbClass::new(125_000);
+        class.set_ceil(1 << 33).set_burst(1500).set_prio(2);
+
+        let bytes = class.bytes();
+        let attrs = NlAttr::parse(&bytes).unwrap();
+        assert_eq!(attrs[0].attr_type(), TCA_HTB_CEIL64);
+        assert_eq!(attrs[1].attr_type(), TCA_HTB_PARMS);
+
+        let opt = attrs[1].payload();
+        assert_eq!(opt.len(), 44);
+        let mut cursor = Cursor::new(opt);
+        cursor.set_position(8);
+        assert_eq!(cursor.read_u32::<NativeEndian>().unwrap(), 125_000);
+        cursor.set_position(20);
+        assert_eq!(cursor.read_u32::<NativeEndian>().unwrap(), u32::MAX);
+        assert_eq!(cursor.read_u32::<NativeEndian>().unwrap(), xmit_ticks(125_000, 1500));
+        cursor.set_position(40);
+        assert_eq!(cursor.read_u32::<NativeEndian>().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_add_htb_class() {
+        netns::run(|| {
+            let mut socket = Socket::new(Protocol::Route).unwrap();
+            match create_veth(&mut socket, "nlrs-htb0", "nlrs-htb1") {
+                Err(ref e) if e.raw_os_error() == Some(EPERM) => return,
+                r => r.unwrap(),
+            }
+            let link = link_by_name(&mut socket, "nlrs-htb0").unwrap();
+            let _guard = LinkGuard(link.index());
+
+            let mut options = HtbOptions::new();
+            options.set_default_class(20);
+            add_htb(&mut socket, link.index(), TC_H_ROOT, tc_handle(1, 0), &options).unwrap();
+
+            let root = tc_handle(1, 1);
+            let mut class = HtbClass::new(1_250_000);
+            add_htb_class(&mut socket, link.index(), tc_handle(1, 0), root, &class).unwrap();
+            class = HtbClass::new(125_000);
+            class.set_ceil(1_250_000);
+            add_htb_class(&mut socket, link.index(), root, tc_handle(1, 20), &class).unwrap();
+
+            let found = classes(&mut socket, link.index()).unwrap();
+            let leaf = found.iter().find(|c| c.classid() == tc_handle(1, 20)).unwrap();
+            assert_eq!(leaf.parent(), root);
+            assert_eq!(leaf.kind(), "htb");
+
+            del_class(&mut socket, link.index(), tc_handle(1, 20)).unwrap();
+            assert!(!classes(&mut socket, link.index()).unwrap()
+                    .iter().any(|c| c.classid() == tc_handle(1, 20)));
+        });
+    }
+}
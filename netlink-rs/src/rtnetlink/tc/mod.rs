@@ -0,0 +1,429 @@
+//! Traffic control (`tc`)
+//!
+//! Qdiscs, classes and filters are addressed by 32-bit handles written
+//! `major:minor` by `tc`; see `tc_handle`. Kind specific options are nested
+//! in TCA_OPTIONS and live in their own modules.
+
+mod cls_bpf;
+pub use self::cls_bpf::*;
+mod cls_u32;
+pub use self::cls_u32::*;
+mod htb;
+pub use self::htb::*;
+mod netem;
+pub use self::netem::*;
+
+use super::{exchange, RTM_NEWQDISC, RTM_DELQDISC, RTM_GETQDISC, RTM_NEWTCLASS,
+            RTM_DELTCLASS, RTM_GETTCLASS, RTM_NEWTFILTER, RTM_DELTFILTER, RTM_GETTFILTER};
+use socket::{NetlinkTransport, NlMsgHeader, NlAttr, string_attr};
+
+use std::cmp;
+use std::io::{self, Cursor};
+
+use byteorder::{NativeEndian, ReadBytesExt};
+use libc::AF_UNSPEC;
+
+const TCA_KIND: u16 = 1;
+const TCA_OPTIONS: u16 = 2;
+
+// Nanoseconds per psched tick, see PSCHED_SHIFT
+const PSCHED_SHIFT: u32 = 6;
+
+/// Parent of a root qdisc
+pub const TC_H_ROOT: u32 = 0xFFFF_FFFF;
+/// Parent of the ingress and clsact qdiscs
+pub const TC_H_INGRESS: u32 = 0xFFFF_FFF1;
+/// Minor of the clsact ingress hook, filters go on `ffff:fff2`
+pub const TC_H_MIN_INGRESS: u16 = 0xFFF2;
+/// Minor of the clsact egress hook, filters go on `ffff:fff3`
+pub const TC_H_MIN_EGRESS: u16 = 0xFFF3;
+
+/// Builds the handle `major:minor`.
+pub fn tc_handle(major: u16, minor: u16) -> u32 {
+    (major as u32) << 16 | minor as u32
+}
+
+// Converts nanoseconds to the psched ticks used by qdisc parameters
+fn ticks(ns: i64) -> u32 {
+    cmp::min(ns >> PSCHED_SHIFT, u32::MAX as i64) as u32
+}
+
+// HEADER FORMAT
+// unsigned char   tcm_family;
+// unsigned char   tcm__pad1;
+// unsigned short  tcm__pad2;
+// int             tcm_ifindex;
+// __u32           tcm_handle;
+// __u32           tcm_parent;
+// __u32           tcm_info;
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+struct TcMsg {
+    family: u8,
+    ifindex: i32,
+    handle: u32,
+    parent: u32,
+    info: u32,
+}
+
+impl TcMsg {
+    fn new(ifindex: i32, handle: u32, parent: u32) -> TcMsg {
+        TcMsg {
+            family: AF_UNSPEC as u8,
+            ifindex,
+            handle,
+            parent,
+            info: 0,
+        }
+    }
+
+    // Filters are keyed by priority and protocol, the latter in network
+    // byte order
+    fn filter(ifindex: i32, parent: u32, prio: u16, protocol: u16) -> TcMsg {
+        TcMsg {
+            info: (prio as u32) << 16 | protocol.to_be() as u32,
+            ..TcMsg::new(ifindex, 0, parent)
+        }
+    }
+
+    fn from_bytes(bytes: &[u8]) -> io::Result<(TcMsg, usize)> {
+        let mut cursor = Cursor::new(bytes);
+        let family = cursor.read_u8()?;
+        let _pad1 = cursor.read_u8()?;
+        let _pad2 = cursor.read_u16::<NativeEndian>()?;
+        let ifindex = cursor.read_i32::<NativeEndian>()?;
+        let handle = cursor.read_u32::<NativeEndian>()?;
+        let parent = cursor.read_u32::<NativeEndian>()?;
+        let info = cursor.read_u32::<NativeEndian>()?;
+        Ok((TcMsg {
+            family,
+            ifindex,
+            handle,
+            parent,
+            info,
+        }, cursor.position() as usize))
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![self.family, 0, 0, 0];
+        bytes.extend_from_slice(&self.ifindex.to_ne_bytes());
+        bytes.extend_from_slice(&self.handle.to_ne_bytes());
+        bytes.extend_from_slice(&self.parent.to_ne_bytes());
+        bytes.extend_from_slice(&self.info.to_ne_bytes());
+        bytes
+    }
+}
+
+/// A queueing discipline attached to a link
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Qdisc {
+    ifindex: i32,
+    handle: u32,
+    parent: u32,
+    kind: String,
+    options: Vec<u8>,
+}
+
+impl Qdisc {
+    fn from_bytes(bytes: &[u8]) -> io::Result<Qdisc> {
+        let (tcm, n) = TcMsg::from_bytes(bytes)?;
+        let mut qdisc = Qdisc {
+            ifindex: tcm.ifindex,
+            handle: tcm.handle,
+            parent: tcm.parent,
+            kind: String::new(),
+            options: vec![],
+        };
+        for attr in NlAttr::parse(&bytes[n..])? {
+            match attr.attr_type() {
+                TCA_KIND => qdisc.kind = attr.get_str()?.to_string(),
+                TCA_OPTIONS => qdisc.options = attr.payload().to_vec(),
+                _ => {},
+            }
+        }
+        Ok(qdisc)
+    }
+
+    pub fn ifindex(&self) -> i32 {
+        self.ifindex
+    }
+
+    pub fn handle(&self) -> u32 {
+        self.handle
+    }
+
+    /// Handle of the parent class, or `TC_H_ROOT`/`TC_H_INGRESS`
+    pub fn parent(&self) -> u32 {
+        self.parent
+    }
+
+    /// Qdisc type, e.g. "fq_codel" or "netem"
+    pub fn kind(&self) -> &str {
+        &self.kind
+    }
+
+    /// Raw TCA_OPTIONS payload, specific to the kind
+    pub fn options(&self) -> &[u8] {
+        &self.options
+    }
+}
+
+/// A class of a classful qdisc such as HTB
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Class {
+    ifindex: i32,
+    classid: u32,
+    parent: u32,
+    kind: String,
+    options: Vec<u8>,
+}
+
+impl Class {
+    fn from_bytes(bytes: &[u8]) -> io::Result<Class> {
+        let (tcm, n) = TcMsg::from_bytes(bytes)?;
+        let mut class = Class {
+            ifindex: tcm.ifindex,
+            classid: tcm.handle,
+            parent: tcm.parent,
+            kind: String::new(),
+            options: vec![],
+        };
+        for attr in NlAttr::parse(&bytes[n..])? {
+            match attr.attr_type() {
+                TCA_KIND => class.kind = attr.get_str()?.to_string(),
+                TCA_OPTIONS => class.options = attr.payload().to_vec(),
+                _ => {},
+            }
+        }
+        Ok(class)
+    }
+
+    pub fn ifindex(&self) -> i32 {
+        self.ifindex
+    }
+
+    pub fn classid(&self) -> u32 {
+        self.classid
+    }
+
+    /// Handle of the parent class or qdisc
+    pub fn parent(&self) -> u32 {
+        self.parent
+    }
+
+    /// Kind of the owning qdisc
+    pub fn kind(&self) -> &str {
+        &self.kind
+    }
+
+    /// Raw TCA_OPTIONS payload, specific to the kind
+    pub fn options(&self) -> &[u8] {
+        &self.options
+    }
+}
+
+/// A classifier attached to a qdisc or class
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Filter {
+    ifindex: i32,
+    handle: u32,
+    parent: u32,
+    prio: u16,
+    protocol: u16,
+    kind: String,
+    options: Vec<u8>,
+}
+
+impl Filter {
+    fn from_bytes(bytes: &[u8]) -> io::Result<Filter> {
+        let (tcm, n) = TcMsg::from_bytes(bytes)?;
+        let mut filter = Filter {
+            ifindex: tcm.ifindex,
+            handle: tcm.handle,
+            parent: tcm.parent,
+            prio: (tcm.info >> 16) as u16,
+            protocol: u16::from_be(tcm.info as u16),
+            kind: String::new(),
+            options: vec![],
+        };
+        for attr in NlAttr::parse(&bytes[n..])? {
+            match attr.attr_type() {
+                TCA_KIND => filter.kind = attr.get_str()?.to_string(),
+                TCA_OPTIONS => filter.options = attr.payload().to_vec(),
+                _ => {},
+            }
+        }
+        Ok(filter)
+    }
+
+    pub fn ifindex(&self) -> i32 {
+        self.ifindex
+    }
+
+    /// Handle assigned by the classifier, e.g. `800::800` for u32
+    pub fn handle(&self) -> u32 {
+        self.handle
+    }
+
+    pub fn parent(&self) -> u32 {
+        self.parent
+    }
+
+    /// Filters with a lower priority run first
+    pub fn prio(&self) -> u16 {
+        self.prio
+    }
+
+    /// Ethernet protocol matched, e.g. ETH_P_IP
+    pub fn protocol(&self) -> u16 {
+        self.protocol
+    }
+
+    /// Classifier type, e.g. "u32" or "bpf"
+    pub fn kind(&self) -> &str {
+        &self.kind
+    }
+
+    /// Raw TCA_OPTIONS payload, specific to the kind
+    pub fn options(&self) -> &[u8] {
+        &self.options
+    }
+}
+
+/// Lists the qdiscs of all links.
+pub fn qdiscs(socket: &mut impl NetlinkTransport) -> io::Result<Vec<Qdisc>> {
+    let mut hdr = NlMsgHeader::user_defined(RTM_GETQDISC);
+    hdr.dump();
+    let replies = exchange(socket, hdr, &TcMsg::default().bytes())?;
+    replies.iter().map(|r| Qdisc::from_bytes(r)).collect()
+}
+
+/// Deletes the qdisc of link `ifindex` attached at `parent`, restoring the
+/// default qdisc when `parent` is `TC_H_ROOT`.
+pub fn del_qdisc(socket: &mut impl NetlinkTransport, ifindex: i32, parent: u32) -> io::Result<()> {
+    let hdr = NlMsgHeader::user_defined(RTM_DELQDISC);
+    exchange(socket, hdr, &TcMsg::new(ifindex, 0, parent).bytes())?;
+    Ok(())
+}
+
+/// Adds a clsact qdisc to link `ifindex`, providing the `TC_H_MIN_INGRESS`
+/// and `TC_H_MIN_EGRESS` hooks for filters such as BPF programs.
+pub fn add_clsact(socket: &mut impl NetlinkTransport, ifindex: i32) -> io::Result<()> {
+    add_qdisc(socket, ifindex, TC_H_INGRESS, tc_handle(0xFFFF, 0), "clsact", &[])
+}
+
+/// Lists the classes of link `ifindex`.
+pub fn classes(socket: &mut impl NetlinkTransport, ifindex: i32) -> io::Result<Vec<Class>> {
+    let mut hdr = NlMsgHeader::user_defined(RTM_GETTCLASS);
+    hdr.dump();
+    let replies = exchange(socket, hdr, &TcMsg::new(ifindex, 0, 0).bytes())?;
+    replies.iter().map(|r| Class::from_bytes(r)).collect()
+}
+
+/// Deletes class `classid` of link `ifindex`, which must not have children.
+pub fn del_class(socket: &mut impl NetlinkTransport, ifindex: i32, classid: u32) -> io::Result<()> {
+    let hdr = NlMsgHeader::user_defined(RTM_DELTCLASS);
+    exchange(socket, hdr, &TcMsg::new(ifindex, classid, 0).bytes())?;
+    Ok(())
+}
+
+/// Lists the filters attached to `parent` of link `ifindex`.
+pub fn filters(socket: &mut impl NetlinkTransport, ifindex: i32, parent: u32) -> io::Result<Vec<Filter>> {
+    let mut hdr = NlMsgHeader::user_defined(RTM_GETTFILTER);
+    hdr.dump();
+    let replies = exchange(socket, hdr, &TcMsg::new(ifindex, 0, parent).bytes())?;
+    replies.iter().map(|r| Filter::from_bytes(r)).collect()
+}
+
+/// Deletes the filters of priority `prio` and `protocol` attached to
+/// `parent` of link `ifindex`.
+pub fn del_filter(socket: &mut impl NetlinkTransport, ifindex: i32, parent: u32, prio: u16, protocol: u16)
+                  -> io::Result<()> {
+    let hdr = NlMsgHeader::user_defined(RTM_DELTFILTER);
+    exchange(socket, hdr, &TcMsg::filter(ifindex, parent, prio, protocol).bytes())?;
+    Ok(())
+}
+
+fn kind_attrs(kind: &str, options: &[u8]) -> Vec<u8> {
+    let mut bytes = string_attr(TCA_KIND, kind);
+    if !options.is_empty() {
+        bytes.extend(NlAttr::new(TCA_OPTIONS, options).bytes());
+    }
+    bytes
+}
+
+/// Adds a qdisc of `kind`, failing with `EEXIST` if `parent` already has
+/// one. `options` is the kind specific TCA_OPTIONS payload.
+fn add_qdisc(socket: &mut impl NetlinkTransport, ifindex: i32, parent: u32, handle: u32, kind: &str,
+             options: &[u8]) -> io::Result<()> {
+    let mut hdr = NlMsgHeader::user_defined(RTM_NEWQDISC);
+    hdr.create().excl();
+    let mut payload = TcMsg::new(ifindex, handle, parent).bytes();
+    payload.extend(kind_attrs(kind, options));
+    exchange(socket, hdr, &payload)?;
+    Ok(())
+}
+
+/// Adds class `classid` below `parent`, a qdisc of `kind` or one of its
+/// classes.
+fn add_class(socket: &mut impl NetlinkTransport, ifindex: i32, parent: u32, classid: u32, kind: &str,
+             options: &[u8]) -> io::Result<()> {
+    let mut hdr = NlMsgHeader::user_defined(RTM_NEWTCLASS);
+    hdr.create().excl();
+    let mut payload = TcMsg::new(ifindex, classid, parent).bytes();
+    payload.extend(kind_attrs(kind, options));
+    exchange(socket, hdr, &payload)?;
+    Ok(())
+}
+
+/// Adds a filter of `kind`, letting the classifier pick its handle.
+fn add_filter(socket: &mut impl NetlinkTransport, ifindex: i32, parent: u32, prio: u16, protocol: u16,
+              kind: &str, options: &[u8]) -> io::Result<()> {
+    let mut hdr = NlMsgHeader::user_defined(RTM_NEWTFILTER);
+    hdr.create().excl();
+    let mut payload = TcMsg::filter(ifindex, parent, prio, protocol).bytes();
+    payload.extend(kind_attrs(kind, options));
+    exchange(socket, hdr, &payload)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket::Socket;
+    use Protocol;
+
+    #[test]
+    fn test_tc_handle() {
+        assert_eq!(tc_handle(1, 0), 0x10000);
+        assert_eq!(tc_handle(0xffff, 0xffff), TC_H_ROOT);
+    }
+
+    #[test]
+    fn test_tcmsg_roundtrip() {
+        let tcm = TcMsg::new(2, tc_handle(1, 0), TC_H_ROOT);
+        let bytes = tcm.bytes();
+        assert_eq!(bytes.len(), 20);
+        let (decoded, n) = TcMsg::from_bytes(&bytes).unwrap();
+        assert_eq!(n, 20);
+        assert_eq!(decoded, tcm);
+    }
+
+    #[test]
+    fn test_filter_info() {
+        let tcm = TcMsg::filter(2, TC_H_ROOT, 10, 0x0800);
+        let filter = Filter::from_bytes(&tcm.bytes()).unwrap();
+        assert_eq!(filter.prio(), 10);
+        assert_eq!(filter.protocol(), 0x0800);
+        assert_eq!(tcm.bytes()[16..], [0x08, 0x00, 10, 0][..]);
+    }
+
+    #[test]
+    fn test_dump_qdiscs() {
+        let mut socket = Socket::new(Protocol::Route).unwrap();
+        let qdiscs = qdiscs(&mut socket).unwrap();
+        assert!(qdiscs.iter().all(|q| !q.kind().is_empty()));
+    }
+}
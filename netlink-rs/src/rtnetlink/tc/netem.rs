@@ -0,0 +1,212 @@
+use super::{add_qdisc, ticks};
+use socket::{NetlinkTransport, NlAttr};
+
+use std::cmp;
+use std::io;
+use std::time::Duration;
+
+const TCA_NETEM_REORDER: u16 = 3;
+const TCA_NETEM_LATENCY64: u16 = 10;
+const TCA_NETEM_JITTER64: u16 = 11;
+
+/// Network emulator impairments (`tc qdisc add ... netem delay 100ms loss 1%`)
+///
+/// Probabilities are given in percent.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct NetemOptions {
+    delay: Duration,
+    jitter: Duration,
+    limit: u32,
+    loss: f64,
+    duplicate: f64,
+    reorder: f64,
+    gap: u32,
+}
+
+impl NetemOptions {
+    pub fn new() -> NetemOptions {
+        NetemOptions {
+            delay: Duration::from_secs(0),
+            jitter: Duration::from_secs(0),
+            limit: 1000,
+            loss: 0.0,
+            duplicate: 0.0,
+            reorder: 0.0,
+            gap: 0,
+        }
+    }
+
+    /// Added delay of every packet
+    pub fn set_delay(&mut self, delay: Duration) -> &mut NetemOptions {
+        self.delay = delay;
+        self
+    }
+
+    /// Random variation of the delay, uniformly distributed
+    pub fn set_jitter(&mut self, jitter: Duration) -> &mut NetemOptions {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Queue length in packets
+    pub fn set_limit(&mut self, limit: u32) -> &mut NetemOptions {
+        self.limit = limit;
+        self
+    }
+
+    pub fn set_loss(&mut self, percent: f64) -> &mut NetemOptions {
+        self.loss = percent;
+        self
+    }
+
+    pub fn set_duplicate(&mut self, percent: f64) -> &mut NetemOptions {
+        self.duplicate = percent;
+        self
+    }
+
+    /// Send this share of packets immediately, ahead of the delayed ones.
+    /// Only has an effect together with a delay.
+    pub fn set_reorder(&mut self, percent: f64) -> &mut NetemOptions {
+        self.reorder = percent;
+        self
+    }
+
+    /// Reorder every `gap`th packet instead of randomly
+    pub fn set_gap(&mut self, gap: u32) -> &mut NetemOptions {
+        self.gap = gap;
+        self
+    }
+
+    // struct tc_netem_qopt {
+    //     __u32   latency;    /* added delay (psched ticks) */
+    //     __u32   limit;      /* fifo limit (packets) */
+    //     __u32   loss;       /* random packet loss (0=none ~0=100%) */
+    //     __u32   gap;        /* re-ordering gap (0 for none) */
+    //     __u32   duplicate;  /* random packet dup  (0=none ~0=100%) */
+    //     __u32   jitter;     /* random jitter in latency (psched ticks) */
+    // };
+    // followed by TCA_NETEM_* attributes, not nested in their own attribute
+    fn bytes(&self) -> Vec<u8> {
+        let delay = duration_ns(self.delay);
+        let jitter = duration_ns(self.jitter);
+        let gap = if self.reorder > 0.0 && self.gap == 0 { 1 } else { self.gap };
+
+        let mut bytes = vec![];
+        for v in &[ticks(delay), self.limit, probability(self.loss), gap,
+                   probability(self.duplicate), ticks(jitter)] {
+            bytes.extend_from_slice(&v.to_ne_bytes());
+        }
+        // The 64-bit attributes override the tick values, which overflow
+        // after about 4.5 minutes
+        bytes.extend(NlAttr::new(TCA_NETEM_LATENCY64, &delay.to_ne_bytes()).bytes());
+        bytes.extend(NlAttr::new(TCA_NETEM_JITTER64, &jitter.to_ne_bytes()).bytes());
+        if self.reorder > 0.0 {
+            // struct tc_netem_reorder { __u32 probability; __u32 correlation; }
+            let mut reorder = probability(self.reorder).to_ne_bytes().to_vec();
+            reorder.extend_from_slice(&0u32.to_ne_bytes());
+            bytes.extend(NlAttr::new(TCA_NETEM_REORDER, &reorder).bytes());
+        }
+        bytes
+    }
+}
+
+impl Default for NetemOptions {
+    fn default() -> NetemOptions {
+        NetemOptions::new()
+    }
+}
+
+fn duration_ns(d: Duration) -> i64 {
+    cmp::min(d.as_nanos(), i64::MAX as u128) as i64
+}
+
+/// Scales a percentage to the kernel's 0..=u32::MAX probability range
+fn probability(percent: f64) -> u32 {
+    if percent >= 100.0 {
+        u32::MAX
+    } else if percent <= 0.0 {
+        0
+    } else {
+        (percent / 100.0 * u32::MAX as f64) as u32
+    }
+}
+
+/// Attaches a netem qdisc to link `ifindex` at `parent`, e.g. `TC_H_ROOT`.
+pub fn add_netem(socket: &mut impl NetlinkTransport, ifindex: i32, parent: u32, handle: u32,
+                 options: &NetemOptions) -> io::Result<()> {
+    add_qdisc(socket, ifindex, parent, handle, "netem", &options.bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{qdiscs, del_qdisc, tc_handle, TC_H_ROOT, PSCHED_SHIFT};
+    use rtnetlink::link::{create_veth, link_by_name, LinkGuard};
+    use socket::{Socket, NlAttr};
+    use Protocol;
+    use netns;
+
+    use std::io::Cursor;
+    use std::time::Duration;
+
+    use byteorder::{NativeEndian, ReadBytesExt};
+    use libc::{EPERM, ENOENT};
+
+    #[test]
+    fn test_probability() {
+        assert_eq!(probability(0.0), 0);
+        assert_eq!(probability(100.0), u32::MAX);
+        assert_eq!(probability(50.0), u32::MAX / 2);
+    }
+
+    #[test]
+    fn test_netem_bytes() {
+        let mut options = NetemOptions::new();
+        options.set_delay(Duration::from_millis(100))
+            .set_jitter(Duration::from_millis(10))
+            .set_loss(1.0)
+            .set_reorder(25.0);
+
+        let bytes = options.bytes();
+        let mut cursor = Cursor::new(&bytes[..]);
+        assert_eq!(cursor.read_u32::<NativeEndian>().unwrap(), 100_000_000 >> PSCHED_SHIFT);
+        assert_eq!(cursor.read_u32::<NativeEndian>().unwrap(), 1000);
+        assert_eq!(cursor.read_u32::<NativeEndian>().unwrap(), probability(1.0));
+        assert_eq!(cursor.read_u32::<NativeEndian>().unwrap(), 1);
+
+        let attrs = NlAttr::parse(&bytes[24..]).unwrap();
+        let types: Vec<u16> = attrs.iter().map(|a| a.attr_type()).collect();
+        assert_eq!(types, [TCA_NETEM_LATENCY64, TCA_NETEM_JITTER64, TCA_NETEM_REORDER]);
+        assert_eq!(attrs[0].payload(), &100_000_000i64.to_ne_bytes());
+        assert_eq!(attrs[1].payload(), &10_000_000i64.to_ne_bytes());
+    }
+
+    #[test]
+    fn test_add_netem() {
+        netns::run(|| {
+            let mut socket = Socket::new(Protocol::Route).unwrap();
+            match create_veth(&mut socket, "nlrs-nem0", "nlrs-nem1") {
+                Err(ref e) if e.raw_os_error() == Some(EPERM) => return,
+                r => r.unwrap(),
+            }
+            let link = link_by_name(&mut socket, "nlrs-nem0").unwrap();
+            let _guard = LinkGuard(link.index());
+
+            let mut options = NetemOptions::new();
+            options.set_delay(Duration::from_millis(20)).set_loss(0.5);
+            match add_netem(&mut socket, link.index(), TC_H_ROOT, tc_handle(1, 0), &options) {
+                // No sch_netem in this kernel
+                Err(ref e) if e.raw_os_error() == Some(ENOENT) => {},
+                r => {
+                    r.unwrap();
+                    let qdiscs = qdiscs(&mut socket).unwrap();
+                    let q = qdiscs.iter().find(|q| q.ifindex() == link.index()).unwrap();
+                    assert_eq!(q.kind(), "netem");
+                    assert_eq!(q.handle(), tc_handle(1, 0));
+                    del_qdisc(&mut socket, link.index(), TC_H_ROOT).unwrap();
+                },
+            }
+        });
+    }
+}
@@ -0,0 +1,110 @@
+//! SELinux notifications
+//!
+//! With SELinux enabled, the kernel announces changes of the enforcing mode
+//! and policy loads on the notify group of `Protocol::SELinux`, e.g. for
+//! userspace object managers to flush their access vector caches.
+
+use socket::{Socket, NetlinkTransport, NetlinkAddr, MsgType, parse};
+use Protocol;
+
+use std::io::{self, ErrorKind};
+
+const SELNL_GRP_AVC: u32 = 1;
+const SELNL_MSG_SETENFORCE: u16 = 0x10;
+const SELNL_MSG_POLICYLOAD: u16 = 0x11;
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum SelinuxEvent {
+    /// Enforcing (`true`) or permissive mode was set (`struct
+    /// selnl_msg_setenforce`)
+    SetEnforce(bool),
+    /// A policy was loaded, with the policy sequence number (`struct
+    /// selnl_msg_policyload`)
+    PolicyLoad(u32),
+}
+
+impl SelinuxEvent {
+    /// Decodes the payload of a message of type `msg_type`. `None` for
+    /// other types.
+    pub fn from_bytes(msg_type: u16, bytes: &[u8]) -> io::Result<Option<SelinuxEvent>> {
+        if msg_type != SELNL_MSG_SETENFORCE && msg_type != SELNL_MSG_POLICYLOAD {
+            return Ok(None);
+        }
+        if bytes.len() < 4 {
+            return Err(io::Error::new(ErrorKind::InvalidData, "SELinux notification too short"));
+        }
+        let val = u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        Ok(Some(match msg_type {
+            SELNL_MSG_SETENFORCE => SelinuxEvent::SetEnforce(val != 0),
+            _ => SelinuxEvent::PolicyLoad(val),
+        }))
+    }
+}
+
+/// A socket subscribed to SELinux notifications
+pub struct SelinuxMonitor<T: NetlinkTransport = Socket> {
+    socket: T,
+}
+
+impl SelinuxMonitor {
+    /// Subscribes to the notify group. Fails with EPROTONOSUPPORT when
+    /// SELinux is disabled.
+    pub fn new() -> io::Result<SelinuxMonitor> {
+        let socket = Socket::new(Protocol::SELinux)?;
+        socket.bind(NetlinkAddr::new(0, SELNL_GRP_AVC))?;
+        Ok(SelinuxMonitor::from_transport(socket))
+    }
+}
+
+impl<T: NetlinkTransport> SelinuxMonitor<T> {
+    /// Reads notifications from `transport`, which is already subscribed.
+    pub fn from_transport(transport: T) -> SelinuxMonitor<T> {
+        SelinuxMonitor { socket: transport }
+    }
+
+    /// Blocks until notifications arrive. Fails with an `Overrun` error if
+    /// some were dropped; there is no dump to resync from, only the
+    /// current mode in selinuxfs.
+    pub fn recv(&mut self) -> io::Result<Vec<SelinuxEvent>> {
+        loop {
+            let (_, datagram) = self.socket.recv_bytes()?;
+            let mut events = vec![];
+            for m in parse::messages(datagram) {
+                let (hdr, payload) = m?;
+                if let MsgType::UserDefined(t) = hdr.msg_type() {
+                    events.extend(SelinuxEvent::from_bytes(t, payload)?);
+                }
+            }
+            if !events.is_empty() {
+                return Ok(events);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_events() {
+        assert_eq!(SelinuxEvent::from_bytes(0x10, &1i32.to_ne_bytes()).unwrap(),
+                   Some(SelinuxEvent::SetEnforce(true)));
+        assert_eq!(SelinuxEvent::from_bytes(0x10, &0i32.to_ne_bytes()).unwrap(),
+                   Some(SelinuxEvent::SetEnforce(false)));
+        assert_eq!(SelinuxEvent::from_bytes(0x11, &7u32.to_ne_bytes()).unwrap(),
+                   Some(SelinuxEvent::PolicyLoad(7)));
+        assert_eq!(SelinuxEvent::from_bytes(0x12, &[]).unwrap(), None);
+        assert!(SelinuxEvent::from_bytes(0x11, &[1, 0]).is_err());
+    }
+
+    #[test]
+    fn test_monitor() {
+        match SelinuxMonitor::new() {
+            Ok(_) => {},
+            Err(ref e) if e.raw_os_error() == Some(::libc::EPROTONOSUPPORT) => {},
+            Err(e) => panic!("{}", e),
+        }
+    }
+}
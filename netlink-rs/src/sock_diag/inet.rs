@@ -0,0 +1,495 @@
+//! Internet sockets (inet_diag, `ss -ti`)
+//!
+//! TCP, UDP, raw and other IP sockets are dumped per family and protocol,
+//! filtered by a mask of TCP_* states. Extensions add the memory usage,
+//! `struct tcp_info` and the congestion control algorithm of each socket.
+
+use super::{dump, u32_at, u64_at, SockId, INET_DIAG_SOCKID_LEN};
+use socket::{NetlinkTransport, NlAttr};
+
+use std::io::{self, ErrorKind};
+
+const INET_DIAG_MEMINFO: u16 = 1;
+const INET_DIAG_INFO: u16 = 2;
+const INET_DIAG_CONG: u16 = 4;
+
+const INET_DIAG_MSG_LEN: usize = 4 + INET_DIAG_SOCKID_LEN + 20;
+/// Size of `struct tcp_info` as of Linux 5.4, up to `tcpi_snd_wnd`
+const TCP_INFO_LEN: usize = 232;
+
+/// Extensions reported with each socket
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum InetExt {
+    /// Queue memory (`struct inet_diag_meminfo`)
+    MemInfo,
+    /// `struct tcp_info` for TCP sockets
+    Info,
+    /// Name of the congestion control algorithm
+    Cong,
+}
+
+impl From<InetExt> for u16 {
+    fn from(e: InetExt) -> u16 {
+        match e {
+            InetExt::MemInfo => INET_DIAG_MEMINFO,
+            InetExt::Info => INET_DIAG_INFO,
+            InetExt::Cong => INET_DIAG_CONG,
+        }
+    }
+}
+
+/// Memory held by the queues of a socket, in bytes
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct MemInfo {
+    rmem: u32,
+    wmem: u32,
+    fmem: u32,
+    tmem: u32,
+}
+
+impl MemInfo {
+    fn from_bytes(bytes: &[u8]) -> io::Result<MemInfo> {
+        if bytes.len() < 16 {
+            return Err(io::Error::new(ErrorKind::InvalidData, "inet_diag_meminfo too short"));
+        }
+        Ok(MemInfo {
+            rmem: u32_at(bytes, 0),
+            wmem: u32_at(bytes, 4),
+            fmem: u32_at(bytes, 8),
+            tmem: u32_at(bytes, 12),
+        })
+    }
+
+    /// Receive queue
+    pub fn rmem(&self) -> u32 {
+        self.rmem
+    }
+
+    /// Data queued but not sent
+    pub fn wmem(&self) -> u32 {
+        self.wmem
+    }
+
+    /// Memory reserved ahead of use
+    pub fn fmem(&self) -> u32 {
+        self.fmem
+    }
+
+    /// Transmit queue
+    pub fn tmem(&self) -> u32 {
+        self.tmem
+    }
+}
+
+/// TCP connection metrics (`struct tcp_info`). Fields newer than the
+/// running kernel read as 0. Times are in microseconds unless noted.
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct TcpInfo {
+    bytes: Vec<u8>,
+}
+
+impl TcpInfo {
+    fn from_bytes(bytes: &[u8]) -> TcpInfo {
+        let mut bytes = bytes[..bytes.len().min(TCP_INFO_LEN)].to_vec();
+        bytes.resize(TCP_INFO_LEN, 0);
+        TcpInfo { bytes }
+    }
+
+    /// TCP_* state
+    pub fn state(&self) -> u8 {
+        self.bytes[0]
+    }
+
+    /// TCP_CA_* congestion avoidance state
+    pub fn ca_state(&self) -> u8 {
+        self.bytes[1]
+    }
+
+    /// Retransmissions of the oldest unacked segment
+    pub fn retransmits(&self) -> u8 {
+        self.bytes[2]
+    }
+
+    /// TCPI_OPT_* options negotiated
+    pub fn options(&self) -> u8 {
+        self.bytes[5]
+    }
+
+    pub fn snd_wscale(&self) -> u8 {
+        self.bytes[6] & 0xf
+    }
+
+    pub fn rcv_wscale(&self) -> u8 {
+        self.bytes[6] >> 4
+    }
+
+    /// Retransmission timeout
+    pub fn rto(&self) -> u32 {
+        u32_at(&self.bytes, 8)
+    }
+
+    pub fn snd_mss(&self) -> u32 {
+        u32_at(&self.bytes, 16)
+    }
+
+    pub fn rcv_mss(&self) -> u32 {
+        u32_at(&self.bytes, 20)
+    }
+
+    /// Segments in flight
+    pub fn unacked(&self) -> u32 {
+        u32_at(&self.bytes, 24)
+    }
+
+    pub fn sacked(&self) -> u32 {
+        u32_at(&self.bytes, 28)
+    }
+
+    pub fn lost(&self) -> u32 {
+        u32_at(&self.bytes, 32)
+    }
+
+    /// Segments being retransmitted
+    pub fn retrans(&self) -> u32 {
+        u32_at(&self.bytes, 36)
+    }
+
+    pub fn pmtu(&self) -> u32 {
+        u32_at(&self.bytes, 60)
+    }
+
+    /// Smoothed round trip time
+    pub fn rtt(&self) -> u32 {
+        u32_at(&self.bytes, 68)
+    }
+
+    pub fn rttvar(&self) -> u32 {
+        u32_at(&self.bytes, 72)
+    }
+
+    /// Slow start threshold, in segments
+    pub fn snd_ssthresh(&self) -> u32 {
+        u32_at(&self.bytes, 76)
+    }
+
+    /// Congestion window, in segments
+    pub fn snd_cwnd(&self) -> u32 {
+        u32_at(&self.bytes, 80)
+    }
+
+    pub fn advmss(&self) -> u32 {
+        u32_at(&self.bytes, 84)
+    }
+
+    pub fn reordering(&self) -> u32 {
+        u32_at(&self.bytes, 88)
+    }
+
+    /// Receiver side round trip time estimate
+    pub fn rcv_rtt(&self) -> u32 {
+        u32_at(&self.bytes, 92)
+    }
+
+    /// Segments retransmitted over the connection's lifetime
+    pub fn total_retrans(&self) -> u32 {
+        u32_at(&self.bytes, 100)
+    }
+
+    /// Pacing rate, in bytes per second
+    pub fn pacing_rate(&self) -> u64 {
+        u64_at(&self.bytes, 104)
+    }
+
+    pub fn bytes_acked(&self) -> u64 {
+        u64_at(&self.bytes, 120)
+    }
+
+    pub fn bytes_received(&self) -> u64 {
+        u64_at(&self.bytes, 128)
+    }
+
+    pub fn segs_out(&self) -> u32 {
+        u32_at(&self.bytes, 136)
+    }
+
+    pub fn segs_in(&self) -> u32 {
+        u32_at(&self.bytes, 140)
+    }
+
+    /// Bytes written by the application but not sent yet
+    pub fn notsent_bytes(&self) -> u32 {
+        u32_at(&self.bytes, 144)
+    }
+
+    /// Minimum round trip time seen
+    pub fn min_rtt(&self) -> u32 {
+        u32_at(&self.bytes, 148)
+    }
+
+    /// Delivery rate, in bytes per second
+    pub fn delivery_rate(&self) -> u64 {
+        u64_at(&self.bytes, 160)
+    }
+
+    pub fn bytes_sent(&self) -> u64 {
+        u64_at(&self.bytes, 200)
+    }
+
+    pub fn bytes_retrans(&self) -> u64 {
+        u64_at(&self.bytes, 208)
+    }
+
+    /// Send window advertised by the peer
+    pub fn snd_wnd(&self) -> u32 {
+        u32_at(&self.bytes, 228)
+    }
+}
+
+// HEADER FORMAT
+// __u8    idiag_family;
+// __u8    idiag_state;
+// __u8    idiag_timer;
+// __u8    idiag_retrans;
+// struct inet_diag_sockid id;
+// __u32   idiag_expires;
+// __u32   idiag_rqueue;
+// __u32   idiag_wqueue;
+// __u32   idiag_uid;
+// __u32   idiag_inode;
+/// An internet socket (`struct inet_diag_msg`) with its extensions
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct InetSocket {
+    family: u8,
+    state: u8,
+    timer: u8,
+    retrans: u8,
+    id: SockId,
+    expires: u32,
+    rqueue: u32,
+    wqueue: u32,
+    uid: u32,
+    inode: u32,
+    meminfo: Option<MemInfo>,
+    tcp_info: Option<TcpInfo>,
+    congestion: Option<String>,
+}
+
+impl InetSocket {
+    fn from_bytes(bytes: &[u8]) -> io::Result<InetSocket> {
+        if bytes.len() < INET_DIAG_MSG_LEN {
+            return Err(io::Error::new(ErrorKind::InvalidData, "inet_diag_msg too short"));
+        }
+        let at = 4 + INET_DIAG_SOCKID_LEN;
+        let mut sock = InetSocket {
+            family: bytes[0],
+            state: bytes[1],
+            timer: bytes[2],
+            retrans: bytes[3],
+            id: SockId::from_bytes(&bytes[4..], bytes[0] == ::libc::AF_INET6 as u8)?,
+            expires: u32_at(bytes, at),
+            rqueue: u32_at(bytes, at + 4),
+            wqueue: u32_at(bytes, at + 8),
+            uid: u32_at(bytes, at + 12),
+            inode: u32_at(bytes, at + 16),
+            meminfo: None,
+            tcp_info: None,
+            congestion: None,
+        };
+        for attr in NlAttr::parse(&bytes[INET_DIAG_MSG_LEN..])? {
+            let p = attr.payload();
+            match attr.attr_type() {
+                INET_DIAG_MEMINFO => sock.meminfo = Some(MemInfo::from_bytes(p)?),
+                INET_DIAG_INFO => sock.tcp_info = Some(TcpInfo::from_bytes(p)),
+                INET_DIAG_CONG => sock.congestion = Some(attr.get_str()?.to_string()),
+                _ => {},
+            }
+        }
+        Ok(sock)
+    }
+
+    pub fn family(&self) -> u8 {
+        self.family
+    }
+
+    /// TCP_* state, TCP_CLOSE or TCP_ESTABLISHED for datagram sockets
+    pub fn state(&self) -> u8 {
+        self.state
+    }
+
+    /// Pending timer: 1 retransmit, 2 keepalive, 3 TIME_WAIT, 4 zero window
+    /// probe
+    pub fn timer(&self) -> u8 {
+        self.timer
+    }
+
+    pub fn retrans(&self) -> u8 {
+        self.retrans
+    }
+
+    pub fn id(&self) -> &SockId {
+        &self.id
+    }
+
+    /// Time until the timer fires, in milliseconds
+    pub fn expires(&self) -> u32 {
+        self.expires
+    }
+
+    /// Bytes in the receive queue, or pending connections of a listener
+    pub fn rqueue(&self) -> u32 {
+        self.rqueue
+    }
+
+    /// Bytes in the send queue, or the backlog of a listener
+    pub fn wqueue(&self) -> u32 {
+        self.wqueue
+    }
+
+    pub fn uid(&self) -> u32 {
+        self.uid
+    }
+
+    pub fn inode(&self) -> u32 {
+        self.inode
+    }
+
+    pub fn meminfo(&self) -> Option<&MemInfo> {
+        self.meminfo.as_ref()
+    }
+
+    pub fn tcp_info(&self) -> Option<&TcpInfo> {
+        self.tcp_info.as_ref()
+    }
+
+    /// Congestion control algorithm, e.g. "cubic"
+    pub fn congestion(&self) -> Option<&str> {
+        self.congestion.as_deref()
+    }
+}
+
+// HEADER FORMAT
+// __u8    sdiag_family;
+// __u8    sdiag_protocol;
+// __u8    idiag_ext;
+// __u8    pad;
+// __u32   idiag_states;       /* States to dump */
+// struct inet_diag_sockid id;
+/// A dump request (`struct inet_diag_req_v2`)
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct InetDiagRequest {
+    family: u8,
+    protocol: u8,
+    ext: u8,
+    states: u32,
+}
+
+impl InetDiagRequest {
+    /// Requests the sockets of `protocol` (IPPROTO_*) in `family`, AF_INET
+    /// or AF_INET6, in any state.
+    pub fn new(family: i32, protocol: i32) -> InetDiagRequest {
+        InetDiagRequest {
+            family: family as u8,
+            protocol: protocol as u8,
+            ext: 0,
+            states: !0,
+        }
+    }
+
+    /// Mask of the TCP_* states to dump, bit `1 << state`
+    pub fn set_states(&mut self, states: u32) -> &mut InetDiagRequest {
+        self.states = states;
+        self
+    }
+
+    /// Reports `ext` with each socket
+    pub fn ext(&mut self, ext: InetExt) -> &mut InetDiagRequest {
+        self.ext |= 1 << (u16::from(ext) - 1);
+        self
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![self.family, self.protocol, self.ext, 0];
+        bytes.extend_from_slice(&self.states.to_ne_bytes());
+        bytes.extend(SockId::any());
+        bytes
+    }
+}
+
+/// Dumps the sockets selected by `req`.
+pub fn inet_sockets(socket: &mut impl NetlinkTransport, req: &InetDiagRequest) -> io::Result<Vec<InetSocket>> {
+    let replies = dump(socket, &req.bytes())?;
+    replies.iter().map(|r| InetSocket::from_bytes(r)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket::Socket;
+    use Protocol;
+
+    use std::net::{TcpListener, TcpStream};
+
+    use libc::{AF_INET, IPPROTO_TCP};
+
+    const TCP_ESTABLISHED: u8 = 1;
+
+    #[test]
+    fn test_decode() {
+        let mut msg = vec![AF_INET as u8, TCP_ESTABLISHED, 0, 0];
+        msg.extend(SockId::any());
+        for v in &[0u32, 10, 20, 1000, 99] {
+            msg.extend_from_slice(&v.to_ne_bytes());
+        }
+        let mut info = vec![0u8; 104];
+        info[0] = TCP_ESTABLISHED;
+        info[6] = 7 << 4 | 9;
+        info[68..72].copy_from_slice(&250u32.to_ne_bytes());
+        info[80..84].copy_from_slice(&10u32.to_ne_bytes());
+        msg.extend(NlAttr::new(INET_DIAG_INFO, &info).bytes());
+        msg.extend(NlAttr::new(INET_DIAG_CONG, b"cubic\0").bytes());
+
+        let sock = InetSocket::from_bytes(&msg).unwrap();
+        assert_eq!((sock.rqueue(), sock.wqueue(), sock.uid(), sock.inode()), (10, 20, 1000, 99));
+        assert_eq!(sock.congestion(), Some("cubic"));
+        assert_eq!(sock.meminfo(), None);
+        let info = sock.tcp_info().unwrap();
+        assert_eq!(info.state(), TCP_ESTABLISHED);
+        assert_eq!((info.snd_wscale(), info.rcv_wscale()), (9, 7));
+        assert_eq!(info.rtt(), 250);
+        assert_eq!(info.snd_cwnd(), 10);
+        // Missing from the older layout
+        assert_eq!(info.bytes_sent(), 0);
+
+        let mut req = InetDiagRequest::new(AF_INET, IPPROTO_TCP);
+        req.ext(InetExt::Info).ext(InetExt::Cong).set_states(1 << TCP_ESTABLISHED);
+        let bytes = req.bytes();
+        assert_eq!(bytes.len(), 56);
+        assert_eq!(&bytes[..4], &[AF_INET as u8, IPPROTO_TCP as u8, 0b1010, 0]);
+    }
+
+    #[test]
+    fn test_inet_sockets() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let _client = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        let _server = listener.accept().unwrap();
+
+        let mut socket = Socket::new(Protocol::INETDiag).unwrap();
+        let mut req = InetDiagRequest::new(AF_INET, IPPROTO_TCP);
+        req.ext(InetExt::MemInfo).ext(InetExt::Info).ext(InetExt::Cong)
+            .set_states(1 << TCP_ESTABLISHED);
+        let socks = inet_sockets(&mut socket, &req).unwrap();
+        let server = socks.iter().find(|s| s.id().src().port() == port).unwrap();
+        assert_eq!(server.state(), TCP_ESTABLISHED);
+        assert!(server.meminfo().is_some());
+        assert!(!server.congestion().unwrap().is_empty());
+        let info = server.tcp_info().unwrap();
+        assert_eq!(info.state(), TCP_ESTABLISHED);
+        assert!(info.snd_mss() > 0 && info.snd_cwnd() > 0);
+    }
+}
@@ -0,0 +1,228 @@
+//! Socket diagnostics (sock_diag)
+//!
+//! `Protocol::INETDiag` is the sock_diag protocol: a SOCK_DIAG_BY_FAMILY dump
+//! carries a request specific to the address family, starting with the
+//! family, and each socket is reported in a family specific message followed
+//! by attributes for the extensions asked for. The handler of a family is
+//! often a module of its own, loaded on the first request.
+
+pub mod inet;
+pub mod netlink;
+pub mod packet;
+pub mod smc;
+pub mod vsock;
+
+use socket::{NetlinkTransport, Msg, NlMsgHeader, Payload};
+
+use std::borrow::Cow;
+use std::io::{self, ErrorKind};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+const SOCK_DIAG_BY_FAMILY: u16 = 20;
+
+const INET_DIAG_SOCKID_LEN: usize = 48;
+const INET_DIAG_NOCOOKIE: u32 = !0;
+
+/// Sends a dump request of a family and returns the reports.
+fn dump(socket: &mut impl NetlinkTransport, req: &[u8]) -> io::Result<Vec<Vec<u8>>> {
+    let mut hdr = NlMsgHeader::user_defined(SOCK_DIAG_BY_FAMILY);
+    hdr.dump().data_length(req.len() as u32);
+    socket.talk(Msg::new(hdr, Payload::Data(Cow::Borrowed(req))))
+}
+
+/// Reads the native endian word at `i` of a report whose length was checked.
+fn u32_at(bytes: &[u8], i: usize) -> u32 {
+    u32::from_ne_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]])
+}
+
+fn u64_at(bytes: &[u8], i: usize) -> u64 {
+    let mut b = [0u8; 8];
+    b.copy_from_slice(&bytes[i..i + 8]);
+    u64::from_ne_bytes(b)
+}
+
+// HEADER FORMAT
+// __be16  idiag_sport;
+// __be16  idiag_dport;
+// __be32  idiag_src[4];
+// __be32  idiag_dst[4];
+// __u32   idiag_if;
+// __u32   idiag_cookie[2];
+/// Identity of an internet socket (`struct inet_diag_sockid`)
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct SockId {
+    src: SocketAddr,
+    dst: SocketAddr,
+    ifindex: u32,
+    cookie: Option<u64>,
+}
+
+impl SockId {
+    /// Decodes the identity, with IPv6 addresses if `v6`.
+    fn from_bytes(bytes: &[u8], v6: bool) -> io::Result<SockId> {
+        if bytes.len() < INET_DIAG_SOCKID_LEN {
+            return Err(io::Error::new(ErrorKind::InvalidData, "inet_diag_sockid too short"));
+        }
+        let addr = |at: usize| -> IpAddr {
+            if v6 {
+                let mut octets = [0u8; 16];
+                octets.copy_from_slice(&bytes[at..at + 16]);
+                IpAddr::V6(Ipv6Addr::from(octets))
+            } else {
+                IpAddr::V4(Ipv4Addr::new(bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]))
+            }
+        };
+        let cookie = [u32_at(bytes, 40), u32_at(bytes, 44)];
+        Ok(SockId {
+            src: SocketAddr::new(addr(4), u16::from_be_bytes([bytes[0], bytes[1]])),
+            dst: SocketAddr::new(addr(20), u16::from_be_bytes([bytes[2], bytes[3]])),
+            ifindex: u32_at(bytes, 36),
+            cookie: if cookie == [INET_DIAG_NOCOOKIE; 2] {
+                None
+            } else {
+                Some((cookie[1] as u64) << 32 | cookie[0] as u64)
+            },
+        })
+    }
+
+    /// Decodes an identity whose family is not reported, taking addresses
+    /// with only their first word set for IPv4.
+    fn from_bytes_guess(bytes: &[u8]) -> io::Result<SockId> {
+        let v6 = bytes.len() >= INET_DIAG_SOCKID_LEN
+            && (bytes[8..20].iter().any(|&b| b != 0) || bytes[24..36].iter().any(|&b| b != 0));
+        SockId::from_bytes(bytes, v6)
+    }
+
+    /// An identity matching any socket, as used in dump requests.
+    fn any() -> Vec<u8> {
+        let mut bytes = vec![0; INET_DIAG_SOCKID_LEN];
+        bytes[40..].copy_from_slice(&[0xff; 8]);
+        bytes
+    }
+
+    /// Local address
+    pub fn src(&self) -> SocketAddr {
+        self.src
+    }
+
+    /// Remote address, unspecified for listening sockets
+    pub fn dst(&self) -> SocketAddr {
+        self.dst
+    }
+
+    /// Index of the link the socket is bound to, 0 for none
+    pub fn ifindex(&self) -> u32 {
+        self.ifindex
+    }
+
+    /// Kernel identifier of the socket
+    pub fn cookie(&self) -> Option<u64> {
+        self.cookie
+    }
+}
+
+/// Memory accounting of a socket (SK_MEMINFO_* array), in bytes
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct SkMemInfo {
+    rmem_alloc: u32,
+    rcvbuf: u32,
+    wmem_alloc: u32,
+    sndbuf: u32,
+    fwd_alloc: u32,
+    wmem_queued: u32,
+    optmem: u32,
+    backlog: Option<u32>,
+    drops: Option<u32>,
+}
+
+impl SkMemInfo {
+    fn from_bytes(bytes: &[u8]) -> io::Result<SkMemInfo> {
+        if bytes.len() < 28 {
+            return Err(io::Error::new(ErrorKind::InvalidData, "sk_meminfo too short"));
+        }
+        let opt = |i: usize| if bytes.len() >= i + 4 { Some(u32_at(bytes, i)) } else { None };
+        Ok(SkMemInfo {
+            rmem_alloc: u32_at(bytes, 0),
+            rcvbuf: u32_at(bytes, 4),
+            wmem_alloc: u32_at(bytes, 8),
+            sndbuf: u32_at(bytes, 12),
+            fwd_alloc: u32_at(bytes, 16),
+            wmem_queued: u32_at(bytes, 20),
+            optmem: u32_at(bytes, 24),
+            backlog: opt(28),
+            drops: opt(32),
+        })
+    }
+
+    /// Memory of the received data queued
+    pub fn rmem_alloc(&self) -> u32 {
+        self.rmem_alloc
+    }
+
+    /// Receive buffer limit (SO_RCVBUF)
+    pub fn rcvbuf(&self) -> u32 {
+        self.rcvbuf
+    }
+
+    /// Memory of the data being sent
+    pub fn wmem_alloc(&self) -> u32 {
+        self.wmem_alloc
+    }
+
+    /// Send buffer limit (SO_SNDBUF)
+    pub fn sndbuf(&self) -> u32 {
+        self.sndbuf
+    }
+
+    /// Memory reserved ahead of use
+    pub fn fwd_alloc(&self) -> u32 {
+        self.fwd_alloc
+    }
+
+    pub fn wmem_queued(&self) -> u32 {
+        self.wmem_queued
+    }
+
+    /// Memory of socket options and filters
+    pub fn optmem(&self) -> u32 {
+        self.optmem
+    }
+
+    pub fn backlog(&self) -> Option<u32> {
+        self.backlog
+    }
+
+    /// Packets dropped on receive
+    pub fn drops(&self) -> Option<u32> {
+        self.drops
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sock_id() {
+        let mut bytes = vec![0x1f, 0x90, 0, 80, 127, 0, 0, 1];
+        bytes.resize(20, 0);
+        bytes.extend_from_slice(&[10, 0, 0, 2]);
+        bytes.resize(36, 0);
+        bytes.extend_from_slice(&3u32.to_ne_bytes());
+        bytes.extend_from_slice(&7u32.to_ne_bytes());
+        bytes.extend_from_slice(&1u32.to_ne_bytes());
+
+        let id = SockId::from_bytes_guess(&bytes).unwrap();
+        assert_eq!(id.src(), "127.0.0.1:8080".parse().unwrap());
+        assert_eq!(id.dst(), "10.0.0.2:80".parse().unwrap());
+        assert_eq!(id.ifindex(), 3);
+        assert_eq!(id.cookie(), Some(1 << 32 | 7));
+        assert_eq!(SockId::from_bytes(&bytes, true).unwrap().src().port(), 8080);
+
+        let any = SockId::any();
+        assert_eq!(SockId::from_bytes(&any, false).unwrap().cookie(), None);
+        assert!(SockId::from_bytes(&any[..40], false).is_err());
+    }
+}
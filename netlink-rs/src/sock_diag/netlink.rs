@@ -0,0 +1,247 @@
+//! Netlink sockets (netlink_diag, `ss -A netlink`)
+//!
+//! Lists the netlink sockets of the namespace with their port ids and
+//! multicast groups, e.g. to find out which process listens to a group.
+//! The inode maps a socket to its owner through /proc/*/fd.
+
+use super::{dump, u32_at, SkMemInfo};
+use socket::{NetlinkTransport, NlAttr};
+
+use std::io::{self, ErrorKind};
+use std::mem;
+
+const AF_NETLINK: u8 = 16;
+const NDIAG_PROTO_ALL: u8 = 255;
+
+const NDIAG_SHOW_MEMINFO: u32 = 0x1;
+const NDIAG_SHOW_GROUPS: u32 = 0x2;
+const NDIAG_SHOW_FLAGS: u32 = 0x8;
+
+const NETLINK_DIAG_MEMINFO: u16 = 0;
+const NETLINK_DIAG_GROUPS: u16 = 1;
+const NETLINK_DIAG_FLAGS: u16 = 4;
+
+const NETLINK_DIAG_MSG_LEN: usize = 28;
+
+/// A dump is in progress on the socket (NDIAG_FLAG_CB_RUNNING)
+pub const NDIAG_FLAG_CB_RUNNING: u32 = 0x1;
+/// NETLINK_PKTINFO is set (NDIAG_FLAG_PKTINFO)
+pub const NDIAG_FLAG_PKTINFO: u32 = 0x2;
+/// NETLINK_BROADCAST_ERROR is set (NDIAG_FLAG_BROADCAST_ERROR)
+pub const NDIAG_FLAG_BROADCAST_ERROR: u32 = 0x4;
+/// NETLINK_NO_ENOBUFS is set (NDIAG_FLAG_NO_ENOBUFS)
+pub const NDIAG_FLAG_NO_ENOBUFS: u32 = 0x8;
+/// NETLINK_LISTEN_ALL_NSID is set (NDIAG_FLAG_LISTEN_ALL_NSID)
+pub const NDIAG_FLAG_LISTEN_ALL_NSID: u32 = 0x10;
+/// NETLINK_CAP_ACK is set (NDIAG_FLAG_CAP_ACK)
+pub const NDIAG_FLAG_CAP_ACK: u32 = 0x20;
+
+/// Information reported with each socket
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum NetlinkShow {
+    MemInfo,
+    /// Multicast group memberships
+    Groups,
+    /// NDIAG_FLAG_* socket options
+    Flags,
+}
+
+impl From<NetlinkShow> for u32 {
+    fn from(s: NetlinkShow) -> u32 {
+        match s {
+            NetlinkShow::MemInfo => NDIAG_SHOW_MEMINFO,
+            NetlinkShow::Groups => NDIAG_SHOW_GROUPS,
+            NetlinkShow::Flags => NDIAG_SHOW_FLAGS,
+        }
+    }
+}
+
+/// Decodes a bitmap of `unsigned long` words into the set bit numbers, plus
+/// one as groups are numbered from 1.
+fn groups(bytes: &[u8]) -> Vec<u32> {
+    let word = mem::size_of::<usize>();
+    let mut groups = vec![];
+    for (i, chunk) in bytes.chunks(word).filter(|c| c.len() == word).enumerate() {
+        let mut b = [0u8; 8];
+        b[..word].copy_from_slice(chunk);
+        let bits = if word == 8 { u64::from_ne_bytes(b) } else { u32_at(&b, 0) as u64 };
+        for bit in 0..(8 * word) as u32 {
+            if bits & 1 << bit != 0 {
+                groups.push(i as u32 * 8 * word as u32 + bit + 1);
+            }
+        }
+    }
+    groups
+}
+
+// HEADER FORMAT
+// __u8    ndiag_family;
+// __u8    ndiag_type;
+// __u8    ndiag_protocol;
+// __u8    ndiag_state;
+// __u32   ndiag_portid;
+// __u32   ndiag_dst_portid;
+// __u32   ndiag_dst_group;
+// __u32   ndiag_ino;
+// __u32   ndiag_cookie[2];
+/// A netlink socket (`struct netlink_diag_msg`) with the information asked
+/// for
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct NetlinkSocket {
+    sock_type: u8,
+    protocol: u8,
+    state: u8,
+    portid: u32,
+    dst_portid: u32,
+    dst_group: u32,
+    inode: u32,
+    cookie: u64,
+    meminfo: Option<SkMemInfo>,
+    groups: Option<Vec<u32>>,
+    flags: Option<u32>,
+}
+
+impl NetlinkSocket {
+    fn from_bytes(bytes: &[u8]) -> io::Result<NetlinkSocket> {
+        if bytes.len() < NETLINK_DIAG_MSG_LEN {
+            return Err(io::Error::new(ErrorKind::InvalidData, "netlink_diag_msg too short"));
+        }
+        let mut sock = NetlinkSocket {
+            sock_type: bytes[1],
+            protocol: bytes[2],
+            state: bytes[3],
+            portid: u32_at(bytes, 4),
+            dst_portid: u32_at(bytes, 8),
+            dst_group: u32_at(bytes, 12),
+            inode: u32_at(bytes, 16),
+            cookie: (u32_at(bytes, 24) as u64) << 32 | u32_at(bytes, 20) as u64,
+            meminfo: None,
+            groups: None,
+            flags: None,
+        };
+        for attr in NlAttr::parse(&bytes[NETLINK_DIAG_MSG_LEN..])? {
+            let p = attr.payload();
+            match attr.attr_type() {
+                NETLINK_DIAG_MEMINFO => sock.meminfo = Some(SkMemInfo::from_bytes(p)?),
+                NETLINK_DIAG_GROUPS => sock.groups = Some(groups(p)),
+                NETLINK_DIAG_FLAGS => sock.flags = Some(attr.get_u32()?),
+                _ => {},
+            }
+        }
+        Ok(sock)
+    }
+
+    /// SOCK_RAW or SOCK_DGRAM
+    pub fn sock_type(&self) -> u8 {
+        self.sock_type
+    }
+
+    /// NETLINK_* protocol, as in `Protocol`
+    pub fn protocol(&self) -> u8 {
+        self.protocol
+    }
+
+    /// 0 when unconnected, 1 (NETLINK_CONNECTED) after connect()
+    pub fn state(&self) -> u8 {
+        self.state
+    }
+
+    /// Port id the socket is bound to, the pid of the socket's owner by
+    /// default; 0 for the kernel's sockets
+    pub fn portid(&self) -> u32 {
+        self.portid
+    }
+
+    /// Peer of a connected socket
+    pub fn dst_portid(&self) -> u32 {
+        self.dst_portid
+    }
+
+    pub fn dst_group(&self) -> u32 {
+        self.dst_group
+    }
+
+    pub fn inode(&self) -> u32 {
+        self.inode
+    }
+
+    pub fn cookie(&self) -> u64 {
+        self.cookie
+    }
+
+    pub fn meminfo(&self) -> Option<&SkMemInfo> {
+        self.meminfo.as_ref()
+    }
+
+    /// Multicast groups joined, numbered from 1, if shown; the kernel's
+    /// sockets report the groups of the protocol instead
+    pub fn groups(&self) -> Option<&[u32]> {
+        self.groups.as_deref()
+    }
+
+    /// NDIAG_FLAG_* options, if shown
+    pub fn flags(&self) -> Option<u32> {
+        self.flags
+    }
+}
+
+// HEADER FORMAT
+// __u8    sdiag_family;
+// __u8    sdiag_protocol;
+// __u16   pad;
+// __u32   ndiag_ino;
+// __u32   ndiag_show;
+// __u32   ndiag_cookie[2];
+/// Dumps the netlink sockets of `protocol`, or of all protocols for
+/// `None`, with `show`.
+pub fn netlink_sockets<P: Into<i32>>(socket: &mut impl NetlinkTransport, protocol: Option<P>, show: &[NetlinkShow])
+                                     -> io::Result<Vec<NetlinkSocket>> {
+    let protocol = protocol.map(|p| p.into() as u8).unwrap_or(NDIAG_PROTO_ALL);
+    let show = show.iter().fold(0, |mask, &s| mask | u32::from(s));
+    let mut req = vec![AF_NETLINK, protocol, 0, 0];
+    req.extend_from_slice(&0u32.to_ne_bytes());
+    req.extend_from_slice(&show.to_ne_bytes());
+    req.extend_from_slice(&[0xff; 8]);
+    let replies = dump(socket, &req)?;
+    replies.iter().map(|r| NetlinkSocket::from_bytes(r)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket::{Socket, NetlinkAddr};
+    use Protocol;
+
+    #[test]
+    fn test_groups() {
+        let mut bitmap = vec![0u8; 2 * mem::size_of::<usize>()];
+        bitmap[0] = 0b101;
+        bitmap[mem::size_of::<usize>()] = 1;
+        assert_eq!(groups(&bitmap), vec![1, 3, 8 * mem::size_of::<usize>() as u32 + 1]);
+        assert!(groups(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_netlink_sockets() {
+        let listener = Socket::new(Protocol::Route).unwrap();
+        // RTNLGRP_LINK and RTNLGRP_IPV4_IFADDR
+        listener.bind(NetlinkAddr::new(115, 1 | 1 << 4)).unwrap();
+
+        let mut socket = Socket::new(Protocol::INETDiag).unwrap();
+        let socks = match netlink_sockets(&mut socket, Some(Protocol::Route), &[NetlinkShow::Groups, NetlinkShow::Flags]) {
+            Err(ref e) if e.raw_os_error() == Some(::libc::ENOENT) => return,
+            r => r.unwrap(),
+        };
+        let sock = socks.iter().find(|s| s.portid() == 115).unwrap();
+        assert_eq!(sock.protocol(), 0);
+        assert_eq!(sock.groups(), Some(&[1, 5][..]));
+        assert_eq!(sock.flags(), Some(0));
+        assert!(socks.iter().all(|s| s.protocol() == 0));
+
+        let all = netlink_sockets::<Protocol>(&mut socket, None, &[NetlinkShow::MemInfo]).unwrap();
+        assert!(all.iter().any(|s| s.protocol() == 4));
+        assert!(all.iter().find(|s| s.portid() == 115).unwrap().meminfo().unwrap().rcvbuf() > 0);
+    }
+}
@@ -0,0 +1,436 @@
+//! Packet sockets (AF_PACKET, packet_diag, `ss -0`)
+//!
+//! Reports the sockets of capture tools and raw L2 senders: the link they
+//! are bound to, their mmap rings, fanout group and attached BPF filter.
+
+use super::{dump, u32_at};
+use socket::{NetlinkTransport, NlAttr};
+
+use std::io::{self, ErrorKind};
+
+const AF_PACKET: u8 = 17;
+
+const PACKET_SHOW_INFO: u32 = 0x1;
+const PACKET_SHOW_RING_CFG: u32 = 0x4;
+const PACKET_SHOW_FANOUT: u32 = 0x8;
+const PACKET_SHOW_FILTER: u32 = 0x20;
+
+const PACKET_DIAG_INFO: u16 = 0;
+const PACKET_DIAG_RX_RING: u16 = 2;
+const PACKET_DIAG_TX_RING: u16 = 3;
+const PACKET_DIAG_FANOUT: u16 = 4;
+const PACKET_DIAG_UID: u16 = 5;
+const PACKET_DIAG_FILTER: u16 = 7;
+
+const PACKET_DIAG_MSG_LEN: usize = 16;
+
+/// The socket is bound and receiving (PDI_RUNNING)
+pub const PDI_RUNNING: u32 = 0x1;
+/// PACKET_AUXDATA is set (PDI_AUXDATA)
+pub const PDI_AUXDATA: u32 = 0x2;
+/// PACKET_ORIGDEV is set (PDI_ORIGDEV)
+pub const PDI_ORIGDEV: u32 = 0x4;
+/// PACKET_VNET_HDR is set (PDI_VNETHDR)
+pub const PDI_VNETHDR: u32 = 0x8;
+/// PACKET_LOSS is set (PDI_LOSS)
+pub const PDI_LOSS: u32 = 0x10;
+
+/// Information reported with each socket
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum PacketShow {
+    /// Bound link, TPACKET version and PDI_* flags
+    Info,
+    /// Receive and transmit rings
+    RingCfg,
+    /// Fanout group
+    Fanout,
+    /// Attached classic BPF filter; needs CAP_NET_ADMIN
+    Filter,
+}
+
+impl From<PacketShow> for u32 {
+    fn from(s: PacketShow) -> u32 {
+        match s {
+            PacketShow::Info => PACKET_SHOW_INFO,
+            PacketShow::RingCfg => PACKET_SHOW_RING_CFG,
+            PacketShow::Fanout => PACKET_SHOW_FANOUT,
+            PacketShow::Filter => PACKET_SHOW_FILTER,
+        }
+    }
+}
+
+/// Link and options of a socket (`struct packet_diag_info`)
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct PacketInfo {
+    ifindex: u32,
+    version: u32,
+    reserve: u32,
+    copy_thresh: u32,
+    tstamp: u32,
+    flags: u32,
+}
+
+impl PacketInfo {
+    fn from_bytes(bytes: &[u8]) -> io::Result<PacketInfo> {
+        if bytes.len() < 24 {
+            return Err(io::Error::new(ErrorKind::InvalidData, "packet_diag_info too short"));
+        }
+        Ok(PacketInfo {
+            ifindex: u32_at(bytes, 0),
+            version: u32_at(bytes, 4),
+            reserve: u32_at(bytes, 8),
+            copy_thresh: u32_at(bytes, 12),
+            tstamp: u32_at(bytes, 16),
+            flags: u32_at(bytes, 20),
+        })
+    }
+
+    /// Link the socket is bound to, 0 for all
+    pub fn ifindex(&self) -> u32 {
+        self.ifindex
+    }
+
+    /// TPACKET_V* version of the rings
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Headroom reserved in ring frames (PACKET_RESERVE)
+    pub fn reserve(&self) -> u32 {
+        self.reserve
+    }
+
+    pub fn copy_thresh(&self) -> u32 {
+        self.copy_thresh
+    }
+
+    /// SOF_TIMESTAMPING_* flags of the ring (PACKET_TIMESTAMP)
+    pub fn tstamp(&self) -> u32 {
+        self.tstamp
+    }
+
+    /// PDI_* flags
+    pub fn flags(&self) -> u32 {
+        self.flags
+    }
+}
+
+/// An mmap ring (`struct packet_diag_ring`)
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct PacketRing {
+    block_size: u32,
+    block_nr: u32,
+    frame_size: u32,
+    frame_nr: u32,
+    retire_tmo: u32,
+    sizeof_priv: u32,
+    features: u32,
+}
+
+impl PacketRing {
+    fn from_bytes(bytes: &[u8]) -> io::Result<PacketRing> {
+        if bytes.len() < 28 {
+            return Err(io::Error::new(ErrorKind::InvalidData, "packet_diag_ring too short"));
+        }
+        Ok(PacketRing {
+            block_size: u32_at(bytes, 0),
+            block_nr: u32_at(bytes, 4),
+            frame_size: u32_at(bytes, 8),
+            frame_nr: u32_at(bytes, 12),
+            retire_tmo: u32_at(bytes, 16),
+            sizeof_priv: u32_at(bytes, 20),
+            features: u32_at(bytes, 24),
+        })
+    }
+
+    pub fn block_size(&self) -> u32 {
+        self.block_size
+    }
+
+    pub fn block_nr(&self) -> u32 {
+        self.block_nr
+    }
+
+    pub fn frame_size(&self) -> u32 {
+        self.frame_size
+    }
+
+    pub fn frame_nr(&self) -> u32 {
+        self.frame_nr
+    }
+
+    /// Block retire timeout of TPACKET_V3 rings, in milliseconds
+    pub fn retire_tmo(&self) -> u32 {
+        self.retire_tmo
+    }
+
+    /// Private area of TPACKET_V3 blocks
+    pub fn sizeof_priv(&self) -> u32 {
+        self.sizeof_priv
+    }
+
+    /// TP_FT_* features of TPACKET_V3 rings
+    pub fn features(&self) -> u32 {
+        self.features
+    }
+}
+
+/// A fanout group membership
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct PacketFanout {
+    id: u16,
+    fanout_type: u8,
+    flags: u8,
+}
+
+impl PacketFanout {
+    // id | type << 16 | flags << 24
+    fn from_u32(v: u32) -> PacketFanout {
+        PacketFanout {
+            id: v as u16,
+            fanout_type: (v >> 16) as u8,
+            flags: (v >> 24) as u8,
+        }
+    }
+
+    pub fn id(&self) -> u16 {
+        self.id
+    }
+
+    /// PACKET_FANOUT_* mode, e.g. 0 for hash
+    pub fn fanout_type(&self) -> u8 {
+        self.fanout_type
+    }
+
+    /// PACKET_FANOUT_FLAG_* flags, shifted right by 8
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+}
+
+/// An instruction of a classic BPF filter (`struct sock_filter`)
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct SockFilter {
+    code: u16,
+    jt: u8,
+    jf: u8,
+    k: u32,
+}
+
+impl SockFilter {
+    pub fn code(&self) -> u16 {
+        self.code
+    }
+
+    pub fn jt(&self) -> u8 {
+        self.jt
+    }
+
+    pub fn jf(&self) -> u8 {
+        self.jf
+    }
+
+    pub fn k(&self) -> u32 {
+        self.k
+    }
+}
+
+// HEADER FORMAT
+// __u8    pdiag_family;
+// __u8    pdiag_type;
+// __u16   pdiag_num;
+// __u32   pdiag_ino;
+// __u32   pdiag_cookie[2];
+/// A packet socket (`struct packet_diag_msg`) with the information asked
+/// for
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct PacketSocket {
+    sock_type: u8,
+    protocol: u16,
+    inode: u32,
+    cookie: u64,
+    uid: Option<u32>,
+    info: Option<PacketInfo>,
+    rx_ring: Option<PacketRing>,
+    tx_ring: Option<PacketRing>,
+    fanout: Option<PacketFanout>,
+    filter: Option<Vec<SockFilter>>,
+}
+
+impl PacketSocket {
+    fn from_bytes(bytes: &[u8]) -> io::Result<PacketSocket> {
+        if bytes.len() < PACKET_DIAG_MSG_LEN {
+            return Err(io::Error::new(ErrorKind::InvalidData, "packet_diag_msg too short"));
+        }
+        let mut sock = PacketSocket {
+            sock_type: bytes[1],
+            protocol: u16::from_ne_bytes([bytes[2], bytes[3]]),
+            inode: u32_at(bytes, 4),
+            cookie: (u32_at(bytes, 12) as u64) << 32 | u32_at(bytes, 8) as u64,
+            uid: None,
+            info: None,
+            rx_ring: None,
+            tx_ring: None,
+            fanout: None,
+            filter: None,
+        };
+        for attr in NlAttr::parse(&bytes[PACKET_DIAG_MSG_LEN..])? {
+            let p = attr.payload();
+            match attr.attr_type() {
+                PACKET_DIAG_INFO => sock.info = Some(PacketInfo::from_bytes(p)?),
+                PACKET_DIAG_RX_RING => sock.rx_ring = Some(PacketRing::from_bytes(p)?),
+                PACKET_DIAG_TX_RING => sock.tx_ring = Some(PacketRing::from_bytes(p)?),
+                PACKET_DIAG_FANOUT => sock.fanout = Some(PacketFanout::from_u32(attr.get_u32()?)),
+                PACKET_DIAG_UID => sock.uid = Some(attr.get_u32()?),
+                PACKET_DIAG_FILTER => {
+                    sock.filter = Some(p.chunks(8).filter(|c| c.len() == 8).map(|c| SockFilter {
+                        code: u16::from_ne_bytes([c[0], c[1]]),
+                        jt: c[2],
+                        jf: c[3],
+                        k: u32_at(c, 4),
+                    }).collect());
+                },
+                _ => {},
+            }
+        }
+        Ok(sock)
+    }
+
+    /// SOCK_RAW or SOCK_DGRAM
+    pub fn sock_type(&self) -> u8 {
+        self.sock_type
+    }
+
+    /// ETH_P_* protocol received, in host byte order
+    pub fn protocol(&self) -> u16 {
+        self.protocol
+    }
+
+    pub fn inode(&self) -> u32 {
+        self.inode
+    }
+
+    pub fn cookie(&self) -> u64 {
+        self.cookie
+    }
+
+    pub fn uid(&self) -> Option<u32> {
+        self.uid
+    }
+
+    pub fn info(&self) -> Option<&PacketInfo> {
+        self.info.as_ref()
+    }
+
+    pub fn rx_ring(&self) -> Option<&PacketRing> {
+        self.rx_ring.as_ref()
+    }
+
+    pub fn tx_ring(&self) -> Option<&PacketRing> {
+        self.tx_ring.as_ref()
+    }
+
+    pub fn fanout(&self) -> Option<&PacketFanout> {
+        self.fanout.as_ref()
+    }
+
+    /// Program of the attached filter, if shown and one is attached
+    pub fn filter(&self) -> Option<&[SockFilter]> {
+        self.filter.as_deref()
+    }
+}
+
+// HEADER FORMAT
+// __u8    sdiag_family;
+// __u8    sdiag_protocol;
+// __u16   pad;
+// __u32   pdiag_ino;
+// __u32   pdiag_show;
+// __u32   pdiag_cookie[2];
+/// Dumps the packet sockets with `show`.
+pub fn packet_sockets(socket: &mut impl NetlinkTransport, show: &[PacketShow]) -> io::Result<Vec<PacketSocket>> {
+    let show = show.iter().fold(0, |mask, &s| mask | u32::from(s));
+    let mut req = vec![AF_PACKET, 0, 0, 0];
+    req.extend_from_slice(&0u32.to_ne_bytes());
+    req.extend_from_slice(&show.to_ne_bytes());
+    req.extend_from_slice(&[0xff; 8]);
+    let replies = dump(socket, &req)?;
+    replies.iter().map(|r| PacketSocket::from_bytes(r)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket::Socket;
+    use Protocol;
+
+    use std::mem;
+
+    #[test]
+    fn test_decode() {
+        let mut msg = vec![AF_PACKET, 3];
+        msg.extend_from_slice(&0x0003u16.to_ne_bytes());
+        msg.extend_from_slice(&42u32.to_ne_bytes());
+        msg.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0]);
+        let mut info = vec![];
+        for v in &[2u32, 2, 0, 0, 0, PDI_RUNNING | PDI_AUXDATA] {
+            info.extend_from_slice(&v.to_ne_bytes());
+        }
+        msg.extend(NlAttr::new(PACKET_DIAG_INFO, &info).bytes());
+        let mut ring = vec![];
+        for v in &[4096u32, 64, 2048, 128, 0, 0, 0] {
+            ring.extend_from_slice(&v.to_ne_bytes());
+        }
+        msg.extend(NlAttr::new(PACKET_DIAG_RX_RING, &ring).bytes());
+        msg.extend(NlAttr::new(PACKET_DIAG_FANOUT, &(7u32 | 1 << 16 | 0x80 << 24).to_ne_bytes()).bytes());
+        // ret #-1
+        let mut filter = 0x06u16.to_ne_bytes().to_vec();
+        filter.extend_from_slice(&[0, 0]);
+        filter.extend_from_slice(&(!0u32).to_ne_bytes());
+        msg.extend(NlAttr::new(PACKET_DIAG_FILTER, &filter).bytes());
+
+        let sock = PacketSocket::from_bytes(&msg).unwrap();
+        assert_eq!((sock.sock_type(), sock.protocol(), sock.inode(), sock.cookie()), (3, 3, 42, 1));
+        assert_eq!(sock.info().unwrap().ifindex(), 2);
+        assert_eq!(sock.info().unwrap().flags(), PDI_RUNNING | PDI_AUXDATA);
+        assert_eq!(sock.rx_ring().unwrap().frame_nr(), 128);
+        assert_eq!(sock.tx_ring(), None);
+        let fanout = sock.fanout().unwrap();
+        assert_eq!((fanout.id(), fanout.fanout_type(), fanout.flags()), (7, 1, 0x80));
+        assert_eq!(sock.filter().unwrap()[0].code(), 0x06);
+        assert_eq!(sock.filter().unwrap()[0].k(), !0);
+        assert_eq!(sock.uid(), None);
+    }
+
+    #[test]
+    fn test_packet_sockets() {
+        let fd = unsafe { ::libc::socket(::libc::AF_PACKET, ::libc::SOCK_DGRAM, 0) };
+        if fd < 0 {
+            return;
+        }
+        let inode = unsafe {
+            let mut st: ::libc::stat = mem::zeroed();
+            ::libc::fstat(fd, &mut st);
+            st.st_ino as u32
+        };
+
+        let mut socket = Socket::new(Protocol::INETDiag).unwrap();
+        let result = packet_sockets(&mut socket, &[PacketShow::Info, PacketShow::Fanout]);
+        unsafe { ::libc::close(fd) };
+        let socks = match result {
+            Err(ref e) if e.raw_os_error() == Some(::libc::ENOENT) => return,
+            r => r.unwrap(),
+        };
+        let sock = socks.iter().find(|s| s.inode() == inode).unwrap();
+        assert_eq!(sock.sock_type(), ::libc::SOCK_DGRAM as u8);
+        assert_eq!(sock.protocol(), 0);
+        assert_eq!(sock.info().unwrap().ifindex(), 0);
+        assert_eq!(sock.fanout(), None);
+    }
+}
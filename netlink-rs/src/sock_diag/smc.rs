@@ -0,0 +1,338 @@
+//! Shared memory communications sockets (AF_SMC, smc_diag)
+//!
+//! SMC-R over RDMA and SMC-D over ISM devices take over TCP connections
+//! once both ends agree, or fall back to the TCP connection (the CLC socket)
+//! otherwise. Sockets report the addresses of their CLC socket.
+
+use super::{dump, u32_at, SockId, INET_DIAG_SOCKID_LEN};
+use socket::{NetlinkTransport, NlAttr};
+
+use std::io::{self, ErrorKind};
+
+const AF_SMC: u8 = 43;
+
+const SMC_DIAG_CONNINFO: u16 = 1;
+const SMC_DIAG_LGRINFO: u16 = 2;
+const SMC_DIAG_SHUTDOWN: u16 = 3;
+const SMC_DIAG_FALLBACK: u16 = 5;
+
+const SMC_DIAG_MSG_LEN: usize = 4 + INET_DIAG_SOCKID_LEN + 4 + 8;
+const IB_DEVICE_NAME_MAX: usize = 64;
+
+/// Operating mode of a connection (SMC_DIAG_MODE_*)
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum SmcMode {
+    /// Over RDMA (RoCE)
+    SmcR,
+    /// Fell back to TCP
+    FallbackTcp,
+    /// Over an ISM device, within a machine
+    SmcD,
+    Other(u8),
+}
+
+impl From<u8> for SmcMode {
+    fn from(m: u8) -> SmcMode {
+        match m {
+            0 => SmcMode::SmcR,
+            1 => SmcMode::FallbackTcp,
+            2 => SmcMode::SmcD,
+            i => SmcMode::Other(i),
+        }
+    }
+}
+
+/// Extensions reported with each socket
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum SmcExt {
+    /// Buffers of the connection
+    ConnInfo,
+    /// Link group, for SMC-R
+    LinkGroup,
+    /// Shutdown state
+    Shutdown,
+    /// Reason of a fallback to TCP
+    Fallback,
+}
+
+impl From<SmcExt> for u16 {
+    fn from(e: SmcExt) -> u16 {
+        match e {
+            SmcExt::ConnInfo => SMC_DIAG_CONNINFO,
+            SmcExt::LinkGroup => SMC_DIAG_LGRINFO,
+            SmcExt::Shutdown => SMC_DIAG_SHUTDOWN,
+            SmcExt::Fallback => SMC_DIAG_FALLBACK,
+        }
+    }
+}
+
+/// Buffers of a connection (`struct smc_diag_conninfo`)
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct SmcConnInfo {
+    token: u32,
+    sndbuf_size: u32,
+    rmbe_size: u32,
+    peer_rmbe_size: u32,
+}
+
+impl SmcConnInfo {
+    fn from_bytes(bytes: &[u8]) -> io::Result<SmcConnInfo> {
+        if bytes.len() < 16 {
+            return Err(io::Error::new(ErrorKind::InvalidData, "smc_diag_conninfo too short"));
+        }
+        Ok(SmcConnInfo {
+            token: u32_at(bytes, 0),
+            sndbuf_size: u32_at(bytes, 4),
+            rmbe_size: u32_at(bytes, 8),
+            peer_rmbe_size: u32_at(bytes, 12),
+        })
+    }
+
+    /// Identifier of the connection within its link group
+    pub fn token(&self) -> u32 {
+        self.token
+    }
+
+    pub fn sndbuf_size(&self) -> u32 {
+        self.sndbuf_size
+    }
+
+    /// Size of the local receive buffer element
+    pub fn rmbe_size(&self) -> u32 {
+        self.rmbe_size
+    }
+
+    pub fn peer_rmbe_size(&self) -> u32 {
+        self.peer_rmbe_size
+    }
+}
+
+// HEADER FORMAT
+// struct smc_diag_linkinfo lnk[1];
+//     __u8 link_id;
+//     __u8 ibname[IB_DEVICE_NAME_MAX];
+//     __u8 ibport;
+//     __u8 gid[40];
+//     __u8 peer_gid[40];
+// __u8 role;
+/// First link of the SMC-R link group of a connection (`struct
+/// smc_diag_lgrinfo`)
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct SmcLinkGroup {
+    link_id: u8,
+    ib_device: String,
+    ib_port: u8,
+    server: bool,
+}
+
+impl SmcLinkGroup {
+    fn from_bytes(bytes: &[u8]) -> io::Result<SmcLinkGroup> {
+        let role = 1 + IB_DEVICE_NAME_MAX + 1 + 80;
+        if bytes.len() <= role {
+            return Err(io::Error::new(ErrorKind::InvalidData, "smc_diag_lgrinfo too short"));
+        }
+        let name = &bytes[1..1 + IB_DEVICE_NAME_MAX];
+        let end = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+        Ok(SmcLinkGroup {
+            link_id: bytes[0],
+            ib_device: String::from_utf8_lossy(&name[..end]).into_owned(),
+            ib_port: bytes[1 + IB_DEVICE_NAME_MAX],
+            server: bytes[role] != 0,
+        })
+    }
+
+    pub fn link_id(&self) -> u8 {
+        self.link_id
+    }
+
+    /// RDMA device of the link, e.g. "mlx5_0"
+    pub fn ib_device(&self) -> &str {
+        &self.ib_device
+    }
+
+    pub fn ib_port(&self) -> u8 {
+        self.ib_port
+    }
+
+    /// Whether the local end is the server of the link group
+    pub fn is_server(&self) -> bool {
+        self.server
+    }
+}
+
+// HEADER FORMAT
+// __u8    diag_family;
+// __u8    diag_state;
+// __u8    diag_mode;
+// __u8    diag_shutdown;
+// struct inet_diag_sockid id;
+// __u32   diag_uid;
+// __aligned_u64 diag_inode;
+/// An SMC socket (`struct smc_diag_msg`) with its extensions
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct SmcSocket {
+    state: u8,
+    mode: SmcMode,
+    shutdown: u8,
+    id: SockId,
+    uid: u32,
+    inode: u64,
+    conn: Option<SmcConnInfo>,
+    link_group: Option<SmcLinkGroup>,
+    fallback: Option<(u32, u32)>,
+}
+
+impl SmcSocket {
+    fn from_bytes(bytes: &[u8]) -> io::Result<SmcSocket> {
+        if bytes.len() < SMC_DIAG_MSG_LEN {
+            return Err(io::Error::new(ErrorKind::InvalidData, "smc_diag_msg too short"));
+        }
+        let inode_at = 4 + INET_DIAG_SOCKID_LEN + 4;
+        let mut inode = [0u8; 8];
+        inode.copy_from_slice(&bytes[inode_at..inode_at + 8]);
+        let mut sock = SmcSocket {
+            state: bytes[1],
+            mode: bytes[2].into(),
+            shutdown: bytes[3],
+            id: SockId::from_bytes_guess(&bytes[4..])?,
+            uid: u32_at(bytes, 4 + INET_DIAG_SOCKID_LEN),
+            inode: u64::from_ne_bytes(inode),
+            conn: None,
+            link_group: None,
+            fallback: None,
+        };
+        for attr in NlAttr::parse(&bytes[SMC_DIAG_MSG_LEN..])? {
+            let p = attr.payload();
+            match attr.attr_type() {
+                SMC_DIAG_CONNINFO => sock.conn = Some(SmcConnInfo::from_bytes(p)?),
+                SMC_DIAG_LGRINFO => sock.link_group = Some(SmcLinkGroup::from_bytes(p)?),
+                SMC_DIAG_SHUTDOWN => sock.shutdown = attr.get_u8()?,
+                SMC_DIAG_FALLBACK if p.len() >= 8 => sock.fallback = Some((u32_at(p, 0), u32_at(p, 4))),
+                _ => {},
+            }
+        }
+        Ok(sock)
+    }
+
+    /// TCP_* state of the socket
+    pub fn state(&self) -> u8 {
+        self.state
+    }
+
+    pub fn mode(&self) -> SmcMode {
+        self.mode
+    }
+
+    /// SHUTDOWN_MASK bits, 1 for receive and 2 for send
+    pub fn shutdown(&self) -> u8 {
+        self.shutdown
+    }
+
+    /// Addresses of the CLC socket
+    pub fn id(&self) -> &SockId {
+        &self.id
+    }
+
+    pub fn uid(&self) -> u32 {
+        self.uid
+    }
+
+    pub fn inode(&self) -> u64 {
+        self.inode
+    }
+
+    pub fn conn_info(&self) -> Option<&SmcConnInfo> {
+        self.conn.as_ref()
+    }
+
+    pub fn link_group(&self) -> Option<&SmcLinkGroup> {
+        self.link_group.as_ref()
+    }
+
+    /// SMC_CLC_DECL_* reasons of the local and the peer end for falling
+    /// back to TCP
+    pub fn fallback(&self) -> Option<(u32, u32)> {
+        self.fallback
+    }
+}
+
+// HEADER FORMAT
+// __u8    diag_family;
+// __u8    pad[2];
+// __u8    diag_ext;           /* Query extended information */
+// struct inet_diag_sockid id;
+/// Dumps the SMC sockets with the extensions `exts`. Fails with ENOENT if
+/// the smc_diag module is not available.
+pub fn smc_sockets(socket: &mut impl NetlinkTransport, exts: &[SmcExt]) -> io::Result<Vec<SmcSocket>> {
+    let ext = exts.iter().fold(0u8, |mask, &e| mask | 1 << (u16::from(e) - 1));
+    let mut req = vec![AF_SMC, 0, 0, ext];
+    req.extend(SockId::any());
+    let replies = dump(socket, &req)?;
+    replies.iter().map(|r| SmcSocket::from_bytes(r)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket::{Socket, ReplayTransport, NlMsgHeader};
+    use Protocol;
+
+    #[test]
+    fn test_replay_smc_sockets() {
+        let mut msg = vec![AF_SMC, 1, 0, 0];
+        msg.extend(SockId::any());
+        msg.extend_from_slice(&1000u32.to_ne_bytes());
+        msg.extend_from_slice(&77u64.to_ne_bytes());
+        let mut conn = vec![];
+        for v in &[5u32, 65536, 65536, 32768] {
+            conn.extend_from_slice(&v.to_ne_bytes());
+        }
+        conn.resize(76, 0);
+        msg.extend(NlAttr::new(SMC_DIAG_CONNINFO, &conn).bytes());
+        let mut lgr = vec![2];
+        lgr.extend_from_slice(b"mlx5_0");
+        lgr.resize(1 + 64, 0);
+        lgr.push(1);
+        lgr.resize(1 + 64 + 1 + 80, 0);
+        lgr.push(1);
+        msg.extend(NlAttr::new(SMC_DIAG_LGRINFO, &lgr).bytes());
+        msg.extend(NlAttr::new(SMC_DIAG_SHUTDOWN, &[2]).bytes());
+
+        let mut hdr = NlMsgHeader::user_defined(20);
+        hdr.data_length(msg.len() as u32).seq(1);
+        let mut datagram = hdr.bytes().to_vec();
+        datagram.extend(msg);
+        datagram.extend_from_slice(&NlMsgHeader::done().bytes());
+
+        let mut replay = ReplayTransport::new(vec![datagram]);
+        let socks = smc_sockets(&mut replay, &[SmcExt::ConnInfo, SmcExt::LinkGroup]).unwrap();
+        assert_eq!(replay.sent()[0][16..20], [AF_SMC, 0, 0, 0b11]);
+        let sock = &socks[0];
+        assert_eq!(sock.state(), 1);
+        assert_eq!(sock.mode(), SmcMode::SmcR);
+        assert_eq!(sock.shutdown(), 2);
+        assert_eq!(sock.uid(), 1000);
+        assert_eq!(sock.inode(), 77);
+        assert_eq!(sock.conn_info().unwrap().token(), 5);
+        assert_eq!(sock.conn_info().unwrap().peer_rmbe_size(), 32768);
+        let lgr = sock.link_group().unwrap();
+        assert_eq!((lgr.link_id(), lgr.ib_device(), lgr.ib_port()), (2, "mlx5_0", 1));
+        assert!(lgr.is_server());
+        assert_eq!(sock.fallback(), None);
+    }
+
+    #[test]
+    fn test_smc_sockets() {
+        let mut socket = Socket::new(Protocol::INETDiag).unwrap();
+        match smc_sockets(&mut socket, &[SmcExt::ConnInfo]) {
+            Ok(_) => {},
+            Err(ref e) if e.raw_os_error() == Some(::libc::ENOENT) => {},
+            Err(e) => panic!("{}", e),
+        }
+    }
+}
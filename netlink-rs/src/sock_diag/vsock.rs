@@ -0,0 +1,181 @@
+//! VM sockets (AF_VSOCK, vsock_diag, `ss --vsock`)
+//!
+//! Connections between a hypervisor and its guests are addressed by
+//! context id and port rather than by IP address. The host has CID 2.
+
+use super::{dump, u32_at};
+use socket::NetlinkTransport;
+
+use std::io::{self, ErrorKind};
+
+const AF_VSOCK: u8 = 40;
+
+const VSOCK_DIAG_MSG_LEN: usize = 32;
+
+/// Context id of the host
+pub const VMADDR_CID_HOST: u32 = 2;
+/// Any context id, as bound by listeners
+pub const VMADDR_CID_ANY: u32 = !0;
+
+// HEADER FORMAT
+// __u8    vdiag_family;
+// __u8    vdiag_type;
+// __u8    vdiag_state;
+// __u8    vdiag_shutdown;
+// __u32   vdiag_src_cid;
+// __u32   vdiag_src_port;
+// __u32   vdiag_dst_cid;
+// __u32   vdiag_dst_port;
+// __u32   vdiag_ino;
+// __u32   vdiag_cookie[2];
+/// A vsock socket (`struct vsock_diag_msg`)
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct VsockSocket {
+    sock_type: u8,
+    state: u8,
+    shutdown: u8,
+    src_cid: u32,
+    src_port: u32,
+    dst_cid: u32,
+    dst_port: u32,
+    inode: u32,
+    cookie: u64,
+}
+
+impl VsockSocket {
+    fn from_bytes(bytes: &[u8]) -> io::Result<VsockSocket> {
+        if bytes.len() < VSOCK_DIAG_MSG_LEN {
+            return Err(io::Error::new(ErrorKind::InvalidData, "vsock_diag_msg too short"));
+        }
+        Ok(VsockSocket {
+            sock_type: bytes[1],
+            state: bytes[2],
+            shutdown: bytes[3],
+            src_cid: u32_at(bytes, 4),
+            src_port: u32_at(bytes, 8),
+            dst_cid: u32_at(bytes, 12),
+            dst_port: u32_at(bytes, 16),
+            inode: u32_at(bytes, 20),
+            cookie: (u32_at(bytes, 28) as u64) << 32 | u32_at(bytes, 24) as u64,
+        })
+    }
+
+    /// SOCK_STREAM, SOCK_DGRAM or SOCK_SEQPACKET
+    pub fn sock_type(&self) -> u8 {
+        self.sock_type
+    }
+
+    /// TCP_* state, e.g. TCP_LISTEN or TCP_ESTABLISHED
+    pub fn state(&self) -> u8 {
+        self.state
+    }
+
+    /// SHUTDOWN_MASK bits, 1 for receive and 2 for send
+    pub fn shutdown(&self) -> u8 {
+        self.shutdown
+    }
+
+    /// Local context id, `VMADDR_CID_ANY` if unbound
+    pub fn src_cid(&self) -> u32 {
+        self.src_cid
+    }
+
+    pub fn src_port(&self) -> u32 {
+        self.src_port
+    }
+
+    /// Remote context id, `VMADDR_CID_ANY` unless connected
+    pub fn dst_cid(&self) -> u32 {
+        self.dst_cid
+    }
+
+    pub fn dst_port(&self) -> u32 {
+        self.dst_port
+    }
+
+    pub fn inode(&self) -> u32 {
+        self.inode
+    }
+
+    pub fn cookie(&self) -> u64 {
+        self.cookie
+    }
+}
+
+// HEADER FORMAT
+// __u8    sdiag_family;   /* must be AF_VSOCK */
+// __u8    sdiag_protocol; /* must be 0 */
+// __u16   pad;            /* must be 0 */
+// __u32   vdiag_states;   /* query bitmap (e.g. 1 << TCP_LISTEN) */
+// __u32   vdiag_ino;      /* must be 0 (reserved) */
+// __u32   vdiag_show;     /* must be 0 (reserved) */
+// __u32   vdiag_cookie[2];
+/// Dumps the vsock sockets in the TCP_* states of mask `states`, bit
+/// `1 << state`. Fails with ENOENT if the vsock_diag module is not
+/// available.
+pub fn vsock_sockets(socket: &mut impl NetlinkTransport, states: u32) -> io::Result<Vec<VsockSocket>> {
+    let mut req = vec![AF_VSOCK, 0, 0, 0];
+    req.extend_from_slice(&states.to_ne_bytes());
+    req.extend_from_slice(&[0; 8]);
+    req.extend_from_slice(&[0xff; 8]);
+    let replies = dump(socket, &req)?;
+    replies.iter().map(|r| VsockSocket::from_bytes(r)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket::Socket;
+    use Protocol;
+
+    use std::mem;
+
+    const TCP_LISTEN: u8 = 10;
+
+    #[test]
+    fn test_decode() {
+        let mut msg = vec![AF_VSOCK, 1, TCP_LISTEN, 0];
+        for v in &[VMADDR_CID_ANY, 1024, VMADDR_CID_ANY, !0, 31, 9, 0] {
+            msg.extend_from_slice(&v.to_ne_bytes());
+        }
+        let sock = VsockSocket::from_bytes(&msg).unwrap();
+        assert_eq!((sock.sock_type(), sock.state(), sock.shutdown()), (1, TCP_LISTEN, 0));
+        assert_eq!((sock.src_cid(), sock.src_port()), (VMADDR_CID_ANY, 1024));
+        assert_eq!((sock.inode(), sock.cookie()), (31, 9));
+        assert!(VsockSocket::from_bytes(&msg[..31]).is_err());
+    }
+
+    #[test]
+    fn test_vsock_sockets() {
+        let fd = unsafe { ::libc::socket(::libc::AF_VSOCK, ::libc::SOCK_STREAM, 0) };
+        if fd < 0 {
+            return;
+        }
+        let (inode, listening) = unsafe {
+            let mut addr: ::libc::sockaddr_vm = mem::zeroed();
+            addr.svm_family = ::libc::AF_VSOCK as ::libc::sa_family_t;
+            addr.svm_cid = VMADDR_CID_ANY;
+            addr.svm_port = ::libc::VMADDR_PORT_ANY;
+            let bound = ::libc::bind(fd, &addr as *const _ as *const ::libc::sockaddr,
+                                     mem::size_of::<::libc::sockaddr_vm>() as ::libc::socklen_t);
+            let mut st: ::libc::stat = mem::zeroed();
+            ::libc::fstat(fd, &mut st);
+            (st.st_ino as u32, bound == 0 && ::libc::listen(fd, 1) == 0)
+        };
+
+        let mut socket = Socket::new(Protocol::INETDiag).unwrap();
+        let result = vsock_sockets(&mut socket, 1 << TCP_LISTEN);
+        unsafe { ::libc::close(fd) };
+        let socks = match result {
+            Err(ref e) if e.raw_os_error() == Some(::libc::ENOENT) => return,
+            r => r.unwrap(),
+        };
+        if listening {
+            let sock = socks.iter().find(|s| s.inode() == inode).unwrap();
+            assert_eq!(sock.state(), TCP_LISTEN);
+            assert_eq!(sock.src_cid(), VMADDR_CID_ANY);
+        }
+        assert!(socks.iter().all(|s| s.state() == TCP_LISTEN));
+    }
+}
@@ -1,10 +1,13 @@
-use libc::{AF_NETLINK, sa_family_t, sockaddr, c_ushort};
+use libc::{AF_NETLINK, sa_family_t, sockaddr, socklen_t, c_ushort};
+
+use super::socket_impl::SockAddr;
 
 use std::mem;
 use std::io::{self, ErrorKind};
 
 #[repr(C)]
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 struct sockaddr_nl {
     pub nl_family: sa_family_t,
     nl_pad: c_ushort,
@@ -13,6 +16,7 @@ struct sockaddr_nl {
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct NetlinkAddr(sockaddr_nl);
 
 impl NetlinkAddr {
@@ -41,6 +45,16 @@ impl NetlinkAddr {
     }
 }
 
+unsafe impl SockAddr for NetlinkAddr {
+    fn as_ptr(&self) -> *const sockaddr {
+        &self.0 as *const sockaddr_nl as *const sockaddr
+    }
+
+    fn addr_len(&self) -> socklen_t {
+        mem::size_of::<sockaddr_nl>() as socklen_t
+    }
+}
+
 pub fn sockaddr_to_netlinkaddr(sa: &sockaddr) -> io::Result<NetlinkAddr> {
     match sa.sa_family as i32 {
         AF_NETLINK => {
@@ -69,4 +83,9 @@ mod tests {
         assert_eq!(nladdr.pid(), nl2.pid());
         assert_eq!(nladdr.groups(), nl2.groups());
     }
+
+    #[test]
+    fn netlink_addr_length() {
+        assert_eq!(NetlinkAddr::new(0, 0).addr_len(), 12);
+    }
 }
@@ -0,0 +1,62 @@
+use super::{NlAttr, NLA_F_NESTED};
+use super::parse::{self, ParseError};
+
+/// Iterator over an attribute array, a nested attribute whose elements are
+/// themselves nested and typed by their ordinal (1, 2, ...) instead of their
+/// meaning, as CTRL_ATTR_OPS and tc actions are. Lists that repeat a single
+/// type, such as IFLA_VFINFO_LIST or the devlink param value and resource
+/// lists, use the same layout and yield that type in place of the ordinal.
+/// It ends after the first error.
+pub struct AttrArray<'a> {
+    attrs: parse::Attrs<'a>,
+}
+
+impl<'a> Iterator for AttrArray<'a> {
+    /// Ordinal and element
+    type Item = Result<(u16, NlAttr<'a>), ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.attrs.next().map(|r| r.map(|a| (a.attr_type(), a)))
+    }
+}
+
+impl<'a> NlAttr<'a> {
+    /// Iterates over the payload as an attribute array.
+    pub fn array(&self) -> AttrArray<'a> {
+        AttrArray { attrs: parse::attrs(self.payload()) }
+    }
+}
+
+/// Encodes `elements`, each a stream of attributes, as attribute array
+/// `attr_type` with ordinals from 1.
+pub fn array_attr<T: AsRef<[u8]>>(attr_type: u16, elements: &[T]) -> Vec<u8> {
+    let mut payload = vec![];
+    for (i, e) in elements.iter().enumerate() {
+        payload.extend(NlAttr::new((i + 1) as u16 | NLA_F_NESTED, e.as_ref()).bytes());
+    }
+    NlAttr::new(attr_type | NLA_F_NESTED, &payload).bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_array() {
+        let first = NlAttr::new(1, &7u32.to_ne_bytes()).bytes();
+        let second = NlAttr::new(1, &8u32.to_ne_bytes()).bytes();
+        let bytes = array_attr(5, &[first, second]);
+
+        let (attr, _) = NlAttr::from_bytes(&bytes).unwrap();
+        assert!(attr.is_nested());
+        let elements: Vec<_> = attr.array().collect::<Result<_, _>>().unwrap();
+        assert_eq!(elements.len(), 2);
+        assert_eq!(elements[1].0, 2);
+        assert!(elements[1].1.is_nested());
+        assert_eq!(elements[1].1.nested().unwrap()[0].get_u32(), Ok(8));
+
+        let broken = NlAttr::new(5, &[8, 0, 1, 0]);
+        assert_eq!(broken.array().next(), Some(Err(ParseError::LengthTooLarge)));
+        assert_eq!(NlAttr::new(5, &[]).array().count(), 0);
+    }
+}
@@ -0,0 +1,282 @@
+use super::parse::{self, ParseError};
+
+use std::convert::TryFrom;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str;
+
+use libc::{AF_INET, AF_INET6};
+
+// #define NLA_ALIGNTO     4
+const NLA_ALIGNTO: usize = 4;
+
+// #define NLA_HDRLEN      ((int) NLA_ALIGN(sizeof(struct nlattr)))
+const NLA_HDRLEN: usize = 4;
+
+/// Attribute payload is itself a stream of attributes
+pub const NLA_F_NESTED: u16 = 1 << 15;
+/// Attribute payload is stored in network byte order
+pub const NLA_F_NET_BYTEORDER: u16 = 1 << 14;
+/// Bits of `nla_type` that hold the actual type
+pub const NLA_TYPE_MASK: u16 = !(NLA_F_NESTED | NLA_F_NET_BYTEORDER);
+
+// NLA_ALIGN()
+// #define NLA_ALIGN(len)      (((len) + NLA_ALIGNTO - 1) & ~(NLA_ALIGNTO - 1))
+#[inline]
+fn nla_align(len: usize) -> usize {
+    (len + (NLA_ALIGNTO - 1)) & !(NLA_ALIGNTO - 1)
+}
+
+/// Address family (AF_INET or AF_INET6) of `addr`, as found in the fixed
+/// headers that precede address attributes
+pub fn ip_family(addr: IpAddr) -> u8 {
+    match addr {
+        IpAddr::V4(_) => AF_INET as u8,
+        IpAddr::V6(_) => AF_INET6 as u8,
+    }
+}
+
+/// Decodes a 4 or 16 byte address payload.
+pub fn ip_from_bytes(payload: &[u8]) -> Result<IpAddr, ParseError> {
+    if let Ok(octets) = <[u8; 4]>::try_from(payload) {
+        Ok(IpAddr::V4(Ipv4Addr::from(octets)))
+    } else if let Ok(octets) = <[u8; 16]>::try_from(payload) {
+        Ok(IpAddr::V6(Ipv6Addr::from(octets)))
+    } else {
+        Err(ParseError::PayloadLength)
+    }
+}
+
+/// Encodes `addr` as attribute `attr_type`.
+pub fn ip_attr(attr_type: u16, addr: IpAddr) -> Vec<u8> {
+    match addr {
+        IpAddr::V4(a) => NlAttr::new(attr_type, &a.octets()).bytes(),
+        IpAddr::V6(a) => NlAttr::new(attr_type, &a.octets()).bytes(),
+    }
+}
+
+/// Encodes `s` as a NUL terminated string attribute, as the kernel expects
+/// for names such as IFLA_IFNAME.
+pub fn string_attr(attr_type: u16, s: &str) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(s.len() + 1);
+    payload.extend_from_slice(s.as_bytes());
+    payload.push(0);
+    NlAttr::new(attr_type, &payload).bytes()
+}
+
+/// Decodes a NUL padded `char[]` field of a fixed size struct.
+#[cfg(any(feature = "genl", feature = "crypto"))]
+pub(crate) fn c_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+// ATTRIBUTE FORMAT
+// __u16 nla_len;      /* Length of attribute including header. */
+// __u16 nla_type;     /* Type of attribute content. */
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct NlAttr<'a> {
+    attr_type: u16,
+    payload: &'a [u8],
+}
+
+impl<'a> NlAttr<'a> {
+    pub fn new(attr_type: u16, payload: &'a [u8]) -> NlAttr<'a> {
+        NlAttr {
+            attr_type,
+            payload,
+        }
+    }
+
+    /// Decodes one attribute, returning it together with the number of bytes
+    /// consumed including alignment padding.
+    pub fn from_bytes(bytes: &'a [u8]) -> io::Result<(NlAttr<'a>, usize)> {
+        Ok(parse::attr(bytes)?)
+    }
+
+    /// Decodes a stream of attributes, such as the tail of a message or the
+    /// payload of a nested attribute.
+    pub fn parse(bytes: &'a [u8]) -> io::Result<Vec<NlAttr<'a>>> {
+        Ok(parse::attrs(bytes).collect::<Result<_, _>>()?)
+    }
+
+    /// Attribute type with the nested and byte order flags masked off
+    pub fn attr_type(&self) -> u16 {
+        self.attr_type & NLA_TYPE_MASK
+    }
+
+    pub fn is_nested(&self) -> bool {
+        self.attr_type & NLA_F_NESTED != 0
+    }
+
+    /// Whether the payload is in network byte order; the integer getters
+    /// honour it
+    pub fn is_net_byteorder(&self) -> bool {
+        self.attr_type & NLA_F_NET_BYTEORDER != 0
+    }
+
+    fn octets<const N: usize>(&self) -> Result<[u8; N], ParseError> {
+        <[u8; N]>::try_from(self.payload).map_err(|_| ParseError::PayloadLength)
+    }
+
+    pub fn get_u8(&self) -> Result<u8, ParseError> {
+        self.octets().map(|[b]| b)
+    }
+
+    pub fn get_u16(&self) -> Result<u16, ParseError> {
+        let a = self.octets()?;
+        Ok(if self.is_net_byteorder() { u16::from_be_bytes(a) } else { u16::from_ne_bytes(a) })
+    }
+
+    pub fn get_u32(&self) -> Result<u32, ParseError> {
+        let a = self.octets()?;
+        Ok(if self.is_net_byteorder() { u32::from_be_bytes(a) } else { u32::from_ne_bytes(a) })
+    }
+
+    pub fn get_u64(&self) -> Result<u64, ParseError> {
+        let a = self.octets()?;
+        Ok(if self.is_net_byteorder() { u64::from_be_bytes(a) } else { u64::from_ne_bytes(a) })
+    }
+
+    /// Payload as a string with its trailing NULs stripped; a NUL before
+    /// them is an error
+    pub fn get_str(&self) -> Result<&'a str, ParseError> {
+        let end = self.payload.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+        let s = &self.payload[..end];
+        if s.contains(&0) {
+            return Err(ParseError::InteriorNul);
+        }
+        str::from_utf8(s).map_err(|_| ParseError::InvalidUtf8)
+    }
+
+    pub fn get_bytes(&self) -> &'a [u8] {
+        self.payload
+    }
+
+    /// Payload as an IPv4 or IPv6 address, which are always in network byte
+    /// order
+    pub fn get_ip(&self) -> Result<IpAddr, ParseError> {
+        ip_from_bytes(self.payload)
+    }
+
+    /// Payload as an address of `family`, AF_INET or AF_INET6; other
+    /// families accept either size
+    pub fn get_ip_in(&self, family: u8) -> Result<IpAddr, ParseError> {
+        let addr = ip_from_bytes(self.payload)?;
+        match family as i32 {
+            AF_INET | AF_INET6 if ip_family(addr) != family => Err(ParseError::PayloadLength),
+            _ => Ok(addr),
+        }
+    }
+
+    pub fn payload(&self) -> &'a [u8] {
+        self.payload
+    }
+
+    /// Decodes the payload as a stream of nested attributes
+    pub fn nested(&self) -> io::Result<Vec<NlAttr<'a>>> {
+        NlAttr::parse(self.payload)
+    }
+
+    /// Encodes header and payload, padded to the attribute alignment.
+    pub fn bytes(&self) -> Vec<u8> {
+        let len = NLA_HDRLEN + self.payload.len();
+        let mut bytes = Vec::with_capacity(nla_align(len));
+        bytes.extend_from_slice(&(len as u16).to_ne_bytes());
+        bytes.extend_from_slice(&self.attr_type.to_ne_bytes());
+        bytes.extend_from_slice(self.payload);
+        bytes.resize(nla_align(len), 0);
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Attribute header in native byte order
+    fn nla(len: u16, attr_type: u16) -> Vec<u8> {
+        let mut bytes = len.to_ne_bytes().to_vec();
+        bytes.extend_from_slice(&attr_type.to_ne_bytes());
+        bytes
+    }
+
+    #[test]
+    fn test_encoding() {
+        let mut expected = nla(7, 2);
+        expected.extend_from_slice(&[1, 2, 3, 0]);
+        let attr = NlAttr::new(2, &[1, 2, 3]);
+        assert_eq!(attr.bytes(), expected);
+    }
+
+    #[test]
+    fn test_decoding() {
+        let mut bytes = nla(7, 2 | NLA_F_NESTED);
+        bytes.extend_from_slice(&[1, 2, 3, 0]);
+        bytes.extend(nla(8, 1));
+        bytes.extend_from_slice(&[9, 0, 0, 0]);
+        let attrs = NlAttr::parse(&bytes).unwrap();
+        assert_eq!(attrs.len(), 2);
+        assert_eq!(attrs[0].attr_type(), 2);
+        assert!(attrs[0].is_nested());
+        assert_eq!(attrs[0].payload(), &[1, 2, 3]);
+        assert_eq!(attrs[1].attr_type(), 1);
+        assert_eq!(attrs[1].payload(), &[9, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_decoding_nested() {
+        let inner = NlAttr::new(1, &[5, 0, 0, 0]).bytes();
+        let outer = NlAttr::new(3 | NLA_F_NESTED, &inner).bytes();
+        let (attr, n) = NlAttr::from_bytes(&outer).unwrap();
+        assert_eq!(n, outer.len());
+
+        let nested = attr.nested().unwrap();
+        assert_eq!(nested, vec![NlAttr::new(1, &[5, 0, 0, 0])]);
+    }
+
+    #[test]
+    fn test_getters() {
+        assert_eq!(NlAttr::new(1, &[7]).get_u8(), Ok(7));
+        assert_eq!(NlAttr::new(1, &0x1234u16.to_ne_bytes()).get_u16(), Ok(0x1234));
+        assert_eq!(NlAttr::new(1 | NLA_F_NET_BYTEORDER, &[0x12, 0x34]).get_u16(), Ok(0x1234));
+        assert_eq!(NlAttr::new(1, &5u32.to_ne_bytes()).get_u32(), Ok(5));
+        assert_eq!(NlAttr::new(1 | NLA_F_NET_BYTEORDER, &5u64.to_be_bytes()).get_u64(), Ok(5));
+        assert_eq!(NlAttr::new(1, &[1, 2, 3]).get_u32(), Err(ParseError::PayloadLength));
+        assert_eq!(NlAttr::new(1, &[0; 8]).get_u32(), Err(ParseError::PayloadLength));
+
+        assert_eq!(NlAttr::new(1, b"eth0\0\0").get_str(), Ok("eth0"));
+        assert_eq!(NlAttr::new(1, b"lo").get_str(), Ok("lo"));
+        assert_eq!(NlAttr::new(1, &[0xff, 0]).get_str(), Err(ParseError::InvalidUtf8));
+        assert_eq!(NlAttr::new(1, b"eth\0x\0").get_str(), Err(ParseError::InteriorNul));
+        assert_eq!(NlAttr::new(1, b"\0").get_str(), Ok(""));
+
+        let bytes = string_attr(3, "veth0");
+        assert_eq!(bytes, NlAttr::new(3, b"veth0\0").bytes());
+        assert_eq!(NlAttr::from_bytes(&bytes).unwrap().0.get_str(), Ok("veth0"));
+
+        assert_eq!(NlAttr::new(1, &[10, 0, 0, 1]).get_ip(), Ok("10.0.0.1".parse().unwrap()));
+        let v6: Ipv6Addr = "fe80::1".parse().unwrap();
+        assert_eq!(NlAttr::new(1, &v6.octets()).get_ip(), Ok(IpAddr::V6(v6)));
+        assert_eq!(NlAttr::new(1, &[1, 2]).get_ip(), Err(ParseError::PayloadLength));
+    }
+
+    #[test]
+    fn test_ip_family() {
+        let v4: IpAddr = "192.0.2.1".parse().unwrap();
+        let attr = ip_attr(1, v4);
+        let (attr, _) = NlAttr::from_bytes(&attr).unwrap();
+        assert_eq!(attr.get_ip_in(ip_family(v4)), Ok(v4));
+        assert_eq!(attr.get_ip_in(AF_INET6 as u8), Err(ParseError::PayloadLength));
+        assert_eq!(attr.get_ip_in(0), Ok(v4));
+        assert_eq!(ip_family("::1".parse().unwrap()), AF_INET6 as u8);
+    }
+
+    #[test]
+    fn test_decoding_error() {
+        let bytes = [12, 0, 1, 0, 1, 2, 3, 4];
+        assert!(NlAttr::parse(&bytes).is_err());
+        let bytes = [2, 0, 1, 0];
+        assert!(NlAttr::parse(&bytes).is_err());
+    }
+}
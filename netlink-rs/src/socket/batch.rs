@@ -0,0 +1,75 @@
+use super::Msg;
+
+use std::io;
+
+/// Messages encoded back to back into one buffer, each padded to the netlink
+/// alignment, to be sent in a single datagram by `Socket::send_batch`.
+/// `clear` keeps the buffer, so a batch can be reused across sends.
+#[derive(Clone, Default, Debug)]
+pub struct MsgBatch {
+    bytes: Vec<u8>,
+    count: usize,
+}
+
+impl MsgBatch {
+    pub fn new() -> MsgBatch {
+        MsgBatch::default()
+    }
+
+    /// Appends `msg`
+    pub fn push(&mut self, msg: &Msg) -> io::Result<&mut MsgBatch> {
+        self.bytes.extend(msg.bytes()?);
+        self.count += 1;
+        Ok(self)
+    }
+
+    /// Total length in bytes, padding included
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Number of messages
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Removes all messages, keeping the allocated buffer
+    pub fn clear(&mut self) {
+        self.bytes.clear();
+        self.count = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket::{NlMsgHeader, Payload, parse};
+
+    use std::borrow::Cow;
+
+    #[test]
+    fn test_batch_alignment() {
+        let mut hdr = NlMsgHeader::request();
+        hdr.data_length(3);
+        let msg = Msg::new(hdr, Payload::Data(Cow::Borrowed(&[1, 2, 3])));
+
+        let mut batch = MsgBatch::new();
+        batch.push(&msg).unwrap().push(&msg).unwrap();
+        assert_eq!(batch.count(), 2);
+        assert_eq!(batch.len(), 40);
+        let decoded: Result<Vec<_>, _> = parse::messages(batch.bytes()).collect();
+        assert_eq!(decoded.unwrap()[1], (hdr, &[1u8, 2, 3][..]));
+
+        batch.clear();
+        assert!(batch.is_empty());
+        assert_eq!(batch.len(), 0);
+    }
+}
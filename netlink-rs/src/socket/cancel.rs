@@ -0,0 +1,128 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use libc::{c_void, close, eventfd, write, EFD_CLOEXEC, EFD_NONBLOCK};
+
+/// Error of a blocking receive stopped by a `CancelToken`
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct Cancelled;
+
+impl Cancelled {
+    /// Whether `err` reports a cancellation
+    pub fn matches(err: &io::Error) -> bool {
+        err.get_ref().is_some_and(|e| e.is::<Cancelled>())
+    }
+}
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "operation cancelled")
+    }
+}
+
+impl Error for Cancelled {}
+
+struct EventFd {
+    fd: RawFd,
+    cancelled: AtomicBool,
+}
+
+impl Drop for EventFd {
+    fn drop(&mut self) {
+        unsafe { close(self.fd) };
+    }
+}
+
+/// Stops the blocking receives of the sockets it is set on, see
+/// `Socket::set_cancel_token`. Clones share the token, so that another
+/// thread, e.g. a signal or shutdown handler, can cancel. A cancelled token
+/// stays cancelled.
+#[derive(Clone)]
+pub struct CancelToken {
+    inner: Arc<EventFd>,
+}
+
+impl CancelToken {
+    pub fn new() -> io::Result<CancelToken> {
+        let fd = unsafe { eventfd(0, EFD_CLOEXEC | EFD_NONBLOCK) };
+        if fd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(CancelToken { inner: Arc::new(EventFd { fd, cancelled: AtomicBool::new(false) }) })
+    }
+
+    /// Makes the receives waiting on the token, and those to come, fail
+    /// with a `Cancelled` error.
+    pub fn cancel(&self) {
+        if !self.inner.cancelled.swap(true, Ordering::SeqCst) {
+            let one = 1u64;
+            unsafe { write(self.inner.fd, &one as *const u64 as *const c_void, 8) };
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+/// The eventfd that turns readable on cancellation, e.g. for
+/// `Socket::recv_or_wake` or an event loop
+impl AsRawFd for CancelToken {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.fd
+    }
+}
+
+impl fmt::Debug for CancelToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CancelToken").field("cancelled", &self.is_cancelled()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket::{Socket, Msg, NlMsgHeader, NlAttr, Payload};
+    use Protocol;
+
+    use std::borrow::Cow;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_cancel_recv() {
+        let token = CancelToken::new().unwrap();
+        let (_, mut recv) = Socket::new(Protocol::Usersock).unwrap().split();
+        recv.set_cancel_token(token.clone());
+
+        let receiver = thread::spawn(move || recv.recv().map(|_| ()).unwrap_err());
+        thread::sleep(Duration::from_millis(50));
+        token.cancel();
+        assert!(Cancelled::matches(&receiver.join().unwrap()));
+        assert!(token.is_cancelled());
+    }
+
+    #[test]
+    fn test_cancel_talk() {
+        let token = CancelToken::new().unwrap();
+        let mut socket = Socket::new(Protocol::Generic).unwrap();
+        socket.set_cancel_token(token.clone());
+
+        // CTRL_CMD_GETFAMILY for nlctrl
+        let mut payload = vec![3, 2, 0, 0];
+        payload.extend(NlAttr::new(2, b"nlctrl\0").bytes());
+        let mut hdr = NlMsgHeader::user_defined(0x10);
+        hdr.data_length(payload.len() as u32);
+        let msg = Msg::new(hdr, Payload::Data(Cow::Borrowed(&payload)));
+        assert!(socket.talk(msg.clone()).is_ok());
+
+        token.cancel();
+        assert!(Cancelled::matches(&socket.talk(msg).unwrap_err()));
+        // Non-blocking receives are not affected
+        assert!(socket.try_recv().unwrap().is_some());
+    }
+}
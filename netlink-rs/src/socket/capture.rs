@@ -0,0 +1,174 @@
+use std::io::{self, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use std::io::{Cursor, ErrorKind};
+
+use byteorder::{BigEndian, NativeEndian, ReadBytesExt, WriteBytesExt};
+
+const SHB_TYPE: u32 = 0x0A0D_0D0A;
+const IDB_TYPE: u32 = 1;
+const EPB_TYPE: u32 = 6;
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+
+const LINKTYPE_NETLINK: u16 = 253;
+const ARPHRD_NETLINK: u16 = 824;
+const PACKET_HOST: u16 = 0;
+const PACKET_OUTGOING: u16 = 4;
+
+/// Writes the datagrams of a socket to a pcapng stream with the netlink link
+/// type, e.g. to inspect them in Wireshark; see `Socket::set_capture`.
+pub struct Capture {
+    out: Box<dyn Write>,
+}
+
+impl Capture {
+    /// Starts a capture file on `out` with one netlink interface.
+    pub fn new<W: Write + 'static>(out: W) -> io::Result<Capture> {
+        let mut capture = Capture { out: Box::new(out) };
+
+        // Section header, version 1.0, unknown section length
+        let mut shb = vec![];
+        shb.write_u32::<NativeEndian>(BYTE_ORDER_MAGIC)?;
+        shb.write_u16::<NativeEndian>(1)?;
+        shb.write_u16::<NativeEndian>(0)?;
+        shb.write_i64::<NativeEndian>(-1)?;
+        capture.block(SHB_TYPE, &shb)?;
+
+        // Interface description, no snapshot length limit
+        let mut idb = vec![];
+        idb.write_u16::<NativeEndian>(LINKTYPE_NETLINK)?;
+        idb.write_u16::<NativeEndian>(0)?;
+        idb.write_u32::<NativeEndian>(0)?;
+        capture.block(IDB_TYPE, &idb)?;
+        Ok(capture)
+    }
+
+    /// Records a datagram of netlink `protocol`.
+    pub(super) fn packet(&mut self, protocol: i32, outgoing: bool, data: &[u8]) -> io::Result<()> {
+        let micros = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map(|d| d.as_micros() as u64)
+            .unwrap_or(0);
+
+        // Cooked header (SLL) carrying the direction and the protocol
+        let mut packet = vec![];
+        packet.write_u16::<BigEndian>(if outgoing { PACKET_OUTGOING } else { PACKET_HOST })?;
+        packet.write_u16::<BigEndian>(ARPHRD_NETLINK)?;
+        packet.write_u16::<BigEndian>(0)?;
+        packet.write_all(&[0; 8])?;
+        packet.write_u16::<BigEndian>(protocol as u16)?;
+        packet.write_all(data)?;
+
+        let mut epb = vec![];
+        epb.write_u32::<NativeEndian>(0)?;
+        epb.write_u32::<NativeEndian>((micros >> 32) as u32)?;
+        epb.write_u32::<NativeEndian>(micros as u32)?;
+        epb.write_u32::<NativeEndian>(packet.len() as u32)?;
+        epb.write_u32::<NativeEndian>(packet.len() as u32)?;
+        epb.write_all(&packet)?;
+        while epb.len() % 4 != 0 {
+            epb.push(0);
+        }
+        self.block(EPB_TYPE, &epb)?;
+        self.out.flush()
+    }
+
+    fn block(&mut self, block_type: u32, body: &[u8]) -> io::Result<()> {
+        let len = 12 + body.len() as u32;
+        let mut bytes = vec![];
+        bytes.write_u32::<NativeEndian>(block_type)?;
+        bytes.write_u32::<NativeEndian>(len)?;
+        bytes.write_all(body)?;
+        bytes.write_u32::<NativeEndian>(len)?;
+        self.out.write_all(&bytes)
+    }
+}
+
+/// Decodes the netlink datagrams of a capture written by `Capture`, with
+/// whether each was sent (`true`) or received. Only the native byte order is
+/// supported.
+pub(super) fn read_pcapng(bytes: &[u8]) -> io::Result<Vec<(bool, Vec<u8>)>> {
+    let mut packets = vec![];
+    let mut n = 0;
+    while n < bytes.len() {
+        let mut cursor = Cursor::new(&bytes[n..]);
+        let block_type = cursor.read_u32::<NativeEndian>()?;
+        let len = cursor.read_u32::<NativeEndian>()? as usize;
+        if len < 12 || n + len > bytes.len() {
+            return Err(io::Error::new(ErrorKind::InvalidData, "bad pcapng block length"));
+        }
+        match block_type {
+            SHB_TYPE if cursor.read_u32::<NativeEndian>()? != BYTE_ORDER_MAGIC => {
+                return Err(io::Error::new(ErrorKind::InvalidData, "foreign pcapng byte order"));
+            },
+            EPB_TYPE => {
+                let _interface = cursor.read_u32::<NativeEndian>()?;
+                let _ts_high = cursor.read_u32::<NativeEndian>()?;
+                let _ts_low = cursor.read_u32::<NativeEndian>()?;
+                let captured = cursor.read_u32::<NativeEndian>()? as usize;
+                let _orig = cursor.read_u32::<NativeEndian>()?;
+                let start = n + cursor.position() as usize;
+                if captured < 16 || start + captured > n + len {
+                    return Err(io::Error::new(ErrorKind::InvalidData, "bad pcapng packet length"));
+                }
+                let packet_type = Cursor::new(&bytes[start..]).read_u16::<BigEndian>()?;
+                packets.push((packet_type == PACKET_OUTGOING, bytes[start + 16..start + captured].to_vec()));
+            },
+            _ => {},
+        }
+        n += len;
+    }
+    Ok(packets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket::{Socket, Msg, NlMsgHeader, NlAttr, Payload};
+    use Protocol;
+
+    use std::borrow::Cow;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[derive(Clone)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_capture() {
+        let out = Shared(Rc::new(RefCell::new(vec![])));
+        let mut socket = Socket::new(Protocol::Generic).unwrap();
+        socket.set_capture(Capture::new(out.clone()).unwrap());
+
+        // CTRL_CMD_GETFAMILY for nlctrl
+        let mut payload = vec![3, 2, 0, 0];
+        payload.extend(NlAttr::new(2, b"nlctrl\0").bytes());
+        let mut hdr = NlMsgHeader::user_defined(0x10);
+        hdr.data_length(payload.len() as u32);
+        socket.talk(Msg::new(hdr, Payload::Data(Cow::Borrowed(&payload)))).unwrap();
+
+        let bytes = out.0.borrow();
+        assert_eq!(&bytes[..4], &SHB_TYPE.to_ne_bytes());
+        let idb = 28;
+        assert_eq!(&bytes[idb + 8..idb + 10], &LINKTYPE_NETLINK.to_ne_bytes());
+
+        // The request, then the replies
+        let epb = idb + 20;
+        assert_eq!(&bytes[epb..epb + 4], &EPB_TYPE.to_ne_bytes());
+        let data = epb + 28;
+        assert_eq!(&bytes[data..data + 2], &PACKET_OUTGOING.to_be_bytes());
+        assert_eq!(&bytes[data + 14..data + 16], &16u16.to_be_bytes());
+        assert_eq!(&bytes[data + 16..data + 32], hdr.ack().bytes());
+        let len = u32::from_ne_bytes([bytes[epb + 4], bytes[epb + 5], bytes[epb + 6], bytes[epb + 7]]);
+        assert!(bytes.len() > epb + len as usize);
+    }
+}
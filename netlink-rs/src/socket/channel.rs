@@ -0,0 +1,147 @@
+use super::{RecvHalf, Msg, CancelToken, Cancelled, Overrun};
+
+use std::io;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+/// Receives the messages of a socket on a background thread and sends them
+/// into a channel, so that netlink events can be waited for along with
+/// other channels of the application.
+///
+/// Errors are sent into the channel too. An `Overrun` keeps the pump
+/// going; after other errors the channel is closed. Pumping also ends once
+/// the receiver is dropped and a message arrives.
+pub struct EventPump {
+    token: CancelToken,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl EventPump {
+    /// Pumps the received messages, copied out of the receive buffer.
+    /// `recv` is bound and subscribed already; its cancel token is
+    /// replaced by that of the pump.
+    pub fn messages(recv: RecvHalf) -> io::Result<(EventPump, Receiver<io::Result<Msg<'static>>>)> {
+        EventPump::events(recv, |msg| Ok(Some(msg.clone().into_owned())))
+    }
+
+    /// Pumps the events decoded from the received messages by `decode`,
+    /// e.g. with `SelinuxEvent::from_bytes`. Messages it returns `None` for
+    /// are skipped, and its errors are sent without ending the pump.
+    pub fn events<T, F>(mut recv: RecvHalf, mut decode: F) -> io::Result<(EventPump, Receiver<io::Result<T>>)>
+        where T: Send + 'static,
+              F: FnMut(&Msg) -> io::Result<Option<T>> + Send + 'static
+    {
+        let token = CancelToken::new()?;
+        recv.set_cancel_token(token.clone());
+        let (tx, rx) = mpsc::channel();
+        let thread = thread::spawn(move || pump(recv, &mut decode, &tx));
+        Ok((EventPump { token, thread: Some(thread) }, rx))
+    }
+
+    /// Stops pumping, which closes the channel, and waits for the thread
+    /// to end. Fails with the panic of `decode`, if any.
+    pub fn stop(mut self) -> thread::Result<()> {
+        self.join()
+    }
+
+    fn join(&mut self) -> thread::Result<()> {
+        self.token.cancel();
+        match self.thread.take() {
+            Some(thread) => thread.join(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for EventPump {
+    fn drop(&mut self) {
+        let _ = self.join();
+    }
+}
+
+fn pump<T, F>(mut recv: RecvHalf, decode: &mut F, tx: &Sender<io::Result<T>>)
+    where F: FnMut(&Msg) -> io::Result<Option<T>>
+{
+    loop {
+        match recv.recv() {
+            Ok((_, msgs)) => {
+                for msg in &msgs {
+                    let sent = match decode(msg) {
+                        Ok(Some(event)) => tx.send(Ok(event)),
+                        Ok(None) => continue,
+                        Err(e) => tx.send(Err(e)),
+                    };
+                    if sent.is_err() {
+                        return;
+                    }
+                }
+            },
+            Err(ref e) if Cancelled::matches(e) => return,
+            Err(e) => {
+                let overrun = Overrun::matches(&e);
+                if tx.send(Err(e)).is_err() || !overrun {
+                    return;
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket::{Socket, NetlinkAddr, NlMsgHeader, Payload};
+    use Protocol;
+
+    use std::borrow::Cow;
+    use std::time::Duration;
+
+    fn send_user(seq: u32, data: &[u8]) {
+        let send = Socket::new(Protocol::Usersock).unwrap();
+        let mut hdr = NlMsgHeader::user_defined(0x20);
+        hdr.data_length(data.len() as u32).seq(seq);
+        // Delivered to the group before the unicast to port 0 is refused
+        let _ = send.send(Msg::new(hdr, Payload::Data(Cow::Borrowed(data))), &NetlinkAddr::new(0, 1 << 8));
+    }
+
+    fn subscribed() -> RecvHalf {
+        let socket = Socket::new(Protocol::Usersock).unwrap();
+        socket.bind(NetlinkAddr::new(0, 0)).unwrap();
+        socket.add_membership(9).unwrap();
+        socket.split().1
+    }
+
+    #[test]
+    fn test_pump_messages() {
+        let (pump, rx) = EventPump::messages(subscribed()).unwrap();
+        send_user(3, &[1, 2, 3, 4]);
+
+        let msg = rx.recv_timeout(Duration::from_secs(5)).unwrap().unwrap();
+        assert_eq!(msg.header().sequence(), 3);
+        assert_eq!(*msg.payload(), Payload::Data(Cow::Borrowed(&[1, 2, 3, 4])));
+        pump.stop().unwrap();
+        assert!(rx.recv().is_err());
+    }
+
+    #[test]
+    fn test_pump_events() {
+        let (pump, rx) = EventPump::events(subscribed(), |msg| {
+            match *msg.payload() {
+                Payload::Data(ref d) if d[0] == 0 => Ok(None),
+                Payload::Data(ref d) if d[0] == 1 => Ok(Some(msg.header().sequence())),
+                _ => Err(io::Error::new(io::ErrorKind::InvalidData, "unknown event")),
+            }
+        }).unwrap();
+        send_user(1, &[0, 0, 0, 0]);
+        send_user(2, &[1, 0, 0, 0]);
+        send_user(3, &[2, 0, 0, 0]);
+        send_user(4, &[1, 0, 0, 0]);
+
+        let timeout = Duration::from_secs(5);
+        assert_eq!(rx.recv_timeout(timeout).unwrap().unwrap(), 2);
+        assert!(rx.recv_timeout(timeout).unwrap().is_err());
+        assert_eq!(rx.recv_timeout(timeout).unwrap().unwrap(), 4);
+        drop(pump);
+        assert!(rx.recv().is_err());
+    }
+}
@@ -0,0 +1,221 @@
+use super::{Socket, Msg, NetlinkAddr, CancelToken, Cancelled, Overrun};
+
+use std::io;
+use std::sync::mpsc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// How long the dispatcher waits before it opens a socket again after
+/// failing to
+const REOPEN_DELAY: Duration = Duration::from_millis(100);
+
+type Callback = Box<dyn FnMut(&Msg) + Send>;
+type ErrorCallback = Box<dyn FnMut(&io::Error) + Send>;
+
+enum Filter {
+    Group(u32),
+    Type(u16),
+}
+
+impl Filter {
+    fn matches(&self, addr: &NetlinkAddr, msg: &Msg) -> bool {
+        match *self {
+            Filter::Group(g) => (1..=32).contains(&g) && addr.groups() & (1 << (g - 1)) != 0,
+            Filter::Type(t) => u16::from(msg.header().msg_type()) == t,
+        }
+    }
+}
+
+/// Receives the messages of a socket on a background thread and hands them
+/// to the callbacks registered for their multicast group or message type.
+///
+/// After an error the socket is opened and subscribed again, and the error
+/// callback is told; an `Overrun` keeps the socket but means that events
+/// were lost, so users of a cache dump it again from there.
+///
+/// Callbacks per group match the group the kernel reports with each
+/// message, which it only does for groups 1 to 32; messages of higher
+/// groups reach the callbacks per type only.
+pub struct Dispatcher {
+    protocol: i32,
+    groups: Vec<u32>,
+    handlers: Vec<(Filter, Callback)>,
+    on_error: Option<ErrorCallback>,
+}
+
+impl Dispatcher {
+    pub fn new<P: Into<i32>>(protocol: P) -> Dispatcher {
+        Dispatcher {
+            protocol: protocol.into(),
+            groups: vec![],
+            handlers: vec![],
+            on_error: None,
+        }
+    }
+
+    /// Subscribes to multicast group `group`.
+    pub fn subscribe(&mut self, group: u32) -> &mut Dispatcher {
+        if !self.groups.contains(&group) {
+            self.groups.push(group);
+        }
+        self
+    }
+
+    /// Subscribes to `group` and calls `f` with its messages.
+    pub fn on_group<F: FnMut(&Msg) + Send + 'static>(&mut self, group: u32, f: F) -> &mut Dispatcher {
+        self.subscribe(group);
+        self.handlers.push((Filter::Group(group), Box::new(f)));
+        self
+    }
+
+    /// Calls `f` with the received messages of type `msg_type`, of any
+    /// group subscribed to.
+    pub fn on_type<F: FnMut(&Msg) + Send + 'static>(&mut self, msg_type: u16, f: F) -> &mut Dispatcher {
+        self.handlers.push((Filter::Type(msg_type), Box::new(f)));
+        self
+    }
+
+    /// Calls `f` with the errors met while receiving or opening the socket
+    /// again.
+    pub fn on_error<F: FnMut(&io::Error) + Send + 'static>(&mut self, f: F) -> &mut Dispatcher {
+        self.on_error = Some(Box::new(f));
+        self
+    }
+
+    /// Opens the socket, subscribes it and starts dispatching on a new
+    /// thread. Fails if the socket cannot be opened or subscribed.
+    pub fn spawn(self) -> io::Result<DispatcherHandle> {
+        let token = CancelToken::new()?;
+        let thread_token = token.clone();
+        let (tx, rx) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            match self.open(&thread_token) {
+                Ok(socket) => {
+                    let _ = tx.send(Ok(()));
+                    self.run(socket, &thread_token);
+                },
+                Err(e) => {
+                    let _ = tx.send(Err(e));
+                },
+            }
+        });
+
+        rx.recv().unwrap_or_else(|_| Err(io::Error::other("dispatcher thread panicked")))?;
+        Ok(DispatcherHandle { token, thread: Some(thread) })
+    }
+
+    fn open(&self, token: &CancelToken) -> io::Result<Socket> {
+        let mut socket = Socket::new(self.protocol)?;
+        socket.set_cancel_token(token.clone());
+        // Unbound sockets are not delivered multicast messages
+        socket.bind(NetlinkAddr::new(0, 0))?;
+        for &group in &self.groups {
+            socket.add_membership(group)?;
+        }
+        Ok(socket)
+    }
+
+    fn run(mut self, mut socket: Socket, token: &CancelToken) {
+        loop {
+            let err = match socket.recv() {
+                Ok((addr, msgs)) => {
+                    for msg in &msgs {
+                        for &mut (ref filter, ref mut f) in &mut self.handlers {
+                            if filter.matches(&addr, msg) {
+                                f(msg);
+                            }
+                        }
+                    }
+                    continue;
+                },
+                Err(ref e) if Cancelled::matches(e) => return,
+                Err(e) => e,
+            };
+            self.report(&err);
+            if Overrun::matches(&err) {
+                continue;
+            }
+
+            loop {
+                if token.is_cancelled() {
+                    return;
+                }
+                match self.open(token) {
+                    Ok(s) => {
+                        socket = s;
+                        break;
+                    },
+                    Err(e) => {
+                        self.report(&e);
+                        thread::sleep(REOPEN_DELAY);
+                    },
+                }
+            }
+        }
+    }
+
+    fn report(&mut self, err: &io::Error) {
+        if let Some(ref mut f) = self.on_error {
+            f(err);
+        }
+    }
+}
+
+/// A running `Dispatcher`. Dropping it stops the dispatcher like `stop`.
+pub struct DispatcherHandle {
+    token: CancelToken,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl DispatcherHandle {
+    /// Stops dispatching and waits for the thread to end. Fails with the
+    /// panic of a callback, if any.
+    pub fn stop(mut self) -> thread::Result<()> {
+        self.join()
+    }
+
+    fn join(&mut self) -> thread::Result<()> {
+        self.token.cancel();
+        match self.thread.take() {
+            Some(thread) => thread.join(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for DispatcherHandle {
+    fn drop(&mut self) {
+        let _ = self.join();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket::{NlMsgHeader, Payload};
+    use Protocol;
+
+    use std::borrow::Cow;
+
+    #[test]
+    fn test_dispatcher() {
+        let (tx, rx) = mpsc::channel();
+        let by_type = tx.clone();
+        let mut dispatcher = Dispatcher::new(Protocol::Usersock);
+        dispatcher.on_group(7, move |msg| tx.send(("group", msg.header().sequence())).unwrap())
+            .on_type(0x20, move |msg| by_type.send(("type", msg.header().sequence())).unwrap())
+            .on_group(8, |_| panic!("group 8 is not sent to"));
+        let handle = dispatcher.spawn().unwrap();
+
+        let send = Socket::new(Protocol::Usersock).unwrap();
+        let mut hdr = NlMsgHeader::user_defined(0x20);
+        hdr.data_length(4).seq(7);
+        // Delivered to the group before the unicast to port 0 is refused
+        let _ = send.send(Msg::new(hdr, Payload::Data(Cow::Borrowed(&[1, 2, 3, 4]))), &NetlinkAddr::new(0, 1 << 6));
+
+        let mut got = vec![rx.recv().unwrap(), rx.recv().unwrap()];
+        got.sort();
+        assert_eq!(got, [("group", 7), ("type", 7)]);
+        handle.stop().unwrap();
+    }
+}
@@ -0,0 +1,215 @@
+use super::{Msg, MsgType, NlMsgHeader, NlAttr, Payload, NLA_TYPE_MASK};
+
+use std::fmt;
+
+const FLAG_NAMES: [(u16, &str); 6] = [
+    (0x01, "REQUEST"),
+    (0x02, "MULTI"),
+    (0x04, "ACK"),
+    (0x08, "ECHO"),
+    (0x10, "DUMP_INTR"),
+    (0x20, "DUMP_FILTERED"),
+];
+
+// NLM_F_ROOT | NLM_F_MATCH
+const NLM_F_DUMP: u16 = 0x300;
+
+/// Names the messages and attributes of a protocol for `Msg::display_with`.
+pub trait MsgDecoder {
+    /// Name of a message type, e.g. "RTM_NEWLINK"
+    fn type_name(&self, msg_type: u16) -> Option<&str>;
+
+    /// Length of the fixed header preceding the attributes of `msg_type`,
+    /// or `None` if the payload is not an attribute stream
+    fn header_len(&self, msg_type: u16) -> Option<usize>;
+
+    /// Name of a top level attribute of `msg_type`
+    fn attr_name(&self, msg_type: u16, attr_type: u16) -> Option<&str>;
+}
+
+/// Renders a message like `ip monitor` or strace do, see `Msg::display_with`
+pub struct MsgDisplay<'a, 'b> {
+    msg: &'a Msg<'b>,
+    decoder: Option<&'a dyn MsgDecoder>,
+}
+
+impl<'a> Msg<'a> {
+    /// Renders the message, decoding its payload with `decoder`. Without a
+    /// decoder, as with `Display`, the payload is shown as a hex dump.
+    pub fn display_with<'b>(&'b self, decoder: &'b dyn MsgDecoder) -> MsgDisplay<'b, 'a> {
+        MsgDisplay { msg: self, decoder: Some(decoder) }
+    }
+}
+
+impl<'a> fmt::Display for Msg<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        MsgDisplay { msg: self, decoder: None }.fmt(f)
+    }
+}
+
+impl<'a, 'b> fmt::Display for MsgDisplay<'a, 'b> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let hdr = self.msg.header();
+        let t = u16::from(hdr.msg_type());
+        let name = match hdr.msg_type() {
+            MsgType::Noop => Some("NLMSG_NOOP"),
+            MsgType::Error => Some("NLMSG_ERROR"),
+            MsgType::Done => Some("NLMSG_DONE"),
+            MsgType::Overrun => Some("NLMSG_OVERRUN"),
+            _ => self.decoder.and_then(|d| d.type_name(t)),
+        };
+        match name {
+            Some(name) => write!(f, "{}", name)?,
+            None => write!(f, "type={}", t)?,
+        }
+        write!(f, " len={} flags={} seq={} pid={}",
+               hdr.msg_length(), flag_names(hdr.flags()), hdr.sequence(), hdr.port_id())?;
+
+        match *self.msg.payload() {
+            Payload::None => Ok(()),
+            Payload::Ack(_) => write!(f, " ack"),
+            Payload::Err(e, ref req) => write!(f, " error={} for {}", e, header_summary(req)),
+            Payload::Data(ref bytes) => {
+                let header_len = self.decoder.and_then(|d| d.header_len(t));
+                match header_len {
+                    Some(n) if n <= bytes.len() => {
+                        if n > 0 {
+                            writeln!(f)?;
+                            write!(f, "  header:")?;
+                            hex_dump(f, &bytes[..n], "    ")?;
+                        }
+                        match NlAttr::parse(&bytes[n..]) {
+                            Ok(attrs) => {
+                                for attr in attrs {
+                                    self.attr(f, t, &attr)?;
+                                }
+                                Ok(())
+                            },
+                            Err(_) => hex_dump(f, &bytes[n..], "  "),
+                        }
+                    },
+                    _ => hex_dump(f, bytes, "  "),
+                }
+            },
+        }
+    }
+}
+
+impl<'a, 'b> MsgDisplay<'a, 'b> {
+    fn attr(&self, f: &mut fmt::Formatter, msg_type: u16, attr: &NlAttr) -> fmt::Result {
+        let attr_type = attr.attr_type() & NLA_TYPE_MASK;
+        writeln!(f)?;
+        match self.decoder.and_then(|d| d.attr_name(msg_type, attr_type)) {
+            Some(name) => write!(f, "  {}", name)?,
+            None => write!(f, "  attr {}", attr_type)?,
+        }
+        if attr.is_nested() {
+            write!(f, " (nested)")?;
+        }
+        write!(f, " len={}:", attr.payload().len())?;
+        hex_dump(f, attr.payload(), "    ")
+    }
+}
+
+fn header_summary(hdr: &NlMsgHeader) -> String {
+    format!("type={} seq={}", u16::from(hdr.msg_type()), hdr.sequence())
+}
+
+/// NLM_F_* flags as names joined by '|'. The meaning of the bits above
+/// NLM_F_DUMP_FILTERED depends on the request, so apart from NLM_F_DUMP they
+/// are shown as a number.
+pub fn flag_names(flags: u16) -> String {
+    let mut names = vec![];
+    let mut rest = flags;
+    for &(bit, name) in FLAG_NAMES.iter() {
+        if flags & bit != 0 {
+            names.push(name.to_string());
+            rest &= !bit;
+        }
+    }
+    if rest & NLM_F_DUMP == NLM_F_DUMP {
+        names.push("DUMP".into());
+        rest &= !NLM_F_DUMP;
+    }
+    if rest != 0 {
+        names.push(format!("{:#x}", rest));
+    }
+    if names.is_empty() {
+        "0".into()
+    } else {
+        names.join("|")
+    }
+}
+
+/// Writes `bytes` as lines of 16 hex bytes followed by their ASCII,
+/// starting each line with `indent`.
+fn hex_dump(f: &mut fmt::Formatter, bytes: &[u8], indent: &str) -> fmt::Result {
+    for (i, line) in bytes.chunks(16).enumerate() {
+        writeln!(f)?;
+        write!(f, "{}{:04x}:", indent, i * 16)?;
+        for b in line {
+            write!(f, " {:02x}", b)?;
+        }
+        for _ in line.len()..16 {
+            write!(f, "   ")?;
+        }
+        write!(f, "  |")?;
+        for &b in line {
+            let c = if (0x20..0x7f).contains(&b) { b as char } else { '.' };
+            write!(f, "{}", c)?;
+        }
+        write!(f, "|")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::borrow::Cow;
+
+    struct TestDecoder;
+
+    impl MsgDecoder for TestDecoder {
+        fn type_name(&self, msg_type: u16) -> Option<&str> {
+            if msg_type == 16 { Some("RTM_NEWLINK") } else { None }
+        }
+
+        fn header_len(&self, _: u16) -> Option<usize> {
+            Some(4)
+        }
+
+        fn attr_name(&self, _: u16, attr_type: u16) -> Option<&str> {
+            if attr_type == 3 { Some("IFLA_IFNAME") } else { None }
+        }
+    }
+
+    #[test]
+    fn test_flag_names() {
+        assert_eq!(flag_names(0x305), "REQUEST|ACK|DUMP");
+        assert_eq!(flag_names(0x402), "MULTI|0x400");
+        assert_eq!(flag_names(0), "0");
+        assert_eq!(flag_names(0x22), "MULTI|DUMP_FILTERED");
+    }
+
+    #[test]
+    fn test_display() {
+        let mut payload = vec![1, 0, 0, 0];
+        payload.extend(NlAttr::new(3, b"lo\0").bytes());
+        let mut hdr = NlMsgHeader::user_defined(16);
+        hdr.data_length(payload.len() as u32).ack().seq(7);
+        let msg = Msg::new(hdr, Payload::Data(Cow::Borrowed(&payload)));
+
+        let plain = msg.to_string();
+        assert!(plain.starts_with("type=16 len=28 flags=REQUEST|ACK seq=7 pid=0\n"));
+        if cfg!(target_endian = "little") {
+            assert!(plain.contains("  0000: 01 00 00 00 07 00 03 00 6c 6f 00 00              |........lo..|"));
+        }
+        assert!(plain.contains("6c 6f 00 00              |........lo..|"));
+
+        let decoded = msg.display_with(&TestDecoder).to_string();
+        assert!(decoded.starts_with("RTM_NEWLINK len=28"));
+        assert!(decoded.contains("\n  IFLA_IFNAME len=3:\n    0000: 6c 6f 00"));
+    }
+}
@@ -0,0 +1,187 @@
+use super::{NlMsgHeader, NlAttr, nlmsg_header_length};
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+// Flags of an NLMSG_ERROR reply
+const NLM_F_CAPPED: u16 = 0x100;
+const NLM_F_ACK_TLVS: u16 = 0x200;
+
+const NLMSGERR_ATTR_MSG: u16 = 1;
+const NLMSGERR_ATTR_OFFS: u16 = 2;
+const NLMSGERR_ATTR_MISS_TYPE: u16 = 5;
+
+/// A kernel error reply with the extended ack details enabled by
+/// `Socket::enable_ext_ack`. It is the inner error of the `io::Error`
+/// returned for such replies, see `ExtAck::from_io`.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ExtAck {
+    errno: i32,
+    msg: Option<String>,
+    offset: Option<u32>,
+    miss_type: Option<u16>,
+}
+
+impl ExtAck {
+    /// Extended ack details of `err`, if the kernel reported any.
+    pub fn from_io(err: &io::Error) -> Option<&ExtAck> {
+        err.get_ref().and_then(|e| e.downcast_ref::<ExtAck>())
+    }
+
+    /// Decodes the details following the errno and the echoed request in
+    /// the NLMSG_ERROR payload `bytes`; `flags` are those of the reply.
+    fn from_bytes(errno: i32, flags: u16, bytes: &[u8]) -> io::Result<ExtAck> {
+        let mut ack = ExtAck {
+            errno,
+            msg: None,
+            offset: None,
+            miss_type: None,
+        };
+        if flags & NLM_F_ACK_TLVS == 0 {
+            return Ok(ack);
+        }
+
+        let (hdr, _) = NlMsgHeader::from_bytes(&bytes[4..])?;
+        let mut n = 4 + nlmsg_header_length();
+        if flags & NLM_F_CAPPED == 0 {
+            n = 4 + hdr.msg_length() as usize;
+        }
+        if n > bytes.len() {
+            return Ok(ack);
+        }
+
+        for attr in NlAttr::parse(&bytes[n..])? {
+            match attr.attr_type() {
+                NLMSGERR_ATTR_MSG => ack.msg = Some(attr.get_str()?.to_string()),
+                NLMSGERR_ATTR_OFFS => ack.offset = Some(attr.get_u32()?),
+                NLMSGERR_ATTR_MISS_TYPE => ack.miss_type = Some(attr.get_u32()? as u16),
+                _ => {},
+            }
+        }
+        Ok(ack)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.msg.is_none() && self.offset.is_none() && self.miss_type.is_none()
+    }
+
+    /// Positive errno
+    pub fn errno(&self) -> i32 {
+        self.errno
+    }
+
+    /// Human readable reason
+    pub fn msg(&self) -> Option<&str> {
+        self.msg.as_deref()
+    }
+
+    /// Offset of the offending attribute from the start of the request
+    pub fn offset(&self) -> Option<u32> {
+        self.offset
+    }
+
+    /// Type of a required attribute missing from the request
+    pub fn miss_type(&self) -> Option<u16> {
+        self.miss_type
+    }
+}
+
+impl fmt::Display for ExtAck {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", io::Error::from_raw_os_error(self.errno))?;
+        if let Some(ref msg) = self.msg {
+            write!(f, ": {}", msg)?;
+        }
+        if let Some(offset) = self.offset {
+            write!(f, " (at offset {})", offset)?;
+        }
+        if let Some(t) = self.miss_type {
+            write!(f, " (missing attribute {})", t)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ExtAck {}
+
+/// Builds the error for an NLMSG_ERROR reply with negative errno `err`,
+/// `flags` and payload `bytes`. Without extended ack details it carries the
+/// raw os error as usual.
+pub(super) fn kernel_error(err: i32, flags: u16, bytes: &[u8]) -> io::Error {
+    let errno = -err;
+    match ExtAck::from_bytes(errno, flags, bytes) {
+        Ok(ref ack) if ack.is_empty() => io::Error::from_raw_os_error(errno),
+        Ok(ack) => io::Error::new(io::Error::from_raw_os_error(errno).kind(), ack),
+        Err(_) => io::Error::from_raw_os_error(errno),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket::{Socket, Msg, Payload};
+    use Protocol;
+
+    use std::borrow::Cow;
+    use std::io::Write;
+
+    use byteorder::{NativeEndian, WriteBytesExt};
+
+    fn error_payload(errno: i32, request: &[u8], tlvs: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.write_i32::<NativeEndian>(-errno).unwrap();
+        bytes.write_all(request).unwrap();
+        bytes.write_all(tlvs).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_ext_ack_decode() {
+        let mut hdr = NlMsgHeader::request();
+        hdr.data_length(4);
+        let mut request = hdr.bytes().to_vec();
+        request.extend_from_slice(&[1, 2, 3, 4]);
+
+        let mut tlvs = NlAttr::new(NLMSGERR_ATTR_MSG, b"bad thing\0").bytes();
+        tlvs.extend(NlAttr::new(NLMSGERR_ATTR_OFFS, &20u32.to_ne_bytes()).bytes());
+        let bytes = error_payload(::libc::EINVAL, &request, &tlvs);
+
+        let err = kernel_error(-::libc::EINVAL, NLM_F_ACK_TLVS, &bytes);
+        let ack = ExtAck::from_io(&err).unwrap();
+        assert_eq!(ack.errno(), ::libc::EINVAL);
+        assert_eq!(ack.msg(), Some("bad thing"));
+        assert_eq!(ack.offset(), Some(20));
+        assert_eq!(ack.miss_type(), None);
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        // Capped replies only echo the request header
+        let bytes = error_payload(::libc::EINVAL, &hdr.bytes(), &tlvs);
+        let err = kernel_error(-::libc::EINVAL, NLM_F_ACK_TLVS | NLM_F_CAPPED, &bytes);
+        assert_eq!(ExtAck::from_io(&err).unwrap().msg(), Some("bad thing"));
+
+        let err = kernel_error(-::libc::EINVAL, 0, &bytes);
+        assert_eq!(err.raw_os_error(), Some(::libc::EINVAL));
+    }
+
+    #[test]
+    fn test_ext_ack_from_kernel() {
+        let mut socket = Socket::new(Protocol::Route).unwrap();
+        socket.enable_ext_ack().unwrap();
+
+        // RTM_NEWADDR for 127.0.0.2/33 on lo
+        let mut payload = vec![::libc::AF_INET as u8, 33, 0, 0];
+        payload.extend_from_slice(&1u32.to_ne_bytes());
+        payload.extend(NlAttr::new(2, &[127, 0, 0, 2]).bytes());
+        let mut hdr = NlMsgHeader::user_defined(20);
+        hdr.data_length(payload.len() as u32).create();
+
+        let err = socket.talk(Msg::new(hdr, Payload::Data(Cow::Borrowed(&payload)))).unwrap_err();
+        if err.raw_os_error() == Some(::libc::EPERM) {
+            return;
+        }
+        let ack = ExtAck::from_io(&err).unwrap();
+        assert_eq!(ack.errno(), ::libc::EINVAL);
+        assert!(ack.msg().is_some());
+    }
+}
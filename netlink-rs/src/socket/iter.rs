@@ -0,0 +1,76 @@
+use super::{Msg, MsgType, SocketStats, nlmsg_align};
+use super::stats::parse_msg;
+
+use std::cell::Cell;
+
+/// Iterator over the messages of a received datagram, decoding each on
+/// demand. It ends at NLMSG_DONE or at the first message that fails to
+/// decode, which is counted in the socket stats.
+pub struct MsgIter<'a> {
+    stats: &'a Cell<SocketStats>,
+    bytes: &'a [u8],
+}
+
+impl<'a> MsgIter<'a> {
+    pub(super) fn new(stats: &'a Cell<SocketStats>, bytes: &'a [u8]) -> MsgIter<'a> {
+        MsgIter { stats, bytes }
+    }
+}
+
+impl<'a> Iterator for MsgIter<'a> {
+    type Item = Msg<'a>;
+
+    fn next(&mut self) -> Option<Msg<'a>> {
+        if self.bytes.is_empty() {
+            return None;
+        }
+        match parse_msg(self.stats, self.bytes) {
+            Ok((msg, _)) => {
+                if let MsgType::Done = msg.header().msg_type() {
+                    self.bytes = &[];
+                    return None;
+                }
+                // Past the padding and anything the decoder skipped, such as
+                // extended ack attributes
+                let n = nlmsg_align(msg.header().msg_length() as usize);
+                self.bytes = &self.bytes[n.min(self.bytes.len())..];
+                Some(msg)
+            },
+            Err(_) => {
+                self.bytes = &[];
+                None
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket::{NlMsgHeader, Payload};
+
+    use std::borrow::Cow;
+
+    #[test]
+    fn test_msg_iter() {
+        let stats = Cell::new(SocketStats::default());
+        let mut hdr = NlMsgHeader::request();
+        hdr.data_length(4).multipart();
+        let mut bytes = vec![];
+        for _ in 0..2 {
+            bytes.extend(Msg::new(hdr, Payload::Data(Cow::Borrowed(&[1, 2, 3, 4]))).bytes().unwrap());
+        }
+        bytes.extend_from_slice(&NlMsgHeader::done().bytes());
+        bytes.extend_from_slice(&hdr.bytes());
+
+        let mut iter = MsgIter::new(&stats, &bytes);
+        assert_eq!(*iter.next().unwrap().payload(), Payload::Data(Cow::Borrowed(&[1, 2, 3, 4])));
+        assert_eq!(iter.count(), 1);
+        assert_eq!(stats.get().msgs_received(), 3);
+
+        // Stops at the truncated message
+        let iter = MsgIter::new(&stats, &bytes[..30]);
+        assert_eq!(iter.count(), 1);
+        assert_eq!(stats.get().parse_errors(), 1);
+    }
+}
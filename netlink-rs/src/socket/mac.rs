@@ -0,0 +1,139 @@
+use super::NlAttr;
+use super::parse::ParseError;
+
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// An Ethernet (EUI-48) hardware address
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Ord, PartialOrd, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct MacAddr([u8; 6]);
+
+impl MacAddr {
+    pub fn new(octets: [u8; 6]) -> MacAddr {
+        MacAddr(octets)
+    }
+
+    /// Decodes a 6 byte attribute payload.
+    pub fn from_bytes(payload: &[u8]) -> Result<MacAddr, ParseError> {
+        <[u8; 6]>::try_from(payload).map(MacAddr).map_err(|_| ParseError::PayloadLength)
+    }
+
+    pub fn octets(&self) -> [u8; 6] {
+        self.0
+    }
+
+    /// Encodes the address as attribute `attr_type`.
+    pub fn attr(&self, attr_type: u16) -> Vec<u8> {
+        NlAttr::new(attr_type, &self.0).bytes()
+    }
+
+    pub fn is_broadcast(&self) -> bool {
+        self.0 == [0xff; 6]
+    }
+
+    /// Group address, the least significant bit of the first octet set
+    pub fn is_multicast(&self) -> bool {
+        self.0[0] & 1 != 0
+    }
+
+    /// Locally administered rather than assigned by the vendor
+    pub fn is_local(&self) -> bool {
+        self.0[0] & 2 != 0
+    }
+}
+
+impl From<[u8; 6]> for MacAddr {
+    fn from(octets: [u8; 6]) -> MacAddr {
+        MacAddr(octets)
+    }
+}
+
+impl From<MacAddr> for [u8; 6] {
+    fn from(mac: MacAddr) -> [u8; 6] {
+        mac.0
+    }
+}
+
+impl AsRef<[u8]> for MacAddr {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Display for MacAddr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let o = self.0;
+        write!(f, "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}", o[0], o[1], o[2], o[3], o[4], o[5])
+    }
+}
+
+/// Error of parsing a `MacAddr` from a string
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct MacAddrParseError;
+
+impl fmt::Display for MacAddrParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid MAC address syntax")
+    }
+}
+
+impl Error for MacAddrParseError {}
+
+impl FromStr for MacAddr {
+    type Err = MacAddrParseError;
+
+    /// Parses six hexadecimal octets separated by `:` or `-`.
+    fn from_str(s: &str) -> Result<MacAddr, MacAddrParseError> {
+        let mut octets = [0; 6];
+        let mut parts = s.split([':', '-']);
+        for o in &mut octets {
+            let part = parts.next().ok_or(MacAddrParseError)?;
+            if part.is_empty() || part.len() > 2 {
+                return Err(MacAddrParseError);
+            }
+            *o = u8::from_str_radix(part, 16).map_err(|_| MacAddrParseError)?;
+        }
+        match parts.next() {
+            Some(_) => Err(MacAddrParseError),
+            None => Ok(MacAddr(octets)),
+        }
+    }
+}
+
+impl<'a> NlAttr<'a> {
+    /// Payload as a MAC address
+    pub fn get_mac(&self) -> Result<MacAddr, ParseError> {
+        MacAddr::from_bytes(self.payload())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mac_string() {
+        let mac: MacAddr = "02:6e:6C:72:73:01".parse().unwrap();
+        assert_eq!(mac.octets(), [2, 0x6e, 0x6c, 0x72, 0x73, 1]);
+        assert_eq!(mac.to_string(), "02:6e:6c:72:73:01");
+        assert_eq!("2-6e-6c-72-73-1".parse(), Ok(mac));
+        assert!(mac.is_local() && !mac.is_multicast());
+        assert!("ff:ff:ff:ff:ff:ff".parse::<MacAddr>().unwrap().is_broadcast());
+
+        for bad in &["", "02:6e:6c:72:73", "02:6e:6c:72:73:01:00", "02:6e:6c:72:73:1g", "02::6c:72:73:01:0"] {
+            assert_eq!(bad.parse::<MacAddr>(), Err(MacAddrParseError), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_mac_attr() {
+        let mac = MacAddr::new([2, 0, 0, 0, 0, 1]);
+        let bytes = mac.attr(1);
+        let (attr, _) = NlAttr::from_bytes(&bytes).unwrap();
+        assert_eq!(attr.get_mac(), Ok(mac));
+        assert_eq!(NlAttr::new(1, &[1, 2, 3]).get_mac(), Err(ParseError::PayloadLength));
+    }
+}
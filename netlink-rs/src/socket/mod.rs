@@ -1,32 +1,98 @@
 mod socket_impl;
 
+#[cfg(feature = "raw")]
+pub mod raw;
+
+pub mod parse;
+
 mod address;
 pub use self::address::*;
 
 mod msg;
 pub use self::msg::*;
 
+mod attr;
+pub use self::attr::*;
+
+mod writer;
+pub use self::writer::*;
+
+mod array;
+pub use self::array::*;
+
+mod mac;
+pub use self::mac::*;
+
+mod ext_ack;
+pub use self::ext_ack::*;
+
+mod capture;
+pub use self::capture::*;
+
+mod display;
+pub use self::display::*;
+
+mod replay;
+pub use self::replay::*;
+
+mod transport;
+pub use self::transport::*;
+
+mod iter;
+pub use self::iter::*;
+
+mod batch;
+pub use self::batch::*;
+
+mod stats;
+pub use self::stats::*;
+
+mod split;
+pub use self::split::*;
+
+mod cancel;
+pub use self::cancel::*;
+
+mod dispatch;
+pub use self::dispatch::*;
+
+mod channel;
+pub use self::channel::*;
+use self::stats::{count_sent, count_received, parse_msg};
+
 use socket::socket_impl::Socket as SocketImpl;
+use socket::socket_impl::SockAddrStorage;
 
-use std::mem::{size_of};
+use std::mem::{self, size_of};
 
-use libc::{AF_NETLINK, SOCK_RAW};
+use libc::{AF_NETLINK, SOCK_RAW, MSG_DONTWAIT, MSG_TRUNC};
 
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
 use std::convert::Into;
-use std::io::{self, Write, Cursor};
-use std::iter::{repeat};
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+use std::os::unix::io::{AsRawFd, RawFd};
 
-use byteorder::{NativeEndian, WriteBytesExt, ReadBytesExt};
+use byteorder::{NativeEndian, WriteBytesExt};
 
 // #define NLMSG_ALIGNTO   4
 const NLMSG_ALIGNTO: usize = 4;
 
+const SOL_NETLINK: i32 = 270;
+const NETLINK_ADD_MEMBERSHIP: i32 = 1;
+const NETLINK_EXT_ACK: i32 = 11;
+
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub enum Payload<'a> {
     None,
-    Data(&'a [u8]),
+    /// Borrowed from the receive buffer when decoded, see `into_owned`
+    Data(Cow<'a, [u8]>),
     Ack(NlMsgHeader),
-    Err(NlMsgHeader),
+    /// Negative errno reported by the kernel and the header of the request
+    /// that caused it
+    Err(i32, NlMsgHeader),
 }
 
 impl<'a> Payload<'a> {
@@ -37,20 +103,28 @@ impl<'a> Payload<'a> {
         if l < len {
             Err(Error::new(ErrorKind::InvalidData, "length of bytes too small"))
         } else {
-            Ok((Payload::Data(&bytes[..len]), len))
+            Ok((Payload::Data(Cow::Borrowed(&bytes[..len])), len))
         }
     }
 
     fn nlmsg_error(bytes: &'a [u8]) -> io::Result<(Payload<'a>, usize)> {
-        let mut cursor = Cursor::new(bytes);
-        let err = try!(cursor.read_u32::<NativeEndian>());
-        let n = cursor.position() as usize;
-        let (hdr, n2) = try!(NlMsgHeader::from_bytes(&bytes[n..]));
-        let num = n + n2;
+        let (err, hdr) = parse::error(bytes)?;
+        let num = 4 + nlmsg_header_length();
         if err == 0 {
             Ok((Payload::Ack(hdr), num))
         } else {
-            Ok((Payload::Err(hdr), num))
+            Ok((Payload::Err(err, hdr), num))
+        }
+    }
+
+    /// Copies borrowed data, so that the payload outlives the buffer it was
+    /// decoded from.
+    pub fn into_owned(self) -> Payload<'static> {
+        match self {
+            Payload::None => Payload::None,
+            Payload::Data(b) => Payload::Data(Cow::Owned(b.into_owned())),
+            Payload::Ack(h) => Payload::Ack(h),
+            Payload::Err(e, h) => Payload::Err(e, h),
         }
     }
 
@@ -59,19 +133,19 @@ impl<'a> Payload<'a> {
             Payload::None => {
                 Ok(vec!())
             },
-            Payload::Data(b) => {
-                Ok(b.into())
+            Payload::Data(ref b) => {
+                Ok(b.to_vec())
             },
             Payload::Ack(h) => {
                 let mut vec = vec![];
-                try!(vec.write_u32::<NativeEndian>(0));
-                try!(vec.write(h.bytes()));
+                vec.write_u32::<NativeEndian>(0)?;
+                vec.write_all(&h.bytes())?;
                 Ok(vec)
             },
-            Payload::Err(h) => {
+            Payload::Err(e, h) => {
                 let mut vec = vec![];
-                try!(vec.write_u32::<NativeEndian>(1));
-                try!(vec.write(h.bytes()));
+                vec.write_i32::<NativeEndian>(e)?;
+                vec.write_all(&h.bytes())?;
                 Ok(vec)
             },
         }
@@ -86,37 +160,41 @@ pub struct Msg<'a> {
 
 impl<'a> Msg<'a> {
     pub fn from_bytes(bytes: &'a [u8]) -> io::Result<(Msg<'a>, usize)> {
-        let (hdr, n) = try!(NlMsgHeader::from_bytes(bytes));
+        let (hdr, n) = NlMsgHeader::from_bytes(bytes)?;
         let (payload, n2) = match hdr.msg_type() {
             MsgType::Done => {
                 (Payload::None, 0)
             },
             MsgType::Error => {
-                try!(Payload::nlmsg_error(&bytes[n..]))
+                Payload::nlmsg_error(&bytes[n..])?
             },
             _ => {
                 let msg_len = hdr.msg_length() as usize - nlmsg_header_length();
-                try!(Payload::data(&bytes[n..], msg_len))
+                Payload::data(&bytes[n..], msg_len)?
             },
         };
 
         Ok((Msg{
             header: hdr,
-            payload: payload,
+            payload,
         }, n + n2))
     }
 
     pub fn new(hdr: NlMsgHeader, payload: Payload<'a>) -> Msg<'a> {
         Msg{
             header: hdr,
-            payload: payload,
+            payload,
         }
     }
 
+    /// Encodes the message, padded to the netlink alignment so that
+    /// messages can be sent back to back.
     pub fn bytes(&self) -> io::Result<Vec<u8>> {
         let mut bytes: Vec<u8> = self.header.bytes().into();
-        let mut payload = try!(self.payload.bytes());
+        let mut payload = self.payload.bytes()?;
         bytes.append(&mut payload);
+        let aligned = nlmsg_align(bytes.len());
+        bytes.resize(aligned, 0);
         Ok(bytes)
     }
 
@@ -124,6 +202,15 @@ impl<'a> Msg<'a> {
         self.header
     }
 
+    /// Copies the payload if it is borrowed, e.g. to keep the message past
+    /// the next receive on its socket.
+    pub fn into_owned(self) -> Msg<'static> {
+        Msg {
+            header: self.header,
+            payload: self.payload.into_owned(),
+        }
+    }
+
     pub fn payload(&self) -> &Payload<'a> {
         &self.payload
     }
@@ -138,72 +225,542 @@ impl<'a> Msg<'a> {
 //     hdr: NlMsgHeader,
 // }
 
+/// How often `Socket::talk` restarts an interrupted dump
+pub const DUMP_RETRIES: usize = 3;
+
+/// Error of a dump that stayed inconsistent after `DUMP_RETRIES` restarts,
+/// the inner error of the `io::Error` returned by `Socket::talk`
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct DumpInterrupted;
+
+impl fmt::Display for DumpInterrupted {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "dump interrupted by concurrent changes")
+    }
+}
+
+impl Error for DumpInterrupted {}
+
+/// Error of a receive that found the socket buffer overrun (ENOBUFS), as
+/// the kernel dropped messages it could not queue. Event monitors have lost
+/// events and need to dump the current state again.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct Overrun;
+
+impl Overrun {
+    /// Whether `err` reports an overrun
+    pub fn matches(err: &io::Error) -> bool {
+        err.get_ref().is_some_and(|e| e.is::<Overrun>())
+    }
+}
+
+impl fmt::Display for Overrun {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "socket buffer overrun, messages were dropped")
+    }
+}
+
+impl Error for Overrun {}
+
+/// Size of the receive buffer of a socket, the largest datagram the kernel
+/// fills with dump replies
+const RECV_BUF_LEN: usize = 32768;
+
 pub struct Socket {
     inner: SocketImpl,
     buf: Vec<u8>,
+    protocol: i32,
+    stats: Cell<SocketStats>,
+    capture: RefCell<Option<Capture>>,
+    cancel: Option<CancelToken>,
+    max_datagram: usize,
+    auto_done: bool,
+    ack_mode: bool,
 }
 
 impl Socket {
     pub fn new<P: Into<i32>>(protocol: P) -> io::Result<Socket> {
-        let s = try!(SocketImpl::new(AF_NETLINK, SOCK_RAW, protocol.into()));
-        let bytes = 4096;
-        let mut buf = Vec::with_capacity(bytes);
-        buf.extend(repeat(0u8).take(bytes));
+        let protocol = protocol.into();
+        let s = SocketImpl::new(AF_NETLINK, SOCK_RAW, protocol)?;
+        let buf = vec![0u8; RECV_BUF_LEN];
         Ok(Socket {
             inner: s,
-            buf: buf,
+            buf,
+            protocol,
+            stats: Cell::new(SocketStats::default()),
+            capture: RefCell::new(None),
+            cancel: None,
+            max_datagram: usize::MAX,
+            auto_done: false,
+            ack_mode: false,
         })
     }
 
+    /// Records every datagram sent and received from now on to `capture`.
+    /// A capture that fails to write is dropped.
+    pub fn set_capture(&mut self, capture: Capture) -> &mut Socket {
+        *self.capture.borrow_mut() = Some(capture);
+        self
+    }
+
+    fn capture(&self, outgoing: bool, bytes: &[u8]) {
+        let mut capture = self.capture.borrow_mut();
+        let failed = match *capture {
+            Some(ref mut c) => c.packet(self.protocol, outgoing, bytes).is_err(),
+            None => false,
+        };
+        if failed {
+            *capture = None;
+        }
+    }
+
+    /// Makes blocking receives, including those of `talk` and the reply
+    /// helpers built on it, fail with a `Cancelled` error once `token` is
+    /// cancelled. Non-blocking receives are not affected.
+    pub fn set_cancel_token(&mut self, token: CancelToken) -> &mut Socket {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Snapshot of the traffic counters
+    pub fn stats(&self) -> SocketStats {
+        self.stats.get()
+    }
+
+    /// The underlying syscall layer, e.g. for socket options or `sendmsg`
+    /// not wrapped here. Messages received through it bypass the stats
+    /// and the capture.
+    #[cfg(feature = "raw")]
+    pub fn raw(&self) -> &raw::Socket {
+        &self.inner
+    }
+
     pub fn bind(&self, addr: NetlinkAddr) -> io::Result<()> {
-        self.inner.bind(&addr.as_sockaddr())
+        self.inner.bind(&addr)
+    }
+
+    /// Whether calls interrupted by a signal are restarted instead of
+    /// failing with EINTR; they are by default.
+    pub fn set_retry_interrupted(&mut self, retry: bool) -> &mut Socket {
+        self.inner.set_retry_eintr(retry);
+        self
+    }
+
+    /// Limits the datagrams sent by `send_multi` to `len` bytes. Batches
+    /// over it are split between messages; a message longer than `len` is
+    /// sent on its own.
+    pub fn set_max_datagram(&mut self, len: usize) -> &mut Socket {
+        self.max_datagram = len;
+        self
+    }
+
+    /// Whether `send_multi` ends batches of multipart messages with the
+    /// NLMSG_DONE receivers wait for, if the caller did not. It takes the
+    /// sequence number and port id of the last message.
+    pub fn set_auto_done(&mut self, auto: bool) -> &mut Socket {
+        self.auto_done = auto;
+        self
     }
 
-    pub fn close(&self) -> io::Result<()> {
+    /// Whether `send` and `send_multi` ask for an ack to every request, and
+    /// `recv` and `try_recv` consume the acks. An error reply then fails the
+    /// receive with the kernel's errno rather than being returned. `talk`
+    /// and `talk_multi` are not affected.
+    pub fn set_ack_mode(&mut self, ack: bool) -> &mut Socket {
+        self.ack_mode = ack;
+        self
+    }
+
+    /// Closes the socket and reports the error of `close(2)`, which
+    /// dropping it ignores.
+    pub fn close(self) -> io::Result<()> {
         self.inner.close()
     }
 
-    pub fn send<'a>(&self, message: Msg<'a>, addr: &NetlinkAddr)
+    /// Splits the socket into a half that sends and a half that receives,
+    /// so that one thread can issue requests while another consumes events.
+    /// Both share the socket, which is closed when the last of them is
+    /// dropped, and start with its stats and settings; the receiving half
+    /// keeps the cancel token. A capture set with
+    /// `set_capture` is not carried over.
+    pub fn split(self) -> (SendHalf, RecvHalf) {
+        let Socket { inner, buf, stats, cancel, max_datagram, auto_done, ack_mode, .. } = self;
+        split::halves(inner, buf, stats.get(), cancel, max_datagram, auto_done, ack_mode)
+    }
+
+    pub fn send<'a>(&self, mut message: Msg<'a>, addr: &NetlinkAddr)
         -> io::Result<usize> {
-            let b = try!(message.bytes());
-            self.inner.sendto(b.as_slice(), 0, &addr.as_sockaddr())
+            if self.ack_mode && message.header.is_request() {
+                message.header.ack();
+            }
+            let b = message.bytes()?;
+            self.send_datagram(&b, 1, addr)
         }
 
-    pub fn send_multi<'a>(&self, messages: Vec<Msg<'a>>, addr: &NetlinkAddr)
+    /// Sends `messages` in one datagram, or in several if they exceed the
+    /// limit set by `set_max_datagram` or the kernel rejects the datagram as
+    /// too large (EMSGSIZE). Returns the number of bytes sent in total.
+    ///
+    /// Split batches are not atomic: the kernel sees each datagram on its
+    /// own, so nfnetlink batches must fit into one.
+    pub fn send_multi<'a>(&self, mut messages: Vec<Msg<'a>>, addr: &NetlinkAddr)
         -> io::Result<usize> {
-            let mut bytes = vec![];
-            for m in messages {
-                let mut b = try!(m.bytes());
-                bytes.append(&mut b);
+            if self.ack_mode {
+                for m in messages.iter_mut().filter(|m| m.header.is_request()) {
+                    m.header.ack();
+                }
+            }
+            self.send_all(messages, addr)
+        }
+
+    fn send_all(&self, messages: Vec<Msg>, addr: &NetlinkAddr) -> io::Result<usize> {
+        send_all(messages, self.auto_done, self.max_datagram, &|bytes, msgs| self.send_datagram(bytes, msgs, addr))
+    }
+
+    /// Sends the messages of `batch` in one datagram.
+    pub fn send_batch(&self, batch: &MsgBatch, addr: &NetlinkAddr) -> io::Result<usize> {
+        self.send_datagram(batch.bytes(), batch.count(), addr)
+    }
+
+    fn send_datagram(&self, bytes: &[u8], msgs: usize, addr: &NetlinkAddr) -> io::Result<usize> {
+        let sent = self.inner.sendto(bytes, 0, addr)?;
+        count_sent(&self.stats, msgs, sent);
+        self.capture(true, &bytes[..sent]);
+        Ok(sent)
+    }
+
+    fn recv_datagram(&mut self, flags: i32) -> io::Result<(SockAddrStorage, usize)> {
+        let mut buf = mem::take(&mut self.buf);
+        let res = self.recv_datagram_into(&mut buf, flags);
+        self.buf = buf;
+        res
+    }
+
+    fn recv_datagram_into(&self, buf: &mut [u8], flags: i32) -> io::Result<(SockAddrStorage, usize)> {
+        let (saddr, received) = recv_datagram(&self.inner, &self.stats, self.cancel.as_ref(), buf, flags)?;
+        self.capture(false, &buf[..received]);
+        Ok((saddr, received))
+    }
+
+    /// Subscribes to multicast group `group`, which unlike the groups of
+    /// `NetlinkAddr` may be above 32.
+    pub fn add_membership(&self, group: u32) -> io::Result<()> {
+        self.inner.setsockopt(SOL_NETLINK, NETLINK_ADD_MEMBERSHIP, group)
+    }
+
+    /// Asks the kernel to explain errors. Errors returned by `talk` and
+    /// `talk_multi` then carry an `ExtAck` when the kernel gave details.
+    pub fn enable_ext_ack(&self) -> io::Result<()> {
+        self.inner.setsockopt(SOL_NETLINK, NETLINK_EXT_ACK, 1u32)
+    }
+
+    /// Reads the messages of one datagram. Fails with an `Overrun` error if
+    /// messages were dropped since the last receive.
+    pub fn recv(&mut self) -> io::Result<(NetlinkAddr, Vec<Msg<'_>>)> {
+        let ack_mode = self.ack_mode;
+        let (addr, msgs) = self.recv_flags(0)?;
+        Ok((addr, consume_acks(ack_mode, msgs)?))
+    }
+
+    /// Reads one datagram without decoding it, for protocols that bend the
+    /// message conventions, such as the connector's NLMSG_DONE messages
+    /// carrying data.
+    pub fn recv_bytes(&mut self) -> io::Result<(NetlinkAddr, &[u8])> {
+        let (saddr, received) = self.recv_datagram(0)?;
+        let addr = sockaddr_to_netlinkaddr(saddr.as_sockaddr())?;
+        Ok((addr, &self.buf[..received]))
+    }
+
+    /// Like `recv`, but decodes the messages as they are iterated, so that
+    /// nothing is allocated and the caller may stop at the one it needs.
+    /// Acks are returned even in ack mode.
+    pub fn recv_iter(&mut self) -> io::Result<(NetlinkAddr, MsgIter<'_>)> {
+        self.recv_flags(0)
+    }
+
+    /// Like `recv`, but returns `None` instead of blocking if nothing is
+    /// queued.
+    pub fn try_recv(&mut self) -> io::Result<Option<(NetlinkAddr, Vec<Msg<'_>>)>> {
+        let ack_mode = self.ack_mode;
+        match self.recv_flags(MSG_DONTWAIT) {
+            Ok((addr, msgs)) => Ok(Some((addr, consume_acks(ack_mode, msgs)?))),
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Like `recv`, but returns `None` once `wake_fd`, e.g. an eventfd or the
+    /// read end of a pipe, becomes readable, so that a receive loop can be
+    /// interrupted for shutdown. The wakeup is not consumed, and takes
+    /// precedence over queued messages.
+    pub fn recv_or_wake(&mut self, wake_fd: RawFd) -> io::Result<Option<(NetlinkAddr, Vec<Msg<'_>>)>> {
+        let (_, woken) = self.inner.poll_with(wake_fd)?;
+        if woken {
+            return Ok(None);
+        }
+        self.recv().map(Some)
+    }
+
+    /// Like `recv`, but reads the datagram into `buf` rather than the
+    /// socket's own buffer and decodes its messages as they are iterated.
+    /// Fails with InvalidData if the datagram does not fit into `buf`.
+    pub fn recv_into<'a>(&'a self, buf: &'a mut [u8]) -> io::Result<(NetlinkAddr, MsgIter<'a>)> {
+        let (saddr, received) = self.recv_datagram_into(buf, 0)?;
+        let addr = sockaddr_to_netlinkaddr(saddr.as_sockaddr())?;
+        Ok((addr, MsgIter::new(&self.stats, &buf[..received])))
+    }
+
+    /// Reads and discards everything queued on the socket without blocking,
+    /// e.g. the rest of a reply after an error, and returns the number of
+    /// datagrams dropped. An overrun found on the way is discarded too.
+    pub fn drain(&mut self) -> io::Result<usize> {
+        let mut dropped = 0;
+        loop {
+            match self.recv_datagram(MSG_DONTWAIT) {
+                Ok(_) => dropped += 1,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(dropped),
+                Err(ref e) if Overrun::matches(e) => {},
+                Err(e) => return Err(e),
             }
+        }
+    }
+
+    fn recv_flags(&mut self, flags: i32) -> io::Result<(NetlinkAddr, MsgIter<'_>)> {
+        let (saddr, received) = self.recv_datagram(flags)?;
+        let buffer = &self.buf[..received];
+        let addr = sockaddr_to_netlinkaddr(saddr.as_sockaddr())?;
+        Ok((addr, MsgIter::new(&self.stats, buffer)))
+    }
 
-            self.inner.sendto(bytes.as_slice(), 0, &addr.as_sockaddr())
-        }
-
-    pub fn recv(&mut self) -> io::Result<(NetlinkAddr, Vec<Msg>)> {
-        let buffer = &mut self.buf[..];
-        let (saddr, _) = try!(self.inner.recvfrom_into(buffer, 0));
-        let addr = try!(sockaddr_to_netlinkaddr(&saddr));
-        let mut messages = vec![];
-
-        let mut n = 0;
-        while let Ok((msg, num_bytes)) = Msg::from_bytes(&buffer[n..]) {
-            n += num_bytes;
-            let t = msg.header().msg_type();
-            match t {
-                MsgType::Done => {
-                    break
-                },
-                _ => {
-                    messages.push(msg);
-                },
+    /// Sends a request to the kernel and collects the payloads of its replies.
+    ///
+    /// The request is sent with NLM_F_ACK set, and replies are read until the
+    /// kernel acknowledges it or ends a multipart dump with NLMSG_DONE. An
+    /// error reply is returned as an `io::Error` carrying the kernel's errno.
+    ///
+    /// A dump the kernel flags with NLM_F_DUMP_INTR, as the objects changed
+    /// while it was in progress, is restarted up to `DUMP_RETRIES` times
+    /// before failing with a `DumpInterrupted` error.
+    pub fn talk(&mut self, mut message: Msg) -> io::Result<Vec<Vec<u8>>> {
+        message.header.ack();
+        let mut attempts = 0;
+        loop {
+            self.send(message.clone(), &NetlinkAddr::new(0, 0))?;
+            let (replies, interrupted) = self.replies()?;
+            if !interrupted || !message.header.is_dump() {
+                return Ok(replies);
+            }
+            attempts += 1;
+            if attempts > DUMP_RETRIES {
+                return Err(io::Error::new(io::ErrorKind::Interrupted, DumpInterrupted));
             }
         }
+    }
 
-        Ok((addr, messages))
+    /// Reads the replies to a request sent by `talk`, and whether any of them
+    /// was flagged with NLM_F_DUMP_INTR.
+    fn replies(&mut self) -> io::Result<(Vec<Vec<u8>>, bool)> {
+        let mut replies = vec![];
+        let mut interrupted = false;
+        loop {
+            let (_, received) = self.recv_datagram(0)?;
+            let buffer = &self.buf[..received];
+            if collect_replies(&self.stats, buffer, &mut replies, &mut interrupted)? {
+                return Ok((replies, interrupted));
+            }
+        }
+    }
+
+    /// Sends several requests in one datagram and collects the payloads of
+    /// the replies, as needed for transactions such as nfnetlink batches.
+    ///
+    /// Replies are read until every request sent with NLM_F_ACK, which must
+    /// have distinct sequence numbers, is acknowledged. If some requests fail
+    /// the first error is returned. An error for a request without NLM_F_ACK
+    /// ends the exchange, as the kernel skips the requests that follow it.
+    pub fn talk_multi(&mut self, messages: Vec<Msg>) -> io::Result<Vec<Vec<u8>>> {
+        let mut pending: Vec<u32> = messages.iter()
+            .filter(|m| m.header.wants_ack())
+            .map(|m| m.header.sequence())
+            .collect();
+        self.send_all(messages, &NetlinkAddr::new(0, 0))?;
+
+        let mut replies = vec![];
+        let mut error = None;
+        while !pending.is_empty() {
+            let (_, received) = self.recv_datagram(0)?;
+            let buffer = &self.buf[..received];
+            collect_batch_replies(&self.stats, buffer, &mut pending, &mut replies, &mut error)?;
+        }
+
+        match error {
+            Some(e) => Err(e),
+            None => Ok(replies),
+        }
     }
 }
 
+/// Encodes `messages`, ending them with NLMSG_DONE if `auto_done` asks for
+/// it, and sends them through `send` in datagrams of up to `max_datagram`
+/// bytes. Returns the number of bytes sent in total.
+fn send_all<F>(mut messages: Vec<Msg>, auto_done: bool, max_datagram: usize, send: &F) -> io::Result<usize>
+    where F: Fn(&[u8], usize) -> io::Result<usize>
+{
+    if auto_done {
+        let last = messages.last().map(|m| m.header);
+        if let Some(last) = last {
+            let done = match last.msg_type() {
+                MsgType::Done => false,
+                _ => last.is_multipart(),
+            };
+            if done {
+                let mut hdr = NlMsgHeader::done();
+                hdr.seq(last.sequence()).pid(last.port_id());
+                messages.push(Msg::new(hdr, Payload::None));
+            }
+        }
+    }
+
+    let mut encoded = vec![];
+    for m in messages {
+        encoded.push(m.bytes()?);
+    }
+
+    let mut sent = 0;
+    let mut start = 0;
+    while start < encoded.len() {
+        let mut end = start + 1;
+        let mut len = encoded[start].len();
+        while end < encoded.len() && len + encoded[end].len() <= max_datagram {
+            len += encoded[end].len();
+            end += 1;
+        }
+        sent += send_split(&encoded[start..end], send)?;
+        start = end;
+    }
+    Ok(sent)
+}
+
+/// Sends `messages` in one datagram, halving the batch while the kernel
+/// finds it too large.
+fn send_split<F>(messages: &[Vec<u8>], send: &F) -> io::Result<usize>
+    where F: Fn(&[u8], usize) -> io::Result<usize>
+{
+    match send(&messages.concat(), messages.len()) {
+        Err(ref e) if e.raw_os_error() == Some(::libc::EMSGSIZE) && messages.len() > 1 => {
+            let (first, rest) = messages.split_at(messages.len() / 2);
+            Ok(send_split(first, send)? + send_split(rest, send)?)
+        },
+        r => r,
+    }
+}
+
+/// Reads one datagram into `buf`, reporting ENOBUFS as an `Overrun` and a
+/// datagram larger than `buf` as InvalidData rather than decoding part of
+/// it. A blocking read fails with `Cancelled` instead once `cancel` is.
+fn recv_datagram(inner: &SocketImpl, stats: &Cell<SocketStats>, cancel: Option<&CancelToken>,
+                 buf: &mut [u8], flags: i32) -> io::Result<(SockAddrStorage, usize)>
+{
+    if let Some(token) = cancel {
+        if flags & MSG_DONTWAIT == 0 && inner.poll_with(token.as_raw_fd())?.1 {
+            return Err(io::Error::other(Cancelled));
+        }
+    }
+    // With MSG_TRUNC the full length of the datagram is returned
+    let (saddr, received) = match inner.recvfrom_into(buf, flags | MSG_TRUNC) {
+        Err(ref e) if e.raw_os_error() == Some(::libc::ENOBUFS) => {
+            return Err(io::Error::other(Overrun));
+        },
+        r => r?,
+    };
+    count_received(stats, received.min(buf.len()));
+    if received > buf.len() {
+        let msg = format!("datagram of {} bytes truncated to {}", received, buf.len());
+        return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+    }
+    Ok((saddr, received))
+}
+
+/// Collects received messages, leaving out acks and failing on the first
+/// error reply if `ack_mode` is set.
+fn consume_acks(ack_mode: bool, msgs: MsgIter) -> io::Result<Vec<Msg>> {
+    if !ack_mode {
+        return Ok(msgs.collect());
+    }
+    let mut data = vec![];
+    for msg in msgs {
+        match *msg.payload() {
+            Payload::Ack(_) => {},
+            Payload::Err(e, _) => return Err(io::Error::from_raw_os_error(-e)),
+            _ => data.push(msg),
+        }
+    }
+    Ok(data)
+}
+
+/// Decodes the messages of a received datagram up to NLMSG_DONE, as
+/// returned by `Socket::recv`.
+fn parse_datagram<'a>(stats: &'a Cell<SocketStats>, buffer: &'a [u8]) -> Vec<Msg<'a>> {
+    MsgIter::new(stats, buffer).collect()
+}
+
+/// Adds the payloads of the replies in `datagram` to `replies`, as read by
+/// `Socket::talk`, and returns whether the exchange ended with an ack or
+/// NLMSG_DONE. An error reply fails it.
+fn collect_replies(stats: &Cell<SocketStats>, datagram: &[u8], replies: &mut Vec<Vec<u8>>,
+                   interrupted: &mut bool) -> io::Result<bool> {
+    let mut n = 0;
+    while n < datagram.len() {
+        let (msg, _) = parse_msg(stats, &datagram[n..])?;
+        let start = n;
+        n += nlmsg_align(msg.header().msg_length() as usize);
+        *interrupted |= msg.header().dump_interrupted();
+        match *msg.payload() {
+            Payload::Data(ref b) => replies.push(b.to_vec()),
+            Payload::Err(e, _) => return Err(error_reply(e, msg.header(), &datagram[start..])),
+            Payload::Ack(_) | Payload::None => return Ok(true),
+        }
+    }
+    Ok(false)
+}
+
+/// Adds the payloads of the replies in `datagram` to `replies`, as read by
+/// `Socket::talk_multi`, removing the acknowledged sequence numbers from
+/// `pending` and keeping the first error.
+fn collect_batch_replies(stats: &Cell<SocketStats>, datagram: &[u8], pending: &mut Vec<u32>,
+                         replies: &mut Vec<Vec<u8>>, error: &mut Option<io::Error>) -> io::Result<()> {
+    let mut n = 0;
+    while n < datagram.len() {
+        let (msg, _) = parse_msg(stats, &datagram[n..])?;
+        let start = n;
+        n += nlmsg_align(msg.header().msg_length() as usize);
+        let (e, seq) = match *msg.payload() {
+            Payload::Data(ref b) => {
+                replies.push(b.to_vec());
+                continue;
+            },
+            Payload::None => continue,
+            Payload::Ack(h) => (0, h.sequence()),
+            Payload::Err(e, h) => (e, h.sequence()),
+        };
+        if e != 0 && error.is_none() {
+            *error = Some(error_reply(e, msg.header(), &datagram[start..]));
+        }
+        match pending.iter().position(|&s| s == seq) {
+            Some(i) => { pending.remove(i); },
+            None if e != 0 => pending.clear(),
+            None => {},
+        }
+    }
+    Ok(())
+}
+
+/// Error for the NLMSG_ERROR reply `hdr` with errno `e`, starting at `bytes`.
+fn error_reply(e: i32, hdr: NlMsgHeader, bytes: &[u8]) -> io::Error {
+    let end = (hdr.msg_length() as usize).min(bytes.len());
+    kernel_error(e, hdr.flags(), &bytes[nlmsg_header_length()..end])
+}
+
 // NLMSG_ALIGN()
 //       Round the length of a netlink message up to align it properly.
 // #define NLMSG_ALIGN(len) ( ((len)+NLMSG_ALIGNTO-1) & ~(NLMSG_ALIGNTO-1) )
@@ -247,17 +804,17 @@ mod tests {
         let bytes = [0,1,2,3,4,5];
         let mut shdr = NlMsgHeader::request();
         shdr.data_length(6).seq(1).pid(102);
-        let msg = Msg::new(shdr, Payload::Data(&bytes));
+        let msg = Msg::new(shdr, Payload::Data(Cow::Borrowed(&bytes)));
 
         send.send(msg, &recv_addr).unwrap();
 
         let (ref addr, ref vec) = recv.recv().unwrap();
         assert_eq!(vec.len(), 1);
 
-        let ref msg = vec.first().unwrap();
+        let msg = vec.first().unwrap();
         assert_eq!(addr, &send_addr);
-        if let &Payload::Data(b) = msg.payload() {
-            assert_eq!(b, &bytes);
+        if let Payload::Data(ref b) = *msg.payload() {
+            assert_eq!(&b[..], &bytes);
         } else {
             panic!("msg is not Data enum");
         }
@@ -276,7 +833,7 @@ mod tests {
         let bytes = [0,1,2,3,4,5];
         let mut shdr = NlMsgHeader::request();
         shdr.data_length(6).multipart().seq(1).pid(100);
-        let msg = Msg::new(shdr, Payload::Data(&bytes));
+        let msg = Msg::new(shdr, Payload::Data(Cow::Borrowed(&bytes)));
         let msg2 = msg.clone();
 
 
@@ -289,23 +846,278 @@ mod tests {
         let (ref addr, ref vec) = recv.recv().unwrap();
         assert_eq!(vec.len(), 2);
 
-        let ref msg = vec.first().unwrap();
+        let msg = vec.first().unwrap();
         assert_eq!(addr, &send_addr);
-        if let &Payload::Data(b) = msg.payload() {
-            assert_eq!(b, &bytes);
+        if let Payload::Data(ref b) = *msg.payload() {
+            assert_eq!(&b[..], &bytes);
         } else {
             panic!("msg is not Data enum");
         }
     }
 
+    #[test]
+    fn test_recv_large_datagram() {
+        let send = Socket::new(Protocol::Usersock).unwrap();
+        let mut recv = Socket::new(Protocol::Usersock).unwrap();
+        let recv_addr = NetlinkAddr::new(113, 0);
+        recv.bind(recv_addr).unwrap();
+
+        let data = vec![7u8; 16384];
+        let mut shdr = NlMsgHeader::request();
+        shdr.data_length(data.len() as u32);
+        send.send(Msg::new(shdr, Payload::Data(Cow::Borrowed(&data))), &recv_addr).unwrap();
+        send.send(Msg::new(shdr, Payload::Data(Cow::Borrowed(&data))), &recv_addr).unwrap();
+
+        let (_, msgs) = recv.recv().unwrap();
+        assert_eq!(*msgs[0].payload(), Payload::Data(Cow::Borrowed(&data[..])));
+
+        let mut small = [0u8; 4096];
+        let err = recv.recv_into(&mut small).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_auto_done() {
+        let mut send = Socket::new(Protocol::Usersock).unwrap();
+        let mut recv = Socket::new(Protocol::Usersock).unwrap();
+        let recv_addr = NetlinkAddr::new(112, 0);
+        recv.bind(recv_addr).unwrap();
+        send.set_auto_done(true);
+
+        let mut shdr = NlMsgHeader::request();
+        shdr.data_length(4).multipart().seq(3);
+        let msg = Msg::new(shdr, Payload::Data(Cow::Borrowed(&[0, 1, 2, 3])));
+        send.send_multi(vec![msg.clone(), msg.clone()], &recv_addr).unwrap();
+        let (_, mut iter) = recv.recv_iter().unwrap();
+        assert_eq!(iter.by_ref().count(), 2);
+        assert_eq!(recv.stats().msgs_received(), 3);
+
+        // Not for single part messages, nor twice
+        let mut done = NlMsgHeader::done();
+        done.seq(3);
+        send.send_multi(vec![msg, Msg::new(done, Payload::None)], &recv_addr).unwrap();
+        send.send_multi(vec![Msg::new(NlMsgHeader::request(), Payload::None)], &recv_addr).unwrap();
+        recv.recv().unwrap();
+        recv.recv().unwrap();
+        assert_eq!(send.stats().msgs_sent(), 6);
+    }
+
+    #[test]
+    fn test_ack_mode() {
+        let mut socket = Socket::new(Protocol::Route).unwrap();
+        socket.set_ack_mode(true);
+
+        // RTM_GETLINK for lo, then a request for an unknown ifindex
+        let mut ifi = vec![0u8; 16];
+        ifi[4..8].copy_from_slice(&1i32.to_ne_bytes());
+        let mut hdr = NlMsgHeader::user_defined(18);
+        hdr.data_length(16).seq(1);
+        socket.send(Msg::new(hdr, Payload::Data(Cow::Borrowed(&ifi))), &NetlinkAddr::new(0, 0)).unwrap();
+        let mut replies = vec![];
+        while replies.is_empty() {
+            let (_, msgs) = socket.recv().unwrap();
+            replies.extend(msgs.into_iter().map(Msg::into_owned));
+        }
+        assert_eq!(replies.len(), 1);
+        assert!(socket.try_recv().unwrap().unwrap().1.is_empty());
+        assert!(socket.try_recv().unwrap().is_none());
+
+        ifi[4..8].copy_from_slice(&0x7fff_fff0i32.to_ne_bytes());
+        hdr.seq(2);
+        socket.send(Msg::new(hdr, Payload::Data(Cow::Borrowed(&ifi))), &NetlinkAddr::new(0, 0)).unwrap();
+        assert_eq!(socket.recv().unwrap_err().raw_os_error(), Some(::libc::ENODEV));
+    }
+
+    #[test]
+    fn test_drain() {
+        let send = Socket::new(Protocol::Usersock).unwrap();
+        let mut recv = Socket::new(Protocol::Usersock).unwrap();
+        let recv_addr = NetlinkAddr::new(104, 0);
+        recv.bind(recv_addr).unwrap();
+
+        let bytes = [0,1,2,3];
+        let mut shdr = NlMsgHeader::request();
+        shdr.data_length(4);
+        for _ in 0..3 {
+            send.send(Msg::new(shdr, Payload::Data(Cow::Borrowed(&bytes))), &recv_addr).unwrap();
+        }
+        assert_eq!(recv.drain().unwrap(), 3);
+        assert_eq!(recv.drain().unwrap(), 0);
+        assert!(recv.try_recv().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_recv_into() {
+        let send = Socket::new(Protocol::Usersock).unwrap();
+        let recv = Socket::new(Protocol::Usersock).unwrap();
+        let recv_addr = NetlinkAddr::new(106, 0);
+        recv.bind(recv_addr).unwrap();
+
+        let mut shdr = NlMsgHeader::request();
+        shdr.data_length(4).pid(107);
+        send.send_multi(vec![Msg::new(shdr, Payload::Data(Cow::Borrowed(&[0, 1, 2, 3]))),
+                             Msg::new(shdr, Payload::Data(Cow::Borrowed(&[4, 5, 6, 7])))], &recv_addr).unwrap();
+        send.send(Msg::new(shdr, Payload::Data(Cow::Borrowed(&[8, 9, 10, 11]))), &recv_addr).unwrap();
+
+        // Both datagrams stay borrowed at once
+        let mut first = [0u8; 64];
+        let mut second = [0u8; 64];
+        let (_, mut a) = recv.recv_into(&mut first).unwrap();
+        let (_, b) = recv.recv_into(&mut second).unwrap();
+        assert_eq!(*a.next().unwrap().payload(), Payload::Data(Cow::Borrowed(&[0, 1, 2, 3])));
+        assert_eq!(b.collect::<Vec<_>>().len(), 1);
+        assert_eq!(a.next().unwrap().header().port_id(), 107);
+        assert!(a.next().is_none());
+        assert_eq!(recv.stats().msgs_received(), 3);
+    }
+
+    #[test]
+    fn test_recv_iter() {
+        let send = Socket::new(Protocol::Usersock).unwrap();
+        let mut recv = Socket::new(Protocol::Usersock).unwrap();
+        let recv_addr = NetlinkAddr::new(108, 0);
+        recv.bind(recv_addr).unwrap();
+
+        let msgs = (0..4u32).map(|i| {
+            let mut hdr = NlMsgHeader::request();
+            hdr.seq(i);
+            Msg::new(hdr, Payload::None)
+        }).collect();
+        send.send_multi(msgs, &recv_addr).unwrap();
+
+        // Only the messages up to the match are decoded
+        let (_, mut iter) = recv.recv_iter().unwrap();
+        assert!(iter.any(|m| m.header().sequence() == 1));
+        assert_eq!(recv.stats().msgs_received(), 2);
+    }
+
+    #[test]
+    fn test_msg_into_owned() {
+        let send = Socket::new(Protocol::Usersock).unwrap();
+        let mut recv = Socket::new(Protocol::Usersock).unwrap();
+        let recv_addr = NetlinkAddr::new(109, 0);
+        recv.bind(recv_addr).unwrap();
+
+        let mut shdr = NlMsgHeader::request();
+        shdr.data_length(4);
+        let mut kept = vec![];
+        for i in 0..2u8 {
+            send.send(Msg::new(shdr, Payload::Data(Cow::Borrowed(&[i; 4]))), &recv_addr).unwrap();
+            let (_, msgs) = recv.recv().unwrap();
+            kept.extend(msgs.into_iter().map(Msg::into_owned));
+        }
+        assert_eq!(*kept[0].payload(), Payload::Data(Cow::Owned(vec![0; 4])));
+        assert_eq!(*kept[1].payload(), Payload::Data(Cow::Borrowed(&[1; 4])));
+    }
+
+    #[test]
+    fn test_send_split() {
+        let mut send = Socket::new(Protocol::Usersock).unwrap();
+        let mut recv = Socket::new(Protocol::Usersock).unwrap();
+        let recv_addr = NetlinkAddr::new(110, 0);
+        recv.bind(recv_addr).unwrap();
+        let batch = |n: usize, data: &'static [u8]| -> Vec<Msg<'static>> {
+            let mut hdr = NlMsgHeader::request();
+            hdr.data_length(data.len() as u32);
+            (0..n).map(|_| Msg::new(hdr, Payload::Data(Cow::Borrowed(data)))).collect()
+        };
+
+        // Three 116 byte messages under a 250 byte limit
+        send.set_max_datagram(250);
+        assert_eq!(send.send_multi(batch(3, &[0; 100]), &recv_addr).unwrap(), 348);
+        assert_eq!(recv.recv().unwrap().1.len(), 2);
+        assert_eq!(recv.recv().unwrap().1.len(), 1);
+
+        // Over the send buffer, which the kernel rejects with EMSGSIZE
+        send.set_max_datagram(usize::MAX);
+        send.inner.setsockopt(::libc::SOL_SOCKET, ::libc::SO_SNDBUF, 2048i32).unwrap();
+        let sent = send.send_multi(batch(12, &[1; 1000]), &recv_addr).unwrap();
+        assert_eq!(sent, 12 * 1016);
+        let mut received = 0;
+        while received < 12 {
+            let (_, msgs) = recv.recv().unwrap();
+            assert!(msgs.len() < 12);
+            received += msgs.len();
+        }
+        assert_eq!(send.stats().msgs_sent(), 15);
+    }
+
+    #[test]
+    fn test_send_batch() {
+        let send = Socket::new(Protocol::Usersock).unwrap();
+        let mut recv = Socket::new(Protocol::Usersock).unwrap();
+        let recv_addr = NetlinkAddr::new(111, 0);
+        recv.bind(recv_addr).unwrap();
+
+        let mut batch = MsgBatch::new();
+        for round in 0..2u8 {
+            batch.clear();
+            for i in 0..3u8 {
+                let mut hdr = NlMsgHeader::request();
+                hdr.data_length(1).seq(i as u32);
+                batch.push(&Msg::new(hdr, Payload::Data(Cow::Borrowed(&[round])))).unwrap();
+            }
+            assert_eq!(send.send_batch(&batch, &recv_addr).unwrap(), batch.len());
+            let (_, msgs) = recv.recv().unwrap();
+            assert_eq!(msgs.len(), 3);
+            assert_eq!(msgs[2].header().sequence(), 2);
+            assert_eq!(*msgs[2].payload(), Payload::Data(Cow::Borrowed(&[round])));
+        }
+        assert_eq!(send.stats().msgs_sent(), 6);
+    }
+
+    #[test]
+    #[cfg(feature = "raw")]
+    fn test_raw() {
+        let mut socket = Socket::new(Protocol::Usersock).unwrap();
+        socket.bind(NetlinkAddr::new(105, 0)).unwrap();
+        let sa = socket.raw().getsockname().unwrap();
+        assert_eq!(sockaddr_to_netlinkaddr(sa.as_sockaddr()).unwrap().pid(), 105);
+
+        // Sent through the raw layer, received as messages
+        let mut hdr = NlMsgHeader::request();
+        hdr.data_length(4);
+        let sent = socket.raw().sendmsg(&hdr.bytes(), &[1, 2, 3, 4], 0, &NetlinkAddr::new(105, 0)).unwrap();
+        assert_eq!(sent, 20);
+        let (_, msgs) = socket.recv().unwrap();
+        assert_eq!(*msgs[0].payload(), Payload::Data(Cow::Borrowed(&[1, 2, 3, 4])));
+    }
+
+    #[test]
+    fn test_recv_overrun() {
+        let send = Socket::new(Protocol::Usersock).unwrap();
+        let mut recv = Socket::new(Protocol::Usersock).unwrap();
+        recv.inner.setsockopt(::libc::SOL_SOCKET, ::libc::SO_RCVBUF, 1024i32).unwrap();
+        recv.bind(NetlinkAddr::new(103, 1)).unwrap();
+
+        let bytes = [0; 256];
+        let mut shdr = NlMsgHeader::request();
+        shdr.data_length(256);
+        for _ in 0..64 {
+            match send.send(Msg::new(shdr, Payload::Data(Cow::Borrowed(&bytes))), &NetlinkAddr::new(0, 1)) {
+                Err(ref e) if e.raw_os_error() == Some(::libc::EPERM) => return,
+                // Broadcast, only the unicast to the absent kernel socket fails
+                Err(ref e) if e.raw_os_error() == Some(::libc::ECONNREFUSED) => {},
+                r => { r.unwrap(); },
+            }
+        }
+
+        let err = loop {
+            if let Err(e) = recv.recv() {
+                break e;
+            }
+        };
+        assert!(Overrun::matches(&err));
+    }
+
     #[test]
     fn test_payload_decode() {
         let bytes = [0,1,2,3,4,5];
         let (payload, n) = Payload::data(&bytes, bytes.len()).unwrap();
         assert_eq!(n, bytes.len());
 
-        if let Payload::Data(b) = payload {
-            assert_eq!(b, &bytes);
+        if let Payload::Data(ref b) = payload {
+            assert_eq!(&b[..], &bytes);
         } else {
             panic!("payload is not Data enum");
         }
@@ -316,17 +1128,15 @@ mod tests {
         let mut bytes = vec![];
         bytes.write_u32::<NativeEndian>(1).unwrap();
 
-        // Little endian only right now
-        let expected = [20, 0, 0, 0, 0, 0, 1, 3, 1, 0, 0, 0, 9, 0, 0, 0];
         let mut hdr = NlMsgHeader::request();
         hdr.data_length(4).pid(9).seq(1).dump();
 
-        bytes.write(&expected).unwrap();
+        bytes.write_all(&hdr.bytes()).unwrap();
 
         let (p, n) = Payload::nlmsg_error(&bytes).unwrap();
 
         assert_eq!(n, bytes.len());
-        if let Payload::Err(h) = p {
+        if let Payload::Err(_, h) = p {
             assert_eq!(h, hdr);
         } else {
             panic!("payload is not Err enum");
@@ -341,7 +1151,7 @@ mod tests {
         let mut hdr = NlMsgHeader::request();
         hdr.data_length(4).pid(9).seq(1).dump();
 
-        bytes.write(&hdr.bytes()).unwrap();
+        bytes.write_all(&hdr.bytes()).unwrap();
 
         let (p, n) = Payload::nlmsg_error(&bytes).unwrap();
 
@@ -355,7 +1165,6 @@ mod tests {
 
     #[test]
     fn test_msg_decode() {
-        // Little endian only right now
         let mut hdr = NlMsgHeader::request();
         hdr.data_length(4).pid(9).seq(1).dump();
         let hdr_bytes = hdr.bytes();
@@ -363,17 +1172,17 @@ mod tests {
         let data = [0,1,2,3];
 
         let mut bytes = vec![];
-        bytes.write(&hdr_bytes).unwrap();
-        bytes.write(&data).unwrap();
+        bytes.write_all(&hdr_bytes).unwrap();
+        bytes.write_all(&data).unwrap();
         // Random data
-        bytes.write(&[1,1,1,1,1,1,1]).unwrap();
+        bytes.write_all(&[1,1,1,1,1,1,1]).unwrap();
 
         let (msg, n) = Msg::from_bytes(&bytes).unwrap();
         assert_eq!(n, hdr_bytes.len() + data.len());
         assert_eq!(hdr, msg.header());
 
-        if let &Payload::Data(b) = msg.payload() {
-            assert_eq!(b, &data);
+        if let Payload::Data(ref b) = *msg.payload() {
+            assert_eq!(&b[..], &data);
         } else {
             panic!("msg is not Data enum");
         }
@@ -386,18 +1195,18 @@ mod tests {
         let hdr_bytes = hdr.bytes();
 
         let mut bytes = vec![];
-        bytes.write(&hdr_bytes).unwrap();
+        bytes.write_all(&hdr_bytes).unwrap();
 
         bytes.write_u32::<NativeEndian>(1).unwrap();
         let mut err_hdr = NlMsgHeader::request();
         err_hdr.data_length(4).pid(9).seq(1).dump();
-        bytes.write(&err_hdr.bytes()).unwrap();
+        bytes.write_all(&err_hdr.bytes()).unwrap();
 
         let (msg, n) = Msg::from_bytes(&bytes).unwrap();
         assert_eq!(n, bytes.len());
         assert_eq!(hdr, msg.header());
 
-        if let &Payload::Err(h) = msg.payload() {
+        if let &Payload::Err(_, h) = msg.payload() {
             assert_eq!(h, err_hdr);
         } else {
             panic!("msg is not Err enum");
@@ -1,9 +1,5 @@
-use super::{nlmsg_length, nlmsg_header_length};
-use std::mem::{size_of};
-use std::slice::{from_raw_parts};
-use std::io::{self, ErrorKind, Cursor};
-
-use byteorder::{NativeEndian, ReadBytesExt};
+use super::{nlmsg_length, nlmsg_header_length, parse};
+use std::io;
 
 #[derive(Clone, Copy)]
 pub enum MsgType {
@@ -23,10 +19,10 @@ pub enum MsgType {
     UserDefined(u16),
 }
 
-impl Into<u16> for MsgType {
-    fn into(self) -> u16 {
+impl From<MsgType> for u16 {
+    fn from(t: MsgType) -> u16 {
         use self::MsgType::*;
-        match self {
+        match t {
             Request => 0,
             Noop => 1,
             Error => 2,
@@ -53,6 +49,13 @@ impl From<u16> for MsgType {
     }
 }
 
+/// Delete only the object, not its children
+pub const NLM_F_NONREC: u16 = 0x100;
+/// Delete every object matching the request
+pub const NLM_F_BULK: u16 = 0x200;
+/// A dump that was filtered as requested
+pub const NLM_F_DUMP_FILTERED: u16 = 0x20;
+
 #[derive(Clone, Copy)]
 enum Flags {
     /// It is request message.
@@ -63,16 +66,22 @@ enum Flags {
     Ack,
     /// Echo this request
     Echo,
+    /// Dump was inconsistent due to sequence change
+    DumpIntr,
+    /// Dump was filtered as requested
+    DumpFiltered,
 }
 
-impl Into<u16> for Flags {
-    fn into(self) -> u16 {
+impl From<Flags> for u16 {
+    fn from(t: Flags) -> u16 {
         use self::Flags::*;
-        match self {
+        match t {
             Request =>  1,
             Multi   =>  2,
             Ack     =>  4,
             Echo    =>  8,
+            DumpIntr => 16,
+            DumpFiltered => NLM_F_DUMP_FILTERED,
         }
     }
 }
@@ -90,10 +99,10 @@ enum GetFlags {
     Dump,
 }
 
-impl Into<u16> for GetFlags {
-    fn into(self) -> u16 {
+impl From<GetFlags> for u16 {
+    fn from(t: GetFlags) -> u16 {
         use self::GetFlags::*;
-        match self {
+        match t {
             Root    =>  0x100,
             Match   =>  0x200,
             Atomic  =>  0x400,
@@ -102,6 +111,25 @@ impl Into<u16> for GetFlags {
     }
 }
 
+/// Modifiers to DELETE request
+#[derive(Clone, Copy)]
+enum DeleteFlags {
+    /// Do not delete recursively
+    NonRec,
+    /// Delete multiple objects
+    Bulk,
+}
+
+impl From<DeleteFlags> for u16 {
+    fn from(t: DeleteFlags) -> u16 {
+        use self::DeleteFlags::*;
+        match t {
+            NonRec => NLM_F_NONREC,
+            Bulk => NLM_F_BULK,
+        }
+    }
+}
+
 /// Modifiers to NEW request
 #[derive(Clone, Copy)]
 enum NewFlags {
@@ -115,10 +143,10 @@ enum NewFlags {
     Append,
 }
 
-impl Into<u16> for NewFlags {
-    fn into(self) -> u16 {
+impl From<NewFlags> for u16 {
+    fn from(t: NewFlags) -> u16 {
         use self::NewFlags::*;
-        match self {
+        match t {
             Replace =>  0x100,
             Excl    =>  0x200,
             Create  =>  0x400,
@@ -135,6 +163,7 @@ impl Into<u16> for NewFlags {
 // __u32 nlmsg_pid;    /* Sender port ID. */
 #[repr(C)]
 #[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct NlMsgHeader {
     msg_length: u32,
     nl_type: u16,
@@ -184,33 +213,30 @@ impl NlMsgHeader {
         }
     }
 
-    pub fn from_bytes(bytes: &[u8]) -> io::Result<(NlMsgHeader, usize)> {
-        let mut cursor = Cursor::new(bytes);
-        let len = try!(cursor.read_u32::<NativeEndian>());
-        let nl_type = try!(cursor.read_u16::<NativeEndian>());
-        let flags = try!(cursor.read_u16::<NativeEndian>());
-        let seq = try!(cursor.read_u32::<NativeEndian>());
-        let pid = try!(cursor.read_u32::<NativeEndian>());
-
-        if len < nlmsg_header_length() as u32 {
-            Err(io::Error::new(ErrorKind::InvalidInput, "length smaller than msg header size"))
-        } else {
-            Ok((NlMsgHeader{
-                msg_length: len,
-                nl_type: nl_type,
-                flags: flags,
-                seq: seq,
-                pid: pid,
-            }, cursor.position() as usize))
+    pub(super) fn from_parts(msg_length: u32, nl_type: u16, flags: u16, seq: u32, pid: u32) -> NlMsgHeader {
+        NlMsgHeader {
+            msg_length,
+            nl_type,
+            flags,
+            seq,
+            pid,
         }
     }
 
-    pub fn bytes(&self) -> &[u8] {
-        let size = size_of::<NlMsgHeader>();
-        unsafe {
-            let head = self as *const NlMsgHeader as *const u8;
-            from_raw_parts(head, size)
-        }
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<(NlMsgHeader, usize)> {
+        Ok((parse::header(bytes)?, nlmsg_header_length()))
+    }
+
+    /// Encodes the header field by field in native byte order, as the
+    /// kernel expects and as `from_bytes` decodes it.
+    pub fn bytes(&self) -> [u8; 16] {
+        let mut bytes = [0; 16];
+        bytes[0..4].copy_from_slice(&self.msg_length.to_ne_bytes());
+        bytes[4..6].copy_from_slice(&self.nl_type.to_ne_bytes());
+        bytes[6..8].copy_from_slice(&self.flags.to_ne_bytes());
+        bytes[8..12].copy_from_slice(&self.seq.to_ne_bytes());
+        bytes[12..16].copy_from_slice(&self.pid.to_ne_bytes());
+        bytes
     }
 
     pub fn msg_type(&self) -> MsgType {
@@ -221,6 +247,71 @@ impl NlMsgHeader {
         self.msg_length
     }
 
+    /// NLM_F_* flags, including bits without a method here
+    pub fn flags(&self) -> u16 {
+        self.flags
+    }
+
+    /// All flag bits as set, for protocol specific bits this crate has no
+    /// method for; the same as `flags`
+    pub fn raw_flags(&self) -> u16 {
+        self.flags()
+    }
+
+    /// Replaces all flags, e.g. for protocol specific bits
+    pub fn set_flags(&mut self, flags: u16) -> &mut NlMsgHeader {
+        self.flags = flags;
+        self
+    }
+
+    /// Adds the bits of `flags`
+    pub fn or_flags(&mut self, flags: u16) -> &mut NlMsgHeader {
+        self.flags |= flags;
+        self
+    }
+
+    /// Whether acknowledgement is requested
+    pub fn wants_ack(&self) -> bool {
+        self.flags & u16::from(Flags::Ack) != 0
+    }
+
+    /// Whether this is a request (NLM_F_REQUEST)
+    pub fn is_request(&self) -> bool {
+        self.flags & u16::from(Flags::Request) != 0
+    }
+
+    /// Whether this is part of a multipart message
+    pub fn is_multipart(&self) -> bool {
+        self.flags & u16::from(Flags::Multi) != 0
+    }
+
+    /// Whether this is a dump request
+    pub fn is_dump(&self) -> bool {
+        let dump = u16::from(GetFlags::Dump);
+        self.flags & dump == dump
+    }
+
+    /// Whether the kernel flagged this dump reply as part of an inconsistent
+    /// snapshot
+    pub fn dump_interrupted(&self) -> bool {
+        self.flags & u16::from(Flags::DumpIntr) != 0
+    }
+
+    /// Whether the kernel applied the filter of this dump request, rather
+    /// than returning every object
+    pub fn dump_filtered(&self) -> bool {
+        self.flags & u16::from(Flags::DumpFiltered) != 0
+    }
+
+    pub fn sequence(&self) -> u32 {
+        self.seq
+    }
+
+    /// Port id of the sender
+    pub fn port_id(&self) -> u32 {
+        self.pid
+    }
+
     /// Set message length
     pub fn data_length(&mut self, len: u32) -> &mut NlMsgHeader {
         self.msg_length = nlmsg_length(len as usize) as u32;
@@ -229,19 +320,19 @@ impl NlMsgHeader {
 
     /// Multipart message
     pub fn multipart(&mut self) -> &mut NlMsgHeader {
-        self.flags |= Flags::Multi.into();
+        self.flags |= u16::from(Flags::Multi);
         self
     }
 
     /// Request acknowledgement
     pub fn ack(&mut self) -> &mut NlMsgHeader {
-        self.flags |= Flags::Ack.into();
+        self.flags |= u16::from(Flags::Ack);
         self
     }
 
     /// Echo message
     pub fn echo(&mut self) -> &mut NlMsgHeader {
-        self.flags |= Flags::Echo.into();
+        self.flags |= u16::from(Flags::Echo);
         self
     }
 
@@ -259,49 +350,61 @@ impl NlMsgHeader {
 
     /// Override existing
     pub fn replace(&mut self) -> &mut NlMsgHeader {
-        self.flags |= NewFlags::Replace.into();
+        self.flags |= u16::from(NewFlags::Replace);
         self
     }
 
     /// Do not touch, if it exists
     pub fn excl(&mut self) -> &mut NlMsgHeader {
-        self.flags |= NewFlags::Excl.into();
+        self.flags |= u16::from(NewFlags::Excl);
         self
     }
 
     /// Create, if it does not exist
     pub fn create(&mut self) -> &mut NlMsgHeader {
-        self.flags |= NewFlags::Create.into();
+        self.flags |= u16::from(NewFlags::Create);
         self
     }
 
     /// Add to end of list
     pub fn append(&mut self) -> &mut NlMsgHeader {
-        self.flags |= NewFlags::Append.into();
+        self.flags |= u16::from(NewFlags::Append);
+        self
+    }
+
+    /// Do not delete recursively
+    pub fn nonrec(&mut self) -> &mut NlMsgHeader {
+        self.flags |= u16::from(DeleteFlags::NonRec);
+        self
+    }
+
+    /// Delete multiple objects
+    pub fn bulk(&mut self) -> &mut NlMsgHeader {
+        self.flags |= u16::from(DeleteFlags::Bulk);
         self
     }
 
     /// specify tree root
     pub fn root(&mut self) -> &mut NlMsgHeader {
-        self.flags |= GetFlags::Root.into();
+        self.flags |= u16::from(GetFlags::Root);
         self
     }
 
     /// return all matching
     pub fn match_provided(&mut self) -> &mut NlMsgHeader {
-        self.flags |= GetFlags::Match.into();
+        self.flags |= u16::from(GetFlags::Match);
         self
     }
 
     /// atomic GET
     pub fn atomic(&mut self) -> &mut NlMsgHeader {
-        self.flags |= GetFlags::Atomic.into();
+        self.flags |= u16::from(GetFlags::Atomic);
         self
     }
 
     /// (Root|Match)
     pub fn dump(&mut self) -> &mut NlMsgHeader {
-        self.flags |= GetFlags::Dump.into();
+        self.flags |= u16::from(GetFlags::Dump);
         self
     }
 }
@@ -327,6 +430,10 @@ http://linux.die.net/include/linux/netlink.h
 #define NLM_F_CREATE    0x400   /* Create, if it does not exist */
 #define NLM_F_APPEND    0x800   /* Add to end of list       */
 
+/* Modifiers to DELETE request */
+#define NLM_F_NONREC    0x100   /* Do not delete recursively    */
+#define NLM_F_BULK  0x200   /* Delete multiple objects  */
+
 /*
    4.4BSD ADD       NLM_F_CREATE|NLM_F_EXCL
    4.4BSD CHANGE    NLM_F_REPLACE
@@ -348,20 +455,30 @@ http://linux.die.net/include/linux/netlink.h
 mod tests {
     use super::*;
 
+    /// The header as the kernel lays it out on this host
+    fn native(len: u32, nl_type: u16, flags: u16, seq: u32, pid: u32) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&len.to_ne_bytes());
+        bytes.extend_from_slice(&nl_type.to_ne_bytes());
+        bytes.extend_from_slice(&flags.to_ne_bytes());
+        bytes.extend_from_slice(&seq.to_ne_bytes());
+        bytes.extend_from_slice(&pid.to_ne_bytes());
+        bytes
+    }
+
     #[test]
     fn test_encoding() {
-        // Little endian only right now
-        let expected = [20, 0, 0, 0, 0, 0, 1, 3, 1, 0, 0, 0, 9, 0, 0, 0];
+        let expected = native(20, 0, 0x301, 1, 9);
         let mut hdr = NlMsgHeader::request();
         let bytes = hdr.data_length(4).pid(9).seq(1).dump().bytes();
 
-        assert_eq!(bytes, expected);
+        assert_eq!(&bytes[..], &expected[..]);
     }
 
     #[test]
     fn test_decoding() {
-        // Little endian only right now
-        let bytes = [16, 0, 0, 0, 0, 0, 1, 3, 1, 0, 0, 0, 9, 0, 0, 0, 1, 1, 1];
+        let mut bytes = native(16, 0, 0x301, 1, 9);
+        bytes.extend_from_slice(&[1, 1, 1]);
         let mut h = NlMsgHeader::request();
         let expected = h.data_length(0).pid(9).seq(1).dump();
 
@@ -370,9 +487,65 @@ mod tests {
         assert_eq!(n, 16);
     }
 
+    #[test]
+    fn test_round_trip() {
+        let mut hdr = NlMsgHeader::user_defined(0x1234);
+        hdr.data_length(0x0102_0304).pid(0xa0b0_c0d0).seq(0x0506_0708).ack();
+        let (decoded, _) = NlMsgHeader::from_bytes(&hdr.bytes()).unwrap();
+        assert_eq!(decoded, hdr);
+    }
+
+    #[test]
+    #[cfg(target_endian = "little")]
+    fn test_encoding_little_endian() {
+        let expected = [20, 0, 0, 0, 0, 0, 1, 3, 1, 0, 0, 0, 9, 0, 0, 0];
+        let mut hdr = NlMsgHeader::request();
+        assert_eq!(hdr.data_length(4).pid(9).seq(1).dump().bytes(), expected);
+    }
+
+    #[test]
+    #[cfg(target_endian = "big")]
+    fn test_encoding_big_endian() {
+        let expected = [0, 0, 0, 20, 0, 0, 3, 1, 0, 0, 0, 1, 0, 0, 0, 9];
+        let mut hdr = NlMsgHeader::request();
+        assert_eq!(hdr.data_length(4).pid(9).seq(1).dump().bytes(), expected);
+    }
+
+    #[test]
+    fn test_dump_flags() {
+        // NLMSG_DONE with NLM_F_MULTI | NLM_F_DUMP_INTR
+        let bytes = native(20, 3, 18, 1, 9);
+        let (hdr, _) = NlMsgHeader::from_bytes(&bytes).unwrap();
+        assert!(hdr.dump_interrupted());
+        assert!(!hdr.is_dump());
+        assert!(NlMsgHeader::request().dump().is_dump());
+        assert!(!NlMsgHeader::request().root().is_dump());
+    }
+
+    #[test]
+    fn test_delete_flags() {
+        let mut hdr = NlMsgHeader::request();
+        assert_eq!(hdr.nonrec().flags(), 0x101);
+        assert_eq!(hdr.bulk().flags(), 0x301);
+
+        let (done, _) = NlMsgHeader::from_bytes(&native(16, 3, 0x22, 1, 0)).unwrap();
+        assert!(done.dump_filtered());
+        assert!(!NlMsgHeader::done().dump_filtered());
+    }
+
+    #[test]
+    fn test_raw_flags() {
+        let mut hdr = NlMsgHeader::request();
+        hdr.or_flags(0x1000).ack();
+        assert_eq!(hdr.flags(), 0x1005);
+        assert_eq!(hdr.raw_flags(), 0x1005);
+        assert!(hdr.wants_ack());
+        assert_eq!(hdr.set_flags(0x2).flags(), 0x2);
+        assert!(hdr.is_multipart() && !hdr.is_request());
+    }
+
     #[test]
     fn test_decoding_error() {
-        // Little endian only right now
         let bytes = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
         let res = NlMsgHeader::from_bytes(&bytes);
         assert!(res.is_err());
@@ -0,0 +1,229 @@
+//! Pure decoding of headers and attributes
+//!
+//! Everything here takes a byte slice and returns borrowed, typed results or
+//! a `ParseError`; nothing allocates or reads through a `Cursor`, which keeps
+//! the code that sees untrusted bytes small enough to audit and to fuzz. The
+//! `Msg` and `NlAttr` decoders are built on it and convert errors into
+//! `io::Error`.
+//!
+//! The fuzz targets in `fuzz/` run over it: `cargo fuzz run parse_messages`.
+
+use super::{NlMsgHeader, NlAttr};
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+const NLMSG_HDRLEN: usize = 16;
+const NLA_HDRLEN: usize = 4;
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum ParseError {
+    /// Fewer bytes than a header
+    Truncated,
+    /// Length field smaller than the header it includes
+    LengthTooSmall,
+    /// Length field larger than the bytes available
+    LengthTooLarge,
+    /// Attribute payload of the wrong size for its type
+    PayloadLength,
+    /// String attribute that is not UTF-8
+    InvalidUtf8,
+    /// String attribute with a NUL before its end
+    InteriorNul,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match *self {
+            ParseError::Truncated => "truncated header",
+            ParseError::LengthTooSmall => "length smaller than header size",
+            ParseError::LengthTooLarge => "length of bytes too small",
+            ParseError::PayloadLength => "attribute payload of wrong length",
+            ParseError::InvalidUtf8 => "attribute string not UTF-8",
+            ParseError::InteriorNul => "attribute string with interior NUL",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl Error for ParseError {}
+
+impl From<ParseError> for io::Error {
+    fn from(e: ParseError) -> io::Error {
+        let kind = match e {
+            ParseError::Truncated => io::ErrorKind::UnexpectedEof,
+            _ => io::ErrorKind::InvalidData,
+        };
+        io::Error::new(kind, e)
+    }
+}
+
+#[inline]
+fn align(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+#[inline]
+fn u16_at(bytes: &[u8], i: usize) -> u16 {
+    u16::from_ne_bytes([bytes[i], bytes[i + 1]])
+}
+
+#[inline]
+fn u32_at(bytes: &[u8], i: usize) -> u32 {
+    u32::from_ne_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]])
+}
+
+/// Decodes a message header. Its length is checked against the header size
+/// only, not against `bytes`.
+pub fn header(bytes: &[u8]) -> Result<NlMsgHeader, ParseError> {
+    if bytes.len() < NLMSG_HDRLEN {
+        return Err(ParseError::Truncated);
+    }
+    let len = u32_at(bytes, 0);
+    if (len as usize) < NLMSG_HDRLEN {
+        return Err(ParseError::LengthTooSmall);
+    }
+    Ok(NlMsgHeader::from_parts(len, u16_at(bytes, 4), u16_at(bytes, 6), u32_at(bytes, 8), u32_at(bytes, 12)))
+}
+
+/// Decodes one message, returning its header, its payload and the number of
+/// bytes consumed including alignment padding.
+pub fn message(bytes: &[u8]) -> Result<(NlMsgHeader, &[u8], usize), ParseError> {
+    let hdr = header(bytes)?;
+    let len = hdr.msg_length() as usize;
+    if len > bytes.len() {
+        return Err(ParseError::LengthTooLarge);
+    }
+    Ok((hdr, &bytes[NLMSG_HDRLEN..len], align(len).min(bytes.len())))
+}
+
+/// Decodes an NLMSG_ERROR payload into the (negative) errno and the header
+/// of the failed request.
+pub fn error(payload: &[u8]) -> Result<(i32, NlMsgHeader), ParseError> {
+    if payload.len() < 4 {
+        return Err(ParseError::Truncated);
+    }
+    Ok((u32_at(payload, 0) as i32, header(&payload[4..])?))
+}
+
+/// Decodes one attribute, returning it with the number of bytes consumed
+/// including alignment padding.
+pub fn attr(bytes: &[u8]) -> Result<(NlAttr<'_>, usize), ParseError> {
+    if bytes.len() < NLA_HDRLEN {
+        return Err(ParseError::Truncated);
+    }
+    let len = u16_at(bytes, 0) as usize;
+    if len < NLA_HDRLEN {
+        return Err(ParseError::LengthTooSmall);
+    }
+    if len > bytes.len() {
+        return Err(ParseError::LengthTooLarge);
+    }
+    Ok((NlAttr::new(u16_at(bytes, 2), &bytes[NLA_HDRLEN..len]), align(len).min(bytes.len())))
+}
+
+/// Iterator over the messages of a datagram. It ends after the first error.
+pub struct Messages<'a> {
+    bytes: &'a [u8],
+}
+
+pub fn messages(bytes: &[u8]) -> Messages<'_> {
+    Messages { bytes }
+}
+
+impl<'a> Iterator for Messages<'a> {
+    type Item = Result<(NlMsgHeader, &'a [u8]), ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.bytes.is_empty() {
+            return None;
+        }
+        match message(self.bytes) {
+            Ok((hdr, payload, n)) => {
+                self.bytes = &self.bytes[n..];
+                Some(Ok((hdr, payload)))
+            },
+            Err(e) => {
+                self.bytes = &[];
+                Some(Err(e))
+            },
+        }
+    }
+}
+
+/// Iterator over a stream of attributes. Trailing bytes too short for an
+/// attribute header are ignored, as the kernel does; it ends after the first
+/// error.
+pub struct Attrs<'a> {
+    bytes: &'a [u8],
+}
+
+pub fn attrs(bytes: &[u8]) -> Attrs<'_> {
+    Attrs { bytes }
+}
+
+impl<'a> Iterator for Attrs<'a> {
+    type Item = Result<NlAttr<'a>, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.bytes.len() < NLA_HDRLEN {
+            return None;
+        }
+        match attr(self.bytes) {
+            Ok((a, n)) => {
+                self.bytes = &self.bytes[n..];
+                Some(Ok(a))
+            },
+            Err(e) => {
+                self.bytes = &[];
+                Some(Err(e))
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages() {
+        let mut hdr = NlMsgHeader::request();
+        hdr.data_length(3).seq(4);
+        let mut bytes = hdr.bytes().to_vec();
+        bytes.extend_from_slice(&[1, 2, 3, 0]);
+        bytes.extend_from_slice(&NlMsgHeader::done().bytes());
+
+        let msgs: Vec<_> = messages(&bytes).collect();
+        assert_eq!(msgs.len(), 2);
+        assert_eq!(msgs[0], Ok((hdr, &[1u8, 2, 3][..])));
+
+        assert_eq!(message(&bytes[..10]), Err(ParseError::Truncated));
+        assert_eq!(message(&bytes[..18]), Err(ParseError::LengthTooLarge));
+        let mut zero = bytes.clone();
+        zero[..4].copy_from_slice(&4u32.to_ne_bytes());
+        assert_eq!(messages(&zero).collect::<Vec<_>>(), [Err(ParseError::LengthTooSmall)]);
+    }
+
+    fn nla(len: u16, attr_type: u16, payload: &[u8]) -> Vec<u8> {
+        let mut bytes = len.to_ne_bytes().to_vec();
+        bytes.extend_from_slice(&attr_type.to_ne_bytes());
+        bytes.extend_from_slice(payload);
+        bytes
+    }
+
+    #[test]
+    fn test_attrs() {
+        let mut bytes = nla(7, 0x8002, &[1, 2, 3, 0]);
+        bytes.extend(nla(8, 1, &[9, 0, 0, 0, 0]));
+        let parsed: Result<Vec<_>, _> = attrs(&bytes).collect();
+        let parsed = parsed.unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert!(parsed[0].is_nested());
+        assert_eq!(parsed[1].payload(), &[9, 0, 0, 0]);
+
+        assert_eq!(attr(&nla(2, 1, &[])), Err(ParseError::LengthTooSmall));
+        assert_eq!(attr(&nla(12, 1, &[1])), Err(ParseError::LengthTooLarge));
+    }
+}
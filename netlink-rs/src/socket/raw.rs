@@ -0,0 +1,13 @@
+//! The syscall layer under `Socket`
+//!
+//! `Socket` here is a file descriptor with thin wrappers around `bind`,
+//! `sendmsg`, `setsockopt`, `getsockname` and the like, for any address
+//! family; the addresses the kernel fills in come back as a
+//! `SockAddrStorage` with the length it reported. It knows nothing of
+//! netlink messages: the netlink `Socket` builds on it to frame requests,
+//! collect replies and keep its stats, and `Socket::raw` lends it out for
+//! what that does not cover.
+//!
+//! Only available with the `raw` feature.
+
+pub use super::socket_impl::{Socket, SockAddr, SockAddrStorage};
@@ -0,0 +1,193 @@
+use super::{Msg, NetlinkAddr, SocketStats, collect_replies, collect_batch_replies, parse_datagram};
+use super::capture::read_pcapng;
+
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::io::{self, ErrorKind};
+
+/// Plays back recorded datagrams in place of the kernel, so that decoders can
+/// be tested deterministically against real replies without a live kernel
+/// or privileges. Requests are recorded rather than sent.
+pub struct ReplayTransport {
+    datagrams: VecDeque<Vec<u8>>,
+    current: Vec<u8>,
+    sent: Vec<Vec<u8>>,
+    stats: Cell<SocketStats>,
+}
+
+impl ReplayTransport {
+    /// Replays `datagrams`, each as read by one receive.
+    pub fn new(datagrams: Vec<Vec<u8>>) -> ReplayTransport {
+        ReplayTransport {
+            datagrams: datagrams.into_iter().collect(),
+            current: vec![],
+            sent: vec![],
+            stats: Cell::new(SocketStats::default()),
+        }
+    }
+
+    /// Replays the received datagrams of a capture written by `Capture`.
+    pub fn from_pcapng(bytes: &[u8]) -> io::Result<ReplayTransport> {
+        let received = read_pcapng(bytes)?.into_iter()
+            .filter(|&(outgoing, _)| !outgoing)
+            .map(|(_, datagram)| datagram)
+            .collect();
+        Ok(ReplayTransport::new(received))
+    }
+
+    /// Requests, as sent by `talk`, `talk_multi` and `send`
+    pub fn sent(&self) -> &[Vec<u8>] {
+        &self.sent
+    }
+
+    /// Number of datagrams not consumed yet
+    pub fn remaining(&self) -> usize {
+        self.datagrams.len()
+    }
+
+    /// Counters of the decoded messages, as kept by `Socket::stats`
+    pub fn stats(&self) -> SocketStats {
+        self.stats.get()
+    }
+
+    fn next(&mut self) -> io::Result<Vec<u8>> {
+        self.datagrams.pop_front()
+            .ok_or_else(|| io::Error::new(ErrorKind::UnexpectedEof, "no datagram left to replay"))
+    }
+
+    /// Decodes the next datagram like `Socket::recv`.
+    pub fn recv(&mut self) -> io::Result<Vec<Msg<'_>>> {
+        self.current = self.next()?;
+        Ok(parse_datagram(&self.stats, &self.current))
+    }
+
+    /// Returns the next datagram undecoded like `Socket::recv_bytes`, as
+    /// sent by the kernel.
+    pub fn recv_bytes(&mut self) -> io::Result<(NetlinkAddr, &[u8])> {
+        self.current = self.next()?;
+        Ok((NetlinkAddr::new(0, 0), &self.current))
+    }
+
+    /// Records `message` like `Socket::send`, and returns its length.
+    pub fn send(&mut self, message: Msg) -> io::Result<usize> {
+        let bytes = message.bytes()?;
+        let sent = bytes.len();
+        self.sent.push(bytes);
+        Ok(sent)
+    }
+
+    /// Records `message` and collects the replies from the next datagrams
+    /// like `Socket::talk`.
+    pub fn talk(&mut self, mut message: Msg) -> io::Result<Vec<Vec<u8>>> {
+        message.header.ack();
+        self.sent.push(message.bytes()?);
+
+        let mut replies = vec![];
+        let mut interrupted = false;
+        loop {
+            let datagram = self.next()?;
+            if collect_replies(&self.stats, &datagram, &mut replies, &mut interrupted)? {
+                return Ok(replies);
+            }
+        }
+    }
+
+    /// Records `messages` as one request and collects the replies like
+    /// `Socket::talk_multi`.
+    pub fn talk_multi(&mut self, messages: Vec<Msg>) -> io::Result<Vec<Vec<u8>>> {
+        let mut pending: Vec<u32> = messages.iter()
+            .filter(|m| m.header.wants_ack())
+            .map(|m| m.header.sequence())
+            .collect();
+        let mut bytes = vec![];
+        for m in messages {
+            bytes.extend(m.bytes()?);
+        }
+        self.sent.push(bytes);
+
+        let mut replies = vec![];
+        let mut error = None;
+        while !pending.is_empty() {
+            let datagram = self.next()?;
+            collect_batch_replies(&self.stats, &datagram, &mut pending, &mut replies, &mut error)?;
+        }
+
+        match error {
+            Some(e) => Err(e),
+            None => Ok(replies),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket::{Socket, Capture, NlMsgHeader, NlAttr, Payload};
+    use Protocol;
+
+    use std::borrow::Cow;
+    use std::cell::RefCell;
+    use std::io::Write;
+    use std::rc::Rc;
+
+    #[derive(Clone)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn getfamily() -> (NlMsgHeader, Vec<u8>) {
+        let mut payload = vec![3, 2, 0, 0];
+        payload.extend(NlAttr::new(2, b"nlctrl\0").bytes());
+        let mut hdr = NlMsgHeader::user_defined(0x10);
+        hdr.data_length(payload.len() as u32);
+        (hdr, payload)
+    }
+
+    #[test]
+    fn test_replay_capture() {
+        let out = Shared(Rc::new(RefCell::new(vec![])));
+        let mut socket = Socket::new(Protocol::Generic).unwrap();
+        socket.set_capture(Capture::new(out.clone()).unwrap());
+        let (hdr, payload) = getfamily();
+        let live = socket.talk(Msg::new(hdr, Payload::Data(Cow::Borrowed(&payload)))).unwrap();
+
+        let mut replay = ReplayTransport::from_pcapng(&out.0.borrow()).unwrap();
+        let replayed = replay.talk(Msg::new(hdr, Payload::Data(Cow::Borrowed(&payload)))).unwrap();
+        assert_eq!(replayed, live);
+        assert_eq!(replay.remaining(), 0);
+        assert_eq!(replay.sent().len(), 1);
+        assert_eq!(replay.stats().acks(), 1);
+
+        // The reply decodes as it did from the kernel
+        let attrs = &replayed[0][4..];
+        assert!(NlAttr::parse(attrs).unwrap().iter().any(|a| a.attr_type() == 1));
+        assert_eq!(replay.talk(Msg::new(hdr, Payload::Data(Cow::Borrowed(&payload)))).unwrap_err().kind(),
+                   ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_replay_error() {
+        let (hdr, _) = getfamily();
+        let mut err = NlMsgHeader::error();
+        err.data_length(20);
+        let mut datagram = err.bytes().to_vec();
+        datagram.extend_from_slice(&(-::libc::ENOENT).to_ne_bytes());
+        datagram.extend_from_slice(&hdr.bytes());
+
+        let mut replay = ReplayTransport::new(vec![datagram.clone()]);
+        let e = replay.talk(Msg::new(hdr, Payload::None)).unwrap_err();
+        assert_eq!(e.raw_os_error(), Some(::libc::ENOENT));
+
+        let mut replay = ReplayTransport::new(vec![datagram]);
+        let msgs = replay.recv().unwrap();
+        assert_eq!(*msgs[0].payload(), Payload::Err(-::libc::ENOENT, hdr));
+    }
+}
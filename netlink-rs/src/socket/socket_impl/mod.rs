@@ -2,21 +2,21 @@
 #![allow(dead_code)]
 #![allow(unused_unsafe)]
 
-use std::iter::{repeat};
 use std::io::{Error, Result,};
 use std::mem;
 use std::ptr;
 use std::ops::Drop;
-use std::vec::{Vec,};
 
 use libc::{
-    c_void, size_t, socklen_t, sockaddr,
+    c_void, size_t, socklen_t, sa_family_t,
+    sockaddr, sockaddr_in, sockaddr_in6, sockaddr_un, sockaddr_storage,
     socket, setsockopt, bind, send, recv, recvfrom,
     connect, getsockname,
     close,
     listen, sendto, accept,
     sendmsg, msghdr, iovec,
-    shutdown,
+    poll, pollfd, POLLIN,
+    shutdown, EINTR,
 };
 
 macro_rules! _try {
@@ -29,23 +29,119 @@ macro_rules! _try {
     }};
 }
 
-fn sockaddr_len() -> socklen_t {
-    let struct_size = mem::size_of::<sockaddr>();
-    let v = struct_size as socklen_t;
-    assert_eq!(v as usize, struct_size);
-    v
+// Like `_try!`, but restarts the call if it was interrupted by a signal and
+// the socket retries interrupted calls.
+macro_rules! _retry {
+    ( $s:expr, $x:expr ) => {{
+        loop {
+            let value = unsafe { $x };
+            if value != -1 {
+                break value;
+            }
+            let err = Error::last_os_error();
+            if !($s.retry_eintr && err.raw_os_error() == Some(EINTR)) {
+                return Err(err);
+            }
+        }
+    }};
+}
+
+/// Socket address of a given family, passed to the kernel with its own
+/// length rather than that of the generic `sockaddr`.
+///
+/// # Safety
+///
+/// `as_ptr` must point to `addr_len` readable bytes laid out as a
+/// `sockaddr_*` structure.
+pub unsafe trait SockAddr {
+    fn as_ptr(&self) -> *const sockaddr;
+
+    fn addr_len(&self) -> socklen_t;
+}
+
+macro_rules! sock_addr {
+    ( $( $t:ty ),* ) => {
+        $(
+            unsafe impl SockAddr for $t {
+                fn as_ptr(&self) -> *const sockaddr {
+                    self as *const $t as *const sockaddr
+                }
+
+                fn addr_len(&self) -> socklen_t {
+                    mem::size_of::<$t>() as socklen_t
+                }
+            }
+        )*
+    };
+}
+
+sock_addr!(sockaddr, sockaddr_in, sockaddr_in6, sockaddr_un);
+
+/// Address of any family as filled in by the kernel, with the length it
+/// reported. It can be passed back, e.g. to `sendto` the peer of `recvfrom`.
+#[derive(Clone, Copy)]
+pub struct SockAddrStorage {
+    storage: sockaddr_storage,
+    len: socklen_t,
+}
+
+impl SockAddrStorage {
+    fn new() -> SockAddrStorage {
+        SockAddrStorage {
+            storage: unsafe { mem::zeroed() },
+            len: mem::size_of::<sockaddr_storage>() as socklen_t,
+        }
+    }
+
+    pub fn family(&self) -> sa_family_t {
+        self.storage.ss_family
+    }
+
+    pub fn as_storage(&self) -> &sockaddr_storage {
+        &self.storage
+    }
+
+    /// The start of the address as a generic `sockaddr`, e.g. to read its
+    /// family or a `sockaddr_nl`.
+    pub fn as_sockaddr(&self) -> &sockaddr {
+        // sockaddr_storage is larger than and aligned for any sockaddr
+        unsafe { &*(&self.storage as *const sockaddr_storage as *const sockaddr) }
+    }
+
+    fn as_mut_ptr(&mut self) -> *mut sockaddr {
+        &mut self.storage as *mut sockaddr_storage as *mut sockaddr
+    }
+}
+
+unsafe impl SockAddr for SockAddrStorage {
+    fn as_ptr(&self) -> *const sockaddr {
+        &self.storage as *const sockaddr_storage as *const sockaddr
+    }
+
+    fn addr_len(&self) -> socklen_t {
+        self.len
+    }
 }
 
+/// A socket of any family, as thin wrappers around the syscalls. Errors are
+/// the OS errors; calls interrupted by a signal are restarted by default.
 #[derive(Debug)]
 pub struct Socket {
     fd: i32,
+    retry_eintr: bool,
 }
 
 
 impl Socket {
     pub fn new(family: i32, socket_type: i32, protocol: i32) -> Result<Socket> {
         let fd = _try!(socket(family, socket_type, protocol));
-        Ok(Socket { fd: fd })
+        Ok(Socket { fd, retry_eintr: true })
+    }
+
+    /// Whether calls interrupted by a signal (EINTR) are restarted; they
+    /// are by default.
+    pub fn set_retry_eintr(&mut self, retry: bool) {
+        self.retry_eintr = retry;
     }
 
     /// Returns the underlying file descriptor.
@@ -53,48 +149,49 @@ impl Socket {
         self.fd
     }
 
-    pub fn getsockname(&self) -> Result<sockaddr> {
-        let mut sa: sockaddr = unsafe { mem::zeroed() };
-        let mut len: socklen_t = mem::size_of::<sockaddr>() as socklen_t;
-        _try!(getsockname(self.fd,
-              &mut sa as *mut sockaddr, &mut len as *mut socklen_t));
-        assert_eq!(len, mem::size_of::<sockaddr>() as socklen_t);
-
+    /// Returns the address the socket is bound to.
+    pub fn getsockname(&self) -> Result<SockAddrStorage> {
+        let mut sa = SockAddrStorage::new();
+        _try!(getsockname(self.fd, sa.as_mut_ptr(), &mut sa.len as *mut socklen_t));
         Ok(sa)
     }
 
+    /// Sets a socket option to `value`, passed with the size of `T`.
     pub fn setsockopt<T>(&self, level: i32, name: i32, value: T) -> Result<()> {
         unsafe {
             let value = &value as *const T as *const c_void;
             _try!(setsockopt(
-                    self.fd, level, name, value, sockaddr_len()));
+                    self.fd, level, name, value, mem::size_of::<T>() as socklen_t));
         }
         Ok(())
     }
 
     /// Binds socket to an address
-    pub fn bind(&self, address: &sockaddr) -> Result<()> {
-        _try!(bind(self.fd, address, sockaddr_len()));
+    pub fn bind<A: SockAddr>(&self, address: &A) -> Result<()> {
+        _retry!(self, bind(self.fd, address.as_ptr(), address.addr_len()));
         Ok(())
     }
 
-    pub fn sendto(&self, buffer: &[u8], flags: i32, sa: &sockaddr)
+    /// Sends a datagram to `sa` and returns the number of bytes sent.
+    pub fn sendto<A: SockAddr>(&self, buffer: &[u8], flags: i32, sa: &A)
             -> Result<usize> {
-        let sent = _try!(
+        let sent = _retry!(self,
             sendto(self.fd, buffer.as_ptr() as *const c_void,
-            buffer.len() as size_t, flags, sa as *const sockaddr,
-            sockaddr_len()));
+            buffer.len() as size_t, flags, sa.as_ptr(), sa.addr_len()));
         Ok(sent as usize)
     }
 
+    /// Sends data on a connected socket.
     pub fn send(&self, buffer: &[u8], flags: i32)
             -> Result<usize> {
-        let sent = _try!(
+        let sent = _retry!(self,
             send(self.fd, buffer.as_ptr() as *const c_void, buffer.len() as size_t, flags));
         Ok(sent as usize)
     }
 
-    pub fn sendmsg(&self, msg: &[u8], data: &[u8], flags: i32, sa: &sockaddr)
+    /// Sends `msg` followed by `data` as one datagram to `sa`, without
+    /// copying them together.
+    pub fn sendmsg<A: SockAddr>(&self, msg: &[u8], data: &[u8], flags: i32, sa: &A)
             -> Result<usize> {
         let msg = unsafe {
             let msg_iovec = iovec {
@@ -107,9 +204,9 @@ impl Socket {
             };
             let mut iovecs = [msg_iovec, data_iovec];
             msghdr{
-                msg_name: sa as *const sockaddr as *mut c_void,
-                msg_namelen: sockaddr_len(),
-                msg_iov: iovecs.as_mut_ptr() as *mut iovec,
+                msg_name: sa.as_ptr() as *mut c_void,
+                msg_namelen: sa.addr_len(),
+                msg_iov: iovecs.as_mut_ptr(),
                 msg_iovlen: 2,
                 msg_control: ptr::null_mut(),
                 msg_controllen: 0,
@@ -117,18 +214,15 @@ impl Socket {
             }
         };
 
-        let sent = _try!(sendmsg(self.fd, &msg as *const msghdr, flags));
+        let sent = _retry!(self, sendmsg(self.fd, &msg as *const msghdr, flags));
         Ok(sent as usize)
     }
 
     /// Receives data from a remote socket and returns it with the address of the socket.
-    pub fn recvfrom(&self, bytes: usize, flags: i32) -> Result<(sockaddr, Box<[u8]>)> {
-        let mut a = Vec::with_capacity(bytes);
+    pub fn recvfrom(&self, bytes: usize, flags: i32) -> Result<(SockAddrStorage, Box<[u8]>)> {
+        let mut a = vec![0u8; bytes];
 
-        // This is needed to get some actual elements in the vector, not just a capacity
-        a.extend(repeat(0u8).take(bytes));
-
-        let (socket_addr, received) = try!(self.recvfrom_into(&mut a[..], flags));
+        let (socket_addr, received) = self.recvfrom_into(&mut a[..], flags)?;
 
         a.truncate(received);
         Ok((socket_addr, a.into_boxed_slice()))
@@ -136,26 +230,19 @@ impl Socket {
 
     /// Similar to `recvfrom` but receives to predefined buffer and returns the number
     /// of bytes read.
-    pub fn recvfrom_into(&self, buffer: &mut [u8], flags: i32) -> Result<(sockaddr, usize)> {
-        let mut sa: sockaddr = unsafe { mem::zeroed() };
-        let sockaddr_len = sockaddr_len();
-        let mut sa_len: socklen_t = sockaddr_len;
-        let received = _try!(
+    pub fn recvfrom_into(&self, buffer: &mut [u8], flags: i32) -> Result<(SockAddrStorage, usize)> {
+        let mut sa = SockAddrStorage::new();
+        let received = _retry!(self,
             recvfrom(self.fd, buffer.as_ptr() as *mut c_void, buffer.len() as size_t, flags,
-            &mut sa as *mut sockaddr, &mut sa_len as *mut socklen_t));
-        // sockaddr_nl only has 12 bytes, still fits into 16 byte sockaddr
-        assert!(sa_len <= sockaddr_len);
+            sa.as_mut_ptr(), &mut sa.len as *mut socklen_t));
         Ok((sa, received as usize))
     }
 
     /// Returns up to `bytes` bytes received from the remote socket.
     pub fn recv(&self, bytes: usize, flags: i32) -> Result<Box<[u8]>> {
-        let mut a = Vec::with_capacity(bytes);
-
-        // This is needed to get some actual elements in the vector, not just a capacity
-        a.extend(repeat(0u8).take(bytes));
+        let mut a = vec![0u8; bytes];
 
-        let received = try!(self.recv_into(&mut a[..], flags));
+        let received = self.recv_into(&mut a[..], flags)?;
 
         a.truncate(received);
         Ok(a.into_boxed_slice())
@@ -164,36 +251,51 @@ impl Socket {
     /// Similar to `recv` but receives to predefined buffer and returns the number
     /// of bytes read.
     pub fn recv_into(&self, buffer: &mut [u8], flags: i32) -> Result<usize> {
-        let received = _try!(recv(self.fd, buffer.as_ptr() as *mut c_void, buffer.len() as size_t, flags));
+        let received = _retry!(self, recv(self.fd, buffer.as_ptr() as *mut c_void, buffer.len() as size_t, flags));
         Ok(received as usize)
     }
 
-    pub fn connect(&self, address: &sockaddr) -> Result<()> {
-        _try!(connect(self.fd, address as *const sockaddr, sockaddr_len()));
+    /// Waits until the socket or the file descriptor `other` has something
+    /// to read, an error or a hangup pending, and returns which of them do.
+    pub fn poll_with(&self, other: i32) -> Result<(bool, bool)> {
+        let mut fds = [
+            pollfd { fd: self.fd, events: POLLIN, revents: 0 },
+            pollfd { fd: other, events: POLLIN, revents: 0 },
+        ];
+        _retry!(self, poll(fds.as_mut_ptr(), fds.len() as _, -1));
+        Ok((fds[0].revents != 0, fds[1].revents != 0))
+    }
+
+    /// Connects the socket to an address
+    pub fn connect<A: SockAddr>(&self, address: &A) -> Result<()> {
+        _retry!(self, connect(self.fd, address.as_ptr(), address.addr_len()));
         Ok(())
     }
 
+    /// Marks a stream socket as accepting connections.
     pub fn listen(&self, backlog: i32) -> Result<()> {
         _try!(listen(self.fd, backlog));
         Ok(())
     }
 
-    pub fn accept(&self) -> Result<(Socket, sockaddr)> {
-        let mut sa: sockaddr = unsafe { mem::zeroed() };
-        let sockaddr_len = sockaddr_len();
-        let mut sa_len: socklen_t = sockaddr_len;
-
-        let fd = _try!(
-            accept(self.fd, &mut sa as *mut sockaddr, &mut sa_len as *mut socklen_t));
-        assert_eq!(sa_len, sockaddr_len);
-        Ok((Socket { fd: fd }, sa))
+    /// Accepts a connection and returns its socket with the peer address.
+    pub fn accept(&self) -> Result<(Socket, SockAddrStorage)> {
+        let mut sa = SockAddrStorage::new();
+        let fd = _retry!(self,
+            accept(self.fd, sa.as_mut_ptr(), &mut sa.len as *mut socklen_t));
+        Ok((Socket { fd, retry_eintr: self.retry_eintr }, sa))
     }
 
-    pub fn close(&self) -> Result<()> {
-        _try!(close(self.fd));
+    /// Closes the file descriptor and reports the error of `close(2)`,
+    /// which dropping the socket ignores.
+    pub fn close(self) -> Result<()> {
+        let fd = self.fd;
+        mem::forget(self);
+        _try!(close(fd));
         Ok(())
     }
 
+    /// Shuts down part of a full duplex connection, see `shutdown(2)`.
     pub fn shutdown(&self, how: i32) -> Result<()> {
         _try!(shutdown(self.fd, how));
         Ok(())
@@ -202,7 +304,7 @@ impl Socket {
 
 impl Drop for Socket {
     fn drop(&mut self) {
-        let _ = self.close();
+        unsafe { close(self.fd) };
     }
 }
 
@@ -258,9 +360,11 @@ mod tests {
         let s = Socket::new(AF_INET, SOCK_DGRAM, 0).unwrap();
         let sa = socketaddr_to_sockaddr("127.0.0.1:0");
         s.bind(&sa).unwrap();
-         assert_eq!(s.getsockname().unwrap().sa_family, sa.sa_family);
+         let name = s.getsockname().unwrap();
+         assert_eq!(name.family(), sa.sa_family);
+         assert_eq!(name.addr_len(), sa.addr_len());
          // Skip port part since we are picking a random port.
-         assert_eq!(s.getsockname().unwrap().sa_data[2..], sa.sa_data[2..]);
+         assert_eq!(name.as_sockaddr().sa_data[2..], sa.sa_data[2..]);
     }
 
     #[test]
@@ -302,6 +406,127 @@ mod tests {
         thread.join().unwrap();
     }
 
+    extern "C" fn ignore_signal(_: i32) {}
+
+    #[test]
+    fn interrupted_recv() {
+        use libc::{sigaction, sigemptyset, pthread_self, pthread_kill, SIGUSR1};
+        use std::sync::mpsc;
+        use std::time::Duration;
+
+        // Without SA_RESTART, so that blocking calls fail with EINTR
+        unsafe {
+            let mut action: sigaction = mem::zeroed();
+            action.sa_sigaction = ignore_signal as extern "C" fn(i32) as usize;
+            sigemptyset(&mut action.sa_mask);
+            sigaction(SIGUSR1, &action, ptr::null_mut());
+        }
+
+        for &retry in &[true, false] {
+            let mut receiver = Socket::new(AF_INET, SOCK_DGRAM, 0).unwrap();
+            receiver.set_retry_eintr(retry);
+            receiver.bind(&socketaddr_to_sockaddr("127.0.0.1:0")).unwrap();
+            let address = receiver.getsockname().unwrap();
+
+            let (tx, rx) = mpsc::channel();
+            let thread = thread::spawn(move || {
+                tx.send(unsafe { pthread_self() }).unwrap();
+                let mut buf = [0u8; 10];
+                receiver.recvfrom_into(&mut buf, 0).map(|(_, n)| n)
+            });
+            let tid = rx.recv().unwrap();
+            thread::sleep(Duration::from_millis(100));
+            unsafe { pthread_kill(tid, SIGUSR1) };
+            thread::sleep(Duration::from_millis(100));
+
+            let sender = Socket::new(AF_INET, SOCK_DGRAM, 0).unwrap();
+            sender.sendto(b"abcd", 0, &address).unwrap();
+            let res = thread.join().unwrap();
+            if retry {
+                assert_eq!(res.unwrap(), 4);
+            } else {
+                assert_eq!(res.unwrap_err().raw_os_error(), Some(EINTR));
+            }
+        }
+    }
+
+    #[test]
+    fn poll_with_works() {
+        use libc::{pipe, write};
+
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { pipe(fds.as_mut_ptr()) }, 0);
+        let (rd, wr) = (Socket { fd: fds[0], retry_eintr: true }, Socket { fd: fds[1], retry_eintr: true });
+
+        let receiver = Socket::new(AF_INET, SOCK_DGRAM, 0).unwrap();
+        receiver.bind(&socketaddr_to_sockaddr("127.0.0.1:0")).unwrap();
+        let sender = Socket::new(AF_INET, SOCK_DGRAM, 0).unwrap();
+        sender.sendto(b"abcd", 0, &receiver.getsockname().unwrap()).unwrap();
+        assert_eq!(receiver.poll_with(rd.fileno()).unwrap(), (true, false));
+
+        assert_eq!(unsafe { write(wr.fileno(), b"x".as_ptr() as *const c_void, 1) }, 1);
+        let mut buf = [0u8; 10];
+        receiver.recv_into(&mut buf, 0).unwrap();
+        assert_eq!(receiver.poll_with(rd.fileno()).unwrap(), (false, true));
+    }
+
+    #[test]
+    fn unix_address_length() {
+        use libc::{AF_UNIX, sa_family_t};
+
+        // An abstract address longer than the 14 bytes of a generic sockaddr
+        let name = b"\0nlrs-sockaddr-length-test";
+        let mut sa: sockaddr_un = unsafe { mem::zeroed() };
+        sa.sun_family = AF_UNIX as sa_family_t;
+        for (dst, &src) in sa.sun_path.iter_mut().zip(name.iter()) {
+            *dst = src as _;
+        }
+        assert!(sa.addr_len() as usize > mem::size_of::<sockaddr>());
+
+        let receiver = Socket::new(AF_UNIX, SOCK_DGRAM, 0).unwrap();
+        receiver.bind(&sa).unwrap();
+        let sender = Socket::new(AF_UNIX, SOCK_DGRAM, 0).unwrap();
+        assert_eq!(sender.sendto(b"abcd", 0, &sa).unwrap(), 4);
+        assert_eq!(receiver.recv(10, 0).unwrap().len(), 4);
+        // Longer than a generic sockaddr, the name is reported in full
+        assert_eq!(receiver.getsockname().unwrap().addr_len(), sa.addr_len());
+    }
+
+    #[test]
+    fn inet6_address_length() {
+        use libc::{AF_INET6, sa_family_t};
+
+        let mut sa: sockaddr_in6 = unsafe { mem::zeroed() };
+        sa.sin6_family = AF_INET6 as sa_family_t;
+        sa.sin6_addr.s6_addr[15] = 1;
+        let receiver = match Socket::new(AF_INET6, SOCK_DGRAM, 0) {
+            Ok(s) => s,
+            // No IPv6 in this environment
+            Err(_) => return,
+        };
+        if receiver.bind(&sa).is_err() {
+            return;
+        }
+        let address = receiver.getsockname().unwrap();
+        assert_eq!(address.family(), AF_INET6 as sa_family_t);
+        assert_eq!(address.addr_len(), sa.addr_len());
+
+        // The 28 byte peer address does not fit into a generic sockaddr
+        let sender = Socket::new(AF_INET6, SOCK_DGRAM, 0).unwrap();
+        sender.bind(&sa).unwrap();
+        sender.sendto(b"abcd", 0, &address).unwrap();
+        let (peer, received) = receiver.recvfrom(10, 0).unwrap();
+        assert_eq!(received.len(), 4);
+        assert_eq!(peer.addr_len(), sa.addr_len());
+        assert_eq!(receiver.sendto(b"ok", 0, &peer).unwrap(), 2);
+    }
+
+    #[test]
+    fn close_works() {
+        let socket = Socket::new(AF_INET, SOCK_DGRAM, 0).unwrap();
+        socket.close().unwrap();
+    }
+
     #[test]
     fn sendmsg_works() {
         let receiver = Socket::new(AF_INET, SOCK_DGRAM, 0).unwrap();
@@ -0,0 +1,211 @@
+use super::{CancelToken, Msg, MsgBatch, MsgIter, NetlinkAddr, SocketImpl, SocketStats};
+use super::{consume_acks, send_all, recv_datagram, sockaddr_to_netlinkaddr};
+use super::{SOL_NETLINK, NETLINK_ADD_MEMBERSHIP};
+use super::stats::count_sent;
+
+use std::cell::Cell;
+use std::io;
+use std::os::unix::io::RawFd;
+use std::sync::Arc;
+
+use libc::MSG_DONTWAIT;
+
+/// Sending half of a `Socket`, see `Socket::split`
+pub struct SendHalf {
+    inner: Arc<SocketImpl>,
+    stats: Cell<SocketStats>,
+    max_datagram: usize,
+    auto_done: bool,
+    ack_mode: bool,
+}
+
+/// Receiving half of a `Socket`, see `Socket::split`
+pub struct RecvHalf {
+    inner: Arc<SocketImpl>,
+    buf: Vec<u8>,
+    stats: Cell<SocketStats>,
+    cancel: Option<CancelToken>,
+    ack_mode: bool,
+}
+
+pub(super) fn halves(inner: SocketImpl, buf: Vec<u8>, stats: SocketStats, cancel: Option<CancelToken>,
+                     max_datagram: usize, auto_done: bool, ack_mode: bool) -> (SendHalf, RecvHalf) {
+    let inner = Arc::new(inner);
+    let send = SendHalf {
+        inner: inner.clone(),
+        stats: Cell::new(stats),
+        max_datagram,
+        auto_done,
+        ack_mode,
+    };
+    let recv = RecvHalf {
+        inner,
+        buf,
+        stats: Cell::new(stats),
+        cancel,
+        ack_mode,
+    };
+    (send, recv)
+}
+
+impl SendHalf {
+    /// Counters of the traffic sent through this half. The received
+    /// counters stay at their values at the split.
+    pub fn stats(&self) -> SocketStats {
+        self.stats.get()
+    }
+
+    /// See `Socket::send`
+    pub fn send(&self, mut message: Msg, addr: &NetlinkAddr) -> io::Result<usize> {
+        if self.ack_mode && message.header.is_request() {
+            message.header.ack();
+        }
+        let b = message.bytes()?;
+        self.send_datagram(&b, 1, addr)
+    }
+
+    /// See `Socket::send_multi`
+    pub fn send_multi(&self, mut messages: Vec<Msg>, addr: &NetlinkAddr) -> io::Result<usize> {
+        if self.ack_mode {
+            for m in messages.iter_mut().filter(|m| m.header.is_request()) {
+                m.header.ack();
+            }
+        }
+        send_all(messages, self.auto_done, self.max_datagram, &|bytes, msgs| self.send_datagram(bytes, msgs, addr))
+    }
+
+    /// See `Socket::send_batch`
+    pub fn send_batch(&self, batch: &MsgBatch, addr: &NetlinkAddr) -> io::Result<usize> {
+        self.send_datagram(batch.bytes(), batch.count(), addr)
+    }
+
+    fn send_datagram(&self, bytes: &[u8], msgs: usize, addr: &NetlinkAddr) -> io::Result<usize> {
+        let sent = self.inner.sendto(bytes, 0, addr)?;
+        count_sent(&self.stats, msgs, sent);
+        Ok(sent)
+    }
+}
+
+impl RecvHalf {
+    /// Counters of the traffic received through this half. The sent
+    /// counters stay at their values at the split.
+    pub fn stats(&self) -> SocketStats {
+        self.stats.get()
+    }
+
+    /// See `Socket::set_cancel_token`
+    pub fn set_cancel_token(&mut self, token: CancelToken) -> &mut RecvHalf {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// See `Socket::add_membership`
+    pub fn add_membership(&self, group: u32) -> io::Result<()> {
+        self.inner.setsockopt(SOL_NETLINK, NETLINK_ADD_MEMBERSHIP, group)
+    }
+
+    /// See `Socket::recv`
+    pub fn recv(&mut self) -> io::Result<(NetlinkAddr, Vec<Msg<'_>>)> {
+        let ack_mode = self.ack_mode;
+        let (addr, msgs) = self.recv_flags(0)?;
+        Ok((addr, consume_acks(ack_mode, msgs)?))
+    }
+
+    /// See `Socket::recv_bytes`
+    pub fn recv_bytes(&mut self) -> io::Result<(NetlinkAddr, &[u8])> {
+        let (saddr, received) = recv_datagram(&self.inner, &self.stats, self.cancel.as_ref(), &mut self.buf, 0)?;
+        let addr = sockaddr_to_netlinkaddr(saddr.as_sockaddr())?;
+        Ok((addr, &self.buf[..received]))
+    }
+
+    /// See `Socket::recv_iter`
+    pub fn recv_iter(&mut self) -> io::Result<(NetlinkAddr, MsgIter<'_>)> {
+        self.recv_flags(0)
+    }
+
+    /// See `Socket::try_recv`
+    pub fn try_recv(&mut self) -> io::Result<Option<(NetlinkAddr, Vec<Msg<'_>>)>> {
+        let ack_mode = self.ack_mode;
+        match self.recv_flags(MSG_DONTWAIT) {
+            Ok((addr, msgs)) => Ok(Some((addr, consume_acks(ack_mode, msgs)?))),
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// See `Socket::recv_or_wake`
+    pub fn recv_or_wake(&mut self, wake_fd: RawFd) -> io::Result<Option<(NetlinkAddr, Vec<Msg<'_>>)>> {
+        let (_, woken) = self.inner.poll_with(wake_fd)?;
+        if woken {
+            return Ok(None);
+        }
+        self.recv().map(Some)
+    }
+
+    fn recv_flags(&mut self, flags: i32) -> io::Result<(NetlinkAddr, MsgIter<'_>)> {
+        let (saddr, received) = recv_datagram(&self.inner, &self.stats, self.cancel.as_ref(), &mut self.buf, flags)?;
+        let addr = sockaddr_to_netlinkaddr(saddr.as_sockaddr())?;
+        Ok((addr, MsgIter::new(&self.stats, &self.buf[..received])))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket::{Socket, NlMsgHeader, NlAttr, Payload};
+    use Protocol;
+
+    use std::borrow::Cow;
+    use std::thread;
+
+    #[test]
+    fn test_split() {
+        let socket = Socket::new(Protocol::Generic).unwrap();
+        let (send, mut recv) = socket.split();
+
+        // Replies are consumed on another thread while this one sends
+        let receiver = thread::spawn(move || {
+            let (_, msgs) = recv.recv().unwrap();
+            let n = msgs.len();
+            (n, recv)
+        });
+
+        // CTRL_CMD_GETFAMILY for nlctrl
+        let mut payload = vec![3, 2, 0, 0];
+        payload.extend(NlAttr::new(2, b"nlctrl\0").bytes());
+        let mut hdr = NlMsgHeader::user_defined(0x10);
+        hdr.data_length(payload.len() as u32);
+        let msg = Msg::new(hdr, Payload::Data(Cow::Borrowed(&payload)));
+        assert!(send.send(msg, &NetlinkAddr::new(0, 0)).unwrap() > 0);
+        assert_eq!(send.stats().msgs_sent(), 1);
+
+        let (n, recv) = receiver.join().unwrap();
+        assert!(n >= 1);
+        assert_eq!(recv.stats().msgs_received(), n as u64);
+        assert_eq!(recv.stats().msgs_sent(), 0);
+    }
+
+    #[test]
+    fn test_recv_or_wake() {
+        use libc::{c_void, close, pipe, write};
+
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { pipe(fds.as_mut_ptr()) }, 0);
+        let (_, mut recv) = Socket::new(Protocol::Usersock).unwrap().split();
+
+        // A loop blocked on the socket is stopped through the pipe
+        let receiver = thread::spawn(move || {
+            let mut received = 0;
+            while recv.recv_or_wake(fds[0]).unwrap().is_some() {
+                received += 1;
+            }
+            received
+        });
+        assert_eq!(unsafe { write(fds[1], b"x".as_ptr() as *const c_void, 1) }, 1);
+        assert_eq!(receiver.join().unwrap(), 0);
+        unsafe {
+            close(fds[0]);
+            close(fds[1]);
+        }
+    }
+}
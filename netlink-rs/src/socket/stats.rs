@@ -0,0 +1,127 @@
+use super::{Msg, Payload};
+
+use std::cell::Cell;
+use std::io;
+
+/// Counters of the traffic of a `Socket`, see `Socket::stats`
+#[derive(Clone, Copy, Default, Eq, PartialEq, Debug)]
+pub struct SocketStats {
+    msgs_sent: u64,
+    bytes_sent: u64,
+    msgs_received: u64,
+    bytes_received: u64,
+    parse_errors: u64,
+    acks: u64,
+    errors: u64,
+}
+
+impl SocketStats {
+    pub fn msgs_sent(&self) -> u64 {
+        self.msgs_sent
+    }
+
+    pub fn bytes_sent(&self) -> u64 {
+        self.bytes_sent
+    }
+
+    /// Messages decoded from received datagrams, including acks and errors
+    pub fn msgs_received(&self) -> u64 {
+        self.msgs_received
+    }
+
+    pub fn bytes_received(&self) -> u64 {
+        self.bytes_received
+    }
+
+    /// Received messages that could not be decoded
+    pub fn parse_errors(&self) -> u64 {
+        self.parse_errors
+    }
+
+    /// NLMSG_ERROR replies with errno 0
+    pub fn acks(&self) -> u64 {
+        self.acks
+    }
+
+    /// NLMSG_ERROR replies reporting an error
+    pub fn errors(&self) -> u64 {
+        self.errors
+    }
+}
+
+pub(super) fn count_sent(stats: &Cell<SocketStats>, msgs: usize, bytes: usize) {
+    let mut s = stats.get();
+    s.msgs_sent += msgs as u64;
+    s.bytes_sent += bytes as u64;
+    stats.set(s);
+}
+
+pub(super) fn count_received(stats: &Cell<SocketStats>, bytes: usize) {
+    let mut s = stats.get();
+    s.bytes_received += bytes as u64;
+    stats.set(s);
+}
+
+/// `Msg::from_bytes`, counting the outcome in `stats`.
+pub(super) fn parse_msg<'a>(stats: &Cell<SocketStats>, bytes: &'a [u8]) -> io::Result<(Msg<'a>, usize)> {
+    let mut s = stats.get();
+    let res = Msg::from_bytes(bytes);
+    match res {
+        Ok((ref msg, _)) => {
+            s.msgs_received += 1;
+            match *msg.payload() {
+                Payload::Ack(_) => s.acks += 1,
+                Payload::Err(..) => s.errors += 1,
+                _ => {},
+            }
+        },
+        Err(_) => s.parse_errors += 1,
+    }
+    stats.set(s);
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket::{Socket, NlMsgHeader, NlAttr};
+    use Protocol;
+
+    use std::borrow::Cow;
+
+    #[test]
+    fn test_parse_counts() {
+        let stats = Cell::new(SocketStats::default());
+        let mut hdr = NlMsgHeader::error();
+        hdr.data_length(20);
+        let mut bytes = hdr.bytes().to_vec();
+        bytes.extend_from_slice(&[0; 4]);
+        bytes.extend_from_slice(&NlMsgHeader::request().bytes());
+        parse_msg(&stats, &bytes).unwrap();
+        assert!(parse_msg(&stats, &bytes[..8]).is_err());
+
+        let s = stats.get();
+        assert_eq!(s.msgs_received(), 1);
+        assert_eq!(s.acks(), 1);
+        assert_eq!(s.errors(), 0);
+        assert_eq!(s.parse_errors(), 1);
+    }
+
+    #[test]
+    fn test_socket_stats() {
+        let mut socket = Socket::new(Protocol::Generic).unwrap();
+        // CTRL_CMD_GETFAMILY for nlctrl
+        let mut payload = vec![3, 2, 0, 0];
+        payload.extend(NlAttr::new(2, b"nlctrl\0").bytes());
+        let mut hdr = NlMsgHeader::user_defined(0x10);
+        hdr.data_length(payload.len() as u32);
+        socket.talk(Msg::new(hdr, Payload::Data(Cow::Borrowed(&payload)))).unwrap();
+
+        let s = socket.stats();
+        assert_eq!(s.msgs_sent(), 1);
+        assert_eq!(s.bytes_sent(), 16 + payload.len() as u64);
+        assert_eq!(s.msgs_received(), 2);
+        assert_eq!(s.acks(), 1);
+        assert!(s.bytes_received() > s.bytes_sent());
+    }
+}
@@ -0,0 +1,157 @@
+use super::{Socket, Msg, NetlinkAddr, ReplayTransport};
+
+use std::io::{self, ErrorKind};
+
+/// Request/reply exchange with the kernel, as used by the protocol helpers.
+///
+/// `Socket` talks to the kernel; `ReplayTransport` and mocks answer from
+/// memory so that code built on the helpers can be tested without a kernel
+/// or privileges.
+pub trait NetlinkTransport {
+    /// Sends a request and returns the payloads of its replies; see
+    /// `Socket::talk`.
+    fn talk(&mut self, message: Msg) -> io::Result<Vec<Vec<u8>>>;
+
+    /// Sends a batch of requests; see `Socket::talk_multi`.
+    fn talk_multi(&mut self, messages: Vec<Msg>) -> io::Result<Vec<Vec<u8>>>;
+
+    /// Returns the messages of the next datagram, as needed by monitors;
+    /// see `Socket::recv`. Unsupported unless implemented.
+    fn recv_msgs(&mut self) -> io::Result<Vec<Msg<'_>>> {
+        Err(io::Error::new(ErrorKind::Unsupported, "transport does not receive events"))
+    }
+
+    /// Returns the next datagram undecoded, with its sender, as needed by
+    /// monitors of protocols that bend the message conventions; see
+    /// `Socket::recv_bytes`. Unsupported unless implemented.
+    fn recv_bytes(&mut self) -> io::Result<(NetlinkAddr, &[u8])> {
+        Err(io::Error::new(ErrorKind::Unsupported, "transport does not receive events"))
+    }
+
+    /// Sends a message to `addr` without waiting for a reply; see
+    /// `Socket::send`. Unsupported unless implemented.
+    fn send(&mut self, _message: Msg, _addr: &NetlinkAddr) -> io::Result<usize> {
+        Err(io::Error::new(ErrorKind::Unsupported, "transport does not send messages"))
+    }
+}
+
+impl NetlinkTransport for Socket {
+    fn talk(&mut self, message: Msg) -> io::Result<Vec<Vec<u8>>> {
+        Socket::talk(self, message)
+    }
+
+    fn talk_multi(&mut self, messages: Vec<Msg>) -> io::Result<Vec<Vec<u8>>> {
+        Socket::talk_multi(self, messages)
+    }
+
+    fn recv_msgs(&mut self) -> io::Result<Vec<Msg<'_>>> {
+        self.recv().map(|(_, msgs)| msgs)
+    }
+
+    fn recv_bytes(&mut self) -> io::Result<(NetlinkAddr, &[u8])> {
+        Socket::recv_bytes(self)
+    }
+
+    fn send(&mut self, message: Msg, addr: &NetlinkAddr) -> io::Result<usize> {
+        Socket::send(self, message, addr)
+    }
+}
+
+impl<T: NetlinkTransport + ?Sized> NetlinkTransport for Box<T> {
+    fn talk(&mut self, message: Msg) -> io::Result<Vec<Vec<u8>>> {
+        (**self).talk(message)
+    }
+
+    fn talk_multi(&mut self, messages: Vec<Msg>) -> io::Result<Vec<Vec<u8>>> {
+        (**self).talk_multi(messages)
+    }
+
+    fn recv_msgs(&mut self) -> io::Result<Vec<Msg<'_>>> {
+        (**self).recv_msgs()
+    }
+
+    fn recv_bytes(&mut self) -> io::Result<(NetlinkAddr, &[u8])> {
+        (**self).recv_bytes()
+    }
+
+    fn send(&mut self, message: Msg, addr: &NetlinkAddr) -> io::Result<usize> {
+        (**self).send(message, addr)
+    }
+}
+
+impl NetlinkTransport for ReplayTransport {
+    fn talk(&mut self, message: Msg) -> io::Result<Vec<Vec<u8>>> {
+        ReplayTransport::talk(self, message)
+    }
+
+    fn talk_multi(&mut self, messages: Vec<Msg>) -> io::Result<Vec<Vec<u8>>> {
+        ReplayTransport::talk_multi(self, messages)
+    }
+
+    fn recv_msgs(&mut self) -> io::Result<Vec<Msg<'_>>> {
+        self.recv()
+    }
+
+    fn recv_bytes(&mut self) -> io::Result<(NetlinkAddr, &[u8])> {
+        ReplayTransport::recv_bytes(self)
+    }
+
+    fn send(&mut self, message: Msg, _addr: &NetlinkAddr) -> io::Result<usize> {
+        ReplayTransport::send(self, message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket::{NlMsgHeader, Payload};
+
+    /// Answers every request with the same payloads
+    #[cfg(feature = "genl")]
+    struct Canned(Vec<Vec<u8>>);
+
+    #[cfg(feature = "genl")]
+    impl NetlinkTransport for Canned {
+        fn talk(&mut self, _: Msg) -> io::Result<Vec<Vec<u8>>> {
+            Ok(self.0.clone())
+        }
+
+        fn talk_multi(&mut self, _: Vec<Msg>) -> io::Result<Vec<Vec<u8>>> {
+            Ok(self.0.clone())
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "genl")]
+    fn test_mock_transport() {
+        use socket::NlAttr;
+        use genl::{GenlFamily, GenlMsgHeader};
+
+        let mut reply = GenlMsgHeader::new(1, 2).bytes().to_vec();
+        reply.extend(NlAttr::new(1, &0x20u16.to_ne_bytes()).bytes());
+        reply.extend(NlAttr::new(2, b"mock\0").bytes());
+        let mut mock = Canned(vec![reply]);
+
+        let family = GenlFamily::resolve(&mut mock, "mock").unwrap();
+        assert_eq!(family.id(), 0x20);
+        assert_eq!(family.name(), "mock");
+        assert_eq!(mock.recv_msgs().unwrap_err().kind(), ErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn test_replay_batch() {
+        let mut hdr = NlMsgHeader::error();
+        hdr.data_length(20);
+        let mut acked = NlMsgHeader::request();
+        acked.seq(5);
+        let mut datagram = hdr.bytes().to_vec();
+        datagram.extend_from_slice(&0i32.to_ne_bytes());
+        datagram.extend_from_slice(&acked.bytes());
+
+        let mut replay = ReplayTransport::new(vec![datagram]);
+        acked.ack();
+        let transport: &mut dyn NetlinkTransport = &mut replay;
+        assert!(transport.talk_multi(vec![Msg::new(acked, Payload::None)]).unwrap().is_empty());
+        assert_eq!(replay.sent().len(), 1);
+    }
+}
@@ -0,0 +1,247 @@
+use super::NLA_F_NESTED;
+
+use std::io::{self, ErrorKind};
+
+const NLA_ALIGNTO: usize = 4;
+const NLA_HDRLEN: usize = 4;
+
+#[inline]
+fn nla_align(len: usize) -> usize {
+    (len + (NLA_ALIGNTO - 1)) & !(NLA_ALIGNTO - 1)
+}
+
+enum Buffer<'a> {
+    Growable(Vec<u8>),
+    Fixed(&'a mut [u8]),
+}
+
+/// Encodes a stream of attributes in place. Lengths of nested attributes are
+/// filled in when they are closed, and every attribute is padded to the
+/// attribute alignment. The calls chain: `w.begin_nested(2)?.put_u32(1, 3)?
+/// .end_nested()?`.
+///
+/// Using it is optional. Most modules still build short messages by
+/// concatenating `NlAttr::new(..).bytes()`, which produces the same bytes.
+/// AttrWriter is worth it for deeply nested requests, as in ipvs, l2tp and
+/// nbd, or when the message has to fit a caller supplied buffer.
+pub struct AttrWriter<'a> {
+    buf: Buffer<'a>,
+    len: usize,
+    nests: Vec<usize>,
+}
+
+impl AttrWriter<'static> {
+    /// Writes into a buffer that grows as needed.
+    pub fn new() -> AttrWriter<'static> {
+        AttrWriter {
+            buf: Buffer::Growable(vec![]),
+            len: 0,
+            nests: vec![],
+        }
+    }
+}
+
+impl Default for AttrWriter<'static> {
+    fn default() -> AttrWriter<'static> {
+        AttrWriter::new()
+    }
+}
+
+impl<'a> AttrWriter<'a> {
+    /// Writes into `buf`, failing with WriteZero once it is full.
+    pub fn with_buffer(buf: &'a mut [u8]) -> AttrWriter<'a> {
+        AttrWriter {
+            buf: Buffer::Fixed(buf),
+            len: 0,
+            nests: vec![],
+        }
+    }
+
+    /// Checks room for `len` more bytes, so that a failed attribute is not
+    /// left half written.
+    fn reserve(&self, len: usize) -> io::Result<()> {
+        match self.buf {
+            Buffer::Fixed(ref b) if self.len + len > b.len() => {
+                Err(io::Error::new(ErrorKind::WriteZero, "attribute buffer full"))
+            },
+            _ => Ok(()),
+        }
+    }
+
+    fn write(&mut self, bytes: &[u8], pad: usize) -> io::Result<()> {
+        let end = self.len + bytes.len() + pad;
+        self.reserve(bytes.len() + pad)?;
+        match self.buf {
+            Buffer::Growable(ref mut v) => {
+                v.extend_from_slice(bytes);
+                v.resize(end, 0);
+            },
+            Buffer::Fixed(ref mut b) => {
+                b[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+                for x in &mut b[self.len + bytes.len()..end] {
+                    *x = 0;
+                }
+            },
+        }
+        self.len = end;
+        Ok(())
+    }
+
+    fn header(&mut self, len: usize, attr_type: u16) -> io::Result<()> {
+        if len > u16::MAX as usize {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "attribute too long"));
+        }
+        let mut hdr = [0; NLA_HDRLEN];
+        hdr[..2].copy_from_slice(&(len as u16).to_ne_bytes());
+        hdr[2..].copy_from_slice(&attr_type.to_ne_bytes());
+        self.write(&hdr, 0)
+    }
+
+    /// Appends an attribute with `payload`.
+    pub fn put(&mut self, attr_type: u16, payload: &[u8]) -> io::Result<&mut AttrWriter<'a>> {
+        let len = NLA_HDRLEN + payload.len();
+        self.reserve(nla_align(len))?;
+        self.header(len, attr_type)?;
+        self.write(payload, nla_align(len) - len)?;
+        Ok(self)
+    }
+
+    /// Appends an attribute without payload, as used for flags.
+    pub fn put_flag(&mut self, attr_type: u16) -> io::Result<&mut AttrWriter<'a>> {
+        self.put(attr_type, &[])
+    }
+
+    pub fn put_u8(&mut self, attr_type: u16, value: u8) -> io::Result<&mut AttrWriter<'a>> {
+        self.put(attr_type, &[value])
+    }
+
+    pub fn put_u16(&mut self, attr_type: u16, value: u16) -> io::Result<&mut AttrWriter<'a>> {
+        self.put(attr_type, &value.to_ne_bytes())
+    }
+
+    pub fn put_u32(&mut self, attr_type: u16, value: u32) -> io::Result<&mut AttrWriter<'a>> {
+        self.put(attr_type, &value.to_ne_bytes())
+    }
+
+    pub fn put_u64(&mut self, attr_type: u16, value: u64) -> io::Result<&mut AttrWriter<'a>> {
+        self.put(attr_type, &value.to_ne_bytes())
+    }
+
+    /// Appends a NUL terminated string.
+    pub fn put_str(&mut self, attr_type: u16, value: &str) -> io::Result<&mut AttrWriter<'a>> {
+        let len = NLA_HDRLEN + value.len() + 1;
+        self.reserve(nla_align(len))?;
+        self.header(len, attr_type)?;
+        self.write(value.as_bytes(), nla_align(len) - len + 1)?;
+        Ok(self)
+    }
+
+    /// Opens a nested attribute; the attributes up to the matching
+    /// `end_nested` make up its payload.
+    pub fn begin_nested(&mut self, attr_type: u16) -> io::Result<&mut AttrWriter<'a>> {
+        let start = self.len;
+        self.header(NLA_HDRLEN, attr_type | NLA_F_NESTED)?;
+        self.nests.push(start);
+        Ok(self)
+    }
+
+    /// Closes the innermost nested attribute, filling in its length.
+    pub fn end_nested(&mut self) -> io::Result<&mut AttrWriter<'a>> {
+        let start = self.nests.pop()
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "no nested attribute open"))?;
+        let len = self.len - start;
+        if len > u16::MAX as usize {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "attribute too long"));
+        }
+        let len = (len as u16).to_ne_bytes();
+        match self.buf {
+            Buffer::Growable(ref mut v) => v[start..start + 2].copy_from_slice(&len),
+            Buffer::Fixed(ref mut b) => b[start..start + 2].copy_from_slice(&len),
+        }
+        Ok(self)
+    }
+
+    /// Bytes written so far
+    pub fn bytes(&self) -> &[u8] {
+        match self.buf {
+            Buffer::Growable(ref v) => v,
+            Buffer::Fixed(ref b) => &b[..self.len],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of bytes written, failing if a nested attribute is
+    /// still open.
+    pub fn finish(self) -> io::Result<usize> {
+        if !self.nests.is_empty() {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "nested attribute not closed"));
+        }
+        Ok(self.len)
+    }
+
+    /// Returns the attributes, failing if a nested attribute is still open.
+    pub fn into_vec(self) -> io::Result<Vec<u8>> {
+        if !self.nests.is_empty() {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "nested attribute not closed"));
+        }
+        Ok(match self.buf {
+            Buffer::Growable(v) => v,
+            Buffer::Fixed(b) => b[..self.len].to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket::NlAttr;
+
+    #[test]
+    fn test_writer() {
+        let mut w = AttrWriter::new();
+        w.put_u32(1, 3).unwrap()
+            .begin_nested(2).unwrap()
+            .put_str(1, "eth0").unwrap()
+            .put_flag(2).unwrap()
+            .end_nested().unwrap()
+            .put_u8(3, 7).unwrap();
+        let bytes = w.into_vec().unwrap();
+
+        let mut inner = NlAttr::new(1, b"eth0\0").bytes();
+        inner.extend(NlAttr::new(2, &[]).bytes());
+        let mut expected = NlAttr::new(1, &3u32.to_ne_bytes()).bytes();
+        expected.extend(NlAttr::new(2 | NLA_F_NESTED, &inner).bytes());
+        expected.extend(NlAttr::new(3, &[7]).bytes());
+        assert_eq!(bytes, expected);
+
+        let attrs = NlAttr::parse(&bytes).unwrap();
+        assert!(attrs[1].is_nested());
+        assert_eq!(attrs[1].nested().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_writer_fixed() {
+        let mut buf = [0xff; 12];
+        let mut w = AttrWriter::with_buffer(&mut buf);
+        w.put_u16(1, 5).unwrap();
+        assert_eq!(w.bytes(), &NlAttr::new(1, &5u16.to_ne_bytes()).bytes()[..]);
+        assert_eq!(w.put_u64(2, 0).err().unwrap().kind(), ErrorKind::WriteZero);
+        assert_eq!(w.finish().unwrap(), 8);
+    }
+
+    #[test]
+    fn test_writer_unbalanced() {
+        let mut w = AttrWriter::new();
+        assert!(w.end_nested().is_err());
+        w.begin_nested(1).unwrap();
+        assert_eq!(w.into_vec().unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert!(AttrWriter::new().put(1, &[0; 70000]).is_err());
+    }
+}
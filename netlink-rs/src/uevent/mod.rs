@@ -0,0 +1,297 @@
+//! Kernel object events (uevents)
+//!
+//! The kernel broadcasts device events on `Protocol::KobjectUevent` as
+//! "ACTION@DEVPATH" followed by NUL separated KEY=VALUE properties, without
+//! netlink headers. udev forwards the events it processed, with extra
+//! properties, on a second group in its own binary envelope. A listener on
+//! both sees most events twice; `Uevent::source` tells them apart.
+
+use socket::{Socket, NetlinkTransport, NetlinkAddr};
+use Protocol;
+
+use std::collections::HashMap;
+use std::io::{self, ErrorKind};
+
+const UDEV_MONITOR_PREFIX: &[u8] = b"libudev\0";
+const UDEV_MONITOR_MAGIC: u32 = 0xfeed_cafe;
+const UDEV_MONITOR_HEADER_LEN: usize = 40;
+
+/// Origin of an event, and the multicast group it is sent to
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum UeventSource {
+    /// Sent by the kernel as the device changed
+    Kernel,
+    /// Forwarded by udev once its rules ran (libudev monitor)
+    Udev,
+}
+
+impl From<UeventSource> for u32 {
+    fn from(s: UeventSource) -> u32 {
+        match s {
+            UeventSource::Kernel => 1,
+            UeventSource::Udev => 2,
+        }
+    }
+}
+
+/// A device event with its raw properties
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Uevent {
+    source: UeventSource,
+    properties: HashMap<String, String>,
+}
+
+impl Uevent {
+    /// Decodes a datagram in either format.
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Uevent> {
+        if bytes.starts_with(UDEV_MONITOR_PREFIX) {
+            return Uevent::from_udev(bytes);
+        }
+        // The summary line is repeated by ACTION and DEVPATH
+        let mut fields = bytes.split(|&b| b == 0);
+        match fields.next() {
+            Some(summary) if summary.contains(&b'@') => {},
+            _ => return Err(io::Error::new(ErrorKind::InvalidData, "not a uevent")),
+        }
+        Ok(Uevent { source: UeventSource::Kernel, properties: properties(fields) })
+    }
+
+    // HEADER FORMAT
+    // char prefix[8];                     /* "libudev" */
+    // unsigned int magic;                 /* htonl(0xfeedcafe) */
+    // unsigned int header_size;
+    // unsigned int properties_off;
+    // unsigned int properties_len;
+    // unsigned int filter_subsystem_hash;
+    // unsigned int filter_devtype_hash;
+    // unsigned int filter_tag_bloom_hi;
+    // unsigned int filter_tag_bloom_lo;
+    fn from_udev(bytes: &[u8]) -> io::Result<Uevent> {
+        if bytes.len() < UDEV_MONITOR_HEADER_LEN {
+            return Err(io::Error::new(ErrorKind::InvalidData, "udev header too short"));
+        }
+        let u32_at = |i: usize| [bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]];
+        if u32::from_be_bytes(u32_at(8)) != UDEV_MONITOR_MAGIC {
+            return Err(io::Error::new(ErrorKind::InvalidData, "bad udev monitor magic"));
+        }
+        let off = u32::from_ne_bytes(u32_at(16)) as usize;
+        let len = u32::from_ne_bytes(u32_at(20)) as usize;
+        if off < UDEV_MONITOR_HEADER_LEN || off + len > bytes.len() {
+            return Err(io::Error::new(ErrorKind::InvalidData, "udev properties out of bounds"));
+        }
+        let fields = bytes[off..off + len].split(|&b| b == 0);
+        Ok(Uevent { source: UeventSource::Udev, properties: properties(fields) })
+    }
+
+    pub fn source(&self) -> UeventSource {
+        self.source
+    }
+
+    /// "add", "remove", "change", "bind", ...
+    pub fn action(&self) -> Option<&str> {
+        self.get("ACTION")
+    }
+
+    /// Path of the device below /sys
+    pub fn devpath(&self) -> Option<&str> {
+        self.get("DEVPATH")
+    }
+
+    pub fn seqnum(&self) -> Option<u64> {
+        self.get("SEQNUM").and_then(|s| s.parse().ok())
+    }
+
+    /// Value of property `key`
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.properties.get(key).map(|v| v.as_str())
+    }
+
+    pub fn properties(&self) -> &HashMap<String, String> {
+        &self.properties
+    }
+
+    /// The well known properties of the device
+    pub fn device(&self) -> Device {
+        let string = |key| self.get(key).map(|v: &str| v.to_string());
+        let number = |key| self.get(key).and_then(|v: &str| v.parse().ok());
+        Device {
+            devpath: string("DEVPATH"),
+            subsystem: string("SUBSYSTEM"),
+            devtype: string("DEVTYPE"),
+            devname: string("DEVNAME"),
+            driver: string("DRIVER"),
+            major: number("MAJOR"),
+            minor: number("MINOR"),
+        }
+    }
+}
+
+fn properties<'a, I: Iterator<Item = &'a [u8]>>(fields: I) -> HashMap<String, String> {
+    fields.filter_map(|f| {
+        let f = String::from_utf8_lossy(f);
+        let mut kv = f.splitn(2, '=');
+        match (kv.next(), kv.next()) {
+            (Some(k), Some(v)) if !k.is_empty() => Some((k.to_string(), v.to_string())),
+            _ => None,
+        }
+    }).collect()
+}
+
+/// Device described by a uevent, as udev names its properties
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Device {
+    devpath: Option<String>,
+    subsystem: Option<String>,
+    devtype: Option<String>,
+    devname: Option<String>,
+    driver: Option<String>,
+    major: Option<u32>,
+    minor: Option<u32>,
+}
+
+impl Device {
+    pub fn devpath(&self) -> Option<&str> {
+        self.devpath.as_deref()
+    }
+
+    pub fn subsystem(&self) -> Option<&str> {
+        self.subsystem.as_deref()
+    }
+
+    pub fn devtype(&self) -> Option<&str> {
+        self.devtype.as_deref()
+    }
+
+    /// Device node below /dev, e.g. "sda1"
+    pub fn devname(&self) -> Option<&str> {
+        self.devname.as_deref()
+    }
+
+    pub fn driver(&self) -> Option<&str> {
+        self.driver.as_deref()
+    }
+
+    /// Major and minor number of the device node
+    pub fn devnum(&self) -> Option<(u32, u32)> {
+        match (self.major, self.minor) {
+            (Some(major), Some(minor)) => Some((major, minor)),
+            _ => None,
+        }
+    }
+}
+
+/// A socket subscribed to uevents
+pub struct UeventMonitor<T: NetlinkTransport = Socket> {
+    socket: T,
+}
+
+impl UeventMonitor {
+    /// Subscribes to the events of `sources`.
+    pub fn new(sources: &[UeventSource]) -> io::Result<UeventMonitor> {
+        let socket = Socket::new(Protocol::KobjectUevent)?;
+        let groups = sources.iter().fold(0, |mask, &s| mask | u32::from(s));
+        socket.bind(NetlinkAddr::new(0, groups))?;
+        Ok(UeventMonitor::from_transport(socket))
+    }
+}
+
+impl<T: NetlinkTransport> UeventMonitor<T> {
+    /// Reads events from `transport`, which is already subscribed.
+    pub fn from_transport(transport: T) -> UeventMonitor<T> {
+        UeventMonitor { socket: transport }
+    }
+
+    /// Blocks until an event arrives. Kernel events sent by a process,
+    /// which anyone may forge, are skipped. Fails with an `Overrun` error
+    /// if events were dropped; uevents cannot be dumped again, but writing
+    /// `add` to the `uevent` files in sysfs replays them.
+    pub fn recv(&mut self) -> io::Result<Uevent> {
+        loop {
+            let (addr, bytes) = self.socket.recv_bytes()?;
+            let event = Uevent::from_bytes(bytes)?;
+            if event.source() == UeventSource::Udev || addr.pid() == 0 {
+                return Ok(event);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket::ReplayTransport;
+
+    use std::fs::OpenOptions;
+    use std::io::Write;
+
+    const KERNEL: &[u8] = b"add@/devices/virtual/block/loop0\0ACTION=add\0\
+        DEVPATH=/devices/virtual/block/loop0\0SUBSYSTEM=block\0MAJOR=7\0MINOR=0\0\
+        DEVNAME=loop0\0DEVTYPE=disk\0SEQNUM=4242\0";
+
+    #[test]
+    fn test_kernel_uevent() {
+        let event = Uevent::from_bytes(KERNEL).unwrap();
+        assert_eq!(event.source(), UeventSource::Kernel);
+        assert_eq!(event.action(), Some("add"));
+        assert_eq!(event.seqnum(), Some(4242));
+        let device = event.device();
+        assert_eq!(device.subsystem(), Some("block"));
+        assert_eq!(device.devname(), Some("loop0"));
+        assert_eq!(device.devnum(), Some((7, 0)));
+        assert_eq!(device.driver(), None);
+
+        assert!(Uevent::from_bytes(b"ACTION=add\0").is_err());
+    }
+
+    #[test]
+    fn test_monitor_replay() {
+        let mut monitor = UeventMonitor::from_transport(ReplayTransport::new(vec![KERNEL.to_vec()]));
+        assert_eq!(monitor.recv().unwrap().devpath(), Some("/devices/virtual/block/loop0"));
+    }
+
+    #[test]
+    fn test_udev_uevent() {
+        let props: &[u8] = b"ACTION=bind\0DEVPATH=/devices/pci0000:00/0000:00:1f.2\0\
+            SUBSYSTEM=pci\0DRIVER=ahci\0SEQNUM=7\0";
+        let mut bytes = UDEV_MONITOR_PREFIX.to_vec();
+        bytes.extend_from_slice(&UDEV_MONITOR_MAGIC.to_be_bytes());
+        for &v in &[40u32, 40, props.len() as u32, 0, 0, 0, 0] {
+            bytes.extend_from_slice(&v.to_ne_bytes());
+        }
+        bytes.extend_from_slice(props);
+
+        let event = Uevent::from_bytes(&bytes).unwrap();
+        assert_eq!(event.source(), UeventSource::Udev);
+        assert_eq!(event.device().driver(), Some("ahci"));
+        assert_eq!(event.device().devnum(), None);
+
+        bytes[8] = 0;
+        assert!(Uevent::from_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_monitor() {
+        let mut monitor = match UeventMonitor::new(&[UeventSource::Kernel]) {
+            Ok(m) => m,
+            Err(ref e) if e.raw_os_error() == Some(::libc::EPERM) => return,
+            Err(e) => panic!("{}", e),
+        };
+        let trigger = OpenOptions::new().write(true).open("/sys/class/net/lo/uevent")
+            .and_then(|mut f| f.write_all(b"change"));
+        if trigger.is_err() {
+            return;
+        }
+        for _ in 0..100 {
+            let event = monitor.recv().unwrap();
+            if event.devpath() == Some("/devices/virtual/net/lo") {
+                assert_eq!(event.action(), Some("change"));
+                assert_eq!(event.device().subsystem(), Some("net"));
+                return;
+            }
+        }
+        panic!("no uevent for lo");
+    }
+}